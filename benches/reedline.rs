@@ -0,0 +1,325 @@
+//! Benchmarks of the performance critical paths of the line editor
+//!
+//! Run with `cargo bench`, a single group with e.g. `cargo bench -- history`.
+//! Criterion compares every run to the previous one, so checking out the base of a
+//! change, running the benchmarks, and rerunning them on the change reports any
+//! regression.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use reedline::{
+    ColumnarMenu, DefaultCompleter, DefaultPrompt, EditMode, Editor, Emacs, ExampleHighlighter,
+    FileBackedHistory, Highlighter, History, HistoryItem, LineBuffer, Menu, Painter,
+    ReedlineRawEvent, SearchQuery, UndoBehavior, Vi,
+};
+
+const COMMANDS: &[&str] = &[
+    "cargo", "cat", "cd", "chmod", "cp", "echo", "git", "grep", "ls", "mkdir", "mv", "rm",
+];
+
+// A single line of `words` space separated words like "ls -l target/debug/cargo"
+fn long_line(words: usize) -> String {
+    COMMANDS
+        .iter()
+        .cycle()
+        .take(words)
+        .enumerate()
+        .map(|(i, command)| format!("{command} --flag-{i} path/to/file_{i}.rs"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// A script of `lines` lines like the ones pasted into the editor
+fn script(lines: usize) -> String {
+    (0..lines)
+        .map(|i| format!("    echo \"line {i}\" | grep line > /tmp/out_{i}.txt"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn key(code: KeyCode, modifiers: KeyModifiers) -> ReedlineRawEvent {
+    ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(code, modifiers)))
+        .expect("key presses are converted")
+}
+
+fn parse_event(c: &mut Criterion) {
+    // The raw events are consumed by the edit mode, so they are converted on every run
+    let keys: Vec<_> = "echo hello world"
+        .chars()
+        .map(|ch| (KeyCode::Char(ch), KeyModifiers::NONE))
+        .chain([
+            (KeyCode::Left, KeyModifiers::CONTROL),
+            (KeyCode::Char('w'), KeyModifiers::CONTROL),
+            (KeyCode::Backspace, KeyModifiers::NONE),
+            (KeyCode::Enter, KeyModifiers::NONE),
+        ])
+        .collect();
+
+    let mut group = c.benchmark_group("parse_event");
+    let mut emacs = Emacs::default();
+    group.bench_function("emacs", |b| {
+        b.iter(|| {
+            for &(code, modifiers) in &keys {
+                black_box(emacs.parse_event(key(code, modifiers)));
+            }
+        })
+    });
+
+    // Stays in normal mode, so that the keys run through the vi command parser
+    let normal_mode = "2dwdbx3lp";
+    group.bench_function("vi_normal", |b| {
+        b.iter(|| {
+            let mut vi = Vi::default();
+            vi.parse_event(key(KeyCode::Esc, KeyModifiers::NONE));
+            for ch in normal_mode.chars() {
+                black_box(vi.parse_event(key(KeyCode::Char(ch), KeyModifiers::NONE)));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn word_motions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("word_motions");
+    for words in [100, 1_000, 10_000] {
+        let mut line_buffer = LineBuffer::new();
+        line_buffer.set_buffer(long_line(words));
+
+        group.bench_with_input(
+            BenchmarkId::new("word_right_across_line", words),
+            &words,
+            |b, _| {
+                b.iter(|| {
+                    line_buffer.move_to_start();
+                    while line_buffer.insertion_point() < line_buffer.len() {
+                        line_buffer.move_word_right();
+                    }
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("word_left_from_end", words),
+            &words,
+            |b, _| {
+                b.iter(|| {
+                    line_buffer.move_to_end();
+                    line_buffer.move_word_left();
+                    black_box(line_buffer.insertion_point())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+// The cost of a keystroke should not grow with the size of the buffer
+fn keystrokes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("keystrokes");
+    for words in [10, 1_000, 10_000] {
+        let mut editor = Editor::default();
+        editor.edit_buffer(
+            |line_buffer| line_buffer.set_buffer(long_line(words)),
+            UndoBehavior::CreateUndoPoint,
+        );
+
+        // Removes the character again in the same edit, so that the buffer keeps its size
+        group.bench_with_input(BenchmarkId::new("insert_char", words), &words, |b, _| {
+            b.iter(|| {
+                editor.edit_buffer(
+                    |line_buffer| {
+                        line_buffer.insert_char('x');
+                        line_buffer.delete_left_grapheme();
+                    },
+                    UndoBehavior::InsertCharacter('x'),
+                )
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("move_left", words), &words, |b, _| {
+            b.iter(|| editor.edit_buffer(LineBuffer::move_left, UndoBehavior::MoveCursor))
+        });
+    }
+
+    // Typing in the middle of a pasted script, where the text behind the cursor is large
+    for lines in [10, 1_000, 100_000] {
+        let mut editor = Editor::default();
+        editor.edit_buffer(
+            |line_buffer| {
+                line_buffer.set_buffer(script(lines));
+                line_buffer.set_insertion_point(line_buffer.len() / 2);
+                line_buffer.move_to_line_start();
+            },
+            UndoBehavior::CreateUndoPoint,
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("insert_char_in_script", lines),
+            &lines,
+            |b, _| {
+                b.iter(|| {
+                    editor.edit_buffer(
+                        |line_buffer| {
+                            line_buffer.insert_char('x');
+                            line_buffer.delete_left_grapheme();
+                        },
+                        UndoBehavior::InsertCharacter('x'),
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("line_of_cursor_in_script", lines),
+            &lines,
+            |b, _| {
+                b.iter(|| {
+                    let line_buffer = editor.line_buffer();
+                    black_box((line_buffer.line(), line_buffer.num_lines()))
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn highlight_and_paint(c: &mut Criterion) {
+    let prompt = DefaultPrompt::default();
+    let highlighter =
+        ExampleHighlighter::new(COMMANDS.iter().map(|command| command.to_string()).collect());
+
+    let mut group = c.benchmark_group("highlight_and_paint");
+    for words in [10, 100, 1_000] {
+        let buffer = long_line(words);
+        let cursor = buffer.len() / 2;
+
+        group.bench_with_input(BenchmarkId::new("highlight", words), &words, |b, _| {
+            b.iter(|| black_box(highlighter.highlight(&buffer, cursor)))
+        });
+        group.bench_with_input(BenchmarkId::new("paint", words), &words, |b, _| {
+            b.iter(|| {
+                let styled = highlighter.highlight(&buffer, cursor);
+                let (before_cursor, after_cursor) =
+                    styled.render_around_insertion_point(cursor, &prompt, true);
+                Painter::render_to_bytes(&prompt, &before_cursor, &after_cursor, (120, 40))
+                    .expect("painting into memory does not fail")
+            })
+        });
+    }
+    group.finish();
+}
+
+fn history_search(c: &mut Criterion) {
+    const ENTRIES: usize = 100_000;
+    let mut history = FileBackedHistory::new(ENTRIES).expect("in memory history");
+    for i in 0..ENTRIES {
+        let command = COMMANDS[i % COMMANDS.len()];
+        history
+            .save(HistoryItem::from_command_line(format!(
+                "{command} --flag-{i} path/to/file_{i}.rs"
+            )))
+            .expect("in memory history");
+    }
+
+    let mut group = c.benchmark_group("history_search");
+    group.bench_function("last_with_prefix", |b| {
+        b.iter(|| {
+            history
+                .search(SearchQuery::last_with_prefix("grep".to_string(), None))
+                .expect("in memory history")
+        })
+    });
+    group.bench_function("all_that_contain_rev", |b| {
+        b.iter(|| {
+            history
+                .search(SearchQuery::all_that_contain_rev("file_4242".to_string()))
+                .expect("in memory history")
+        })
+    });
+    group.bench_function("all_that_contain_rev_no_match", |b| {
+        b.iter(|| {
+            history
+                .search(SearchQuery::all_that_contain_rev(
+                    "does not exist".to_string(),
+                ))
+                .expect("in memory history")
+        })
+    });
+    group.finish();
+}
+
+// Run with `cargo bench --features sqlite`
+#[cfg(feature = "sqlite")]
+fn sqlite_history_search(c: &mut Criterion) {
+    use reedline::SqliteBackedHistory;
+
+    const ENTRIES: usize = 100_000;
+    let mut group = c.benchmark_group("sqlite_history_search");
+    for substring_index in [false, true] {
+        let mut history = SqliteBackedHistory::in_memory().expect("in memory history");
+        if substring_index {
+            history
+                .enable_substring_index()
+                .expect("bundled sqlite supports the index");
+        }
+        for i in 0..ENTRIES {
+            let command = COMMANDS[i % COMMANDS.len()];
+            history
+                .save(HistoryItem::from_command_line(format!(
+                    "{command} --flag-{i} path/to/file_{i}.rs"
+                )))
+                .expect("in memory history");
+        }
+
+        group.bench_with_input(
+            BenchmarkId::new("all_that_contain_rev", substring_index),
+            &substring_index,
+            |b, _| {
+                b.iter(|| {
+                    history
+                        .search(SearchQuery::all_that_contain_rev("file_4242".to_string()))
+                        .expect("in memory history")
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn completion_menu(c: &mut Criterion) {
+    let words: Vec<String> = (0..10_000)
+        .map(|i| format!("{}_{i}", COMMANDS[i % COMMANDS.len()]))
+        .collect();
+    let mut completer = DefaultCompleter::new_with_wordlen(words, 2);
+
+    let mut group = c.benchmark_group("completion_menu");
+    for input in ["c", "cargo_1", "git_9999"] {
+        let mut editor = Editor::default();
+        editor.edit_buffer(
+            |line_buffer| line_buffer.set_buffer(input.to_string()),
+            UndoBehavior::CreateUndoPoint,
+        );
+        let mut menu = ColumnarMenu::default();
+
+        group.bench_with_input(BenchmarkId::new("filter", input), &input, |b, _| {
+            b.iter(|| {
+                menu.update_values(&mut editor, &mut completer);
+                black_box(menu.get_values().len())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    parse_event,
+    word_motions,
+    keystrokes,
+    highlight_and_paint,
+    history_search,
+    completion_menu
+);
+#[cfg(feature = "sqlite")]
+criterion_group!(sqlite_benches, sqlite_history_search);
+
+#[cfg(feature = "sqlite")]
+criterion_main!(benches, sqlite_benches);
+#[cfg(not(feature = "sqlite"))]
+criterion_main!(benches);
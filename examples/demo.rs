@@ -76,7 +76,9 @@ fn main() -> reedline::Result<()> {
     let cursor_config = CursorConfig {
         vi_insert: Some(SetCursorStyle::BlinkingBar),
         vi_normal: Some(SetCursorStyle::SteadyBlock),
+        vi_visual: Some(SetCursorStyle::BlinkingBlock),
         emacs: None,
+        menu: Some(SetCursorStyle::SteadyUnderScore),
     };
 
     let mut line_editor = Reedline::create()
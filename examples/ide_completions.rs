@@ -58,6 +58,8 @@ fn main() -> io::Result<()> {
     // ```
     // If a border is being used
     let correct_cursor_pos: bool = false;
+    let scrollbar: bool = true;
+    let flip_above: bool = true;
 
     let commands = vec![
         "test".into(),
@@ -96,7 +98,9 @@ fn main() -> io::Result<()> {
         .with_min_description_width(min_description_width)
         .with_max_description_width(max_description_width)
         .with_description_offset(description_offset)
-        .with_correct_cursor_pos(correct_cursor_pos);
+        .with_correct_cursor_pos(correct_cursor_pos)
+        .with_scrollbar(scrollbar)
+        .with_flip_above(flip_above);
 
     if border {
         ide_menu = ide_menu.with_default_border();
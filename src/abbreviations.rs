@@ -0,0 +1,180 @@
+use crate::{core_editor::Editor, UndoBehavior};
+use nu_ansi_term::{Color, Style};
+use std::collections::HashMap;
+
+/// Words that are replaced by longer commands as they are typed, like `ga` by `git add`
+///
+/// An abbreviation is expanded when a space is typed right after it or when the line is
+/// submitted, but only in command position: at the start of the line or after `|`, `;`, `&`
+/// or `(`. With hints on, the expansion is shown after the cursor while the abbreviation is
+/// typed, unless the [`crate::Hinter`] has a hint of its own.
+///
+/// ```rust
+/// use reedline::{Abbreviations, Reedline};
+///
+/// let abbreviations = Abbreviations::default()
+///     .with_abbreviation("ga", "git add")
+///     .with_abbreviation("gc", "git commit -m")
+///     .with_hints(true);
+/// let mut line_editor = Reedline::create().with_abbreviations(abbreviations);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Abbreviations {
+    expansions: HashMap<String, String>,
+    hints: bool,
+    hint_style: Style,
+}
+
+impl Default for Abbreviations {
+    fn default() -> Self {
+        Self {
+            expansions: HashMap::new(),
+            hints: false,
+            hint_style: Style::new().italic().fg(Color::DarkGray),
+        }
+    }
+}
+
+impl Abbreviations {
+    /// A builder which adds `abbreviation`, replacing an earlier expansion of it
+    #[must_use]
+    pub fn with_abbreviation(
+        mut self,
+        abbreviation: impl Into<String>,
+        expansion: impl Into<String>,
+    ) -> Self {
+        self.expansions
+            .insert(abbreviation.into(), expansion.into());
+        self
+    }
+
+    /// A builder which shows what the abbreviation before the cursor expands to, off by default
+    #[must_use]
+    pub fn with_hints(mut self, hints: bool) -> Self {
+        self.hints = hints;
+        self
+    }
+
+    /// A builder which sets the style of the hints, dark gray italics by default
+    #[must_use]
+    pub fn with_hint_style(mut self, hint_style: Style) -> Self {
+        self.hint_style = hint_style;
+        self
+    }
+
+    /// The expansion of `abbreviation`, if there is one
+    pub fn expansion(&self, abbreviation: &str) -> Option<&str> {
+        self.expansions.get(abbreviation).map(String::as_str)
+    }
+
+    /// Replace the abbreviation before the cursor of `editor`, `false` if there is none
+    pub(crate) fn expand(&self, editor: &mut Editor) -> bool {
+        let cursor = editor.insertion_point();
+        match self.abbreviation_before(editor.get_buffer(), cursor) {
+            Some((start, expansion)) => {
+                let expansion = expansion.to_string();
+                editor.edit_buffer(
+                    |line_buffer| {
+                        line_buffer.replace_range(start..cursor, &expansion);
+                        line_buffer.set_insertion_point(start + expansion.len());
+                    },
+                    UndoBehavior::CreateUndoPoint,
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The hint announcing the expansion of the abbreviation before the cursor
+    pub(crate) fn hint(&self, buffer: &str, cursor: usize, use_ansi_coloring: bool) -> String {
+        let expansion = self
+            .abbreviation_before(buffer, cursor)
+            .filter(|_| self.hints)
+            .map(|(_, expansion)| expansion);
+        match expansion {
+            Some(expansion) if use_ansi_coloring => {
+                self.hint_style.paint(format!(" → {expansion}")).to_string()
+            }
+            Some(expansion) => format!(" → {expansion}"),
+            None => String::new(),
+        }
+    }
+
+    // The start and the expansion of the abbreviation ending at `cursor`
+    fn abbreviation_before<'a>(&'a self, buffer: &str, cursor: usize) -> Option<(usize, &'a str)> {
+        if buffer[cursor..]
+            .chars()
+            .next()
+            .map_or(false, |c| !c.is_whitespace())
+        {
+            return None;
+        }
+        let start = buffer[..cursor]
+            .rfind(|c: char| c.is_whitespace() || is_separator(c))
+            .map_or(0, |index| {
+                index + buffer[index..].chars().next().map_or(1, char::len_utf8)
+            });
+        let in_command_position = buffer[..start]
+            .trim_end()
+            .chars()
+            .last()
+            .map_or(true, is_separator);
+        let expansion = self.expansion(&buffer[start..cursor])?;
+        in_command_position.then_some((start, expansion))
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '|' | ';' | '&' | '(')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::LineBuffer;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn abbreviations() -> Abbreviations {
+        Abbreviations::default()
+            .with_abbreviation("ga", "git add")
+            .with_hints(true)
+    }
+
+    #[rstest]
+    #[case("ga", 2, Some("git add"))]
+    #[case("ls | ga", 7, Some("git add"))]
+    #[case("(ga", 3, Some("git add"))]
+    #[case("echo ga", 7, None)]
+    #[case("gap", 3, None)]
+    #[case("ga", 1, None)]
+    #[case("gax", 2, None)]
+    fn only_commands_are_abbreviations(
+        #[case] buffer: &str,
+        #[case] cursor: usize,
+        #[case] expected: Option<&str>,
+    ) {
+        let abbreviations = abbreviations();
+        let found = abbreviations
+            .abbreviation_before(buffer, cursor)
+            .map(|(_, expansion)| expansion);
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn the_abbreviation_is_expanded_and_hinted() {
+        let abbreviations = abbreviations();
+        assert_eq!(abbreviations.hint("ls; ga", 6, false), " → git add");
+        assert_eq!(abbreviations.hint("ls; g", 5, false), "");
+
+        let mut line_buffer = LineBuffer::from("ls; ga file");
+        line_buffer.set_insertion_point(6);
+        let mut editor = Editor::default();
+        editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+        assert!(abbreviations.expand(&mut editor));
+        assert_eq!(editor.get_buffer(), "ls; git add file");
+        assert_eq!(editor.insertion_point(), 11);
+        assert!(!abbreviations.expand(&mut editor));
+    }
+}
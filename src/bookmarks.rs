@@ -0,0 +1,351 @@
+use crate::{
+    completion::usage::{escape, unescape},
+    core_editor::Editor,
+    menu_functions::parse_selection_char,
+    Completer, ExCommand, ListMenu, MenuBuilder, ReedlineMenu, Span, Suggestion,
+};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+// Same as the history menu, `!3` picks the third bookmark
+const SELECTION_CHAR: char = '!';
+
+/// Commands saved under a name, to insert them again later from a menu
+///
+/// Unlike the history the bookmarks are only changed explicitly, so they are never
+/// pruned. A store kept in a file can be shared by copying the file: it has one
+/// bookmark per line, the name and the command separated by a tab.
+///
+/// Clones share the same bookmarks, e.g. the ones of the menu and of the ex command.
+///
+/// ```rust
+/// use reedline::{Bookmarks, Reedline};
+///
+/// let bookmarks = Bookmarks::new();
+/// bookmarks.add("deploy-prod", "kubectl apply -f prod.yaml").unwrap();
+/// let mut line_editor = Reedline::create()
+///     .with_menu(bookmarks.menu("bookmark_menu"))
+///     .use_ex_commands(true)
+///     .with_ex_command(bookmarks.ex_command("bookmark"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Bookmarks {
+    store: Arc<Mutex<BookmarkStore>>,
+}
+
+/// A command saved in [`Bookmarks`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    /// The name to pick the bookmark by
+    pub name: String,
+    /// The command inserted into the buffer
+    pub command: String,
+}
+
+#[derive(Debug, Default)]
+struct BookmarkStore {
+    bookmarks: BTreeMap<String, String>,
+    file: Option<PathBuf>,
+}
+
+impl Bookmarks {
+    /// An empty store in memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store kept in `path`, with the bookmarks saved before if the file exists
+    ///
+    /// The file is rewritten after every change.
+    pub fn with_file(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let mut store = BookmarkStore::default();
+        match File::open(&path) {
+            Ok(file) => store.load(BufReader::new(file))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        store.file = Some(path);
+        Ok(Self {
+            store: Arc::new(Mutex::new(store)),
+        })
+    }
+
+    /// Save `command` as `name`, returning the command it replaces
+    pub fn add(
+        &self,
+        name: impl Into<String>,
+        command: impl Into<String>,
+    ) -> io::Result<Option<String>> {
+        let mut store = self.lock();
+        let replaced = store.bookmarks.insert(name.into(), command.into());
+        store.save()?;
+        Ok(replaced)
+    }
+
+    /// Remove the bookmark `name`, returning its command
+    pub fn remove(&self, name: &str) -> io::Result<Option<String>> {
+        let mut store = self.lock();
+        let removed = store.bookmarks.remove(name);
+        if removed.is_some() {
+            store.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// The command saved as `name`
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.lock().bookmarks.get(name).cloned()
+    }
+
+    /// All bookmarks, sorted by name
+    pub fn list(&self) -> Vec<Bookmark> {
+        self.lock()
+            .bookmarks
+            .iter()
+            .map(|(name, command)| Bookmark {
+                name: name.clone(),
+                command: command.clone(),
+            })
+            .collect()
+    }
+
+    /// A completer suggesting the commands whose names fuzzy match the line
+    pub fn completer(&self) -> Box<dyn Completer> {
+        Box::new(BookmarkCompleter(self.clone()))
+    }
+
+    /// A list menu called `name` to pick a bookmark, which replaces the typed text
+    ///
+    /// Names starting with the text come first, then the ones containing it and then
+    /// the ones containing its characters in the same order.
+    pub fn menu(&self, name: &str) -> ReedlineMenu {
+        ReedlineMenu::WithCompleter {
+            menu: Box::new(ListMenu::default().with_name(name)),
+            completer: self.completer(),
+        }
+    }
+
+    /// An ex command called `name` saving the buffer as the bookmark given as argument,
+    /// e.g. `:bookmark deploy-prod`
+    pub fn ex_command(&self, name: impl Into<String>) -> Box<dyn ExCommand> {
+        Box::new(BookmarkCommand {
+            name: name.into(),
+            bookmarks: self.clone(),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BookmarkStore> {
+        // A panic while holding the lock cannot leave the store half updated
+        self.store.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl BookmarkStore {
+    fn load(&mut self, reader: impl BufRead) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            let (name, command) = line.split_once('\t').ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid bookmark entry")
+            })?;
+            self.bookmarks.insert(unescape(name), unescape(command));
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.file {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        for (name, command) in &self.bookmarks {
+            writeln!(writer, "{}\t{}", escape(name), escape(command))?;
+        }
+        Ok(())
+    }
+}
+
+/// How well `name` matches `query`, lower is better: a prefix, a substring or the
+/// characters in order
+fn fuzzy_rank(name: &str, query: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+    let query = query.to_lowercase();
+    if name.starts_with(&query) {
+        Some(0)
+    } else if name.contains(&query) {
+        Some(1)
+    } else {
+        let mut name_chars = name.chars();
+        query
+            .chars()
+            .all(|c| name_chars.any(|n| n == c))
+            .then_some(2)
+    }
+}
+
+struct BookmarkCompleter(Bookmarks);
+
+impl Completer for BookmarkCompleter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let query = parse_selection_char(line, SELECTION_CHAR).remainder;
+        let span = Span {
+            start: pos - line.len(),
+            end: pos,
+        };
+        let mut matches: Vec<_> = self
+            .0
+            .list()
+            .into_iter()
+            .filter_map(|bookmark| fuzzy_rank(&bookmark.name, query).map(|rank| (rank, bookmark)))
+            .collect();
+        // Stable, so bookmarks of the same rank stay sorted by name
+        matches.sort_by_key(|(rank, _)| *rank);
+        matches
+            .into_iter()
+            .map(|(_, bookmark)| Suggestion {
+                value: bookmark.command,
+                description: Some(bookmark.name),
+                span,
+                ..Suggestion::default()
+            })
+            .collect()
+    }
+}
+
+struct BookmarkCommand {
+    name: String,
+    bookmarks: Bookmarks,
+}
+
+impl ExCommand for BookmarkCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&mut self, args: &str, editor: &mut Editor) -> Result<(), String> {
+        let name = args.trim();
+        if name.is_empty() {
+            return Err(format!("Usage: {} <name>", self.name));
+        }
+        if editor.get_buffer().is_empty() {
+            return Err("Nothing to bookmark".to_string());
+        }
+        self.bookmarks
+            .add(name, editor.get_buffer())
+            .map(|_| ())
+            .map_err(|err| format!("Can't save the bookmark: {err}"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn bookmarks_with(entries: &[(&str, &str)]) -> Bookmarks {
+        let bookmarks = Bookmarks::new();
+        for (name, command) in entries {
+            bookmarks.add(*name, *command).unwrap();
+        }
+        bookmarks
+    }
+
+    #[test]
+    fn bookmarks_can_be_added_replaced_and_removed() {
+        let bookmarks = bookmarks_with(&[("b", "echo b"), ("a", "echo a")]);
+        assert_eq!(
+            bookmarks.add("b", "echo new").unwrap(),
+            Some("echo b".to_string())
+        );
+        assert_eq!(bookmarks.get("b"), Some("echo new".to_string()));
+        assert_eq!(
+            bookmarks.list(),
+            [
+                Bookmark {
+                    name: "a".to_string(),
+                    command: "echo a".to_string()
+                },
+                Bookmark {
+                    name: "b".to_string(),
+                    command: "echo new".to_string()
+                }
+            ]
+        );
+
+        assert_eq!(bookmarks.remove("a").unwrap(), Some("echo a".to_string()));
+        assert_eq!(bookmarks.remove("a").unwrap(), None);
+        assert_eq!(bookmarks.list().len(), 1);
+    }
+
+    #[test]
+    fn bookmarks_are_kept_in_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("bookmarks");
+        let bookmarks = Bookmarks::with_file(&file).unwrap();
+        bookmarks
+            .add("multi\tline", "for x in a b\ndo\n  echo $x\ndone")
+            .unwrap();
+        bookmarks.add("ls", "ls -l").unwrap();
+        bookmarks.remove("ls").unwrap();
+
+        let loaded = Bookmarks::with_file(&file).unwrap();
+        assert_eq!(loaded.list(), bookmarks.list());
+        std::fs::write(&file, "no tab").unwrap();
+        assert!(Bookmarks::with_file(&file).is_err());
+    }
+
+    #[test]
+    fn the_completer_ranks_fuzzy_matches() {
+        let bookmarks = bookmarks_with(&[
+            ("deploy-prod", "deploy prod"),
+            ("prod-logs", "logs prod"),
+            ("dump-prod", "dump prod"),
+            ("stage", "deploy stage"),
+        ]);
+        let mut completer = bookmarks.completer();
+
+        let suggestions = completer.complete("prd", 3);
+        let names: Vec<_> = suggestions
+            .iter()
+            .map(|suggestion| suggestion.description.as_deref().unwrap())
+            .collect();
+        assert_eq!(names, ["deploy-prod", "dump-prod", "prod-logs"]);
+        assert_eq!(suggestions[0].value, "deploy prod");
+        assert_eq!(suggestions[0].span, Span::new(0, 3));
+
+        let names: Vec<_> = completer
+            .complete("PROD", 4)
+            .into_iter()
+            .map(|suggestion| suggestion.description.unwrap())
+            .collect();
+        assert_eq!(names, ["prod-logs", "deploy-prod", "dump-prod"]);
+        assert_eq!(completer.complete("", 0).len(), 4);
+    }
+
+    #[test]
+    fn the_ex_command_bookmarks_the_buffer() {
+        let bookmarks = Bookmarks::new();
+        let mut command = bookmarks.ex_command("bookmark");
+        let mut editor = Editor::default();
+        assert!(command.run("deploy", &mut editor).is_err());
+
+        editor.set_buffer(
+            "make deploy".to_string(),
+            crate::UndoBehavior::CreateUndoPoint,
+        );
+        assert!(command.run("  ", &mut editor).is_err());
+        command.run("deploy", &mut editor).unwrap();
+        assert_eq!(bookmarks.get("deploy"), Some("make deploy".to_string()));
+    }
+}
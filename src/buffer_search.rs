@@ -0,0 +1,173 @@
+use crate::{core_editor::Editor, EditCommand, UndoBehavior};
+use std::ops::Range;
+
+/// State of an incremental search within the buffer
+///
+/// The search term is entered in a command line below the buffer. Every match of it is
+/// highlighted and the cursor follows the current match, until the search is accepted or the
+/// cursor is moved back to where the search started.
+pub(crate) struct BufferSearch {
+    input: Editor,
+    origin: usize,
+    matches: Vec<Range<usize>>,
+    current: Option<usize>,
+}
+
+impl BufferSearch {
+    /// Start searching with the cursor at `origin`
+    pub(crate) fn new(origin: usize) -> Self {
+        Self {
+            input: Editor::default(),
+            origin,
+            matches: Vec::new(),
+            current: None,
+        }
+    }
+
+    /// The command line with the search term
+    pub(crate) const fn input(&self) -> &Editor {
+        &self.input
+    }
+
+    /// Edit the search term and move the cursor to the first match after the origin
+    pub(crate) fn run_input_command(&mut self, command: &EditCommand, editor: &mut Editor) {
+        self.input.run_edit_command(command);
+        let term = self.input.get_buffer();
+        self.matches = if term.is_empty() {
+            Vec::new()
+        } else {
+            editor
+                .get_buffer()
+                .match_indices(term)
+                .map(|(start, found)| start..start + found.len())
+                .collect()
+        };
+        self.current = if self.matches.is_empty() {
+            None
+        } else {
+            // Wraps around to the first match if there is none after the origin
+            Some(
+                self.matches
+                    .iter()
+                    .position(|found| found.start >= self.origin)
+                    .unwrap_or(0),
+            )
+        };
+        self.move_cursor(editor);
+    }
+
+    /// Move to the next match, wrapping around at the end of the buffer
+    pub(crate) fn next(&mut self, editor: &mut Editor) {
+        if let Some(current) = self.current {
+            self.current = Some((current + 1) % self.matches.len());
+            self.move_cursor(editor);
+        }
+    }
+
+    /// Move to the previous match, wrapping around at the start of the buffer
+    pub(crate) fn previous(&mut self, editor: &mut Editor) {
+        if let Some(current) = self.current {
+            self.current = Some(current.checked_sub(1).unwrap_or(self.matches.len() - 1));
+            self.move_cursor(editor);
+        }
+    }
+
+    /// Put the cursor back to where the search started
+    pub(crate) fn restore_cursor(&self, editor: &mut Editor) {
+        set_insertion_point(editor, self.origin);
+    }
+
+    /// Every match of the search term
+    pub(crate) fn matches(&self) -> &[Range<usize>] {
+        &self.matches
+    }
+
+    /// The match the cursor is at
+    pub(crate) fn current_match(&self) -> Option<Range<usize>> {
+        self.current.map(|current| self.matches[current].clone())
+    }
+
+    /// Prompt shown in front of the command line
+    pub(crate) fn prompt(&self) -> String {
+        match self.current {
+            Some(current) => format!("Search ({}/{}): ", current + 1, self.matches.len()),
+            None if self.input.is_empty() => "Search: ".to_string(),
+            None => "Failing search: ".to_string(),
+        }
+    }
+
+    fn move_cursor(&self, editor: &mut Editor) {
+        let position = self
+            .current_match()
+            .map_or(self.origin, |current| current.start);
+        set_insertion_point(editor, position);
+    }
+}
+
+fn set_insertion_point(editor: &mut Editor, position: usize) {
+    let mut line_buffer = editor.line_buffer().clone();
+    line_buffer.set_insertion_point(position);
+    editor.set_line_buffer(line_buffer, UndoBehavior::MoveCursor);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::LineBuffer;
+    use pretty_assertions::assert_eq;
+
+    fn editor_with(buffer: &str, insertion_point: usize) -> Editor {
+        let mut line_buffer = LineBuffer::from(buffer);
+        line_buffer.set_insertion_point(insertion_point);
+        let mut editor = Editor::default();
+        editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+        editor
+    }
+
+    fn search(editor: &mut Editor, term: &str) -> BufferSearch {
+        let mut search = BufferSearch::new(editor.insertion_point());
+        for c in term.chars() {
+            search.run_input_command(&EditCommand::InsertChar(c), editor);
+        }
+        search
+    }
+
+    #[test]
+    fn the_search_starts_at_the_first_match_after_the_cursor() {
+        let mut editor = editor_with("let a = 1;\nlet b = a;\nlet c = b;", 4);
+        let search = search(&mut editor, "let");
+
+        assert_eq!(search.matches(), [0..3, 11..14, 22..25]);
+        assert_eq!(search.current_match(), Some(11..14));
+        assert_eq!(editor.insertion_point(), 11);
+        assert_eq!(search.prompt(), "Search (2/3): ");
+    }
+
+    #[test]
+    fn matches_are_cycled_through() {
+        let mut editor = editor_with("a b a b a", 0);
+        let mut search = search(&mut editor, "a");
+
+        search.previous(&mut editor);
+        assert_eq!(editor.insertion_point(), 8);
+        search.next(&mut editor);
+        search.next(&mut editor);
+        assert_eq!(editor.insertion_point(), 4);
+    }
+
+    #[test]
+    fn failing_searches_keep_the_cursor_at_the_origin() {
+        let mut editor = editor_with("foo bar", 5);
+        let mut search = search(&mut editor, "ba");
+        assert_eq!(editor.insertion_point(), 4);
+
+        search.run_input_command(&EditCommand::InsertChar('z'), &mut editor);
+        assert_eq!(search.current_match(), None);
+        assert_eq!(editor.insertion_point(), 5);
+        assert_eq!(search.prompt(), "Failing search: ");
+
+        search.run_input_command(&EditCommand::Backspace, &mut editor);
+        search.restore_cursor(&mut editor);
+        assert_eq!(editor.insertion_point(), 5);
+    }
+}
@@ -0,0 +1,376 @@
+use crate::KeyCombination;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use itertools::Itertools;
+use nu_ansi_term::{Color, Style};
+use std::{
+    fmt::Display,
+    ops::{Bound, RangeBounds},
+    str::FromStr,
+};
+
+/// What a key typed while reading a [`Choice`] leads to
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ChoiceStep<T> {
+    /// The choice waits for more keys
+    Pending,
+    /// The choice is made, `None` if it was cancelled
+    Done(Option<T>),
+}
+
+/// A small question read with the painter of the editor instead of a whole line, e.g. the
+/// `y/n` of [`crate::Reedline::confirm`]
+pub(crate) trait Choice {
+    type Value;
+
+    /// What is painted after the prompt: the answer so far, in place of the buffer, and the
+    /// text after the cursor, in place of the hint
+    fn render(&self, use_ansi_coloring: bool) -> (String, String);
+
+    /// Continue with the typed `key`, unless it is Esc, Ctrl-C or Ctrl-D which cancel first
+    fn handle_key(&mut self, key: KeyEvent) -> ChoiceStep<Self::Value>;
+
+    /// The answer left on the screen once the choice is made
+    fn answer(&self, value: Option<&Self::Value>) -> String;
+}
+
+fn paint(style: Style, text: String, use_ansi_coloring: bool) -> String {
+    if use_ansi_coloring {
+        style.paint(text).to_string()
+    } else {
+        text
+    }
+}
+
+/// The question of [`crate::Reedline::confirm`]
+pub(crate) struct Confirmation {
+    default: bool,
+}
+
+impl Confirmation {
+    pub(crate) fn new(default: bool) -> Self {
+        Self { default }
+    }
+}
+
+impl Choice for Confirmation {
+    type Value = bool;
+
+    fn render(&self, use_ansi_coloring: bool) -> (String, String) {
+        let choices = if self.default { "[Y/n] " } else { "[y/N] " };
+        let style = Style::new().fg(Color::DarkGray);
+        (
+            paint(style, choices.to_string(), use_ansi_coloring),
+            String::new(),
+        )
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> ChoiceStep<bool> {
+        match key.code {
+            KeyCode::Char('y' | 'Y') => ChoiceStep::Done(Some(true)),
+            KeyCode::Char('n' | 'N') => ChoiceStep::Done(Some(false)),
+            KeyCode::Enter => ChoiceStep::Done(Some(self.default)),
+            _ => ChoiceStep::Pending,
+        }
+    }
+
+    fn answer(&self, value: Option<&bool>) -> String {
+        match value {
+            Some(true) => "yes".into(),
+            Some(false) => "no".into(),
+            None => String::new(),
+        }
+    }
+}
+
+/// The list of options of [`crate::Reedline::select`]
+pub(crate) struct Selection {
+    options: Vec<String>,
+    selected: usize,
+}
+
+impl Selection {
+    pub(crate) fn new(options: Vec<String>) -> Self {
+        Self {
+            options,
+            selected: 0,
+        }
+    }
+}
+
+impl Choice for Selection {
+    type Value = usize;
+
+    fn render(&self, use_ansi_coloring: bool) -> (String, String) {
+        let selected_style = Style::new().fg(Color::Green).bold();
+        let other_style = Style::new().fg(Color::DarkGray);
+        let options = self
+            .options
+            .iter()
+            .enumerate()
+            .map(|(index, option)| {
+                if index == self.selected {
+                    paint(selected_style, format!("\n> {option}"), use_ansi_coloring)
+                } else {
+                    paint(other_style, format!("\n  {option}"), use_ansi_coloring)
+                }
+            })
+            .collect();
+        (self.options[self.selected].clone(), options)
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> ChoiceStep<usize> {
+        let last = self.options.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Up | KeyCode::BackTab | KeyCode::Char('k') => {
+                self.selected = self.selected.checked_sub(1).unwrap_or(last);
+            }
+            KeyCode::Down | KeyCode::Tab | KeyCode::Char('j') => {
+                self.selected = if self.selected == last {
+                    0
+                } else {
+                    self.selected + 1
+                };
+            }
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            KeyCode::Enter => return ChoiceStep::Done(Some(self.selected)),
+            _ => {}
+        }
+        ChoiceStep::Pending
+    }
+
+    fn answer(&self, value: Option<&usize>) -> String {
+        value
+            .and_then(|index| self.options.get(*index))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// The single key of [`crate::Reedline::read_key`]
+pub(crate) struct KeyChoice {
+    allowed: Vec<KeyCode>,
+}
+
+impl KeyChoice {
+    pub(crate) fn new(allowed: Vec<KeyCode>) -> Self {
+        Self { allowed }
+    }
+}
+
+fn key_name(key_code: KeyCode) -> String {
+    KeyCombination {
+        modifier: KeyModifiers::NONE,
+        key_code,
+    }
+    .to_string()
+}
+
+impl Choice for KeyChoice {
+    type Value = KeyCode;
+
+    fn render(&self, use_ansi_coloring: bool) -> (String, String) {
+        let keys = self.allowed.iter().map(|key| key_name(*key)).join("/");
+        let style = Style::new().fg(Color::DarkGray);
+        (
+            paint(style, format!("[{keys}] "), use_ansi_coloring),
+            String::new(),
+        )
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> ChoiceStep<KeyCode> {
+        if self.allowed.contains(&key.code) {
+            ChoiceStep::Done(Some(key.code))
+        } else {
+            ChoiceStep::Pending
+        }
+    }
+
+    fn answer(&self, value: Option<&KeyCode>) -> String {
+        value.map(|key| key_name(*key)).unwrap_or_default()
+    }
+}
+
+/// The number of [`crate::Reedline::read_number`], typed and checked against its range
+pub(crate) struct NumberInput<T> {
+    text: String,
+    start: Bound<T>,
+    end: Bound<T>,
+}
+
+impl<T: FromStr + PartialOrd + Clone + Display> NumberInput<T> {
+    pub(crate) fn new(range: impl RangeBounds<T>) -> Self {
+        Self {
+            text: String::new(),
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+        }
+    }
+
+    // The typed number, if it is one in the range
+    fn value(&self) -> Option<T> {
+        let value = self.text.parse().ok()?;
+        let range = (self.start.clone(), self.end.clone());
+        range.contains(&value).then_some(value)
+    }
+
+    // Whether `text` is a number or could still become one, like `-` or `1.`
+    fn could_be_number(text: &str) -> bool {
+        let parses = |text: &str| text.parse::<T>().is_ok();
+        parses(text)
+            || (text == "-" && parses("-1"))
+            || (text.ends_with('.') && !text[..text.len() - 1].contains('.') && {
+                parses(&format!("{text}0"))
+            })
+    }
+
+    fn range_hint(&self) -> String {
+        let start = match &self.start {
+            Bound::Included(start) => Some(format!("at least {start}")),
+            Bound::Excluded(start) => Some(format!("more than {start}")),
+            Bound::Unbounded => None,
+        };
+        let end = match &self.end {
+            Bound::Included(end) => Some(format!("at most {end}")),
+            Bound::Excluded(end) => Some(format!("less than {end}")),
+            Bound::Unbounded => None,
+        };
+        match (&self.start, &self.end) {
+            (Bound::Included(start), Bound::Included(end)) => format!(" ({start} to {end})"),
+            _ if start.is_none() && end.is_none() => String::new(),
+            _ => format!(" ({})", start.into_iter().chain(end).join(", ")),
+        }
+    }
+}
+
+impl<T: FromStr + PartialOrd + Clone + Display> Choice for NumberInput<T> {
+    type Value = T;
+
+    fn render(&self, use_ansi_coloring: bool) -> (String, String) {
+        let text_style = if self.value().is_some() || self.text.is_empty() {
+            Style::new()
+        } else {
+            Style::new().fg(Color::Red)
+        };
+        (
+            paint(text_style, self.text.clone(), use_ansi_coloring),
+            paint(
+                Style::new().fg(Color::DarkGray),
+                self.range_hint(),
+                use_ansi_coloring,
+            ),
+        )
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> ChoiceStep<T> {
+        match key.code {
+            KeyCode::Char(c) => {
+                let text = format!("{}{c}", self.text);
+                if Self::could_be_number(&text) {
+                    self.text = text;
+                }
+            }
+            KeyCode::Backspace => {
+                self.text.pop();
+            }
+            KeyCode::Enter => {
+                if let Some(value) = self.value() {
+                    return ChoiceStep::Done(Some(value));
+                }
+            }
+            _ => {}
+        }
+        ChoiceStep::Pending
+    }
+
+    fn answer(&self, value: Option<&T>) -> String {
+        value.map(ToString::to_string).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn enter_confirms_the_default() {
+        let mut confirmation = Confirmation::new(false);
+        assert_eq!(confirmation.render(false).0, "[y/N] ");
+        assert_eq!(
+            confirmation.handle_key(key(KeyCode::Char('x'))),
+            ChoiceStep::Pending
+        );
+        assert_eq!(
+            confirmation.handle_key(key(KeyCode::Enter)),
+            ChoiceStep::Done(Some(false))
+        );
+        assert_eq!(
+            confirmation.handle_key(key(KeyCode::Char('Y'))),
+            ChoiceStep::Done(Some(true))
+        );
+        assert_eq!(confirmation.answer(Some(&true)), "yes");
+    }
+
+    #[test]
+    fn the_selection_wraps_around_the_options() {
+        let mut selection = Selection::new(vec!["one".into(), "two".into(), "three".into()]);
+        assert_eq!(
+            selection.render(false),
+            ("one".into(), "\n> one\n  two\n  three".into())
+        );
+        selection.handle_key(key(KeyCode::Up));
+        assert_eq!(selection.render(false).0, "three");
+        selection.handle_key(key(KeyCode::Down));
+        selection.handle_key(key(KeyCode::Char('j')));
+        assert_eq!(
+            selection.handle_key(key(KeyCode::Enter)),
+            ChoiceStep::Done(Some(1))
+        );
+        assert_eq!(selection.answer(Some(&1)), "two");
+    }
+
+    #[test]
+    fn only_the_allowed_keys_are_taken() {
+        let mut choice = KeyChoice::new(vec![KeyCode::Char('a'), KeyCode::Enter]);
+        assert_eq!(choice.render(false).0, "[a/enter] ");
+        assert_eq!(
+            choice.handle_key(key(KeyCode::Char('b'))),
+            ChoiceStep::Pending
+        );
+        assert_eq!(
+            choice.handle_key(key(KeyCode::Enter)),
+            ChoiceStep::Done(Some(KeyCode::Enter))
+        );
+    }
+
+    #[test]
+    fn numbers_are_checked_as_they_are_typed() {
+        let mut input = NumberInput::<i32>::new(-5..=10);
+        assert_eq!(input.range_hint(), " (-5 to 10)");
+        for c in ['-', 'x', '1', '.', '2'] {
+            input.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(input.render(false).0, "-12");
+        assert_eq!(input.handle_key(key(KeyCode::Enter)), ChoiceStep::Pending);
+        input.handle_key(key(KeyCode::Backspace));
+        assert_eq!(
+            input.handle_key(key(KeyCode::Enter)),
+            ChoiceStep::Done(Some(-1))
+        );
+
+        let mut input = NumberInput::<f64>::new(..1.0);
+        assert_eq!(input.range_hint(), " (less than 1)");
+        for c in ['0', '.', '5'] {
+            input.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(
+            input.handle_key(key(KeyCode::Enter)),
+            ChoiceStep::Done(Some(0.5))
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use nu_ansi_term::Style;
-use std::ops::Range;
+use std::{any::Any, fmt, ops::Range};
 
 /// A span of source code, with positions in bytes
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
@@ -27,6 +27,35 @@ impl Span {
     }
 }
 
+/// Why a [`Completer`] couldn't complete a line, shown by the menus in place of the suggestions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompletionError {
+    /// Reading what to suggest failed, e.g. listing a directory
+    Io(String),
+    /// The completer panicked, with the message of the panic
+    Panicked(String),
+    /// Any other failure, described for the user
+    Other(String),
+}
+
+impl fmt::Display for CompletionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(message) => write!(f, "IO error: {message}"),
+            Self::Panicked(message) => write!(f, "panicked: {message}"),
+            Self::Other(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for CompletionError {}
+
+impl From<std::io::Error> for CompletionError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error.to_string())
+    }
+}
+
 /// A trait that defines how to convert some text and a position to a list of potential completions in that position.
 /// The text could be a part of the whole line, and the position is the index of the end of the text in the original line.
 pub trait Completer: Send {
@@ -34,6 +63,22 @@ pub trait Completer: Send {
     /// span to replace and the contents of that replacement
     fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion>;
 
+    /// [`Completer::complete`] with the state of the application given to
+    /// [`crate::Reedline::with_context`], e.g. the variables in scope
+    ///
+    /// Called instead of [`Completer::complete`] once there is a context, also by the
+    /// default implementations of the other methods; override it to look at the context
+    /// with `context.downcast_ref::<MyContext>()`.
+    fn complete_in_context(
+        &mut self,
+        line: &str,
+        pos: usize,
+        context: &dyn Any,
+    ) -> Vec<Suggestion> {
+        let _ = context;
+        self.complete(line, pos)
+    }
+
     /// same as [`Completer::complete`] but it will return a vector of ranges of the strings
     /// the suggestions are based on
     fn complete_with_base_ranges(
@@ -50,6 +95,21 @@ pub trait Completer: Send {
         (suggestions, ranges)
     }
 
+    /// [`Completer::complete_with_base_ranges`] for completers that can fail, e.g. when they
+    /// read a directory or ask another process
+    ///
+    /// The menus complete with this method and show the error in place of the suggestions,
+    /// see also [`crate::Reedline::with_completion_error_handler`]. Never fails by default;
+    /// a completer overriding it usually implements [`Completer::complete`] with it, dropping
+    /// the error. Once there is a context [`Completer::complete_in_context`] is asked instead.
+    fn try_complete_with_base_ranges(
+        &mut self,
+        line: &str,
+        pos: usize,
+    ) -> Result<(Vec<Suggestion>, Vec<Range<usize>>), CompletionError> {
+        Ok(self.complete_with_base_ranges(line, pos))
+    }
+
     /// action that will return a partial section of available completions
     /// this command comes handy when trying to avoid to pull all the data at once
     /// from the completer
@@ -71,6 +131,15 @@ pub trait Completer: Send {
     fn total_completions(&mut self, line: &str, pos: usize) -> usize {
         self.complete(line, pos).len()
     }
+
+    /// Whether the suggestions of the last call are still being computed, e.g. on another thread
+    ///
+    /// While they are, the engine asks the completer again every few milliseconds and
+    /// shows a progress indicator once that takes longer than
+    /// [`crate::Reedline::with_progress_indicator_delay`].
+    fn is_pending(&self) -> bool {
+        false
+    }
 }
 
 /// Suggestion returned by the Completer
@@ -90,4 +159,7 @@ pub struct Suggestion {
     /// Whether to append a space after selecting this suggestion.
     /// This helps to avoid that a completer repeats the complete suggestion.
     pub append_whitespace: bool,
+    /// Optional target of a hyperlink on the description, e.g. a `file://` URL or the
+    /// documentation of the value, see [`crate::StyledText::link_range`]
+    pub link: Option<String>,
 }
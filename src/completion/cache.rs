@@ -0,0 +1,328 @@
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+#[cfg(feature = "tracing")]
+use crate::CacheStats;
+use crate::{Completer, CompletionError, Span, Suggestion};
+
+/// Handle to drop the cached completions of a [`crate::Reedline`] instance
+///
+/// The cache can't know when the completions of a line change, e.g. after a `cd` or
+/// when new commands got installed. Clones of the handle can be moved to wherever
+/// that happens, e.g. into a host command or another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CompletionCacheInvalidator {
+    generation: Arc<AtomicU64>,
+}
+
+impl CompletionCacheInvalidator {
+    /// Drop the cached completions, the next completion asks the completer again
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+}
+
+struct CacheEntry {
+    generation: u64,
+    line: String,
+    pos: usize,
+    suggestions: Vec<Suggestion>,
+    ranges: Vec<Range<usize>>,
+}
+
+/// The suggestions of the last completion of the engine's [`Completer`]
+///
+/// Reused as is for the same line, e.g. when the menu is opened again, and filtered
+/// by the typed prefix while the user keeps typing the same word.
+#[derive(Default)]
+pub(crate) struct CompletionCache {
+    enabled: bool,
+    entry: Option<CacheEntry>,
+    invalidator: CompletionCacheInvalidator,
+    #[cfg(feature = "tracing")]
+    stats: CacheStats,
+}
+
+impl CompletionCache {
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.entry = None;
+    }
+
+    pub(crate) fn invalidator(&self) -> CompletionCacheInvalidator {
+        self.invalidator.clone()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entry = None;
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// `completer` behind the cache, to be handed to the menus
+    pub(crate) fn wrap<'a>(&'a mut self, completer: &'a mut dyn Completer) -> CachedCompleter<'a> {
+        CachedCompleter {
+            cache: self,
+            completer,
+        }
+    }
+
+    // The suggestions for `line` derived from the cached ones, if they are still valid
+    fn lookup(&self, line: &str, pos: usize) -> Option<(Vec<Suggestion>, Vec<Range<usize>>)> {
+        let entry = self.entry.as_ref()?;
+        if entry.generation != self.invalidator.generation() {
+            return None;
+        }
+        if entry.line == line && entry.pos == pos {
+            return Some((entry.suggestions.clone(), entry.ranges.clone()));
+        }
+
+        // The line may only be the end of the buffer, ending at `pos`
+        let origin = pos.checked_sub(line.len())?;
+        let typed = line.strip_prefix(entry.line.as_str())?;
+        if origin != entry.pos.checked_sub(entry.line.len())?
+            || typed.is_empty()
+            || !typed
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            return None;
+        }
+
+        // Only suggestions for the word in front of the cursor can be narrowed down
+        let start = entry.suggestions.first()?.span.start;
+        if start < origin
+            || !entry
+                .suggestions
+                .iter()
+                .all(|suggestion| suggestion.span == Span::new(start, entry.pos))
+        {
+            return None;
+        }
+        let word = line.get(start - origin..)?;
+        let suggestions: Vec<Suggestion> = entry
+            .suggestions
+            .iter()
+            .filter(|suggestion| suggestion.value.starts_with(word))
+            .map(|suggestion| Suggestion {
+                span: Span::new(start, pos),
+                ..suggestion.clone()
+            })
+            .collect();
+        let mut ranges: Vec<Range<usize>> = suggestions
+            .iter()
+            .map(|suggestion| suggestion.span.start..suggestion.span.end)
+            .collect();
+        ranges.dedup();
+        Some((suggestions, ranges))
+    }
+}
+
+/// A [`Completer`] answering from the [`CompletionCache`] when possible
+pub(crate) struct CachedCompleter<'a> {
+    cache: &'a mut CompletionCache,
+    completer: &'a mut dyn Completer,
+}
+
+impl<'a> Completer for CachedCompleter<'a> {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        self.complete_with_base_ranges(line, pos).0
+    }
+
+    fn complete_with_base_ranges(
+        &mut self,
+        line: &str,
+        pos: usize,
+    ) -> (Vec<Suggestion>, Vec<Range<usize>>) {
+        self.try_complete_with_base_ranges(line, pos)
+            .unwrap_or_default()
+    }
+
+    fn try_complete_with_base_ranges(
+        &mut self,
+        line: &str,
+        pos: usize,
+    ) -> Result<(Vec<Suggestion>, Vec<Range<usize>>), CompletionError> {
+        if !self.cache.enabled {
+            return self.completer.try_complete_with_base_ranges(line, pos);
+        }
+
+        if let Some(found) = self.cache.lookup(line, pos) {
+            #[cfg(feature = "tracing")]
+            {
+                self.cache.stats.hits += 1;
+            }
+            return Ok(found);
+        }
+
+        #[cfg(feature = "tracing")]
+        {
+            self.cache.stats.misses += 1;
+        }
+        // Failures are not cached, the next completion tries again
+        let (suggestions, ranges) = self.completer.try_complete_with_base_ranges(line, pos)?;
+        // Suggestions still being computed are incomplete
+        self.cache.entry = (!self.completer.is_pending()).then(|| CacheEntry {
+            generation: self.cache.invalidator.generation(),
+            line: line.to_string(),
+            pos,
+            suggestions: suggestions.clone(),
+            ranges: ranges.clone(),
+        });
+        Ok((suggestions, ranges))
+    }
+
+    fn partial_complete(
+        &mut self,
+        line: &str,
+        pos: usize,
+        start: usize,
+        offset: usize,
+    ) -> Vec<Suggestion> {
+        if self.cache.enabled {
+            self.complete(line, pos)
+                .into_iter()
+                .skip(start)
+                .take(offset)
+                .collect()
+        } else {
+            self.completer.partial_complete(line, pos, start, offset)
+        }
+    }
+
+    fn total_completions(&mut self, line: &str, pos: usize) -> usize {
+        if self.cache.enabled {
+            self.complete(line, pos).len()
+        } else {
+            self.completer.total_completions(line, pos)
+        }
+    }
+
+    fn is_pending(&self) -> bool {
+        self.completer.is_pending()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::DefaultCompleter;
+    use pretty_assertions::assert_eq;
+
+    // Completes like the wrapped completer, counting how often it was asked
+    struct CountingCompleter {
+        inner: DefaultCompleter,
+        calls: usize,
+        pending: bool,
+    }
+
+    impl Completer for CountingCompleter {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            self.calls += 1;
+            self.inner.complete(line, pos)
+        }
+
+        fn is_pending(&self) -> bool {
+            self.pending
+        }
+    }
+
+    fn counting_completer() -> CountingCompleter {
+        CountingCompleter {
+            inner: DefaultCompleter::new_with_wordlen(
+                ["cargo", "carpet", "cart", "cat", "ls"]
+                    .iter()
+                    .map(|word| word.to_string())
+                    .collect(),
+                2,
+            ),
+            calls: 0,
+            pending: false,
+        }
+    }
+
+    fn values(suggestions: &[Suggestion]) -> Vec<&str> {
+        suggestions.iter().map(|s| s.value.as_str()).collect()
+    }
+
+    #[test]
+    fn narrows_cached_suggestions_while_typing_the_word() {
+        let mut completer = counting_completer();
+        let mut cache = CompletionCache::default();
+        cache.set_enabled(true);
+
+        let first = cache.wrap(&mut completer).complete("echo ca", 7);
+        assert_eq!(values(&first), ["cargo", "carpet", "cart", "cat"]);
+
+        let narrowed = cache.wrap(&mut completer).complete("echo carp", 9);
+        assert_eq!(values(&narrowed), ["carpet"]);
+        assert_eq!(narrowed[0].span, Span::new(5, 9));
+        assert_eq!(completer.calls, 1);
+
+        let reopened = cache.wrap(&mut completer).complete("echo carp", 9);
+        assert_eq!(values(&reopened), ["carpet"]);
+        assert_eq!(completer.calls, 1);
+    }
+
+    #[test]
+    fn asks_the_completer_outside_of_the_word() {
+        let mut completer = counting_completer();
+        let mut cache = CompletionCache::default();
+        cache.set_enabled(true);
+
+        cache.wrap(&mut completer).complete("echo ca", 7);
+        cache.wrap(&mut completer).complete("echo c", 6);
+        assert_eq!(completer.calls, 2);
+        cache.wrap(&mut completer).complete("echo c l", 8);
+        assert_eq!(completer.calls, 3);
+    }
+
+    #[test]
+    fn invalidator_drops_the_cached_suggestions() {
+        let mut completer = counting_completer();
+        let mut cache = CompletionCache::default();
+        cache.set_enabled(true);
+        let invalidator = cache.invalidator();
+
+        cache.wrap(&mut completer).complete("ca", 2);
+        invalidator.invalidate();
+        cache.wrap(&mut completer).complete("ca", 2);
+        assert_eq!(completer.calls, 2);
+    }
+
+    #[test]
+    fn disabled_cache_always_asks_the_completer() {
+        let mut completer = counting_completer();
+        let mut cache = CompletionCache::default();
+
+        cache.wrap(&mut completer).complete("ca", 2);
+        cache.wrap(&mut completer).complete("ca", 2);
+        assert_eq!(completer.calls, 2);
+    }
+
+    #[test]
+    fn pending_suggestions_are_not_cached() {
+        let mut completer = counting_completer();
+        completer.pending = true;
+        let mut cache = CompletionCache::default();
+        cache.set_enabled(true);
+
+        cache.wrap(&mut completer).complete("ca", 2);
+        completer.pending = false;
+        cache.wrap(&mut completer).complete("ca", 2);
+        cache.wrap(&mut completer).complete("ca", 2);
+        assert_eq!(completer.calls, 2);
+    }
+}
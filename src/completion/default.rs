@@ -1,4 +1,4 @@
-use crate::{Completer, Span, Suggestion};
+use crate::{utils::typo::starts_with_typos, Completer, Span, Suggestion};
 use std::{
     collections::{BTreeMap, BTreeSet},
     str::Chars,
@@ -26,6 +26,7 @@ use std::{
 pub struct DefaultCompleter {
     root: CompletionNode,
     min_word_len: usize,
+    typo_tolerance: usize,
 }
 
 impl Default for DefaultCompleter {
@@ -34,6 +35,7 @@ impl Default for DefaultCompleter {
         Self {
             root: CompletionNode::new(inclusions),
             min_word_len: 2,
+            typo_tolerance: 0,
         }
     }
 }
@@ -55,17 +57,17 @@ impl Completer for DefaultCompleter {
     /// assert_eq!(
     ///     completions.complete("bat",3),
     ///     vec![
-    ///         Suggestion {value: "batcave".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 3 }, append_whitespace: false},
-    ///         Suggestion {value: "batman".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 3 }, append_whitespace: false},
-    ///         Suggestion {value: "batmobile".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 3 }, append_whitespace: false},
+    ///         Suggestion {value: "batcave".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 3 }, append_whitespace: false, link: None},
+    ///         Suggestion {value: "batman".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 3 }, append_whitespace: false, link: None},
+    ///         Suggestion {value: "batmobile".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 3 }, append_whitespace: false, link: None},
     ///     ]);
     ///
     /// assert_eq!(
     ///     completions.complete("to the\r\nbat",11),
     ///     vec![
-    ///         Suggestion {value: "batcave".into(), description: None, style: None, extra: None, span: Span { start: 8, end: 11 }, append_whitespace: false},
-    ///         Suggestion {value: "batman".into(), description: None, style: None, extra: None, span: Span { start: 8, end: 11 }, append_whitespace: false},
-    ///         Suggestion {value: "batmobile".into(), description: None, style: None, extra: None, span: Span { start: 8, end: 11 }, append_whitespace: false},
+    ///         Suggestion {value: "batcave".into(), description: None, style: None, extra: None, span: Span { start: 8, end: 11 }, append_whitespace: false, link: None},
+    ///         Suggestion {value: "batman".into(), description: None, style: None, extra: None, span: Span { start: 8, end: 11 }, append_whitespace: false, link: None},
+    ///         Suggestion {value: "batmobile".into(), description: None, style: None, extra: None, span: Span { start: 8, end: 11 }, append_whitespace: false, link: None},
     ///     ]);
     /// ```
     fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
@@ -110,6 +112,7 @@ impl Completer for DefaultCompleter {
                                         extra: None,
                                         span,
                                         append_whitespace: false,
+                                        link: None,
                                     }
                                 })
                                 .filter(|t| t.value.len() > (t.span.end - t.span.start))
@@ -120,6 +123,9 @@ impl Completer for DefaultCompleter {
             }
         }
         completions.dedup();
+        if completions.is_empty() && self.typo_tolerance > 0 {
+            return self.complete_with_typos(line, pos);
+        }
         completions
     }
 }
@@ -182,15 +188,15 @@ impl DefaultCompleter {
     /// completions.insert(vec!["test-hyphen","test_underscore"].iter().map(|s| s.to_string()).collect());
     /// assert_eq!(
     ///     completions.complete("te",2),
-    ///     vec![Suggestion {value: "test".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 2 }, append_whitespace: false}]);
+    ///     vec![Suggestion {value: "test".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 2 }, append_whitespace: false, link: None}]);
     ///
     /// let mut completions = DefaultCompleter::with_inclusions(&['-', '_']);
     /// completions.insert(vec!["test-hyphen","test_underscore"].iter().map(|s| s.to_string()).collect());
     /// assert_eq!(
     ///     completions.complete("te",2),
     ///     vec![
-    ///         Suggestion {value: "test-hyphen".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 2 }, append_whitespace: false},
-    ///         Suggestion {value: "test_underscore".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 2 }, append_whitespace: false},
+    ///         Suggestion {value: "test-hyphen".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 2 }, append_whitespace: false, link: None},
+    ///         Suggestion {value: "test_underscore".into(), description: None, style: None, extra: None, span: Span { start: 0, end: 2 }, append_whitespace: false, link: None},
     ///     ]);
     /// ```
     pub fn with_inclusions(incl: &[char]) -> Self {
@@ -276,6 +282,46 @@ impl DefaultCompleter {
         self.min_word_len = len;
         self
     }
+
+    /// Suggest the words starting with the word before the cursor with up to `max_typos`
+    /// typos, if none starts with it exactly. At most one typo per three characters of the
+    /// word is tolerated.
+    ///
+    /// # Example
+    /// ```
+    /// use reedline::{DefaultCompleter,Completer};
+    ///
+    /// let mut completions = DefaultCompleter::default().with_typo_tolerance(2);
+    /// completions.insert(vec!["status","stash"].iter().map(|s| s.to_string()).collect());
+    /// assert_eq!(completions.complete("git sttau", 9)[0].value, "status");
+    /// ```
+    #[must_use]
+    pub fn with_typo_tolerance(mut self, max_typos: usize) -> Self {
+        self.typo_tolerance = max_typos;
+        self
+    }
+
+    fn complete_with_typos(&self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let line = if line.len() > pos { &line[..pos] } else { line };
+        let word = line.rsplit(char::is_whitespace).next().unwrap_or_default();
+        if word.is_empty() {
+            return vec![];
+        }
+        self.root
+            .collect("")
+            .into_iter()
+            .filter(|value| value != word && starts_with_typos(value, word, self.typo_tolerance))
+            .map(|value| Suggestion {
+                value,
+                description: None,
+                style: None,
+                extra: None,
+                span: Span::new(pos - word.len(), pos),
+                append_whitespace: false,
+                link: None,
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -384,6 +430,7 @@ mod tests {
                     extra: None,
                     span: Span { start: 0, end: 3 },
                     append_whitespace: false,
+                    link: None,
                 },
                 Suggestion {
                     value: "ｎｕｍｂｅｒ".into(),
@@ -392,6 +439,7 @@ mod tests {
                     extra: None,
                     span: Span { start: 0, end: 3 },
                     append_whitespace: false,
+                    link: None,
                 },
                 Suggestion {
                     value: "ｎｕｓｈｅｌｌ".into(),
@@ -400,6 +448,7 @@ mod tests {
                     extra: None,
                     span: Span { start: 0, end: 3 },
                     append_whitespace: false,
+                    link: None,
                 },
             ]
         );
@@ -428,6 +477,7 @@ mod tests {
                     extra: None,
                     span: Span { start: 8, end: 9 },
                     append_whitespace: false,
+                    link: None,
                 },
                 Suggestion {
                     value: "this is the reedline crate".into(),
@@ -436,6 +486,7 @@ mod tests {
                     extra: None,
                     span: Span { start: 8, end: 9 },
                     append_whitespace: false,
+                    link: None,
                 },
                 Suggestion {
                     value: "this is the reedline crate".into(),
@@ -444,6 +495,7 @@ mod tests {
                     extra: None,
                     span: Span { start: 0, end: 9 },
                     append_whitespace: false,
+                    link: None,
                 },
             ]
         );
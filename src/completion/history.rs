@@ -1,33 +1,108 @@
-use std::{collections::HashSet, ops::Deref};
+use std::{collections::HashSet, ops::ControlFlow, ops::Deref};
 
 use crate::{
-    history::SearchQuery, menu_functions::parse_selection_char, Completer, History, HistoryItem,
-    Result, Span, Suggestion,
+    history::{SearchDirection, SearchQuery},
+    menu_functions::parse_selection_char,
+    utils::typo::contains_with_typos,
+    Completer, History, HistoryItem, Result, Span, Suggestion,
 };
 
 const SELECTION_CHAR: char = '!';
 
+// Number of history entries loaded at once when filling a page of the menu
+const HISTORY_PAGE_SIZE: usize = 100;
+
 // The HistoryCompleter is created just before updating the menu
 // It pulls data from the object that contains access to the History
-pub(crate) struct HistoryCompleter<'menu>(&'menu dyn History);
+pub(crate) struct HistoryCompleter<'menu> {
+    history: &'menu dyn History,
+    typo_tolerance: usize,
+}
 
 // Safe to implement Send since the HistoryCompleter should only be used when
 // updating the menu and that must happen in the same thread
 unsafe impl<'menu> Send for HistoryCompleter<'menu> {}
 
-fn search_unique(
-    completer: &HistoryCompleter,
-    line: &str,
-) -> Result<impl Iterator<Item = HistoryItem>> {
-    let parsed = parse_selection_char(line, SELECTION_CHAR);
-    let values = completer.0.search(SearchQuery::all_that_contain_rev(
-        parsed.remainder.to_string(),
-    ))?;
+// How the entries are matched with the text of the line
+struct Matcher<'a> {
+    contains: &'a str,
+    // The typos tolerated if no entry contains the text exactly
+    max_typos: Option<usize>,
+}
 
-    let mut seen_matching_command_lines = HashSet::new();
-    Ok(values
+impl<'a> Matcher<'a> {
+    fn new(completer: &HistoryCompleter, contains: &'a str) -> Self {
+        let exact = Matcher {
+            contains,
+            max_typos: None,
+        };
+        if completer.typo_tolerance == 0 || contains.is_empty() {
+            return exact;
+        }
+        let has_exact_match = !pinned_matching(completer, &exact).is_empty()
+            || completer
+                .history
+                .search_paged(exact.query(), 0, 1)
+                .map_or(true, |values| !values.is_empty());
+        if has_exact_match {
+            exact
+        } else {
+            Matcher {
+                contains,
+                max_typos: Some(completer.typo_tolerance),
+            }
+        }
+    }
+
+    // The history can't match with typos itself, all entries are checked then
+    fn query(&self) -> SearchQuery {
+        match self.max_typos {
+            None => SearchQuery::all_that_contain_rev(self.contains.to_string()),
+            Some(_) => SearchQuery::everything(SearchDirection::Backward, None),
+        }
+    }
+
+    fn matches(&self, value: &HistoryItem) -> bool {
+        match self.max_typos {
+            None => value.command_line.contains(self.contains),
+            Some(max_typos) => contains_with_typos(&value.command_line, self.contains, max_typos),
+        }
+    }
+}
+
+// The pinned entries that match, which are listed before all others
+fn pinned_matching(completer: &HistoryCompleter, matcher: &Matcher) -> Vec<HistoryItem> {
+    completer
+        .history
+        .pinned()
+        .unwrap_or_default()
         .into_iter()
-        .filter(move |value| seen_matching_command_lines.insert(value.command_line.clone())))
+        .filter(|value| matcher.matches(value))
+        .collect()
+}
+
+// Duplicates are dropped while the results come in, so they are never all held at once
+fn search_unique(completer: &HistoryCompleter, line: &str) -> Result<Vec<HistoryItem>> {
+    let parsed = parse_selection_char(line, SELECTION_CHAR);
+    let matcher = Matcher::new(completer, parsed.remainder);
+    let mut seen_matching_command_lines = HashSet::new();
+    let mut values = Vec::new();
+    for value in pinned_matching(completer, &matcher) {
+        if seen_matching_command_lines.insert(value.command_line.clone()) {
+            values.push(value);
+        }
+    }
+    completer
+        .history
+        .search_streaming(matcher.query(), &mut |value| {
+            if matcher.matches(&value)
+                && seen_matching_command_lines.insert(value.command_line.clone())
+            {
+                values.push(value);
+            }
+            ControlFlow::Continue(())
+        })?;
+    Ok(values)
 }
 
 impl<'menu> Completer for HistoryCompleter<'menu> {
@@ -35,21 +110,87 @@ impl<'menu> Completer for HistoryCompleter<'menu> {
         match search_unique(self, line) {
             Err(_) => vec![],
             Ok(search_results) => search_results
+                .into_iter()
                 .map(|value| self.create_suggestion(line, pos, value.command_line.deref()))
                 .collect(),
         }
     }
 
-    // TODO: Implement `fn partial_complete()`
+    // Reads the history page by page until the requested unique entries are found, so
+    // that only the entries up to the shown page of the menu are loaded
+    fn partial_complete(
+        &mut self,
+        line: &str,
+        pos: usize,
+        start: usize,
+        offset: usize,
+    ) -> Vec<Suggestion> {
+        let parsed = parse_selection_char(line, SELECTION_CHAR);
+        let matcher = Matcher::new(self, parsed.remainder);
+        let mut seen_matching_command_lines = HashSet::new();
+        let mut suggestions = Vec::new();
+        for value in pinned_matching(self, &matcher) {
+            if seen_matching_command_lines.insert(value.command_line.clone())
+                && seen_matching_command_lines.len() > start
+                && suggestions.len() < offset
+            {
+                suggestions.push(self.create_suggestion(line, pos, &value.command_line));
+            }
+        }
+        let mut page = 0;
+        while suggestions.len() < offset {
+            let values = match self
+                .history
+                .search_paged(matcher.query(), page, HISTORY_PAGE_SIZE)
+            {
+                Ok(values) => values,
+                Err(_) => return vec![],
+            };
+            let is_last_page = values.len() < HISTORY_PAGE_SIZE;
+            for value in values.into_iter().filter(|value| matcher.matches(value)) {
+                if seen_matching_command_lines.insert(value.command_line.clone())
+                    && seen_matching_command_lines.len() > start
+                    && suggestions.len() < offset
+                {
+                    suggestions.push(self.create_suggestion(line, pos, &value.command_line));
+                }
+            }
+            if is_last_page {
+                break;
+            }
+            page += 1;
+        }
+        suggestions
+    }
 
     fn total_completions(&mut self, line: &str, _pos: usize) -> usize {
-        search_unique(self, line).map(|i| i.count()).unwrap_or(0)
+        let parsed = parse_selection_char(line, SELECTION_CHAR);
+        let matcher = Matcher::new(self, parsed.remainder);
+        let mut seen_matching_command_lines = HashSet::new();
+        let result = self
+            .history
+            .search_streaming(matcher.query(), &mut |value| {
+                if matcher.matches(&value) {
+                    seen_matching_command_lines.insert(value.command_line);
+                }
+                ControlFlow::Continue(())
+            });
+        result.map_or(0, |_| seen_matching_command_lines.len())
     }
 }
 
 impl<'menu> HistoryCompleter<'menu> {
     pub fn new(history: &'menu dyn History) -> Self {
-        Self(history)
+        Self {
+            history,
+            typo_tolerance: 0,
+        }
+    }
+
+    /// Match the entries with up to `max_typos` typos if none contains the line exactly
+    pub fn with_typo_tolerance(mut self, max_typos: usize) -> Self {
+        self.typo_tolerance = max_typos;
+        self
     }
 
     fn create_suggestion(&self, line: &str, pos: usize, value: &str) -> Suggestion {
@@ -65,6 +206,7 @@ impl<'menu> HistoryCompleter<'menu> {
             extra: None,
             span,
             append_whitespace: false,
+            link: None,
         }
     }
 }
@@ -133,6 +275,66 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn partial_complete_skips_duplicates_across_pages() -> Result<()> {
+        let mut history = FileBackedHistory::new(1000)?;
+        for i in 0..300 {
+            history.save(new_history_item(&format!("cmd {}", i % 150)))?;
+        }
+        let mut sut = HistoryCompleter::new(&history);
+
+        let page = sut.partial_complete("", 0, 145, 10);
+        let values: Vec<_> = page.iter().map(|s| s.value.as_str()).collect();
+
+        assert_eq!(values, ["cmd 4", "cmd 3", "cmd 2", "cmd 1", "cmd 0"]);
+        assert_eq!(sut.total_completions("", 0), 150);
+        Ok(())
+    }
+
+    #[test]
+    fn pinned_entries_come_first() -> Result<()> {
+        let mut history = FileBackedHistory::new(1000)?;
+        let pinned = history.save(new_history_item("git stash drop"))?;
+        for command_line in ["ls", "git status", "git stash drop", "git push"] {
+            history.save(new_history_item(command_line))?;
+        }
+        history.set_pinned(pinned.id.unwrap(), true)?;
+        let mut sut = HistoryCompleter::new(&history);
+
+        let values: Vec<_> = sut
+            .complete("git", 3)
+            .into_iter()
+            .map(|suggestion| suggestion.value)
+            .collect();
+        assert_eq!(values, ["git stash drop", "git push", "git status"]);
+
+        let page = sut.partial_complete("git", 3, 1, 2);
+        let values: Vec<_> = page.iter().map(|s| s.value.as_str()).collect();
+        assert_eq!(values, ["git push", "git status"]);
+        assert_eq!(sut.total_completions("git", 3), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn typos_are_tolerated_if_nothing_matches_exactly() -> Result<()> {
+        let mut history = FileBackedHistory::new(1000)?;
+        for command_line in ["git status", "ls", "git stash", "git status --short"] {
+            history.save(new_history_item(command_line))?;
+        }
+        let mut sut = HistoryCompleter::new(&history).with_typo_tolerance(2);
+
+        let values: Vec<_> = sut
+            .complete("gti sttaus", 10)
+            .into_iter()
+            .map(|suggestion| suggestion.value)
+            .collect();
+        assert_eq!(values, ["git status --short", "git status"]);
+        assert_eq!(sut.total_completions("gti sttaus", 10), 2);
+        assert_eq!(sut.partial_complete("gti sttaus", 10, 1, 5).len(), 1);
+        assert_eq!(sut.total_completions("git st", 6), 3);
+        Ok(())
+    }
+
     #[rstest]
     #[case(vec![], "any", vec![])]
     #[case(vec!["old match","recent match","between","recent match"], "match", vec!["recent match","old match"])]
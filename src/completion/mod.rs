@@ -1,6 +1,13 @@
 mod base;
+mod cache;
 mod default;
 pub(crate) mod history;
+mod sort;
+pub(crate) mod usage;
 
-pub use base::{Completer, Span, Suggestion};
+pub use base::{Completer, CompletionError, Span, Suggestion};
+pub(crate) use cache::CompletionCache;
+pub use cache::CompletionCacheInvalidator;
 pub use default::DefaultCompleter;
+pub use sort::{SuggestionComparator, SuggestionSort};
+pub use usage::{CompletionUsage, CompletionUsageEntry};
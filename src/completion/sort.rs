@@ -0,0 +1,190 @@
+use std::{cmp::Ordering, ops::Range, sync::Arc};
+
+use super::usage::CompletionUsage;
+use crate::{Completer, CompletionError, Suggestion};
+
+/// Compares two suggestions for [`SuggestionSort::Custom`]
+pub type SuggestionComparator = Arc<dyn Fn(&Suggestion, &Suggestion) -> Ordering + Send + Sync>;
+
+/// Order of the suggestions shown by a menu, set with [`crate::MenuBuilder::with_sort`]
+///
+/// Sorting is stable: suggestions that compare equal keep the order of the completer.
+#[derive(Clone, Default)]
+pub enum SuggestionSort {
+    /// The order of the completer
+    #[default]
+    Completer,
+    /// Alphabetical by value, ignoring case
+    Alphabetical,
+    /// Shortest value first
+    Length,
+    /// The values picked most often and most recently first, as learned by the
+    /// [`crate::CompletionUsage`] of the editor
+    Frecency,
+    /// Ordered by the given comparator
+    Custom(SuggestionComparator),
+}
+
+impl SuggestionSort {
+    /// Sorts the `suggestions` for the cursor at `pos` in `line`
+    pub(crate) fn sort(
+        &self,
+        suggestions: &mut [Suggestion],
+        usage: &CompletionUsage,
+        line: &str,
+        pos: usize,
+    ) {
+        match self {
+            Self::Completer => {}
+            Self::Alphabetical => {
+                suggestions.sort_by_cached_key(|suggestion| suggestion.value.to_lowercase());
+            }
+            Self::Length => suggestions.sort_by_key(|suggestion| suggestion.value.chars().count()),
+            Self::Frecency => {
+                let score = usage.scorer();
+                let score = |suggestion: &Suggestion| {
+                    let context = line.get(suggestion.span.start..pos).unwrap_or_default();
+                    score(context, &suggestion.value)
+                };
+                suggestions.sort_by(|a, b| score(b).total_cmp(&score(a)));
+            }
+            Self::Custom(compare) => suggestions.sort_by(|a, b| compare(a, b)),
+        }
+    }
+
+    /// `completer` with its suggestions in this order
+    pub(crate) fn wrap<'a>(
+        &'a self,
+        completer: &'a mut dyn Completer,
+        usage: &'a CompletionUsage,
+    ) -> SortedCompleter<'a> {
+        SortedCompleter {
+            sort: self,
+            completer,
+            usage,
+        }
+    }
+}
+
+/// A [`Completer`] returning the suggestions of another one in the order of a
+/// [`SuggestionSort`]
+pub(crate) struct SortedCompleter<'a> {
+    sort: &'a SuggestionSort,
+    completer: &'a mut dyn Completer,
+    usage: &'a CompletionUsage,
+}
+
+impl<'a> Completer for SortedCompleter<'a> {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let mut suggestions = self.completer.complete(line, pos);
+        self.sort.sort(&mut suggestions, self.usage, line, pos);
+        suggestions
+    }
+
+    fn complete_with_base_ranges(
+        &mut self,
+        line: &str,
+        pos: usize,
+    ) -> (Vec<Suggestion>, Vec<Range<usize>>) {
+        let (mut suggestions, ranges) = self.completer.complete_with_base_ranges(line, pos);
+        self.sort.sort(&mut suggestions, self.usage, line, pos);
+        (suggestions, ranges)
+    }
+
+    fn try_complete_with_base_ranges(
+        &mut self,
+        line: &str,
+        pos: usize,
+    ) -> Result<(Vec<Suggestion>, Vec<Range<usize>>), CompletionError> {
+        let (mut suggestions, ranges) = self.completer.try_complete_with_base_ranges(line, pos)?;
+        self.sort.sort(&mut suggestions, self.usage, line, pos);
+        Ok((suggestions, ranges))
+    }
+
+    fn partial_complete(
+        &mut self,
+        line: &str,
+        pos: usize,
+        start: usize,
+        offset: usize,
+    ) -> Vec<Suggestion> {
+        if let SuggestionSort::Completer = self.sort {
+            return self.completer.partial_complete(line, pos, start, offset);
+        }
+        // A page of the sorted suggestions needs all of them
+        self.complete(line, pos)
+            .into_iter()
+            .skip(start)
+            .take(offset)
+            .collect()
+    }
+
+    fn total_completions(&mut self, line: &str, pos: usize) -> usize {
+        self.completer.total_completions(line, pos)
+    }
+
+    fn is_pending(&self) -> bool {
+        self.completer.is_pending()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Span;
+    use pretty_assertions::assert_eq;
+
+    fn suggestions(values: &[&str]) -> Vec<Suggestion> {
+        values
+            .iter()
+            .map(|value| Suggestion {
+                value: value.to_string(),
+                span: Span::new(0, 1),
+                ..Suggestion::default()
+            })
+            .collect()
+    }
+
+    fn sorted(sort: SuggestionSort, usage: &CompletionUsage) -> Vec<String> {
+        let mut values = suggestions(&["commit", "Checkout", "cd", "cherry-pick", "cp"]);
+        sort.sort(&mut values, usage, "c", 1);
+        values
+            .into_iter()
+            .map(|suggestion| suggestion.value)
+            .collect()
+    }
+
+    #[test]
+    fn sorts_by_each_strategy() {
+        let usage = CompletionUsage::default();
+        usage.record("c", "cp").unwrap();
+        usage.record("c", "commit").unwrap();
+        usage.record("co", "commit").unwrap();
+        usage.record("c", "cd").unwrap();
+        usage.record("c", "cd").unwrap();
+
+        assert_eq!(
+            sorted(SuggestionSort::Completer, &usage),
+            ["commit", "Checkout", "cd", "cherry-pick", "cp"]
+        );
+        assert_eq!(
+            sorted(SuggestionSort::Alphabetical, &usage),
+            ["cd", "Checkout", "cherry-pick", "commit", "cp"]
+        );
+        assert_eq!(
+            sorted(SuggestionSort::Length, &usage),
+            ["cd", "cp", "commit", "Checkout", "cherry-pick"]
+        );
+        assert_eq!(
+            sorted(SuggestionSort::Frecency, &usage),
+            ["cd", "commit", "cp", "Checkout", "cherry-pick"]
+        );
+        assert_eq!(
+            sorted(
+                SuggestionSort::Custom(Arc::new(|a, b| b.value.cmp(&a.value))),
+                &usage
+            ),
+            ["cp", "commit", "cherry-pick", "cd", "Checkout"]
+        );
+    }
+}
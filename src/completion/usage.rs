@@ -0,0 +1,392 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// Entries kept in a store, the ones with the lowest scores are dropped beyond that
+const MAX_ENTRIES: usize = 2000;
+
+/// Learns which suggestions are picked from menus, to rank them higher the next time
+///
+/// Every accepted suggestion is recorded with the text it replaced, its context: picking
+/// `checkout` after typing `ch` boosts it most when `ch` is typed again. Menus sorted by
+/// [`crate::SuggestionSort::Frecency`] and a [`crate::DefaultHinter`] given the store
+/// with [`crate::DefaultHinter::with_completion_usage`] use what was learned.
+///
+/// Clones share the same store, like one passed to
+/// [`crate::Reedline::with_completion_usage`] and the one of a hinter. Without a file the
+/// store is forgotten when the application exits.
+///
+/// ```rust
+/// use reedline::{CompletionUsage, DefaultHinter, Reedline};
+///
+/// let usage = CompletionUsage::new();
+/// let mut line_editor = Reedline::create()
+///     .with_completion_usage(usage.clone())
+///     .with_hinter(Box::new(DefaultHinter::default().with_completion_usage(usage)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CompletionUsage {
+    store: Arc<Mutex<UsageStore>>,
+}
+
+/// A suggestion picked in a context, as returned by [`CompletionUsage::export`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionUsageEntry {
+    /// The text the suggestion replaced when it was picked
+    pub context: String,
+    /// The value of the suggestion
+    pub value: String,
+    /// How often it was picked in this context
+    pub count: u32,
+    /// When it was picked last
+    pub last_used: SystemTime,
+}
+
+#[derive(Debug, Default)]
+struct UsageStore {
+    // Counts by value, then by context
+    entries: HashMap<String, HashMap<String, UsageEntry>>,
+    file: Option<PathBuf>,
+    paused: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct UsageEntry {
+    count: u32,
+    // Seconds since the unix epoch
+    last_used: u64,
+}
+
+impl CompletionUsage {
+    /// An empty store in memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store kept in `path`, with what was learned before if the file exists
+    ///
+    /// The file is rewritten after every recorded pick.
+    pub fn with_file(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let mut store = UsageStore::default();
+        match File::open(&path) {
+            Ok(file) => store.load(BufReader::new(file))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        store.file = Some(path);
+        Ok(Self {
+            store: Arc::new(Mutex::new(store)),
+        })
+    }
+
+    /// Stop (`false`) or resume (`true`) learning, e.g. while typing something private
+    ///
+    /// What was learned before is still used.
+    pub fn set_learning(&self, learning: bool) {
+        self.lock().paused = !learning;
+    }
+
+    /// Whether picked suggestions are recorded
+    pub fn is_learning(&self) -> bool {
+        !self.lock().paused
+    }
+
+    /// Forget everything that was learned, also in the file of the store
+    pub fn clear(&self) -> io::Result<()> {
+        let mut store = self.lock();
+        store.entries.clear();
+        store.save()
+    }
+
+    /// Everything that was learned, e.g. to move it to another store
+    pub fn export(&self) -> Vec<CompletionUsageEntry> {
+        let store = self.lock();
+        let mut exported: Vec<_> = store
+            .entries
+            .iter()
+            .flat_map(|(value, contexts)| {
+                contexts
+                    .iter()
+                    .map(move |(context, entry)| CompletionUsageEntry {
+                        context: context.clone(),
+                        value: value.clone(),
+                        count: entry.count,
+                        last_used: UNIX_EPOCH + Duration::from_secs(entry.last_used),
+                    })
+            })
+            .collect();
+        exported.sort_by(|a, b| (&a.value, &a.context).cmp(&(&b.value, &b.context)));
+        exported
+    }
+
+    /// Remember that `value` was picked just now, replacing `context`
+    pub(crate) fn record(&self, context: &str, value: &str) -> io::Result<()> {
+        let mut store = self.lock();
+        if store.paused {
+            return Ok(());
+        }
+        store.record_at(context, value, now());
+        store.save()
+    }
+
+    /// Scores of values picked often and recently, all at the same point in time
+    ///
+    /// Picks in the given context count twice.
+    pub(crate) fn scorer(&self) -> impl Fn(&str, &str) -> f64 + '_ {
+        let store = self.lock();
+        let now = now();
+        move |context, value| store.frecency_at(context, value, now)
+    }
+
+    /// The highest scored value picked after typing a prefix of `token` that `token` is a
+    /// prefix of itself
+    pub(crate) fn best_completion(&self, token: &str) -> Option<String> {
+        let store = self.lock();
+        let now = now();
+        store
+            .entries
+            .iter()
+            .filter(|(value, contexts)| {
+                value.len() > token.len()
+                    && value.starts_with(token)
+                    && contexts
+                        .keys()
+                        .any(|context| token.starts_with(context.as_str()))
+            })
+            .map(|(value, _)| (store.frecency_at(token, value, now), value))
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, value)| value.clone())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, UsageStore> {
+        // A panic while holding the lock cannot leave the store half updated
+        self.store.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl UsageStore {
+    fn record_at(&mut self, context: &str, value: &str, time: u64) {
+        let entry = self
+            .entries
+            .entry(value.to_string())
+            .or_default()
+            .entry(context.to_string())
+            .or_insert(UsageEntry {
+                count: 0,
+                last_used: time,
+            });
+        entry.count = entry.count.saturating_add(1);
+        entry.last_used = entry.last_used.max(time);
+
+        if self.entries.values().map(HashMap::len).sum::<usize>() > MAX_ENTRIES {
+            self.drop_lowest_score(time, (context, value));
+        }
+    }
+
+    // Drops the entry with the lowest score, other than the one just recorded
+    fn drop_lowest_score(&mut self, now: u64, recorded: (&str, &str)) {
+        let lowest = self
+            .entries
+            .iter()
+            .flat_map(|(value, contexts)| {
+                contexts
+                    .iter()
+                    .filter(move |(context, _)| (context.as_str(), value.as_str()) != recorded)
+                    .map(move |(context, entry)| (weighted(entry, now), value, context))
+            })
+            .min_by(|(a, ..), (b, ..)| a.total_cmp(b))
+            .map(|(_, value, context)| (value.clone(), context.clone()));
+        if let Some((value, context)) = lowest {
+            if let Some(contexts) = self.entries.get_mut(&value) {
+                contexts.remove(&context);
+                if contexts.is_empty() {
+                    self.entries.remove(&value);
+                }
+            }
+        }
+    }
+
+    /// The number of times `value` was picked, weighted by how long ago that was
+    fn frecency_at(&self, context: &str, value: &str, now: u64) -> f64 {
+        self.entries.get(value).map_or(0.0, |contexts| {
+            let all: f64 = contexts.values().map(|entry| weighted(entry, now)).sum();
+            let in_context = contexts
+                .get(context)
+                .map_or(0.0, |entry| weighted(entry, now));
+            all + in_context
+        })
+    }
+
+    fn load(&mut self, reader: impl BufRead) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            let mut fields = line.split('\t');
+            let entry = match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                (Some(count), Some(last_used), Some(context), Some(value)) => count
+                    .parse()
+                    .ok()
+                    .zip(last_used.parse().ok())
+                    .map(|(count, last_used)| (count, last_used, context, value)),
+                _ => None,
+            };
+            let (count, last_used, context, value) = entry.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid completion usage entry")
+            })?;
+            self.entries
+                .entry(unescape(value))
+                .or_default()
+                .insert(unescape(context), UsageEntry { count, last_used });
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.file {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        for (value, contexts) in &self.entries {
+            for (context, entry) in contexts {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}",
+                    entry.count,
+                    entry.last_used,
+                    escape(context),
+                    escape(value)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn weighted(entry: &UsageEntry, now: u64) -> f64 {
+    let weight = match now.saturating_sub(entry.last_used) {
+        age if age < HOUR => 4.0,
+        age if age < DAY => 2.0,
+        age if age < WEEK => 1.0,
+        _ => 0.5,
+    };
+    f64::from(entry.count) * weight
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+pub(crate) fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn recent_picks_in_the_same_context_weigh_more() {
+        let mut store = UsageStore::default();
+        let now = 100 * WEEK;
+        store.record_at("c", "old", now - 2 * WEEK);
+        store.record_at("c", "old", now - 2 * WEEK);
+        store.record_at("c", "old", now - 2 * WEEK);
+        store.record_at("c", "new", now - 10);
+        store.record_at("n", "new", now - 10);
+
+        assert_eq!(store.frecency_at("c", "old", now), 3.0);
+        assert_eq!(store.frecency_at("x", "old", now), 1.5);
+        assert_eq!(store.frecency_at("n", "new", now), 12.0);
+        assert_eq!(store.frecency_at("c", "never", now), 0.0);
+    }
+
+    #[test]
+    fn the_store_is_limited() {
+        let mut store = UsageStore::default();
+        for i in 0..MAX_ENTRIES {
+            store.record_at("", &i.to_string(), 1000);
+        }
+        store.record_at("", "0", 1000);
+        store.record_at("", "new", 1000);
+
+        assert_eq!(store.entries.len(), MAX_ENTRIES);
+        assert!(store.entries.contains_key("0"));
+        assert!(store.entries.contains_key("new"));
+    }
+
+    #[test]
+    fn entries_survive_a_round_trip() {
+        let mut store = UsageStore::default();
+        store.record_at("a\tb", "a\tb\\c\nd", 42);
+        store.record_at("", "plain", 7);
+        let mut written = Vec::new();
+        store.write(&mut written).unwrap();
+
+        let mut loaded = UsageStore::default();
+        loaded.load(written.as_slice()).unwrap();
+        assert_eq!(loaded.entries, store.entries);
+        assert!(loaded.load("1\tnot a time\t\tx".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn learning_can_be_paused_exported_and_cleared() {
+        let usage = CompletionUsage::new();
+        usage.record("ch", "checkout").unwrap();
+        usage.set_learning(false);
+        usage.record("co", "commit").unwrap();
+        assert!(!usage.is_learning());
+
+        let exported = usage.export();
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].context, "ch");
+        assert_eq!(exported[0].value, "checkout");
+        assert_eq!(exported[0].count, 1);
+
+        assert_eq!(usage.best_completion("che"), Some("checkout".to_string()));
+        assert_eq!(usage.best_completion("c"), None);
+        assert_eq!(usage.best_completion("checkout"), None);
+
+        usage.clear().unwrap();
+        assert_eq!(usage.export(), vec![]);
+    }
+}
@@ -0,0 +1,65 @@
+/// Callback deciding whether auto-pairing is active at a byte position of the buffer
+pub type AutoPairFilter = Box<dyn Fn(&str, usize) -> bool + Send>;
+
+/// Opt-in configuration to automatically insert and skip matching pairs of characters
+///
+/// - Typing an opening character inserts the closing one behind the cursor
+/// - Typing a closing character in front of the same character moves over it
+/// - Backspace between an empty pair deletes both characters
+pub struct AutoPairs {
+    pairs: Vec<(char, char)>,
+    filter: Option<AutoPairFilter>,
+}
+
+impl Default for AutoPairs {
+    fn default() -> Self {
+        Self::new(vec![
+            ('(', ')'),
+            ('[', ']'),
+            ('{', '}'),
+            ('"', '"'),
+            ('\'', '\''),
+        ])
+    }
+}
+
+impl AutoPairs {
+    /// Create an auto-pair configuration from a set of `(opening, closing)` characters
+    pub fn new(pairs: Vec<(char, char)>) -> Self {
+        Self {
+            pairs,
+            filter: None,
+        }
+    }
+
+    /// A builder that sets a callback to disable auto-pairing at certain positions,
+    /// e.g. inside string literals
+    ///
+    /// The callback receives the buffer and the cursor position and returns `false`
+    /// where no closing character should be inserted
+    #[must_use]
+    pub fn with_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&str, usize) -> bool + Send + 'static,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    pub(crate) fn is_enabled(&self, buffer: &str, pos: usize) -> bool {
+        self.filter
+            .as_ref()
+            .map_or(true, |filter| filter(buffer, pos))
+    }
+
+    pub(crate) fn closing_for(&self, c: char) -> Option<char> {
+        self.pairs
+            .iter()
+            .find(|(open, _)| *open == c)
+            .map(|(_, close)| *close)
+    }
+
+    pub(crate) fn is_closing(&self, c: char) -> bool {
+        self.pairs.iter().any(|(_, close)| *close == c)
+    }
+}
@@ -6,10 +6,12 @@ pub trait Clipboard: Send {
 
     fn get(&mut self) -> (String, ClipboardMode);
 
+    #[allow(dead_code)]
     fn clear(&mut self) {
         self.set("", ClipboardMode::Normal);
     }
 
+    #[allow(dead_code)]
     fn len(&mut self) -> usize {
         self.get().0.len()
     }
@@ -36,6 +36,11 @@ where
         &self.internal_list[self.index]
     }
 
+    /// How many times an undo can go back
+    pub(super) fn undo_depth(&self) -> usize {
+        self.index
+    }
+
     /// Insert a new entry to the undo stack.
     /// NOTE: (IMP): If we have hit undo a few times then discard all the other values that come
     /// after the current point
@@ -49,15 +54,25 @@ where
         self.index += 1;
     }
 
+    /// The last entry, if the stack currently points to it
+    pub(super) fn latest_mut(&mut self) -> Option<&mut T> {
+        if self.index > 0 && self.index == self.internal_list.len() - 1 {
+            self.internal_list.last_mut()
+        } else {
+            None
+        }
+    }
+
     /// Reset the stack to the initial state
     pub(super) fn reset(&mut self) {
         self.index = 0;
         self.internal_list = vec![T::default()];
     }
 
-    /// Return the entry currently being pointed to
-    pub(super) fn current(&mut self) -> &T {
-        &self.internal_list[self.index]
+    /// Reset the stack after passing every entry to `zeroize`
+    pub(super) fn reset_zeroized(&mut self, zeroize: impl FnMut(&mut T)) {
+        self.internal_list.iter_mut().for_each(zeroize);
+        self.reset();
     }
 }
 
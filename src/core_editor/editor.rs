@@ -1,9 +1,12 @@
-use super::{edit_stack::EditStack, Clipboard, ClipboardMode, LineBuffer};
+use super::{
+    edit_stack::EditStack, AutoPairs, Clipboard, ClipboardMode, LineBuffer, ReadOnlyRanges,
+};
 #[cfg(feature = "system_clipboard")]
 use crate::core_editor::get_system_clipboard;
 use crate::enums::{EditType, UndoBehavior};
 use crate::{core_editor::get_local_clipboard, EditCommand};
-use std::ops::DerefMut;
+use regex::Regex;
+use std::ops::{DerefMut, Range};
 
 /// Stateful editor executing changes to the underlying [`LineBuffer`]
 ///
@@ -15,8 +18,19 @@ pub struct Editor {
     #[cfg(feature = "system_clipboard")]
     system_clipboard: Box<dyn Clipboard>,
     edit_stack: EditStack<LineBuffer>,
+    // The latest entry of the edit stack is a placeholder for the current line buffer, it is
+    // only copied once the undo set is completed or the stack is used
+    undo_snapshot_pending: bool,
     last_undo_behavior: UndoBehavior,
     selection_anchor: Option<usize>,
+    // The anchor and the cursor of the selection before the current or the last one
+    last_selection: Option<(usize, usize)>,
+    indent_unit: String,
+    auto_pairs: Option<AutoPairs>,
+    comment_prefix: String,
+    // The last pattern of a regex motion, compiled, or `None` if it isn't a valid regex
+    last_regex: Option<(String, Option<Regex>)>,
+    read_only: ReadOnlyRanges,
 }
 
 impl Default for Editor {
@@ -27,8 +41,15 @@ impl Default for Editor {
             #[cfg(feature = "system_clipboard")]
             system_clipboard: get_system_clipboard(),
             edit_stack: EditStack::new(),
+            undo_snapshot_pending: false,
             last_undo_behavior: UndoBehavior::CreateUndoPoint,
             selection_anchor: None,
+            last_selection: None,
+            indent_unit: "    ".to_string(),
+            auto_pairs: None,
+            comment_prefix: "#".to_string(),
+            last_regex: None,
+            read_only: ReadOnlyRanges::default(),
         }
     }
 }
@@ -42,11 +63,101 @@ impl Editor {
     /// Set the current [`LineBuffer`].
     /// [`UndoBehavior`] specifies how this change should be reflected on the undo stack.
     pub(crate) fn set_line_buffer(&mut self, line_buffer: LineBuffer, undo_behavior: UndoBehavior) {
-        self.line_buffer = line_buffer;
-        self.update_undo_state(undo_behavior);
+        let before = (!self.read_only.is_empty()).then(|| self.line_buffer.clone());
+        self.record_change(undo_behavior, |editor| editor.line_buffer = line_buffer);
+        if let Some(before) = before {
+            self.keep_read_only(before);
+        }
+    }
+
+    /// Protect the byte `ranges` of the buffer from edits, see [`crate::Reedline::set_read_only`]
+    pub(crate) fn set_read_only(&mut self, ranges: impl IntoIterator<Item = Range<usize>>) {
+        self.read_only = ReadOnlyRanges::new(ranges, self.get_buffer());
+        let cursor = self.insertion_point();
+        self.line_buffer
+            .set_insertion_point(self.read_only.escape(cursor, cursor));
+    }
+
+    /// The fields of the buffer, the text between the read-only ranges
+    pub(crate) fn fields(&self) -> Vec<Range<usize>> {
+        self.read_only.fields(self.get_buffer().len())
+    }
+
+    fn select_field(&mut self, forward: bool) {
+        let fields = self.fields();
+        let cursor = self.insertion_point();
+        let current = fields
+            .iter()
+            .position(|field| field.contains(&cursor) || field.end == cursor);
+        let index = match (current, forward) {
+            (Some(index), true) => (index + 1) % fields.len(),
+            (Some(index), false) => index.checked_sub(1).unwrap_or(fields.len() - 1),
+            (None, true) => fields
+                .iter()
+                .position(|field| field.start > cursor)
+                .unwrap_or(0),
+            (None, false) => fields
+                .iter()
+                .rposition(|field| field.end < cursor)
+                .unwrap_or(fields.len() - 1),
+        };
+        let field = fields[index].clone();
+        self.selection_anchor = Some(field.start);
+        self.line_buffer.set_insertion_point(field.end);
+    }
+
+    // Undo a change that touched a read-only range, or move the ranges along with it, and
+    // keep the cursor out of them
+    fn keep_read_only(&mut self, before: LineBuffer) {
+        if !self.read_only.follow_change(
+            before.get_buffer(),
+            self.line_buffer.get_buffer(),
+            self.insertion_point(),
+        ) {
+            self.line_buffer = before;
+            return;
+        }
+        let cursor = self
+            .read_only
+            .escape(self.insertion_point(), before.insertion_point());
+        self.line_buffer.set_insertion_point(cursor);
     }
 
     pub(crate) fn run_edit_command(&mut self, command: &EditCommand) {
+        if self.read_only.is_empty() {
+            self.apply_edit_command(command);
+        } else {
+            let before = self.line_buffer.clone();
+            self.apply_edit_command(command);
+            self.keep_read_only(before);
+        }
+    }
+
+    fn apply_edit_command(&mut self, command: &EditCommand) {
+        let new_undo_behavior = match (command, command.edit_type()) {
+            (_, EditType::MoveCursor { .. }) => UndoBehavior::MoveCursor,
+            (EditCommand::InsertChar(c), EditType::EditText) => UndoBehavior::InsertCharacter(*c),
+            (EditCommand::Delete, EditType::EditText) => {
+                let deleted_char = self.line_buffer.grapheme_right().chars().next();
+                UndoBehavior::Delete(deleted_char)
+            }
+            (EditCommand::Backspace, EditType::EditText) => {
+                let deleted_char = self.line_buffer.grapheme_left().chars().next();
+                UndoBehavior::Backspace(deleted_char)
+            }
+            (_, EditType::UndoRedo) => UndoBehavior::UndoRedo,
+            (_, _) => UndoBehavior::CreateUndoPoint,
+        };
+        self.record_change(new_undo_behavior, |editor| {
+            editor.dispatch_edit_command(command);
+        });
+    }
+
+    fn dispatch_edit_command(&mut self, command: &EditCommand) {
+        if let Some(anchor) = self.selection_anchor {
+            self.last_selection = Some((anchor, self.insertion_point()));
+        }
+
         match command {
             EditCommand::MoveToStart { select } => self.move_to_start(*select),
             EditCommand::MoveToLineStart { select } => self.move_to_line_start(*select),
@@ -66,6 +177,18 @@ impl Editor {
             }
             EditCommand::MoveWordRightEnd { select } => self.move_word_right_end(*select),
             EditCommand::MoveBigWordRightEnd { select } => self.move_big_word_right_end(*select),
+            EditCommand::MoveParagraphLeft { select } => {
+                self.move_to_position(self.line_buffer.paragraph_left_index(), *select)
+            }
+            EditCommand::MoveParagraphRight { select } => {
+                self.move_to_position(self.line_buffer.paragraph_right_index(), *select)
+            }
+            EditCommand::MoveSentenceLeft { select } => {
+                self.move_to_position(self.line_buffer.sentence_left_index(), *select)
+            }
+            EditCommand::MoveSentenceRight { select } => {
+                self.move_to_position(self.line_buffer.sentence_right_index(), *select)
+            }
             EditCommand::InsertChar(c) => self.insert_char(*c),
             EditCommand::Complete => {}
             EditCommand::InsertString(str) => self.insert_str(str),
@@ -80,6 +203,7 @@ impl Editor {
             EditCommand::Clear => self.line_buffer.clear(),
             EditCommand::ClearToLineEnd => self.line_buffer.clear_to_line_end(),
             EditCommand::CutCurrentLine => self.cut_current_line(),
+            EditCommand::CutCurrentParagraph => self.cut_current_paragraph(),
             EditCommand::CutFromStart => self.cut_from_start(),
             EditCommand::CutFromLineStart => self.cut_from_line_start(),
             EditCommand::CutToEnd => self.cut_from_end(),
@@ -98,6 +222,15 @@ impl Editor {
             EditCommand::CapitalizeChar => self.line_buffer.capitalize_char(),
             EditCommand::SwapWords => self.line_buffer.swap_words(),
             EditCommand::SwapGraphemes => self.line_buffer.swap_graphemes(),
+            EditCommand::TransposeWords => self.line_buffer.transpose_words(),
+            EditCommand::TransposeGraphemes(count) => self.line_buffer.transpose_graphemes(*count),
+            EditCommand::MoveLineUp => self.line_buffer.swap_line_up(),
+            EditCommand::MoveLineDown => self.line_buffer.swap_line_down(),
+            EditCommand::DuplicateLine => self.line_buffer.duplicate_line(),
+            EditCommand::JoinLines => self.line_buffer.join_lines(),
+            EditCommand::IndentLine => self.indent_lines(),
+            EditCommand::DedentLine => self.dedent_lines(),
+            EditCommand::ToggleComment { prefix } => self.toggle_comment(prefix),
             EditCommand::Undo => self.undo(),
             EditCommand::Redo => self.redo(),
             EditCommand::CutRightUntil(c) => self.cut_right_until_char(*c, false, true),
@@ -108,6 +241,10 @@ impl Editor {
             EditCommand::MoveRightBefore { c, select } => {
                 self.move_right_until_char(*c, true, true, *select)
             }
+            EditCommand::MoveToRegexMatch { pattern, select } => {
+                self.move_to_regex_match(pattern, *select)
+            }
+            EditCommand::CutToRegexMatch { pattern } => self.cut_to_regex_match(pattern),
             EditCommand::CutLeftUntil(c) => self.cut_left_until_char(*c, false, true),
             EditCommand::CutLeftBefore(c) => self.cut_left_until_char(*c, true, true),
             EditCommand::MoveLeftUntil { c, select } => {
@@ -116,7 +253,40 @@ impl Editor {
             EditCommand::MoveLeftBefore { c, select } => {
                 self.move_left_until_char(*c, true, true, *select)
             }
+            EditCommand::SwitchcaseChars(count) => self.line_buffer.switchcase_chars(*count),
+            EditCommand::SwitchcaseSelection => self.switchcase_selection(),
+            EditCommand::IncrementNumber => self.line_buffer.increment_number(1),
+            EditCommand::DecrementNumber => self.line_buffer.increment_number(-1),
+            EditCommand::MoveToCharAcrossLines {
+                c,
+                right: true,
+                before,
+                select,
+            } => self.move_right_until_char(*c, *before, false, *select),
+            EditCommand::MoveToCharAcrossLines {
+                c,
+                right: false,
+                before,
+                select,
+            } => self.move_left_until_char(*c, *before, false, *select),
+            EditCommand::CutToCharAcrossLines {
+                c,
+                right: true,
+                before,
+            } => self.cut_right_until_char(*c, *before, false),
+            EditCommand::CutToCharAcrossLines {
+                c,
+                right: false,
+                before,
+            } => self.cut_left_until_char(*c, *before, false),
             EditCommand::SelectAll => self.select_all(),
+            EditCommand::SelectNextField => self.select_field(true),
+            EditCommand::SelectPreviousField => self.select_field(false),
+            EditCommand::ReselectLast => {
+                if let Some((anchor, cursor)) = self.last_selection {
+                    self.set_selection(anchor, cursor);
+                }
+            }
             EditCommand::CutSelection => self.cut_selection_to_cut_buffer(),
             EditCommand::CopySelection => self.copy_selection_to_cut_buffer(),
             EditCommand::Paste => self.paste_cut_buffer(),
@@ -127,27 +297,16 @@ impl Editor {
             #[cfg(feature = "system_clipboard")]
             EditCommand::PasteSystem => self.paste_from_system(),
         }
-        if !matches!(command.edit_type(), EditType::MoveCursor { select: true }) {
+        // Indenting or commenting the selected lines keeps them selected to do it again
+        let keeps_selection = matches!(
+            command,
+            EditCommand::IndentLine | EditCommand::DedentLine | EditCommand::ToggleComment { .. }
+        );
+        if !keeps_selection && !matches!(command.edit_type(), EditType::MoveCursor { select: true })
+        {
             self.selection_anchor = None;
         }
         if let EditType::MoveCursor { select: true } = command.edit_type() {}
-
-        let new_undo_behavior = match (command, command.edit_type()) {
-            (_, EditType::MoveCursor { .. }) => UndoBehavior::MoveCursor,
-            (EditCommand::InsertChar(c), EditType::EditText) => UndoBehavior::InsertCharacter(*c),
-            (EditCommand::Delete, EditType::EditText) => {
-                let deleted_char = self.edit_stack.current().grapheme_right().chars().next();
-                UndoBehavior::Delete(deleted_char)
-            }
-            (EditCommand::Backspace, EditType::EditText) => {
-                let deleted_char = self.edit_stack.current().grapheme_left().chars().next();
-                UndoBehavior::Backspace(deleted_char)
-            }
-            (_, EditType::UndoRedo) => UndoBehavior::UndoRedo,
-            (_, _) => UndoBehavior::CreateUndoPoint,
-        };
-
-        self.update_undo_state(new_undo_behavior);
     }
     fn update_selection_anchor(&mut self, select: bool) {
         self.selection_anchor = if select {
@@ -163,13 +322,15 @@ impl Editor {
     }
 
     pub(crate) fn move_line_up(&mut self) {
-        self.line_buffer.move_line_up();
-        self.update_undo_state(UndoBehavior::MoveCursor);
+        self.record_change(UndoBehavior::MoveCursor, |editor| {
+            editor.line_buffer.move_line_up();
+        });
     }
 
     pub(crate) fn move_line_down(&mut self) {
-        self.line_buffer.move_line_down();
-        self.update_undo_state(UndoBehavior::MoveCursor);
+        self.record_change(UndoBehavior::MoveCursor, |editor| {
+            editor.line_buffer.move_line_down();
+        });
     }
 
     /// Get the text of the current [`LineBuffer`]
@@ -182,15 +343,25 @@ impl Editor {
     where
         F: FnOnce(&mut LineBuffer),
     {
-        self.update_undo_state(undo_behavior);
-        func(&mut self.line_buffer);
+        let before = (!self.read_only.is_empty()).then(|| self.line_buffer.clone());
+        self.record_change(undo_behavior, |editor| func(&mut editor.line_buffer));
+        if let Some(before) = before {
+            self.keep_read_only(before);
+        }
     }
 
     /// Set the text of the current [`LineBuffer`] given the specified [`UndoBehavior`]
-    /// Insertion point update to the end of the buffer.
+    /// Insertion point update to the end of the buffer. The read-only ranges are dropped.
     pub(crate) fn set_buffer(&mut self, buffer: String, undo_behavior: UndoBehavior) {
-        self.line_buffer.set_buffer(buffer);
-        self.update_undo_state(undo_behavior);
+        self.read_only = ReadOnlyRanges::default();
+        self.record_change(undo_behavior, |editor| {
+            editor.line_buffer.set_buffer(buffer)
+        });
+    }
+
+    /// How many changes an undo can revert
+    pub(crate) fn undo_depth(&self) -> usize {
+        self.edit_stack.undo_depth()
     }
 
     pub(crate) fn insertion_point(&self) -> usize {
@@ -215,6 +386,45 @@ impl Editor {
 
     pub(crate) fn reset_undo_stack(&mut self) {
         self.edit_stack.reset();
+        self.undo_snapshot_pending = false;
+    }
+
+    /// Replace the buffer with `buffer` as the only undo step, so undoing empties it
+    ///
+    /// `cursor` is moved to the end of the character it points into.
+    pub(crate) fn prefill(&mut self, buffer: String, cursor: usize) {
+        let mut cursor = cursor.min(buffer.len());
+        while !buffer.is_char_boundary(cursor) {
+            cursor += 1;
+        }
+        self.reset_undo_stack();
+        self.selection_anchor = None;
+        self.read_only = ReadOnlyRanges::default();
+        self.line_buffer.set_buffer(buffer);
+        self.line_buffer.set_insertion_point(cursor);
+        self.edit_stack.insert(self.line_buffer.clone());
+        self.last_undo_behavior = UndoBehavior::CreateUndoPoint;
+    }
+
+    /// Empty the buffer and the undo stack, overwriting their memory with zeros
+    pub(crate) fn zeroize(&mut self) {
+        self.line_buffer.zeroize();
+        self.edit_stack.reset_zeroized(LineBuffer::zeroize);
+        self.undo_snapshot_pending = false;
+        self.selection_anchor = None;
+    }
+
+    /// Reserve room in the buffer for `additional` more bytes
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.line_buffer.reserve(additional);
+    }
+
+    /// Use `cut_buffer` for cutting and pasting, returning the one used so far
+    pub(crate) fn replace_cut_buffer(
+        &mut self,
+        cut_buffer: Box<dyn Clipboard>,
+    ) -> Box<dyn Clipboard> {
+        std::mem::replace(&mut self.cut_buffer, cut_buffer)
     }
 
     pub(crate) fn move_to_start(&mut self, select: bool) {
@@ -238,27 +448,67 @@ impl Editor {
     }
 
     fn undo(&mut self) {
+        self.sync_undo_snapshot();
         let val = self.edit_stack.undo();
         self.line_buffer = val.clone();
     }
 
     fn redo(&mut self) {
+        self.sync_undo_snapshot();
         let val = self.edit_stack.redo();
         self.line_buffer = val.clone();
     }
 
-    pub(crate) fn update_undo_state(&mut self, undo_behavior: UndoBehavior) {
+    /// Make the `change` to the buffer with the given [`UndoBehavior`]
+    ///
+    /// Every change recorded on the undo stack goes through here, the current undo set is
+    /// completed first if the change starts a new one.
+    pub(crate) fn record_change<R>(
+        &mut self,
+        undo_behavior: UndoBehavior,
+        change: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        self.prepare_undo_state(&undo_behavior);
+        let result = change(self);
+        self.update_undo_state(undo_behavior);
+        result
+    }
+
+    fn update_undo_state(&mut self, undo_behavior: UndoBehavior) {
         if matches!(undo_behavior, UndoBehavior::UndoRedo) {
             self.last_undo_behavior = UndoBehavior::UndoRedo;
             return;
         }
-        if !undo_behavior.create_undo_point_after(&self.last_undo_behavior) {
+        if undo_behavior.create_undo_point_after(&self.last_undo_behavior) {
+            // Normally done by `prepare_undo_state` before the change
+            self.sync_undo_snapshot();
+            self.edit_stack.insert(LineBuffer::default());
+        } else if !self.undo_snapshot_pending {
             self.edit_stack.undo();
+            self.edit_stack.insert(LineBuffer::default());
         }
-        self.edit_stack.insert(self.line_buffer.clone());
+        // Combined changes only update the line buffer, keeping the cost of a keystroke
+        // independent of the buffer size
+        self.undo_snapshot_pending = true;
         self.last_undo_behavior = undo_behavior;
     }
 
+    /// Complete the current undo set before a change that starts a new one
+    fn prepare_undo_state(&mut self, undo_behavior: &UndoBehavior) {
+        if undo_behavior.create_undo_point_after(&self.last_undo_behavior) {
+            self.sync_undo_snapshot();
+        }
+    }
+
+    fn sync_undo_snapshot(&mut self) {
+        if self.undo_snapshot_pending {
+            if let Some(latest) = self.edit_stack.latest_mut() {
+                latest.clone_from(&self.line_buffer);
+            }
+            self.undo_snapshot_pending = false;
+        }
+    }
+
     fn cut_current_line(&mut self) {
         let deletion_range = self.line_buffer.current_line_range();
 
@@ -270,6 +520,17 @@ impl Editor {
         }
     }
 
+    fn cut_current_paragraph(&mut self) {
+        let deletion_range = self.line_buffer.current_paragraph_range();
+
+        let cut_slice = &self.line_buffer.get_buffer()[deletion_range.clone()];
+        if !cut_slice.is_empty() {
+            self.cut_buffer.set(cut_slice, ClipboardMode::Lines);
+            self.line_buffer.set_insertion_point(deletion_range.start);
+            self.line_buffer.clear_range(deletion_range);
+        }
+    }
+
     fn cut_from_start(&mut self) {
         let insertion_offset = self.line_buffer.insertion_point();
         if insertion_offset > 0 {
@@ -477,6 +738,42 @@ impl Editor {
         }
     }
 
+    /// Find the start of the next match of `pattern` after the insertion point.
+    /// Compiled patterns are cached, invalid patterns never match.
+    fn find_regex_match_right(&mut self, pattern: &str) -> Option<usize> {
+        let cached = matches!(&self.last_regex, Some((last, _)) if last == pattern);
+        if !cached {
+            self.last_regex = Some((pattern.to_string(), Regex::new(pattern).ok()));
+        }
+        let regex = self.last_regex.as_ref()?.1.as_ref()?;
+
+        let start = self.line_buffer.insertion_point() + self.line_buffer.grapheme_right().len();
+        if start >= self.line_buffer.get_buffer().len() {
+            return None;
+        }
+
+        regex
+            .find_at(self.line_buffer.get_buffer(), start)
+            .map(|found| found.start())
+    }
+
+    fn move_to_regex_match(&mut self, pattern: &str, select: bool) {
+        if let Some(index) = self.find_regex_match_right(pattern) {
+            self.move_to_position(index, select);
+        }
+    }
+
+    fn cut_to_regex_match(&mut self, pattern: &str) {
+        if let Some(index) = self.find_regex_match_right(pattern) {
+            let range = self.line_buffer.insertion_point()..index;
+            self.cut_buffer.set(
+                &self.line_buffer.get_buffer()[range.clone()],
+                ClipboardMode::Normal,
+            );
+            self.line_buffer.clear_range(range);
+        }
+    }
+
     fn cut_left_until_char(&mut self, c: char, before_char: bool, current_line: bool) {
         if let Some(index) = self.line_buffer.find_char_left(c, current_line) {
             // Saving the section of the string that will be deleted to be
@@ -536,6 +833,13 @@ impl Editor {
         }
     }
 
+    fn switchcase_selection(&mut self) {
+        if let Some((start, end)) = self.get_selection() {
+            self.line_buffer.switchcase_range(start..end);
+            self.line_buffer.set_insertion_point(start);
+        }
+    }
+
     fn cut_selection_to_cut_buffer(&mut self) {
         if let Some((start, end)) = self.get_selection() {
             let cut_slice = &self.line_buffer.get_buffer()[start..end];
@@ -560,6 +864,29 @@ impl Editor {
         }
     }
 
+    /// Select from `anchor` to `cursor`, moving the cursor there
+    ///
+    /// Positions past the end of the buffer or inside a character are moved to the end of
+    /// the buffer or of the character.
+    pub fn set_selection(&mut self, anchor: usize, cursor: usize) {
+        let buffer = self.line_buffer.get_buffer();
+        let clamp = |position: usize| {
+            let mut position = position.min(buffer.len());
+            while !buffer.is_char_boundary(position) {
+                position += 1;
+            }
+            position
+        };
+        let (anchor, cursor) = (clamp(anchor), clamp(cursor));
+        self.selection_anchor = Some(anchor);
+        self.line_buffer.set_insertion_point(cursor);
+    }
+
+    /// Drop the selection, keeping the cursor where it is
+    pub fn clear_selection(&mut self) {
+        self.selection_anchor = None;
+    }
+
     /// If a selection is active returns the selected range, otherwise None.
     /// The range is guaranteed to be ascending.
     pub fn get_selection(&self) -> Option<(usize, usize)> {
@@ -583,6 +910,9 @@ impl Editor {
         if self.selection_anchor.is_some() {
             self.delete_selection();
         } else {
+            if self.is_inside_empty_pair() {
+                self.line_buffer.delete_right_grapheme();
+            }
             self.line_buffer.delete_left_grapheme();
         }
     }
@@ -625,7 +955,64 @@ impl Editor {
 
     fn insert_char(&mut self, c: char) {
         self.delete_selection();
+        let auto_pairs = match &self.auto_pairs {
+            Some(auto_pairs) => auto_pairs,
+            None => {
+                self.line_buffer.insert_char(c);
+                return;
+            }
+        };
+
+        let mut right = self.line_buffer.grapheme_right().chars();
+        let next = match (right.next(), right.next()) {
+            (Some(next), None) => Some(next),
+            _ => None,
+        };
+        let previous = self.line_buffer.grapheme_left().chars().next_back();
+
+        if auto_pairs.is_closing(c) && next == Some(c) {
+            // Move over the already present closing character
+            self.line_buffer.move_right();
+            return;
+        }
+        let closing = auto_pairs.closing_for(c).filter(|closing| {
+            let followed_by_space_or_closing =
+                next.map_or(true, |n| n.is_whitespace() || auto_pairs.is_closing(n));
+            // Avoid pairing apostrophes and similar symmetric characters inside words
+            let after_word = *closing == c && previous.map_or(false, char::is_alphanumeric);
+            followed_by_space_or_closing
+                && !after_word
+                && auto_pairs.is_enabled(
+                    self.line_buffer.get_buffer(),
+                    self.line_buffer.insertion_point(),
+                )
+        });
         self.line_buffer.insert_char(c);
+        if let Some(closing) = closing {
+            self.line_buffer.insert_char(closing);
+            self.line_buffer.move_left();
+        }
+    }
+
+    /// Checks if the cursor sits between the two characters of an empty pair
+    fn is_inside_empty_pair(&self) -> bool {
+        match &self.auto_pairs {
+            Some(auto_pairs) => {
+                let mut left = self.line_buffer.grapheme_left().chars();
+                let mut right = self.line_buffer.grapheme_right().chars();
+                match (left.next(), left.next(), right.next(), right.next()) {
+                    (Some(open), None, Some(close), None) => {
+                        auto_pairs.closing_for(open) == Some(close)
+                    }
+                    _ => false,
+                }
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn set_auto_pairs(&mut self, auto_pairs: Option<AutoPairs>) {
+        self.auto_pairs = auto_pairs;
     }
 
     fn insert_str(&mut self, str: &str) {
@@ -638,6 +1025,75 @@ impl Editor {
         self.line_buffer.insert_newline();
     }
 
+    /// Insert a newline followed by `indent` as a single undo step
+    pub(crate) fn insert_newline_with_indent(&mut self, indent: &str) {
+        self.record_change(UndoBehavior::CreateUndoPoint, |editor| {
+            editor.delete_selection();
+            editor.line_buffer.insert_newline();
+            editor.line_buffer.insert_str(indent);
+        });
+    }
+
+    pub(crate) fn set_indent_unit(&mut self, indent_unit: &str) {
+        self.indent_unit = indent_unit.to_string();
+    }
+
+    fn selected_or_current_range(&self) -> std::ops::Range<usize> {
+        self.get_selection().map_or(
+            self.insertion_point()..self.insertion_point(),
+            |(start, end)| start..end,
+        )
+    }
+
+    // Run `edit` of the starts of the current or the selected lines, keeping the selection
+    // anchor at its place in the text of its line
+    fn edit_line_starts(&mut self, edit: impl FnOnce(&mut LineBuffer, std::ops::Range<usize>)) {
+        let range = self.selected_or_current_range();
+        let anchor = self.selection_anchor.map(|anchor| {
+            let buffer = self.line_buffer.get_buffer();
+            let start = buffer[..anchor].rfind('\n').map_or(0, |offset| offset + 1);
+            let line = buffer[..start].matches('\n').count();
+            (line, anchor - start, line_length(buffer, start))
+        });
+        edit(&mut self.line_buffer, range);
+        if let Some((line, column, length)) = anchor {
+            let buffer = self.line_buffer.get_buffer();
+            let start = match line {
+                0 => 0,
+                line => buffer
+                    .match_indices('\n')
+                    .nth(line - 1)
+                    .map_or(buffer.len(), |(offset, _)| offset + 1),
+            };
+            // The lines only change at their start
+            let column = (column + line_length(buffer, start)).saturating_sub(length);
+            self.selection_anchor = Some(start + column);
+        }
+    }
+
+    fn indent_lines(&mut self) {
+        let unit = self.indent_unit.clone();
+        self.edit_line_starts(|line_buffer, range| line_buffer.indent_lines(range, &unit));
+    }
+
+    fn dedent_lines(&mut self) {
+        let unit = self.indent_unit.clone();
+        self.edit_line_starts(|line_buffer, range| line_buffer.dedent_lines(range, &unit));
+    }
+
+    pub(crate) fn set_comment_prefix(&mut self, comment_prefix: &str) {
+        self.comment_prefix = comment_prefix.to_string();
+    }
+
+    fn toggle_comment(&mut self, prefix: &str) {
+        let prefix = if prefix.is_empty() {
+            self.comment_prefix.clone()
+        } else {
+            prefix.to_string()
+        };
+        self.edit_line_starts(|line_buffer, range| line_buffer.toggle_comment(range, &prefix));
+    }
+
     #[cfg(feature = "system_clipboard")]
     fn paste_from_system(&mut self) {
         self.delete_selection();
@@ -650,6 +1106,11 @@ impl Editor {
     }
 }
 
+// The length of the line starting at `start`, without its newline
+fn line_length(buffer: &str, start: usize) -> usize {
+    buffer[start..].find('\n').unwrap_or(buffer.len() - start)
+}
+
 fn insert_clipboard_content_before(line_buffer: &mut LineBuffer, clipboard: &mut dyn Clipboard) {
     match clipboard.get() {
         (content, ClipboardMode::Normal) => {
@@ -737,6 +1198,53 @@ mod test {
         assert_eq!(editor.get_buffer(), expected);
     }
 
+    #[rstest]
+    #[case(EditCommand::MoveToCharAcrossLines { c: 'c', right: true, before: false, select: false }, 8, "ab\nab\nabc")]
+    #[case(EditCommand::MoveToCharAcrossLines { c: 'b', right: false, before: true, select: false }, 2, "ab\nab\nabc")]
+    #[case(EditCommand::CutToCharAcrossLines { c: 'c', right: true, before: true }, 4, "ab\nac")]
+    #[case(EditCommand::CutToCharAcrossLines { c: 'b', right: false, before: false }, 1, "ab\nabc")]
+    fn test_char_search_across_lines(
+        #[case] command: EditCommand,
+        #[case] expected_position: usize,
+        #[case] expected: &str,
+    ) {
+        let mut editor = editor_with("ab\nab\nabc");
+        editor.line_buffer.set_insertion_point(4);
+        editor.run_edit_command(&command);
+        assert_eq!(editor.insertion_point(), expected_position);
+        assert_eq!(editor.get_buffer(), expected);
+    }
+
+    #[test]
+    fn test_switchcase_selection() {
+        let mut editor = editor_with("hello world");
+        editor.line_buffer.set_insertion_point(0);
+        editor.run_edit_command(&EditCommand::MoveWordRightEnd { select: true });
+        editor.run_edit_command(&EditCommand::MoveRight { select: true });
+        editor.run_edit_command(&EditCommand::SwitchcaseSelection);
+
+        assert_eq!(editor.get_buffer(), "HELLO world");
+        assert_eq!(editor.insertion_point(), 0);
+        assert_eq!(editor.get_selection(), None);
+    }
+
+    #[test]
+    fn test_reselect_last() {
+        let mut editor = editor_with("hello world");
+        editor.line_buffer.set_insertion_point(6);
+        editor.run_edit_command(&EditCommand::MoveToLineEnd { select: true });
+        editor.run_edit_command(&EditCommand::MoveToStart { select: false });
+        assert_eq!(editor.get_selection(), None);
+
+        editor.run_edit_command(&EditCommand::ReselectLast);
+        assert_eq!(editor.get_selection(), Some((6, 11)));
+        assert_eq!(editor.insertion_point(), 11);
+
+        editor.set_selection(20, 2);
+        assert_eq!(editor.get_selection(), Some((2, 11)));
+        assert_eq!(editor.insertion_point(), 2);
+    }
+
     #[rstest]
     #[case("abc", 1, 'X', "aXc")]
     #[case("abc", 1, '🔄', "a🔄c")]
@@ -773,6 +1281,18 @@ mod test {
         assert_eq!(editor.get_buffer(), "This is  a test");
     }
 
+    #[test]
+    fn one_undo_reverts_transposed_words() {
+        let mut editor = editor_with("ls");
+        for cmd in str_to_edit_commands(" foo bar") {
+            editor.run_edit_command(&cmd);
+        }
+        editor.run_edit_command(&EditCommand::TransposeWords);
+        assert_eq!(editor.get_buffer(), "ls bar foo");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "ls foo bar");
+    }
+
     #[test]
     fn test_undo_backspace_works_on_word_boundaries() {
         let mut editor = editor_with("This is  a test");
@@ -876,6 +1396,244 @@ mod test {
         editor.run_edit_command(&EditCommand::Undo);
         assert_eq!(editor.get_buffer(), "This \r\n is a test");
     }
+    #[rstest]
+    #[case(EditCommand::MoveLineUp, "line 2\nline 1")]
+    #[case(EditCommand::DuplicateLine, "line 1\nline 2\nline 2")]
+    fn test_undo_line_manipulation(#[case] command: EditCommand, #[case] expected: &str) {
+        let mut editor = editor_with("line 1\nline 2");
+        editor.run_edit_command(&command);
+        assert_eq!(editor.get_buffer(), expected);
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "line 1\nline 2");
+    }
+
+    #[test]
+    fn test_indent_selected_lines() {
+        let mut editor = editor_with("foo\nbar\nbaz");
+        editor.line_buffer.set_insertion_point(1);
+        editor.run_edit_command(&EditCommand::MoveToPosition {
+            position: 5,
+            select: true,
+        });
+        editor.run_edit_command(&EditCommand::IndentLine);
+        assert_eq!(editor.get_buffer(), "    foo\n    bar\nbaz");
+        assert_eq!(editor.get_selection(), Some((5, 13)));
+        editor.run_edit_command(&EditCommand::IndentLine);
+        assert_eq!(editor.get_buffer(), "        foo\n        bar\nbaz");
+        editor.run_edit_command(&EditCommand::DedentLine);
+        assert_eq!(editor.get_buffer(), "    foo\n    bar\nbaz");
+        assert_eq!(editor.get_selection(), Some((5, 13)));
+        editor.run_edit_command(&EditCommand::DedentLine);
+        assert_eq!(editor.get_buffer(), "foo\nbar\nbaz");
+        assert_eq!(editor.get_selection(), Some((1, 5)));
+        editor.run_edit_command(&EditCommand::DedentLine);
+        assert_eq!(editor.get_buffer(), "foo\nbar\nbaz");
+        assert_eq!(editor.get_selection(), Some((1, 5)));
+    }
+
+    #[test]
+    fn test_insert_newline_with_indent_is_one_undo_step() {
+        let mut editor = editor_with("foo {");
+        editor.insert_newline_with_indent("    ");
+        assert_eq!(editor.get_buffer(), "foo {\n    ");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "foo {");
+    }
+
+    #[test]
+    fn moving_between_lines_completes_the_undo_set() {
+        let mut editor = editor_with("foo\nbar");
+        for cmd in str_to_edit_commands(" baz") {
+            editor.run_edit_command(&cmd);
+        }
+        editor.move_line_up();
+        editor.run_edit_command(&EditCommand::InsertChar('!'));
+        assert_eq!(editor.get_buffer(), "foo!\nbar baz");
+
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "foo\nbar baz");
+        assert_eq!(editor.insertion_point(), 3);
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "foo\nbar");
+        assert_eq!(editor.insertion_point(), 7);
+    }
+
+    #[test]
+    fn test_undo_redo_after_combined_edits() {
+        let mut editor = editor_with("");
+        for c in "foo bar".chars() {
+            editor.run_edit_command(&EditCommand::InsertChar(c));
+        }
+        editor.run_edit_command(&EditCommand::MoveLeft { select: false });
+
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "foo");
+        editor.run_edit_command(&EditCommand::Redo);
+        assert_eq!(editor.get_buffer(), "foo bar");
+        assert_eq!(editor.insertion_point(), 6);
+
+        editor.run_edit_command(&EditCommand::InsertChar('x'));
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "foo bar");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "foo");
+    }
+
+    #[test]
+    fn test_undo_clears_prefill() {
+        let mut editor = editor_with("old");
+        editor.prefill("git commit".to_string(), 3);
+        assert_eq!(editor.insertion_point(), 3);
+        for c in "ish".chars() {
+            editor.run_edit_command(&EditCommand::InsertChar(c));
+        }
+        assert_eq!(editor.get_buffer(), "gitish commit");
+
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "git commit");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "");
+        editor.run_edit_command(&EditCommand::Undo);
+        assert_eq!(editor.get_buffer(), "");
+        editor.run_edit_command(&EditCommand::Redo);
+        assert_eq!(editor.get_buffer(), "git commit");
+    }
+
+    #[rstest]
+    #[case("cd foo/bar/baz", 0, "/", 6)]
+    #[case("cd foo/bar/baz", 6, "/", 10)]
+    #[case("cd foo/bar/baz", 10, "/", 10)]
+    #[case("ls | grep x | wc", 3, r"\|", 12)]
+    #[case("a1 b22 c333", 0, r"\d{2,}", 4)]
+    #[case("abc", 0, "(", 0)]
+    fn test_move_to_regex_match(
+        #[case] input: &str,
+        #[case] position: usize,
+        #[case] pattern: &str,
+        #[case] expected: usize,
+    ) {
+        let mut editor = editor_with(input);
+        editor.line_buffer.set_insertion_point(position);
+        editor.run_edit_command(&EditCommand::MoveToRegexMatch {
+            pattern: pattern.to_string(),
+            select: false,
+        });
+
+        assert_eq!(editor.insertion_point(), expected);
+    }
+
+    #[rstest]
+    #[case("ls -la | grep x | wc", 0, r"\|", "| grep x | wc", "ls -la ")]
+    #[case("ls -la | grep x | wc", 7, r"\|", "ls -la | wc", "| grep x ")]
+    #[case("ls -la", 0, r"\|", "ls -la", "")]
+    fn test_cut_to_regex_match(
+        #[case] input: &str,
+        #[case] position: usize,
+        #[case] pattern: &str,
+        #[case] expected: &str,
+        #[case] cut: &str,
+    ) {
+        let mut editor = editor_with(input);
+        editor.line_buffer.set_insertion_point(position);
+        editor.run_edit_command(&EditCommand::CutToRegexMatch {
+            pattern: pattern.to_string(),
+        });
+
+        assert_eq!(editor.get_buffer(), expected);
+        assert_eq!(editor.insertion_point(), position);
+        assert_eq!(editor.cut_buffer.get().0, cut);
+    }
+
+    #[test]
+    fn test_regex_pattern_is_cached() {
+        let mut editor = editor_with("a/b/c");
+        editor.line_buffer.set_insertion_point(0);
+        let command = EditCommand::MoveToRegexMatch {
+            pattern: "/".to_string(),
+            select: false,
+        };
+        editor.run_edit_command(&command);
+        editor.run_edit_command(&command);
+
+        assert_eq!(editor.insertion_point(), 3);
+        assert!(matches!(&editor.last_regex, Some((pattern, Some(_))) if pattern == "/"));
+
+        // Invalid patterns are remembered as such and replace the last one
+        let invalid = EditCommand::MoveToRegexMatch {
+            pattern: "(".to_string(),
+            select: false,
+        };
+        editor.run_edit_command(&invalid);
+        editor.run_edit_command(&invalid);
+
+        assert_eq!(editor.insertion_point(), 3);
+        assert!(matches!(&editor.last_regex, Some((pattern, None)) if pattern == "("));
+    }
+
+    fn editor_with_auto_pairs(buffer: &str, position: usize) -> Editor {
+        let mut editor = editor_with(buffer);
+        editor.set_auto_pairs(Some(AutoPairs::default()));
+        editor.line_buffer.set_insertion_point(position);
+        editor
+    }
+
+    #[rstest]
+    #[case("", 0, '(', "()", 1)]
+    #[case("foo ", 4, '[', "foo []", 5)]
+    #[case("()", 1, ')', "()", 2)]
+    #[case("\"\"", 1, '"', "\"\"", 2)]
+    #[case("foo", 0, '(', "(foo", 1)]
+    #[case("don", 3, '\'', "don'", 4)]
+    #[case("", 0, 'a', "a", 1)]
+    fn test_auto_pair_insert(
+        #[case] input: &str,
+        #[case] position: usize,
+        #[case] c: char,
+        #[case] expected: &str,
+        #[case] expected_position: usize,
+    ) {
+        let mut editor = editor_with_auto_pairs(input, position);
+        editor.run_edit_command(&EditCommand::InsertChar(c));
+        assert_eq!(editor.get_buffer(), expected);
+        assert_eq!(editor.insertion_point(), expected_position);
+    }
+
+    #[rstest]
+    #[case("()", 1, "")]
+    #[case("(a)", 2, "()")]
+    #[case("(]", 1, "]")]
+    fn test_auto_pair_backspace(
+        #[case] input: &str,
+        #[case] position: usize,
+        #[case] expected: &str,
+    ) {
+        let mut editor = editor_with_auto_pairs(input, position);
+        editor.run_edit_command(&EditCommand::Backspace);
+        assert_eq!(editor.get_buffer(), expected);
+    }
+
+    #[test]
+    fn test_auto_pair_filter() {
+        let mut editor = editor_with("\"foo");
+        editor.set_auto_pairs(Some(
+            AutoPairs::default()
+                .with_filter(|buffer, pos| buffer[..pos].matches('"').count() % 2 == 0),
+        ));
+        editor.run_edit_command(&EditCommand::InsertChar('('));
+        assert_eq!(editor.get_buffer(), "\"foo(");
+    }
+
+    #[rstest]
+    #[case("", "#ls")]
+    #[case("// ", "// ls")]
+    fn test_toggle_comment_prefix(#[case] prefix: &str, #[case] expected: &str) {
+        let mut editor = editor_with("ls");
+        editor.run_edit_command(&EditCommand::ToggleComment {
+            prefix: prefix.to_string(),
+        });
+        assert_eq!(editor.get_buffer(), expected);
+    }
+
     #[cfg(feature = "system_clipboard")]
     mod without_system_clipboard {
         use super::*;
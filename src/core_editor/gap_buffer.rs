@@ -0,0 +1,485 @@
+use {
+    once_cell::sync::OnceCell,
+    std::{
+        fmt,
+        ops::{Bound, Range, RangeBounds},
+        sync::{Mutex, PoisonError},
+    },
+    unicode_segmentation::{GraphemeCursor, GraphemeIncomplete},
+};
+
+// The least room made at the gap once it is full
+const MIN_GAP: usize = 64;
+
+/// The text of a [`super::LineBuffer`], stored with a gap of unused bytes at the last edit
+///
+/// Typing or deleting where the last edit happened only moves the bounds of the gap, where a
+/// `String` shifts all the text behind the cursor. The offsets of the line feeds are kept on
+/// both sides of the gap, the ones behind it counted from the end of the text, so that edits
+/// don't change them and finding a line doesn't read the text.
+///
+/// Reading the whole text while the gap is in the middle copies it into one piece, which is
+/// kept until the next edit. Slices that don't span the gap are read in place.
+#[derive(Default)]
+pub(crate) struct GapBuffer {
+    // The text, with the bytes in `gap` unused
+    bytes: Vec<u8>,
+    gap: Range<usize>,
+    // Offsets of the line feeds in front of the gap, ascending
+    feeds_before: Vec<usize>,
+    // Distances of the line feeds behind the gap from the end of the text, ascending
+    feeds_after: Vec<usize>,
+    // The text in one piece, made when it is read while the gap is in the middle
+    joined: OnceCell<String>,
+    // The memory of the last joined text, reused for the next one
+    spare: Mutex<String>,
+}
+
+impl GapBuffer {
+    pub(crate) fn len(&self) -> usize {
+        self.bytes.len() - self.gap.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The text in front of and behind the gap
+    pub(crate) fn as_slices(&self) -> (&str, &str) {
+        let (before, rest) = self.bytes.split_at(self.gap.start);
+        let after = &rest[self.gap.len()..];
+        // SAFETY: the bytes outside of the gap are only written by copying a `&str` into the
+        // gap, and the gap is only moved to char boundaries, so both sides stay valid UTF-8
+        unsafe {
+            (
+                std::str::from_utf8_unchecked(before),
+                std::str::from_utf8_unchecked(after),
+            )
+        }
+    }
+
+    /// The whole text, joined into one piece if the gap is in the middle
+    pub(crate) fn as_str(&self) -> &str {
+        match self.as_slices() {
+            (text, "") | ("", text) => text,
+            (before, after) => self.joined.get_or_init(|| {
+                let mut joined =
+                    std::mem::take(&mut *self.spare.lock().unwrap_or_else(PoisonError::into_inner));
+                joined.clear();
+                joined.reserve(before.len() + after.len());
+                joined.push_str(before);
+                joined.push_str(after);
+                joined
+            }),
+        }
+    }
+
+    /// The text in `range`, read in place unless it spans the gap
+    pub(crate) fn slice(&self, range: impl RangeBounds<usize>) -> &str {
+        let range = self.bounds(range);
+        let (before, after) = self.as_slices();
+        let gap = before.len();
+        if range.end <= gap {
+            &before[range]
+        } else if range.start >= gap {
+            &after[range.start - gap..range.end - gap]
+        } else {
+            &self.as_str()[range]
+        }
+    }
+
+    /// The byte at `offset`
+    pub(crate) fn byte(&self, offset: usize) -> u8 {
+        let (before, after) = self.as_slices();
+        match offset.checked_sub(before.len()) {
+            Some(offset) => after.as_bytes()[offset],
+            None => before.as_bytes()[offset],
+        }
+    }
+
+    pub(crate) fn is_char_boundary(&self, offset: usize) -> bool {
+        // Continuation bytes of UTF-8 look like 0b10xx_xxxx
+        offset == 0
+            || offset == self.len()
+            || (offset < self.len() && (self.byte(offset) as i8) >= -0x40)
+    }
+
+    /// Replace the text in `range` with `text`, which leaves the gap behind it
+    ///
+    /// Panics like [`String::replace_range`] if the range is out of bounds or its ends are not
+    /// on char boundaries.
+    pub(crate) fn replace_range(&mut self, range: impl RangeBounds<usize>, text: &str) {
+        let range = self.bounds(range);
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "range {range:?} out of bounds of a text of {} bytes",
+            self.len()
+        );
+        assert!(
+            self.is_char_boundary(range.start),
+            "range start not on a char boundary"
+        );
+        assert!(
+            self.is_char_boundary(range.end),
+            "range end not on a char boundary"
+        );
+
+        self.forget_joined();
+        self.move_gap(range.end);
+        // The replaced text becomes part of the gap
+        self.gap.start = range.start;
+        let kept = self
+            .feeds_before
+            .partition_point(|&feed| feed < range.start);
+        self.feeds_before.truncate(kept);
+
+        self.grow_gap(text.len());
+        let end = self.gap.start + text.len();
+        self.bytes[self.gap.start..end].copy_from_slice(text.as_bytes());
+        self.feeds_before.extend(
+            text.match_indices('\n')
+                .map(|(index, _)| self.gap.start + index),
+        );
+        self.gap.start = end;
+    }
+
+    pub(crate) fn insert_str(&mut self, offset: usize, text: &str) {
+        self.replace_range(offset..offset, text);
+    }
+
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.replace_range(len.., "");
+        }
+    }
+
+    /// Make room for typing `additional` more bytes at the gap without moving the text
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.grow_gap(additional);
+        let spare = self.spare.get_mut().unwrap_or_else(PoisonError::into_inner);
+        spare.reserve(self.bytes.len().saturating_sub(spare.len()));
+    }
+
+    /// Overwrite all the memory of the text with zeros and empty it
+    pub(crate) fn zeroize(&mut self) {
+        crate::utils::zeroize_bytes(&mut self.bytes);
+        if let Some(mut joined) = self.joined.take() {
+            crate::utils::zeroize(&mut joined);
+        }
+        crate::utils::zeroize(self.spare.get_mut().unwrap_or_else(PoisonError::into_inner));
+        self.gap = 0..0;
+        self.feeds_before.clear();
+        self.feeds_after.clear();
+    }
+
+    /// The number of lines, one more than the line feeds
+    pub(crate) fn line_count(&self) -> usize {
+        self.feeds_before.len() + self.feeds_after.len() + 1
+    }
+
+    /// The zero-based line of `offset`, the number of line feeds in front of it
+    pub(crate) fn line_of(&self, offset: usize) -> usize {
+        let before = self.feeds_before.partition_point(|&feed| feed < offset);
+        match self.len().checked_sub(offset) {
+            Some(from_end) if offset > self.gap.start => {
+                let behind = self
+                    .feeds_after
+                    .partition_point(|&distance| distance <= from_end);
+                before + self.feeds_after.len() - behind
+            }
+            _ => before,
+        }
+    }
+
+    /// The start of the line of `offset`, behind the last line feed in front of it
+    pub(crate) fn line_start(&self, offset: usize) -> usize {
+        let after_gap = match self.len().checked_sub(offset) {
+            Some(from_end) if offset > self.gap.start => {
+                let index = self
+                    .feeds_after
+                    .partition_point(|&distance| distance <= from_end);
+                self.feeds_after
+                    .get(index)
+                    .map(|distance| self.len() - distance)
+            }
+            _ => None,
+        };
+        after_gap
+            .or_else(|| {
+                let index = self.feeds_before.partition_point(|&feed| feed < offset);
+                index.checked_sub(1).map(|index| self.feeds_before[index])
+            })
+            .map_or(0, |feed| feed + 1)
+    }
+
+    /// The offset of the first line feed at or behind `offset`
+    pub(crate) fn line_feed_from(&self, offset: usize) -> Option<usize> {
+        let index = self.feeds_before.partition_point(|&feed| feed < offset);
+        if let Some(&feed) = self.feeds_before.get(index) {
+            return Some(feed);
+        }
+        let from_end = self.len().checked_sub(offset)?;
+        let index = self
+            .feeds_after
+            .partition_point(|&distance| distance <= from_end);
+        index
+            .checked_sub(1)
+            .map(|index| self.len() - self.feeds_after[index])
+    }
+
+    /// The end of the first grapheme of the text from `offset` on, segmented without the
+    /// text in front of `offset`
+    pub(crate) fn next_grapheme_boundary(&self, offset: usize) -> usize {
+        let (before, after) = self.as_slices();
+        // The cursor needs a chunk that holds the text at its offset
+        let (first, second) = match before.get(offset..) {
+            Some(first) if !first.is_empty() => (first, after),
+            _ => (&after[offset - before.len()..], ""),
+        };
+        let mut cursor = GraphemeCursor::new(0, first.len() + second.len(), true);
+        let boundary = match cursor.next_boundary(first, 0) {
+            Err(GraphemeIncomplete::NextChunk) => cursor.next_boundary(second, first.len()),
+            boundary => boundary,
+        };
+        match boundary {
+            Ok(Some(boundary)) => offset + boundary,
+            _ => self.len(),
+        }
+    }
+
+    /// The start of the last grapheme of the text in front of `offset`
+    pub(crate) fn previous_grapheme_boundary(&self, offset: usize) -> usize {
+        let (before, after) = self.as_slices();
+        let (first, second) = match before.get(..offset) {
+            Some(first) => (first, ""),
+            None => (before, &after[..offset - before.len()]),
+        };
+        let mut cursor = GraphemeCursor::new(offset, offset, true);
+        let mut boundary = cursor.prev_boundary(second, first.len());
+        loop {
+            boundary = match boundary {
+                Err(GraphemeIncomplete::PrevChunk) => cursor.prev_boundary(first, 0),
+                Err(GraphemeIncomplete::PreContext(end)) => {
+                    cursor.provide_context(&first[..end], 0);
+                    cursor.prev_boundary(second, first.len())
+                }
+                Ok(boundary) => return boundary.unwrap_or(0),
+                Err(_) => return 0,
+            };
+        }
+    }
+
+    fn bounds(&self, range: impl RangeBounds<usize>) -> Range<usize> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len(),
+        };
+        start..end
+    }
+
+    // Moves the gap in front of the text at `offset`, carrying the line feeds across it over
+    // to the other side
+    fn move_gap(&mut self, offset: usize) {
+        let len = self.len();
+        if offset < self.gap.start {
+            let moved = self.gap.start - offset;
+            self.bytes
+                .copy_within(offset..self.gap.start, self.gap.end - moved);
+            self.gap = offset..self.gap.end - moved;
+            while let Some(&feed) = self.feeds_before.last() {
+                if feed < offset {
+                    break;
+                }
+                self.feeds_before.pop();
+                self.feeds_after.push(len - feed);
+            }
+        } else if offset > self.gap.start {
+            let moved = offset - self.gap.start;
+            self.bytes
+                .copy_within(self.gap.end..self.gap.end + moved, self.gap.start);
+            self.gap = offset..self.gap.end + moved;
+            while let Some(&distance) = self.feeds_after.last() {
+                if len - distance >= offset {
+                    break;
+                }
+                self.feeds_after.pop();
+                self.feeds_before.push(len - distance);
+            }
+        }
+    }
+
+    // Makes the gap at least `needed` bytes long, growing it with the text so that typing
+    // at one place moves the text behind it only now and then
+    fn grow_gap(&mut self, needed: usize) {
+        if self.gap.len() >= needed {
+            return;
+        }
+        let extra = needed.max(self.len() / 4).max(MIN_GAP);
+        self.bytes
+            .splice(self.gap.end..self.gap.end, std::iter::repeat(0).take(extra));
+        self.gap.end += extra;
+    }
+
+    // Drops the joined text before an edit, keeping its memory for the next one
+    fn forget_joined(&mut self) {
+        if let Some(joined) = self.joined.take() {
+            *self.spare.get_mut().unwrap_or_else(PoisonError::into_inner) = joined;
+        }
+    }
+}
+
+impl From<String> for GapBuffer {
+    fn from(text: String) -> Self {
+        let feeds_before = text.match_indices('\n').map(|(index, _)| index).collect();
+        let bytes = text.into_bytes();
+        GapBuffer {
+            gap: bytes.len()..bytes.len(),
+            bytes,
+            feeds_before,
+            ..GapBuffer::default()
+        }
+    }
+}
+
+impl Clone for GapBuffer {
+    fn clone(&self) -> Self {
+        GapBuffer {
+            bytes: self.bytes.clone(),
+            gap: self.gap.clone(),
+            feeds_before: self.feeds_before.clone(),
+            feeds_after: self.feeds_after.clone(),
+            ..GapBuffer::default()
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.forget_joined();
+        self.bytes.clone_from(&source.bytes);
+        self.gap.clone_from(&source.gap);
+        self.feeds_before.clone_from(&source.feeds_before);
+        self.feeds_after.clone_from(&source.feeds_after);
+    }
+}
+
+impl PartialEq for GapBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        let (before, after) = self.as_slices();
+        let (other_before, other_after) = other.as_slices();
+        self.len() == other.len()
+            && before
+                .bytes()
+                .chain(after.bytes())
+                .eq(other_before.bytes().chain(other_after.bytes()))
+    }
+}
+
+impl Eq for GapBuffer {}
+
+impl fmt::Debug for GapBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // The line feeds found by the index, against the ones in the text
+    fn assert_lines(buffer: &GapBuffer) {
+        let text = buffer.as_str().to_string();
+        assert_eq!(buffer.line_count(), text.split('\n').count());
+        for offset in (0..=text.len()).filter(|&offset| text.is_char_boundary(offset)) {
+            assert_eq!(buffer.line_of(offset), text[..offset].matches('\n').count());
+            assert_eq!(
+                buffer.line_start(offset),
+                text[..offset].rfind('\n').map_or(0, |feed| feed + 1)
+            );
+            assert_eq!(
+                buffer.line_feed_from(offset),
+                text[offset..].find('\n').map(|feed| offset + feed)
+            );
+        }
+    }
+
+    #[test]
+    fn edits_anywhere_keep_the_text_and_the_lines() {
+        let mut buffer = GapBuffer::from("first\nsecond\nthird".to_string());
+        let mut expected = buffer.as_str().to_string();
+        let edits: &[(Range<usize>, &str)] = &[
+            (5..5, "!"),
+            (6..6, "line\nbreak "),
+            (0..3, ""),
+            (10..20, "\n\n"),
+            (2..2, "ä"),
+            (0..0, "start\n"),
+            (8..14, "x"),
+        ];
+        for (range, text) in edits {
+            buffer.replace_range(range.clone(), text);
+            expected.replace_range(range.clone(), text);
+            assert_eq!(buffer.as_str(), expected);
+            assert_lines(&buffer);
+        }
+
+        buffer.truncate(4);
+        expected.truncate(4);
+        assert_eq!(buffer.as_str(), expected);
+        assert_lines(&buffer);
+    }
+
+    #[test]
+    fn slices_on_one_side_of_the_gap_are_read_in_place() {
+        let mut buffer = GapBuffer::from("hello world".to_string());
+        buffer.insert_str(5, ",");
+        assert_eq!(buffer.as_slices(), ("hello,", " world"));
+        assert_eq!(buffer.slice(..6), "hello,");
+        assert_eq!(buffer.slice(6..), " world");
+        assert!(buffer.joined.get().is_none());
+
+        assert_eq!(buffer.slice(4..8), "o, w");
+        assert_eq!(buffer.as_str(), "hello, world");
+        assert!(buffer.joined.get().is_some());
+        assert_eq!(buffer.byte(6), b' ');
+    }
+
+    #[test]
+    fn graphemes_are_found_across_the_gap() {
+        let mut buffer = GapBuffer::from("ae\u{301}b".to_string());
+        buffer.insert_str(2, "");
+        // The gap lies between the `e` and its combining accent
+        assert_eq!(buffer.as_slices().0, "ae");
+        assert_eq!(buffer.next_grapheme_boundary(1), 4);
+        assert_eq!(buffer.previous_grapheme_boundary(4), 1);
+        assert_eq!(buffer.next_grapheme_boundary(4), 5);
+        assert_eq!(buffer.previous_grapheme_boundary(1), 0);
+        assert_eq!(buffer.next_grapheme_boundary(5), 5);
+        assert_eq!(buffer.previous_grapheme_boundary(0), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "char boundary")]
+    fn edits_inside_a_char_panic() {
+        let mut buffer = GapBuffer::from("ä".to_string());
+        buffer.insert_str(1, "x");
+    }
+
+    #[test]
+    fn clones_compare_by_their_text() {
+        let mut buffer = GapBuffer::from("one two".to_string());
+        buffer.insert_str(3, " and");
+        let mut clone = GapBuffer::default();
+        clone.clone_from(&buffer);
+        assert_eq!(clone, buffer);
+        assert_eq!(clone, GapBuffer::from("one and two".to_string()));
+        clone.insert_str(0, "x");
+        assert_ne!(clone, buffer);
+    }
+}
@@ -1,13 +1,17 @@
 use {
+    super::gap_buffer::GapBuffer,
     itertools::Itertools,
     std::{convert::From, ops::Range},
     unicode_segmentation::UnicodeSegmentation,
 };
 
 /// In memory representation of the entered line(s) including a cursor position to facilitate cursor based editing.
+///
+/// The text is kept with a gap at the last edit, so that typing into a large buffer doesn't
+/// move the text behind the cursor on every keystroke, and with the offsets of its lines.
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct LineBuffer {
-    lines: String,
+    text: GapBuffer,
     insertion_point: usize,
 }
 
@@ -27,35 +31,37 @@ impl LineBuffer {
 
     /// Check to see if the line buffer is empty
     pub fn is_empty(&self) -> bool {
-        self.lines.is_empty()
+        self.text.is_empty()
     }
 
     /// Check if the line buffer is valid utf-8 and the cursor sits on a valid grapheme boundary
     pub fn is_valid(&self) -> bool {
-        self.lines.is_char_boundary(self.insertion_point())
+        self.text.is_char_boundary(self.insertion_point())
             && (self
-                .lines
+                .text
+                .as_str()
                 .grapheme_indices(true)
                 .any(|(i, _)| i == self.insertion_point())
-                || self.insertion_point() == self.lines.len())
-            && std::str::from_utf8(self.lines.as_bytes()).is_ok()
+                || self.insertion_point() == self.text.len())
+            && std::str::from_utf8(self.text.as_str().as_bytes()).is_ok()
     }
 
     #[cfg(test)]
     fn assert_valid(&self) {
         assert!(
-            self.lines.is_char_boundary(self.insertion_point()),
+            self.text.is_char_boundary(self.insertion_point()),
             "Not on valid char boundary"
         );
         assert!(
-            self.lines
+            self.text
+                .as_str()
                 .grapheme_indices(true)
                 .any(|(i, _)| i == self.insertion_point())
-                || self.insertion_point() == self.lines.len(),
+                || self.insertion_point() == self.text.len(),
             "Not on valid grapheme"
         );
         assert!(
-            std::str::from_utf8(self.lines.as_bytes()).is_ok(),
+            std::str::from_utf8(self.text.as_str().as_bytes()).is_ok(),
             "Not valid utf-8"
         );
     }
@@ -74,30 +80,45 @@ impl LineBuffer {
 
     /// Output the current line in the multiline buffer
     pub fn get_buffer(&self) -> &str {
-        &self.lines
+        self.text.as_str()
+    }
+
+    /// Reserve room for `additional` more bytes, so typing them doesn't reallocate the
+    /// buffer and leave copies of it in freed memory
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.text.reserve(additional);
+    }
+
+    /// Overwrite the memory of the buffer with zeros and empty it
+    pub(crate) fn zeroize(&mut self) {
+        self.text.zeroize();
+        self.insertion_point = 0;
     }
 
     /// Set to a single line of `buffer` and reset the `InsertionPoint` cursor to the end
     pub fn set_buffer(&mut self, buffer: String) {
-        self.lines = buffer;
-        self.insertion_point = self.lines.len();
+        self.text = GapBuffer::from(buffer);
+        self.insertion_point = self.text.len();
     }
 
     /// Calculates the current the user is on
     ///
     /// Zero-based index
     pub fn line(&self) -> usize {
-        self.lines[..self.insertion_point].matches('\n').count()
+        self.text.line_of(self.insertion_point)
     }
 
     /// Counts the number of lines in the buffer
     pub fn num_lines(&self) -> usize {
-        self.lines.split('\n').count()
+        self.text.line_count()
     }
 
     /// Checks to see if the buffer ends with a given character
     pub fn ends_with(&self, c: char) -> bool {
-        self.lines.ends_with(c)
+        match self.text.as_slices() {
+            (before, "") => before.ends_with(c),
+            (_, after) => after.ends_with(c),
+        }
     }
 
     /// Reset the insertion point to the start of the buffer
@@ -107,10 +128,7 @@ impl LineBuffer {
 
     /// Move the cursor before the first character of the line
     pub fn move_to_line_start(&mut self) {
-        self.insertion_point = self.lines[..self.insertion_point]
-            .rfind('\n')
-            .map_or(0, |offset| offset + 1);
-        // str is guaranteed to be utf8, thus \n is safe to assume 1 byte long
+        self.insertion_point = self.text.line_start(self.insertion_point);
     }
 
     /// Move cursor position to the end of the line
@@ -123,12 +141,12 @@ impl LineBuffer {
 
     /// Set the insertion point *behind* the last character.
     pub fn move_to_end(&mut self) {
-        self.insertion_point = self.lines.len();
+        self.insertion_point = self.text.len();
     }
 
     /// Get the length of the buffer
     pub fn len(&self) -> usize {
-        self.lines.len()
+        self.text.len()
     }
 
     /// Returns where the current line terminates
@@ -137,14 +155,13 @@ impl LineBuffer {
     /// - end of buffer (`len()`)
     /// - `\n` or `\r\n` (on the first byte)
     pub fn find_current_line_end(&self) -> usize {
-        self.lines[self.insertion_point..].find('\n').map_or_else(
-            || self.lines.len(),
-            |i| {
-                let absolute_index = i + self.insertion_point;
-                if absolute_index > 0 && self.lines.as_bytes()[absolute_index - 1] == b'\r' {
-                    absolute_index - 1
+        self.text.line_feed_from(self.insertion_point).map_or_else(
+            || self.text.len(),
+            |index| {
+                if index > 0 && self.text.byte(index - 1) == b'\r' {
+                    index - 1
                 } else {
-                    absolute_index
+                    index
                 }
             },
         )
@@ -152,48 +169,43 @@ impl LineBuffer {
 
     /// Cursor position *behind* the next unicode grapheme to the right
     pub fn grapheme_right_index(&self) -> usize {
-        self.lines[self.insertion_point..]
-            .grapheme_indices(true)
-            .nth(1)
-            .map(|(i, _)| self.insertion_point + i)
-            .unwrap_or_else(|| self.lines.len())
+        self.text.next_grapheme_boundary(self.insertion_point)
     }
 
     /// Cursor position *in front of* the next unicode grapheme to the left
     pub fn grapheme_left_index(&self) -> usize {
-        self.lines[..self.insertion_point]
-            .grapheme_indices(true)
-            .last()
-            .map(|(i, _)| i)
-            .unwrap_or(0)
+        self.text.previous_grapheme_boundary(self.insertion_point)
     }
 
     /// Cursor position *behind* the next word to the right
     pub fn word_right_index(&self) -> usize {
-        self.lines[self.insertion_point..]
+        self.text
+            .slice(self.insertion_point..)
             .split_word_bound_indices()
             .find(|(_, word)| !is_whitespace_str(word))
             .map(|(i, word)| self.insertion_point + i + word.len())
-            .unwrap_or_else(|| self.lines.len())
+            .unwrap_or_else(|| self.text.len())
     }
 
     /// Cursor position *behind* the next WORD to the right
     pub fn big_word_right_index(&self) -> usize {
         let mut found_ws = false;
 
-        self.lines[self.insertion_point..]
+        self.text
+            .slice(self.insertion_point..)
             .split_word_bound_indices()
             .find(|(_, word)| {
                 found_ws = found_ws || is_whitespace_str(word);
                 found_ws && !is_whitespace_str(word)
             })
             .map(|(i, word)| self.insertion_point + i + word.len())
-            .unwrap_or_else(|| self.lines.len())
+            .unwrap_or_else(|| self.text.len())
     }
 
     /// Cursor position *at end of* the next word to the right
     pub fn word_right_end_index(&self) -> usize {
-        self.lines[self.insertion_point..]
+        self.text
+            .slice(self.insertion_point..)
             .split_word_bound_indices()
             .find_map(|(i, word)| {
                 word.grapheme_indices(true)
@@ -202,9 +214,10 @@ impl LineBuffer {
                     .filter(|x| !is_whitespace_str(word) && *x != self.insertion_point)
             })
             .unwrap_or_else(|| {
-                self.lines
+                self.text
+                    .as_str()
                     .grapheme_indices(true)
-                    .last()
+                    .next_back()
                     .map(|x| x.0)
                     .unwrap_or(0)
             })
@@ -212,7 +225,8 @@ impl LineBuffer {
 
     /// Cursor position *at end of* the next WORD to the right
     pub fn big_word_right_end_index(&self) -> usize {
-        self.lines[self.insertion_point..]
+        self.text
+            .slice(self.insertion_point..)
             .split_word_bound_indices()
             .tuple_windows()
             .find_map(|((prev_i, prev_word), (_, word))| {
@@ -227,9 +241,10 @@ impl LineBuffer {
                 }
             })
             .unwrap_or_else(|| {
-                self.lines
+                self.text
+                    .as_str()
                     .grapheme_indices(true)
-                    .last()
+                    .next_back()
                     .map(|x| x.0)
                     .unwrap_or(0)
             })
@@ -237,47 +252,50 @@ impl LineBuffer {
 
     /// Cursor position *in front of* the next word to the right
     pub fn word_right_start_index(&self) -> usize {
-        self.lines[self.insertion_point..]
+        self.text
+            .slice(self.insertion_point..)
             .split_word_bound_indices()
             .find(|(i, word)| *i != 0 && !is_whitespace_str(word))
             .map(|(i, _)| self.insertion_point + i)
-            .unwrap_or_else(|| self.lines.len())
+            .unwrap_or_else(|| self.text.len())
     }
 
     /// Cursor position *in front of* the next WORD to the right
     pub fn big_word_right_start_index(&self) -> usize {
         let mut found_ws = false;
 
-        self.lines[self.insertion_point..]
+        self.text
+            .slice(self.insertion_point..)
             .split_word_bound_indices()
             .find(|(i, word)| {
                 found_ws = found_ws || *i != 0 && is_whitespace_str(word);
                 found_ws && *i != 0 && !is_whitespace_str(word)
             })
             .map(|(i, _)| self.insertion_point + i)
-            .unwrap_or_else(|| self.lines.len())
+            .unwrap_or_else(|| self.text.len())
     }
 
     /// Cursor position *in front of* the next word to the left
     pub fn word_left_index(&self) -> usize {
-        self.lines[..self.insertion_point]
+        self.text
+            .slice(..self.insertion_point)
             .split_word_bound_indices()
-            .filter(|(_, word)| !is_whitespace_str(word))
-            .last()
+            .rfind(|(_, word)| !is_whitespace_str(word))
             .map(|(i, _)| i)
             .unwrap_or(0)
     }
 
     /// Cursor position *in front of* the next WORD to the left
     pub fn big_word_left_index(&self) -> usize {
-        self.lines[..self.insertion_point]
+        self.text
+            .slice(..self.insertion_point)
             .split_word_bound_indices()
             .fold(None, |last_word_index, (i, word)| {
                 match (last_word_index, is_whitespace_str(word)) {
                     (None, true) => None,
                     (None, false) => Some(i),
                     (Some(v), true) => {
-                        if is_whitespace_str(&self.lines[i..self.insertion_point]) {
+                        if is_whitespace_str(self.text.slice(i..self.insertion_point)) {
                             Some(v)
                         } else {
                             None
@@ -291,11 +309,12 @@ impl LineBuffer {
 
     /// Cursor position on the next whitespace
     pub fn next_whitespace(&self) -> usize {
-        self.lines[self.insertion_point..]
+        self.text
+            .slice(self.insertion_point..)
             .split_word_bound_indices()
             .find(|(i, word)| *i != 0 && is_whitespace_str(word))
             .map(|(i, _)| self.insertion_point + i)
-            .unwrap_or_else(|| self.lines.len())
+            .unwrap_or_else(|| self.text.len())
     }
 
     /// Move cursor position *behind* the next unicode grapheme to the right
@@ -345,7 +364,8 @@ impl LineBuffer {
 
     ///Insert a single character at the insertion point and move right
     pub fn insert_char(&mut self, c: char) {
-        self.lines.insert(self.insertion_point, c);
+        self.text
+            .insert_str(self.insertion_point, c.encode_utf8(&mut [0; 4]));
         self.move_right();
     }
 
@@ -356,7 +376,7 @@ impl LineBuffer {
     /// ## Unicode safety:
     /// Does not validate the incoming string or the current cursor position
     pub fn insert_str(&mut self, string: &str) {
-        self.lines.insert_str(self.insertion_point(), string);
+        self.text.insert_str(self.insertion_point(), string);
         self.insertion_point = self.insertion_point() + string.len();
     }
 
@@ -373,14 +393,14 @@ impl LineBuffer {
 
     /// Empty buffer and reset cursor
     pub fn clear(&mut self) {
-        self.lines = String::new();
+        self.text = GapBuffer::default();
         self.insertion_point = 0;
     }
 
     /// Clear everything beginning at the cursor to the right/end.
     /// Keeps the cursor at the end.
     pub fn clear_to_end(&mut self) {
-        self.lines.truncate(self.insertion_point);
+        self.text.truncate(self.insertion_point);
     }
 
     /// Clear beginning at the cursor up to the end of the line.
@@ -434,12 +454,13 @@ impl LineBuffer {
     where
         R: std::ops::RangeBounds<usize>,
     {
-        self.lines.replace_range(range, replace_with);
+        self.text.replace_range(range, replace_with);
     }
 
     /// Checks to see if the current edit position is pointing to whitespace
     pub fn on_whitespace(&self) -> bool {
-        self.lines[self.insertion_point..]
+        self.text
+            .slice(self.insertion_point..)
             .chars()
             .next()
             .map(char::is_whitespace)
@@ -448,21 +469,24 @@ impl LineBuffer {
 
     /// Get the grapheme immediately to the right of the cursor, if any
     pub fn grapheme_right(&self) -> &str {
-        &self.lines[self.insertion_point..self.grapheme_right_index()]
+        self.text
+            .slice(self.insertion_point..self.grapheme_right_index())
     }
 
     /// Get the grapheme immediately to the left of the cursor, if any
     pub fn grapheme_left(&self) -> &str {
-        &self.lines[self.grapheme_left_index()..self.insertion_point]
+        self.text
+            .slice(self.grapheme_left_index()..self.insertion_point)
     }
 
     /// Gets the range of the word the current edit position is pointing to
     pub fn current_word_range(&self) -> Range<usize> {
         let right_index = self.word_right_index();
-        let left_index = self.lines[..right_index]
+        let left_index = self
+            .text
+            .slice(..right_index)
             .split_word_bound_indices()
-            .filter(|(_, word)| !is_whitespace_str(word))
-            .last()
+            .rfind(|(_, word)| !is_whitespace_str(word))
             .map(|(i, _)| i)
             .unwrap_or(0);
 
@@ -475,16 +499,184 @@ impl LineBuffer {
     /// extending beyond the potential carriage return and line feed characters
     /// terminating the line
     pub fn current_line_range(&self) -> Range<usize> {
-        let left_index = self.lines[..self.insertion_point]
-            .rfind('\n')
-            .map_or(0, |offset| offset + 1);
-        let right_index = self.lines[self.insertion_point..]
-            .find('\n')
-            .map_or_else(|| self.lines.len(), |i| i + self.insertion_point + 1);
+        let left_index = self.text.line_start(self.insertion_point);
+        let right_index = self
+            .text
+            .line_feed_from(self.insertion_point)
+            .map_or_else(|| self.text.len(), |index| index + 1);
 
         left_index..right_index
     }
 
+    /// Range over the current paragraph
+    ///
+    /// A paragraph is the block of lines between blank lines, or lines with only whitespace,
+    /// and includes the blank lines following it or, for the last paragraph, the blank lines
+    /// before it. On a blank line the range covers the blank lines around it.
+    pub fn current_paragraph_range(&self) -> Range<usize> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for line in self.text.as_str().split_inclusive('\n') {
+            lines.push((start..start + line.len(), line.trim().is_empty()));
+            start += line.len();
+        }
+        let current = lines
+            .iter()
+            .position(|(range, _)| range.contains(&self.insertion_point))
+            .unwrap_or(lines.len().saturating_sub(1));
+        let blank = match lines.get(current) {
+            Some((_, blank)) => *blank,
+            None => return 0..0,
+        };
+
+        // The lines next to `current` that are blank, or not, like it
+        let block_start = lines[..current]
+            .iter()
+            .rposition(|(_, other)| *other != blank)
+            .map_or(0, |index| index + 1);
+        let block_end = lines[current..]
+            .iter()
+            .position(|(_, other)| *other != blank)
+            .map_or(lines.len(), |index| current + index);
+        let (mut first, mut last) = (block_start, block_end);
+        if !blank {
+            let blank_after = lines[block_end..]
+                .iter()
+                .take_while(|(_, blank)| *blank)
+                .count();
+            if blank_after > 0 {
+                last += blank_after;
+            } else {
+                first -= lines[..block_start]
+                    .iter()
+                    .rev()
+                    .take_while(|(_, blank)| *blank)
+                    .count();
+            }
+        }
+        let end = lines[last - 1].0.end;
+        // Without blank lines after it, the last paragraph takes the line break before it
+        let start = if last == lines.len() && first > 0 && !self.text.slice(..end).ends_with('\n') {
+            lines[first].0.start - 1
+        } else {
+            lines[first].0.start
+        };
+        start..end
+    }
+
+    // The lines of the buffer with their start and whether they are blank
+    fn lines_with_blanks(&self) -> Vec<(usize, bool)> {
+        let mut start = 0;
+        self.text
+            .as_str()
+            .split_inclusive('\n')
+            .map(|line| {
+                let line_start = start;
+                start += line.len();
+                (line_start, line.trim().is_empty())
+            })
+            .collect()
+    }
+
+    // The index of the line the cursor is on in `lines`
+    fn current_line_index(&self, lines: &[(usize, bool)]) -> usize {
+        lines
+            .iter()
+            .rposition(|(start, _)| *start <= self.insertion_point)
+            .unwrap_or(0)
+    }
+
+    /// Cursor position at the start of the blank line before the paragraph, or the buffer
+    /// start, as with `{` in vi
+    pub fn paragraph_left_index(&self) -> usize {
+        let lines = self.lines_with_blanks();
+        let current = self.current_line_index(&lines);
+        let mut in_paragraph = lines.get(current).map_or(false, |(_, blank)| !blank);
+        for (start, blank) in lines[..current].iter().rev() {
+            if *blank && in_paragraph {
+                return *start;
+            }
+            in_paragraph |= !blank;
+        }
+        0
+    }
+
+    /// Cursor position at the start of the blank line after the paragraph, or the buffer
+    /// end, as with `}` in vi
+    pub fn paragraph_right_index(&self) -> usize {
+        let lines = self.lines_with_blanks();
+        let current = self.current_line_index(&lines);
+        let mut in_paragraph = lines.get(current).map_or(false, |(_, blank)| !blank);
+        for (start, blank) in lines.iter().skip(current + 1) {
+            if *blank && in_paragraph {
+                return *start;
+            }
+            in_paragraph |= !blank;
+        }
+        self.text.len()
+    }
+
+    // Where the sentences start: after `.`, `!` or `?` and any closing brackets or quotes
+    // followed by whitespace, and at the blank lines between paragraphs and after them
+    fn sentence_starts(&self) -> Vec<usize> {
+        let mut starts = Vec::new();
+        let mut sentence_ended = true;
+        let mut previous_blank = false;
+        for (line_start, line) in self
+            .text
+            .as_str()
+            .split_inclusive('\n')
+            .scan(0, |start, line| {
+                let line_start = *start;
+                *start += line.len();
+                Some((line_start, line))
+            })
+        {
+            let blank = line.trim().is_empty();
+            if blank {
+                if !previous_blank {
+                    starts.push(line_start);
+                }
+                sentence_ended = true;
+            }
+            previous_blank = blank;
+            let mut chars = line.char_indices().peekable();
+            while let Some((index, c)) = chars.next() {
+                if c.is_whitespace() {
+                    continue;
+                }
+                if sentence_ended {
+                    starts.push(line_start + index);
+                    sentence_ended = false;
+                }
+                if matches!(c, '.' | '!' | '?') {
+                    while let Some((_, ')' | ']' | '"' | '\'')) = chars.peek() {
+                        chars.next();
+                    }
+                    sentence_ended = chars.peek().map_or(true, |(_, c)| c.is_whitespace());
+                }
+            }
+        }
+        starts
+    }
+
+    /// Cursor position at the start of the sentence, or of the previous one when the cursor
+    /// is already there, as with `(` in vi
+    pub fn sentence_left_index(&self) -> usize {
+        self.sentence_starts()
+            .into_iter()
+            .rfind(|start| *start < self.insertion_point)
+            .unwrap_or(0)
+    }
+
+    /// Cursor position at the start of the next sentence, or the buffer end, as with `)` in vi
+    pub fn sentence_right_index(&self) -> usize {
+        self.sentence_starts()
+            .into_iter()
+            .find(|start| *start > self.insertion_point)
+            .unwrap_or(self.text.len())
+    }
+
     /// Uppercases the current word
     pub fn uppercase_word(&mut self) {
         let change_range = self.current_word_range();
@@ -507,22 +699,52 @@ impl LineBuffer {
         let right_index = self.grapheme_right_index();
 
         if right_index > insertion_offset {
-            let change_range = insertion_offset..right_index;
-            let swapped = self.get_buffer()[change_range.clone()]
-                .chars()
-                .map(|c| {
-                    if c.is_ascii_uppercase() {
-                        c.to_ascii_lowercase()
-                    } else {
-                        c.to_ascii_uppercase()
-                    }
-                })
-                .collect::<String>();
-            self.replace_range(change_range, &swapped);
+            self.switchcase_range(insertion_offset..right_index);
             self.move_right();
         }
     }
 
+    /// Switches the case of up to `count` graphemes from the insertion point on the current
+    /// line, like `~` in vim
+    ///
+    /// The insertion point moves after them, but stays on the last grapheme of the line.
+    pub fn switchcase_chars(&mut self, count: usize) {
+        let line_end = self.find_current_line_end();
+        let start = self.insertion_point;
+        let mut end = start;
+        let mut last = start;
+        for (offset, grapheme) in self
+            .text
+            .slice(start..line_end)
+            .grapheme_indices(true)
+            .take(count)
+        {
+            last = start + offset;
+            end = last + grapheme.len();
+        }
+        if end > start {
+            self.switchcase_range(start..end);
+            self.insertion_point = if end < line_end { end } else { last };
+        }
+    }
+
+    /// Switches the case of the ASCII letters in `range`, shared by the case switching commands
+    pub fn switchcase_range(&mut self, range: Range<usize>) {
+        let swapped = self
+            .text
+            .slice(range.clone())
+            .chars()
+            .map(|c| {
+                if c.is_ascii_uppercase() {
+                    c.to_ascii_lowercase()
+                } else {
+                    c.to_ascii_uppercase()
+                }
+            })
+            .collect::<String>();
+        self.replace_range(range, &swapped);
+    }
+
     /// Capitalize the character at insertion point (or the first character
     /// following the whitespace at the insertion point) and move the insertion
     /// point right one grapheme.
@@ -590,6 +812,79 @@ impl LineBuffer {
         }
     }
 
+    /// Swaps the word under or before the cursor with the word before it, like `Alt-t` in zsh
+    ///
+    /// Words are runs of letters, digits and `_`, so the punctuation and the whitespace
+    /// between them stay in place. Before the second word the first two are swapped. The
+    /// cursor ends after the swapped words.
+    pub fn transpose_words(&mut self) {
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+        let mut words: Vec<Range<usize>> = Vec::new();
+        let mut start = None;
+        for (index, c) in self.text.as_str().char_indices() {
+            match (start, is_word_char(c)) {
+                (None, true) => start = Some(index),
+                (Some(word_start), false) => {
+                    words.push(word_start..index);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(word_start) = start {
+            words.push(word_start..self.text.len());
+        }
+
+        let second = words
+            .iter()
+            .rposition(|word| word.start <= self.insertion_point)
+            .unwrap_or_default()
+            .max(1);
+        if let (Some(first), Some(second)) = (words.get(second - 1), words.get(second)) {
+            let transposed = format!(
+                "{}{}{}",
+                self.text.slice(second.clone()),
+                self.text.slice(first.end..second.start),
+                self.text.slice(first.clone())
+            );
+            let end = second.end;
+            self.replace_range(first.start..end, &transposed);
+            self.insertion_point = end;
+        }
+    }
+
+    /// Swaps the grapheme before the cursor with the one after it `count` times, dragging it
+    /// forward like `Ctrl-t` with a count in Emacs
+    ///
+    /// Stops at the end of the buffer, where [`LineBuffer::swap_graphemes`] would swap the
+    /// same graphemes back.
+    pub fn transpose_graphemes(&mut self, count: usize) {
+        for swapped in 0..count {
+            if swapped > 0 && self.insertion_point == self.text.len() {
+                break;
+            }
+            self.swap_graphemes();
+        }
+    }
+
+    /// Adds `delta` to the number at or after the cursor on the current line, like `Ctrl-a`
+    /// and `Ctrl-x` in vim
+    ///
+    /// Decimal numbers may be negative and hexadecimal ones start with `0x`, the width of
+    /// numbers with leading zeros is kept. The cursor ends on the last digit.
+    pub fn increment_number(&mut self, delta: i64) {
+        let line_range = self.current_line_range();
+        let line = self.text.slice(line_range.clone());
+        let cursor = self.insertion_point - line_range.start;
+        if let Some((range, number)) = find_number(line, cursor)
+            .and_then(|range| add_to_number(&line[range.clone()], delta).map(|n| (range, n)))
+        {
+            let start = line_range.start + range.start;
+            self.replace_range(start..line_range.start + range.end, &number);
+            self.insertion_point = start + number.len() - 1;
+        }
+    }
+
     /// Swaps current grapheme with grapheme on right
     pub fn swap_graphemes(&mut self) {
         let initial_offset = self.insertion_point();
@@ -620,7 +915,9 @@ impl LineBuffer {
         if !self.is_cursor_at_first_line() {
             let old_range = self.current_line_range();
 
-            let grapheme_col = self.lines[old_range.start..self.insertion_point()]
+            let grapheme_col = self
+                .text
+                .slice(old_range.start..self.insertion_point())
                 .graphemes(true)
                 .count();
 
@@ -631,7 +928,7 @@ impl LineBuffer {
             self.move_left();
 
             let new_range = self.current_line_range();
-            let new_line = &self.lines[new_range.clone()];
+            let new_line = self.text.slice(new_range.clone());
 
             self.insertion_point = new_line
                 .grapheme_indices(true)
@@ -646,7 +943,9 @@ impl LineBuffer {
         if !self.is_cursor_at_last_line() {
             let old_range = self.current_line_range();
 
-            let grapheme_col = self.lines[old_range.start..self.insertion_point()]
+            let grapheme_col = self
+                .text
+                .slice(old_range.start..self.insertion_point())
                 .graphemes(true)
                 .count();
 
@@ -654,7 +953,7 @@ impl LineBuffer {
             self.set_insertion_point(old_range.end);
 
             let new_range = self.current_line_range();
-            let new_line = &self.lines[new_range.clone()];
+            let new_line = self.text.slice(new_range.clone());
 
             // Slightly different to move_line_up to account for the special
             // case of the last line without newline char at the end.
@@ -669,14 +968,187 @@ impl LineBuffer {
         }
     }
 
+    /// Swaps the current line with the one above it
+    ///
+    /// The cursor stays on the moved line at the same column
+    pub fn swap_line_up(&mut self) {
+        let (line, column) = self.line_and_column();
+        if line > 0 {
+            let (mut lines, newline) = self.split_lines();
+            lines.swap(line - 1, line);
+            self.set_lines(&lines, newline, line - 1, column);
+        }
+    }
+
+    /// Swaps the current line with the one below it
+    ///
+    /// The cursor stays on the moved line at the same column
+    pub fn swap_line_down(&mut self) {
+        let (line, column) = self.line_and_column();
+        let (mut lines, newline) = self.split_lines();
+        if line + 1 < lines.len() {
+            lines.swap(line, line + 1);
+            self.set_lines(&lines, newline, line + 1, column);
+        }
+    }
+
+    /// Inserts a copy of the current line below it and moves the cursor onto the copy
+    pub fn duplicate_line(&mut self) {
+        let (line, column) = self.line_and_column();
+        let (mut lines, newline) = self.split_lines();
+        lines.insert(line + 1, lines[line].clone());
+        self.set_lines(&lines, newline, line + 1, column);
+    }
+
+    /// Joins the next line onto the end of the current one (vi `J`)
+    ///
+    /// Leading whitespace of the joined line is replaced by a single space,
+    /// which is omitted if either side of the join is empty or the current line
+    /// already ends in whitespace. The cursor is placed at the join point.
+    pub fn join_lines(&mut self) {
+        let (line, _) = self.line_and_column();
+        let (mut lines, newline) = self.split_lines();
+        if line + 1 < lines.len() {
+            let next = lines.remove(line + 1);
+            let next = next.trim_start();
+            let current = &mut lines[line];
+            let join_point = current.len();
+            if !current.is_empty() && !next.is_empty() && !current.ends_with(char::is_whitespace) {
+                current.push(' ');
+            }
+            current.push_str(next);
+            self.set_lines(&lines, newline, line, join_point);
+        }
+    }
+
+    /// Prepends `unit` to every line overlapping `range`
+    ///
+    /// The cursor keeps its position relative to the surrounding text
+    pub fn indent_lines(&mut self, range: Range<usize>, unit: &str) {
+        for start in self.line_starts_in(range).into_iter().rev() {
+            self.text.insert_str(start, unit);
+            if start <= self.insertion_point {
+                self.insertion_point += unit.len();
+            }
+        }
+    }
+
+    /// Removes one level of indentation from every line overlapping `range`
+    ///
+    /// Strips `unit` if the line starts with it, otherwise a single tab or as many
+    /// leading spaces as `unit` is wide.
+    pub fn dedent_lines(&mut self, range: Range<usize>, unit: &str) {
+        for start in self.line_starts_in(range).into_iter().rev() {
+            let line = self.text.slice(start..);
+            let removed = if !unit.is_empty() && line.starts_with(unit) {
+                unit.len()
+            } else if line.starts_with('\t') {
+                1
+            } else {
+                line.chars()
+                    .take(unit.len())
+                    .take_while(|c| *c == ' ')
+                    .count()
+            };
+            self.clear_range(start..start + removed);
+            if self.insertion_point > start {
+                self.insertion_point -= removed.min(self.insertion_point - start);
+            }
+        }
+    }
+
+    /// Comments out every line overlapping `range` by prepending `prefix`
+    ///
+    /// If all of these lines are already commented, the prefix is removed instead
+    pub fn toggle_comment(&mut self, range: Range<usize>, prefix: &str) {
+        if prefix.is_empty() {
+            return;
+        }
+        let starts = self.line_starts_in(range);
+        let commented = starts
+            .iter()
+            .all(|start| self.text.slice(*start..).starts_with(prefix));
+        for start in starts.into_iter().rev() {
+            if commented {
+                self.clear_range(start..start + prefix.len());
+                if self.insertion_point > start {
+                    self.insertion_point -= prefix.len().min(self.insertion_point - start);
+                }
+            } else {
+                self.text.insert_str(start, prefix);
+                if start <= self.insertion_point {
+                    self.insertion_point += prefix.len();
+                }
+            }
+        }
+    }
+
+    /// Start offsets of all lines overlapping `range`, in ascending order
+    fn line_starts_in(&self, range: Range<usize>) -> Vec<usize> {
+        let first = self
+            .text
+            .slice(..range.start)
+            .rfind('\n')
+            .map_or(0, |offset| offset + 1);
+        std::iter::once(first)
+            .chain(
+                self.text
+                    .slice(range.clone())
+                    .match_indices('\n')
+                    .map(|(i, _)| range.start + i + 1)
+                    .filter(|start| *start < range.end),
+            )
+            .collect()
+    }
+
+    /// Zero-based index of the current line and the byte offset of the cursor within it
+    fn line_and_column(&self) -> (usize, usize) {
+        (
+            self.line(),
+            self.insertion_point - self.current_line_range().start,
+        )
+    }
+
+    /// Splits the buffer into its lines without their terminators
+    ///
+    /// Returns the line terminator used by the buffer to reassemble it
+    fn split_lines(&self) -> (Vec<String>, &'static str) {
+        let newline = if self.text.as_str().contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let lines = self
+            .text
+            .as_str()
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+            .collect();
+        (lines, newline)
+    }
+
+    /// Replaces the buffer with `lines` and places the cursor on `line` at `column`
+    ///
+    /// The column is clamped to the length of the target line
+    fn set_lines(&mut self, lines: &[String], newline: &str, line: usize, column: usize) {
+        let line_start: usize = lines[..line].iter().map(|l| l.len() + newline.len()).sum();
+        let target = &lines[line];
+        let mut column = column.min(target.len());
+        while !target.is_char_boundary(column) {
+            column -= 1;
+        }
+        self.text = GapBuffer::from(lines.join(newline));
+        self.insertion_point = line_start + column;
+    }
+
     /// Checks to see if the cursor is on the first line of the buffer
     pub fn is_cursor_at_first_line(&self) -> bool {
-        !self.get_buffer()[0..self.insertion_point()].contains('\n')
+        self.text.line_of(self.insertion_point) == 0
     }
 
     /// Checks to see if the cursor is on the last line of the buffer
     pub fn is_cursor_at_last_line(&self) -> bool {
-        !self.get_buffer()[self.insertion_point()..].contains('\n')
+        self.text.line_feed_from(self.insertion_point).is_none()
     }
 
     /// Finds index for the first occurrence of a char to the right of offset
@@ -686,9 +1158,12 @@ impl LineBuffer {
         let range = if current_line {
             char_offset..self.current_line_range().end
         } else {
-            char_offset..self.lines.len()
+            char_offset..self.text.len()
         };
-        self.lines[range].find(c).map(|index| index + char_offset)
+        self.text
+            .slice(range)
+            .find(c)
+            .map(|index| index + char_offset)
     }
 
     /// Finds index for the first occurrence of a char to the left of offset
@@ -698,7 +1173,10 @@ impl LineBuffer {
         } else {
             0..self.insertion_point()
         };
-        self.lines[range.clone()].rfind(c).map(|i| i + range.start)
+        self.text
+            .slice(range.clone())
+            .rfind(c)
+            .map(|i| i + range.start)
     }
 
     /// Moves the insertion point until the next char to the right
@@ -769,6 +1247,84 @@ impl LineBuffer {
     }
 }
 
+// The range of the first number of `line` that ends after `cursor`, including the `0x` of
+// hexadecimal numbers and the sign of negative ones
+fn find_number(line: &str, cursor: usize) -> Option<Range<usize>> {
+    let bytes = line.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        let start = index;
+        let is_hex = bytes[index] == b'0'
+            && matches!(bytes.get(index + 1), Some(b'x' | b'X'))
+            && bytes.get(index + 2).map_or(false, u8::is_ascii_hexdigit);
+        let end = if is_hex {
+            index += 2;
+            while bytes.get(index).map_or(false, u8::is_ascii_hexdigit) {
+                index += 1;
+            }
+            index
+        } else if bytes[index].is_ascii_digit() {
+            while bytes.get(index).map_or(false, u8::is_ascii_digit) {
+                index += 1;
+            }
+            index
+        } else {
+            index += 1;
+            continue;
+        };
+        if end > cursor {
+            // A `-` is the sign unless it joins words like in `x-1`
+            let negative = !is_hex
+                && start > 0
+                && bytes[start - 1] == b'-'
+                && (start < 2 || !bytes[start - 2].is_ascii_alphanumeric());
+            return Some(if negative { start - 1 } else { start }..end);
+        }
+    }
+    None
+}
+
+// `number` as found by `find_number` plus `delta`, keeping its format
+fn add_to_number(number: &str, delta: i64) -> Option<String> {
+    if let Some(digits) = number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix("0X"))
+    {
+        // Hexadecimal numbers are unsigned and wrap around, like in vim
+        let value = u64::from_str_radix(digits, 16)
+            .ok()?
+            .wrapping_add(delta as u64);
+        let uppercase = digits
+            .chars()
+            .rev()
+            .find(char::is_ascii_alphabetic)
+            .map_or(false, |c| c.is_ascii_uppercase());
+        let width = digits.len();
+        let digits = if uppercase {
+            format!("{value:0width$X}")
+        } else {
+            format!("{value:0width$x}")
+        };
+        return Some(format!("{}{}", &number[..2], digits));
+    }
+
+    let digits = number.trim_start_matches('-');
+    let value = digits.parse::<i64>().ok()?;
+    let value = if number.starts_with('-') {
+        -value
+    } else {
+        value
+    };
+    let value = value.saturating_add(delta);
+    let width = if digits.len() > 1 && digits.starts_with('0') {
+        digits.len()
+    } else {
+        0
+    };
+    let sign = if value < 0 { "-" } else { "" };
+    Some(format!("{sign}{:0width$}", value.unsigned_abs()))
+}
+
 /// Match any sequence of characters that are considered a word boundary
 fn is_whitespace_str(s: &str) -> bool {
     s.chars().all(char::is_whitespace)
@@ -1023,6 +1579,30 @@ mod test {
         line_buffer.assert_valid();
     }
 
+    #[rstest]
+    #[case("a test", 2, 2, "a TEst", 4)]
+    #[case("a test", 2, 9, "a TEST", 5)]
+    #[case("ab\ncd", 0, 5, "AB\ncd", 1)]
+    #[case("ab\ncd", 2, 1, "ab\ncd", 2)]
+    #[case("", 0, 1, "", 0)]
+    fn switchcase_chars(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] count: usize,
+        #[case] output: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+        line_buffer.switchcase_chars(count);
+
+        let mut expected = buffer_with(output);
+        expected.set_insertion_point(out_location);
+
+        assert_eq!(expected, line_buffer);
+        line_buffer.assert_valid();
+    }
+
     #[rstest]
     #[case("This is a test", 13, "This is a tets", 14)]
     #[case("This is a test", 14, "This is a tets", 14)] // NOTE: Swapping works in opposite direction at last index
@@ -1046,6 +1626,38 @@ mod test {
         line_buffer.assert_valid();
     }
 
+    #[rstest]
+    #[case("x = 41", 0, 1, "x = 42", 5)]
+    #[case("x = 41", 5, -42, "x = -1", 5)]
+    #[case("x = -1", 4, 3, "x = 2", 4)]
+    #[case("x-1", 0, 1, "x-2", 2)]
+    #[case("9 and 9", 2, 1, "9 and 10", 7)]
+    #[case("007", 0, 1, "008", 2)]
+    #[case("a 0xff", 0, 1, "a 0x100", 6)]
+    #[case("0x0F", 1, 1, "0x10", 3)]
+    #[case("0xFF", 0, 1, "0x100", 4)]
+    #[case("0x00", 3, -1, "0xffffffffffffffff", 17)]
+    #[case("no numbers", 0, 1, "no numbers", 0)]
+    #[case("1\nline 2", 2, 1, "1\nline 3", 7)]
+    fn increment_number_works(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] delta: i64,
+        #[case] output: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        line_buffer.increment_number(delta);
+
+        let mut expected = buffer_with(output);
+        expected.set_insertion_point(out_location);
+
+        assert_eq!(line_buffer, expected);
+        line_buffer.assert_valid();
+    }
+
     #[rstest]
     #[case("This is a test", 8, "This is test a", 8)]
     #[case("This is a test", 0, "is This a test", 0)]
@@ -1068,6 +1680,55 @@ mod test {
         line_buffer.assert_valid();
     }
 
+    #[rstest]
+    #[case("foo bar", 7, "bar foo", 7)]
+    #[case("foo bar", 5, "bar foo", 7)]
+    #[case("foo bar", 4, "bar foo", 7)]
+    #[case("foo bar", 0, "bar foo", 7)]
+    #[case("ls foo, bar!", 12, "ls bar, foo!", 11)]
+    #[case("ls foo bar  ", 12, "ls bar foo  ", 10)]
+    #[case("foo", 3, "foo", 3)]
+    fn transpose_words_works(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] output: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        line_buffer.transpose_words();
+
+        let mut expected = buffer_with(output);
+        expected.set_insertion_point(out_location);
+
+        assert_eq!(line_buffer, expected);
+        line_buffer.assert_valid();
+    }
+
+    #[rstest]
+    #[case("abcd", 1, 2, "bcad", 3)]
+    #[case("abcd", 3, 5, "abdc", 4)]
+    #[case("abcd", 4, 2, "abdc", 4)]
+    fn transpose_graphemes_works(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] count: usize,
+        #[case] output: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        line_buffer.transpose_graphemes(count);
+
+        let mut expected = buffer_with(output);
+        expected.set_insertion_point(out_location);
+
+        assert_eq!(line_buffer, expected);
+        line_buffer.assert_valid();
+    }
+
     #[rstest]
     #[case("line 1\nline 2", 7, 0)]
     #[case("line 1\nline 2", 8, 1)]
@@ -1121,6 +1782,164 @@ mod test {
         line_buffer.assert_valid();
     }
 
+    #[rstest]
+    #[case("line 1", 3, "line 1", 3)]
+    #[case("line 1\nline 2", 3, "line 1\nline 2", 3)]
+    #[case("line 1\nline 2", 10, "line 2\nline 1", 3)]
+    #[case("a\nline 2\nb", 9, "a\nb\nline 2", 2)]
+    #[case("line 1\r\nline 2", 11, "line 2\r\nline 1", 3)]
+    fn swap_line_up_works(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] expected: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        line_buffer.swap_line_up();
+
+        assert_eq!(line_buffer.get_buffer(), expected);
+        assert_eq!(line_buffer.insertion_point(), out_location);
+        line_buffer.assert_valid();
+    }
+
+    #[rstest]
+    #[case("line 1", 3, "line 1", 3)]
+    #[case("line 1\nline 2", 10, "line 1\nline 2", 10)]
+    #[case("line 1\nline 2", 3, "line 2\nline 1", 10)]
+    #[case("a\nline 2\nb", 3, "a\nb\nline 2", 5)]
+    fn swap_line_down_works(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] expected: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        line_buffer.swap_line_down();
+
+        assert_eq!(line_buffer.get_buffer(), expected);
+        assert_eq!(line_buffer.insertion_point(), out_location);
+        line_buffer.assert_valid();
+    }
+
+    #[rstest]
+    #[case("", 0, "\n", 1)]
+    #[case("line 1", 3, "line 1\nline 1", 10)]
+    #[case("a\nline 2\nb", 4, "a\nline 2\nline 2\nb", 11)]
+    #[case("a\nb", 3, "a\nb\nb", 5)]
+    fn duplicate_line_works(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] expected: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        line_buffer.duplicate_line();
+
+        assert_eq!(line_buffer.get_buffer(), expected);
+        assert_eq!(line_buffer.insertion_point(), out_location);
+        line_buffer.assert_valid();
+    }
+
+    #[rstest]
+    #[case("line 1", 3, "line 1", 3)]
+    #[case("line 1\nline 2", 2, "line 1 line 2", 6)]
+    #[case("line 1\n    line 2", 0, "line 1 line 2", 6)]
+    #[case("line 1 \nline 2", 0, "line 1 line 2", 7)]
+    #[case("line 1\n\nline 3", 0, "line 1\nline 3", 6)]
+    #[case("\nline 2", 0, "line 2", 0)]
+    fn join_lines_works(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] expected: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        line_buffer.join_lines();
+
+        assert_eq!(line_buffer.get_buffer(), expected);
+        assert_eq!(line_buffer.insertion_point(), out_location);
+        line_buffer.assert_valid();
+    }
+
+    #[rstest]
+    #[case("foo", 1..1, 1, "    foo", 5)]
+    #[case("foo", 0..0, 0, "    foo", 4)]
+    #[case("foo\nbar", 5..5, 5, "foo\n    bar", 9)]
+    #[case("foo\nbar\nbaz", 1..5, 5, "    foo\n    bar\nbaz", 13)]
+    #[case("foo\nbar", 0..4, 0, "    foo\nbar", 4)]
+    fn indent_lines_works(
+        #[case] input: &str,
+        #[case] range: Range<usize>,
+        #[case] in_location: usize,
+        #[case] expected: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        line_buffer.indent_lines(range, "    ");
+
+        assert_eq!(line_buffer.get_buffer(), expected);
+        assert_eq!(line_buffer.insertion_point(), out_location);
+        line_buffer.assert_valid();
+    }
+
+    #[rstest]
+    #[case("    foo", 0..0, 6, "foo", 2)]
+    #[case("      foo", 0..0, 8, "  foo", 4)]
+    #[case("  foo", 0..0, 1, "foo", 0)]
+    #[case("\t\tfoo", 0..0, 4, "\tfoo", 3)]
+    #[case("foo", 0..0, 1, "foo", 1)]
+    #[case("    foo\n    bar", 2..10, 10, "foo\nbar", 4)]
+    fn dedent_lines_works(
+        #[case] input: &str,
+        #[case] range: Range<usize>,
+        #[case] in_location: usize,
+        #[case] expected: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        line_buffer.dedent_lines(range, "    ");
+
+        assert_eq!(line_buffer.get_buffer(), expected);
+        assert_eq!(line_buffer.insertion_point(), out_location);
+        line_buffer.assert_valid();
+    }
+
+    #[rstest]
+    #[case("ls", 2, 2..2, "#ls", 3)]
+    #[case("#ls", 3, 3..3, "ls", 2)]
+    #[case("#ls", 0, 0..0, "ls", 0)]
+    #[case("foo\nbar", 5, 5..5, "foo\n#bar", 6)]
+    #[case("foo\n#bar", 1, 1..6, "#foo\n##bar", 2)]
+    #[case("#foo\n#bar", 1, 1..6, "foo\nbar", 0)]
+    fn toggle_comment_works(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] range: Range<usize>,
+        #[case] expected: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        line_buffer.toggle_comment(range, "#");
+
+        assert_eq!(line_buffer.get_buffer(), expected);
+        assert_eq!(line_buffer.insertion_point(), out_location);
+        line_buffer.assert_valid();
+    }
+
     #[rstest]
     #[case("line", 4, true)]
     #[case("line 1\nline 2\nline 3", 0, true)]
@@ -1223,7 +2042,7 @@ mod test {
 
         line_buffer.delete_right_until_char(c, current_line);
 
-        assert_eq!(line_buffer.lines, expected);
+        assert_eq!(line_buffer.get_buffer(), expected);
         line_buffer.assert_valid();
     }
 
@@ -1243,7 +2062,7 @@ mod test {
 
         line_buffer.delete_right_before_char(c, current_line);
 
-        assert_eq!(line_buffer.lines, expected);
+        assert_eq!(line_buffer.get_buffer(), expected);
         line_buffer.assert_valid();
     }
 
@@ -1305,7 +2124,7 @@ mod test {
 
         line_buffer.delete_left_until_char(c, current_line);
 
-        assert_eq!(line_buffer.lines, expected);
+        assert_eq!(line_buffer.get_buffer(), expected);
         line_buffer.assert_valid();
     }
 
@@ -1325,7 +2144,7 @@ mod test {
 
         line_buffer.delete_left_before_char(c, current_line);
 
-        assert_eq!(line_buffer.lines, expected);
+        assert_eq!(line_buffer.get_buffer(), expected);
         line_buffer.assert_valid();
     }
 
@@ -1455,6 +2274,68 @@ mod test {
         assert_eq!(line_buffer.current_line_range(), expected);
     }
 
+    #[rstest]
+    #[case("", 0, 0..0)]
+    #[case("one line", 3, 0..8)]
+    #[case("a\nb\n\nc\nd", 2, 0..5)]
+    #[case("a\nb\n\nc\nd", 7, 3..8)]
+    #[case("a\nb\n\n  \nc\n", 4, 4..8)]
+    #[case("a\n\nb\n", 3, 2..5)]
+    #[case("a\n\nb\n\n", 3, 3..6)]
+    fn test_current_paragraph_range(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] expected: Range<usize>,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        assert_eq!(line_buffer.current_paragraph_range(), expected);
+    }
+
+    #[rstest]
+    #[case("", 0, 0, 0)]
+    #[case("a\nb\n\nc\nd", 0, 0, 4)]
+    #[case("a\nb\n\nc\nd", 2, 0, 4)]
+    #[case("a\nb\n\nc\nd", 4, 0, 8)]
+    #[case("a\nb\n\nc\nd", 5, 4, 8)]
+    #[case("a\n\n\nb\n\nc", 3, 0, 6)]
+    #[case("a\n\n\nb\n\nc", 5, 3, 6)]
+    fn test_paragraph_indices(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] left: usize,
+        #[case] right: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        assert_eq!(line_buffer.paragraph_left_index(), left);
+        assert_eq!(line_buffer.paragraph_right_index(), right);
+    }
+
+    #[rstest]
+    #[case("One. Two! Three", 0, 0, 5)]
+    #[case("One. Two! Three", 7, 5, 10)]
+    #[case("One. Two! Three", 5, 0, 10)]
+    #[case("One. Two! Three", 12, 10, 15)]
+    #[case("Say (\"hi.\") Then", 2, 0, 12)]
+    #[case("e.g. this.that", 6, 5, 14)]
+    #[case("One\n\nTwo", 1, 0, 4)]
+    #[case("One\n\nTwo", 4, 0, 5)]
+    fn test_sentence_indices(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] left: usize,
+        #[case] right: usize,
+    ) {
+        let mut line_buffer = buffer_with(input);
+        line_buffer.set_insertion_point(in_location);
+
+        assert_eq!(line_buffer.sentence_left_index(), left);
+        assert_eq!(line_buffer.sentence_right_index(), right);
+    }
+
     #[rstest]
     #[case("This is a test", 7, "This is", 7)]
     #[case("This is a test\nunrelated", 7, "This is\nunrelated", 7)]
@@ -1597,4 +2478,13 @@ mod test {
 
         assert_eq!(index, expected);
     }
+
+    #[test]
+    fn zeroize_empties_the_buffer() {
+        let mut line_buffer = buffer_with("hunter2");
+        line_buffer.zeroize();
+
+        assert_eq!(line_buffer.get_buffer(), "");
+        assert_eq!(line_buffer.insertion_point(), 0);
+    }
 }
@@ -1,10 +1,15 @@
+mod auto_pairs;
 mod clip_buffer;
 mod edit_stack;
 mod editor;
+mod gap_buffer;
 mod line_buffer;
+mod read_only;
 
+pub use auto_pairs::{AutoPairFilter, AutoPairs};
 #[cfg(feature = "system_clipboard")]
 pub(crate) use clip_buffer::get_system_clipboard;
 pub(crate) use clip_buffer::{get_local_clipboard, Clipboard, ClipboardMode};
 pub use editor::Editor;
 pub use line_buffer::LineBuffer;
+pub(crate) use read_only::ReadOnlyRanges;
@@ -0,0 +1,159 @@
+use std::ops::Range;
+
+/// Byte ranges of the buffer that edits can't change, see [`crate::Reedline::set_read_only`]
+///
+/// The ranges move along with the changes before them. A range at the start of the buffer
+/// is a prefix: nothing can be inserted in front of it either.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ReadOnlyRanges {
+    ranges: Vec<Range<usize>>,
+}
+
+impl ReadOnlyRanges {
+    /// The `ranges` of `buffer`, cut to the buffer and joined where they overlap
+    ///
+    /// Ranges next to each other stay apart, an empty field is left between them.
+    pub(crate) fn new(ranges: impl IntoIterator<Item = Range<usize>>, buffer: &str) -> Self {
+        let boundary = |mut index: usize| {
+            index = index.min(buffer.len());
+            while !buffer.is_char_boundary(index) {
+                index += 1;
+            }
+            index
+        };
+        let mut ranges: Vec<_> = ranges
+            .into_iter()
+            .map(|range| boundary(range.start)..boundary(range.end))
+            .filter(|range| range.start < range.end)
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        let mut joined: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match joined.last_mut() {
+                Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+                _ => joined.push(range),
+            }
+        }
+        Self { ranges: joined }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The text between the ranges of a buffer of `len` bytes, except in front of a prefix
+    pub(crate) fn fields(&self, len: usize) -> Vec<Range<usize>> {
+        let mut fields = Vec::with_capacity(self.ranges.len() + 1);
+        let mut start = 0;
+        for range in &self.ranges {
+            if range.start > 0 {
+                fields.push(start..range.start);
+            }
+            start = range.end;
+        }
+        fields.push(start..len);
+        fields
+    }
+
+    /// Move the ranges along with the change from `old` to `new`, with the `cursor` in
+    /// `new`, or `false` if the change touches a range and has to be undone
+    pub(crate) fn follow_change(&mut self, old: &str, new: &str, cursor: usize) -> bool {
+        if old == new {
+            return true;
+        }
+        let (start, old_end, new_end) = changed_range(old, new, cursor);
+        let touches = |range: &Range<usize>| {
+            if start == old_end {
+                start < range.end && (range.start < start || range.start == 0)
+            } else {
+                start < range.end && range.start < old_end
+            }
+        };
+        if self.ranges.iter().any(touches) {
+            return false;
+        }
+        for range in self
+            .ranges
+            .iter_mut()
+            .filter(|range| range.start >= old_end)
+        {
+            *range = range.start - old_end + new_end..range.end - old_end + new_end;
+        }
+        true
+    }
+
+    /// Where the `cursor`, moved from `previous`, goes when it lands in a range: past its
+    /// end when moving right or into a prefix, in front of it when moving left
+    pub(crate) fn escape(&self, cursor: usize, previous: usize) -> usize {
+        let inside = self
+            .ranges
+            .iter()
+            .find(|range| (range.start < cursor || range.start == 0) && cursor < range.end);
+        match inside {
+            Some(range) if cursor >= previous || range.start == 0 => range.end,
+            Some(range) => range.start,
+            None => cursor,
+        }
+    }
+}
+
+// The bytes `start..old_end` of `old` replaced by `start..new_end` of `new`, found from the
+// text around them that stayed the same. The change starts at the cursor at the latest,
+// which settles where repeated characters were typed or removed.
+fn changed_range(old: &str, new: &str, cursor: usize) -> (usize, usize, usize) {
+    let start = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, old_char), new_char)| old_char != new_char)
+        .map_or(old.len().min(new.len()), |((index, _), _)| index)
+        .min(cursor);
+    let suffix: usize = old[start..]
+        .chars()
+        .rev()
+        .zip(new[start..].chars().rev())
+        .take_while(|(old_char, new_char)| old_char == new_char)
+        .map(|(old_char, _)| old_char.len_utf8())
+        .sum();
+    (start, old.len() - suffix, new.len() - suffix)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("sudo ", vec![0..5], "sudo x", 6, true, vec![0..5])]
+    #[case("sudo ls", vec![0..5], "sudo l", 6, true, vec![0..5])]
+    #[case("sudo ", vec![0..5], "sudo", 4, false, vec![0..5])]
+    #[case("sudo ", vec![0..5], "xsudo ", 1, false, vec![0..5])]
+    #[case("sudo ", vec![0..5], "sud o ", 4, false, vec![0..5])]
+    #[case("ab: c", vec![1..4], "aab: c", 2, true, vec![2..5])]
+    #[case("ab: c", vec![1..4], "ab:c", 3, false, vec![1..4])]
+    #[case("abb", vec![2..3], "ab", 1, true, vec![1..2])]
+    fn changes_around_the_ranges_move_them(
+        #[case] old: &str,
+        #[case] ranges: Vec<Range<usize>>,
+        #[case] new: &str,
+        #[case] cursor: usize,
+        #[case] accepted: bool,
+        #[case] expected: Vec<Range<usize>>,
+    ) {
+        let mut read_only = ReadOnlyRanges::new(ranges, old);
+        assert_eq!(read_only.follow_change(old, new, cursor), accepted);
+        assert_eq!(read_only.ranges, expected);
+    }
+
+    #[test]
+    fn the_cursor_skips_over_the_ranges() {
+        let read_only = ReadOnlyRanges::new([0..5, 8..10, 9..12, 12..13], "sudo ls -la --all");
+        assert_eq!(read_only.ranges, vec![0..5, 8..12, 12..13]);
+        assert_eq!(read_only.fields(17), vec![5..8, 12..12, 13..17]);
+        assert_eq!(read_only.escape(0, 6), 5);
+        assert_eq!(read_only.escape(9, 7), 12);
+        assert_eq!(read_only.escape(9, 13), 8);
+        assert_eq!(read_only.escape(8, 13), 8);
+        assert_eq!(read_only.escape(6, 13), 6);
+    }
+}
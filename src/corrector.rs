@@ -0,0 +1,32 @@
+/// Suggests a corrected line when the buffer is submitted, e.g. for a misspelled command
+///
+/// If it returns a different line, [`crate::Reedline::read_line()`] does not return right
+/// away but asks below the buffer whether the suggestion should be used: `y` submits the
+/// suggestion, `n` the line as typed and `e` puts the suggestion into the buffer to edit it
+/// further. Esc goes back to editing the line as typed.
+pub trait Corrector: Send {
+    /// The corrected version of `line`, or `None` if it should be submitted as is
+    fn correct(&mut self, line: &str) -> Option<String>;
+}
+
+/// Answer to the question whether to use the suggestion of the [`Corrector`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CorrectionAnswer {
+    /// Submit the suggestion
+    Accept,
+    /// Submit the line as typed
+    Reject,
+    /// Edit the suggestion before submitting it
+    Edit,
+}
+
+impl CorrectionAnswer {
+    pub(crate) fn from_char(c: char) -> Option<Self> {
+        match c {
+            'y' | 'Y' => Some(Self::Accept),
+            'n' | 'N' => Some(Self::Reject),
+            'e' | 'E' => Some(Self::Edit),
+            _ => None,
+        }
+    }
+}
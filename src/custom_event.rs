@@ -0,0 +1,11 @@
+use crate::core_editor::Editor;
+
+/// Handler for the application defined [`crate::ReedlineEvent::Custom`] events
+///
+/// Lets keybindings trigger application specific actions (toggling panes, inserting a
+/// timestamp, ...) without ending [`crate::Reedline::read_line()`]. The buffer is repainted
+/// after every handled event.
+pub trait CustomEventHandler: Send {
+    /// Handle the custom event called `name`, the `editor` gives access to the current buffer
+    fn handle(&mut self, name: &str, editor: &mut Editor);
+}
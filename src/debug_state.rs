@@ -0,0 +1,40 @@
+//! A snapshot of the engine state, for diagnosing reports of unexpected behavior
+use crate::{PromptEditMode, ReedlineEvent};
+use std::fmt::{self, Display};
+
+/// The state of the engine at one point, returned by [`crate::Reedline::debug_state`]
+///
+/// Its [`Display`] is what the debug panel of [`ReedlineEvent::ToggleDebugPanel`] shows,
+/// one field per line, so it can be pasted as is into a bug report.
+#[derive(Debug, Clone)]
+pub struct EngineStateSnapshot {
+    /// The text of the buffer
+    pub buffer: String,
+    /// The byte position of the cursor in the buffer
+    pub cursor: usize,
+    /// The mode of the edit mode, as shown by the prompt
+    pub mode: PromptEditMode,
+    /// The name of the active menu
+    pub active_menu: Option<String>,
+    /// How many changes an undo can revert
+    pub undo_depth: usize,
+    /// The keys of a started but incomplete sequence, e.g. `d` in vi normal mode
+    pub pending_keys: String,
+    /// The last event handled by the engine
+    pub last_event: Option<ReedlineEvent>,
+}
+
+impl Display for EngineStateSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "buffer: {:?}", self.buffer)?;
+        writeln!(f, "cursor: {}", self.cursor)?;
+        writeln!(f, "mode: {}", self.mode)?;
+        writeln!(f, "menu: {}", self.active_menu.as_deref().unwrap_or("none"))?;
+        writeln!(f, "undo depth: {}", self.undo_depth)?;
+        writeln!(f, "pending keys: {:?}", self.pending_keys)?;
+        match &self.last_event {
+            Some(event) => write!(f, "last event: {event:?}"),
+            None => write!(f, "last event: none"),
+        }
+    }
+}
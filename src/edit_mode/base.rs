@@ -1,6 +1,6 @@
 use crate::{
     enums::{ReedlineEvent, ReedlineRawEvent},
-    PromptEditMode,
+    Keybindings, PromptEditMode,
 };
 
 /// Define the style of parsing for the edit events
@@ -13,4 +13,40 @@ pub trait EditMode: Send {
 
     /// What to display in the prompt indicator
     fn edit_mode(&self) -> PromptEditMode;
+
+    /// Whether the keys parsed last started a sequence that is not complete yet, e.g. `d`
+    /// in vi normal mode
+    ///
+    /// The keys of such a sequence are not reported as unhandled input
+    fn has_pending_keys(&self) -> bool {
+        false
+    }
+
+    /// The keys of the incomplete sequence of [`EditMode::has_pending_keys`], e.g. `2d`
+    fn pending_keys(&self) -> String {
+        String::new()
+    }
+
+    /// Called when the editor starts reading a new line, e.g. to forget state kept for a
+    /// single line
+    fn start_line(&mut self) {}
+
+    /// Called when the editor leaves a line typed below the buffer that the edit mode opened,
+    /// e.g. the ex command line of vi, to return to the mode it was opened from
+    fn leave_command_line(&mut self) {}
+
+    /// The keybindings of the edit mode, labelled by the mode they apply in
+    ///
+    /// Used for introspection, e.g. by `Reedline::describe_keybindings`
+    fn keybindings(&self) -> Vec<(&str, &Keybindings)> {
+        Vec::new()
+    }
+
+    /// Mutable access to the keybindings of the edit mode, labelled like in
+    /// [`EditMode::keybindings`]
+    ///
+    /// Used to change bindings of a running editor, e.g. by `Reedline::update_keybindings`
+    fn keybindings_mut(&mut self) -> Vec<(&str, &mut Keybindings)> {
+        Vec::new()
+    }
 }
@@ -1,6 +1,8 @@
+use crate::{PromptEditMode, PromptViMode};
 use crossterm::cursor::SetCursorStyle;
 
-/// Maps cursor shapes to each edit mode (emacs, vi normal & vi insert).
+/// Maps cursor shapes to each edit mode (emacs, vi normal, vi visual & vi insert) and to an
+/// active menu.
 /// If any of the fields is `None`, the cursor won't get changed by Reedline for that mode.
 #[derive(Default)]
 pub struct CursorConfig {
@@ -8,6 +10,58 @@ pub struct CursorConfig {
     pub vi_insert: Option<SetCursorStyle>,
     /// The cursor to be used when in vi normal mode
     pub vi_normal: Option<SetCursorStyle>,
+    /// The cursor to be used when in vi normal mode with a selection
+    pub vi_visual: Option<SetCursorStyle>,
     /// The cursor to be used when in emacs mode
     pub emacs: Option<SetCursorStyle>,
+    /// The cursor to be used while a menu is active, in any edit mode
+    pub menu: Option<SetCursorStyle>,
+}
+
+impl CursorConfig {
+    pub(crate) fn shape(
+        &self,
+        mode: &PromptEditMode,
+        selecting: bool,
+        menu_active: bool,
+    ) -> Option<SetCursorStyle> {
+        if menu_active && self.menu.is_some() {
+            return self.menu;
+        }
+        match mode {
+            PromptEditMode::Emacs => self.emacs,
+            PromptEditMode::Vi(PromptViMode::Insert) => self.vi_insert,
+            PromptEditMode::Vi(PromptViMode::Normal | PromptViMode::Pending(_)) if selecting => {
+                self.vi_visual.or(self.vi_normal)
+            }
+            PromptEditMode::Vi(PromptViMode::Normal | PromptViMode::Pending(_)) => self.vi_normal,
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossterm::Command;
+
+    #[test]
+    fn the_menu_and_the_selection_pick_their_shapes() {
+        let config = CursorConfig {
+            vi_normal: Some(SetCursorStyle::SteadyBlock),
+            menu: Some(SetCursorStyle::SteadyUnderScore),
+            ..CursorConfig::default()
+        };
+        let normal = PromptEditMode::Vi(PromptViMode::Normal);
+        let shape = |mode, selecting, menu_active| {
+            config.shape(mode, selecting, menu_active).map(|shape| {
+                let mut ansi = String::new();
+                shape.write_ansi(&mut ansi).unwrap();
+                ansi
+            })
+        };
+        assert_eq!(shape(&normal, true, false), Some("\x1b[2 q".into()));
+        assert_eq!(shape(&normal, false, true), Some("\x1b[4 q".into()));
+        assert_eq!(shape(&PromptEditMode::Emacs, false, false), None);
+    }
 }
@@ -87,6 +87,8 @@ pub fn default_emacs_keybindings() -> Keybindings {
         ]),
     );
     // Edits
+    kb.add_binding(KM::ALT, KC::Up, edit_bind(EC::MoveLineUp));
+    kb.add_binding(KM::ALT, KC::Down, edit_bind(EC::MoveLineDown));
     kb.add_binding(KM::ALT, KC::Delete, edit_bind(EC::DeleteWord));
     kb.add_binding(KM::ALT, KC::Backspace, edit_bind(EC::BackspaceWord));
     kb.add_binding(
@@ -98,6 +100,22 @@ pub fn default_emacs_keybindings() -> Keybindings {
     kb.add_binding(KM::ALT, KC::Char('u'), edit_bind(EC::UppercaseWord));
     kb.add_binding(KM::ALT, KC::Char('l'), edit_bind(EC::LowercaseWord));
     kb.add_binding(KM::ALT, KC::Char('c'), edit_bind(EC::CapitalizeChar));
+    kb.add_binding(KM::ALT, KC::Char('t'), edit_bind(EC::TransposeWords));
+    // Comments
+    let toggle_comment = edit_bind(EC::ToggleComment {
+        prefix: String::new(),
+    });
+    kb.add_binding(KM::ALT, KC::Char('#'), toggle_comment.clone());
+    kb.add_binding(KM::ALT | KM::SHIFT, KC::Char('#'), toggle_comment);
+    // Search & replace
+    kb.add_binding(KM::ALT, KC::Char('%'), ReedlineEvent::SearchReplace);
+    kb.add_binding(
+        KM::ALT | KM::SHIFT,
+        KC::Char('%'),
+        ReedlineEvent::SearchReplace,
+    );
+    // Words of the buffer and the history, like dabbrev
+    kb.add_binding(KM::ALT, KC::Char('/'), ReedlineEvent::CompleteWordNext);
 
     kb
 }
@@ -177,6 +195,14 @@ impl EditMode for Emacs {
     fn edit_mode(&self) -> PromptEditMode {
         PromptEditMode::Emacs
     }
+
+    fn keybindings(&self) -> Vec<(&str, &Keybindings)> {
+        vec![("emacs", &self.keybindings)]
+    }
+
+    fn keybindings_mut(&mut self) -> Vec<(&str, &mut Keybindings)> {
+        vec![("emacs", &mut self.keybindings)]
+    }
 }
 
 impl Emacs {
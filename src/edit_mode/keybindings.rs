@@ -1,16 +1,257 @@
 use {
     crate::{enums::ReedlineEvent, EditCommand},
-    crossterm::event::{KeyCode, KeyModifiers},
-    serde::{Deserialize, Serialize},
-    std::collections::HashMap,
+    crossterm::event::{KeyCode, KeyModifiers, MediaKeyCode, ModifierKeyCode},
+    serde::{Deserialize, Deserializer, Serialize, Serializer},
+    std::{cmp::Ordering, collections::HashMap, fmt::Display, str::FromStr},
 };
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+/// A key together with its modifiers, the key of a binding in [`Keybindings`]
+///
+/// Its text form, also used for serialization, lists the modifiers followed by the key, all
+/// separated by `-`, e.g. `"ctrl-alt-b"`, `"shift-tab"`, `"f5"` or `"ctrl--"`
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct KeyCombination {
+    /// Modifier keys held down
     pub modifier: KeyModifiers,
+    /// The pressed key
     pub key_code: KeyCode,
 }
 
+impl KeyCombination {
+    /// The combination as it is stored and looked up in [`Keybindings`]
+    ///
+    /// Letters pressed together with `Ctrl` or `Alt` are lowercased, which is how the edit
+    /// modes and most terminals report them, so that a binding to e.g. `Ctrl-R` matches.
+    pub fn normalized(modifier: KeyModifiers, key_code: KeyCode) -> Self {
+        let key_code = match key_code {
+            KeyCode::Char(c) if modifier.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            other => other,
+        };
+        KeyCombination { modifier, key_code }
+    }
+}
+
+const MODIFIER_NAMES: [(KeyModifiers, &str); 6] = [
+    (KeyModifiers::CONTROL, "ctrl"),
+    (KeyModifiers::ALT, "alt"),
+    (KeyModifiers::SHIFT, "shift"),
+    (KeyModifiers::SUPER, "super"),
+    (KeyModifiers::HYPER, "hyper"),
+    (KeyModifiers::META, "meta"),
+];
+
+const KEY_NAMES: [(KeyCode, &str); 51] = [
+    (KeyCode::Backspace, "backspace"),
+    (KeyCode::Enter, "enter"),
+    (KeyCode::Left, "left"),
+    (KeyCode::Right, "right"),
+    (KeyCode::Up, "up"),
+    (KeyCode::Down, "down"),
+    (KeyCode::Home, "home"),
+    (KeyCode::End, "end"),
+    (KeyCode::PageUp, "pageup"),
+    (KeyCode::PageDown, "pagedown"),
+    (KeyCode::Tab, "tab"),
+    (KeyCode::BackTab, "backtab"),
+    (KeyCode::Delete, "delete"),
+    (KeyCode::Insert, "insert"),
+    (KeyCode::Esc, "esc"),
+    (KeyCode::Null, "null"),
+    (KeyCode::Char(' '), "space"),
+    (KeyCode::CapsLock, "capslock"),
+    (KeyCode::ScrollLock, "scrolllock"),
+    (KeyCode::NumLock, "numlock"),
+    (KeyCode::PrintScreen, "printscreen"),
+    (KeyCode::Pause, "pause"),
+    (KeyCode::Menu, "menu"),
+    (KeyCode::KeypadBegin, "keypadbegin"),
+    (KeyCode::Media(MediaKeyCode::Play), "mediaplay"),
+    (KeyCode::Media(MediaKeyCode::Pause), "mediapause"),
+    (KeyCode::Media(MediaKeyCode::PlayPause), "mediaplaypause"),
+    (KeyCode::Media(MediaKeyCode::Reverse), "mediareverse"),
+    (KeyCode::Media(MediaKeyCode::Stop), "mediastop"),
+    (
+        KeyCode::Media(MediaKeyCode::FastForward),
+        "mediafastforward",
+    ),
+    (KeyCode::Media(MediaKeyCode::Rewind), "mediarewind"),
+    (KeyCode::Media(MediaKeyCode::TrackNext), "mediatracknext"),
+    (
+        KeyCode::Media(MediaKeyCode::TrackPrevious),
+        "mediatrackprevious",
+    ),
+    (KeyCode::Media(MediaKeyCode::Record), "mediarecord"),
+    (
+        KeyCode::Media(MediaKeyCode::LowerVolume),
+        "medialowervolume",
+    ),
+    (
+        KeyCode::Media(MediaKeyCode::RaiseVolume),
+        "mediaraisevolume",
+    ),
+    (KeyCode::Media(MediaKeyCode::MuteVolume), "mediamutevolume"),
+    (KeyCode::Modifier(ModifierKeyCode::LeftShift), "leftshift"),
+    (KeyCode::Modifier(ModifierKeyCode::LeftControl), "leftctrl"),
+    (KeyCode::Modifier(ModifierKeyCode::LeftAlt), "leftalt"),
+    (KeyCode::Modifier(ModifierKeyCode::LeftSuper), "leftsuper"),
+    (KeyCode::Modifier(ModifierKeyCode::LeftHyper), "lefthyper"),
+    (KeyCode::Modifier(ModifierKeyCode::LeftMeta), "leftmeta"),
+    (KeyCode::Modifier(ModifierKeyCode::RightShift), "rightshift"),
+    (
+        KeyCode::Modifier(ModifierKeyCode::RightControl),
+        "rightctrl",
+    ),
+    (KeyCode::Modifier(ModifierKeyCode::RightAlt), "rightalt"),
+    (KeyCode::Modifier(ModifierKeyCode::RightSuper), "rightsuper"),
+    (KeyCode::Modifier(ModifierKeyCode::RightHyper), "righthyper"),
+    (KeyCode::Modifier(ModifierKeyCode::RightMeta), "rightmeta"),
+    (
+        KeyCode::Modifier(ModifierKeyCode::IsoLevel3Shift),
+        "isolevel3shift",
+    ),
+    (
+        KeyCode::Modifier(ModifierKeyCode::IsoLevel5Shift),
+        "isolevel5shift",
+    ),
+];
+
+impl Display for KeyCombination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (modifier, name) in MODIFIER_NAMES {
+            if self.modifier.contains(modifier) {
+                write!(f, "{name}-")?;
+            }
+        }
+
+        match KEY_NAMES
+            .iter()
+            .find(|(key_code, _)| *key_code == self.key_code)
+        {
+            Some((_, name)) => write!(f, "{name}"),
+            None => match self.key_code {
+                KeyCode::Char(c) => write!(f, "{c}"),
+                KeyCode::F(n) => write!(f, "f{n}"),
+                // Every other key is named in `KEY_NAMES`
+                other => write!(f, "{other:?}"),
+            },
+        }
+    }
+}
+
+impl FromStr for KeyCombination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // A trailing `-` is the minus key itself
+        let (modifiers, key) = match s.strip_suffix("--") {
+            Some(modifiers) => (modifiers, "-"),
+            None if s == "-" => ("", "-"),
+            None => s.rsplit_once('-').unwrap_or(("", s)),
+        };
+
+        let mut modifier = KeyModifiers::NONE;
+        for name in modifiers.split('-').filter(|name| !name.is_empty()) {
+            let name = name.to_ascii_lowercase();
+            match MODIFIER_NAMES.iter().find(|(_, known)| *known == name) {
+                Some((known, _)) => modifier |= *known,
+                None => return Err(format!("Unknown modifier `{name}` in `{s}`")),
+            }
+        }
+
+        let lowercase_key = key.to_ascii_lowercase();
+        let mut chars = key.chars();
+        let key_code = if let Some((key_code, _)) =
+            KEY_NAMES.iter().find(|(_, name)| *name == lowercase_key)
+        {
+            *key_code
+        } else if let (Some(c), None) = (chars.next(), chars.next()) {
+            KeyCode::Char(c)
+        } else if let Some(Ok(n)) = lowercase_key.strip_prefix('f').map(str::parse::<u8>) {
+            KeyCode::F(n)
+        } else {
+            return Err(format!("Unknown key `{key}` in `{s}`"));
+        };
+
+        Ok(KeyCombination::normalized(modifier, key_code))
+    }
+}
+
+impl Serialize for KeyCombination {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyCombination {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A single binding of an edit mode as reported by `Reedline::describe_keybindings`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KeybindingDescription {
+    /// The mode the binding applies in, e.g. `"emacs"` or `"vi normal"`
+    pub mode: String,
+    /// The bound key combination
+    pub key: KeyCombination,
+    /// The event emitted by the binding
+    pub event: ReedlineEvent,
+    /// Names of the registered menus the event can activate
+    pub menus: Vec<String>,
+}
+
+/// Problem found in the keybindings by `Reedline::describe_keybindings`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum KeybindingConflict {
+    /// Both key combinations are bound to different events, but most terminals send the same
+    /// input for them (e.g. `Tab` and `Ctrl-i`), so only one of them can ever trigger
+    IndistinguishableKeys {
+        /// The mode containing both bindings
+        mode: String,
+        /// The binding of the named key
+        first: KeyCombination,
+        /// The binding of the control key combination that is reported as the named key
+        second: KeyCombination,
+    },
+    /// The binding activates a menu that is not registered with the engine
+    UnknownMenu {
+        /// The mode containing the binding
+        mode: String,
+        /// The bound key combination
+        key: KeyCombination,
+        /// Name of the missing menu
+        menu: String,
+    },
+}
+
+/// Structured overview of all keybindings, e.g. to render a help screen
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct KeybindingReport {
+    /// All bindings, grouped by mode
+    pub bindings: Vec<KeybindingDescription>,
+    /// Conflicts found between the bindings
+    pub conflicts: Vec<KeybindingConflict>,
+}
+
+/// Key combinations that are indistinguishable without the kitty keyboard protocol
+const INDISTINGUISHABLE_KEYS: [(KeyCode, char); 5] = [
+    (KeyCode::Tab, 'i'),
+    (KeyCode::Enter, 'm'),
+    (KeyCode::Enter, 'j'),
+    (KeyCode::Backspace, 'h'),
+    (KeyCode::Esc, '['),
+];
+
 /// Main definition of editor keybindings
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Keybindings {
@@ -55,13 +296,32 @@ impl Keybindings {
             );
         }
 
-        let key_combo = KeyCombination { modifier, key_code };
+        let key_combo = KeyCombination::normalized(modifier, key_code);
         self.bindings.insert(key_combo, command);
     }
 
+    /// Binds `Tab` and `Shift-Tab` to indent and dedent the current or the selected lines
+    ///
+    /// Not part of the default keybindings, where `Tab` is left to the completion menus
+    pub fn add_indent_bindings(&mut self) {
+        self.add_binding(
+            KeyModifiers::NONE,
+            KeyCode::Tab,
+            edit_bind(EditCommand::IndentLine),
+        );
+        // Depending on the terminal Shift-Tab comes with or without the modifier
+        for modifier in [KeyModifiers::NONE, KeyModifiers::SHIFT] {
+            self.add_binding(
+                modifier,
+                KeyCode::BackTab,
+                edit_bind(EditCommand::DedentLine),
+            );
+        }
+    }
+
     /// Find a keybinding based on the modifier and keycode
     pub fn find_binding(&self, modifier: KeyModifiers, key_code: KeyCode) -> Option<ReedlineEvent> {
-        let key_combo = KeyCombination { modifier, key_code };
+        let key_combo = KeyCombination::normalized(modifier, key_code);
         self.bindings.get(&key_combo).cloned()
     }
 
@@ -73,7 +333,7 @@ impl Keybindings {
         modifier: KeyModifiers,
         key_code: KeyCode,
     ) -> Option<ReedlineEvent> {
-        let key_combo = KeyCombination { modifier, key_code };
+        let key_combo = KeyCombination::normalized(modifier, key_code);
         self.bindings.remove(&key_combo)
     }
 
@@ -81,6 +341,83 @@ impl Keybindings {
     pub fn get_keybindings(&self) -> &HashMap<KeyCombination, ReedlineEvent> {
         &self.bindings
     }
+
+    /// Iterate over all key combinations and the events they are bound to
+    ///
+    /// Ordered by the modifiers and then by the key, so that listings of the bindings
+    /// don't change from one run to the next
+    pub fn iter(&self) -> impl Iterator<Item = (&KeyCombination, &ReedlineEvent)> {
+        let mut bindings: Vec<_> = self.bindings.iter().collect();
+        bindings.sort_by(|(a, _), (b, _)| {
+            a.modifier.bits().cmp(&b.modifier.bits()).then_with(|| {
+                a.key_code
+                    .partial_cmp(&b.key_code)
+                    .unwrap_or(Ordering::Equal)
+            })
+        });
+        bindings.into_iter()
+    }
+
+    /// Find all key combinations that emit `event`, either directly or as part of a
+    /// [`ReedlineEvent::UntilFound`] or [`ReedlineEvent::Multiple`]
+    pub fn find_keys(&self, event: &ReedlineEvent) -> Vec<&KeyCombination> {
+        self.iter()
+            .filter(|(_, bound)| event_contains(bound, event))
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// Pairs of bindings that most terminals can not tell apart, but that emit different events
+    pub(crate) fn indistinguishable_keys(&self) -> Vec<(KeyCombination, KeyCombination)> {
+        INDISTINGUISHABLE_KEYS
+            .iter()
+            .filter_map(|(key_code, c)| {
+                let first = KeyCombination {
+                    modifier: KeyModifiers::NONE,
+                    key_code: *key_code,
+                };
+                let second = KeyCombination {
+                    modifier: KeyModifiers::CONTROL,
+                    key_code: KeyCode::Char(*c),
+                };
+                match (self.bindings.get(&first), self.bindings.get(&second)) {
+                    (Some(a), Some(b)) if a != b => Some((first, second)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Check if `event` is or contains `needle`
+pub(crate) fn event_contains(event: &ReedlineEvent, needle: &ReedlineEvent) -> bool {
+    event == needle
+        || match event {
+            ReedlineEvent::UntilFound(events) | ReedlineEvent::Multiple(events) => {
+                events.iter().any(|event| event_contains(event, needle))
+            }
+            ReedlineEvent::Conditional {
+                then, otherwise, ..
+            } => event_contains(then, needle) || event_contains(otherwise, needle),
+            _ => false,
+        }
+}
+
+/// Names of all menus `event` can activate
+pub(crate) fn menus_in_event(event: &ReedlineEvent) -> Vec<String> {
+    match event {
+        ReedlineEvent::Menu(name) => vec![name.clone()],
+        ReedlineEvent::UntilFound(events) | ReedlineEvent::Multiple(events) => {
+            events.iter().flat_map(menus_in_event).collect()
+        }
+        ReedlineEvent::Conditional {
+            then, otherwise, ..
+        } => [then, otherwise]
+            .into_iter()
+            .flat_map(|event| menus_in_event(event))
+            .collect(),
+        _ => Vec::new(),
+    }
 }
 
 pub fn edit_bind(command: EditCommand) -> ReedlineEvent {
@@ -89,7 +426,7 @@ pub fn edit_bind(command: EditCommand) -> ReedlineEvent {
 
 /// Add the basic special keybindings
 ///
-/// `Ctrl-C`, `Ctrl-D`, `Ctrl-O`, `Ctrl-R`
+/// `Ctrl-C`, `Ctrl-D`, `Ctrl-L`, `Ctrl-O`, `Ctrl-R`, `Ctrl-S`
 /// + `Esc`
 /// + `Ctrl-O` to open the external editor
 pub fn add_common_control_bindings(kb: &mut Keybindings) {
@@ -101,6 +438,7 @@ pub fn add_common_control_bindings(kb: &mut Keybindings) {
     kb.add_binding(KM::CONTROL, KC::Char('d'), ReedlineEvent::CtrlD);
     kb.add_binding(KM::CONTROL, KC::Char('l'), ReedlineEvent::ClearScreen);
     kb.add_binding(KM::CONTROL, KC::Char('r'), ReedlineEvent::SearchHistory);
+    kb.add_binding(KM::CONTROL, KC::Char('s'), ReedlineEvent::SearchBuffer);
     kb.add_binding(KM::CONTROL, KC::Char('o'), ReedlineEvent::OpenEditor);
 }
 /// Add the arrow navigation and its `Ctrl` variants
@@ -285,3 +623,224 @@ pub fn add_common_selection_bindings(kb: &mut Keybindings) {
         edit_bind(EC::SelectAll),
     );
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(KeyModifiers::CONTROL | KeyModifiers::ALT, KeyCode::Char('b'), "ctrl-alt-b")]
+    #[case(KeyModifiers::SHIFT, KeyCode::BackTab, "shift-backtab")]
+    #[case(KeyModifiers::NONE, KeyCode::Enter, "enter")]
+    #[case(KeyModifiers::NONE, KeyCode::F(5), "f5")]
+    #[case(KeyModifiers::NONE, KeyCode::Char('f'), "f")]
+    #[case(KeyModifiers::CONTROL, KeyCode::Char('-'), "ctrl--")]
+    #[case(KeyModifiers::NONE, KeyCode::Char('-'), "-")]
+    #[case(KeyModifiers::ALT, KeyCode::Char(' '), "alt-space")]
+    #[case(KeyModifiers::SHIFT, KeyCode::Char('A'), "shift-A")]
+    #[case(
+        KeyModifiers::NONE,
+        KeyCode::Media(MediaKeyCode::PlayPause),
+        "mediaplaypause"
+    )]
+    #[case(
+        KeyModifiers::CONTROL,
+        KeyCode::Modifier(ModifierKeyCode::RightAlt),
+        "ctrl-rightalt"
+    )]
+    fn key_combination_text_form(
+        #[case] modifier: KeyModifiers,
+        #[case] key_code: KeyCode,
+        #[case] text: &str,
+    ) {
+        let key = KeyCombination { modifier, key_code };
+
+        assert_eq!(key.to_string(), text);
+        assert_eq!(text.parse::<KeyCombination>(), Ok(key));
+    }
+
+    #[test]
+    fn every_named_key_is_parsed_back() {
+        for (key_code, name) in KEY_NAMES {
+            let key = KeyCombination::normalized(KeyModifiers::ALT, key_code);
+            let text = key.to_string();
+
+            assert_eq!(text, format!("alt-{name}"));
+            assert_eq!(text.parse::<KeyCombination>(), Ok(key));
+        }
+    }
+
+    #[rstest]
+    #[case("Ctrl-Alt-B", KeyModifiers::CONTROL | KeyModifiers::ALT, KeyCode::Char('b'))]
+    #[case("ctrl-Enter", KeyModifiers::CONTROL, KeyCode::Enter)]
+    fn key_combination_parsing_ignores_name_case(
+        #[case] text: &str,
+        #[case] modifier: KeyModifiers,
+        #[case] key_code: KeyCode,
+    ) {
+        assert_eq!(text.parse(), Ok(KeyCombination { modifier, key_code }));
+    }
+
+    #[rstest]
+    #[case("ctrl-alt")]
+    #[case("hyperctrl-b")]
+    #[case("ctrl-")]
+    #[case("ctrl-f99x")]
+    fn invalid_key_combination(#[case] text: &str) {
+        assert!(text.parse::<KeyCombination>().is_err());
+    }
+
+    #[test]
+    fn tab_and_shift_tab_indent_and_dedent_once_added() {
+        let mut keybindings = crate::default_emacs_keybindings();
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::NONE, KeyCode::Tab),
+            None
+        );
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::SHIFT, KeyCode::BackTab),
+            None
+        );
+
+        keybindings.add_indent_bindings();
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::NONE, KeyCode::Tab),
+            Some(ReedlineEvent::Edit(vec![EditCommand::IndentLine]))
+        );
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::SHIFT, KeyCode::BackTab),
+            Some(ReedlineEvent::Edit(vec![EditCommand::DedentLine]))
+        );
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::NONE, KeyCode::BackTab),
+            Some(ReedlineEvent::Edit(vec![EditCommand::DedentLine]))
+        );
+    }
+
+    #[test]
+    fn keybindings_round_trip_through_json() {
+        let keybindings = crate::default_emacs_keybindings();
+
+        let json = serde_json::to_string(&keybindings).unwrap();
+        assert!(json.contains(r#""ctrl-r":"SearchHistory""#));
+
+        let parsed: Keybindings = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.bindings, keybindings.bindings);
+    }
+
+    #[test]
+    fn find_keys_looks_into_composite_events() {
+        let mut keybindings = Keybindings::new();
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('l'),
+            ReedlineEvent::ClearScreen,
+        );
+        keybindings.add_binding(
+            KeyModifiers::NONE,
+            KeyCode::Tab,
+            ReedlineEvent::UntilFound(vec![
+                ReedlineEvent::Menu("completion_menu".to_string()),
+                ReedlineEvent::MenuNext,
+            ]),
+        );
+
+        assert_eq!(
+            keybindings.find_keys(&ReedlineEvent::MenuNext),
+            vec![&KeyCombination {
+                modifier: KeyModifiers::NONE,
+                key_code: KeyCode::Tab
+            }]
+        );
+        assert_eq!(keybindings.find_keys(&ReedlineEvent::ClearScreen).len(), 1);
+        assert!(keybindings.find_keys(&ReedlineEvent::CtrlD).is_empty());
+        assert_eq!(
+            menus_in_event(
+                keybindings
+                    .find_binding(KeyModifiers::NONE, KeyCode::Tab)
+                    .as_ref()
+                    .unwrap()
+            ),
+            vec!["completion_menu".to_string()]
+        );
+    }
+
+    #[test]
+    fn indistinguishable_keys_with_different_events_conflict() {
+        let mut keybindings = Keybindings::new();
+        keybindings.add_binding(KeyModifiers::NONE, KeyCode::Tab, ReedlineEvent::MenuNext);
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('i'),
+            ReedlineEvent::ClearScreen,
+        );
+        keybindings.add_binding(
+            KeyModifiers::NONE,
+            KeyCode::Backspace,
+            edit_bind(EditCommand::Backspace),
+        );
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('h'),
+            edit_bind(EditCommand::Backspace),
+        );
+
+        assert_eq!(
+            keybindings.indistinguishable_keys(),
+            vec![(
+                KeyCombination {
+                    modifier: KeyModifiers::NONE,
+                    key_code: KeyCode::Tab
+                },
+                KeyCombination {
+                    modifier: KeyModifiers::CONTROL,
+                    key_code: KeyCode::Char('i')
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn bindings_to_uppercase_control_letters_are_found() {
+        let mut keybindings = Keybindings::new();
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('R'),
+            ReedlineEvent::SearchHistory,
+        );
+        keybindings.add_binding(KeyModifiers::SHIFT, KeyCode::Char('A'), ReedlineEvent::Up);
+
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::CONTROL, KeyCode::Char('r')),
+            Some(ReedlineEvent::SearchHistory)
+        );
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::SHIFT, KeyCode::Char('A')),
+            Some(ReedlineEvent::Up)
+        );
+        assert_eq!(
+            keybindings.find_binding(KeyModifiers::SHIFT, KeyCode::Char('a')),
+            None
+        );
+    }
+
+    #[test]
+    fn iteration_is_ordered_by_modifiers_and_key() {
+        let keybindings = crate::default_emacs_keybindings();
+
+        let modifiers: Vec<_> = keybindings.iter().map(|(key, _)| key.modifier).collect();
+        assert_eq!(modifiers.len(), keybindings.bindings.len());
+        assert!(modifiers
+            .windows(2)
+            .all(|pair| pair[0].bits() <= pair[1].bits()));
+        assert_eq!(
+            keybindings.iter().next().map(|(key, _)| key.clone()),
+            Some(KeyCombination {
+                modifier: KeyModifiers::NONE,
+                key_code: KeyCode::Backspace
+            })
+        );
+    }
+}
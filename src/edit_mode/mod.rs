@@ -1,11 +1,13 @@
 mod base;
 mod cursors;
 mod emacs;
-mod keybindings;
+pub(crate) mod keybindings;
 mod vi;
 
 pub use base::EditMode;
 pub use cursors::CursorConfig;
 pub use emacs::{default_emacs_keybindings, Emacs};
-pub use keybindings::Keybindings;
-pub use vi::{default_vi_insert_keybindings, default_vi_normal_keybindings, Vi};
+pub use keybindings::{
+    KeyCombination, KeybindingConflict, KeybindingDescription, KeybindingReport, Keybindings,
+};
+pub use vi::{default_vi_insert_keybindings, default_vi_normal_keybindings, Vi, ViCharSearchReset};
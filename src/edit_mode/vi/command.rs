@@ -1,95 +1,72 @@
 use super::{motion::Motion, motion::ViCharSearch, parser::ReedlineOption};
-use crate::{EditCommand, ReedlineEvent, Vi};
-use std::iter::Peekable;
+use crate::{EditCommand, ReedlineEvent, Vi, ViewportLine};
 
-pub fn parse_command<'iter, I>(input: &mut Peekable<I>) -> Option<Command>
-where
-    I: Iterator<Item = &'iter char>,
-{
-    match input.peek() {
-        Some('d') => {
-            let _ = input.next();
-            Some(Command::Delete)
-        }
-        Some('p') => {
-            let _ = input.next();
-            Some(Command::PasteAfter)
-        }
-        Some('P') => {
-            let _ = input.next();
-            Some(Command::PasteBefore)
-        }
-        Some('i') => {
-            let _ = input.next();
-            Some(Command::EnterViInsert)
-        }
-        Some('a') => {
-            let _ = input.next();
-            Some(Command::EnterViAppend)
-        }
-        Some('u') => {
-            let _ = input.next();
-            Some(Command::Undo)
-        }
-        Some('c') => {
-            let _ = input.next();
-            Some(Command::Change)
-        }
-        Some('x') => {
-            let _ = input.next();
-            Some(Command::DeleteChar)
-        }
-        Some('r') => {
-            let _ = input.next();
-            match input.next() {
-                Some(c) => Some(Command::ReplaceChar(*c)),
-                None => Some(Command::Incomplete),
-            }
-        }
-        Some('s') => {
-            let _ = input.next();
-            Some(Command::SubstituteCharWithInsert)
-        }
-        Some('?') => {
-            let _ = input.next();
-            Some(Command::HistorySearch)
-        }
-        Some('C') => {
-            let _ = input.next();
-            Some(Command::ChangeToLineEnd)
-        }
-        Some('D') => {
-            let _ = input.next();
-            Some(Command::DeleteToEnd)
-        }
-        Some('I') => {
-            let _ = input.next();
-            Some(Command::PrependToStart)
-        }
-        Some('A') => {
-            let _ = input.next();
-            Some(Command::AppendToEnd)
-        }
-        Some('S') => {
-            let _ = input.next();
-            Some(Command::RewriteCurrentLine)
-        }
-        Some('~') => {
-            let _ = input.next();
-            Some(Command::Switchcase)
-        }
-        Some('.') => {
-            let _ = input.next();
-            Some(Command::RepeatLastAction)
-        }
-        _ => None,
-    }
+/// What a key sequence of the [`COMMANDS`] table starts in normal mode
+#[derive(Clone)]
+pub enum CommandKey {
+    /// A command that is complete on its own, e.g. `x`
+    Command(Command),
+    /// A command completed by the next typed character, e.g. `r`
+    WithChar(fn(char) -> Command),
+    /// An operator applied to the following motion, e.g. `d`
+    Operator(Operator),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Key sequences of the normal mode commands and operators
+///
+/// No key sequence may be the prefix of another one, as the parser completes a command
+/// as soon as its keys are typed.
+pub const COMMANDS: &[(&str, CommandKey)] = &[
+    ("d", CommandKey::Operator(Operator::Delete)),
+    ("c", CommandKey::Operator(Operator::Change)),
+    ("p", CommandKey::Command(Command::PasteAfter)),
+    ("P", CommandKey::Command(Command::PasteBefore)),
+    ("i", CommandKey::Command(Command::EnterViInsert)),
+    ("a", CommandKey::Command(Command::EnterViAppend)),
+    ("u", CommandKey::Command(Command::Undo)),
+    ("x", CommandKey::Command(Command::DeleteChar)),
+    ("r", CommandKey::WithChar(Command::ReplaceChar)),
+    ("s", CommandKey::Command(Command::SubstituteCharWithInsert)),
+    ("?", CommandKey::Command(Command::HistorySearch)),
+    (":", CommandKey::Command(Command::ExCommandLine)),
+    ("C", CommandKey::Command(Command::ChangeToLineEnd)),
+    ("D", CommandKey::Command(Command::DeleteToEnd)),
+    ("I", CommandKey::Command(Command::PrependToStart)),
+    ("A", CommandKey::Command(Command::AppendToEnd)),
+    ("S", CommandKey::Command(Command::RewriteCurrentLine)),
+    ("~", CommandKey::Command(Command::Switchcase)),
+    ("g~", CommandKey::Operator(Operator::Switchcase)),
+    ("gv", CommandKey::Command(Command::ReselectLast)),
+    ("J", CommandKey::Command(Command::JoinLines)),
+    (".", CommandKey::Command(Command::RepeatLastAction)),
+    (
+        "H",
+        CommandKey::Command(Command::MoveToViewport(ViewportLine::Top)),
+    ),
+    (
+        "M",
+        CommandKey::Command(Command::MoveToViewport(ViewportLine::Middle)),
+    ),
+    (
+        "L",
+        CommandKey::Command(Command::MoveToViewport(ViewportLine::Bottom)),
+    ),
+    (
+        "zt",
+        CommandKey::Command(Command::ScrollViewport(ViewportLine::Top)),
+    ),
+    (
+        "zz",
+        CommandKey::Command(Command::ScrollViewport(ViewportLine::Middle)),
+    ),
+    (
+        "zb",
+        CommandKey::Command(Command::ScrollViewport(ViewportLine::Bottom)),
+    ),
+];
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Command {
-    Incomplete,
-    Delete,
     DeleteChar,
     ReplaceChar(char),
     SubstituteCharWithInsert,
@@ -103,23 +80,31 @@ pub enum Command {
     AppendToEnd,
     PrependToStart,
     RewriteCurrentLine,
-    Change,
     HistorySearch,
+    ExCommandLine,
     Switchcase,
+    JoinLines,
     RepeatLastAction,
+    ReselectLast,
+    MoveToViewport(ViewportLine),
+    ScrollViewport(ViewportLine),
 }
 
 impl Command {
-    pub fn whole_line_char(&self) -> Option<char> {
-        match self {
-            Command::Delete => Some('d'),
-            Command::Change => Some('c'),
-            _ => None,
-        }
-    }
-
-    pub fn requires_motion(&self) -> bool {
-        matches!(self, Command::Delete | Command::Change)
+    /// Whether the command leaves normal mode
+    pub fn enters_insert_mode(&self) -> bool {
+        matches!(
+            self,
+            Command::EnterViInsert
+                | Command::EnterViAppend
+                | Command::ChangeToLineEnd
+                | Command::AppendToEnd
+                | Command::PrependToStart
+                | Command::RewriteCurrentLine
+                | Command::SubstituteCharWithInsert
+                | Command::HistorySearch
+                | Command::ExCommandLine
+        )
     }
 
     pub fn to_reedline(&self, vi_state: &mut Vi) -> Vec<ReedlineOption> {
@@ -146,16 +131,34 @@ impl Command {
             }
             Self::SubstituteCharWithInsert => vec![ReedlineOption::Edit(EditCommand::CutChar)],
             Self::HistorySearch => vec![ReedlineOption::Event(ReedlineEvent::SearchHistory)],
-            Self::Switchcase => vec![ReedlineOption::Edit(EditCommand::SwitchcaseChar)],
-            // Mark a command as incomplete whenever a motion is required to finish the command
-            Self::Delete | Self::Change | Self::Incomplete => vec![ReedlineOption::Incomplete],
+            Self::ExCommandLine => vec![ReedlineOption::Event(ReedlineEvent::ExCommand)],
+            Self::Switchcase => vec![ReedlineOption::Edit(EditCommand::SwitchcaseChars(1))],
+            Self::JoinLines => vec![ReedlineOption::Edit(EditCommand::JoinLines)],
+            Self::ReselectLast => vec![ReedlineOption::Edit(EditCommand::ReselectLast)],
+            Self::MoveToViewport(line) => {
+                vec![ReedlineOption::Event(ReedlineEvent::MoveToViewport(*line))]
+            }
+            Self::ScrollViewport(line) => {
+                vec![ReedlineOption::Event(ReedlineEvent::ScrollViewport(*line))]
+            }
             Command::RepeatLastAction => match &vi_state.previous {
                 Some(event) => vec![ReedlineOption::Event(event.clone())],
                 None => vec![],
             },
         }
     }
+}
 
+/// Commands applied to the text covered by a motion
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Operator {
+    Delete,
+    Change,
+    /// Switch the case of the covered text, the last option switches it
+    Switchcase,
+}
+
+impl Operator {
     pub fn to_reedline_with_motion(
         &self,
         motion: &Motion,
@@ -179,35 +182,44 @@ impl Command {
                 Motion::PreviousBigWord => {
                     Some(vec![ReedlineOption::Edit(EditCommand::CutBigWordLeft)])
                 }
-                Motion::RightUntil(c) => {
-                    vi_state.last_char_search = Some(ViCharSearch::ToRight(*c));
-                    Some(vec![ReedlineOption::Edit(EditCommand::CutRightUntil(*c))])
-                }
-                Motion::RightBefore(c) => {
-                    vi_state.last_char_search = Some(ViCharSearch::TillRight(*c));
-                    Some(vec![ReedlineOption::Edit(EditCommand::CutRightBefore(*c))])
-                }
-                Motion::LeftUntil(c) => {
-                    vi_state.last_char_search = Some(ViCharSearch::ToLeft(*c));
-                    Some(vec![ReedlineOption::Edit(EditCommand::CutLeftUntil(*c))])
-                }
-                Motion::LeftBefore(c) => {
-                    vi_state.last_char_search = Some(ViCharSearch::TillLeft(*c));
-                    Some(vec![ReedlineOption::Edit(EditCommand::CutLeftBefore(*c))])
-                }
+                Motion::RightUntil(c) => Some(vec![ReedlineOption::Edit(
+                    vi_state.start_char_search(ViCharSearch::ToRight(*c), true),
+                )]),
+                Motion::RightBefore(c) => Some(vec![ReedlineOption::Edit(
+                    vi_state.start_char_search(ViCharSearch::TillRight(*c), true),
+                )]),
+                Motion::LeftUntil(c) => Some(vec![ReedlineOption::Edit(
+                    vi_state.start_char_search(ViCharSearch::ToLeft(*c), true),
+                )]),
+                Motion::LeftBefore(c) => Some(vec![ReedlineOption::Edit(
+                    vi_state.start_char_search(ViCharSearch::TillLeft(*c), true),
+                )]),
                 Motion::Start => Some(vec![ReedlineOption::Edit(EditCommand::CutFromLineStart)]),
+                Motion::PreviousParagraph
+                | Motion::NextParagraph
+                | Motion::PreviousSentence
+                | Motion::NextSentence => Some(vec![
+                    ReedlineOption::Edit(motion.selection()?),
+                    ReedlineOption::Edit(EditCommand::CutSelection),
+                ]),
                 Motion::Left => Some(vec![ReedlineOption::Edit(EditCommand::Backspace)]),
                 Motion::Right => Some(vec![ReedlineOption::Edit(EditCommand::Delete)]),
                 Motion::Up => None,
                 Motion::Down => None,
-                Motion::ReplayCharSearch => vi_state
-                    .last_char_search
-                    .as_ref()
-                    .map(|char_search| vec![ReedlineOption::Edit(char_search.to_cut())]),
-                Motion::ReverseCharSearch => vi_state
-                    .last_char_search
-                    .as_ref()
-                    .map(|char_search| vec![ReedlineOption::Edit(char_search.reverse().to_cut())]),
+                Motion::ReplayCharSearch => vi_state.last_char_search.as_ref().map(|char_search| {
+                    vec![ReedlineOption::Edit(
+                        char_search.to_cut(vi_state.char_search_across_lines),
+                    )]
+                }),
+                Motion::ReverseCharSearch => {
+                    vi_state.last_char_search.as_ref().map(|char_search| {
+                        vec![ReedlineOption::Edit(
+                            char_search
+                                .reverse()
+                                .to_cut(vi_state.char_search_across_lines),
+                        )]
+                    })
+                }
             },
             Self::Change => {
                 let op = match motion {
@@ -232,36 +244,46 @@ impl Command {
                     Motion::PreviousBigWord => {
                         Some(vec![ReedlineOption::Edit(EditCommand::CutBigWordLeft)])
                     }
-                    Motion::RightUntil(c) => {
-                        vi_state.last_char_search = Some(ViCharSearch::ToRight(*c));
-                        Some(vec![ReedlineOption::Edit(EditCommand::CutRightUntil(*c))])
-                    }
-                    Motion::RightBefore(c) => {
-                        vi_state.last_char_search = Some(ViCharSearch::TillRight(*c));
-                        Some(vec![ReedlineOption::Edit(EditCommand::CutRightBefore(*c))])
-                    }
-                    Motion::LeftUntil(c) => {
-                        vi_state.last_char_search = Some(ViCharSearch::ToLeft(*c));
-                        Some(vec![ReedlineOption::Edit(EditCommand::CutLeftUntil(*c))])
-                    }
-                    Motion::LeftBefore(c) => {
-                        vi_state.last_char_search = Some(ViCharSearch::TillLeft(*c));
-                        Some(vec![ReedlineOption::Edit(EditCommand::CutLeftBefore(*c))])
-                    }
+                    Motion::RightUntil(c) => Some(vec![ReedlineOption::Edit(
+                        vi_state.start_char_search(ViCharSearch::ToRight(*c), true),
+                    )]),
+                    Motion::RightBefore(c) => Some(vec![ReedlineOption::Edit(
+                        vi_state.start_char_search(ViCharSearch::TillRight(*c), true),
+                    )]),
+                    Motion::LeftUntil(c) => Some(vec![ReedlineOption::Edit(
+                        vi_state.start_char_search(ViCharSearch::ToLeft(*c), true),
+                    )]),
+                    Motion::LeftBefore(c) => Some(vec![ReedlineOption::Edit(
+                        vi_state.start_char_search(ViCharSearch::TillLeft(*c), true),
+                    )]),
                     Motion::Start => {
                         Some(vec![ReedlineOption::Edit(EditCommand::CutFromLineStart)])
                     }
+                    Motion::PreviousParagraph
+                    | Motion::NextParagraph
+                    | Motion::PreviousSentence
+                    | Motion::NextSentence => Some(vec![
+                        ReedlineOption::Edit(motion.selection()?),
+                        ReedlineOption::Edit(EditCommand::CutSelection),
+                    ]),
                     Motion::Left => Some(vec![ReedlineOption::Edit(EditCommand::Backspace)]),
                     Motion::Right => Some(vec![ReedlineOption::Edit(EditCommand::Delete)]),
                     Motion::Up => None,
                     Motion::Down => None,
-                    Motion::ReplayCharSearch => vi_state
-                        .last_char_search
-                        .as_ref()
-                        .map(|char_search| vec![ReedlineOption::Edit(char_search.to_cut())]),
+                    Motion::ReplayCharSearch => {
+                        vi_state.last_char_search.as_ref().map(|char_search| {
+                            vec![ReedlineOption::Edit(
+                                char_search.to_cut(vi_state.char_search_across_lines),
+                            )]
+                        })
+                    }
                     Motion::ReverseCharSearch => {
                         vi_state.last_char_search.as_ref().map(|char_search| {
-                            vec![ReedlineOption::Edit(char_search.reverse().to_cut())]
+                            vec![ReedlineOption::Edit(
+                                char_search
+                                    .reverse()
+                                    .to_cut(vi_state.char_search_across_lines),
+                            )]
                         })
                     }
                 };
@@ -271,7 +293,70 @@ impl Command {
                     vec
                 })
             }
-            _ => None,
+            Self::Switchcase => {
+                // Selects the covered text, including the char the cursor ends on for the
+                // inclusive motions
+                let (selection, inclusive) = match motion {
+                    Motion::Line => {
+                        return Some(vec![
+                            ReedlineOption::Edit(EditCommand::MoveToLineStart { select: false }),
+                            ReedlineOption::Edit(EditCommand::MoveToLineEnd { select: true }),
+                            ReedlineOption::Edit(EditCommand::SwitchcaseSelection),
+                        ])
+                    }
+                    Motion::Up | Motion::Down => return None,
+                    Motion::Left => (EditCommand::MoveLeft { select: true }, false),
+                    Motion::Right => (EditCommand::MoveRight { select: true }, false),
+                    Motion::NextWord => (EditCommand::MoveWordRightStart { select: true }, false),
+                    Motion::NextBigWord => {
+                        (EditCommand::MoveBigWordRightStart { select: true }, false)
+                    }
+                    Motion::NextWordEnd => (EditCommand::MoveWordRightEnd { select: true }, true),
+                    Motion::NextBigWordEnd => {
+                        (EditCommand::MoveBigWordRightEnd { select: true }, true)
+                    }
+                    Motion::PreviousWord => (EditCommand::MoveWordLeft { select: true }, false),
+                    Motion::PreviousBigWord => {
+                        (EditCommand::MoveBigWordLeft { select: true }, false)
+                    }
+                    Motion::Start => (EditCommand::MoveToLineStart { select: true }, false),
+                    Motion::End => (EditCommand::MoveToLineEnd { select: true }, false),
+                    Motion::PreviousParagraph
+                    | Motion::NextParagraph
+                    | Motion::PreviousSentence
+                    | Motion::NextSentence => (motion.selection()?, false),
+                    Motion::RightUntil(c) => vi_state.select_char_search(ViCharSearch::ToRight(*c)),
+                    Motion::RightBefore(c) => {
+                        vi_state.select_char_search(ViCharSearch::TillRight(*c))
+                    }
+                    Motion::LeftUntil(c) => vi_state.select_char_search(ViCharSearch::ToLeft(*c)),
+                    Motion::LeftBefore(c) => {
+                        vi_state.select_char_search(ViCharSearch::TillLeft(*c))
+                    }
+                    Motion::ReplayCharSearch => {
+                        let char_search = vi_state.last_char_search.as_ref()?;
+                        (
+                            char_search.to_selection(vi_state.char_search_across_lines),
+                            char_search.is_inclusive(),
+                        )
+                    }
+                    Motion::ReverseCharSearch => {
+                        let char_search = vi_state.last_char_search.as_ref()?.reverse();
+                        (
+                            char_search.to_selection(vi_state.char_search_across_lines),
+                            char_search.is_inclusive(),
+                        )
+                    }
+                };
+                let mut options = vec![ReedlineOption::Edit(selection)];
+                if inclusive {
+                    options.push(ReedlineOption::Edit(EditCommand::MoveRight {
+                        select: true,
+                    }));
+                }
+                options.push(ReedlineOption::Edit(EditCommand::SwitchcaseSelection));
+                Some(options)
+            }
         }
     }
 }
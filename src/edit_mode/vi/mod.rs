@@ -6,11 +6,14 @@ mod vi_keybindings;
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 pub use vi_keybindings::{default_vi_insert_keybindings, default_vi_normal_keybindings};
 
-use self::motion::ViCharSearch;
+use self::{
+    motion::ViCharSearch,
+    parser::{ParseResult, ViParser},
+};
 
 use super::EditMode;
 use crate::{
-    edit_mode::{keybindings::Keybindings, vi::parser::parse},
+    edit_mode::keybindings::Keybindings,
     enums::{EditCommand, ReedlineEvent, ReedlineRawEvent},
     PromptEditMode, PromptViMode,
 };
@@ -23,14 +26,27 @@ enum ViMode {
 
 /// This parses incoming input `Event`s like a Vi-Style editor
 pub struct Vi {
-    cache: Vec<char>,
+    parser: ViParser,
     insert_keybindings: Keybindings,
     normal_keybindings: Keybindings,
     mode: ViMode,
     previous: Option<ReedlineEvent>,
     // last f, F, t, T motion for ; and ,
     last_char_search: Option<ViCharSearch>,
-    seq_completed: bool,
+    char_search_across_lines: bool,
+    char_search_reset: ViCharSearchReset,
+}
+
+/// When the last `f`, `F`, `t` or `T` of vi normal mode, repeated by `;` and `,`, is forgotten
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViCharSearchReset {
+    /// Kept until the next of these motions, also for the following lines
+    #[default]
+    Never,
+    /// Forgotten at the start of every line read, but kept while switching modes
+    EachLine,
+    /// Forgotten whenever insert mode is entered
+    InsertMode,
 }
 
 impl Default for Vi {
@@ -38,11 +54,12 @@ impl Default for Vi {
         Vi {
             insert_keybindings: default_vi_insert_keybindings(),
             normal_keybindings: default_vi_normal_keybindings(),
-            cache: Vec::new(),
+            parser: ViParser::default(),
             mode: ViMode::Insert,
             previous: None,
             last_char_search: None,
-            seq_completed: true,
+            char_search_across_lines: false,
+            char_search_reset: ViCharSearchReset::default(),
         }
     }
 }
@@ -56,6 +73,70 @@ impl Vi {
             ..Default::default()
         }
     }
+
+    /// A builder that lets `f`, `F`, `t`, `T`, `;` and `,` find the char on the other lines of
+    /// a multi-line buffer, instead of only on the line of the cursor
+    #[must_use]
+    pub fn with_char_search_across_lines(mut self, across_lines: bool) -> Self {
+        self.char_search_across_lines = across_lines;
+        self
+    }
+
+    /// A builder setting when the char search repeated by `;` and `,` is forgotten
+    #[must_use]
+    pub fn with_char_search_reset(mut self, reset: ViCharSearchReset) -> Self {
+        self.char_search_reset = reset;
+        self
+    }
+}
+
+impl Vi {
+    // Remember `char_search` for `;` and `,` and return the motion to the char, or the cut
+    fn start_char_search(&mut self, char_search: ViCharSearch, cut: bool) -> EditCommand {
+        let command = if cut {
+            char_search.to_cut(self.char_search_across_lines)
+        } else {
+            char_search.to_move(self.char_search_across_lines)
+        };
+        self.last_char_search = Some(char_search);
+        command
+    }
+
+    // Like `start_char_search`, selecting the text on the way and returning whether the
+    // selection has to include the char the cursor ends on
+    fn select_char_search(&mut self, char_search: ViCharSearch) -> (EditCommand, bool) {
+        let selection = char_search.to_selection(self.char_search_across_lines);
+        let inclusive = char_search.is_inclusive();
+        self.last_char_search = Some(char_search);
+        (selection, inclusive)
+    }
+
+    fn enter_insert_mode(&mut self) {
+        self.mode = ViMode::Insert;
+        if self.char_search_reset == ViCharSearchReset::InsertMode {
+            self.last_char_search = None;
+        }
+    }
+
+    // The binding of a typed character in normal mode
+    //
+    // Uppercase letters are typed with Shift, but terminals don't agree whether they report
+    // it, so a binding of e.g. `D` is found with or without Shift. A binding of Shift and
+    // the lowercase letter also matches the uppercase letter, but never the lowercase one.
+    fn find_normal_binding(&self, modifier: KeyModifiers, c: char) -> Option<ReedlineEvent> {
+        let find = |modifier, c| {
+            self.normal_keybindings
+                .find_binding(modifier, KeyCode::Char(c))
+        };
+        find(modifier, c).or_else(|| {
+            if c.is_uppercase() {
+                find(modifier ^ KeyModifiers::SHIFT, c)
+                    .or_else(|| find(modifier | KeyModifiers::SHIFT, c.to_ascii_lowercase()))
+            } else {
+                None
+            }
+        })
+    }
 }
 
 impl EditMode for Vi {
@@ -65,36 +146,33 @@ impl EditMode for Vi {
                 code, modifiers, ..
             }) => match (self.mode, modifiers, code) {
                 (ViMode::Normal, modifier, KeyCode::Char(c)) => {
-                    let c = c.to_ascii_lowercase();
-
-                    let binding = self
-                        .normal_keybindings
-                        .find_binding(modifiers, KeyCode::Char(c));
-                    if !self.seq_completed
+                    let binding = self.find_normal_binding(modifier, c);
+                    let types_char =
+                        modifier == KeyModifiers::NONE || modifier == KeyModifiers::SHIFT;
+                    if let Some(event) = binding.as_ref().filter(|_| !types_char) {
+                        // A count repeats the bound event, like `5 Ctrl-a`
+                        if let Some(count) = self.parser.take_count() {
+                            return ReedlineEvent::Multiple(vec![event.clone(); count]);
+                        }
+                    }
+                    if self.parser.is_pending()
                         || binding.is_none()
                             && (modifier == KeyModifiers::NONE || modifier == KeyModifiers::SHIFT)
                     {
-                        self.cache.push(if modifier == KeyModifiers::SHIFT {
+                        let c = if modifier == KeyModifiers::SHIFT {
                             c.to_ascii_uppercase()
                         } else {
                             c
-                        });
-
-                        let res = parse(&mut self.cache.iter().peekable());
-                        self.seq_completed = res.is_complete();
-                        if !res.is_valid() {
-                            self.cache.clear();
-                            ReedlineEvent::None
-                        } else if res.is_complete() {
-                            if res.enters_insert_mode() {
-                                self.mode = ViMode::Insert;
-                            }
+                        };
 
-                            let event = res.to_reedline_event(self);
-                            self.cache.clear();
-                            event
-                        } else {
-                            ReedlineEvent::None
+                        match self.parser.push(c) {
+                            ParseResult::Valid(sequence) => {
+                                if sequence.enters_insert_mode() {
+                                    self.enter_insert_mode();
+                                }
+                                sequence.to_reedline_event(self)
+                            }
+                            ParseResult::Incomplete | ParseResult::Invalid => ReedlineEvent::None,
                         }
                     } else if let Some(event) = binding {
                         event
@@ -140,12 +218,12 @@ impl EditMode for Vi {
                         })
                 }
                 (_, KeyModifiers::NONE, KeyCode::Esc) => {
-                    self.cache.clear();
+                    self.parser.reset();
                     self.mode = ViMode::Normal;
                     ReedlineEvent::Multiple(vec![ReedlineEvent::Esc, ReedlineEvent::Repaint])
                 }
                 (_, KeyModifiers::NONE, KeyCode::Enter) => {
-                    self.mode = ViMode::Insert;
+                    self.enter_insert_mode();
                     ReedlineEvent::Enter
                 }
                 (ViMode::Normal, _, _) => self
@@ -170,10 +248,46 @@ impl EditMode for Vi {
 
     fn edit_mode(&self) -> PromptEditMode {
         match self.mode {
+            ViMode::Normal if self.parser.is_pending() => {
+                PromptEditMode::Vi(PromptViMode::Pending(self.parser.pending()))
+            }
             ViMode::Normal => PromptEditMode::Vi(PromptViMode::Normal),
             ViMode::Insert => PromptEditMode::Vi(PromptViMode::Insert),
         }
     }
+
+    fn has_pending_keys(&self) -> bool {
+        self.parser.is_pending()
+    }
+
+    fn pending_keys(&self) -> String {
+        self.parser.pending_keys().to_string()
+    }
+
+    fn start_line(&mut self) {
+        if self.char_search_reset == ViCharSearchReset::EachLine {
+            self.last_char_search = None;
+        }
+    }
+
+    // The ex command line is typed in insert mode and opened from normal mode
+    fn leave_command_line(&mut self) {
+        self.mode = ViMode::Normal;
+    }
+
+    fn keybindings(&self) -> Vec<(&str, &Keybindings)> {
+        vec![
+            ("vi insert", &self.insert_keybindings),
+            ("vi normal", &self.normal_keybindings),
+        ]
+    }
+
+    fn keybindings_mut(&mut self) -> Vec<(&str, &mut Keybindings)> {
+        vec![
+            ("vi insert", &mut self.insert_keybindings),
+            ("vi normal", &mut self.normal_keybindings),
+        ]
+    }
 }
 
 #[cfg(test)]
@@ -276,7 +390,7 @@ mod test {
         keybindings.add_binding(
             KeyModifiers::SHIFT,
             KeyCode::Char('B'),
-            ReedlineEvent::Edit(vec![EditCommand::MoveBigWordLeft]),
+            ReedlineEvent::Edit(vec![EditCommand::MoveBigWordLeft { select: false }]),
         );
         let mut vi = Vi {
             insert_keybindings: default_vi_insert_keybindings(),
@@ -303,8 +417,171 @@ mod test {
         assert_eq!(
             res,
             ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![
-                EditCommand::MoveRightUntil('B')
+                EditCommand::MoveRightUntil {
+                    c: 'B',
+                    select: false
+                }
             ])])
         );
     }
+
+    #[test]
+    fn counts_repeat_the_bound_events() {
+        let mut vi = Vi {
+            mode: ViMode::Normal,
+            ..Default::default()
+        };
+        let increment = ReedlineEvent::Edit(vec![EditCommand::IncrementNumber]);
+        assert_eq!(
+            normal_mode_key(&mut vi, KeyModifiers::CONTROL, 'a'),
+            increment
+        );
+
+        normal_mode_key(&mut vi, KeyModifiers::NONE, '1');
+        normal_mode_key(&mut vi, KeyModifiers::NONE, '2');
+        assert_eq!(
+            normal_mode_key(&mut vi, KeyModifiers::CONTROL, 'a'),
+            ReedlineEvent::Multiple(vec![increment; 12])
+        );
+        assert!(!vi.has_pending_keys());
+    }
+
+    #[test]
+    fn char_searches_can_cross_lines() {
+        let mut vi = Vi::default().with_char_search_across_lines(true);
+        vi.mode = ViMode::Normal;
+        normal_mode_key(&mut vi, KeyModifiers::NONE, 'd');
+        normal_mode_key(&mut vi, KeyModifiers::NONE, 't');
+        assert_eq!(
+            normal_mode_key(&mut vi, KeyModifiers::NONE, 'x'),
+            ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![
+                EditCommand::CutToCharAcrossLines {
+                    c: 'x',
+                    right: true,
+                    before: true
+                }
+            ])])
+        );
+        assert_eq!(
+            normal_mode_key(&mut vi, KeyModifiers::NONE, ','),
+            ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![
+                EditCommand::MoveToCharAcrossLines {
+                    c: 'x',
+                    right: false,
+                    before: true,
+                    select: false
+                }
+            ])])
+        );
+    }
+
+    #[test]
+    fn char_searches_are_forgotten_per_the_reset_policy() {
+        let search = |vi: &mut Vi| {
+            normal_mode_key(vi, KeyModifiers::NONE, 'f');
+            normal_mode_key(vi, KeyModifiers::NONE, 'x');
+        };
+        let repeats =
+            |vi: &mut Vi| normal_mode_key(vi, KeyModifiers::NONE, ';') != ReedlineEvent::None;
+
+        let mut vi = Vi::default().with_char_search_reset(ViCharSearchReset::Never);
+        vi.mode = ViMode::Normal;
+        search(&mut vi);
+        normal_mode_key(&mut vi, KeyModifiers::NONE, 'i');
+        vi.start_line();
+        vi.mode = ViMode::Normal;
+        assert!(repeats(&mut vi));
+
+        let mut vi = Vi::default().with_char_search_reset(ViCharSearchReset::EachLine);
+        vi.mode = ViMode::Normal;
+        search(&mut vi);
+        normal_mode_key(&mut vi, KeyModifiers::NONE, 'i');
+        vi.mode = ViMode::Normal;
+        assert!(repeats(&mut vi));
+        vi.start_line();
+        assert!(!repeats(&mut vi));
+
+        let mut vi = Vi::default().with_char_search_reset(ViCharSearchReset::InsertMode);
+        vi.mode = ViMode::Normal;
+        search(&mut vi);
+        assert!(repeats(&mut vi));
+        normal_mode_key(&mut vi, KeyModifiers::NONE, 'i');
+        vi.mode = ViMode::Normal;
+        assert!(!repeats(&mut vi));
+    }
+
+    fn normal_mode_key(vi: &mut Vi, modifier: KeyModifiers, c: char) -> ReedlineEvent {
+        vi.parse_event(
+            ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(KeyCode::Char(c), modifier)))
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn normal_mode_bindings_keep_the_case_of_letters() {
+        let mut keybindings = default_vi_normal_keybindings();
+        keybindings.add_binding(KeyModifiers::NONE, KeyCode::Char('q'), ReedlineEvent::CtrlD);
+        keybindings.add_binding(
+            KeyModifiers::SHIFT,
+            KeyCode::Char('Q'),
+            ReedlineEvent::ClearScreen,
+        );
+        let mut vi = Vi {
+            normal_keybindings: keybindings,
+            mode: ViMode::Normal,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            normal_mode_key(&mut vi, KeyModifiers::NONE, 'q'),
+            ReedlineEvent::CtrlD
+        );
+        assert_eq!(
+            normal_mode_key(&mut vi, KeyModifiers::SHIFT, 'Q'),
+            ReedlineEvent::ClearScreen
+        );
+        // Without Shift reported, e.g. with caps lock
+        assert_eq!(
+            normal_mode_key(&mut vi, KeyModifiers::NONE, 'Q'),
+            ReedlineEvent::ClearScreen
+        );
+    }
+
+    #[test]
+    fn shift_with_lowercase_letter_binding_matches_the_uppercase_letter() {
+        let mut keybindings = default_vi_normal_keybindings();
+        keybindings.add_binding(
+            KeyModifiers::SHIFT,
+            KeyCode::Char('q'),
+            ReedlineEvent::CtrlD,
+        );
+        let mut vi = Vi {
+            normal_keybindings: keybindings,
+            mode: ViMode::Normal,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            normal_mode_key(&mut vi, KeyModifiers::SHIFT, 'Q'),
+            ReedlineEvent::CtrlD
+        );
+        assert_eq!(
+            normal_mode_key(&mut vi, KeyModifiers::NONE, 'q'),
+            ReedlineEvent::None
+        );
+    }
+
+    #[test]
+    fn uppercase_letter_without_shift_is_not_its_lowercase_command() {
+        let mut vi = Vi {
+            mode: ViMode::Normal,
+            ..Default::default()
+        };
+
+        // `D` deletes to the line end, `d` would wait for a motion
+        assert_eq!(
+            normal_mode_key(&mut vi, KeyModifiers::NONE, 'D'),
+            ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![EditCommand::CutToLineEnd])])
+        );
+    }
 }
@@ -1,123 +1,48 @@
-use std::iter::Peekable;
-
 use crate::{EditCommand, ReedlineEvent, Vi};
 
-use super::parser::{ParseResult, ReedlineOption};
+use super::parser::ReedlineOption;
 
-pub fn parse_motion<'iter, I>(
-    input: &mut Peekable<I>,
-    command_char: Option<char>,
-) -> ParseResult<Motion>
-where
-    I: Iterator<Item = &'iter char>,
-{
-    match input.peek() {
-        Some('h') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::Left)
-        }
-        Some('l') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::Right)
-        }
-        Some('j') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::Down)
-        }
-        Some('k') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::Up)
-        }
-        Some('b') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::PreviousWord)
-        }
-        Some('B') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::PreviousBigWord)
-        }
-        Some('w') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::NextWord)
-        }
-        Some('W') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::NextBigWord)
-        }
-        Some('e') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::NextWordEnd)
-        }
-        Some('E') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::NextBigWordEnd)
-        }
-        Some('0' | '^') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::Start)
-        }
-        Some('$') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::End)
-        }
-        Some('f') => {
-            let _ = input.next();
-            match input.peek() {
-                Some(&x) => {
-                    input.next();
-                    ParseResult::Valid(Motion::RightUntil(*x))
-                }
-                None => ParseResult::Incomplete,
-            }
-        }
-        Some('t') => {
-            let _ = input.next();
-            match input.peek() {
-                Some(&x) => {
-                    input.next();
-                    ParseResult::Valid(Motion::RightBefore(*x))
-                }
-                None => ParseResult::Incomplete,
-            }
-        }
-        Some('F') => {
-            let _ = input.next();
-            match input.peek() {
-                Some(&x) => {
-                    input.next();
-                    ParseResult::Valid(Motion::LeftUntil(*x))
-                }
-                None => ParseResult::Incomplete,
-            }
-        }
-        Some('T') => {
-            let _ = input.next();
-            match input.peek() {
-                Some(&x) => {
-                    input.next();
-                    ParseResult::Valid(Motion::LeftBefore(*x))
-                }
-                None => ParseResult::Incomplete,
-            }
-        }
-        Some(';') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::ReplayCharSearch)
-        }
-        Some(',') => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::ReverseCharSearch)
-        }
-        ch if ch == command_char.as_ref().as_ref() && command_char.is_some() => {
-            let _ = input.next();
-            ParseResult::Valid(Motion::Line)
-        }
-        None => ParseResult::Incomplete,
-        _ => ParseResult::Invalid,
-    }
+/// What a key sequence of the [`MOTIONS`] table starts in normal mode
+#[derive(Clone)]
+pub enum MotionKey {
+    /// A motion that is complete on its own, e.g. `w`
+    Motion(Motion),
+    /// A motion completed by the next typed character, e.g. `f`
+    WithChar(fn(char) -> Motion),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Key sequences of the normal mode motions, used on their own or after an operator
+///
+/// No key sequence may be the prefix of another one, or of one in
+/// [`super::command::COMMANDS`], as the parser completes a motion as soon as its keys
+/// are typed. `0` is only a motion when no count is being typed.
+pub const MOTIONS: &[(&str, MotionKey)] = &[
+    ("h", MotionKey::Motion(Motion::Left)),
+    ("l", MotionKey::Motion(Motion::Right)),
+    ("j", MotionKey::Motion(Motion::Down)),
+    ("k", MotionKey::Motion(Motion::Up)),
+    ("b", MotionKey::Motion(Motion::PreviousWord)),
+    ("B", MotionKey::Motion(Motion::PreviousBigWord)),
+    ("w", MotionKey::Motion(Motion::NextWord)),
+    ("W", MotionKey::Motion(Motion::NextBigWord)),
+    ("e", MotionKey::Motion(Motion::NextWordEnd)),
+    ("E", MotionKey::Motion(Motion::NextBigWordEnd)),
+    ("0", MotionKey::Motion(Motion::Start)),
+    ("^", MotionKey::Motion(Motion::Start)),
+    ("$", MotionKey::Motion(Motion::End)),
+    ("{", MotionKey::Motion(Motion::PreviousParagraph)),
+    ("}", MotionKey::Motion(Motion::NextParagraph)),
+    ("(", MotionKey::Motion(Motion::PreviousSentence)),
+    (")", MotionKey::Motion(Motion::NextSentence)),
+    ("f", MotionKey::WithChar(Motion::RightUntil)),
+    ("t", MotionKey::WithChar(Motion::RightBefore)),
+    ("F", MotionKey::WithChar(Motion::LeftUntil)),
+    ("T", MotionKey::WithChar(Motion::LeftBefore)),
+    (";", MotionKey::Motion(Motion::ReplayCharSearch)),
+    (",", MotionKey::Motion(Motion::ReverseCharSearch)),
+];
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Motion {
     Left,
     Right,
@@ -129,9 +54,14 @@ pub enum Motion {
     NextBigWordEnd,
     PreviousWord,
     PreviousBigWord,
+    /// The whole line, typed by repeating the operator, e.g. `dd`
     Line,
     Start,
     End,
+    PreviousParagraph,
+    NextParagraph,
+    PreviousSentence,
+    NextSentence,
     RightUntil(char),
     RightBefore(char),
     LeftUntil(char),
@@ -141,6 +71,18 @@ pub enum Motion {
 }
 
 impl Motion {
+    /// The move selecting the text covered by the paragraph and sentence motions, which
+    /// operators cut or change as a selection
+    pub(super) fn selection(&self) -> Option<EditCommand> {
+        match self {
+            Motion::PreviousParagraph => Some(EditCommand::MoveParagraphLeft { select: true }),
+            Motion::NextParagraph => Some(EditCommand::MoveParagraphRight { select: true }),
+            Motion::PreviousSentence => Some(EditCommand::MoveSentenceLeft { select: true }),
+            Motion::NextSentence => Some(EditCommand::MoveSentenceRight { select: true }),
+            _ => None,
+        }
+    }
+
     pub fn to_reedline(&self, vi_state: &mut Vi) -> Vec<ReedlineOption> {
         match self {
             Motion::Left => vec![ReedlineOption::Event(ReedlineEvent::UntilFound(vec![
@@ -187,44 +129,48 @@ impl Motion {
             Motion::End => vec![ReedlineOption::Edit(EditCommand::MoveToLineEnd {
                 select: false,
             })],
-            Motion::RightUntil(ch) => {
-                vi_state.last_char_search = Some(ViCharSearch::ToRight(*ch));
-                vec![ReedlineOption::Edit(EditCommand::MoveRightUntil {
-                    c: *ch,
-                    select: false,
-                })]
-            }
-            Motion::RightBefore(ch) => {
-                vi_state.last_char_search = Some(ViCharSearch::TillRight(*ch));
-                vec![ReedlineOption::Edit(EditCommand::MoveRightBefore {
-                    c: *ch,
-                    select: false,
-                })]
-            }
-            Motion::LeftUntil(ch) => {
-                vi_state.last_char_search = Some(ViCharSearch::ToLeft(*ch));
-                vec![ReedlineOption::Edit(EditCommand::MoveLeftUntil {
-                    c: *ch,
-                    select: false,
-                })]
-            }
-            Motion::LeftBefore(ch) => {
-                vi_state.last_char_search = Some(ViCharSearch::TillLeft(*ch));
-                vec![ReedlineOption::Edit(EditCommand::MoveLeftBefore {
-                    c: *ch,
+            Motion::PreviousParagraph => {
+                vec![ReedlineOption::Edit(EditCommand::MoveParagraphLeft {
                     select: false,
                 })]
             }
+            Motion::NextParagraph => vec![ReedlineOption::Edit(EditCommand::MoveParagraphRight {
+                select: false,
+            })],
+            Motion::PreviousSentence => vec![ReedlineOption::Edit(EditCommand::MoveSentenceLeft {
+                select: false,
+            })],
+            Motion::NextSentence => vec![ReedlineOption::Edit(EditCommand::MoveSentenceRight {
+                select: false,
+            })],
+            Motion::RightUntil(ch) => vec![ReedlineOption::Edit(
+                vi_state.start_char_search(ViCharSearch::ToRight(*ch), false),
+            )],
+            Motion::RightBefore(ch) => vec![ReedlineOption::Edit(
+                vi_state.start_char_search(ViCharSearch::TillRight(*ch), false),
+            )],
+            Motion::LeftUntil(ch) => vec![ReedlineOption::Edit(
+                vi_state.start_char_search(ViCharSearch::ToLeft(*ch), false),
+            )],
+            Motion::LeftBefore(ch) => vec![ReedlineOption::Edit(
+                vi_state.start_char_search(ViCharSearch::TillLeft(*ch), false),
+            )],
             Motion::ReplayCharSearch => {
                 if let Some(char_search) = vi_state.last_char_search.as_ref() {
-                    vec![ReedlineOption::Edit(char_search.to_move())]
+                    vec![ReedlineOption::Edit(
+                        char_search.to_move(vi_state.char_search_across_lines),
+                    )]
                 } else {
                     vec![]
                 }
             }
             Motion::ReverseCharSearch => {
                 if let Some(char_search) = vi_state.last_char_search.as_ref() {
-                    vec![ReedlineOption::Edit(char_search.reverse().to_move())]
+                    vec![ReedlineOption::Edit(
+                        char_search
+                            .reverse()
+                            .to_move(vi_state.char_search_across_lines),
+                    )]
                 } else {
                     vec![]
                 }
@@ -257,28 +203,55 @@ impl ViCharSearch {
         }
     }
 
-    pub fn to_move(&self) -> EditCommand {
+    // The char, whether the search goes to the right and whether it stops before the char
+    fn parts(&self) -> (char, bool, bool) {
         match self {
-            ViCharSearch::ToRight(c) => EditCommand::MoveRightUntil {
-                c: *c,
-                select: false,
-            },
-            ViCharSearch::ToLeft(c) => EditCommand::MoveLeftUntil {
-                c: *c,
-                select: false,
-            },
-            ViCharSearch::TillRight(c) => EditCommand::MoveRightBefore {
-                c: *c,
-                select: false,
-            },
-            ViCharSearch::TillLeft(c) => EditCommand::MoveLeftBefore {
-                c: *c,
-                select: false,
-            },
+            ViCharSearch::ToRight(c) => (*c, true, false),
+            ViCharSearch::ToLeft(c) => (*c, false, false),
+            ViCharSearch::TillRight(c) => (*c, true, true),
+            ViCharSearch::TillLeft(c) => (*c, false, true),
+        }
+    }
+
+    /// Whether an operator covers the char the motion ends on, as for `f` and `t`
+    pub fn is_inclusive(&self) -> bool {
+        matches!(self, ViCharSearch::ToRight(_) | ViCharSearch::TillRight(_))
+    }
+
+    /// The motion to the char, searching the other lines too if `across_lines`
+    pub fn to_move(&self, across_lines: bool) -> EditCommand {
+        self.to_motion(across_lines, false)
+    }
+
+    /// The motion to the char selecting the text on the way, see [`ViCharSearch::to_move`]
+    pub fn to_selection(&self, across_lines: bool) -> EditCommand {
+        self.to_motion(across_lines, true)
+    }
+
+    fn to_motion(&self, across_lines: bool, select: bool) -> EditCommand {
+        if across_lines {
+            let (c, right, before) = self.parts();
+            return EditCommand::MoveToCharAcrossLines {
+                c,
+                right,
+                before,
+                select,
+            };
+        }
+        match self {
+            ViCharSearch::ToRight(c) => EditCommand::MoveRightUntil { c: *c, select },
+            ViCharSearch::ToLeft(c) => EditCommand::MoveLeftUntil { c: *c, select },
+            ViCharSearch::TillRight(c) => EditCommand::MoveRightBefore { c: *c, select },
+            ViCharSearch::TillLeft(c) => EditCommand::MoveLeftBefore { c: *c, select },
         }
     }
 
-    pub fn to_cut(&self) -> EditCommand {
+    /// Cutting to the char, searching the other lines too if `across_lines`
+    pub fn to_cut(&self, across_lines: bool) -> EditCommand {
+        if across_lines {
+            let (c, right, before) = self.parts();
+            return EditCommand::CutToCharAcrossLines { c, right, before };
+        }
         match self {
             ViCharSearch::ToRight(c) => EditCommand::CutRightUntil(*c),
             ViCharSearch::ToLeft(c) => EditCommand::CutLeftUntil(*c),
@@ -1,21 +1,18 @@
-use super::command::{parse_command, Command};
-use super::motion::{parse_motion, Motion};
-use crate::{EditCommand, ReedlineEvent, Vi};
-use std::iter::Peekable;
+use super::command::{Command, CommandKey, Operator, COMMANDS};
+use super::motion::{Motion, MotionKey, MOTIONS};
+use crate::{EditCommand, PromptViPending, ReedlineEvent, Vi};
 
 #[derive(Debug, Clone)]
 pub enum ReedlineOption {
     Event(ReedlineEvent),
     Edit(EditCommand),
-    Incomplete,
 }
 
 impl ReedlineOption {
-    pub fn into_reedline_event(self) -> Option<ReedlineEvent> {
+    pub fn into_reedline_event(self) -> ReedlineEvent {
         match self {
-            ReedlineOption::Event(event) => Some(event),
-            ReedlineOption::Edit(edit) => Some(ReedlineEvent::Edit(vec![edit])),
-            ReedlineOption::Incomplete => None,
+            ReedlineOption::Event(event) => event,
+            ReedlineOption::Edit(edit) => ReedlineEvent::Edit(vec![edit]),
         }
     }
 }
@@ -27,40 +24,23 @@ pub enum ParseResult<T> {
     Invalid,
 }
 
-impl<T> ParseResult<T> {
-    fn is_invalid(&self) -> bool {
-        match self {
-            ParseResult::Valid(_) => false,
-            ParseResult::Incomplete => false,
-            ParseResult::Invalid => true,
-        }
-    }
-}
-
-#[derive(Debug, PartialEq, Eq)]
+/// A complete normal mode sequence
+///
+/// Follows the grammar `[count] ["register] (command | [operator [count]] motion)`,
+/// where commands, operators and motions are the key sequences of [`COMMANDS`] and
+/// [`MOTIONS`]. Repeating the keys of an operator, e.g. `dd`, is the [`Motion::Line`].
+#[derive(Debug, PartialEq, Eq, Default)]
 pub struct ParsedViSequence {
     multiplier: Option<usize>,
+    // Accepted so that sequences with a register work, all registers share the cut buffer
+    register: Option<char>,
     command: Option<Command>,
+    operator: Option<Operator>,
     count: Option<usize>,
-    motion: ParseResult<Motion>,
+    motion: Option<Motion>,
 }
 
 impl ParsedViSequence {
-    pub fn is_valid(&self) -> bool {
-        !self.motion.is_invalid()
-    }
-
-    pub fn is_complete(&self) -> bool {
-        match (&self.command, &self.motion) {
-            (None, ParseResult::Valid(_)) => true,
-            (Some(Command::Incomplete), _) => false,
-            (Some(cmd), ParseResult::Incomplete) if !cmd.requires_motion() => true,
-            (Some(_), ParseResult::Valid(_)) => true,
-            (Some(cmd), ParseResult::Incomplete) if cmd.requires_motion() => false,
-            _ => false,
-        }
-    }
-
     /// Combine `multiplier` and `count` as vim only considers the product
     ///
     /// Default return value: 1
@@ -73,11 +53,15 @@ impl ParsedViSequence {
     }
 
     fn apply_multiplier(&self, raw_events: Option<Vec<ReedlineOption>>) -> ReedlineEvent {
+        Self::repeat(raw_events, self.total_multiplier())
+    }
+
+    fn repeat(raw_events: Option<Vec<ReedlineOption>>, times: usize) -> ReedlineEvent {
         if let Some(raw_events) = raw_events {
             let events = std::iter::repeat(raw_events)
-                .take(self.total_multiplier())
+                .take(times)
                 .flatten()
-                .filter_map(ReedlineOption::into_reedline_event)
+                .map(ReedlineOption::into_reedline_event)
                 .collect::<Vec<ReedlineEvent>>();
 
             if events.is_empty() || events.contains(&ReedlineEvent::None) {
@@ -92,317 +76,513 @@ impl ParsedViSequence {
     }
 
     pub fn enters_insert_mode(&self) -> bool {
-        matches!(
-            (&self.command, &self.motion),
-            (Some(Command::EnterViInsert), ParseResult::Incomplete)
-                | (Some(Command::EnterViAppend), ParseResult::Incomplete)
-                | (Some(Command::ChangeToLineEnd), ParseResult::Incomplete)
-                | (Some(Command::AppendToEnd), ParseResult::Incomplete)
-                | (Some(Command::PrependToStart), ParseResult::Incomplete)
-                | (Some(Command::RewriteCurrentLine), ParseResult::Incomplete)
-                | (
-                    Some(Command::SubstituteCharWithInsert),
-                    ParseResult::Incomplete
-                )
-                | (Some(Command::HistorySearch), ParseResult::Incomplete)
-                | (Some(Command::Change), ParseResult::Valid(_))
-        )
+        self.command
+            .as_ref()
+            .map_or(false, |command| command.enters_insert_mode())
+            || self.operator == Some(Operator::Change)
     }
 
     pub fn to_reedline_event(&self, vi_state: &mut Vi) -> ReedlineEvent {
-        match (&self.multiplier, &self.command, &self.count, &self.motion) {
-            (_, Some(command), None, ParseResult::Incomplete) => {
-                let events = self.apply_multiplier(Some(command.to_reedline(vi_state)));
-                match &events {
-                    ReedlineEvent::None => {}
-                    event => vi_state.previous = Some(event.clone()),
-                }
-                events
-            }
-            // This case handles all combinations of commands and motions that could exist
-            (_, Some(command), _, ParseResult::Valid(motion)) => {
-                let events =
-                    self.apply_multiplier(command.to_reedline_with_motion(motion, vi_state));
-                match &events {
-                    ReedlineEvent::None => {}
-                    event => vi_state.previous = Some(event.clone()),
-                }
-                events
+        let (events, repeatable) = match (&self.command, &self.operator, &self.motion) {
+            // Repeating would switch the case back, the count applies to the chars or motion
+            (Some(Command::Switchcase), _, _) => (
+                Self::repeat(
+                    Some(vec![ReedlineOption::Edit(EditCommand::SwitchcaseChars(
+                        self.total_multiplier(),
+                    ))]),
+                    1,
+                ),
+                true,
+            ),
+            (None, Some(Operator::Switchcase), Some(motion)) => {
+                let options = Operator::Switchcase
+                    .to_reedline_with_motion(motion, vi_state)
+                    .map(|mut options| {
+                        let switch = options.pop();
+                        let mut repeated: Vec<_> = std::iter::repeat(options)
+                            .take(self.total_multiplier())
+                            .flatten()
+                            .collect();
+                        repeated.extend(switch);
+                        repeated
+                    });
+                (Self::repeat(options, 1), true)
             }
-            (_, None, _, ParseResult::Valid(motion)) => {
-                self.apply_multiplier(Some(motion.to_reedline(vi_state)))
-            }
-            _ => ReedlineEvent::None,
+            (Some(command), _, _) => (
+                self.apply_multiplier(Some(command.to_reedline(vi_state))),
+                true,
+            ),
+            (None, Some(operator), Some(motion)) => (
+                self.apply_multiplier(operator.to_reedline_with_motion(motion, vi_state)),
+                true,
+            ),
+            (None, None, Some(motion)) => (
+                self.apply_multiplier(Some(motion.to_reedline(vi_state))),
+                false,
+            ),
+            _ => (ReedlineEvent::None, false),
+        };
+        if repeatable && events != ReedlineEvent::None {
+            vi_state.previous = Some(events.clone());
         }
+        events
     }
 }
 
-fn parse_number<'iter, I>(input: &mut Peekable<I>) -> Option<usize>
-where
-    I: Iterator<Item = &'iter char>,
-{
-    match input.peek() {
-        Some('0') => None,
-        Some(x) if x.is_ascii_digit() => {
-            let mut count: usize = 0;
-            while let Some(&c) = input.peek() {
-                if c.is_ascii_digit() {
-                    let c = c.to_digit(10).expect("already checked if is a digit");
-                    let _ = input.next();
-                    count *= 10;
-                    count += c as usize;
-                } else {
-                    return Some(count);
-                }
-            }
-            Some(count)
+/// What the next typed character continues
+#[derive(Debug, Clone, Copy)]
+enum State {
+    /// Before the command, operator or motion, a count or register may come first
+    Start,
+    /// After `"`, waiting for the name of the register
+    Register,
+    /// After the keys of an operator, a count may come before the motion
+    Operator(&'static str),
+    /// Waiting for the character argument of a command, e.g. after `r`
+    CommandChar(fn(char) -> Command),
+    /// Waiting for the character argument of a motion, e.g. after `f`
+    MotionChar(fn(char) -> Motion),
+}
+
+enum Lookup<T> {
+    Found(&'static str, T),
+    Prefix,
+    Missing,
+}
+
+// The entry of `table` for the typed `keys`, or whether more keys could still match one
+fn lookup<T: Clone>(table: &'static [(&'static str, T)], keys: &str) -> Lookup<T> {
+    let mut lookup = Lookup::Missing;
+    for (entry_keys, entry) in table {
+        if *entry_keys == keys {
+            return Lookup::Found(entry_keys, entry.clone());
+        } else if entry_keys.starts_with(keys) {
+            lookup = Lookup::Prefix;
         }
-        _ => None,
     }
+    lookup
+}
+
+/// Parser of the normal mode key sequences, fed one character at a time
+pub struct ViParser {
+    state: State,
+    // The keys typed so far of a command, operator or motion that spans several keys
+    keys: String,
+    sequence: ParsedViSequence,
+    // The keys of the operator of the started sequence
+    operator_keys: Option<&'static str>,
+    // All keys of the started sequence
+    typed: String,
 }
 
-pub fn parse<'iter, I>(input: &mut Peekable<I>) -> ParsedViSequence
-where
-    I: Iterator<Item = &'iter char>,
-{
-    let multiplier = parse_number(input);
-    let command = parse_command(input);
-    let count = parse_number(input);
-    let motion = parse_motion(input, command.as_ref().and_then(Command::whole_line_char));
-
-    ParsedViSequence {
-        multiplier,
-        command,
-        count,
-        motion,
+impl Default for ViParser {
+    fn default() -> Self {
+        Self {
+            state: State::Start,
+            keys: String::new(),
+            sequence: ParsedViSequence::default(),
+            operator_keys: None,
+            typed: String::new(),
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use pretty_assertions::assert_eq;
-    use rstest::rstest;
+impl ViParser {
+    /// Whether a started sequence waits for more characters
+    pub fn is_pending(&self) -> bool {
+        !matches!(self.state, State::Start)
+            || !self.keys.is_empty()
+            || self.sequence != ParsedViSequence::default()
+    }
 
-    fn vi_parse(input: &[char]) -> ParsedViSequence {
-        parse(&mut input.iter().peekable())
+    /// The keys typed so far of the started sequence, e.g. `2d`
+    pub fn pending_keys(&self) -> &str {
+        &self.typed
     }
 
-    #[test]
-    fn test_delete_word() {
-        let input = ['d', 'w'];
-        let output = vi_parse(&input);
+    /// The state of the started sequence, for the prompt
+    pub fn pending(&self) -> PromptViPending {
+        let count = match (self.sequence.multiplier, self.sequence.count) {
+            (None, None) => None,
+            (multiplier, count) => Some(multiplier.unwrap_or(1) * count.unwrap_or(1)),
+        };
+        PromptViPending {
+            keys: self.typed.clone(),
+            count,
+            register: self.sequence.register,
+            operator: self.operator_keys.map(str::to_string),
+        }
+    }
 
-        assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: None,
-                command: Some(Command::Delete),
-                count: None,
-                motion: ParseResult::Valid(Motion::NextWord),
-            }
-        );
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), true);
+    /// The count typed so far, if nothing but a count was typed, dropping it
+    ///
+    /// Lets a count like the `5` of `5 Ctrl-a` apply to a keybinding.
+    pub fn take_count(&mut self) -> Option<usize> {
+        let only_count = matches!(self.state, State::Start)
+            && self.keys.is_empty()
+            && self.sequence
+                == ParsedViSequence {
+                    multiplier: self.sequence.multiplier,
+                    ..ParsedViSequence::default()
+                };
+        let count = self.sequence.multiplier.filter(|_| only_count)?;
+        self.reset();
+        Some(count)
     }
 
-    #[test]
-    fn test_two_delete_word() {
-        let input = ['2', 'd', 'w'];
-        let output = vi_parse(&input);
+    /// Drop the started sequence
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
 
-        assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: Some(2),
-                command: Some(Command::Delete),
-                count: None,
-                motion: ParseResult::Valid(Motion::NextWord),
-            }
-        );
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), true);
+    /// Continue the sequence with `c`
+    ///
+    /// Once the sequence is complete or invalid, the parser starts over with the next
+    /// character.
+    pub fn push(&mut self, c: char) -> ParseResult<ParsedViSequence> {
+        self.typed.push(c);
+        let result = self.step(c);
+        match result {
+            ParseResult::Incomplete => {}
+            ParseResult::Valid(_) | ParseResult::Invalid => self.reset(),
+        }
+        result
     }
 
-    #[test]
-    fn test_two_delete_two_word() {
-        let input = ['2', 'd', '2', 'w'];
-        let output = vi_parse(&input);
+    fn step(&mut self, c: char) -> ParseResult<ParsedViSequence> {
+        match self.state {
+            State::Register => {
+                self.sequence.register = Some(c);
+                self.state = State::Start;
+                return ParseResult::Incomplete;
+            }
+            State::CommandChar(command) => return self.complete_command(command(c)),
+            State::MotionChar(motion) => return self.complete_motion(motion(c)),
+            State::Start | State::Operator(..) => {}
+        }
 
-        assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: Some(2),
-                command: Some(Command::Delete),
-                count: Some(2),
-                motion: ParseResult::Valid(Motion::NextWord),
+        if self.keys.is_empty() {
+            let count = match self.state {
+                State::Operator(..) => &mut self.sequence.count,
+                _ => &mut self.sequence.multiplier,
+            };
+            // A leading `0` is the motion to the line start
+            if let Some(digit) = c.to_digit(10).filter(|&d| d != 0 || count.is_some()) {
+                *count = Some(
+                    count
+                        .unwrap_or(0)
+                        .saturating_mul(10)
+                        .saturating_add(digit as usize),
+                );
+                return ParseResult::Incomplete;
             }
-        );
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), true);
+            if c == '"' && matches!(self.state, State::Start) && self.sequence.register.is_none() {
+                self.state = State::Register;
+                return ParseResult::Incomplete;
+            }
+        }
+
+        self.keys.push(c);
+        let command = match self.state {
+            // Repeating the operator, or only its last key like in `g~~`, covers the line
+            State::Operator(operator_keys) if operator_keys.ends_with(self.keys.as_str()) => {
+                return self.complete_motion(Motion::Line)
+            }
+            State::Operator(..) => Lookup::Missing,
+            _ => lookup(COMMANDS, &self.keys),
+        };
+        let motion = lookup(MOTIONS, &self.keys);
+
+        match (command, motion) {
+            (Lookup::Found(_, CommandKey::Command(command)), _) => self.complete_command(command),
+            (Lookup::Found(_, CommandKey::WithChar(command)), _) => {
+                self.state = State::CommandChar(command);
+                ParseResult::Incomplete
+            }
+            (Lookup::Found(keys, CommandKey::Operator(operator)), _) => {
+                self.sequence.operator = Some(operator);
+                self.operator_keys = Some(keys);
+                self.state = State::Operator(keys);
+                self.keys.clear();
+                ParseResult::Incomplete
+            }
+            (_, Lookup::Found(_, MotionKey::Motion(motion))) => self.complete_motion(motion),
+            (_, Lookup::Found(_, MotionKey::WithChar(motion))) => {
+                self.state = State::MotionChar(motion);
+                ParseResult::Incomplete
+            }
+            (Lookup::Prefix, _) | (_, Lookup::Prefix) => ParseResult::Incomplete,
+            (Lookup::Missing, Lookup::Missing) => ParseResult::Invalid,
+        }
     }
 
-    #[test]
-    fn test_two_delete_twenty_word() {
-        let input = ['2', 'd', '2', '0', 'w'];
-        let output = vi_parse(&input);
+    fn complete_command(&mut self, command: Command) -> ParseResult<ParsedViSequence> {
+        self.sequence.command = Some(command);
+        ParseResult::Valid(std::mem::take(&mut self.sequence))
+    }
 
-        assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: Some(2),
-                command: Some(Command::Delete),
-                count: Some(20),
-                motion: ParseResult::Valid(Motion::NextWord),
-            }
-        );
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), true);
+    fn complete_motion(&mut self, motion: Motion) -> ParseResult<ParsedViSequence> {
+        self.sequence.motion = Some(motion);
+        ParseResult::Valid(std::mem::take(&mut self.sequence))
     }
+}
 
-    #[test]
-    fn test_two_delete_two_lines() {
-        let input = ['2', 'd', 'd'];
-        let output = vi_parse(&input);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ViewportLine;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
 
-        assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: Some(2),
-                command: Some(Command::Delete),
-                count: None,
-                motion: ParseResult::Valid(Motion::Line),
-            }
-        );
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), true);
+    fn vi_parse(input: &[char]) -> ParseResult<ParsedViSequence> {
+        let mut parser = ViParser::default();
+        let (last, init) = input.split_last().expect("input is not empty");
+        for &c in init {
+            assert_eq!(parser.push(c), ParseResult::Incomplete, "after {c:?}");
+        }
+        parser.push(*last)
     }
 
-    #[test]
-    fn test_find_action() {
-        let input = ['d', 't', 'd'];
-        let output = vi_parse(&input);
+    fn motion(multiplier: Option<usize>, motion: Motion) -> ParsedViSequence {
+        ParsedViSequence {
+            multiplier,
+            motion: Some(motion),
+            ..Default::default()
+        }
+    }
 
-        assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: None,
-                command: Some(Command::Delete),
-                count: None,
-                motion: ParseResult::Valid(Motion::RightBefore('d')),
-            }
-        );
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), true);
+    fn command(multiplier: Option<usize>, command: Command) -> ParsedViSequence {
+        ParsedViSequence {
+            multiplier,
+            command: Some(command),
+            ..Default::default()
+        }
     }
 
-    #[test]
-    fn test_has_garbage() {
-        let input = ['2', 'd', 'm'];
-        let output = vi_parse(&input);
+    fn operation(
+        multiplier: Option<usize>,
+        operator: Operator,
+        count: Option<usize>,
+        motion: Motion,
+    ) -> ParsedViSequence {
+        ParsedViSequence {
+            multiplier,
+            operator: Some(operator),
+            count,
+            motion: Some(motion),
+            ..Default::default()
+        }
+    }
 
-        assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: Some(2),
-                command: Some(Command::Delete),
-                count: None,
-                motion: ParseResult::Invalid,
-            }
-        );
-        assert_eq!(output.is_valid(), false);
+    #[rstest]
+    #[case(&['w'], motion(None, Motion::NextWord))]
+    #[case(&['2', 'k'], motion(Some(2), Motion::Up))]
+    #[case(&['0'], motion(None, Motion::Start))]
+    #[case(&['1', '0', 'l'], motion(Some(10), Motion::Right))]
+    #[case(&['2', 'f', 'f'], motion(Some(2), Motion::RightUntil('f')))]
+    #[case(&['T', '0'], motion(None, Motion::LeftBefore('0')))]
+    #[case(&['f', '"'], motion(None, Motion::RightUntil('"')))]
+    #[case(&[';'], motion(None, Motion::ReplayCharSearch))]
+    #[case(&['x'], command(None, Command::DeleteChar))]
+    #[case(&['3', 'p'], command(Some(3), Command::PasteAfter))]
+    #[case(&['r', 'k'], command(None, Command::ReplaceChar('k')))]
+    #[case(&['r', 'd'], command(None, Command::ReplaceChar('d')))]
+    #[case(&['r', '2'], command(None, Command::ReplaceChar('2')))]
+    #[case(&['.'], command(None, Command::RepeatLastAction))]
+    #[case(&['d', 'w'], operation(None, Operator::Delete, None, Motion::NextWord))]
+    #[case(&['d', 'd'], operation(None, Operator::Delete, None, Motion::Line))]
+    #[case(&['c', 'c'], operation(None, Operator::Change, None, Motion::Line))]
+    #[case(&['d', '0'], operation(None, Operator::Delete, None, Motion::Start))]
+    #[case(&['2', 'd', 'w'], operation(Some(2), Operator::Delete, None, Motion::NextWord))]
+    #[case(&['2', 'd', 'd'], operation(Some(2), Operator::Delete, None, Motion::Line))]
+    #[case(&['2', 'd', '2', 'w'], operation(Some(2), Operator::Delete, Some(2), Motion::NextWord))]
+    #[case(&['2', 'd', '2', '0', 'w'], operation(Some(2), Operator::Delete, Some(20), Motion::NextWord))]
+    #[case(&['d', 't', 'd'], operation(None, Operator::Delete, None, Motion::RightBefore('d')))]
+    #[case(&['c', 'F', 'c'], operation(None, Operator::Change, None, Motion::LeftUntil('c')))]
+    #[case(&['d', '}'], operation(None, Operator::Delete, None, Motion::NextParagraph))]
+    #[case(&['2', 'c', '('], operation(Some(2), Operator::Change, None, Motion::PreviousSentence))]
+    fn valid_sequences(#[case] input: &[char], #[case] expected: ParsedViSequence) {
+        assert_eq!(vi_parse(input), ParseResult::Valid(expected));
     }
 
     #[test]
-    fn test_partial_action() {
-        let input = ['r'];
-        let output = vi_parse(&input);
-
+    fn register_prefix() {
         assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: None,
-                command: Some(Command::Incomplete),
-                count: None,
-                motion: ParseResult::Incomplete,
-            }
+            vi_parse(&['"', 'a', '2', 'd', 'w']),
+            ParseResult::Valid(ParsedViSequence {
+                multiplier: Some(2),
+                register: Some('a'),
+                operator: Some(Operator::Delete),
+                motion: Some(Motion::NextWord),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            vi_parse(&['3', '"', 'b', 'p']),
+            ParseResult::Valid(ParsedViSequence {
+                multiplier: Some(3),
+                register: Some('b'),
+                command: Some(Command::PasteAfter),
+                ..Default::default()
+            })
         );
+    }
 
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), false);
+    #[rstest]
+    #[case(&['2', 'd', 'm'])]
+    #[case(&['q'])]
+    #[case(&['d', 'x'])]
+    #[case(&['d', 'c'])]
+    #[case(&['d', '"'])]
+    #[case(&['"', 'a', '"'])]
+    #[case(&['2', 'd', 'p'])]
+    fn invalid_sequences(#[case] input: &[char]) {
+        assert_eq!(vi_parse(input), ParseResult::Invalid);
     }
 
-    #[test]
-    fn test_partial_motion() {
-        let input = ['f'];
-        let output = vi_parse(&input);
+    #[rstest]
+    #[case(&['r'])]
+    #[case(&['f'])]
+    #[case(&['2'])]
+    #[case(&['d'])]
+    #[case(&['d', '2'])]
+    #[case(&['"'])]
+    #[case(&['"', 'a'])]
+    fn incomplete_sequences(#[case] input: &[char]) {
+        let mut parser = ViParser::default();
+        for &c in input {
+            assert_eq!(parser.push(c), ParseResult::Incomplete);
+        }
+        assert!(parser.is_pending());
+        assert_eq!(parser.pending_keys(), input.iter().collect::<String>());
+    }
 
+    #[test]
+    fn the_pending_sequence_is_described_for_the_prompt() {
+        let mut parser = ViParser::default();
+        for c in ['2', '"', 'a', 'g', '~', '3'] {
+            parser.push(c);
+        }
         assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: None,
-                command: None,
-                count: None,
-                motion: ParseResult::Incomplete,
+            parser.pending(),
+            PromptViPending {
+                keys: "2\"ag~3".into(),
+                count: Some(6),
+                register: Some('a'),
+                operator: Some("g~".into()),
             }
         );
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), false);
+        parser.push('w');
+        assert_eq!(parser.pending(), PromptViPending::default());
     }
 
     #[test]
-    fn test_two_char_action_replace() {
-        let input = ['r', 'k'];
-        let output = vi_parse(&input);
-
+    fn parser_starts_over_after_a_sequence() {
+        let mut parser = ViParser::default();
+        assert!(!parser.is_pending());
+
+        assert_eq!(parser.push('d'), ParseResult::Incomplete);
+        assert_eq!(parser.push('m'), ParseResult::Invalid);
+        assert!(!parser.is_pending());
+        assert_eq!(parser.pending_keys(), "");
         assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: None,
-                command: Some(Command::ReplaceChar('k')),
-                count: None,
-                motion: ParseResult::Incomplete,
-            }
+            parser.push('w'),
+            ParseResult::Valid(motion(None, Motion::NextWord))
         );
 
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), true);
+        assert_eq!(parser.push('2'), ParseResult::Incomplete);
+        parser.reset();
+        assert!(!parser.is_pending());
+        assert_eq!(
+            parser.push('x'),
+            ParseResult::Valid(command(None, Command::DeleteChar))
+        );
     }
 
     #[test]
-    fn test_find_motion() {
-        let input = ['2', 'f', 'f'];
-        let output = vi_parse(&input);
+    fn case_switches_apply_their_count_once() {
+        let mut vi = Vi::default();
+        let mut parse = |keys: &str| {
+            let mut parser = ViParser::default();
+            let mut result = ParseResult::Incomplete;
+            for c in keys.chars() {
+                result = parser.push(c);
+            }
+            match result {
+                ParseResult::Valid(sequence) => sequence.to_reedline_event(&mut vi),
+                _ => panic!("`{keys}` is incomplete"),
+            }
+        };
+        let edit = |command| ReedlineEvent::Edit(vec![command]);
 
         assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: Some(2),
-                command: None,
-                count: None,
-                motion: ParseResult::Valid(Motion::RightUntil('f')),
-            }
+            parse("3~"),
+            ReedlineEvent::Multiple(vec![edit(EditCommand::SwitchcaseChars(3))])
+        );
+        assert_eq!(
+            parse("2g~e"),
+            ReedlineEvent::Multiple(vec![
+                edit(EditCommand::MoveWordRightEnd { select: true }),
+                edit(EditCommand::MoveRight { select: true }),
+                edit(EditCommand::MoveWordRightEnd { select: true }),
+                edit(EditCommand::MoveRight { select: true }),
+                edit(EditCommand::SwitchcaseSelection),
+            ])
+        );
+        assert_eq!(
+            parse("g~~"),
+            ReedlineEvent::Multiple(vec![
+                edit(EditCommand::MoveToLineStart { select: false }),
+                edit(EditCommand::MoveToLineEnd { select: true }),
+                edit(EditCommand::SwitchcaseSelection),
+            ])
         );
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), true);
     }
 
     #[test]
-    fn test_two_up() {
-        let input = ['2', 'k'];
-        let output = vi_parse(&input);
+    fn key_sequences_are_unambiguous() {
+        let keys: Vec<&str> = COMMANDS
+            .iter()
+            .map(|(keys, _)| *keys)
+            .chain(MOTIONS.iter().map(|(keys, _)| *keys))
+            .collect();
+        for (i, a) in keys.iter().enumerate() {
+            assert!(!a.is_empty());
+            assert!(
+                !a.starts_with(|c: char| c.is_ascii_digit() && c != '0') && !a.starts_with('"'),
+                "`{a}` would be read as a count or register"
+            );
+            for b in &keys[i + 1..] {
+                assert!(
+                    !a.starts_with(b) && !b.starts_with(a),
+                    "`{a}` and `{b}` overlap"
+                );
+            }
+        }
+    }
 
-        assert_eq!(
-            output,
-            ParsedViSequence {
-                multiplier: Some(2),
-                command: None,
-                count: None,
-                motion: ParseResult::Valid(Motion::Up),
+    #[rstest]
+    #[case(&['i'], true)]
+    #[case(&['a'], true)]
+    #[case(&['A'], true)]
+    #[case(&['I'], true)]
+    #[case(&['s'], true)]
+    #[case(&['S'], true)]
+    #[case(&['C'], true)]
+    #[case(&['?'], true)]
+    #[case(&[':'], true)]
+    #[case(&['c', 'w'], true)]
+    #[case(&['c', 'c'], true)]
+    #[case(&['d', 'w'], false)]
+    #[case(&['x'], false)]
+    #[case(&['w'], false)]
+    fn sequences_entering_insert_mode(#[case] input: &[char], #[case] enters_insert: bool) {
+        match vi_parse(input) {
+            ParseResult::Valid(sequence) => {
+                assert_eq!(sequence.enters_insert_mode(), enters_insert)
             }
-        );
-        assert_eq!(output.is_valid(), true);
-        assert_eq!(output.is_complete(), true);
+            other => panic!("{input:?} is not valid: {other:?}"),
+        }
     }
 
     #[rstest]
@@ -456,11 +636,35 @@ mod tests {
     #[case(&['d', 'e'], ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![EditCommand::CutWordRight])]))]
     #[case(&['d', 'b'], ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![EditCommand::CutWordLeft])]))]
     #[case(&['d', 'B'], ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![EditCommand::CutBigWordLeft])]))]
+    #[case(&['J'], ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![EditCommand::JoinLines])]))]
+    #[case(&[':'], ReedlineEvent::Multiple(vec![ReedlineEvent::ExCommand]))]
+    #[case(&['L'], ReedlineEvent::Multiple(vec![ReedlineEvent::MoveToViewport(ViewportLine::Bottom)]))]
+    #[case(&['z', 'z'], ReedlineEvent::Multiple(vec![ReedlineEvent::ScrollViewport(ViewportLine::Middle)]))]
     fn test_reedline_move(#[case] input: &[char], #[case] expected: ReedlineEvent) {
         let mut vi = Vi::default();
-        let res = vi_parse(input);
-        let output = res.to_reedline_event(&mut vi);
+        let output = match vi_parse(input) {
+            ParseResult::Valid(sequence) => sequence.to_reedline_event(&mut vi),
+            _ => ReedlineEvent::None,
+        };
 
         assert_eq!(output, expected);
     }
+
+    #[test]
+    fn repeat_last_action_replays_the_last_edit() {
+        let mut vi = Vi::default();
+        let mut parse_event = |input: &[char]| match vi_parse(input) {
+            ParseResult::Valid(sequence) => sequence.to_reedline_event(&mut vi),
+            _ => ReedlineEvent::None,
+        };
+
+        let delete = parse_event(&['d', 'w']);
+        assert_eq!(
+            parse_event(&['w']),
+            ReedlineEvent::Multiple(vec![ReedlineEvent::Edit(vec![
+                EditCommand::MoveWordRightStart { select: false }
+            ])])
+        );
+        assert_eq!(parse_event(&['.']), ReedlineEvent::Multiple(vec![delete]));
+    }
 }
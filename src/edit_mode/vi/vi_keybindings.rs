@@ -8,7 +8,7 @@ use crate::{
         },
         Keybindings,
     },
-    EditCommand,
+    EditCommand, ReedlineEvent,
 };
 
 /// Default Vi normal keybindings
@@ -28,6 +28,9 @@ pub fn default_vi_normal_keybindings() -> Keybindings {
         edit_bind(EC::MoveLeft { select: false }),
     );
     kb.add_binding(KM::NONE, KC::Delete, edit_bind(EC::Delete));
+    // Numbers under the cursor, instead of moving to the line start
+    kb.add_binding(KM::CONTROL, KC::Char('a'), edit_bind(EC::IncrementNumber));
+    kb.add_binding(KM::CONTROL, KC::Char('x'), edit_bind(EC::DecrementNumber));
 
     kb
 }
@@ -35,11 +38,30 @@ pub fn default_vi_normal_keybindings() -> Keybindings {
 /// Default Vi insert keybindings
 pub fn default_vi_insert_keybindings() -> Keybindings {
     let mut kb = Keybindings::new();
+    use KeyCode as KC;
+    use KeyModifiers as KM;
 
     add_common_control_bindings(&mut kb);
     add_common_navigation_bindings(&mut kb);
     add_common_edit_bindings(&mut kb);
     add_common_selection_bindings(&mut kb);
+    // Words of the buffer and the history, instead of the history entries
+    kb.add_binding(
+        KM::CONTROL,
+        KC::Char('n'),
+        ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::MenuDown,
+            ReedlineEvent::CompleteWordNext,
+        ]),
+    );
+    kb.add_binding(
+        KM::CONTROL,
+        KC::Char('p'),
+        ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::MenuUp,
+            ReedlineEvent::CompleteWordPrevious,
+        ]),
+    );
 
     kb
 }
@@ -1,14 +1,24 @@
-use std::path::PathBuf;
+use std::{
+    any::Any,
+    fmt::Display,
+    ops::{Range, RangeBounds},
+    path::PathBuf,
+    str::FromStr,
+};
 
 use itertools::Itertools;
 use nu_ansi_term::{Color, Style};
+use unicode_width::UnicodeWidthStr;
 
-use crate::{enums::ReedlineRawEvent, CursorConfig};
+use crate::host_context::{ContextCompleter, ContextHinter, HostContext};
+use crate::key_overlay::KeyOverlay;
+use crate::menu_functions::{can_partially_complete, common_string_extends_input};
 #[cfg(feature = "bashisms")]
-use crate::{
-    history::SearchFilter,
-    menu_functions::{parse_selection_char, ParseAction},
-};
+use crate::menu_functions::{parse_selection_char, ParseAction};
+use crate::{enums::ReedlineRawEvent, CursorConfig, EngineStateSnapshot};
+#[cfg(feature = "tracing")]
+use crate::{CacheStats, FrameTimings};
+
 #[cfg(feature = "external_printer")]
 use {
     crate::external_printer::ExternalPrinter,
@@ -17,23 +27,52 @@ use {
 };
 use {
     crate::{
-        completion::{Completer, DefaultCompleter},
-        core_editor::Editor,
+        buffer_search::BufferSearch,
+        choice::{Choice, ChoiceStep, Confirmation, KeyChoice, NumberInput, Selection},
+        completion::{
+            Completer, CompletionCache, CompletionCacheInvalidator, CompletionUsage,
+            DefaultCompleter,
+        },
+        core_editor::{get_local_clipboard, Editor},
+        corrector::CorrectionAnswer,
+        edit_mode::keybindings::menus_in_event,
         edit_mode::{EditMode, Emacs},
         enums::{EventStatus, ReedlineEvent},
+        ex_command::{run_ex_command, ExCommand},
+        folding::PasteFolds,
+        formatter::formatted_cursor,
         highlighter::SimpleMatchHighlighter,
-        hinter::Hinter,
+        hinter::{HintCache, Hinter},
         history::{
-            FileBackedHistory, History, HistoryCursor, HistoryItem, HistoryItemId,
-            HistoryNavigationQuery, HistorySessionId, SearchDirection, SearchQuery,
+            CommandLineSearch, DirectoryHistoryScope, FileBackedHistory, History, HistoryCursor,
+            HistoryItem, HistoryItemId, HistoryNavigationQuery, HistorySessionId, SearchDirection,
+            SearchFilter, SearchQuery,
         },
-        painting::{Painter, PromptLines},
+        indenter::Indenter,
+        menu::CompletionSources,
+        painting::{strip_ansi, EchoPrompt, Painter, PromptLines, SanitizedPrompt, SubmitEcho},
         prompt::{PromptEditMode, PromptHistorySearchStatus},
-        result::{ReedlineError, ReedlineErrorVariants},
-        terminal_extensions::{bracketed_paste::BracketedPasteGuard, kitty::KittyProtocolGuard},
-        utils::text_manipulation,
-        EditCommand, ExampleHighlighter, Highlighter, LineBuffer, Menu, MenuEvent, Prompt,
-        PromptHistorySearch, ReedlineMenu, Signal, UndoBehavior, ValidationResult, Validator,
+        result::{ReedlineError, ReedlineErrorVariants, RenderErrorAction},
+        search_replace::{SearchReplace, SearchReplaceAnswer, SearchReplaceStage},
+        template::FilledTemplate,
+        terminal_extensions::{
+            bracketed_paste::BracketedPasteGuard, cursor_shape::CursorShapeGuard,
+            kitty::KittyProtocolGuard,
+        },
+        utils::{
+            external_text::{
+                decode_prefix, normalize_external_text, read_file_prefix, run_in_shell,
+            },
+            text_manipulation,
+        },
+        word_expansion::WordExpansion,
+        Abbreviations, AutoPairs, BranchOutcome, BufferFormatter, CompletionError, Corrector,
+        CustomEventHandler, EditCommand, EventCondition, EventInjector, ExampleHighlighter,
+        Highlighter, HostCommand, KeyCombination, KeybindingConflict, KeybindingDescription,
+        KeybindingReport, Keybindings, LineBuffer, Menu, MenuEvent, Prompt, PromptHistorySearch,
+        ReadLineOptions, ReedlineMenu, RepaintHandle, Sanitizer, Signal, SignalInfo, SignalReason,
+        StyledText, Submission, Template, UndoBehavior, UnhandledInput, ValidationResult,
+        Validator, ViewportLine,
     },
     crossterm::{
         cursor::{SetCursorStyle, Show},
@@ -42,7 +81,8 @@ use {
         terminal, QueueableCommand,
     },
     std::{
-        fs::File, io, io::Result, io::Write, process::Command, time::Duration, time::SystemTime,
+        borrow::Cow, fs::File, io, io::Result, io::Write, process::Command, time::Duration,
+        time::Instant, time::SystemTime,
     },
 };
 
@@ -57,6 +97,76 @@ const POLL_WAIT: u64 = 10;
 // a paste. 10 events in 10 milliseconds is conservative enough (unlikely somebody
 // will type more than 10 characters in 10 milliseconds)
 const EVENTS_THRESHOLD: usize = 10;
+// While input keeps arriving, the buffer is repainted at most once per interval (about 60 Hz)
+// instead of after every handled event.
+const DEFAULT_MIN_REPAINT_INTERVAL: Duration = Duration::from_millis(16);
+// Room reserved in the buffer of `read_line_masked`, to not reallocate it while typing
+const MASKED_BUFFER_CAPACITY: usize = 1024;
+// How long `UnhandledInput::Flash` shows the reversed prompt indicator
+const FLASH_DURATION: Duration = Duration::from_millis(100);
+// How often completers and hinters that are still computing their results are asked again,
+// which is also the pace of the progress indicator
+const PENDING_POLL_INTERVAL: Duration = Duration::from_millis(80);
+// How often a `RepaintHandle` and an `EventInjector` are checked without input
+const HANDLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+// How long a failure of the history is shown without `with_history_error_handler`
+const HISTORY_ERROR_MESSAGE_DURATION: Duration = Duration::from_secs(5);
+// Number of the latest history entries with the prefix that words are completed from
+const WORD_EXPANSION_HISTORY: i64 = 100;
+const DEFAULT_PROGRESS_INDICATOR_DELAY: Duration = Duration::from_millis(200);
+const PROGRESS_INDICATOR_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+// Evaluates `$body` inside a tracing span named after the `$stage` and adds the elapsed
+// time to that field of the `FrameTimings` in `$timings`.
+// Without the `tracing` feature this is just `$body`.
+macro_rules! timed {
+    ($timings:expr, $stage:ident, $body:expr) => {{
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!(stringify!($stage)).entered();
+        #[cfg(feature = "tracing")]
+        let start = Instant::now();
+        let result = $body;
+        #[cfg(feature = "tracing")]
+        {
+            $timings.$stage += start.elapsed();
+        }
+        result
+    }};
+}
+
+// A message of `Reedline::show_message`
+struct TransientMessage {
+    text: String,
+    expires: Instant,
+}
+
+// The search term, entry and directory filter of a history search, with the match counter for them
+type HistorySearchMatches = (String, HistoryItemId, bool, Option<(usize, usize)>);
+
+// The callbacks of `Reedline::with_history_error_handler` and `with_render_error_handler`
+type HistoryErrorHandler = Box<dyn FnMut(&ReedlineError) + Send>;
+type RenderErrorHandler = Box<dyn FnMut(&ReedlineError) -> RenderErrorAction + Send>;
+
+// The callback of `Reedline::with_completion_error_handler`
+type CompletionErrorHandler = Box<dyn FnMut(&CompletionError) + Send>;
+
+// The callback of `Reedline::with_selection_handler`, with the action and the text
+type SelectionHandler = Box<dyn FnMut(&str, &str) + Send>;
+
+// The callback of `Reedline::with_idle_handler`
+struct IdleHandler {
+    period: Duration,
+    callback: Box<dyn FnMut(Duration) -> Vec<ReedlineEvent> + Send>,
+    // The last key press or resize, and the last call of the callback after it
+    last_input: Instant,
+    last_call: Option<Instant>,
+}
+
+impl IdleHandler {
+    fn next_call(&self) -> Instant {
+        self.last_call.unwrap_or(self.last_input) + self.period
+    }
+}
 
 /// Determines if inputs should be used to extend the regular line buffer,
 /// traverse the history in the standard prompt or edit the search string in the
@@ -74,6 +184,43 @@ enum InputMode {
     /// Either bash style up/down history or fish style prefix search,
     /// Edits directly switch to [`InputMode::Regular`]
     HistoryTraversal,
+    /// Vi style `:` command line below the buffer,
+    /// editing affects the command line
+    ExCommand,
+    /// Interactive search & replace,
+    /// editing affects the pattern or replacement, then matches await confirmation
+    SearchReplace,
+    /// Incremental search within the buffer,
+    /// editing affects the search term, the cursor follows the current match
+    BufferSearch,
+    /// The submitted line waits for the answer whether to use the suggested correction
+    Correction,
+}
+
+// How the current line was edited, for `Reedline::read_line_detailed`
+struct LineUsage {
+    started: Instant,
+    used_hint: bool,
+    used_completion: bool,
+    // The submitted line as it was highlighted
+    styled_text: StyledText,
+}
+
+impl LineUsage {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            used_hint: false,
+            used_completion: false,
+            styled_text: StyledText::new(),
+        }
+    }
+}
+
+// A correction of the `Corrector` for a submitted line, awaiting the answer
+struct PendingCorrection {
+    suggestion: String,
+    reason: SignalReason,
 }
 
 /// Line editor engine
@@ -107,6 +254,19 @@ pub struct Reedline {
     history_exclusion_prefix: Option<String>,
     history_excluded_item: Option<HistoryItem>,
     history_cursor_on_excluded: bool,
+    // The match counter of the last history search, see `history_search_match_position`
+    history_search_matches: Option<HistorySearchMatches>,
+    // Only navigate through the entries of the working directory
+    directory_history: bool,
+    directory_history_scope: DirectoryHistoryScope,
+    // Typos tolerated by the history search when nothing matches exactly
+    history_search_typo_tolerance: usize,
+    // The history entry the buffer was last recalled from, kept across edits
+    history_recalled_id: Option<HistoryItemId>,
+    // The history entry opened with `edit_history_item`, updated on submit
+    edited_history_item: Option<HistoryItemId>,
+    // Rows where the entries submitted in this session were painted, top to bottom
+    submitted_rows: Vec<(HistoryItemId, u16)>,
     input_mode: InputMode,
 
     // Yielded to the host program after a `ReedlineEvent::ExecuteHostCommand`, thus redraw in-place
@@ -114,17 +274,103 @@ pub struct Reedline {
 
     // Validator
     validator: Option<Box<dyn Validator>>,
+    // Delay after the last edit before the buffer is validated for the prompt indicator
+    live_validation_debounce: Option<Duration>,
+    // The buffer last validated for the prompt indicator, with the result
+    live_validation: Option<(String, ValidationResult)>,
+    // The edited buffer waiting to be validated, and when it is
+    live_validation_pending: Option<(String, Instant)>,
+
+    // Indentation of continuation lines
+    indenter: Option<Box<dyn Indenter>>,
+
+    // Options of the current `read_line_with_options` call
+    read_line_options: ReadLineOptions,
+
+    // Set while reading a line with `read_line_masked`, to the character shown for every
+    // typed one, if any
+    input_mask: Option<Option<char>>,
+
+    // How the current line was edited
+    line_usage: LineUsage,
+
+    // Set while reading a line with `read_template`, to the fields of the submitted buffer
+    template_fields: Option<Vec<Range<usize>>>,
+
+    // Abbreviations expanded in command position
+    abbreviations: Option<Abbreviations>,
+
+    // Long pasted blocks painted collapsed
+    paste_folds: Option<PasteFolds>,
+
+    // Pretty-prints the buffer on `ReedlineEvent::FormatBuffer`
+    formatter: Option<Box<dyn BufferFormatter>>,
+
+    // Gets the selection on `ReedlineEvent::SendSelection`
+    selection_handler: Option<SelectionHandler>,
+
+    // Corrections suggested for submitted lines
+    corrector: Option<Box<dyn Corrector>>,
+    pending_correction: Option<PendingCorrection>,
+
+    // Ex command line
+    use_ex_commands: bool,
+    ex_commands: Vec<Box<dyn ExCommand>>,
+    ex_command_line: Editor,
+    command_line_message: Option<String>,
+
+    // Interactive search & replace
+    search_replace: SearchReplace,
+
+    // Incremental search within the buffer
+    buffer_search: BufferSearch,
+
+    // The last completion of a word from the buffer and the history
+    word_expansion: Option<WordExpansion>,
+
+    // Size limit for text inserted from files or command output
+    max_insert_size: usize,
+
+    // Repaints are skipped while more input is pending and the last one is more recent
+    min_repaint_interval: Duration,
+
+    // Receives the application defined events
+    custom_event_handler: Option<Box<dyn CustomEventHandler>>,
+
+    // Feedback for keys that are bound to nothing, and the ones not yet reported
+    unhandled_input: UnhandledInput,
+    unhandled_keys: Vec<KeyCombination>,
+    // Until when the prompt indicator is shown reversed, turned back by `handle_idle_timeout`
+    flash_until: Option<Instant>,
+
+    // How the last `read_line` ended
+    last_signal_info: Option<SignalInfo>,
+    // Whether the event after the insertion of a menu value is handled
+    accepting_from_menu: bool,
+
+    // Timings of the current or most recently handled batch of events
+    #[cfg(feature = "tracing")]
+    frame_timings: FrameTimings,
 
     // Stdout
     painter: Painter,
 
     transient_prompt: Option<Box<dyn Prompt>>,
 
+    // How the submitted line is painted once more
+    submit_echo: Option<SubmitEcho>,
+
+    // The text of `Prompt::render_post_submit` for the submitted line, printed on exit
+    post_submit: Option<String>,
+
     // Edit Mode: Vi, Emacs
     edit_mode: Box<dyn EditMode>,
 
     // Provides the tab completions
     completer: Box<dyn Completer>,
+    completion_cache: CompletionCache,
+    // Learns the suggestions picked from menus
+    completion_usage: CompletionUsage,
     quick_completions: bool,
     partial_completions: bool,
 
@@ -134,13 +380,59 @@ pub struct Reedline {
     // Style used for visual selection
     visual_selection_style: Style,
 
+    // Painted over the buffer while a menu or the history search has the focus
+    inactive_buffer_style: Option<Style>,
+
     // Showcase hints based on various strategies (history, language-completion, spellcheck, etc)
     hinter: Option<Box<dyn Hinter>>,
     hide_hints: bool,
+    hint_cache: HintCache,
+
+    // State of the application handed to the completer, hinter and highlighter
+    context: Option<HostContext>,
+
+    // Line of application defined text below the buffer
+    status: StyledText,
+
+    // Message shown below the prompt until it expires or the next key press
+    transient_message: Option<TransientMessage>,
+
+    // Told about failures to save to the history and to paint, which end `read_line` else
+    history_error_handler: Option<HistoryErrorHandler>,
+    render_error_handler: Option<RenderErrorHandler>,
+
+    // Told about failures of the completers, once until the menu shows a different one
+    completion_error_handler: Option<CompletionErrorHandler>,
+    reported_completion_error: Option<CompletionError>,
+
+    // Recently pressed keys shown below the buffer
+    key_overlay: Option<KeyOverlay>,
+
+    // Shows `debug_state` below the buffer
+    debug_panel: bool,
+    last_event: Option<ReedlineEvent>,
+
+    // Called while there is no input
+    idle_handler: Option<IdleHandler>,
+
+    // Repaints requested and events sent by other threads
+    repaint_handle: Option<RepaintHandle>,
+    event_injector: Option<EventInjector>,
+
+    // Since when the completer or hinter is still computing its results
+    pending_since: Option<Instant>,
+    progress_indicator_delay: Duration,
+    progress_indicator_style: Style,
+
+    // The rendered buffer around the cursor, kept to reuse its memory on every repaint
+    rendered_buffer: (String, String),
 
     // Use ansi coloring or not
     use_ansi_coloring: bool,
 
+    // Removes the control sequences from the painted text of the app
+    sanitizer: Option<Sanitizer>,
+
     // Engine Menus
     menus: Vec<ReedlineMenu>,
 
@@ -149,6 +441,7 @@ pub struct Reedline {
 
     // Use different cursors depending on the current edit mode
     cursor_shapes: Option<CursorConfig>,
+    cursor_shape_guard: CursorShapeGuard,
 
     // Manage bracketed paste mode
     bracketed_paste: BracketedPasteGuard,
@@ -167,13 +460,7 @@ struct BufferEditor {
 
 impl Drop for Reedline {
     fn drop(&mut self) {
-        if self.cursor_shapes.is_some() {
-            let _ignore = terminal::enable_raw_mode();
-            let mut stdout = std::io::stdout();
-            let _ignore = stdout.queue(SetCursorStyle::DefaultUserShape);
-            let _ignore = stdout.queue(Show);
-            let _ignore = stdout.flush();
-        }
+        self.cursor_shape_guard.exit();
 
         // Ensures that the terminal is in a good state if we panic semigracefully
         // Calling `disable_raw_mode()` twice is fine with Linux
@@ -181,6 +468,71 @@ impl Drop for Reedline {
     }
 }
 
+/// Keeps the terminal out of the modes of the editor while it lives, see
+/// [`Reedline::suspend_guard`]
+pub struct SuspendGuard<'editor> {
+    editor: &'editor mut Reedline,
+    raw_mode: bool,
+}
+
+impl<'editor> SuspendGuard<'editor> {
+    fn new(editor: &'editor mut Reedline) -> Result<Self> {
+        let raw_mode = terminal::is_raw_mode_enabled()?;
+        editor.bracketed_paste.exit();
+        editor.kitty_protocol.exit();
+        editor.painter.release_scroll_region()?;
+
+        editor.cursor_shape_guard.exit();
+        let mut stdout = std::io::stdout();
+        stdout.queue(Show)?;
+        stdout.flush()?;
+        if raw_mode {
+            terminal::disable_raw_mode()?;
+        }
+
+        Ok(Self { editor, raw_mode })
+    }
+}
+
+impl<'editor> Drop for SuspendGuard<'editor> {
+    fn drop(&mut self) {
+        if self.raw_mode {
+            let _ignore = terminal::enable_raw_mode();
+        }
+        let _ignore = self.editor.painter.apply_reserved_rows();
+        // The application may have written anywhere on the screen
+        self.editor.painter.invalidate_painted_frame();
+        self.editor.hint_cache.clear();
+    }
+}
+
+/// Leaves the terminal to a command run by [`HostCommand`], restoring it when dropped, also
+/// on the way out of an error
+struct HostCommandGuard<'editor> {
+    editor: &'editor mut Reedline,
+}
+
+impl<'editor> HostCommandGuard<'editor> {
+    fn new(editor: &'editor mut Reedline) -> Result<Self> {
+        editor.bracketed_paste.exit();
+        editor.kitty_protocol.exit();
+        editor.cursor_shape_guard.exit();
+        let guard = Self { editor };
+        terminal::disable_raw_mode()?;
+
+        Ok(guard)
+    }
+}
+
+impl<'editor> Drop for HostCommandGuard<'editor> {
+    fn drop(&mut self) {
+        let _ignore = terminal::enable_raw_mode();
+        self.editor.bracketed_paste.enter();
+        self.editor.kitty_protocol.enter();
+        let _ignore = self.editor.painter.initialize_prompt_position();
+    }
+}
+
 impl Reedline {
     const FILTERED_ITEM_ID: HistoryItemId = HistoryItemId(i64::MAX);
 
@@ -209,23 +561,86 @@ impl Reedline {
             history_exclusion_prefix: None,
             history_excluded_item: None,
             history_cursor_on_excluded: false,
+            history_search_matches: None,
+            directory_history: false,
+            directory_history_scope: DirectoryHistoryScope::default(),
+            history_search_typo_tolerance: 0,
+            history_recalled_id: None,
+            edited_history_item: None,
+            submitted_rows: Vec::new(),
             input_mode: InputMode::Regular,
             executing_host_command: false,
             painter,
             transient_prompt: None,
+            submit_echo: None,
+            post_submit: None,
             edit_mode,
             completer,
+            completion_cache: CompletionCache::default(),
+            completion_usage: CompletionUsage::default(),
             quick_completions: false,
             partial_completions: false,
             highlighter: buffer_highlighter,
             visual_selection_style,
+            inactive_buffer_style: None,
             hinter,
+            hint_cache: HintCache::default(),
+            context: None,
+            status: StyledText::new(),
+            transient_message: None,
+            history_error_handler: None,
+            completion_error_handler: None,
+            reported_completion_error: None,
+            render_error_handler: None,
+            key_overlay: None,
+            debug_panel: false,
+            last_event: None,
+            idle_handler: None,
+            repaint_handle: None,
+            event_injector: None,
+            pending_since: None,
+            progress_indicator_delay: DEFAULT_PROGRESS_INDICATOR_DELAY,
+            progress_indicator_style: Style::new().fg(Color::DarkGray),
+            rendered_buffer: (String::new(), String::new()),
             hide_hints: false,
             validator,
+            live_validation_debounce: None,
+            live_validation: None,
+            live_validation_pending: None,
+            indenter: None,
+            use_ex_commands: false,
+            ex_commands: Vec::new(),
+            ex_command_line: Editor::default(),
+            command_line_message: None,
+            search_replace: SearchReplace::new(),
+            buffer_search: BufferSearch::new(0),
+            word_expansion: None,
+            max_insert_size: 1024 * 1024,
+            min_repaint_interval: DEFAULT_MIN_REPAINT_INTERVAL,
+            custom_event_handler: None,
+            read_line_options: ReadLineOptions::default(),
+            input_mask: None,
+            line_usage: LineUsage::new(),
+            template_fields: None,
+            abbreviations: None,
+            paste_folds: None,
+            formatter: None,
+            selection_handler: None,
+            corrector: None,
+            pending_correction: None,
+            unhandled_input: UnhandledInput::default(),
+            unhandled_keys: Vec::new(),
+            flash_until: None,
+            last_signal_info: None,
+            accepting_from_menu: false,
+            #[cfg(feature = "tracing")]
+            frame_timings: FrameTimings::default(),
             use_ansi_coloring: true,
+            sanitizer: None,
             menus: Vec::new(),
             buffer_editor: None,
             cursor_shapes: None,
+            cursor_shape_guard: CursorShapeGuard::default(),
             bracketed_paste: BracketedPasteGuard::default(),
             kitty_protocol: KittyProtocolGuard::default(),
             #[cfg(feature = "external_printer")]
@@ -278,7 +693,10 @@ impl Reedline {
     /// Set a new history session id
     /// This should be used in situations where the user initially did not have a history_session_id
     /// and then later realized they want to have one without restarting the application.
-    pub fn set_history_session_id(&mut self, session: Option<HistorySessionId>) -> Result<()> {
+    pub fn set_history_session_id(
+        &mut self,
+        session: Option<HistorySessionId>,
+    ) -> crate::Result<()> {
         self.history_session_id = session;
         Ok(())
     }
@@ -302,6 +720,7 @@ impl Reedline {
     #[must_use]
     pub fn with_hinter(mut self, hinter: Box<dyn Hinter>) -> Self {
         self.hinter = Some(hinter);
+        self.hint_cache.clear();
         self
     }
 
@@ -332,9 +751,129 @@ impl Reedline {
     #[must_use]
     pub fn with_completer(mut self, completer: Box<dyn Completer>) -> Self {
         self.completer = completer;
+        self.completion_cache.clear();
+        self
+    }
+
+    /// A builder that reuses the suggestions of the [`Completer`] within a line
+    ///
+    /// Completing the same line again, e.g. when reopening the menu, returns the last
+    /// suggestions. While the word in front of the cursor is typed on, the suggestions are
+    /// narrowed down to the values starting with the word, instead of asking the completer.
+    /// This assumes that the completer matches the start of the word case-sensitively,
+    /// like [`DefaultCompleter`]. Only letters, digits, `_` and `-` are considered to
+    /// continue the word.
+    ///
+    /// The cache is emptied for every [`Reedline::read_line`] and host command. Other changes
+    /// to what the completer returns have to be announced with the
+    /// [`Reedline::completion_cache_invalidator`].
+    #[must_use]
+    pub fn with_completion_cache(mut self, enable: bool) -> Self {
+        self.completion_cache.set_enabled(enable);
+        self
+    }
+
+    /// A builder that sets the store learning which suggestions are picked from menus
+    ///
+    /// Each editor starts with an empty store in memory. Share a store kept in a file to
+    /// remember the picks across sessions, see [`CompletionUsage`].
+    #[must_use]
+    pub fn with_completion_usage(mut self, usage: CompletionUsage) -> Self {
+        self.completion_usage = usage;
+        self
+    }
+
+    /// The store learning which suggestions are picked from menus, e.g. to clear it
+    pub fn completion_usage(&self) -> CompletionUsage {
+        self.completion_usage.clone()
+    }
+
+    /// Handle to drop the suggestions cached with [`Reedline::with_completion_cache`]
+    pub fn completion_cache_invalidator(&self) -> CompletionCacheInvalidator {
+        self.completion_cache.invalidator()
+    }
+
+    /// A builder that hands the state of the application, e.g. the variables in scope, to
+    /// the completer, hinter and highlighter
+    ///
+    /// They get it as `&dyn Any` in [`Completer::complete_in_context`],
+    /// [`Hinter::handle_in_context`] and [`Highlighter::highlight_in_context`].
+    ///
+    /// ```rust
+    /// use reedline::{Completer, Reedline, Span, Suggestion};
+    /// use std::any::Any;
+    ///
+    /// struct Scope {
+    ///     variables: Vec<String>,
+    /// }
+    ///
+    /// struct VariableCompleter;
+    ///
+    /// impl Completer for VariableCompleter {
+    ///     fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+    ///         vec![]
+    ///     }
+    ///
+    ///     fn complete_in_context(&mut self, line: &str, pos: usize, context: &dyn Any) -> Vec<Suggestion> {
+    ///         let scope = match context.downcast_ref::<Scope>() {
+    ///             Some(scope) => scope,
+    ///             None => return vec![],
+    ///         };
+    ///         let start = line[..pos].rfind('$').unwrap_or(pos);
+    ///         scope
+    ///             .variables
+    ///             .iter()
+    ///             .filter(|variable| variable.starts_with(&line[start..pos]))
+    ///             .map(|variable| Suggestion {
+    ///                 value: variable.clone(),
+    ///                 span: Span::new(start, pos),
+    ///                 ..Suggestion::default()
+    ///             })
+    ///             .collect()
+    ///     }
+    /// }
+    ///
+    /// let mut line_editor = Reedline::create()
+    ///     .with_completer(Box::new(VariableCompleter))
+    ///     .with_context(Scope { variables: vec!["$HOME".to_string()] });
+    ///
+    /// // Between two lines, e.g. after running the previous one
+    /// if let Some(scope) = line_editor.context_mut::<Scope>() {
+    ///     scope.variables.push("$PWD".to_string());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn with_context(mut self, context: impl Any + Send) -> Self {
+        self.set_context(context);
         self
     }
 
+    /// Replace the state of the application handed to the completer, hinter and
+    /// highlighter, see [`Reedline::with_context`]
+    pub fn set_context(&mut self, context: impl Any + Send) {
+        self.context = Some(Box::new(context));
+        self.clear_context_caches();
+    }
+
+    /// The state of the application given to [`Reedline::with_context`], if it is a `T`
+    pub fn context<T: Any>(&self) -> Option<&T> {
+        self.context.as_ref()?.downcast_ref()
+    }
+
+    /// Change the state of the application given to [`Reedline::with_context`] in place,
+    /// if it is a `T`
+    ///
+    /// The cached hint and suggestions are dropped, as they may depend on it.
+    pub fn context_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.clear_context_caches();
+        self.context.as_mut()?.downcast_mut()
+    }
+
+    fn clear_context_caches(&mut self) {
+        self.hint_cache.clear();
+        self.completion_cache.clear();
+    }
+
     /// Turn on quick completions. These completions will auto-select if the completer
     /// ever narrows down to a single entry.
     #[must_use]
@@ -359,7 +898,48 @@ impl Reedline {
         self
     }
 
+    /// A builder which enables or disables repainting only the changed parts of the screen
+    ///
+    /// Reduces flicker and the amount of output, e.g. over slow SSH connections. Everything is
+    /// still repainted after a resize, on a new prompt or for output that can't be tracked.
+    /// As the painter assumes that nothing else changed the screen, output from other
+    /// sources while editing may stay visible until the next full repaint (e.g. by
+    /// [`ReedlineEvent::ClearScreen`]).
+    ///
+    /// Not supported on Windows.
+    #[must_use]
+    pub fn use_diff_repaint(mut self, enable: bool) -> Self {
+        self.painter.set_diff_repaint(enable);
+        self
+    }
+
+    /// A builder which leaves `top` rows at the top and `bottom` rows at the bottom of the
+    /// terminal to the application, e.g. for status panes it draws itself
+    ///
+    /// The editor never paints in these rows and scrolls only the rows in between, by setting
+    /// the scrolling region of the terminal. The region stays set after a line is read, so the
+    /// output of the application doesn't scroll the reserved rows either, until they are
+    /// released with [`Reedline::set_reserved_rows`]. At least one row has to be left.
+    #[must_use]
+    pub fn with_reserved_rows(mut self, top: u16, bottom: u16) -> Self {
+        self.painter.set_reserved_rows(top, bottom);
+        self
+    }
+
+    /// A builder which removes the control sequences that are not allowed by `sanitizer` from
+    /// the text of the prompt, the hints, the suggestions of the menus and the external printer
+    /// before it is painted, e.g. for prompts or completions showing untrusted data
+    ///
+    /// By default the text is painted as it is.
+    #[must_use]
+    pub fn with_sanitizer(mut self, sanitizer: Sanitizer) -> Self {
+        self.sanitizer = Some(sanitizer);
+        self
+    }
+
     /// A builder that configures the highlighter for your instance of the Reedline engine
+    ///
+    /// Several highlighters can be combined with a [`crate::LayeredHighlighter`].
     /// # Example
     /// ```rust
     /// // Create a reedline object with highlighter support
@@ -388,6 +968,81 @@ impl Reedline {
         self
     }
 
+    /// A builder that de-emphasizes the buffer with `style` while a menu or the history
+    /// search is active, e.g. `Some(Style::new().dimmed())`, so the focus is on them
+    ///
+    /// The style is painted over the highlighting, keeping what it does not set.
+    /// `None` (the default) paints the buffer as usual.
+    #[must_use]
+    pub fn with_inactive_buffer_style(mut self, style: Option<Style>) -> Self {
+        self.inactive_buffer_style = style;
+        self
+    }
+
+    /// A builder that calls `handler` when a completer fails, e.g. to log the error
+    ///
+    /// The menu shows the error in place of the suggestions either way, see
+    /// [`crate::Completer::try_complete_with_base_ranges`]. The same error is only reported
+    /// once while the menu keeps showing it.
+    #[must_use]
+    pub fn with_completion_error_handler(
+        mut self,
+        handler: impl FnMut(&CompletionError) + Send + 'static,
+    ) -> Self {
+        self.completion_error_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// A builder that calls `handler` when the history fails while editing, e.g. when a
+    /// submitted line can't be saved because the disk is full, or the history can't be searched
+    ///
+    /// The line is still returned by [`Reedline::read_line`]. Without a handler the error
+    /// is shown below the next prompt for a few seconds.
+    #[must_use]
+    pub fn with_history_error_handler(
+        mut self,
+        handler: impl FnMut(&ReedlineError) + Send + 'static,
+    ) -> Self {
+        self.history_error_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// A builder that lets `handler` decide whether to keep editing after the line could
+    /// not be painted, e.g. after a write to the terminal timed out
+    ///
+    /// Without a handler [`Reedline::read_line`] ends with the error, like with
+    /// [`RenderErrorAction::Abort`]. With [`RenderErrorAction::Continue`] the next repaint
+    /// paints the whole line again.
+    ///
+    /// ```rust
+    /// use reedline::{Reedline, RenderErrorAction};
+    ///
+    /// let mut line_editor = Reedline::create().with_render_error_handler(|err| {
+    ///     eprintln!("{err}");
+    ///     RenderErrorAction::Continue
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_render_error_handler(
+        mut self,
+        handler: impl FnMut(&ReedlineError) -> RenderErrorAction + Send + 'static,
+    ) -> Self {
+        self.render_error_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// A builder that shows the recently pressed keys in the corner below the buffer, each
+    /// for `duration`, e.g. for screencasts or to find out what a terminal sends
+    ///
+    /// Keys are shown like on keycaps, e.g. `⌃R` or `⌥←`, and typed characters are
+    /// grouped, e.g. `dw`. `None` (the default) shows no keys. Keys pressed while reading
+    /// a line with [`Reedline::read_line_masked`] are neither shown nor remembered.
+    #[must_use]
+    pub fn with_key_overlay(mut self, duration: Option<Duration>) -> Self {
+        self.key_overlay = duration.map(KeyOverlay::new);
+        self
+    }
+
     /// A builder which configures the history for your instance of the Reedline engine
     /// # Example
     /// ```rust,no_run
@@ -405,6 +1060,7 @@ impl Reedline {
     #[must_use]
     pub fn with_history(mut self, history: Box<dyn History>) -> Self {
         self.history = history;
+        self.hint_cache.clear();
         self
     }
 
@@ -429,6 +1085,39 @@ impl Reedline {
         self
     }
 
+    /// A builder which restricts the history navigation and search to the entries recorded in
+    /// the current working directory, or also below it with [`DirectoryHistoryScope::Subtree`]
+    ///
+    /// [`ReedlineEvent::ToggleDirectoryHistory`] turns the restriction on and off. It needs
+    /// a history storing the working directory of the entries, see
+    /// [`History::supports_directories`], like [`crate::SqliteBackedHistory`] with
+    /// [`HistoryItem::cwd`] set through [`Reedline::update_last_command_context`], and is
+    /// ignored for other histories.
+    #[must_use]
+    pub fn with_directory_history(mut self, enabled: bool, scope: DirectoryHistoryScope) -> Self {
+        self.directory_history = enabled;
+        self.directory_history_scope = scope;
+        self
+    }
+
+    /// Whether the history navigation and search only consider the entries of the working
+    /// directory, see [`Reedline::with_directory_history`]
+    pub fn directory_history(&self) -> bool {
+        self.directory_history
+    }
+
+    /// A builder which lets the history search find the entries containing the search term
+    /// with up to `max_typos` typos, e.g. `gti sttaus` finds `git status`
+    ///
+    /// The typos are only tolerated if no entry contains the term exactly, and at most one
+    /// per three characters of the term. A typo is a missing, an extra or a wrong character
+    /// or two swapped characters. Defaults to 0, which turns it off.
+    #[must_use]
+    pub fn with_history_search_typo_tolerance(mut self, max_typos: usize) -> Self {
+        self.history_search_typo_tolerance = max_typos;
+        self
+    }
+
     /// A builder that configures the validator for your instance of the Reedline engine
     /// # Example
     /// ```rust
@@ -445,6 +1134,93 @@ impl Reedline {
         self
     }
 
+    /// A builder which validates the buffer while it is edited, once no edit was made for
+    /// `debounce`, and passes the result to [`Prompt::render_prompt_indicator_for_validation`]
+    /// and [`Prompt::get_indicator_color_for_validation`], e.g. to color the indicator red
+    /// while the input is incomplete
+    ///
+    /// Needs a validator, see [`Reedline::with_validator`]. Masked input is not validated.
+    #[must_use]
+    pub fn with_live_validation(mut self, debounce: Duration) -> Self {
+        self.live_validation_debounce = Some(debounce);
+        self
+    }
+
+    /// A builder that configures the abbreviations expanded when a space is typed after them
+    ///
+    /// See [`Abbreviations`]. Masked input is never expanded.
+    #[must_use]
+    pub fn with_abbreviations(mut self, abbreviations: Abbreviations) -> Self {
+        self.abbreviations = Some(abbreviations);
+        self
+    }
+
+    /// A builder that paints pasted blocks of more than `min_lines` lines collapsed to a
+    /// single `⋯ 120 lines` marker, off by default
+    ///
+    /// Only the painting is folded, the submitted line has every pasted line. A block is
+    /// painted in full while the cursor is in it, and [`ReedlineEvent::ToggleFolds`] expands
+    /// or collapses all of them. Blocks count as pasted when they are inserted at once, as
+    /// with bracketed paste, see [`Reedline::use_bracketed_paste`].
+    #[must_use]
+    pub fn with_paste_folding(mut self, min_lines: usize) -> Self {
+        self.paste_folds = Some(PasteFolds::new(min_lines));
+        self
+    }
+
+    /// A builder that configures the formatter replacing the buffer on
+    /// [`ReedlineEvent::FormatBuffer`], see [`BufferFormatter`]
+    #[must_use]
+    pub fn with_formatter(mut self, formatter: Box<dyn BufferFormatter>) -> Self {
+        self.formatter = Some(formatter);
+        self
+    }
+
+    /// A builder that calls `handler` on [`ReedlineEvent::SendSelection`] with the name of the
+    /// action and the selected text, or the whole buffer if nothing is selected
+    ///
+    /// `read_line` goes on, so the application can e.g. explain or run a part of the line
+    /// and print the result with the external printer.
+    ///
+    /// ```rust
+    /// use reedline::Reedline;
+    ///
+    /// let mut line_editor = Reedline::create().with_selection_handler(|action, text| {
+    ///     if action == "scratchpad" {
+    ///         eprintln!("saved {text:?}");
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_selection_handler(
+        mut self,
+        handler: impl FnMut(&str, &str) + Send + 'static,
+    ) -> Self {
+        self.selection_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// A builder that configures the corrector offering a corrected line on submit
+    ///
+    /// ```rust
+    /// use reedline::{Corrector, Reedline};
+    ///
+    /// struct GitTypos;
+    ///
+    /// impl Corrector for GitTypos {
+    ///     fn correct(&mut self, line: &str) -> Option<String> {
+    ///         line.strip_prefix("gti ").map(|rest| format!("git {rest}"))
+    ///     }
+    /// }
+    ///
+    /// let mut line_editor = Reedline::create().with_corrector(Box::new(GitTypos));
+    /// ```
+    #[must_use]
+    pub fn with_corrector(mut self, corrector: Box<dyn Corrector>) -> Self {
+        self.corrector = Some(corrector);
+        self
+    }
+
     /// A builder that configures the alternate text editor used to edit the line buffer
     ///
     /// You are responsible for providing a file path that is unique to this reedline session
@@ -485,46 +1261,426 @@ impl Reedline {
         self
     }
 
-    /// Set a different prompt to be used after submitting each line
+    /// A builder that configures the [`Indenter`] used to indent new lines of incomplete input
+    ///
+    /// `Tab` and `Shift-Tab` adjust the indentation of the lines once bound with
+    /// [`Keybindings::add_indent_bindings`].
+    /// # Example
+    /// ```rust
+    /// // Keep the indentation of the previous line when the input continues on a new line
+    ///
+    /// use reedline::{DefaultIndenter, DefaultValidator, Reedline};
+    ///
+    /// let mut line_editor = Reedline::create()
+    ///     .with_validator(Box::new(DefaultValidator))
+    ///     .with_indenter(Box::new(DefaultIndenter));
+    /// ```
     #[must_use]
-    pub fn with_transient_prompt(mut self, transient_prompt: Box<dyn Prompt>) -> Self {
-        self.transient_prompt = Some(transient_prompt);
+    pub fn with_indenter(mut self, indenter: Box<dyn Indenter>) -> Self {
+        self.indenter = Some(indenter);
         self
     }
 
-    /// A builder which configures the edit mode for your instance of the Reedline engine
+    /// Remove the current [`Indenter`]
     #[must_use]
-    pub fn with_edit_mode(mut self, edit_mode: Box<dyn EditMode>) -> Self {
-        self.edit_mode = edit_mode;
+    pub fn disable_indenter(mut self) -> Self {
+        self.indenter = None;
         self
     }
 
-    /// A builder that appends a menu to the engine
+    /// A builder that sets the prefix used by [`EditCommand::ToggleComment`] when the command
+    /// does not specify one (default: `#`)
     #[must_use]
-    pub fn with_menu(mut self, menu: ReedlineMenu) -> Self {
-        self.menus.push(menu);
+    pub fn with_comment_prefix(mut self, comment_prefix: &str) -> Self {
+        self.editor.set_comment_prefix(comment_prefix);
         self
     }
 
-    /// A builder that clears the list of menus added to the engine
+    /// Toggle whether [`ReedlineEvent::ExCommand`] (`:` in Vi normal mode) opens the ex
+    /// command line (default: disabled)
     #[must_use]
-    pub fn clear_menus(mut self) -> Self {
-        self.menus = Vec::new();
+    pub fn use_ex_commands(mut self, enable: bool) -> Self {
+        self.use_ex_commands = enable;
         self
     }
 
-    /// A builder that adds the history item id
+    /// A builder that registers an additional command for the ex command line
+    ///
+    /// Registered commands take precedence over the built-in ones with the same name. The
+    /// command line itself is enabled with [`Reedline::use_ex_commands`].
     #[must_use]
-    pub fn with_history_session_id(mut self, session: Option<HistorySessionId>) -> Self {
-        self.history_session_id = session;
+    pub fn with_ex_command(mut self, command: Box<dyn ExCommand>) -> Self {
+        self.ex_commands.push(command);
         self
     }
 
-    /// A builder that enables reedline changing the cursor shape based on the current edit mode.
-    /// The current implementation sets the cursor shape when drawing the prompt.
-    /// Do not use this if the cursor shape is set elsewhere, e.g. in the terminal settings or by ansi escape sequences.
-    pub fn with_cursor_config(mut self, cursor_shapes: CursorConfig) -> Self {
+    /// A builder that enables automatic pairing of brackets and quotes
+    /// # Example
+    /// ```rust
+    /// // Auto-pair brackets, except when the cursor is inside a string
+    ///
+    /// use reedline::{AutoPairs, Reedline};
+    ///
+    /// let auto_pairs = AutoPairs::new(vec![('(', ')'), ('[', ']'), ('"', '"')])
+    ///     .with_filter(|buffer, pos| buffer[..pos].matches('"').count() % 2 == 0);
+    /// let mut line_editor = Reedline::create().with_auto_pairs(auto_pairs);
+    /// ```
+    #[must_use]
+    pub fn with_auto_pairs(mut self, auto_pairs: AutoPairs) -> Self {
+        self.editor.set_auto_pairs(Some(auto_pairs));
+        self
+    }
+
+    /// Disable the automatic pairing of brackets and quotes
+    #[must_use]
+    pub fn disable_auto_pairs(mut self) -> Self {
+        self.editor.set_auto_pairs(None);
+        self
+    }
+
+    /// A builder that sets the text inserted or removed by [`EditCommand::IndentLine`]
+    /// and [`EditCommand::DedentLine`] (default: four spaces)
+    #[must_use]
+    pub fn with_indent_unit(mut self, indent_unit: &str) -> Self {
+        self.editor.set_indent_unit(indent_unit);
+        self
+    }
+
+    /// A builder that limits the size in bytes of text inserted by
+    /// [`ReedlineEvent::ReadFileIntoBuffer`] or the captured output of
+    /// [`ReedlineEvent::RunHostCommand`] (default: 1 MiB)
+    #[must_use]
+    pub fn with_max_insert_size(mut self, max_insert_size: usize) -> Self {
+        self.max_insert_size = max_insert_size;
+        self
+    }
+
+    /// A builder that bounds how often the buffer is repainted while input keeps arriving,
+    /// e.g. during key repeat or a paste without bracketed paste support (default: 16 ms)
+    ///
+    /// Pending events are handled as a batch and the buffer is only repainted once at the
+    /// end. A zero interval repaints after every batch of events.
+    #[must_use]
+    pub fn with_min_repaint_interval(mut self, min_repaint_interval: Duration) -> Self {
+        self.min_repaint_interval = min_repaint_interval;
+        self
+    }
+
+    /// A builder that sets how long a completer or hinter may keep computing its results
+    /// before a progress indicator is shown (default: 200 ms)
+    ///
+    /// See [`Completer::is_pending`] and [`Hinter::is_pending`]. The indicator follows the
+    /// hint, or the indicator of the active menu, and goes away when the results arrive.
+    #[must_use]
+    pub fn with_progress_indicator_delay(mut self, delay: Duration) -> Self {
+        self.progress_indicator_delay = delay;
+        self
+    }
+
+    /// A builder that configures the style of the progress indicator shown for a pending
+    /// completer or hinter
+    #[must_use]
+    pub fn with_progress_indicator_style(mut self, style: Style) -> Self {
+        self.progress_indicator_style = style;
+        self
+    }
+
+    /// A builder that sets the handler for [`ReedlineEvent::Custom`] events
+    /// # Example
+    /// ```rust
+    /// use reedline::{
+    ///     default_emacs_keybindings, CustomEventHandler, Editor, Emacs, KeyCode, KeyModifiers,
+    ///     Reedline, ReedlineEvent, UndoBehavior,
+    /// };
+    ///
+    /// struct Shout;
+    ///
+    /// impl CustomEventHandler for Shout {
+    ///     fn handle(&mut self, name: &str, editor: &mut Editor) {
+    ///         if name == "shout" {
+    ///             let buffer = editor.get_buffer().to_uppercase();
+    ///             editor.edit_buffer(|lb| lb.set_buffer(buffer), UndoBehavior::CreateUndoPoint);
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut keybindings = default_emacs_keybindings();
+    /// keybindings.add_binding(
+    ///     KeyModifiers::ALT,
+    ///     KeyCode::Char('s'),
+    ///     ReedlineEvent::Custom("shout".to_string()),
+    /// );
+    /// let mut line_editor = Reedline::create()
+    ///     .with_edit_mode(Box::new(Emacs::new(keybindings)))
+    ///     .with_custom_event_handler(Box::new(Shout));
+    /// ```
+    #[must_use]
+    pub fn with_custom_event_handler(mut self, handler: Box<dyn CustomEventHandler>) -> Self {
+        self.custom_event_handler = Some(handler);
+        self
+    }
+
+    /// A builder that sets the feedback for keys that are bound to nothing, e.g. typos in
+    /// vi normal mode
+    ///
+    /// Silently ignored by default.
+    /// # Example
+    /// ```rust
+    /// use reedline::{Reedline, UnhandledInput};
+    ///
+    /// let mut line_editor = Reedline::create().with_unhandled_input(UnhandledInput::Bell);
+    /// ```
+    #[must_use]
+    pub fn with_unhandled_input(mut self, unhandled_input: UnhandledInput) -> Self {
+        self.unhandled_input = unhandled_input;
+        self
+    }
+
+    /// A builder that calls `callback` whenever there was no input for `period`, and again
+    /// after every further `period` without input
+    ///
+    /// It gets the time since the last input and returns events to handle as if they were
+    /// typed, e.g. [`ReedlineEvent::CtrlD`] to end an abandoned session. The prompt is
+    /// repainted after every call, which keeps a clock in it up to date.
+    /// # Example
+    /// ```rust
+    /// use reedline::{Reedline, ReedlineEvent};
+    /// use std::time::Duration;
+    ///
+    /// let mut line_editor = Reedline::create().with_idle_handler(Duration::from_secs(1), |idle| {
+    ///     if idle >= Duration::from_secs(600) {
+    ///         vec![ReedlineEvent::CtrlD]
+    ///     } else {
+    ///         vec![]
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_idle_handler(
+        mut self,
+        period: Duration,
+        callback: impl FnMut(Duration) -> Vec<ReedlineEvent> + Send + 'static,
+    ) -> Self {
+        self.idle_handler = Some(IdleHandler {
+            period,
+            callback: Box::new(callback),
+            last_input: Instant::now(),
+            last_call: None,
+        });
+        self
+    }
+
+    /// A builder that repaints the prompt whenever another thread asks for it through a
+    /// clone of `handle`
+    #[must_use]
+    pub fn with_repaint_handle(mut self, handle: RepaintHandle) -> Self {
+        self.repaint_handle = Some(handle);
+        self
+    }
+
+    /// A builder that handles the events other threads send through clones of `injector`
+    #[must_use]
+    pub fn with_event_injector(mut self, injector: EventInjector) -> Self {
+        self.event_injector = Some(injector);
+        self
+    }
+
+    /// Set a different prompt to be used after submitting each line
+    #[must_use]
+    pub fn with_transient_prompt(mut self, transient_prompt: Box<dyn Prompt>) -> Self {
+        self.transient_prompt = Some(transient_prompt);
+        self
+    }
+
+    /// A builder which paints the submitted line once more the way `echo` says, highlighted
+    /// as a whole without the selection, before the output of the application
+    ///
+    /// ```rust
+    /// use reedline::{Reedline, SubmitEcho};
+    ///
+    /// let line_editor = Reedline::create().with_submit_echo(SubmitEcho::new().with_timestamp("%T"));
+    /// ```
+    #[must_use]
+    pub fn with_submit_echo(mut self, echo: SubmitEcho) -> Self {
+        self.submit_echo = Some(echo);
+        self
+    }
+
+    /// A builder which configures the edit mode for your instance of the Reedline engine
+    #[must_use]
+    pub fn with_edit_mode(mut self, edit_mode: Box<dyn EditMode>) -> Self {
+        self.edit_mode = edit_mode;
+        self
+    }
+
+    /// Show a status bar with `status` on the line below the input, above any menu
+    ///
+    /// Meant for information like the edit mode, a running macro recording or the number of
+    /// matches of a search. Text that doesn't fit the width of the terminal is cut off and
+    /// line breaks are shown as spaces. The status bar is repainted with every frame until
+    /// it is cleared and is not left behind on the screen after a line is submitted.
+    ///
+    /// ```rust
+    /// use nu_ansi_term::{Color, Style};
+    /// use reedline::{Reedline, StyledText};
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// let mut status = StyledText::new();
+    /// status.push((Style::new().fg(Color::Green), "recording @q".to_string()));
+    /// line_editor.set_status(status);
+    /// ```
+    pub fn set_status(&mut self, status: StyledText) {
+        self.status = status;
+    }
+
+    /// Hide the status bar set with [`Reedline::set_status`]
+    pub fn clear_status(&mut self) {
+        self.status = StyledText::new();
+    }
+
+    /// Show `message` below the prompt for `duration`
+    ///
+    /// The message goes away by itself once the time is up or with the next key press,
+    /// whichever comes first. It may be styled with ANSI escape sequences, which are
+    /// removed if ANSI coloring is turned off. Showing another message replaces it.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use nu_ansi_term::Color;
+    /// use reedline::Reedline;
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// line_editor.show_message(
+    ///     Color::Yellow.paint("history saved").to_string(),
+    ///     Duration::from_secs(2),
+    /// );
+    /// ```
+    pub fn show_message(&mut self, message: impl Into<String>, duration: Duration) {
+        self.transient_message = Some(TransientMessage {
+            text: message.into(),
+            expires: Instant::now() + duration,
+        });
+    }
+
+    /// Change the rows left to the application, see [`Reedline::with_reserved_rows`]
+    ///
+    /// Takes effect on the terminal right away, `(0, 0)` releases the rows and resets the
+    /// scrolling region of the terminal.
+    pub fn set_reserved_rows(&mut self, top: u16, bottom: u16) -> crate::Result<()> {
+        self.painter.set_reserved_rows(top, bottom);
+        self.painter
+            .apply_reserved_rows()
+            .map_err(ReedlineError::terminal)
+    }
+
+    /// Replace the edit mode of a running editor, e.g. to switch between Emacs and Vi
+    ///
+    /// Takes effect with the next key press
+    pub fn set_edit_mode(&mut self, edit_mode: Box<dyn EditMode>) {
+        self.edit_mode = edit_mode;
+    }
+
+    /// Change the keybindings of the current edit mode without rebuilding the editor
+    ///
+    /// `update` is called for every set of keybindings of the edit mode together with the
+    /// mode it applies in (`"emacs"`, `"vi insert"` or `"vi normal"` for the built-in modes)
+    ///
+    /// # Example
+    /// ```rust
+    /// use reedline::{KeyCode, KeyModifiers, Reedline, ReedlineEvent};
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// line_editor.update_keybindings(|_mode, keybindings| {
+    ///     keybindings.add_binding(KeyModifiers::ALT, KeyCode::Char('l'), ReedlineEvent::ClearScreen);
+    /// });
+    /// ```
+    pub fn update_keybindings<F>(&mut self, mut update: F)
+    where
+        F: FnMut(&str, &mut Keybindings),
+    {
+        for (mode, keybindings) in self.edit_mode.keybindings_mut() {
+            update(mode, keybindings);
+        }
+    }
+
+    /// Describe the keybindings of the current edit mode together with the menus they
+    /// activate, and report conflicts such as bindings to menus that are not registered
+    pub fn describe_keybindings(&self) -> KeybindingReport {
+        let mut report = KeybindingReport::default();
+
+        for (mode, keybindings) in self.edit_mode.keybindings() {
+            let bindings = keybindings
+                .iter()
+                .map(|(key, event)| KeybindingDescription {
+                    mode: mode.to_string(),
+                    key: key.clone(),
+                    event: event.clone(),
+                    menus: menus_in_event(event),
+                })
+                .collect::<Vec<_>>();
+
+            for binding in &bindings {
+                for menu in &binding.menus {
+                    if !self
+                        .menus
+                        .iter()
+                        .any(|registered| registered.name() == menu)
+                    {
+                        report.conflicts.push(KeybindingConflict::UnknownMenu {
+                            mode: mode.to_string(),
+                            key: binding.key.clone(),
+                            menu: menu.clone(),
+                        });
+                    }
+                }
+            }
+            for (first, second) in keybindings.indistinguishable_keys() {
+                report
+                    .conflicts
+                    .push(KeybindingConflict::IndistinguishableKeys {
+                        mode: mode.to_string(),
+                        first,
+                        second,
+                    });
+            }
+
+            report.bindings.extend(bindings);
+        }
+
+        report
+    }
+
+    /// A builder that appends a menu to the engine
+    #[must_use]
+    pub fn with_menu(mut self, menu: ReedlineMenu) -> Self {
+        self.menus.push(menu);
+        self
+    }
+
+    /// A builder that clears the list of menus added to the engine
+    #[must_use]
+    pub fn clear_menus(mut self) -> Self {
+        self.menus = Vec::new();
+        self
+    }
+
+    /// A builder that adds the history item id
+    #[must_use]
+    pub fn with_history_session_id(mut self, session: Option<HistorySessionId>) -> Self {
+        self.history_session_id = session;
+        self
+    }
+
+    /// A builder that enables reedline changing the cursor shape based on the current edit mode.
+    /// The current implementation sets the cursor shape when drawing the prompt.
+    /// Do not use this if the cursor shape is set elsewhere, e.g. in the terminal settings or by ansi escape sequences.
+    ///
+    /// The shape of the user is restored whenever reedline hands the terminal back, also when
+    /// it is dropped during a panic. Terminals that don't support cursor shapes (`TERM` being
+    /// `dumb` or `linux`) are left alone.
+    pub fn with_cursor_config(mut self, cursor_shapes: CursorConfig) -> Self {
         self.cursor_shapes = Some(cursor_shapes);
+        self.cursor_shape_guard.set(true);
         self
     }
 
@@ -534,36 +1690,34 @@ impl Reedline {
     }
 
     /// Output the complete [`History`] chronologically with numbering to the terminal
-    pub fn print_history(&mut self) -> Result<()> {
+    pub fn print_history(&mut self) -> crate::Result<()> {
         let history: Vec<_> = self
             .history
-            .search(SearchQuery::everything(SearchDirection::Forward, None))
-            .expect("todo: error handling");
+            .search(SearchQuery::everything(SearchDirection::Forward, None))?;
 
         for (i, entry) in history.iter().enumerate() {
-            self.print_line(&format!("{}\t{}", i, entry.command_line))?;
+            self.print_line(&format!("{}\t{}", i, entry.command_line))
+                .map_err(ReedlineError::terminal)?;
         }
         Ok(())
     }
 
     /// Output the complete [`History`] for this session, chronologically with numbering to the terminal
-    pub fn print_history_session(&mut self) -> Result<()> {
-        let history: Vec<_> = self
-            .history
-            .search(SearchQuery::everything(
-                SearchDirection::Forward,
-                self.get_history_session_id(),
-            ))
-            .expect("todo: error handling");
+    pub fn print_history_session(&mut self) -> crate::Result<()> {
+        let history: Vec<_> = self.history.search(SearchQuery::everything(
+            SearchDirection::Forward,
+            self.get_history_session_id(),
+        ))?;
 
         for (i, entry) in history.iter().enumerate() {
-            self.print_line(&format!("{}\t{}", i, entry.command_line))?;
+            self.print_line(&format!("{}\t{}", i, entry.command_line))
+                .map_err(ReedlineError::terminal)?;
         }
         Ok(())
     }
 
     /// Print the history session id
-    pub fn print_history_session_id(&mut self) -> Result<()> {
+    pub fn print_history_session_id(&mut self) -> crate::Result<()> {
         println!("History Session Id: {:?}", self.get_history_session_id());
         Ok(())
     }
@@ -572,7 +1726,7 @@ impl Reedline {
     pub fn toggle_history_session_matching(
         &mut self,
         session: Option<HistorySessionId>,
-    ) -> Result<()> {
+    ) -> crate::Result<()> {
         self.history_session_id = match self.get_history_session_id() {
             Some(_) => None,
             None => session,
@@ -587,13 +1741,15 @@ impl Reedline {
 
     /// Mutable view of the history
     pub fn history_mut(&mut self) -> &mut dyn History {
+        self.hint_cache.clear();
         &mut *self.history
     }
 
     /// Update the underlying [`History`] to/from disk
-    pub fn sync_history(&mut self) -> std::io::Result<()> {
+    pub fn sync_history(&mut self) -> crate::Result<()> {
         // TODO: check for interactions in the non-submitting events
-        self.history.sync()
+        self.history.sync()?;
+        Ok(())
     }
 
     /// Check if any commands have been run.
@@ -614,142 +1770,847 @@ impl Reedline {
                 self.history_excluded_item = Some(f(self.history_excluded_item.take().unwrap()));
                 Ok(())
             }
-            Some(r) => self.history.update(*r, f),
+            Some(r) => {
+                self.hint_cache.clear();
+                self.history.update(*r, f)
+            }
             None => Err(ReedlineError(ReedlineErrorVariants::OtherHistoryError(
                 "No command run",
             ))),
         }
     }
 
+    /// Open the history entry `id` for editing with the next [`Reedline::read_line`]
+    ///
+    /// Meant for notebook style REPLs going back to an earlier entry. If the entry was
+    /// submitted in this session and is still where it was painted, the prompt is painted
+    /// over it and everything below is cleared. Otherwise the next prompt simply starts with
+    /// the entry in its buffer. Submitting the edited line updates the entry in the history
+    /// instead of adding a new one, if the history supports updating entries.
+    pub fn edit_history_item(&mut self, id: HistoryItemId) -> crate::Result<()> {
+        let item = self.history.load(id)?;
+        self.editor
+            .set_buffer(item.command_line, UndoBehavior::CreateUndoPoint);
+        self.edited_history_item = Some(id);
+        Ok(())
+    }
+
     /// Wait for input and provide the user with a specified [`Prompt`].
     ///
-    /// Returns a [`std::io::Result`] in which the `Err` type is [`std::io::Result`]
-    /// and the `Ok` variant wraps a [`Signal`] which handles user inputs.
-    pub fn read_line(&mut self, prompt: &dyn Prompt) -> Result<Signal> {
-        terminal::enable_raw_mode()?;
+    /// Returns a [`crate::Result`] in which the `Ok` variant wraps a [`Signal`] which
+    /// handles user inputs. A failure to save the line to the history does not end the
+    /// line, see [`Reedline::with_history_error_handler`].
+    pub fn read_line(&mut self, prompt: &dyn Prompt) -> crate::Result<Signal> {
+        terminal::enable_raw_mode().map_err(ReedlineError::terminal)?;
         self.bracketed_paste.enter();
         self.kitty_protocol.enter();
+        self.completion_cache.clear();
 
         let result = self.read_line_helper(prompt);
+        if !self.executing_host_command {
+            self.read_line_options = ReadLineOptions::default();
+        }
 
         self.bracketed_paste.exit();
         self.kitty_protocol.exit();
-        terminal::disable_raw_mode()?;
-        result
+        self.cursor_shape_guard.exit();
+        terminal::disable_raw_mode().map_err(ReedlineError::terminal)?;
+        result.map_err(ReedlineError::terminal)
     }
 
-    /// Returns the current insertion point of the input buffer.
-    pub fn current_insertion_point(&self) -> usize {
-        self.editor.insertion_point()
+    /// Wait for input like [`Reedline::read_line`], returning the signal with details on how
+    /// the line was edited: the line as it was highlighted, the cursor position when it
+    /// ended, the time it took and whether hints or completions were used
+    ///
+    /// ```no_run
+    /// use reedline::{DefaultPrompt, Reedline, Signal};
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// let submission = line_editor.read_line_detailed(&DefaultPrompt::default()).unwrap();
+    /// if let Signal::Success(line) = &submission.signal {
+    ///     println!("{line} took {:?}", submission.editing_time);
+    /// }
+    /// ```
+    pub fn read_line_detailed(&mut self, prompt: &dyn Prompt) -> crate::Result<Submission> {
+        let signal = self.read_line(prompt)?;
+        Ok(self.submission(signal))
     }
 
-    /// Returns the current contents of the input buffer.
-    pub fn current_buffer_contents(&self) -> &str {
-        self.editor.get_buffer()
+    fn submission(&mut self, signal: Signal) -> Submission {
+        let styled_text = match signal {
+            Signal::Success(_) => std::mem::take(&mut self.line_usage.styled_text),
+            _ => StyledText::new(),
+        };
+        Submission {
+            signal,
+            styled_text,
+            cursor_position: self
+                .last_signal_info
+                .as_ref()
+                .map_or(0, |info| info.cursor_position),
+            editing_time: self.line_usage.started.elapsed(),
+            used_hint: self.line_usage.used_hint,
+            used_completion: self.line_usage.used_completion,
+        }
     }
 
-    /// Writes `msg` to the terminal with a following carriage return and newline
-    fn print_line(&mut self, msg: &str) -> Result<()> {
-        self.painter.paint_line(msg)
+    /// Hand the terminal over to a full-screen application, e.g. a pager or `fzf`, for as
+    /// long as the returned guard lives
+    ///
+    /// Raw mode, bracketed paste, the keyboard enhancements, the cursor shape and the
+    /// scrolling region of the reserved rows (see [`Reedline::with_reserved_rows`]) are
+    /// reset. Raw mode and the scrolling region are restored when the guard is dropped, the
+    /// other modes with the next [`Reedline::read_line`]. The editor forgets what it painted, so that the
+    /// next [`Reedline::read_line`] after a [`ReedlineEvent::ExecuteHostCommand`] repaints the
+    /// prompt and the buffer in full where they were.
+    ///
+    /// ```rust,no_run
+    /// use reedline::{DefaultPrompt, Reedline, Signal};
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// let prompt = DefaultPrompt::default();
+    /// if let Ok(Signal::Success(command)) = line_editor.read_line(&prompt) {
+    ///     if command == "pick-file" {
+    ///         let _suspended = line_editor.suspend_guard().unwrap();
+    ///         std::process::Command::new("fzf").status().unwrap();
+    ///     }
+    /// }
+    /// ```
+    pub fn suspend_guard(&mut self) -> crate::Result<SuspendGuard<'_>> {
+        SuspendGuard::new(self).map_err(ReedlineError::terminal)
     }
 
-    /// Clear the screen by printing enough whitespace to start the prompt or
-    /// other output back at the first line of the terminal.
-    pub fn clear_screen(&mut self) -> Result<()> {
-        self.painter.clear_screen()?;
+    /// Fill the buffer for the next [`Reedline::read_line`] with `buffer`, e.g. to edit the
+    /// last command again
+    ///
+    /// The cursor is placed at the byte offset `cursor`, or at the end. The previous content
+    /// and its undo steps are dropped; a single undo empties the buffer.
+    ///
+    /// ```rust
+    /// use reedline::Reedline;
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// line_editor.prefill_buffer("git commit -m \"\"", Some(15));
+    /// assert_eq!(line_editor.current_insertion_point(), 15);
+    /// ```
+    pub fn prefill_buffer(&mut self, buffer: impl Into<String>, cursor: Option<usize>) {
+        let buffer = buffer.into();
+        let cursor = cursor.unwrap_or(buffer.len());
+        self.editor.prefill(buffer, cursor);
+    }
 
-        Ok(())
+    /// Protect the byte `ranges` of the current buffer from edits, e.g. a prefilled `sudo `
+    /// or the labels of a form
+    ///
+    /// Edit commands changing the text of a range do nothing, and motions landing in a range
+    /// skip over it. A range at the start of the buffer is a prefix, nothing can be typed in
+    /// front of it either. The ranges move along with the text before them and are dropped
+    /// when the line is returned or the whole buffer is replaced, e.g. by browsing the
+    /// history.
+    ///
+    /// ```rust
+    /// use reedline::Reedline;
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// line_editor.prefill_buffer("sudo ", None);
+    /// line_editor.set_read_only(Some(0..5));
+    /// ```
+    pub fn set_read_only(&mut self, ranges: impl IntoIterator<Item = Range<usize>>) {
+        self.editor.set_read_only(ranges);
     }
 
-    /// Clear the screen and the scrollback buffer of the terminal
-    pub fn clear_scrollback(&mut self) -> Result<()> {
-        self.painter.clear_scrollback()?;
+    /// Wait for input like [`Reedline::read_line`], with `options` overriding the
+    /// configuration of the editor for this line only
+    ///
+    /// If the line is left to run a [`ReedlineEvent::ExecuteHostCommand`], the options stay
+    /// in effect for the following [`Reedline::read_line`] call continuing it.
+    pub fn read_line_with_options(
+        &mut self,
+        prompt: &dyn Prompt,
+        options: ReadLineOptions,
+    ) -> crate::Result<Signal> {
+        self.apply_read_line_options(options);
 
-        Ok(())
+        self.read_line(prompt)
     }
 
-    /// Helper implementing the logic for [`Reedline::read_line()`] to be wrapped
-    /// in a `raw_mode` context.
-    fn read_line_helper(&mut self, prompt: &dyn Prompt) -> Result<Signal> {
-        if self.executing_host_command {
-            self.executing_host_command = false;
-        } else {
-            self.painter.initialize_prompt_position()?;
-            self.hide_hints = false;
+    fn apply_read_line_options(&mut self, mut options: ReadLineOptions) {
+        if let Some((buffer, cursor)) = options.buffer.take() {
+            self.prefill_buffer(buffer, cursor);
         }
+        if let Some(ranges) = options.read_only.take() {
+            self.set_read_only(ranges);
+        }
+        self.read_line_options = options;
+    }
 
-        self.repaint(prompt)?;
-
-        let mut crossterm_events: Vec<ReedlineRawEvent> = vec![];
-        let mut reedline_events: Vec<ReedlineEvent> = vec![];
+    /// Read a line filled in from `template`, returning it with the values of its fields or
+    /// `None` if it was cancelled with Ctrl-C or Ctrl-D
+    ///
+    /// Only the fields can be edited. The first one starts selected, so typing replaces its
+    /// default; Tab and Shift-Tab select the next and the previous field. The line is neither
+    /// saved to nor recalled from the history.
+    ///
+    /// ```no_run
+    /// use reedline::{DefaultPrompt, Reedline, Template};
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// let template = Template::new()
+    ///     .with_text("ssh ")
+    ///     .with_field("user", "root")
+    ///     .with_text("@")
+    ///     .with_field("host", "");
+    /// if let Ok(Some(filled)) = line_editor.read_template(&DefaultPrompt::default(), &template) {
+    ///     let host = &filled.fields["host"];
+    ///     // ...
+    /// }
+    /// ```
+    pub fn read_template(
+        &mut self,
+        prompt: &dyn Prompt,
+        template: &Template,
+    ) -> crate::Result<Option<FilledTemplate>> {
+        self.start_template(template);
+        let result = self.read_line(prompt);
+        let fields = self.template_fields.take().unwrap_or_default();
+        Ok(match result? {
+            Signal::Success(line) => Some(FilledTemplate {
+                fields: template.values(&line, &fields),
+                line,
+            }),
+            _ => None,
+        })
+    }
 
-        loop {
-            let mut paste_enter_state = false;
+    fn start_template(&mut self, template: &Template) {
+        let mut keybindings = Keybindings::new();
+        let next_field = ReedlineEvent::Edit(vec![EditCommand::SelectNextField]);
+        let previous_field = ReedlineEvent::Edit(vec![EditCommand::SelectPreviousField]);
+        keybindings.add_binding(KeyModifiers::NONE, KeyCode::Tab, next_field);
+        keybindings.add_binding(KeyModifiers::NONE, KeyCode::BackTab, previous_field.clone());
+        keybindings.add_binding(KeyModifiers::SHIFT, KeyCode::BackTab, previous_field);
+        let options = ReadLineOptions::new()
+            .with_buffer(template.text(), None)
+            .with_read_only(template.read_only())
+            .with_history(false)
+            .with_keybindings(keybindings);
+        self.apply_read_line_options(options);
+        self.template_fields = Some(Vec::new());
+        // From the end of the buffer, in the last field, on to the first one
+        self.run_edit_commands(&[EditCommand::SelectNextField]);
+    }
 
-            #[cfg(feature = "external_printer")]
-            if let Some(ref external_printer) = self.external_printer {
-                // get messages from printer as crlf separated "lines"
-                let messages = Self::external_messages(external_printer)?;
-                if !messages.is_empty() {
-                    // print the message(s)
-                    self.painter.print_external_message(
-                        messages,
-                        self.editor.line_buffer(),
-                        prompt,
+    /// Read a secret like a password, showing `mask` for every typed character or nothing
+    ///
+    /// The line is neither saved to nor recalled from the history, and there are no hints,
+    /// no menus and no external editor. What is cut goes to a separate cut buffer that is
+    /// dropped afterwards. Once the line is returned, the memory of the buffer and its undo
+    /// steps is overwritten with zeros; the buffer held before is restored.
+    ///
+    /// ```no_run
+    /// use reedline::{DefaultPrompt, DefaultPromptSegment, Reedline, Signal};
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// let prompt = DefaultPrompt::new(
+    ///     DefaultPromptSegment::Basic("password".to_string()),
+    ///     DefaultPromptSegment::Empty,
+    /// );
+    /// if let Ok(Signal::Success(password)) = line_editor.read_line_masked(&prompt, Some('*')) {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn read_line_masked(
+        &mut self,
+        prompt: &dyn Prompt,
+        mask: Option<char>,
+    ) -> crate::Result<Signal> {
+        let held_buffer = self.editor.get_buffer().to_string();
+        self.editor.zeroize();
+        self.editor.reserve(MASKED_BUFFER_CAPACITY);
+        let cut_buffer = self.editor.replace_cut_buffer(get_local_clipboard());
+        self.input_mask = Some(mask);
+
+        let result = self.read_line(prompt);
+
+        self.input_mask = None;
+        self.editor.zeroize();
+        self.editor.replace_cut_buffer(cut_buffer);
+        self.editor
+            .set_buffer(held_buffer, UndoBehavior::CreateUndoPoint);
+        self.editor.reset_undo_stack();
+        result
+    }
+
+    /// Ask a yes or no question between two lines, returning the answer or `None` if the
+    /// question was cancelled
+    ///
+    /// `y` and `n` answer right away and Enter picks `default`, the capitalized one of the
+    /// two; Esc, Ctrl-C and Ctrl-D cancel. The question is painted with `prompt`, like a line
+    /// of [`Reedline::read_line`], but leaves the buffer and the history alone.
+    ///
+    /// ```no_run
+    /// use reedline::{DefaultPrompt, DefaultPromptSegment, Reedline};
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// let prompt = DefaultPrompt::new(
+    ///     DefaultPromptSegment::Basic("Delete all files?".to_string()),
+    ///     DefaultPromptSegment::Empty,
+    /// );
+    /// if let Ok(Some(true)) = line_editor.confirm(&prompt, false) {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn confirm(&mut self, prompt: &dyn Prompt, default: bool) -> crate::Result<Option<bool>> {
+        self.read_choice(prompt, Confirmation::new(default))
+    }
+
+    /// Let the user pick one of `options`, returning its index or `None` if the choice was
+    /// cancelled
+    ///
+    /// The options are listed below the prompt. The arrow keys, Tab, `j` and `k` move through
+    /// them, Enter picks the selected one; Esc, Ctrl-C and Ctrl-D cancel. Like
+    /// [`Reedline::confirm`], this leaves the buffer and the history alone.
+    pub fn select<S: AsRef<str>>(
+        &mut self,
+        prompt: &dyn Prompt,
+        options: &[S],
+    ) -> crate::Result<Option<usize>> {
+        if options.is_empty() {
+            return Ok(None);
+        }
+        let options = options
+            .iter()
+            .map(|option| option.as_ref().to_string())
+            .collect();
+        self.read_choice(prompt, Selection::new(options))
+    }
+
+    /// Wait for one of the `allowed` keys, e.g. the hotkeys of a wizard, returning it or
+    /// `None` if the question was cancelled
+    ///
+    /// The keys are listed after the prompt and other keys are ignored, whatever their
+    /// modifiers; Esc, Ctrl-C and Ctrl-D cancel, so Esc can't be one of the keys.
+    pub fn read_key(
+        &mut self,
+        prompt: &dyn Prompt,
+        allowed: &[KeyCode],
+    ) -> crate::Result<Option<KeyCode>> {
+        self.read_choice(prompt, KeyChoice::new(allowed.to_vec()))
+    }
+
+    /// Read a number in `range`, returning it or `None` if the input was cancelled
+    ///
+    /// Characters that can't continue a number are ignored, the number is shown in red
+    /// while it is outside of the range, which follows it, and Enter only takes a number in
+    /// the range; Esc, Ctrl-C and Ctrl-D cancel.
+    ///
+    /// ```no_run
+    /// use reedline::{DefaultPrompt, DefaultPromptSegment, Reedline};
+    ///
+    /// let mut line_editor = Reedline::create();
+    /// let prompt = DefaultPrompt::new(
+    ///     DefaultPromptSegment::Basic("Number of workers".to_string()),
+    ///     DefaultPromptSegment::Empty,
+    /// );
+    /// let workers: Option<u16> = line_editor.read_number(&prompt, 1..=64).unwrap();
+    /// ```
+    pub fn read_number<T>(
+        &mut self,
+        prompt: &dyn Prompt,
+        range: impl RangeBounds<T>,
+    ) -> crate::Result<Option<T>>
+    where
+        T: FromStr + PartialOrd + Clone + Display,
+    {
+        self.read_choice(prompt, NumberInput::new(range))
+    }
+
+    fn read_choice<C: Choice>(
+        &mut self,
+        prompt: &dyn Prompt,
+        mut choice: C,
+    ) -> crate::Result<Option<C::Value>> {
+        terminal::enable_raw_mode().map_err(ReedlineError::terminal)?;
+        let result = self.read_choice_helper(prompt, &mut choice);
+        self.cursor_shape_guard.exit();
+        terminal::disable_raw_mode().map_err(ReedlineError::terminal)?;
+        result.map_err(ReedlineError::terminal)
+    }
+
+    // Reads keys until `choice` is made, leaving its answer on the line of the prompt
+    fn read_choice_helper<C: Choice>(
+        &mut self,
+        prompt: &dyn Prompt,
+        choice: &mut C,
+    ) -> Result<Option<C::Value>> {
+        self.painter.initialize_prompt_position()?;
+        let value = loop {
+            let (answer, rest) = choice.render(self.use_ansi_coloring);
+            self.paint_choice(prompt, &answer, &rest)?;
+            let event = ReedlineRawEvent::convert_from(event::read()?).map(ReedlineRawEvent::into);
+            let key = match event {
+                Some(Event::Key(key)) => key,
+                Some(Event::Resize(width, height)) => {
+                    self.painter.handle_resize(width, height);
+                    continue;
+                }
+                _ => continue,
+            };
+            let cancels = key.code == KeyCode::Esc
+                || (key.modifiers == KeyModifiers::CONTROL
+                    && matches!(key.code, KeyCode::Char('c' | 'd')));
+            if cancels {
+                break None;
+            }
+            if let ChoiceStep::Done(value) = choice.handle_key(key) {
+                break value;
+            }
+        };
+        self.paint_choice(prompt, &choice.answer(value.as_ref()), "")?;
+        self.painter.move_cursor_to_end()?;
+        Ok(value)
+    }
+
+    fn paint_choice(&mut self, prompt: &dyn Prompt, answer: &str, rest: &str) -> Result<()> {
+        let sanitized;
+        let prompt = match self.sanitizer {
+            Some(sanitizer) => {
+                sanitized = SanitizedPrompt::new(prompt, sanitizer);
+                &sanitized as &dyn Prompt
+            }
+            None => prompt,
+        };
+        let cursor_shape = self.cursor_shape(false);
+        let lines = PromptLines::new(prompt, self.prompt_edit_mode(), None, answer, "", rest, "");
+        self.painter
+            .repaint_buffer(prompt, &lines, None, self.use_ansi_coloring, cursor_shape)
+    }
+
+    /// Which events of `event`, a [`ReedlineEvent::UntilFound`], would be tried in the
+    /// current state of the editor and why the ones before the handled one don't apply,
+    /// without handling any of them
+    ///
+    /// Meant for writing bindings, e.g. from a [`ReedlineEvent::ExecuteHostCommand`]. Events
+    /// are judged the way the line editor handles them, the other modes like the history
+    /// search aren't considered. Events that only turn out not to apply once handled, e.g.
+    /// because a menu has no values, count as handled. Any other event is reported as a list
+    /// of one. With the `tracing` feature, handling the events logs every tried one at the
+    /// `debug` level.
+    ///
+    /// ```rust
+    /// use reedline::{BranchOutcome, Reedline, ReedlineEvent};
+    ///
+    /// let line_editor = Reedline::create();
+    /// let event = ReedlineEvent::UntilFound(vec![ReedlineEvent::MenuNext, ReedlineEvent::Down]);
+    /// assert_eq!(
+    ///     line_editor.explain_until_found(&event),
+    ///     vec![
+    ///         (&ReedlineEvent::MenuNext, BranchOutcome::Inapplicable("no menu is active")),
+    ///         (&ReedlineEvent::Down, BranchOutcome::Handled),
+    ///     ]
+    /// );
+    /// ```
+    pub fn explain_until_found<'event>(
+        &self,
+        event: &'event ReedlineEvent,
+    ) -> Vec<(&'event ReedlineEvent, BranchOutcome)> {
+        let events = match event {
+            ReedlineEvent::UntilFound(events) => events.iter().collect(),
+            event => vec![event],
+        };
+        let mut handled = false;
+        events
+            .into_iter()
+            .map(|event| {
+                let outcome = if handled {
+                    BranchOutcome::NotTried
+                } else if let Some(reason) = self.inapplicable_reason(event) {
+                    BranchOutcome::Inapplicable(reason)
+                } else {
+                    handled = true;
+                    BranchOutcome::Handled
+                };
+                (event, outcome)
+            })
+            .collect()
+    }
+
+    // Why the line editor wouldn't handle `event` now, if it can tell without handling it
+    fn inapplicable_reason(&self, event: &ReedlineEvent) -> Option<&'static str> {
+        if let Some(reason) = self.refusal_reason(event) {
+            return Some(reason);
+        }
+        let menu_active = self.menus.iter().any(|menu| menu.is_active());
+        match event {
+            ReedlineEvent::Menu(_) if menu_active => Some("a menu is already active"),
+            ReedlineEvent::Menu(name) if !self.menus.iter().any(|menu| menu.name() == name) => {
+                Some("there is no menu of that name")
+            }
+            ReedlineEvent::MenuNext
+            | ReedlineEvent::MenuPrevious
+            | ReedlineEvent::MenuUp
+            | ReedlineEvent::MenuDown
+            | ReedlineEvent::MenuLeft
+            | ReedlineEvent::MenuRight
+            | ReedlineEvent::MenuPageNext
+            | ReedlineEvent::MenuPagePrevious
+                if !menu_active =>
+            {
+                Some("no menu is active")
+            }
+            ReedlineEvent::HistoryHintComplete if !self.hint_applies(&self.current_hint()) => {
+                Some("no hint is shown at the cursor")
+            }
+            ReedlineEvent::HistoryHintWordComplete
+                if !self.hint_applies(&self.current_hint_token()) =>
+            {
+                Some("no hint is shown at the cursor")
+            }
+            ReedlineEvent::DeleteHistoryItem
+            | ReedlineEvent::EditHistoryItem
+            | ReedlineEvent::TogglePinHistoryItem
+                if self.selected_history_menu_value().is_none() =>
+            {
+                Some("no value of a history menu is selected")
+            }
+            ReedlineEvent::CompleteWordNext | ReedlineEvent::CompleteWordPrevious
+                if !self.word_expansion_continues()
+                    && self
+                        .new_word_expansion()
+                        .map_or(true, |expansion| expansion.candidates().is_empty()) =>
+            {
+                Some("no word completes the one before the cursor")
+            }
+            ReedlineEvent::FormatBuffer if self.formatter.is_none() => {
+                Some("there is no formatter")
+            }
+            ReedlineEvent::SendSelection(_) if self.selection_handler.is_none() => {
+                Some("there is no selection handler")
+            }
+            ReedlineEvent::Custom(_) if self.custom_event_handler.is_none() => {
+                Some("there is no custom event handler")
+            }
+            ReedlineEvent::ToggleFolds
+                if self.paste_folds.as_ref().map_or(true, PasteFolds::is_empty) =>
+            {
+                Some("no pasted block is folded")
+            }
+            ReedlineEvent::ExCommand if !self.use_ex_commands => Some("ex commands are off"),
+            ReedlineEvent::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                if self.condition_holds(condition) {
+                    self.inapplicable_reason(then)
+                } else {
+                    self.inapplicable_reason(otherwise)
+                }
+            }
+            ReedlineEvent::UntilFound(events) | ReedlineEvent::Multiple(events)
+                if events
+                    .iter()
+                    .all(|event| self.inapplicable_reason(event).is_some()) =>
+            {
+                Some("none of its events applies")
+            }
+            _ => None,
+        }
+    }
+
+    // Why `handle_editor_event` turns down `event` before handling it
+    fn refusal_reason(&self, event: &ReedlineEvent) -> Option<&'static str> {
+        if self.input_mask.is_some() && Self::exposes_masked_input(event) {
+            return Some("it could show the masked input");
+        }
+        match event {
+            ReedlineEvent::None | ReedlineEvent::Mouse => Some("the event does nothing"),
+            ReedlineEvent::Resize(..) => Some("resizing only updates the size of the screen"),
+            ReedlineEvent::ScrollViewport(_) if self.painter.visible_lines().is_none() => {
+                Some("the buffer fits on the screen")
+            }
+            _ => None,
+        }
+    }
+
+    /// Details on how the last call to [`Reedline::read_line()`] ended, e.g. which binding
+    /// submitted the line and where the cursor was at that moment
+    pub fn last_signal_info(&self) -> Option<&SignalInfo> {
+        self.last_signal_info.as_ref()
+    }
+
+    /// Time spent in the stages of handling the most recent batch of input events
+    ///
+    /// The breakdown of every frame is also emitted as a `tracing` event at the `debug` level.
+    ///
+    /// ## Required feature:
+    /// `tracing`
+    #[cfg(feature = "tracing")]
+    pub fn last_frame_timings(&self) -> FrameTimings {
+        self.frame_timings
+    }
+
+    /// The state of the engine, e.g. to attach to a bug report
+    ///
+    /// [`ReedlineEvent::ToggleDebugPanel`] shows the same state below the buffer while
+    /// editing. While reading a line with [`Reedline::read_line_masked`] the buffer is masked
+    /// and neither the pending keys nor the last event are shown, they could hold what was
+    /// typed.
+    pub fn debug_state(&self) -> EngineStateSnapshot {
+        let (buffer, cursor) = match self.input_mask {
+            Some(mask) => {
+                let (masked, cursor) = mask_buffer(
+                    self.editor.get_buffer(),
+                    self.editor.insertion_point(),
+                    mask,
+                );
+                (masked.raw_string(), cursor)
+            }
+            None => (
+                self.editor.get_buffer().to_string(),
+                self.editor.insertion_point(),
+            ),
+        };
+        let unmasked = self.input_mask.is_none();
+        EngineStateSnapshot {
+            buffer,
+            cursor,
+            mode: self.prompt_edit_mode(),
+            active_menu: self
+                .menus
+                .iter()
+                .find(|menu| menu.is_active())
+                .map(|menu| menu.name().to_string()),
+            undo_depth: self.editor.undo_depth(),
+            pending_keys: if unmasked {
+                self.edit_mode.pending_keys()
+            } else {
+                String::new()
+            },
+            last_event: self.last_event.clone().filter(|_| unmasked),
+        }
+    }
+
+    /// How often the hint of the last repaint could be reused, instead of asking the
+    /// [`Hinter`] again
+    ///
+    /// ## Required feature:
+    /// `tracing`
+    #[cfg(feature = "tracing")]
+    pub fn hint_cache_stats(&self) -> CacheStats {
+        self.hint_cache.stats()
+    }
+
+    /// How often the cache enabled with [`Reedline::with_completion_cache`] could provide
+    /// the suggestions, instead of asking the [`Completer`]
+    ///
+    /// ## Required feature:
+    /// `tracing`
+    #[cfg(feature = "tracing")]
+    pub fn completion_cache_stats(&self) -> CacheStats {
+        self.completion_cache.stats()
+    }
+
+    /// Returns the current insertion point of the input buffer.
+    pub fn current_insertion_point(&self) -> usize {
+        self.editor.insertion_point()
+    }
+
+    /// Returns the current contents of the input buffer.
+    pub fn current_buffer_contents(&self) -> &str {
+        self.editor.get_buffer()
+    }
+
+    /// Returns the selected range of the input buffer, if any, e.g. to hand the selected
+    /// text to another tool
+    pub fn current_selection(&self) -> Option<std::ops::Range<usize>> {
+        self.editor.get_selection().map(|(start, end)| start..end)
+    }
+
+    /// Select from `anchor` to `cursor` in the input buffer, or drop the selection with `None`
+    ///
+    /// See [`Editor::set_selection`] for positions that are out of place.
+    pub fn set_selection(&mut self, selection: Option<(usize, usize)>) {
+        match selection {
+            Some((anchor, cursor)) => self.editor.set_selection(anchor, cursor),
+            None => self.editor.clear_selection(),
+        }
+    }
+
+    /// Writes `msg` to the terminal with a following carriage return and newline
+    fn print_line(&mut self, msg: &str) -> Result<()> {
+        self.painter.paint_line(msg)
+    }
+
+    /// Clear the screen by printing enough whitespace to start the prompt or
+    /// other output back at the first line of the terminal.
+    pub fn clear_screen(&mut self) -> crate::Result<()> {
+        self.painter
+            .clear_screen()
+            .map_err(ReedlineError::terminal)?;
+
+        Ok(())
+    }
+
+    /// Clear the screen and the scrollback buffer of the terminal
+    pub fn clear_scrollback(&mut self) -> crate::Result<()> {
+        self.painter
+            .clear_scrollback()
+            .map_err(ReedlineError::terminal)?;
+
+        Ok(())
+    }
+
+    /// Helper implementing the logic for [`Reedline::read_line()`] to be wrapped
+    /// in a `raw_mode` context.
+    fn read_line_helper(&mut self, prompt: &dyn Prompt) -> Result<Signal> {
+        if self.executing_host_command {
+            self.executing_host_command = false;
+            // The host command may have written to the screen
+            self.painter.invalidate_painted_frame();
+            self.hint_cache.clear();
+            self.completion_cache.clear();
+        } else {
+            self.painter.initialize_prompt_position()?;
+            self.hide_hints = false;
+            self.reset_idle_time();
+            self.line_usage = LineUsage::new();
+            self.edit_mode.start_line();
+            self.live_validation = None;
+            self.live_validation_pending = None;
+            // An entry opened with `edit_history_item` is edited where it was painted
+            if let Some(row) = self.edited_history_item.and_then(|id| {
+                self.submitted_rows
+                    .iter()
+                    .find(|(submitted, _)| *submitted == id)
+                    .map(|(_, row)| *row)
+            }) {
+                self.painter.move_prompt_to_row(row);
+            }
+        }
+
+        if let Some(menu) = self.read_line_options.menu.take() {
+            if let EventStatus::Exits(signal) =
+                self.handle_event(prompt, ReedlineEvent::Menu(menu))?
+            {
+                return Ok(signal);
+            }
+        }
+
+        self.repaint(prompt)?;
+        let mut last_repaint = Instant::now();
+        let mut needs_repaint = false;
+
+        let mut crossterm_events: Vec<ReedlineRawEvent> = vec![];
+        let mut reedline_events: Vec<ReedlineEvent> = vec![];
+
+        loop {
+            let mut paste_enter_state = false;
+
+            #[cfg(feature = "external_printer")]
+            if let Some(ref external_printer) = self.external_printer {
+                // get messages from printer as crlf separated "lines"
+                let mut messages = Self::external_messages(external_printer)?;
+                if let Some(sanitizer) = &self.sanitizer {
+                    for message in messages.iter_mut() {
+                        *message = sanitizer.sanitize(message).into_owned();
+                    }
+                }
+                if !messages.is_empty() {
+                    // print the message(s)
+                    self.painter.print_external_message(
+                        messages,
+                        self.editor.line_buffer(),
+                        prompt,
                     )?;
                     self.repaint(prompt)?;
                 }
             }
 
+            // Events of the `EventInjector` are handled right away, like typed ones
+            let injected = self
+                .event_injector
+                .as_ref()
+                .map(EventInjector::take_events)
+                .unwrap_or_default();
             let mut latest_resize = None;
-            loop {
-                match event::read()? {
-                    Event::Resize(x, y) => {
-                        latest_resize = Some((x, y));
-                    }
-                    enter @ Event::Key(KeyEvent {
-                        code: KeyCode::Enter,
-                        modifiers: KeyModifiers::NONE,
-                        ..
-                    }) => {
-                        let enter = ReedlineRawEvent::convert_from(enter);
-                        if let Some(enter) = enter {
-                            crossterm_events.push(enter);
-                            // Break early to check if the input is complete and
-                            // can be send to the hosting application. If
-                            // multiple complete entries are submitted, events
-                            // are still in the crossterm queue for us to
-                            // process.
-                            paste_enter_state = crossterm_events.len() > EVENTS_THRESHOLD;
-                            break;
+            if injected.is_empty() {
+                // Without input, wake up to clear an expired transient message, to ask the
+                // completer and hinter again for their pending results or for the idle handler
+                if let Some(timeout) = self.idle_timeout() {
+                    if !event::poll(timeout)? {
+                        if let Some(signal) = self.handle_idle_timeout(prompt)? {
+                            return Ok(signal);
                         }
+                        continue;
                     }
-                    x => {
-                        let raw_event = ReedlineRawEvent::convert_from(x);
-                        if let Some(evt) = raw_event {
-                            crossterm_events.push(evt);
+                }
+
+                loop {
+                    match event::read()? {
+                        Event::Resize(x, y) => {
+                            latest_resize = Some((x, y));
+                        }
+                        enter @ Event::Key(KeyEvent {
+                            code: KeyCode::Enter,
+                            modifiers: KeyModifiers::NONE,
+                            ..
+                        }) => {
+                            let enter = ReedlineRawEvent::convert_from(enter);
+                            if let Some(enter) = enter {
+                                crossterm_events.push(enter);
+                                // Break early to check if the input is complete and
+                                // can be send to the hosting application. If
+                                // multiple complete entries are submitted, events
+                                // are still in the crossterm queue for us to
+                                // process.
+                                paste_enter_state = crossterm_events.len() > EVENTS_THRESHOLD;
+                                break;
+                            }
+                        }
+                        x => {
+                            let raw_event = ReedlineRawEvent::convert_from(x);
+                            if let Some(evt) = raw_event {
+                                crossterm_events.push(evt);
+                            }
                         }
                     }
-                }
 
-                // There could be multiple events queued up!
-                // pasting text, resizes, blocking this thread (e.g. during debugging)
-                // We should be able to handle all of them as quickly as possible without causing unnecessary output steps.
-                if !event::poll(Duration::from_millis(POLL_WAIT))? {
-                    break;
+                    // There could be multiple events queued up!
+                    // pasting text, resizes, blocking this thread (e.g. during debugging)
+                    // We should be able to handle all of them as quickly as possible without causing unnecessary output steps.
+                    if !event::poll(Duration::from_millis(POLL_WAIT))? {
+                        break;
+                    }
                 }
             }
+            self.reset_idle_time();
+
+            #[cfg(feature = "tracing")]
+            let _frame_span = tracing::debug_span!("frame").entered();
+            #[cfg(feature = "tracing")]
+            let frame_start = Instant::now();
+            #[cfg(feature = "tracing")]
+            {
+                self.frame_timings = FrameTimings::default();
+            }
 
             if let Some((x, y)) = latest_resize {
                 reedline_events.push(ReedlineEvent::Resize(x, y));
             }
+            reedline_events.extend(injected);
 
             // Accelerate pasted text by fusing `EditCommand`s
             //
             // (Text should only be `EditCommand::InsertChar`s)
             let mut last_edit_commands = None;
             for event in crossterm_events.drain(..) {
-                match (&mut last_edit_commands, self.edit_mode.parse_event(event)) {
+                let parsed_event = timed!(
+                    self.frame_timings,
+                    event_parsing,
+                    self.parse_raw_event(event)
+                );
+                match (&mut last_edit_commands, parsed_event) {
                     (None, ReedlineEvent::Edit(ec)) => {
                         last_edit_commands = Some(ec);
                     }
@@ -771,17 +2632,26 @@ impl Reedline {
             }
 
             for event in reedline_events.drain(..) {
-                match self.handle_event(prompt, event)? {
+                let status = timed!(
+                    self.frame_timings,
+                    event_handling,
+                    self.handle_event(prompt, event)
+                )?;
+                match status {
                     EventStatus::Exits(signal) => {
-                        if !self.executing_host_command {
-                            // Move the cursor below the input area, for external commands or new read_line call
-                            self.painter.move_cursor_to_end()?;
-                        }
+                        self.prepare_exit()?;
+                        #[cfg(feature = "tracing")]
+                        self.finish_frame(frame_start);
                         return Ok(signal);
                     }
                     EventStatus::Handled => {
-                        if !paste_enter_state {
+                        needs_repaint = true;
+                        // Menus update their values while painting,
+                        // the following events need to see them
+                        if !paste_enter_state && self.menus.iter().any(|menu| menu.is_active()) {
                             self.repaint(prompt)?;
+                            needs_repaint = false;
+                            last_repaint = Instant::now();
                         }
                     }
                     EventStatus::Inapplicable => {
@@ -789,48 +2659,351 @@ impl Reedline {
                     }
                 }
             }
+
+            // Paint the whole batch at once
+            let input_pending = needs_repaint && event::poll(Duration::ZERO)?;
+            if repaint_after_batch(
+                needs_repaint,
+                paste_enter_state,
+                input_pending,
+                last_repaint.elapsed(),
+                self.min_repaint_interval,
+            ) {
+                self.repaint(prompt)?;
+                needs_repaint = false;
+                last_repaint = Instant::now();
+            }
+
+            self.report_unhandled_keys(prompt)?;
+
+            #[cfg(feature = "tracing")]
+            self.finish_frame(frame_start);
         }
     }
 
-    fn handle_event(&mut self, prompt: &dyn Prompt, event: ReedlineEvent) -> Result<EventStatus> {
-        if self.input_mode == InputMode::HistorySearch {
-            self.handle_history_search_event(event)
-        } else {
-            self.handle_editor_event(prompt, event)
-        }
+    // How long to wait for input before `handle_idle_timeout`, if there is anything to do then
+    fn idle_timeout(&self) -> Option<Duration> {
+        let message = self
+            .transient_message
+            .as_ref()
+            .map(|message| message.expires.saturating_duration_since(Instant::now()));
+        let pending = self.pending_since.map(|_| PENDING_POLL_INTERVAL);
+        let idle = self.idle_handler.as_ref().map(|handler| {
+            handler
+                .next_call()
+                .saturating_duration_since(Instant::now())
+        });
+        let handles = (self.repaint_handle.is_some() || self.event_injector.is_some())
+            .then_some(HANDLE_POLL_INTERVAL);
+        let keys = self
+            .key_overlay
+            .as_ref()
+            .and_then(KeyOverlay::next_expiry)
+            .map(|expires| expires.saturating_duration_since(Instant::now()));
+        let validation = self
+            .live_validation_pending
+            .as_ref()
+            .map(|(_, due)| due.saturating_duration_since(Instant::now()));
+        let flash = self
+            .flash_until
+            .map(|until| until.saturating_duration_since(Instant::now()));
+        [message, pending, idle, handles, keys, validation, flash]
+            .into_iter()
+            .flatten()
+            .min()
     }
 
-    fn handle_history_search_event(&mut self, event: ReedlineEvent) -> io::Result<EventStatus> {
-        match event {
-            ReedlineEvent::UntilFound(events) => {
-                for event in events {
-                    match self.handle_history_search_event(event)? {
-                        EventStatus::Inapplicable => {
-                            // Try again with the next event handler
-                        }
-                        success => {
-                            return Ok(success);
-                        }
-                    }
-                }
-                // Exhausting the event handlers is still considered handled
-                Ok(EventStatus::Handled)
+    fn handle_idle_timeout(&mut self, prompt: &dyn Prompt) -> Result<Option<Signal>> {
+        let mut needs_repaint = self
+            .repaint_handle
+            .as_ref()
+            .map_or(false, RepaintHandle::take_request);
+        if self
+            .transient_message
+            .as_ref()
+            .map_or(false, |message| message.expires <= Instant::now())
+        {
+            self.transient_message = None;
+            needs_repaint = true;
+        }
+        if self
+            .flash_until
+            .map_or(false, |until| until <= Instant::now())
+        {
+            self.flash_until = None;
+            needs_repaint = true;
+        }
+        if let Some(key_overlay) = self.key_overlay.as_mut() {
+            needs_repaint |= key_overlay.prune(Instant::now());
+        }
+        needs_repaint |= self.update_live_validation();
+        if self.pending_since.is_some() {
+            needs_repaint = true;
+            // Menus only ask for new values after an edit
+            for menu in self.menus.iter_mut().filter(|menu| menu.is_active()) {
+                menu.menu_event(MenuEvent::Edit(false));
             }
-            ReedlineEvent::CtrlD => {
-                if self.editor.is_empty() {
-                    self.input_mode = InputMode::Regular;
-                    self.editor.reset_undo_stack();
-                    Ok(EventStatus::Exits(Signal::CtrlD))
-                } else {
-                    self.run_history_commands(&[EditCommand::Delete]);
-                    Ok(EventStatus::Handled)
-                }
+        }
+        let now = Instant::now();
+        let events = match self.idle_handler.as_mut() {
+            Some(handler) if handler.next_call() <= now => {
+                handler.last_call = Some(now);
+                needs_repaint = true;
+                (handler.callback)(now - handler.last_input)
             }
-            ReedlineEvent::CtrlC => {
-                self.input_mode = InputMode::Regular;
-                Ok(EventStatus::Exits(Signal::CtrlC))
+            _ => vec![],
+        };
+        for event in events {
+            if let EventStatus::Exits(signal) = self.handle_event(prompt, event)? {
+                self.prepare_exit()?;
+                return Ok(Some(signal));
             }
-            ReedlineEvent::ClearScreen => {
+        }
+        if needs_repaint {
+            self.repaint(prompt)?;
+        }
+        Ok(None)
+    }
+
+    // Validates the buffer for the prompt indicator once it wasn't edited for the debounce,
+    // returns whether there is a new result to paint
+    fn update_live_validation(&mut self) -> bool {
+        let (debounce, validator) = match (self.live_validation_debounce, &self.validator) {
+            (Some(debounce), Some(validator)) if self.input_mask.is_none() => (debounce, validator),
+            _ => return false,
+        };
+        let buffer = self.editor.get_buffer();
+        if matches!(&self.live_validation, Some((validated, _)) if validated == buffer) {
+            self.live_validation_pending = None;
+            return false;
+        }
+        let now = Instant::now();
+        match &self.live_validation_pending {
+            Some((pending, due)) if pending == buffer && *due > now => return false,
+            Some((pending, _)) if pending == buffer => {}
+            // Every edit restarts the wait
+            _ if !debounce.is_zero() => {
+                self.live_validation_pending = Some((buffer.to_string(), now + debounce));
+                return false;
+            }
+            _ => {}
+        }
+        self.live_validation = Some((buffer.to_string(), validator.validate(buffer)));
+        self.live_validation_pending = None;
+        true
+    }
+
+    fn reset_idle_time(&mut self) {
+        if let Some(handler) = self.idle_handler.as_mut() {
+            handler.last_input = Instant::now();
+            handler.last_call = None;
+        }
+    }
+
+    // Leaves the painted lines behind once `read_line` returns
+    fn prepare_exit(&mut self) -> Result<()> {
+        if !self.executing_host_command {
+            self.edited_history_item = None;
+            // Move the cursor below the input area, for external commands or new read_line call
+            self.painter.move_cursor_to_end()?;
+            if let Some(text) = self.post_submit.take() {
+                self.painter.paint_post_submit(&text)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Translates the raw event with the edit mode, remembering keys that are bound to nothing
+    fn parse_raw_event(&mut self, event: ReedlineRawEvent) -> ReedlineEvent {
+        let key = event.key();
+        // The keys of a secret would show what was typed
+        if let (Some(key_overlay), Some(key), None) =
+            (self.key_overlay.as_mut(), key.as_ref(), self.input_mask)
+        {
+            key_overlay.record(key, Instant::now());
+        }
+        if let Some(overridden) = key.as_ref().and_then(|key| {
+            self.read_line_options
+                .keybindings
+                .as_ref()?
+                .find_binding(key.modifier, key.key_code)
+        }) {
+            return overridden;
+        }
+        let parsed_event = self.edit_mode.parse_event(event);
+        if parsed_event == ReedlineEvent::None && !self.edit_mode.has_pending_keys() {
+            self.unhandled_keys.extend(key);
+        }
+        parsed_event
+    }
+
+    // Gives the configured feedback for the keys that were bound to nothing
+    fn report_unhandled_keys(&mut self, prompt: &dyn Prompt) -> Result<()> {
+        if self.unhandled_keys.is_empty() {
+            return Ok(());
+        }
+        match &mut self.unhandled_input {
+            UnhandledInput::Ignore => {}
+            UnhandledInput::Bell => self.painter.ring_bell()?,
+            UnhandledInput::Flash => {
+                if self.use_ansi_coloring {
+                    self.flash_until = Some(Instant::now() + FLASH_DURATION);
+                    self.repaint(prompt)?;
+                }
+            }
+            UnhandledInput::Callback(callback) => {
+                for key in &self.unhandled_keys {
+                    callback(key);
+                }
+            }
+        }
+        self.unhandled_keys.clear();
+        Ok(())
+    }
+
+    #[cfg(feature = "tracing")]
+    fn finish_frame(&mut self, frame_start: Instant) {
+        self.frame_timings.total = frame_start.elapsed();
+        tracing::debug!(
+            timings = ?self.frame_timings,
+            hint_cache = ?self.hint_cache.stats(),
+            completion_cache = ?self.completion_cache.stats(),
+            "frame handled"
+        );
+    }
+
+    fn handle_event(&mut self, prompt: &dyn Prompt, event: ReedlineEvent) -> Result<EventStatus> {
+        if !matches!(event, ReedlineEvent::Resize(..)) {
+            self.command_line_message = None;
+            self.transient_message = None;
+        }
+        if event != ReedlineEvent::None {
+            self.last_event = Some(event.clone());
+        }
+
+        let event = self.resolve_conditions(event);
+        if self.input_mode == InputMode::ExCommand {
+            self.handle_ex_command_event(event)
+        } else if self.input_mode == InputMode::SearchReplace {
+            self.handle_search_replace_event(event)
+        } else if self.input_mode == InputMode::BufferSearch {
+            self.handle_buffer_search_event(prompt, event)
+        } else if self.input_mode == InputMode::Correction {
+            self.handle_correction_event(prompt, event)
+        } else if self.input_mode == InputMode::HistorySearch
+            && event == ReedlineEvent::OperateAndGetNext
+        {
+            // Accept the search result before executing it
+            self.handle_history_search_event(ReedlineEvent::Enter)?;
+            self.handle_editor_event(prompt, event)
+        } else if self.input_mode == InputMode::HistorySearch {
+            self.handle_history_search_event(event)
+        } else {
+            self.handle_editor_event(prompt, event)
+        }
+    }
+
+    // Handles the events that every mode with an input line of its own treats alike, e.g. the
+    // combinators and clearing the screen, and hands the others to `handle` of the mode
+    fn handle_mode_event<F>(
+        &mut self,
+        event: ReedlineEvent,
+        handle: &mut F,
+    ) -> io::Result<EventStatus>
+    where
+        F: FnMut(&mut Self, ReedlineEvent) -> io::Result<EventStatus>,
+    {
+        match event {
+            ReedlineEvent::UntilFound(events) => {
+                for event in events {
+                    match self.handle_mode_event(event, handle)? {
+                        EventStatus::Inapplicable => {
+                            // Try again with the next event handler
+                        }
+                        success => {
+                            return Ok(success);
+                        }
+                    }
+                }
+                // Exhausting the event handlers is still considered handled
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Multiple(events) => {
+                let mut latest_signal = EventStatus::Inapplicable;
+                for event in events {
+                    match self.handle_mode_event(event, handle)? {
+                        EventStatus::Handled => {
+                            latest_signal = EventStatus::Handled;
+                        }
+                        EventStatus::Inapplicable => {
+                            // NO OP
+                        }
+                        EventStatus::Exits(signal) => {
+                            return Ok(EventStatus::Exits(signal));
+                        }
+                    }
+                }
+
+                Ok(latest_signal)
+            }
+            ReedlineEvent::ClearScreen => {
+                self.painter.clear_screen()?;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::ClearScrollback => {
+                self.painter.clear_scrollback()?;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::ClearAbovePrompt => {
+                self.painter.clear_above_prompt()?;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Mouse => Ok(EventStatus::Handled),
+            ReedlineEvent::Resize(width, height) => {
+                self.painter.handle_resize(width, height);
+                Ok(EventStatus::Inapplicable)
+            }
+            ReedlineEvent::Repaint => {
+                // A handled Event causes a repaint
+                Ok(EventStatus::Handled)
+            }
+            event => handle(self, event),
+        }
+    }
+
+    fn handle_history_search_event(&mut self, event: ReedlineEvent) -> io::Result<EventStatus> {
+        match event {
+            ReedlineEvent::UntilFound(events) => {
+                for event in events {
+                    match self.handle_history_search_event(event)? {
+                        EventStatus::Inapplicable => {
+                            // Try again with the next event handler
+                        }
+                        success => {
+                            return Ok(success);
+                        }
+                    }
+                }
+                // Exhausting the event handlers is still considered handled
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::CtrlD => {
+                if self.editor.is_empty() {
+                    self.input_mode = InputMode::Regular;
+                    self.record_signal(SignalReason::CtrlD);
+                    self.editor.reset_undo_stack();
+                    Ok(EventStatus::Exits(Signal::CtrlD))
+                } else {
+                    self.run_history_commands(&[EditCommand::Delete]);
+                    Ok(EventStatus::Handled)
+                }
+            }
+            ReedlineEvent::CtrlC => {
+                self.input_mode = InputMode::Regular;
+                self.record_signal(SignalReason::CtrlC);
+                Ok(EventStatus::Exits(Signal::CtrlC))
+            }
+            ReedlineEvent::ClearScreen => {
                 self.painter.clear_screen()?;
                 Ok(EventStatus::Handled)
             }
@@ -838,6 +3011,10 @@ impl Reedline {
                 self.painter.clear_scrollback()?;
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::ClearAbovePrompt => {
+                self.painter.clear_above_prompt()?;
+                Ok(EventStatus::Handled)
+            }
             ReedlineEvent::Enter
             | ReedlineEvent::HistoryHintComplete
             | ReedlineEvent::Submit
@@ -845,6 +3022,7 @@ impl Reedline {
                 if let Some(string) = self.history_cursor.string_at_cursor() {
                     self.editor
                         .set_buffer(string, UndoBehavior::CreateUndoPoint);
+                    self.history_recalled_id = self.history_cursor.id_at_cursor();
                 }
 
                 self.input_mode = InputMode::Regular;
@@ -853,6 +3031,7 @@ impl Reedline {
             ReedlineEvent::ExecuteHostCommand(host_command) => {
                 // TODO: Decide if we need to do something special to have a nicer painter state on the next go
                 self.executing_host_command = true;
+                self.record_signal(SignalReason::ExecuteHostCommand);
                 Ok(EventStatus::Exits(Signal::Success(host_command)))
             }
             ReedlineEvent::Edit(commands) => {
@@ -869,20 +3048,28 @@ impl Reedline {
                 Ok(EventStatus::Handled)
             }
             ReedlineEvent::PreviousHistory | ReedlineEvent::Up | ReedlineEvent::SearchHistory => {
-                self.history_cursor
-                    .back(self.history.as_ref())
-                    .expect("todo: error handling");
+                if let Err(err) = self.history_cursor.back(self.history.as_ref()) {
+                    self.report_history_error(&err);
+                }
                 Ok(EventStatus::Handled)
             }
-            ReedlineEvent::NextHistory | ReedlineEvent::Down => {
-                self.history_cursor
+            ReedlineEvent::NextHistory
+            | ReedlineEvent::Down
+            | ReedlineEvent::SearchHistoryForward
+            | ReedlineEvent::SearchBuffer => {
+                let moved = self
+                    .history_cursor
                     .forward(self.history.as_ref())
-                    .expect("todo: error handling");
-                // Hacky way to ensure that we don't fall of into failed search going forward
-                if self.history_cursor.string_at_cursor().is_none() {
-                    self.history_cursor
-                        .back(self.history.as_ref())
-                        .expect("todo: error handling");
+                    .and_then(|()| {
+                        // Hacky way to ensure that we don't fall of into failed search going forward
+                        if self.history_cursor.string_at_cursor().is_none() {
+                            self.history_cursor.back(self.history.as_ref())
+                        } else {
+                            Ok(())
+                        }
+                    });
+                if let Err(err) = moved {
+                    self.report_history_error(&err);
                 }
                 Ok(EventStatus::Handled)
             }
@@ -890,6 +3077,66 @@ impl Reedline {
                 self.input_mode = InputMode::Regular;
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::DeleteHistoryItem => match self.history_cursor.id_at_cursor() {
+                Some(id) => {
+                    if let Err(err) = self.history.delete(id) {
+                        self.command_line_message =
+                            Some(format!("Can't delete the history entry: {err}"));
+                        return Ok(EventStatus::Handled);
+                    }
+                    // Show the next older match, or a newer one if there is none
+                    let moved = self
+                        .history_cursor
+                        .back(self.history.as_ref())
+                        .and_then(|()| {
+                            if self.history_cursor.id_at_cursor() == Some(id) {
+                                self.history_cursor.forward(self.history.as_ref())
+                            } else {
+                                Ok(())
+                            }
+                        });
+                    if let Err(err) = moved {
+                        self.report_history_error(&err);
+                    }
+                    Ok(EventStatus::Handled)
+                }
+                None => Ok(EventStatus::Inapplicable),
+            },
+            ReedlineEvent::EditHistoryItem => match self.history_cursor.id_at_cursor() {
+                Some(id) => {
+                    match self.edit_history_item(id) {
+                        Ok(()) => self.input_mode = InputMode::Regular,
+                        Err(err) => {
+                            self.command_line_message =
+                                Some(format!("Can't load the history entry: {err}"));
+                        }
+                    }
+                    Ok(EventStatus::Handled)
+                }
+                None => Ok(EventStatus::Inapplicable),
+            },
+            ReedlineEvent::TogglePinHistoryItem => match self.history_cursor.id_at_cursor() {
+                Some(id) => {
+                    if let Err(err) = self.toggle_pinned(vec![id]) {
+                        self.command_line_message =
+                            Some(format!("Can't pin the history entry: {err}"));
+                    }
+                    Ok(EventStatus::Handled)
+                }
+                None => Ok(EventStatus::Inapplicable),
+            },
+            ReedlineEvent::ToggleDebugPanel => {
+                self.debug_panel = !self.debug_panel;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::ToggleDirectoryHistory => {
+                self.toggle_directory_history();
+                self.history_cursor = self.new_history_cursor(self.history_cursor.get_navigation());
+                if let Err(err) = self.history_cursor.back(self.history.as_ref()) {
+                    self.report_history_error(&err);
+                }
+                Ok(EventStatus::Handled)
+            }
             // TODO: Check if events should be handled
             ReedlineEvent::Right
             | ReedlineEvent::Left
@@ -897,6 +3144,8 @@ impl Reedline {
             | ReedlineEvent::None
             | ReedlineEvent::HistoryHintWordComplete
             | ReedlineEvent::OpenEditor
+            | ReedlineEvent::FormatBuffer
+            | ReedlineEvent::SendSelection(_)
             | ReedlineEvent::Menu(_)
             | ReedlineEvent::MenuNext
             | ReedlineEvent::MenuPrevious
@@ -905,69 +3154,355 @@ impl Reedline {
             | ReedlineEvent::MenuLeft
             | ReedlineEvent::MenuRight
             | ReedlineEvent::MenuPageNext
-            | ReedlineEvent::MenuPagePrevious => Ok(EventStatus::Inapplicable),
+            | ReedlineEvent::MenuPagePrevious
+            | ReedlineEvent::AcceptAndHold
+            | ReedlineEvent::AcceptAndDownHistory
+            | ReedlineEvent::OperateAndGetNext
+            | ReedlineEvent::ExCommand
+            | ReedlineEvent::SearchReplace
+            | ReedlineEvent::CompleteWordNext
+            | ReedlineEvent::CompleteWordPrevious
+            | ReedlineEvent::ToggleFolds
+            | ReedlineEvent::Conditional { .. }
+            | ReedlineEvent::MoveToViewport(_)
+            | ReedlineEvent::ScrollViewport(_)
+            | ReedlineEvent::RunHostCommand(_)
+            | ReedlineEvent::ReadFileIntoBuffer(_)
+            | ReedlineEvent::Custom(_)
+            | ReedlineEvent::SubmitCustom(_) => Ok(EventStatus::Inapplicable),
         }
     }
 
-    fn handle_editor_event(
-        &mut self,
-        prompt: &dyn Prompt,
-        event: ReedlineEvent,
-    ) -> io::Result<EventStatus> {
-        match event {
-            ReedlineEvent::Menu(name) => {
-                if self.active_menu().is_none() {
-                    if let Some(menu) = self.menus.iter_mut().find(|menu| menu.name() == name) {
-                        menu.menu_event(MenuEvent::Activate(self.quick_completions));
+    // Like in vim, the buffer is edited in normal mode again after the ex command line
+    fn leave_ex_command_line(&mut self) {
+        self.input_mode = InputMode::Regular;
+        self.edit_mode.leave_command_line();
+    }
 
-                        if self.quick_completions && menu.can_quick_complete() {
-                            menu.update_values(
-                                &mut self.editor,
-                                self.completer.as_mut(),
-                                self.history.as_ref(),
-                            );
+    fn handle_ex_command_event(&mut self, event: ReedlineEvent) -> io::Result<EventStatus> {
+        self.handle_mode_event(event, &mut |this, event| match event {
+            ReedlineEvent::Enter | ReedlineEvent::Submit | ReedlineEvent::SubmitOrNewline => {
+                this.leave_ex_command_line();
+                let command_line = this.ex_command_line.get_buffer().to_string();
+                if let Err(message) =
+                    run_ex_command(&command_line, &mut this.editor, &mut this.ex_commands)
+                {
+                    this.command_line_message = Some(message);
+                }
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Esc | ReedlineEvent::CtrlC => {
+                this.leave_ex_command_line();
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Edit(commands) => {
+                // Like in vim, backspacing over the `:` leaves the command line
+                if this.ex_command_line.is_empty()
+                    && commands.first() == Some(&EditCommand::Backspace)
+                {
+                    this.leave_ex_command_line();
+                } else {
+                    for command in &commands {
+                        this.ex_command_line.run_edit_command(command);
+                    }
+                }
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Left => {
+                this.ex_command_line
+                    .run_edit_command(&EditCommand::MoveLeft { select: false });
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Right => {
+                this.ex_command_line
+                    .run_edit_command(&EditCommand::MoveRight { select: false });
+                Ok(EventStatus::Handled)
+            }
+            _ => Ok(EventStatus::Inapplicable),
+        })
+    }
 
-                            if menu.get_values().len() == 1 {
-                                return self.handle_editor_event(prompt, ReedlineEvent::Enter);
+    fn handle_search_replace_event(&mut self, event: ReedlineEvent) -> io::Result<EventStatus> {
+        self.handle_mode_event(event, &mut |this, event| match event {
+            ReedlineEvent::Enter | ReedlineEvent::Submit | ReedlineEvent::SubmitOrNewline => {
+                if this.search_replace.stage() == SearchReplaceStage::Confirm
+                    || !this.search_replace.submit_input(&mut this.editor)
+                {
+                    this.leave_search_replace();
+                }
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Esc | ReedlineEvent::CtrlC => {
+                this.leave_search_replace();
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Edit(commands) => {
+                if this.search_replace.stage() == SearchReplaceStage::Confirm {
+                    for command in &commands {
+                        let answer = match command {
+                            EditCommand::InsertChar(c) => SearchReplaceAnswer::from_char(*c),
+                            _ => None,
+                        };
+                        if let Some(answer) = answer {
+                            if !this.search_replace.confirm(answer, &mut this.editor) {
+                                this.leave_search_replace();
+                                break;
                             }
                         }
-
-                        if self.partial_completions
-                            && menu.can_partially_complete(
-                                self.quick_completions,
-                                &mut self.editor,
-                                self.completer.as_mut(),
-                                self.history.as_ref(),
-                            )
-                        {
-                            return Ok(EventStatus::Handled);
-                        }
-
-                        return Ok(EventStatus::Handled);
+                    }
+                } else if this.search_replace.input().is_empty()
+                    && commands.first() == Some(&EditCommand::Backspace)
+                {
+                    this.leave_search_replace();
+                } else {
+                    for command in &commands {
+                        this.search_replace.run_input_command(command);
                     }
                 }
-                Ok(EventStatus::Inapplicable)
+                Ok(EventStatus::Handled)
             }
-            ReedlineEvent::MenuNext => match self.active_menu() {
-                None => Ok(EventStatus::Inapplicable),
-                Some(menu) => {
-                    if menu.get_values().len() == 1 && menu.can_quick_complete() {
-                        self.handle_editor_event(prompt, ReedlineEvent::Enter)
+            ReedlineEvent::Left | ReedlineEvent::Right
+                if this.search_replace.stage() != SearchReplaceStage::Confirm =>
+            {
+                this.search_replace
+                    .run_input_command(&if event == ReedlineEvent::Left {
+                        EditCommand::MoveLeft { select: false }
                     } else {
-                        menu.menu_event(MenuEvent::NextElement);
+                        EditCommand::MoveRight { select: false }
+                    });
+                Ok(EventStatus::Handled)
+            }
+            _ => Ok(EventStatus::Inapplicable),
+        })
+    }
+
+    fn handle_correction_event(
+        &mut self,
+        prompt: &dyn Prompt,
+        event: ReedlineEvent,
+    ) -> io::Result<EventStatus> {
+        self.handle_mode_event(event, &mut |this, event| match event {
+            ReedlineEvent::Edit(commands) => {
+                let answer = match commands.first() {
+                    Some(EditCommand::InsertChar(c)) => CorrectionAnswer::from_char(*c),
+                    _ => None,
+                };
+                match (answer, this.pending_correction.take()) {
+                    (Some(answer), Some(correction)) => {
+                        this.input_mode = InputMode::Regular;
+                        if answer != CorrectionAnswer::Reject {
+                            this.editor
+                                .set_buffer(correction.suggestion, UndoBehavior::CreateUndoPoint);
+                        }
+                        if answer == CorrectionAnswer::Edit {
+                            Ok(EventStatus::Handled)
+                        } else {
+                            this.submit_buffer_as_is(prompt, correction.reason)
+                        }
+                    }
+                    (None, correction) => {
+                        // Keep waiting for an answer
+                        this.pending_correction = correction;
                         Ok(EventStatus::Handled)
                     }
-                }
-            },
-            ReedlineEvent::MenuPrevious => {
-                self.active_menu()
-                    .map_or(Ok(EventStatus::Inapplicable), |menu| {
-                        menu.menu_event(MenuEvent::PreviousElement);
+                    (Some(_), None) => {
+                        this.input_mode = InputMode::Regular;
                         Ok(EventStatus::Handled)
-                    })
+                    }
+                }
             }
-            ReedlineEvent::MenuUp => {
-                self.active_menu()
+            ReedlineEvent::Esc | ReedlineEvent::CtrlC => {
+                this.pending_correction = None;
+                this.input_mode = InputMode::Regular;
+                Ok(EventStatus::Handled)
+            }
+            _ => Ok(EventStatus::Inapplicable),
+        })
+    }
+
+    fn handle_buffer_search_event(
+        &mut self,
+        prompt: &dyn Prompt,
+        event: ReedlineEvent,
+    ) -> io::Result<EventStatus> {
+        self.handle_mode_event(event, &mut |this, event| match event {
+            ReedlineEvent::Enter | ReedlineEvent::Submit | ReedlineEvent::SubmitOrNewline => {
+                this.input_mode = InputMode::Regular;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Esc | ReedlineEvent::CtrlC => {
+                this.buffer_search.restore_cursor(&mut this.editor);
+                this.input_mode = InputMode::Regular;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Edit(commands) => {
+                if this.buffer_search.input().is_empty()
+                    && commands.first() == Some(&EditCommand::Backspace)
+                {
+                    this.buffer_search.restore_cursor(&mut this.editor);
+                    this.input_mode = InputMode::Regular;
+                } else {
+                    for command in &commands {
+                        this.buffer_search
+                            .run_input_command(command, &mut this.editor);
+                    }
+                }
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::SearchBuffer
+            | ReedlineEvent::SearchHistoryForward
+            | ReedlineEvent::NextHistory
+            | ReedlineEvent::Down => {
+                this.buffer_search.next(&mut this.editor);
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::SearchHistory | ReedlineEvent::PreviousHistory | ReedlineEvent::Up => {
+                this.buffer_search.previous(&mut this.editor);
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::None => Ok(EventStatus::Handled),
+            event => {
+                // Any other action ends the search at the current match, like in Emacs
+                this.input_mode = InputMode::Regular;
+                this.handle_editor_event(prompt, event)
+            }
+        })
+    }
+
+    fn leave_search_replace(&mut self) {
+        if self.search_replace.stage() == SearchReplaceStage::Confirm {
+            self.command_line_message = Some(self.search_replace.summary());
+        }
+        self.search_replace = SearchReplace::new();
+        self.input_mode = InputMode::Regular;
+    }
+
+    // Events that would show, store or hand on the secret of `read_line_masked`
+    fn exposes_masked_input(event: &ReedlineEvent) -> bool {
+        matches!(
+            event,
+            ReedlineEvent::PreviousHistory
+                | ReedlineEvent::NextHistory
+                | ReedlineEvent::Up
+                | ReedlineEvent::Down
+                | ReedlineEvent::SearchHistory
+                | ReedlineEvent::SearchHistoryForward
+                | ReedlineEvent::HistoryHintComplete
+                | ReedlineEvent::HistoryHintWordComplete
+                | ReedlineEvent::Menu(_)
+                | ReedlineEvent::MenuNext
+                | ReedlineEvent::MenuPrevious
+                | ReedlineEvent::MenuUp
+                | ReedlineEvent::MenuDown
+                | ReedlineEvent::MenuLeft
+                | ReedlineEvent::MenuRight
+                | ReedlineEvent::MenuPageNext
+                | ReedlineEvent::MenuPagePrevious
+                | ReedlineEvent::OpenEditor
+                | ReedlineEvent::FormatBuffer
+                | ReedlineEvent::SendSelection(_)
+                | ReedlineEvent::AcceptAndHold
+                | ReedlineEvent::AcceptAndDownHistory
+                | ReedlineEvent::OperateAndGetNext
+                | ReedlineEvent::ExCommand
+                | ReedlineEvent::SearchReplace
+                | ReedlineEvent::SearchBuffer
+                | ReedlineEvent::CompleteWordNext
+                | ReedlineEvent::CompleteWordPrevious
+                | ReedlineEvent::DeleteHistoryItem
+                | ReedlineEvent::EditHistoryItem
+                | ReedlineEvent::TogglePinHistoryItem
+                | ReedlineEvent::ToggleDirectoryHistory
+                | ReedlineEvent::ToggleDebugPanel
+                | ReedlineEvent::RunHostCommand(_)
+        )
+    }
+
+    fn handle_editor_event(
+        &mut self,
+        prompt: &dyn Prompt,
+        event: ReedlineEvent,
+    ) -> io::Result<EventStatus> {
+        if let ReedlineEvent::Resize(width, height) = event {
+            self.painter.handle_resize(width, height);
+        }
+        if self.refusal_reason(&event).is_some() {
+            return Ok(EventStatus::Inapplicable);
+        }
+
+        match event {
+            ReedlineEvent::Menu(name) => {
+                if self.active_menu().is_none() {
+                    if let Some(menu) = self.menus.iter_mut().find(|menu| menu.name() == name) {
+                        menu.menu_event(MenuEvent::Activate(self.quick_completions));
+
+                        if self.quick_completions && menu.can_quick_complete() {
+                            menu.update_values(
+                                &mut self.editor,
+                                &mut self.completion_cache.wrap(&mut ContextCompleter::new(
+                                    self.completer.as_mut(),
+                                    self.context.as_deref(),
+                                )),
+                                &CompletionSources {
+                                    history: self.history.as_ref(),
+                                    usage: &self.completion_usage,
+                                    context: self.context.as_deref(),
+                                    sanitizer: self.sanitizer,
+                                },
+                            );
+
+                            if menu.get_values().len() == 1 {
+                                return self.handle_editor_event(prompt, ReedlineEvent::Enter);
+                            }
+                        }
+
+                        if self.partial_completions
+                            && menu.can_partially_complete(
+                                self.quick_completions,
+                                &mut self.editor,
+                                &mut self.completion_cache.wrap(&mut ContextCompleter::new(
+                                    self.completer.as_mut(),
+                                    self.context.as_deref(),
+                                )),
+                                &CompletionSources {
+                                    history: self.history.as_ref(),
+                                    usage: &self.completion_usage,
+                                    context: self.context.as_deref(),
+                                    sanitizer: self.sanitizer,
+                                },
+                            )
+                        {
+                            self.line_usage.used_completion = true;
+                            return Ok(EventStatus::Handled);
+                        }
+
+                        return Ok(EventStatus::Handled);
+                    }
+                }
+                Ok(EventStatus::Inapplicable)
+            }
+            ReedlineEvent::MenuNext => match self.active_menu() {
+                None => Ok(EventStatus::Inapplicable),
+                Some(menu) => {
+                    if menu.get_values().len() == 1 && menu.can_quick_complete() {
+                        self.handle_editor_event(prompt, ReedlineEvent::Enter)
+                    } else if menu.settings().inserts_and_filters() {
+                        Ok(self.insert_and_filter())
+                    } else {
+                        menu.menu_event(MenuEvent::NextElement);
+                        Ok(EventStatus::Handled)
+                    }
+                }
+            },
+            ReedlineEvent::MenuPrevious => {
+                self.active_menu()
+                    .map_or(Ok(EventStatus::Inapplicable), |menu| {
+                        menu.menu_event(MenuEvent::PreviousElement);
+                        Ok(EventStatus::Handled)
+                    })
+            }
+            ReedlineEvent::MenuUp => {
+                self.active_menu()
                     .map_or(Ok(EventStatus::Inapplicable), |menu| {
                         menu.menu_event(MenuEvent::MoveUp);
                         Ok(EventStatus::Handled)
@@ -1008,33 +3543,9 @@ impl Reedline {
                         Ok(EventStatus::Handled)
                     })
             }
-            ReedlineEvent::HistoryHintComplete => {
-                if let Some(hinter) = self.hinter.as_mut() {
-                    let current_hint = hinter.complete_hint();
-                    if self.hints_active()
-                        && self.editor.is_cursor_at_buffer_end()
-                        && !current_hint.is_empty()
-                        && self.active_menu().is_none()
-                    {
-                        self.run_edit_commands(&[EditCommand::InsertString(current_hint)]);
-                        return Ok(EventStatus::Handled);
-                    }
-                }
-                Ok(EventStatus::Inapplicable)
-            }
+            ReedlineEvent::HistoryHintComplete => Ok(self.complete_hint(self.current_hint())),
             ReedlineEvent::HistoryHintWordComplete => {
-                if let Some(hinter) = self.hinter.as_mut() {
-                    let current_hint_part = hinter.next_hint_token();
-                    if self.hints_active()
-                        && self.editor.is_cursor_at_buffer_end()
-                        && !current_hint_part.is_empty()
-                        && self.active_menu().is_none()
-                    {
-                        self.run_edit_commands(&[EditCommand::InsertString(current_hint_part)]);
-                        return Ok(EventStatus::Handled);
-                    }
-                }
-                Ok(EventStatus::Inapplicable)
+                Ok(self.complete_hint(self.current_hint_token()))
             }
             ReedlineEvent::Esc => {
                 self.deactivate_menus();
@@ -1042,6 +3553,7 @@ impl Reedline {
             }
             ReedlineEvent::CtrlD => {
                 if self.editor.is_empty() {
+                    self.record_signal(SignalReason::CtrlD);
                     self.editor.reset_undo_stack();
                     Ok(EventStatus::Exits(Signal::CtrlD))
                 } else {
@@ -1050,7 +3562,10 @@ impl Reedline {
                 }
             }
             ReedlineEvent::CtrlC => {
+                self.record_signal(SignalReason::CtrlC);
                 self.deactivate_menus();
+                self.history_recalled_id = None;
+                self.editor.set_read_only([]);
                 self.run_edit_commands(&[EditCommand::Clear]);
                 self.editor.reset_undo_stack();
                 Ok(EventStatus::Exits(Signal::CtrlC))
@@ -1065,15 +3580,48 @@ impl Reedline {
                 self.painter.clear_scrollback()?;
                 Ok(EventStatus::Handled)
             }
-            ReedlineEvent::Enter | ReedlineEvent::Submit | ReedlineEvent::SubmitOrNewline
+            ReedlineEvent::ClearAbovePrompt => {
+                self.painter.clear_above_prompt()?;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::Enter
+            | ReedlineEvent::Submit
+            | ReedlineEvent::SubmitOrNewline
+            | ReedlineEvent::AcceptAndHold
+            | ReedlineEvent::AcceptAndDownHistory
+            | ReedlineEvent::OperateAndGetNext
+            | ReedlineEvent::SubmitCustom(_)
                 if self.menus.iter().any(|menu| menu.is_active()) =>
             {
                 for menu in self.menus.iter_mut() {
                     if menu.is_active() {
+                        let selected = menu.selected_value();
+                        if let Some(suggestion) = &selected {
+                            let context = self
+                                .editor
+                                .get_buffer()
+                                .get(suggestion.span.start..self.editor.insertion_point())
+                                .unwrap_or_default();
+                            // Learning is best effort, a store that can't be written is
+                            // still used in memory
+                            let _ = self.completion_usage.record(context, &suggestion.value);
+                            self.line_usage.used_completion = true;
+                        }
                         menu.replace_in_buffer(&mut self.editor);
                         menu.menu_event(MenuEvent::Deactivate);
 
-                        return Ok(EventStatus::Handled);
+                        let next = selected
+                            .and_then(|suggestion| menu.settings().after_insert(&suggestion));
+                        return match next {
+                            Some(event) => {
+                                let event = self.resolve_conditions(event);
+                                self.accepting_from_menu = true;
+                                let status = self.handle_editor_event(prompt, event);
+                                self.accepting_from_menu = false;
+                                status
+                            }
+                            None => Ok(EventStatus::Handled),
+                        };
                     }
                 }
                 unreachable!()
@@ -1086,9 +3634,11 @@ impl Reedline {
 
                 let buffer = self.editor.get_buffer().to_string();
                 match self.validator.as_mut().map(|v| v.validate(&buffer)) {
-                    None | Some(ValidationResult::Complete) => Ok(self.submit_buffer(prompt)?),
+                    None | Some(ValidationResult::Complete) => {
+                        Ok(self.submit_buffer(prompt, SignalReason::Enter)?)
+                    }
                     Some(ValidationResult::Incomplete) => {
-                        self.run_edit_commands(&[EditCommand::InsertNewline]);
+                        self.insert_continuation_newline();
 
                         Ok(EventStatus::Handled)
                     }
@@ -1099,7 +3649,54 @@ impl Reedline {
                 if let Some(event) = self.parse_bang_command() {
                     return self.handle_editor_event(prompt, event);
                 }
-                Ok(self.submit_buffer(prompt)?)
+                Ok(self.submit_buffer(prompt, SignalReason::Submit)?)
+            }
+            ReedlineEvent::SubmitCustom(name) => {
+                Ok(self.submit_buffer(prompt, SignalReason::Custom(name))?)
+            }
+            ReedlineEvent::AcceptAndHold => {
+                #[cfg(feature = "bashisms")]
+                if let Some(event) = self.parse_bang_command() {
+                    return self.handle_editor_event(prompt, event);
+                }
+                let buffer = self.editor.get_buffer().to_string();
+                let status = self.submit_buffer(prompt, SignalReason::AcceptAndHold)?;
+                self.editor
+                    .set_buffer(buffer, UndoBehavior::CreateUndoPoint);
+                Ok(status)
+            }
+            ReedlineEvent::AcceptAndDownHistory => {
+                #[cfg(feature = "bashisms")]
+                if let Some(event) = self.parse_bang_command() {
+                    return self.handle_editor_event(prompt, event);
+                }
+                let recalled_id = if self.input_mode == InputMode::HistoryTraversal
+                    && !self.history_cursor_on_excluded
+                {
+                    self.history_cursor.id_at_cursor()
+                } else {
+                    None
+                };
+                let next = recalled_id.and_then(|id| self.history_entry_after(id));
+                let status = self.submit_buffer(prompt, SignalReason::AcceptAndDownHistory)?;
+                if let Some(next) = next {
+                    self.load_history_entry(next);
+                }
+                Ok(status)
+            }
+            ReedlineEvent::OperateAndGetNext => {
+                #[cfg(feature = "bashisms")]
+                if let Some(event) = self.parse_bang_command() {
+                    return self.handle_editor_event(prompt, event);
+                }
+                let next = self
+                    .history_recalled_id
+                    .and_then(|id| self.history_entry_after(id));
+                let status = self.submit_buffer(prompt, SignalReason::OperateAndGetNext)?;
+                if let Some(next) = next {
+                    self.load_history_entry(next);
+                }
+                Ok(status)
             }
             ReedlineEvent::SubmitOrNewline => {
                 #[cfg(feature = "bashisms")]
@@ -1113,9 +3710,11 @@ impl Reedline {
                     return Ok(EventStatus::Handled);
                 }
                 match self.validator.as_mut().map(|v| v.validate(&buffer)) {
-                    None | Some(ValidationResult::Complete) => Ok(self.submit_buffer(prompt)?),
+                    None | Some(ValidationResult::Complete) => {
+                        Ok(self.submit_buffer(prompt, SignalReason::SubmitOrNewline)?)
+                    }
                     Some(ValidationResult::Incomplete) => {
-                        self.run_edit_commands(&[EditCommand::InsertNewline]);
+                        self.insert_continuation_newline();
 
                         Ok(EventStatus::Handled)
                     }
@@ -1124,9 +3723,20 @@ impl Reedline {
             ReedlineEvent::ExecuteHostCommand(host_command) => {
                 // TODO: Decide if we need to do something special to have a nicer painter state on the next go
                 self.executing_host_command = true;
+                self.record_signal(SignalReason::ExecuteHostCommand);
                 Ok(EventStatus::Exits(Signal::Success(host_command)))
             }
             ReedlineEvent::Edit(commands) => {
+                if commands.first() == Some(&EditCommand::InsertChar(' ')) {
+                    self.expand_abbreviation();
+                }
+                if let Some(paste_folds) = &mut self.paste_folds {
+                    for command in &commands {
+                        if let EditCommand::InsertString(text) = command {
+                            paste_folds.add(text);
+                        }
+                    }
+                }
                 self.run_edit_commands(&commands);
                 if let Some(menu) = self.menus.iter_mut().find(|men| men.is_active()) {
                     if self.quick_completions && menu.can_quick_complete() {
@@ -1140,8 +3750,16 @@ impl Reedline {
                                 menu.menu_event(MenuEvent::Edit(self.quick_completions));
                                 menu.update_values(
                                     &mut self.editor,
-                                    self.completer.as_mut(),
-                                    self.history.as_ref(),
+                                    &mut self.completion_cache.wrap(&mut ContextCompleter::new(
+                                        self.completer.as_mut(),
+                                        self.context.as_deref(),
+                                    )),
+                                    &CompletionSources {
+                                        history: self.history.as_ref(),
+                                        usage: &self.completion_usage,
+                                        context: self.context.as_deref(),
+                                        sanitizer: self.sanitizer,
+                                    },
                                 );
                                 if let Some(&EditCommand::Complete) = commands.first() {
                                     if menu.get_values().len() == 1 {
@@ -1151,8 +3769,18 @@ impl Reedline {
                                         && menu.can_partially_complete(
                                             self.quick_completions,
                                             &mut self.editor,
-                                            self.completer.as_mut(),
-                                            self.history.as_ref(),
+                                            &mut self.completion_cache.wrap(
+                                                &mut ContextCompleter::new(
+                                                    self.completer.as_mut(),
+                                                    self.context.as_deref(),
+                                                ),
+                                            ),
+                                            &CompletionSources {
+                                                history: self.history.as_ref(),
+                                                usage: &self.completion_usage,
+                                                context: self.context.as_deref(),
+                                                sanitizer: self.sanitizer,
+                                            },
                                         )
                                     {
                                         return Ok(EventStatus::Handled);
@@ -1170,9 +3798,26 @@ impl Reedline {
                 Ok(EventStatus::Handled)
             }
             ReedlineEvent::OpenEditor => self.open_editor().map(|_| EventStatus::Handled),
-            ReedlineEvent::Resize(width, height) => {
-                self.painter.handle_resize(width, height);
-                Ok(EventStatus::Inapplicable)
+            ReedlineEvent::FormatBuffer => Ok(self.format_buffer()),
+            ReedlineEvent::SendSelection(action) => Ok(self.send_selection(&action)),
+            ReedlineEvent::RunHostCommand(host_command) => self
+                .run_host_command(&host_command)
+                .map(|_| EventStatus::Handled),
+            ReedlineEvent::Custom(name) => match self.custom_event_handler.as_mut() {
+                Some(handler) => {
+                    handler.handle(&name, &mut self.editor);
+                    Ok(EventStatus::Handled)
+                }
+                None => Ok(EventStatus::Inapplicable),
+            },
+            ReedlineEvent::ReadFileIntoBuffer(path) => {
+                match read_file_prefix(&path, self.max_insert_size) {
+                    Ok((text, truncated)) => self.insert_external_text(&text, truncated),
+                    Err(err) => {
+                        self.command_line_message = Some(format!("Can't read \"{path}\": {err}"));
+                    }
+                }
+                Ok(EventStatus::Handled)
             }
             ReedlineEvent::Repaint => {
                 // A handled Event causes a repaint
@@ -1202,10 +3847,60 @@ impl Reedline {
                 self.run_edit_commands(&[EditCommand::MoveRight { select: false }]);
                 Ok(EventStatus::Handled)
             }
-            ReedlineEvent::SearchHistory => {
+            ReedlineEvent::SearchHistory | ReedlineEvent::SearchHistoryForward => {
                 self.enter_history_search();
                 Ok(EventStatus::Handled)
             }
+            ReedlineEvent::DeleteHistoryItem => Ok(self.delete_history_menu_item()),
+            ReedlineEvent::EditHistoryItem => Ok(self.edit_history_menu_item()),
+            ReedlineEvent::TogglePinHistoryItem => Ok(self.toggle_pin_history_menu_item()),
+            ReedlineEvent::ToggleDirectoryHistory => {
+                self.toggle_directory_history();
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::ToggleDebugPanel => {
+                self.debug_panel = !self.debug_panel;
+                Ok(EventStatus::Handled)
+            }
+            // Chosen by `resolve_conditions` before the event is handled
+            ReedlineEvent::Conditional { .. } => Ok(EventStatus::Inapplicable),
+            ReedlineEvent::ToggleFolds => {
+                if self.paste_folds.as_mut().map_or(false, PasteFolds::toggle) {
+                    Ok(EventStatus::Handled)
+                } else {
+                    Ok(EventStatus::Inapplicable)
+                }
+            }
+            ReedlineEvent::SearchReplace => {
+                self.search_replace = SearchReplace::new();
+                self.input_mode = InputMode::SearchReplace;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::SearchBuffer => {
+                self.buffer_search = BufferSearch::new(self.editor.insertion_point());
+                self.input_mode = InputMode::BufferSearch;
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::MoveToViewport(line) => {
+                self.move_to_viewport(line);
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::ScrollViewport(line) => {
+                self.painter.scroll_view(line);
+                Ok(EventStatus::Handled)
+            }
+            ReedlineEvent::CompleteWordNext => Ok(self.expand_word(WordExpansion::next)),
+            ReedlineEvent::CompleteWordPrevious => Ok(self.expand_word(WordExpansion::previous)),
+            ReedlineEvent::ExCommand => {
+                if self.use_ex_commands {
+                    self.ex_command_line = Editor::default();
+                    self.input_mode = InputMode::ExCommand;
+                    Ok(EventStatus::Handled)
+                } else {
+                    self.edit_mode.leave_command_line();
+                    Ok(EventStatus::Inapplicable)
+                }
+            }
             ReedlineEvent::Multiple(events) => {
                 let mut latest_signal = EventStatus::Inapplicable;
                 for event in events {
@@ -1229,7 +3924,16 @@ impl Reedline {
             }
             ReedlineEvent::UntilFound(events) => {
                 for event in events {
-                    match self.handle_editor_event(prompt, event)? {
+                    #[cfg(feature = "tracing")]
+                    let name = event.to_string();
+                    let status = self.handle_editor_event(prompt, event)?;
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        event = name,
+                        handled = !matches!(status, EventStatus::Inapplicable),
+                        "until found branch"
+                    );
+                    match status {
                         EventStatus::Inapplicable => {
                             // Try again with the next event handler
                         }
@@ -1241,7 +3945,10 @@ impl Reedline {
                 // Exhausting the event handlers is still considered handled
                 Ok(EventStatus::Inapplicable)
             }
-            ReedlineEvent::None | ReedlineEvent::Mouse => Ok(EventStatus::Inapplicable),
+            // Turned down by `refusal_reason`
+            ReedlineEvent::None | ReedlineEvent::Mouse | ReedlineEvent::Resize(..) => {
+                Ok(EventStatus::Inapplicable)
+            }
         }
     }
 
@@ -1249,6 +3956,115 @@ impl Reedline {
         self.menus.iter_mut().find(|menu| menu.is_active())
     }
 
+    // The command line selected in the active history menu
+    fn selected_history_menu_value(&self) -> Option<String> {
+        self.menus
+            .iter()
+            .find(|menu| menu.is_active() && matches!(menu, ReedlineMenu::HistoryMenu(_)))?
+            .selected_value()
+            .map(|suggestion| suggestion.value)
+    }
+
+    // The entries with `command_line`, most recent first
+    fn history_items_with(&self, command_line: String) -> crate::Result<Vec<HistoryItemId>> {
+        let mut query = SearchQuery::everything(SearchDirection::Backward, None);
+        query.filter.command_line = Some(CommandLineSearch::Exact(command_line));
+        Ok(self
+            .history
+            .search(query)?
+            .into_iter()
+            .filter_map(|item| item.id)
+            .collect())
+    }
+
+    fn delete_history_menu_item(&mut self) -> EventStatus {
+        let command_line = match self.selected_history_menu_value() {
+            Some(command_line) => command_line,
+            None => return EventStatus::Inapplicable,
+        };
+        // The menu shows every command line once
+        let deleted = self
+            .history_items_with(command_line)
+            .and_then(|ids| ids.into_iter().try_for_each(|id| self.history.delete(id)));
+        if let Err(err) = deleted {
+            self.command_line_message = Some(format!("Can't delete the history entry: {err}"));
+        }
+        if let Some(menu) = self.active_menu() {
+            menu.menu_event(MenuEvent::Edit(false));
+        }
+        EventStatus::Handled
+    }
+
+    fn edit_history_menu_item(&mut self) -> EventStatus {
+        let command_line = match self.selected_history_menu_value() {
+            Some(command_line) => command_line,
+            None => return EventStatus::Inapplicable,
+        };
+        let edited = self
+            .history_items_with(command_line)
+            .and_then(|ids| match ids.first() {
+                Some(id) => self.edit_history_item(*id),
+                None => Ok(()),
+            });
+        if let Err(err) = edited {
+            self.command_line_message = Some(format!("Can't load the history entry: {err}"));
+        }
+        self.deactivate_menus();
+        EventStatus::Handled
+    }
+
+    fn toggle_pin_history_menu_item(&mut self) -> EventStatus {
+        let command_line = match self.selected_history_menu_value() {
+            Some(command_line) => command_line,
+            None => return EventStatus::Inapplicable,
+        };
+        let toggled = self
+            .history_items_with(command_line)
+            .and_then(|ids| self.toggle_pinned(ids));
+        if let Err(err) = toggled {
+            self.command_line_message = Some(format!("Can't pin the history entry: {err}"));
+        }
+        // Pinned entries move to the top of the menu
+        if let Some(menu) = self.active_menu() {
+            menu.menu_event(MenuEvent::Edit(false));
+        }
+        EventStatus::Handled
+    }
+
+    /// Unpins all of `ids` if one of them is pinned, pins the first one otherwise
+    fn toggle_pinned(&mut self, ids: Vec<HistoryItemId>) -> crate::Result<()> {
+        let mut pinned = Vec::new();
+        for id in &ids {
+            if self.history.is_pinned(*id)? {
+                pinned.push(*id);
+            }
+        }
+        match (pinned.is_empty(), ids.first()) {
+            (true, Some(id)) => self.history.set_pinned(*id, true),
+            (true, None) => Ok(()),
+            (false, _) => pinned
+                .into_iter()
+                .try_for_each(|id| self.history.set_pinned(id, false)),
+        }
+    }
+
+    // Inserts the common prefix of the values of the active menu, or else its selected value,
+    // and lets the menu filter its values again for the longer token
+    fn insert_and_filter(&mut self) -> EventStatus {
+        let menu = match self.menus.iter_mut().find(|menu| menu.is_active()) {
+            Some(menu) => menu,
+            None => return EventStatus::Inapplicable,
+        };
+        if common_string_extends_input(menu.get_values(), &self.editor) {
+            can_partially_complete(menu.get_values(), &mut self.editor);
+        } else {
+            menu.replace_in_buffer(&mut self.editor);
+        }
+        menu.menu_event(MenuEvent::Edit(false));
+        self.line_usage.used_completion = true;
+        EventStatus::Handled
+    }
+
     fn deactivate_menus(&mut self) {
         self.menus
             .iter_mut()
@@ -1256,15 +4072,16 @@ impl Reedline {
     }
 
     fn previous_history(&mut self) {
+        if !self.read_line_options.history {
+            return;
+        }
         if self.history_cursor_on_excluded {
             self.history_cursor_on_excluded = false;
         }
         if self.input_mode != InputMode::HistoryTraversal {
             self.input_mode = InputMode::HistoryTraversal;
-            self.history_cursor = HistoryCursor::new(
-                self.get_history_navigation_based_on_line_buffer(),
-                self.get_history_session_id(),
-            );
+            self.history_cursor =
+                self.new_history_cursor(self.get_history_navigation_based_on_line_buffer());
 
             if self.history_excluded_item.is_some() {
                 self.history_cursor_on_excluded = true;
@@ -1272,35 +4089,38 @@ impl Reedline {
         }
 
         if !self.history_cursor_on_excluded {
-            self.history_cursor
-                .back(self.history.as_ref())
-                .expect("todo: error handling");
+            if let Err(err) = self.history_cursor.back(self.history.as_ref()) {
+                self.report_history_error(&err);
+            }
         }
         self.update_buffer_from_history();
-        self.editor.move_to_start(false);
         self.editor
-            .update_undo_state(UndoBehavior::HistoryNavigation);
-        self.editor.move_to_line_end(false);
+            .record_change(UndoBehavior::HistoryNavigation, |editor| {
+                editor.move_to_start(false);
+            });
         self.editor
-            .update_undo_state(UndoBehavior::HistoryNavigation);
+            .record_change(UndoBehavior::HistoryNavigation, |editor| {
+                editor.move_to_line_end(false);
+            });
     }
 
     fn next_history(&mut self) {
+        if !self.read_line_options.history {
+            return;
+        }
         if self.input_mode != InputMode::HistoryTraversal {
             self.input_mode = InputMode::HistoryTraversal;
-            self.history_cursor = HistoryCursor::new(
-                self.get_history_navigation_based_on_line_buffer(),
-                self.get_history_session_id(),
-            );
+            self.history_cursor =
+                self.new_history_cursor(self.get_history_navigation_based_on_line_buffer());
         }
 
         if self.history_cursor_on_excluded {
             self.history_cursor_on_excluded = false;
         } else {
             let cursor_was_on_item = self.history_cursor.string_at_cursor().is_some();
-            self.history_cursor
-                .forward(self.history.as_ref())
-                .expect("todo: error handling");
+            if let Err(err) = self.history_cursor.forward(self.history.as_ref()) {
+                self.report_history_error(&err);
+            }
 
             if cursor_was_on_item
                 && self.history_cursor.string_at_cursor().is_none()
@@ -1314,9 +4134,38 @@ impl Reedline {
             self.input_mode = InputMode::Regular;
         }
         self.update_buffer_from_history();
-        self.editor.move_to_end(false);
         self.editor
-            .update_undo_state(UndoBehavior::HistoryNavigation)
+            .record_change(UndoBehavior::HistoryNavigation, |editor| {
+                editor.move_to_end(false);
+            });
+    }
+
+    fn new_history_cursor(&self, query: HistoryNavigationQuery) -> HistoryCursor {
+        let cursor = HistoryCursor::new(query, self.get_history_session_id())
+            .with_typo_tolerance(self.history_search_typo_tolerance);
+        match self.history_directory() {
+            Some(cwd) => cursor.in_directory(self.directory_history_scope, cwd),
+            None => cursor,
+        }
+    }
+
+    /// The working directory the history is restricted to, if any
+    fn history_directory(&self) -> Option<String> {
+        if !self.directory_history || !self.history.supports_directories() {
+            return None;
+        }
+        std::env::current_dir()
+            .ok()
+            .map(|cwd| cwd.to_string_lossy().to_string())
+    }
+
+    fn toggle_directory_history(&mut self) {
+        if !self.directory_history && !self.history.supports_directories() {
+            self.command_line_message =
+                Some("The history doesn't store the directories of its entries".to_string());
+            return;
+        }
+        self.directory_history = !self.directory_history;
     }
 
     /// Enable the search and navigation through the history from the line buffer prompt
@@ -1340,14 +4189,115 @@ impl Reedline {
         }
     }
 
+    /// The history entry following `id`
+    ///
+    /// Looked up before the line is submitted, so the entry saved for it isn't found. A
+    /// failing search is reported like the other history errors and finds no entry.
+    fn history_entry_after(&mut self, id: HistoryItemId) -> Option<HistoryItem> {
+        let found = self.history.search(SearchQuery {
+            direction: SearchDirection::Forward,
+            start_time: None,
+            end_time: None,
+            start_id: Some(id),
+            end_id: None,
+            limit: Some(1),
+            filter: SearchFilter::anything(self.get_history_session_id()),
+        });
+        match found {
+            Ok(mut entries) => entries.pop(),
+            Err(err) => {
+                self.report_history_error(&err);
+                None
+            }
+        }
+    }
+
+    /// Load the history `entry` into the buffer and continue the history traversal from
+    /// there
+    fn load_history_entry(&mut self, entry: HistoryItem) {
+        self.editor
+            .set_buffer(entry.command_line.clone(), UndoBehavior::CreateUndoPoint);
+        self.history_recalled_id = entry.id;
+        self.history_cursor = HistoryCursor::new_at(
+            HistoryNavigationQuery::Normal(LineBuffer::default()),
+            self.get_history_session_id(),
+            entry,
+        );
+        self.input_mode = InputMode::HistoryTraversal;
+    }
+
+    // Moves the cursor to a line of the buffer on the screen, which then stays in place
+    fn move_to_viewport(&mut self, line: ViewportLine) {
+        let buffer = self.editor.get_buffer();
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(buffer.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        let visible = self.painter.visible_lines().unwrap_or(0..line_starts.len());
+        let last = visible.end.min(line_starts.len()).saturating_sub(1);
+        let first = visible.start.min(last);
+        let target = match line {
+            ViewportLine::Top => first,
+            ViewportLine::Middle => first + (last - first) / 2,
+            ViewportLine::Bottom => last,
+        };
+        self.painter.hold_view();
+        self.run_edit_commands(&[EditCommand::MoveToPosition {
+            position: line_starts[target],
+            select: false,
+        }]);
+    }
+
+    // Whether the last word expansion goes on at the cursor
+    fn word_expansion_continues(&self) -> bool {
+        self.word_expansion
+            .as_ref()
+            .map_or(false, |expansion| expansion.is_continued_in(&self.editor))
+    }
+
+    fn expand_word(&mut self, step: fn(&mut WordExpansion, &mut Editor)) -> EventStatus {
+        if !self.word_expansion_continues() {
+            self.word_expansion = self.new_word_expansion();
+        }
+        match self.word_expansion.as_mut() {
+            Some(expansion) if !expansion.candidates().is_empty() => {
+                step(expansion, &mut self.editor);
+                EventStatus::Handled
+            }
+            _ => EventStatus::Inapplicable,
+        }
+    }
+
+    fn new_word_expansion(&self) -> Option<WordExpansion> {
+        let mut expansion =
+            WordExpansion::new(self.editor.get_buffer(), self.editor.insertion_point())?;
+        if self.read_line_options.history {
+            let query = SearchQuery {
+                limit: Some(WORD_EXPANSION_HISTORY),
+                filter: SearchFilter::from_text_search(
+                    CommandLineSearch::Substring(expansion.prefix().to_string()),
+                    None,
+                ),
+                ..SearchQuery::everything(SearchDirection::Backward, None)
+            };
+            // Expanding from the buffer alone if the history can't be searched
+            if let Ok(items) = self.history.search(query) {
+                expansion.add_lines(items.iter().map(|item| item.command_line.as_str()));
+            }
+        }
+        Some(expansion)
+    }
+
     /// Switch into reverse history search mode
     ///
     /// This mode uses a separate prompt and handles keybindings slightly differently!
     fn enter_history_search(&mut self) {
-        self.history_cursor = HistoryCursor::new(
-            HistoryNavigationQuery::SubstringSearch("".to_string()),
-            self.get_history_session_id(),
-        );
+        if !self.read_line_options.history {
+            return;
+        }
+        self.history_cursor =
+            self.new_history_cursor(HistoryNavigationQuery::SubstringSearch("".to_string()));
+        // The entries may have changed since the last search
+        self.history_search_matches = None;
         self.input_mode = InputMode::HistorySearch;
     }
 
@@ -1359,35 +4309,21 @@ impl Reedline {
             match command {
                 EditCommand::InsertChar(c) => {
                     let navigation = self.history_cursor.get_navigation();
-                    if let HistoryNavigationQuery::SubstringSearch(mut substring) = navigation {
-                        substring.push(*c);
-                        self.history_cursor = HistoryCursor::new(
-                            HistoryNavigationQuery::SubstringSearch(substring),
-                            self.get_history_session_id(),
-                        );
-                    } else {
-                        self.history_cursor = HistoryCursor::new(
-                            HistoryNavigationQuery::SubstringSearch(String::from(*c)),
-                            self.get_history_session_id(),
-                        );
-                    }
-                    self.history_cursor
-                        .back(self.history.as_mut())
-                        .expect("todo: error handling");
+                    let substring = match navigation {
+                        HistoryNavigationQuery::SubstringSearch(mut substring) => {
+                            substring.push(*c);
+                            substring
+                        }
+                        _ => String::from(*c),
+                    };
+                    self.search_history_for(substring);
                 }
                 EditCommand::Backspace => {
                     let navigation = self.history_cursor.get_navigation();
 
                     if let HistoryNavigationQuery::SubstringSearch(substring) = navigation {
                         let new_substring = text_manipulation::remove_last_grapheme(&substring);
-
-                        self.history_cursor = HistoryCursor::new(
-                            HistoryNavigationQuery::SubstringSearch(new_substring.to_string()),
-                            self.get_history_session_id(),
-                        );
-                        self.history_cursor
-                            .back(self.history.as_mut())
-                            .expect("todo: error handling");
+                        self.search_history_for(new_substring.to_string());
                     }
                 }
                 _ => {
@@ -1397,11 +4333,27 @@ impl Reedline {
         }
     }
 
-    /// Set the buffer contents for history traversal/search in the standard prompt
+    // Moves the history search to the latest match of `substring`, or keeps the previous
+    // match if the history can't be searched
+    fn search_history_for(&mut self, substring: String) {
+        let mut cursor =
+            self.new_history_cursor(HistoryNavigationQuery::SubstringSearch(substring));
+        match cursor.back(self.history.as_ref()) {
+            Ok(()) => self.history_cursor = cursor,
+            Err(err) => self.report_history_error(&err),
+        }
+    }
+
+    /// Set the buffer contents for history traversal/search in the standard prompt
     ///
     /// When using the up/down traversal or fish/zsh style prefix search update the main line buffer accordingly.
     /// Not used for the separate modal reverse search!
     fn update_buffer_from_history(&mut self) {
+        self.history_recalled_id = if self.history_cursor_on_excluded {
+            None
+        } else {
+            self.history_cursor.id_at_cursor()
+        };
         match self.history_cursor.get_navigation() {
             _ if self.history_cursor_on_excluded => self.editor.set_buffer(
                 self.history_excluded_item
@@ -1436,6 +4388,39 @@ impl Reedline {
 
     /// Executes [`EditCommand`] actions by modifying the internal state appropriately. Does not output itself.
     pub fn run_edit_commands(&mut self, commands: &[EditCommand]) {
+        self.leave_history_traversal();
+
+        // Run the commands over the edit buffer
+        for command in commands {
+            self.editor.run_edit_command(command);
+        }
+    }
+
+    /// Insert a newline for incomplete input, indented as determined by the [`Indenter`]
+    fn insert_continuation_newline(&mut self) {
+        let indent = self.indenter.as_ref().map(|indenter| {
+            indenter.indent(self.editor.get_buffer(), self.editor.insertion_point())
+        });
+        match indent {
+            Some(indent) if !indent.is_empty() => {
+                self.leave_history_traversal();
+                self.editor.insert_newline_with_indent(&indent);
+            }
+            _ => self.run_edit_commands(&[EditCommand::InsertNewline]),
+        }
+    }
+
+    fn expand_abbreviation(&mut self) {
+        if self.abbreviations.is_none() || self.input_mask.is_some() {
+            return;
+        }
+        self.leave_history_traversal();
+        if let Some(abbreviations) = &self.abbreviations {
+            abbreviations.expand(&mut self.editor);
+        }
+    }
+
+    fn leave_history_traversal(&mut self) {
         if self.input_mode == InputMode::HistoryTraversal {
             if matches!(
                 self.history_cursor.get_navigation(),
@@ -1448,11 +4433,6 @@ impl Reedline {
             }
             self.input_mode = InputMode::Regular;
         }
-
-        // Run the commands over the edit buffer
-        for command in commands {
-            self.editor.run_edit_command(command);
-        }
     }
 
     fn up_command(&mut self) {
@@ -1477,22 +4457,140 @@ impl Reedline {
 
     /// Checks if hints should be displayed and are able to be completed
     fn hints_active(&self) -> bool {
-        !self.hide_hints && matches!(self.input_mode, InputMode::Regular)
+        !self.hide_hints
+            && matches!(self.input_mode, InputMode::Regular)
+            && self.input_mask.is_none()
+            && self.read_line_options.hints
+    }
+
+    // Whether `hint`, or a part of it, can be inserted at the cursor now
+    fn hint_applies(&self, hint: &str) -> bool {
+        self.hints_active()
+            && self.editor.is_cursor_at_buffer_end()
+            && !hint.is_empty()
+            && !self.menus.iter().any(|menu| menu.is_active())
+    }
+
+    // Insert `hint`, or a part of it, if it applies
+    fn complete_hint(&mut self, hint: String) -> EventStatus {
+        if !self.hint_applies(&hint) {
+            return EventStatus::Inapplicable;
+        }
+        self.run_edit_commands(&[EditCommand::InsertString(hint)]);
+        self.line_usage.used_hint = true;
+        EventStatus::Handled
+    }
+
+    fn current_hint(&self) -> String {
+        self.hinter
+            .as_ref()
+            .map(|hinter| hinter.complete_hint())
+            .unwrap_or_default()
+    }
+
+    fn current_hint_token(&self) -> String {
+        self.hinter
+            .as_ref()
+            .map(|hinter| hinter.next_hint_token())
+            .unwrap_or_default()
+    }
+
+    // `event` with every `ReedlineEvent::Conditional`, also those in `Multiple` and
+    // `UntilFound`, replaced by the event its condition chooses now
+    fn resolve_conditions(&self, event: ReedlineEvent) -> ReedlineEvent {
+        match event {
+            ReedlineEvent::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let event = if self.condition_holds(&condition) {
+                    then
+                } else {
+                    otherwise
+                };
+                self.resolve_conditions(*event)
+            }
+            ReedlineEvent::Multiple(events) => ReedlineEvent::Multiple(
+                events
+                    .into_iter()
+                    .map(|event| self.resolve_conditions(event))
+                    .collect(),
+            ),
+            ReedlineEvent::UntilFound(events) => ReedlineEvent::UntilFound(
+                events
+                    .into_iter()
+                    .map(|event| self.resolve_conditions(event))
+                    .collect(),
+            ),
+            event => event,
+        }
+    }
+
+    // Whether `condition` of a `ReedlineEvent::Conditional` holds
+    fn condition_holds(&self, condition: &EventCondition) -> bool {
+        let menu_active = self.menus.iter().any(|menu| menu.is_active());
+        match condition {
+            EventCondition::HintVisible => self.hint_applies(&self.current_hint()),
+            EventCondition::CursorAtLineEnd => self.editor.get_buffer()
+                [self.editor.insertion_point()..]
+                .chars()
+                .next()
+                .map_or(true, |c| c == '\n' || c == '\r'),
+            EventCondition::CursorAtBufferEnd => self.editor.is_cursor_at_buffer_end(),
+            EventCondition::BufferEmpty => self.editor.is_empty(),
+            EventCondition::MenuActive => menu_active,
+            EventCondition::SelectionActive => self.editor.get_selection().is_some(),
+            EventCondition::Not(condition) => !self.condition_holds(condition),
+            EventCondition::All(conditions) => conditions
+                .iter()
+                .all(|condition| self.condition_holds(condition)),
+            EventCondition::Any(conditions) => conditions
+                .iter()
+                .any(|condition| self.condition_holds(condition)),
+        }
     }
 
     /// Repaint of either the buffer or the parts for reverse history search
     fn repaint(&mut self, prompt: &dyn Prompt) -> io::Result<()> {
+        let sanitized;
+        let prompt = match self.sanitizer {
+            Some(sanitizer) => {
+                sanitized = SanitizedPrompt::new(prompt, sanitizer);
+                &sanitized as &dyn Prompt
+            }
+            None => prompt,
+        };
         // Repainting
-        if self.input_mode == InputMode::HistorySearch {
+        let painted = if self.input_mode == InputMode::HistorySearch {
             self.history_search_paint(prompt)
         } else {
             self.buffer_paint(prompt)
+        };
+        let err = match painted {
+            Ok(()) => return Ok(()),
+            Err(err) => ReedlineError(ReedlineErrorVariants::RenderError(err)),
+        };
+        let action = self
+            .render_error_handler
+            .as_mut()
+            .map_or(RenderErrorAction::Abort, |handler| handler(&err));
+        match action {
+            RenderErrorAction::Abort => Err(err.into()),
+            RenderErrorAction::Continue => {
+                // What made it to the terminal is unknown
+                self.painter.invalidate_painted_frame();
+                Ok(())
+            }
         }
     }
 
     #[cfg(feature = "bashisms")]
     /// Parses the ! command to replace entries from the history
     fn parse_bang_command(&mut self) -> Option<ReedlineEvent> {
+        if self.input_mask.is_some() {
+            return None;
+        }
         let buffer = self.editor.get_buffer();
         let parsed = parse_selection_char(buffer, '!');
 
@@ -1618,11 +4716,93 @@ impl Reedline {
         }
     }
 
+    fn format_buffer(&mut self) -> EventStatus {
+        if self.formatter.is_none() {
+            return EventStatus::Inapplicable;
+        }
+        self.leave_history_traversal();
+        let buffer = self.editor.get_buffer();
+        let formatted = self
+            .formatter
+            .as_mut()
+            .and_then(|formatter| formatter.format(buffer))
+            .filter(|formatted| formatted != buffer);
+        if let Some(formatted) = formatted {
+            let cursor = formatted_cursor(buffer, self.editor.insertion_point(), &formatted);
+            self.editor.edit_buffer(
+                |line_buffer| {
+                    line_buffer.set_buffer(formatted);
+                    line_buffer.set_insertion_point(cursor);
+                },
+                UndoBehavior::CreateUndoPoint,
+            );
+        }
+        EventStatus::Handled
+    }
+
+    fn send_selection(&mut self, action: &str) -> EventStatus {
+        let buffer = self.editor.get_buffer();
+        let text = self
+            .editor
+            .get_selection()
+            .map_or(buffer, |(start, end)| &buffer[start..end]);
+        match self.selection_handler.as_mut() {
+            Some(handler) => {
+                handler(action, text);
+                EventStatus::Handled
+            }
+            None => EventStatus::Inapplicable,
+        }
+    }
+
+    fn run_host_command(&mut self, host_command: &HostCommand) -> Result<()> {
+        if host_command.save_buffer_to_history {
+            let buffer = self.editor.get_buffer().to_string();
+            self.save_to_history(&buffer);
+        }
+
+        self.painter.move_cursor_to_end()?;
+        let output = {
+            // Restore the terminal before reporting any error of the command
+            let _guard = HostCommandGuard::new(self)?;
+            run_in_shell(&host_command.command, host_command.insert_output)
+        };
+
+        if let Some(output) = output? {
+            let (text, truncated) = decode_prefix(output, self.max_insert_size);
+            self.insert_external_text(&text, truncated);
+        }
+
+        Ok(())
+    }
+
+    /// Insert text read from a file or the output of a command at the cursor, `truncated`
+    /// if it was cut to the limit
+    fn insert_external_text(&mut self, text: &str, truncated: bool) {
+        let text = normalize_external_text(text);
+        if truncated {
+            self.command_line_message =
+                Some(format!("Inserted text truncated to {} bytes", text.len()));
+        }
+        self.run_edit_commands(&[EditCommand::InsertString(text)]);
+    }
+
+    // The shape the cursor should have now, if reedline sets it
+    fn cursor_shape(&mut self, menu_active: bool) -> Option<SetCursorStyle> {
+        let shape = self.cursor_shapes.as_ref().and_then(|shapes| {
+            let selecting = self.editor.get_selection().is_some();
+            shapes.shape(&self.edit_mode.edit_mode(), selecting, menu_active)
+        });
+        self.cursor_shape_guard.change(shape)
+    }
+
     /// Repaint logic for the history reverse search
     ///
     /// Overwrites the prompt indicator and highlights the search string
     /// separately from the result buffer.
     fn history_search_paint(&mut self, prompt: &dyn Prompt) -> Result<()> {
+        // Neither hints nor menus are shown during the search
+        self.pending_since = None;
         let navigation = self.history_cursor.get_navigation();
 
         if let HistoryNavigationQuery::SubstringSearch(substring) = navigation {
@@ -1633,19 +4813,26 @@ impl Reedline {
                     PromptHistorySearchStatus::Passing
                 };
 
-            let prompt_history_search = PromptHistorySearch::new(status, substring.clone());
+            let mut prompt_history_search = PromptHistorySearch::new(status, substring.clone());
+            if let Some((position, total)) = self.history_search_match_position(&substring) {
+                prompt_history_search = prompt_history_search.with_match_position(position, total);
+            }
 
             let res_string = self.history_cursor.string_at_cursor().unwrap_or_default();
 
             // Highlight matches
             let res_string = if self.use_ansi_coloring {
                 let match_highlighter = SimpleMatchHighlighter::new(substring);
-                let styled = match_highlighter.highlight(&res_string, 0);
+                let mut styled = match_highlighter.highlight(&res_string, 0);
+                if let Some(style) = self.inactive_buffer_style {
+                    styled.overlay_style(style);
+                }
                 styled.render_simple()
             } else {
                 res_string
             };
 
+            let status = self.status_line();
             let lines = PromptLines::new(
                 prompt,
                 self.prompt_edit_mode(),
@@ -1653,52 +4840,254 @@ impl Reedline {
                 &res_string,
                 "",
                 "",
+                &status,
             );
 
-            self.painter.repaint_buffer(
-                prompt,
-                &lines,
-                self.prompt_edit_mode(),
-                None,
-                self.use_ansi_coloring,
-                &self.cursor_shapes,
+            let cursor_shape = self.cursor_shape(false);
+            timed!(
+                self.frame_timings,
+                painting,
+                self.painter.repaint_buffer(
+                    prompt,
+                    &lines,
+                    None,
+                    self.use_ansi_coloring,
+                    cursor_shape,
+                )
             )?;
         }
 
         Ok(())
     }
 
+    // Which match of the search is shown and how many there are, counted again only once the
+    // search changes
+    fn history_search_match_position(&mut self, substring: &str) -> Option<(usize, usize)> {
+        let id = self.history_cursor.id_at_cursor()?;
+        match &self.history_search_matches {
+            Some((cached, cached_id, directory_history, matches))
+                if cached == substring
+                    && *cached_id == id
+                    && *directory_history == self.directory_history =>
+            {
+                *matches
+            }
+            _ => {
+                // The counter is left out if the history can't count the matches
+                let matches = self
+                    .history_cursor
+                    .match_position(self.history.as_ref())
+                    .ok()
+                    .flatten();
+                self.history_search_matches =
+                    Some((substring.to_string(), id, self.directory_history, matches));
+                matches
+            }
+        }
+    }
+
+    // The frame of the progress indicator, once the pending results took long enough
+    fn progress_indicator(&self) -> Option<String> {
+        let elapsed = self.pending_since?.elapsed();
+        if elapsed < self.progress_indicator_delay {
+            return None;
+        }
+        let frame = (elapsed.as_millis() / PENDING_POLL_INTERVAL.as_millis()) as usize;
+        let frame = PROGRESS_INDICATOR_FRAMES[frame % PROGRESS_INDICATOR_FRAMES.len()];
+        if self.use_ansi_coloring {
+            Some(self.progress_indicator_style.paint(frame).to_string())
+        } else {
+            Some(frame.to_string())
+        }
+    }
+
+    // The status bar fitted to the terminal, empty when there is none or hints are hidden
+    fn status_line(&self) -> String {
+        if self.hide_hints {
+            String::new()
+        } else {
+            self.status
+                .render_single_line(self.painter.screen_width() as usize, self.use_ansi_coloring)
+        }
+    }
+
     /// Triggers a full repaint including the prompt parts
     ///
     /// Includes the highlighting and hinting calls.
     fn buffer_paint(&mut self, prompt: &dyn Prompt) -> Result<()> {
+        self.update_live_validation();
+        let cursor_shape = self.cursor_shape(self.menus.iter().any(|menu| menu.is_active()));
         let cursor_position_in_buffer = self.editor.insertion_point();
         let buffer_to_paint = self.editor.get_buffer();
 
-        let mut styled_text = self
-            .highlighter
-            .highlight(buffer_to_paint, cursor_position_in_buffer);
-        if let Some((from, to)) = self.editor.get_selection() {
+        // Secrets are never handed to the highlighter
+        let (mut styled_text, cursor_position_in_buffer) = match self.input_mask {
+            Some(mask) => mask_buffer(buffer_to_paint, cursor_position_in_buffer, mask),
+            None => (
+                timed!(self.frame_timings, highlighting, self.highlight_buffer()),
+                cursor_position_in_buffer,
+            ),
+        };
+        if let Some((from, to)) = self
+            .editor
+            .get_selection()
+            .filter(|_| self.input_mask.is_none())
+        {
             styled_text.style_range(from, to, self.visual_selection_style);
         }
+        if self.input_mode == InputMode::SearchReplace {
+            if let Some(current_match) = self.search_replace.current_match() {
+                styled_text.style_range(
+                    current_match.start,
+                    current_match.end,
+                    Style::new().reverse(),
+                );
+            }
+        }
+        if self.input_mode == InputMode::BufferSearch {
+            for found in self.buffer_search.matches() {
+                styled_text.style_range(found.start, found.end, Style::new().underline());
+            }
+            if let Some(current_match) = self.buffer_search.current_match() {
+                styled_text.style_range(
+                    current_match.start,
+                    current_match.end,
+                    Style::new().reverse(),
+                );
+            }
+        }
+        if let Some(style) = self.inactive_buffer_style {
+            if self.menus.iter().any(|menu| menu.is_active()) {
+                styled_text.overlay_style(style);
+            }
+        }
+        let painted_cursor = match self
+            .paste_folds
+            .as_mut()
+            .filter(|_| self.input_mask.is_none())
+        {
+            Some(paste_folds) => {
+                paste_folds.fold(&mut styled_text, buffer_to_paint, cursor_position_in_buffer)
+            }
+            None => cursor_position_in_buffer,
+        };
 
-        let (before_cursor, after_cursor) = styled_text.render_around_insertion_point(
-            cursor_position_in_buffer,
+        let (mut before_cursor, mut after_cursor) = std::mem::take(&mut self.rendered_buffer);
+        styled_text.render_around_insertion_point_into(
+            painted_cursor,
             prompt,
             self.use_ansi_coloring,
+            &mut before_cursor,
+            &mut after_cursor,
         );
 
-        let hint: String = if self.hints_active() {
-            self.hinter.as_mut().map_or_else(String::new, |hinter| {
-                hinter.handle(
+        let command_line = match self.input_mode {
+            InputMode::ExCommand => Some((":".to_string(), self.ex_command_line.line_buffer())),
+            InputMode::SearchReplace => Some((
+                self.search_replace.prompt(),
+                self.search_replace.input().line_buffer(),
+            )),
+            InputMode::BufferSearch => Some((
+                self.buffer_search.prompt(),
+                self.buffer_search.input().line_buffer(),
+            )),
+            _ => None,
+        };
+
+        if let Some((indicator, command_line)) = command_line {
+            let (line_before_cursor, line_after_cursor) = command_line
+                .get_buffer()
+                .split_at(command_line.insertion_point());
+            before_cursor.push_str(&after_cursor);
+            before_cursor.push('\n');
+            before_cursor.push_str(&indicator);
+            before_cursor.push_str(line_before_cursor);
+            after_cursor.clear();
+            after_cursor.push_str(line_after_cursor);
+        } else if let Some(correction) = &self.pending_correction {
+            after_cursor.push('\n');
+            after_cursor.push_str(&format!(
+                "did you mean `{}`? [y/n/e]",
+                correction.suggestion
+            ));
+        } else if let Some(message) = &self.command_line_message {
+            after_cursor.push('\n');
+            after_cursor.push_str(message);
+        }
+
+        if let Some(message) = self.transient_message.as_ref().filter(|_| !self.hide_hints) {
+            after_cursor.push('\n');
+            if self.use_ansi_coloring {
+                after_cursor.push_str(&message.text);
+            } else {
+                after_cursor.push_str(&strip_ansi(&message.text));
+            }
+        }
+
+        if self.debug_panel {
+            for line in self.debug_state().to_string().lines() {
+                after_cursor.push('\n');
+                after_cursor.push_str(line);
+            }
+        }
+
+        if let Some(keys) = self
+            .key_overlay
+            .as_ref()
+            .filter(|_| self.input_mask.is_none())
+            .and_then(KeyOverlay::text)
+        {
+            let padding = (self.painter.screen_width() as usize).saturating_sub(keys.width() + 1);
+            after_cursor.push('\n');
+            after_cursor.push_str(&" ".repeat(padding));
+            if self.use_ansi_coloring {
+                after_cursor.push_str(&Style::new().reverse().paint(keys).to_string());
+            } else {
+                after_cursor.push_str(&keys);
+            }
+        }
+
+        let prompt_mode = self.prompt_edit_mode();
+        let status = self.status_line();
+        let progress = self.progress_indicator();
+        let menu_active = self.menus.iter().any(|menu| menu.is_active());
+        let hints_active = self.hints_active();
+        let hint = if hints_active && self.command_line_message.is_none() {
+            timed!(
+                self.frame_timings,
+                hinting,
+                self.hinter.as_mut().map_or("", |hinter| {
+                    self.hint_cache.handle(
+                        &mut ContextHinter::new(hinter.as_mut(), self.context.as_deref()),
+                        buffer_to_paint,
+                        cursor_position_in_buffer,
+                        self.history.as_ref(),
+                        self.use_ansi_coloring,
+                    )
+                })
+            )
+        } else {
+            ""
+        };
+        let hint = match self.sanitizer {
+            Some(sanitizer) => sanitizer.sanitize(hint),
+            None => Cow::Borrowed(hint),
+        };
+        let hint = match &self.abbreviations {
+            // The expansion is only announced when the hinter has nothing to offer, as the
+            // hint it shows is the one completed
+            Some(abbreviations) if hints_active && hint.is_empty() => {
+                Cow::Owned(abbreviations.hint(
                     buffer_to_paint,
                     cursor_position_in_buffer,
-                    self.history.as_ref(),
                     self.use_ansi_coloring,
-                )
-            })
-        } else {
-            String::new()
+                ))
+            }
+            _ => hint,
+        };
+        let hint = match &progress {
+            Some(progress) if !menu_active => Cow::Owned(format!("{hint} {progress}")),
+            _ => hint,
         };
 
         // Needs to add return carriage to newlines because when not in raw mode
@@ -1706,40 +5095,89 @@ impl Reedline {
 
         let mut lines = PromptLines::new(
             prompt,
-            self.prompt_edit_mode(),
+            prompt_mode.clone(),
             None,
             &before_cursor,
             &after_cursor,
             &hint,
+            &status,
         );
+        if let Some((_, validation)) = &self.live_validation {
+            lines = lines.with_validation(prompt, prompt_mode.clone(), *validation);
+        }
 
         // Updating the working details of the active menu
         for menu in self.menus.iter_mut() {
             if menu.is_active() {
-                lines.prompt_indicator = menu.indicator().to_owned().into();
+                lines.prompt_indicator = match &progress {
+                    Some(progress) => format!("{}{progress} ", menu.indicator()).into(),
+                    None => menu.indicator().to_owned().into(),
+                };
                 // If the menu requires the cursor position, update it (ide menu)
                 let cursor_pos = lines.cursor_pos(self.painter.screen_width());
                 menu.set_cursor_pos(cursor_pos);
 
-                menu.update_working_details(
-                    &mut self.editor,
-                    self.completer.as_mut(),
-                    self.history.as_ref(),
-                    &self.painter,
+                timed!(
+                    self.frame_timings,
+                    completion,
+                    menu.update_working_details(
+                        &mut self.editor,
+                        &mut self.completion_cache.wrap(&mut ContextCompleter::new(
+                            self.completer.as_mut(),
+                            self.context.as_deref()
+                        )),
+                        &CompletionSources {
+                            history: self.history.as_ref(),
+                            usage: &self.completion_usage,
+                            context: self.context.as_deref(),
+                            sanitizer: self.sanitizer,
+                        },
+                        &self.painter,
+                    )
                 );
+
+                let error = menu.completion_error();
+                if error != self.reported_completion_error.as_ref() {
+                    if let (Some(handler), Some(error)) =
+                        (self.completion_error_handler.as_mut(), error)
+                    {
+                        handler(error);
+                    }
+                    self.reported_completion_error = error.cloned();
+                }
             }
         }
 
+        if self.flash_until.is_some() {
+            lines.prompt_indicator = Style::new()
+                .reverse()
+                .paint(lines.prompt_indicator.as_ref())
+                .to_string()
+                .into();
+        }
+
         let menu = self.menus.iter().find(|menu| menu.is_active());
 
-        self.painter.repaint_buffer(
-            prompt,
-            &lines,
-            self.prompt_edit_mode(),
-            menu,
-            self.use_ansi_coloring,
-            &self.cursor_shapes,
-        )
+        let pending = (menu.is_some() && self.completer.is_pending())
+            || (hints_active
+                && self
+                    .hinter
+                    .as_ref()
+                    .map_or(false, |hinter| hinter.is_pending()));
+        if !pending {
+            self.pending_since = None;
+        } else if self.pending_since.is_none() {
+            self.pending_since = Some(Instant::now());
+        }
+
+        let painted = timed!(
+            self.frame_timings,
+            painting,
+            self.painter
+                .repaint_buffer(prompt, &lines, menu, self.use_ansi_coloring, cursor_shape,)
+        );
+        self.rendered_buffer = (before_cursor, after_cursor);
+        painted
     }
 
     /// Adds an external printer
@@ -1776,18 +5214,123 @@ impl Reedline {
         Ok(messages)
     }
 
-    fn submit_buffer(&mut self, prompt: &dyn Prompt) -> io::Result<EventStatus> {
+    // Submits the buffer, unless the corrector suggests a different line to ask about first
+    fn submit_buffer(
+        &mut self,
+        prompt: &dyn Prompt,
+        reason: SignalReason,
+    ) -> io::Result<EventStatus> {
+        let asks_for_correction = matches!(
+            reason,
+            SignalReason::Enter | SignalReason::Submit | SignalReason::SubmitOrNewline
+        );
+        let asks_for_correction = asks_for_correction && self.input_mask.is_none();
+        if asks_for_correction {
+            self.expand_abbreviation();
+        }
+        if let Some(corrector) = self.corrector.as_mut().filter(|_| asks_for_correction) {
+            let buffer = self.editor.get_buffer();
+            if let Some(suggestion) = corrector
+                .correct(buffer)
+                .filter(|suggestion| suggestion != buffer)
+            {
+                self.pending_correction = Some(PendingCorrection { suggestion, reason });
+                self.input_mode = InputMode::Correction;
+                return Ok(EventStatus::Handled);
+            }
+        }
+        self.submit_buffer_as_is(prompt, reason)
+    }
+
+    fn submit_buffer_as_is(
+        &mut self,
+        prompt: &dyn Prompt,
+        reason: SignalReason,
+    ) -> io::Result<EventStatus> {
         let buffer = self.editor.get_buffer().to_string();
+        self.record_signal(reason);
+        self.history_recalled_id = None;
         self.hide_hints = true;
         // Additional repaint to show the content without hints etc.
-        if let Some(transient_prompt) = self.transient_prompt.take() {
-            self.repaint(transient_prompt.as_ref())?;
-            self.transient_prompt = Some(transient_prompt);
+        let transient_prompt = self.transient_prompt.take();
+        let submitted_prompt = transient_prompt.as_deref().unwrap_or(prompt);
+        let painted = match self.submit_echo.clone() {
+            Some(echo) => {
+                self.run_edit_commands(&[EditCommand::MoveToEnd { select: false }]);
+                self.repaint(&EchoPrompt::new(submitted_prompt, &echo))
+            }
+            None => self.repaint(submitted_prompt),
+        };
+        let post_submit = submitted_prompt.render_post_submit();
+        self.post_submit = Some(match self.sanitizer {
+            Some(sanitizer) => sanitizer.sanitize(&post_submit).into_owned(),
+            None => post_submit.into_owned(),
+        })
+        .filter(|text| !text.is_empty());
+        self.transient_prompt = transient_prompt;
+        painted?;
+        if self.input_mask.is_some() {
+            self.editor.zeroize();
         } else {
-            self.repaint(prompt)?;
+            self.line_usage.styled_text = self.highlight_buffer();
+            self.save_to_history(&buffer);
+            if let Some(fields) = &mut self.template_fields {
+                *fields = self.editor.fields();
+            }
+            self.editor.set_read_only([]);
+            self.run_edit_commands(&[EditCommand::Clear]);
+            self.editor.reset_undo_stack();
+        }
+
+        Ok(EventStatus::Exits(Signal::Success(buffer)))
+    }
+
+    fn highlight_buffer(&self) -> StyledText {
+        let buffer = self.editor.get_buffer();
+        let cursor = self.editor.insertion_point();
+        match self.context.as_deref() {
+            Some(context) => self
+                .highlighter
+                .highlight_in_context(buffer, cursor, context),
+            None => self.highlighter.highlight(buffer, cursor),
+        }
+    }
+
+    fn record_signal(&mut self, reason: SignalReason) {
+        let reason = match reason {
+            SignalReason::Enter | SignalReason::Submit | SignalReason::SubmitOrNewline
+                if self.accepting_from_menu =>
+            {
+                SignalReason::MenuAccept
+            }
+            reason => reason,
+        };
+        self.last_signal_info = Some(SignalInfo {
+            reason,
+            cursor_position: self.editor.insertion_point(),
+        });
+    }
+
+    fn save_to_history(&mut self, buffer: &str) {
+        if !self.read_line_options.history {
+            return;
+        }
+        // Histories that can't update entries get the edited line as a new one
+        if let Some(id) = self.edited_history_item.take() {
+            let updated = self.history.update(id, &|item| HistoryItem {
+                command_line: buffer.to_string(),
+                ..item
+            });
+            if updated.is_ok() {
+                self.hint_cache.clear();
+                self.history_last_run_id = Some(id);
+                self.history_excluded_item = None;
+                self.record_submitted_row(id);
+                return;
+            }
         }
         if !buffer.is_empty() {
-            let mut entry = HistoryItem::from_command_line(&buffer);
+            let mut entry = HistoryItem::from_command_line(buffer);
             entry.session_id = self.get_history_session_id();
 
             if self
@@ -1800,20 +5343,2484 @@ impl Reedline {
                 self.history_last_run_id = entry.id;
                 self.history_excluded_item = Some(entry);
             } else {
-                entry = self.history.save(entry).expect("todo: error handling");
-                self.history_last_run_id = entry.id;
-                self.history_excluded_item = None;
+                match self.history.save(entry) {
+                    Ok(entry) => {
+                        self.hint_cache.clear();
+                        self.history_last_run_id = entry.id;
+                        self.history_excluded_item = None;
+                        if let Some(id) = entry.id {
+                            self.record_submitted_row(id);
+                        }
+                    }
+                    Err(err) => {
+                        self.history_last_run_id = None;
+                        self.report_history_error(&err);
+                    }
+                }
             }
         }
-        self.run_edit_commands(&[EditCommand::Clear]);
-        self.editor.reset_undo_stack();
+    }
 
-        Ok(EventStatus::Exits(Signal::Success(buffer)))
+    // Hands a failure of the history to the handler, or shows it with the next prompt
+    fn report_history_error(&mut self, err: &ReedlineError) {
+        match self.history_error_handler.as_mut() {
+            Some(handler) => handler(err),
+            None => self.show_message(
+                format!("The history failed: {err}"),
+                HISTORY_ERROR_MESSAGE_DURATION,
+            ),
+        }
+    }
+
+    // Remembers where the entry `id` was painted, forgetting the entries it was painted over
+    fn record_submitted_row(&mut self, id: HistoryItemId) {
+        let row = self.painter.prompt_start_row();
+        self.submitted_rows
+            .retain(|(submitted, submitted_row)| *submitted_row < row && *submitted != id);
+        self.submitted_rows.push((id, row));
     }
 }
 
+/// Whether a handled batch of events is repainted right away
+///
+/// Not while pasted lines are submitted one by one. If more input is already waiting, the
+/// repaint can wait for the next batch, unless that would drop below the repaint rate.
+fn repaint_after_batch(
+    needs_repaint: bool,
+    paste_enter_state: bool,
+    input_pending: bool,
+    since_repaint: Duration,
+    min_repaint_interval: Duration,
+) -> bool {
+    needs_repaint && !paste_enter_state && (!input_pending || since_repaint >= min_repaint_interval)
+}
+
+/// The masked text shown for `buffer` and the cursor position in it
+fn mask_buffer(buffer: &str, insertion_point: usize, mask: Option<char>) -> (StyledText, usize) {
+    let mut masked = StyledText::new();
+    match mask {
+        Some(mask) => {
+            let mask_up_to = |end: usize| buffer[..end].chars().map(|_| mask).collect::<String>();
+            let cursor = mask_up_to(insertion_point).len();
+            masked.push((Style::new(), mask_up_to(buffer.len())));
+            (masked, cursor)
+        }
+        None => (masked, 0),
+    }
+}
+
+#[test]
+fn batches_are_repainted_unless_input_is_waiting_within_the_interval() {
+    let interval = DEFAULT_MIN_REPAINT_INTERVAL;
+    let recently = Duration::from_millis(1);
+    let long_ago = Duration::from_millis(100);
+
+    // Nothing waits, the batch is painted right away
+    assert!(repaint_after_batch(true, false, false, recently, interval));
+    // More input waits, the repaint is left to the next batch within the interval
+    assert!(!repaint_after_batch(true, false, true, recently, interval));
+    assert!(repaint_after_batch(true, false, true, long_ago, interval));
+    assert!(repaint_after_batch(true, false, true, interval, interval));
+    // A zero interval repaints after every batch
+    assert!(repaint_after_batch(
+        true,
+        false,
+        true,
+        Duration::ZERO,
+        Duration::ZERO
+    ));
+    // Pasted lines are submitted without painting each of them
+    assert!(!repaint_after_batch(true, true, false, long_ago, interval));
+    assert!(!repaint_after_batch(
+        false, false, false, long_ago, interval
+    ));
+}
+
 #[test]
 fn thread_safe() {
     fn f<S: Send>(_: S) {}
     f(Reedline::create());
 }
+
+#[test]
+fn describe_keybindings_reports_unknown_menus() {
+    let mut keybindings = crate::default_emacs_keybindings();
+    keybindings.add_binding(
+        KeyModifiers::NONE,
+        KeyCode::Tab,
+        ReedlineEvent::Menu("completion_menu".to_string()),
+    );
+    let line_editor = Reedline::create().with_edit_mode(Box::new(Emacs::new(keybindings)));
+
+    let report = line_editor.describe_keybindings();
+
+    let tab = crate::KeyCombination {
+        modifier: KeyModifiers::NONE,
+        key_code: KeyCode::Tab,
+    };
+    assert!(report
+        .bindings
+        .iter()
+        .any(|binding| binding.key == tab && binding.menus == vec!["completion_menu".to_string()]));
+    assert_eq!(
+        report.conflicts,
+        vec![KeybindingConflict::UnknownMenu {
+            mode: "emacs".to_string(),
+            key: tab,
+            menu: "completion_menu".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn update_keybindings_changes_live_edit_mode() {
+    let mut line_editor = Reedline::create().with_edit_mode(Box::<crate::Vi>::default());
+    line_editor.update_keybindings(|mode, keybindings| {
+        if mode == "vi normal" {
+            keybindings.add_binding(
+                KeyModifiers::CONTROL,
+                KeyCode::Char('x'),
+                ReedlineEvent::CtrlD,
+            );
+        }
+    });
+
+    let bound = line_editor
+        .describe_keybindings()
+        .bindings
+        .into_iter()
+        .filter(|binding| {
+            binding.event == ReedlineEvent::CtrlD && binding.key.key_code == KeyCode::Char('x')
+        })
+        .map(|binding| binding.mode)
+        .collect::<Vec<_>>();
+    assert_eq!(bound, vec!["vi normal".to_string()]);
+}
+
+#[test]
+fn custom_events_reach_the_handler() {
+    struct Append;
+
+    impl CustomEventHandler for Append {
+        fn handle(&mut self, name: &str, editor: &mut Editor) {
+            let buffer = format!("{}{name}", editor.get_buffer());
+            editor.edit_buffer(|lb| lb.set_buffer(buffer), UndoBehavior::CreateUndoPoint);
+        }
+    }
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    let event = ReedlineEvent::Custom("foo".to_string());
+    assert!(matches!(
+        line_editor.handle_editor_event(&prompt, event.clone()),
+        Ok(EventStatus::Inapplicable)
+    ));
+
+    let mut line_editor = line_editor.with_custom_event_handler(Box::new(Append));
+    assert!(matches!(
+        line_editor.handle_editor_event(&prompt, event),
+        Ok(EventStatus::Handled)
+    ));
+    assert_eq!(line_editor.current_buffer_contents(), "foo");
+}
+
+#[test]
+fn last_signal_info_reports_reason_and_cursor() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    assert_eq!(line_editor.last_signal_info(), None);
+
+    line_editor.run_edit_commands(&[
+        EditCommand::InsertString("foo bar".to_string()),
+        EditCommand::MoveLeft { select: false },
+    ]);
+    assert!(matches!(
+        line_editor.handle_editor_event(&prompt, ReedlineEvent::CtrlC),
+        Ok(EventStatus::Exits(Signal::CtrlC))
+    ));
+    assert_eq!(
+        line_editor.last_signal_info(),
+        Some(&SignalInfo {
+            reason: SignalReason::CtrlC,
+            cursor_position: 6,
+        })
+    );
+}
+
+#[test]
+fn unhandled_keys_reach_the_callback() {
+    use std::sync::{Arc, Mutex};
+
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let callback_reported = Arc::clone(&reported);
+    let mut line_editor = Reedline::create()
+        .with_edit_mode(Box::<crate::Vi>::default())
+        .with_unhandled_input(UnhandledInput::Callback(Box::new(move |key| {
+            callback_reported.lock().unwrap().push(key.clone())
+        })));
+    let prompt = crate::DefaultPrompt::default();
+    let mut press = |code| {
+        let event = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        line_editor.parse_raw_event(ReedlineRawEvent::convert_from(event).unwrap());
+    };
+
+    press(KeyCode::Esc);
+    // `d` waits for a motion, `q` is neither one nor a command
+    press(KeyCode::Char('d'));
+    press(KeyCode::Char('q'));
+    press(KeyCode::Char('w'));
+    line_editor.report_unhandled_keys(&prompt).unwrap();
+
+    assert_eq!(
+        *reported.lock().unwrap(),
+        vec![KeyCombination {
+            modifier: KeyModifiers::NONE,
+            key_code: KeyCode::Char('q')
+        }]
+    );
+}
+
+#[test]
+fn flashing_the_prompt_indicator_does_not_wait_for_it_to_end() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create()
+        .with_edit_mode(Box::<crate::Vi>::default())
+        .with_unhandled_input(UnhandledInput::Flash);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    let press = |line_editor: &mut Reedline, code| {
+        let event = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        line_editor.parse_raw_event(ReedlineRawEvent::convert_from(event).unwrap());
+    };
+    press(&mut line_editor, KeyCode::Esc);
+    press(&mut line_editor, KeyCode::Char('q'));
+
+    let start = Instant::now();
+    line_editor.report_unhandled_keys(&prompt).unwrap();
+    assert!(start.elapsed() < FLASH_DURATION);
+    assert!(line_editor.flash_until.is_some());
+    assert!(line_editor.idle_timeout().unwrap() <= FLASH_DURATION);
+
+    // The flash is turned back once it expired
+    line_editor.handle_idle_timeout(&prompt).unwrap();
+    assert!(line_editor.flash_until.is_some());
+    line_editor.flash_until = Some(Instant::now());
+    line_editor.handle_idle_timeout(&prompt).unwrap();
+    assert!(line_editor.flash_until.is_none());
+    assert_eq!(line_editor.idle_timeout(), None);
+}
+
+#[test]
+fn transient_message_is_cleared_by_the_next_event() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor.show_message("saved", Duration::from_secs(60));
+    assert!(line_editor.transient_message.is_some());
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    assert!(line_editor.transient_message.is_some());
+
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('a')]),
+        )
+        .unwrap();
+    assert!(line_editor.transient_message.is_none());
+}
+
+#[test]
+fn progress_indicator_shows_after_the_delay() {
+    let mut line_editor = Reedline::create()
+        .with_ansi_colors(false)
+        .with_progress_indicator_delay(Duration::from_millis(50));
+    assert_eq!(line_editor.progress_indicator(), None);
+    assert_eq!(line_editor.idle_timeout(), None);
+
+    line_editor.pending_since = Some(Instant::now());
+    assert_eq!(line_editor.progress_indicator(), None);
+    assert_eq!(line_editor.idle_timeout(), Some(PENDING_POLL_INTERVAL));
+
+    line_editor.pending_since = Some(Instant::now() - Duration::from_millis(50));
+    assert_eq!(line_editor.progress_indicator().as_deref(), Some("⠋"));
+}
+
+#[test]
+fn corrections_are_offered_on_submit() {
+    struct GitTypos;
+
+    impl Corrector for GitTypos {
+        fn correct(&mut self, line: &str) -> Option<String> {
+            line.strip_prefix("gti ").map(|rest| format!("git {rest}"))
+        }
+    }
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_corrector(Box::new(GitTypos));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    let answer = |line_editor: &mut Reedline, c| {
+        line_editor.run_edit_commands(&[EditCommand::Clear]);
+        line_editor.run_edit_commands(&[EditCommand::InsertString("gti status".to_string())]);
+        assert!(matches!(
+            line_editor.handle_event(&prompt, ReedlineEvent::Submit),
+            Ok(EventStatus::Handled)
+        ));
+        assert_eq!(line_editor.input_mode, InputMode::Correction);
+        line_editor.handle_event(
+            &prompt,
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar(c)]),
+        )
+    };
+
+    assert!(matches!(
+        answer(&mut line_editor, 'y'),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "git status"
+    ));
+    assert!(matches!(
+        answer(&mut line_editor, 'n'),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "gti status"
+    ));
+    assert!(matches!(
+        answer(&mut line_editor, 'e'),
+        Ok(EventStatus::Handled)
+    ));
+    assert_eq!(line_editor.input_mode, InputMode::Regular);
+    assert_eq!(line_editor.current_buffer_contents(), "git status");
+}
+
+#[test]
+fn edited_history_items_are_updated_on_submit() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ls".to_string())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Submit)
+        .unwrap();
+    let id = line_editor.history_last_run_id.unwrap();
+
+    line_editor.edit_history_item(id).unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "ls");
+    line_editor.run_edit_commands(&[EditCommand::InsertString(" -la".to_string())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Submit)
+        .unwrap();
+
+    // The file backed history can't update entries
+    assert_eq!(line_editor.history.count_all().unwrap(), 2);
+    let new_id = line_editor.history_last_run_id.unwrap();
+    assert_eq!(
+        line_editor.history.load(new_id).unwrap().command_line,
+        "ls -la"
+    );
+    assert_eq!(line_editor.submitted_rows, vec![(new_id, 0)]);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn edited_history_items_are_updated_on_submit_in_sqlite() {
+    let prompt = crate::DefaultPrompt::default();
+    let history = crate::SqliteBackedHistory::in_memory().unwrap();
+    let mut line_editor = Reedline::create().with_history(Box::new(history));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ls".to_string())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Submit)
+        .unwrap();
+    let id = line_editor.history_last_run_id.unwrap();
+
+    line_editor.edit_history_item(id).unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString(" -la".to_string())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Submit)
+        .unwrap();
+
+    assert_eq!(line_editor.history.count_all().unwrap(), 1);
+    assert_eq!(line_editor.history.load(id).unwrap().command_line, "ls -la");
+}
+
+#[test]
+fn accept_and_hold_keeps_the_line_for_the_next_prompt() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("make test".to_string())]);
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::AcceptAndHold),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "make test"
+    ));
+    assert_eq!(line_editor.current_buffer_contents(), "make test");
+    assert_eq!(line_editor.history.count_all().unwrap(), 1);
+}
+
+#[test]
+fn accept_and_down_history_loads_the_next_entry() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    for line in ["cd src", "cargo build", "cd .."] {
+        line_editor.run_edit_commands(&[EditCommand::InsertString(line.to_string())]);
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::Submit)
+            .unwrap();
+    }
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::PreviousHistory)
+        .unwrap();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::PreviousHistory)
+        .unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "cargo build");
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::AcceptAndDownHistory),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "cargo build"
+    ));
+    assert_eq!(line_editor.current_buffer_contents(), "cd ..");
+
+    // Without a recalled entry the next prompt starts empty
+    line_editor.run_edit_commands(&[EditCommand::Clear]);
+    line_editor.input_mode = InputMode::Regular;
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ls".to_string())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::AcceptAndDownHistory)
+        .unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "");
+}
+
+#[test]
+fn the_history_entry_after_an_id_is_loaded_for_traversal() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    let mut ids = Vec::new();
+    for line in ["cd src", "cargo build", "cd .."] {
+        line_editor.run_edit_commands(&[EditCommand::InsertString(line.to_string())]);
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::Submit)
+            .unwrap();
+        ids.push(line_editor.history_last_run_id.unwrap());
+    }
+
+    let next = line_editor.history_entry_after(ids[0]).unwrap();
+    line_editor.load_history_entry(next);
+    assert_eq!(line_editor.current_buffer_contents(), "cargo build");
+    assert!(line_editor.input_mode == InputMode::HistoryTraversal);
+    assert_eq!(line_editor.history_recalled_id, Some(ids[1]));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::NextHistory)
+        .unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "cd ..");
+
+    assert!(line_editor.history_entry_after(ids[2]).is_none());
+}
+
+// Runs the recalled entries one after the other with `OperateAndGetNext`
+#[cfg(test)]
+fn operate_and_get_next_runs_the_following_entries(history: Box<dyn History>) {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_history(history);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    for line in ["cd src", "cargo build", "cd .."] {
+        line_editor.run_edit_commands(&[EditCommand::InsertString(line.to_string())]);
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::Submit)
+            .unwrap();
+    }
+    for _ in 0..3 {
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::PreviousHistory)
+            .unwrap();
+    }
+    let operate = |line_editor: &mut Reedline| match line_editor
+        .handle_event(&prompt, ReedlineEvent::OperateAndGetNext)
+    {
+        Ok(EventStatus::Exits(Signal::Success(line))) => line,
+        _ => panic!("the line is not submitted"),
+    };
+    assert_eq!(operate(&mut line_editor), "cd src");
+    assert_eq!(line_editor.current_buffer_contents(), "cargo build");
+    assert_eq!(operate(&mut line_editor), "cargo build");
+    assert_eq!(line_editor.current_buffer_contents(), "cd ..");
+
+    // The entry saved for the last line doesn't follow it
+    line_editor.run_edit_commands(&[EditCommand::Clear]);
+    line_editor.input_mode = InputMode::Regular;
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::PreviousHistory)
+        .unwrap();
+    let last = line_editor.current_buffer_contents().to_string();
+    assert_eq!(operate(&mut line_editor), last);
+    assert_eq!(line_editor.current_buffer_contents(), "");
+}
+
+#[test]
+fn operate_and_get_next_runs_the_following_entries_of_a_file() {
+    operate_and_get_next_runs_the_following_entries(Box::<FileBackedHistory>::default());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn operate_and_get_next_runs_the_following_entries_in_sqlite() {
+    operate_and_get_next_runs_the_following_entries(Box::new(
+        crate::SqliteBackedHistory::in_memory().unwrap(),
+    ));
+}
+
+#[test]
+fn operate_and_get_next_follows_the_entries_of_a_full_history() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor =
+        Reedline::create().with_history(Box::new(FileBackedHistory::new(3).unwrap()));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    for line in ["a", "b", "c"] {
+        line_editor.run_edit_commands(&[EditCommand::InsertString(line.to_string())]);
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::Submit)
+            .unwrap();
+    }
+    for _ in 0..3 {
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::PreviousHistory)
+            .unwrap();
+    }
+
+    // Saving "a" again drops the oldest entry, the others keep their ids
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::OperateAndGetNext)
+        .unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "b");
+    assert_eq!(line_editor.history_recalled_id, Some(HistoryItemId::new(1)));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::NextHistory)
+        .unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "c");
+}
+
+#[test]
+fn mask_buffer_hides_every_character() {
+    let (masked, cursor) = mask_buffer("pässwd", 3, Some('*'));
+    assert_eq!(masked.raw_string(), "******");
+    assert_eq!(cursor, 2);
+
+    let (masked, cursor) = mask_buffer("pässwd", 3, None);
+    assert_eq!(masked.raw_string(), "");
+    assert_eq!(cursor, 0);
+}
+
+#[test]
+fn masked_input_is_kept_out_of_the_history() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.input_mask = Some(Some('*'));
+    line_editor.run_edit_commands(&[EditCommand::InsertString("hunter2".to_string())]);
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::SearchHistory),
+        Ok(EventStatus::Inapplicable)
+    ));
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::Enter),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "hunter2"
+    ));
+    assert_eq!(line_editor.history.count_all().unwrap(), 0);
+    assert!(line_editor.editor.is_empty());
+}
+
+#[test]
+fn the_debug_state_keeps_masked_input_secret() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.input_mask = Some(Some('*'));
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit(vec![EditCommand::InsertString("hünter2".into())]),
+        )
+        .unwrap();
+
+    let state = line_editor.debug_state();
+    assert_eq!(state.buffer, "*******");
+    assert_eq!(state.cursor, 7);
+    assert!(state.last_event.is_none());
+    assert!(!state.to_string().contains("hünter2"));
+
+    line_editor.input_mask = Some(None);
+    let state = line_editor.debug_state();
+    assert_eq!(state.buffer, "");
+    assert_eq!(state.cursor, 0);
+
+    line_editor.input_mask = None;
+    let state = line_editor.debug_state();
+    assert_eq!(state.buffer, "hünter2");
+    assert!(state.last_event.is_some());
+}
+
+#[test]
+fn read_line_options_apply_to_the_line() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.apply_read_line_options(
+        ReadLineOptions::new()
+            .with_buffer("añb", Some(2))
+            .with_history(false)
+            .with_hints(false),
+    );
+    // Not a char boundary
+    assert_eq!(line_editor.current_buffer_contents(), "añb");
+    assert_eq!(line_editor.current_insertion_point(), 3);
+    assert!(!line_editor.hints_active());
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::Enter),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "añb"
+    ));
+    assert_eq!(line_editor.history.count_all().unwrap(), 0);
+}
+
+#[test]
+fn read_line_options_override_keybindings() {
+    let mut keybindings = Keybindings::default();
+    keybindings.add_binding(KeyModifiers::NONE, KeyCode::Esc, ReedlineEvent::CtrlC);
+    let mut line_editor = Reedline::create();
+    line_editor.apply_read_line_options(ReadLineOptions::new().with_keybindings(keybindings));
+
+    let mut press = |code| {
+        let event = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        line_editor.parse_raw_event(ReedlineRawEvent::convert_from(event).unwrap())
+    };
+    assert_eq!(press(KeyCode::Esc), ReedlineEvent::CtrlC);
+    assert_eq!(
+        press(KeyCode::Char('a')),
+        ReedlineEvent::Edit(vec![EditCommand::InsertChar('a')])
+    );
+}
+
+#[test]
+fn idle_handler_is_called_without_input() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_idle_handler(Duration::from_secs(60), |idle| {
+        if idle >= Duration::from_secs(120) {
+            vec![ReedlineEvent::CtrlD]
+        } else {
+            vec![]
+        }
+    });
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    assert!(line_editor.idle_timeout().unwrap() > Duration::from_secs(59));
+
+    let handler = line_editor.idle_handler.as_mut().unwrap();
+    handler.last_input -= Duration::from_secs(61);
+    assert_eq!(line_editor.idle_timeout(), Some(Duration::ZERO));
+    assert!(matches!(line_editor.handle_idle_timeout(&prompt), Ok(None)));
+    assert!(line_editor.idle_timeout().unwrap() > Duration::from_secs(59));
+
+    let handler = line_editor.idle_handler.as_mut().unwrap();
+    handler.last_input -= Duration::from_secs(60);
+    handler.last_call = Some(Instant::now() - Duration::from_secs(60));
+    assert!(matches!(
+        line_editor.handle_idle_timeout(&prompt),
+        Ok(Some(Signal::CtrlD))
+    ));
+}
+
+#[test]
+fn repaint_handle_wakes_up_the_editor() {
+    let handle = RepaintHandle::new();
+    let line_editor = Reedline::create().with_repaint_handle(handle.clone());
+    assert_eq!(line_editor.idle_timeout(), Some(HANDLE_POLL_INTERVAL));
+
+    handle.request_repaint();
+    assert!(line_editor.repaint_handle.as_ref().unwrap().take_request());
+}
+
+#[test]
+fn frecency_sorted_menus_learn_the_picked_suggestions() {
+    use crate::{ColumnarMenu, DefaultCompleter, MenuBuilder, SuggestionSort};
+
+    let prompt = crate::DefaultPrompt::default();
+    let completer =
+        DefaultCompleter::new_with_wordlen(vec!["checkout".to_string(), "commit".to_string()], 2);
+    let mut line_editor = Reedline::create()
+        .with_completer(Box::new(completer))
+        .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default()
+                .with_name("completion_menu")
+                .with_sort(SuggestionSort::Frecency),
+        )));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    let complete = |line_editor: &mut Reedline| {
+        line_editor.run_edit_commands(&[EditCommand::Clear, EditCommand::InsertString("c".into())]);
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::Menu("completion_menu".into()))
+            .unwrap();
+        line_editor.repaint(&prompt).unwrap();
+        line_editor.menus[0]
+            .get_values()
+            .iter()
+            .map(|suggestion| suggestion.value.trim_end().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(complete(&mut line_editor), ["checkout", "commit"]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::MenuNext)
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Enter)
+        .unwrap();
+    assert!(line_editor.current_buffer_contents().starts_with("commit"));
+
+    assert_eq!(complete(&mut line_editor), ["commit", "checkout"]);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn history_menu_entries_can_be_deleted_and_edited() {
+    use crate::{ListMenu, MenuBuilder};
+
+    let prompt = crate::DefaultPrompt::default();
+    let history = crate::SqliteBackedHistory::in_memory().unwrap();
+    let mut line_editor = Reedline::create()
+        .with_history(Box::new(history))
+        .with_menu(ReedlineMenu::HistoryMenu(Box::new(
+            ListMenu::default().with_name("history_menu"),
+        )));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    for line in ["ls", "cd", "ls -l", "cd"] {
+        line_editor.run_edit_commands(&[EditCommand::InsertString(line.to_string())]);
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::Submit)
+            .unwrap();
+    }
+    let open_menu = |line_editor: &mut Reedline| {
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::Menu("history_menu".into()))
+            .unwrap();
+        line_editor.repaint(&prompt).unwrap();
+        line_editor.menus[0]
+            .get_values()
+            .iter()
+            .map(|suggestion| suggestion.value.clone())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(open_menu(&mut line_editor), ["cd", "ls -l", "ls"]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::DeleteHistoryItem)
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    assert_eq!(line_editor.history.count_all().unwrap(), 2);
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::EditHistoryItem)
+        .unwrap();
+    assert!(line_editor.active_menu().is_none());
+    assert_eq!(line_editor.current_buffer_contents(), "ls -l");
+    line_editor.run_edit_commands(&[EditCommand::InsertString("a".to_string())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Submit)
+        .unwrap();
+    assert_eq!(open_menu(&mut line_editor), ["ls -la", "ls"]);
+}
+
+#[test]
+fn history_search_results_can_be_edited() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ls".to_string())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Submit)
+        .unwrap();
+    let id = line_editor.history_last_run_id.unwrap();
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::SearchHistory)
+        .unwrap();
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('l')]),
+        )
+        .unwrap();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::EditHistoryItem)
+        .unwrap();
+
+    assert_eq!(line_editor.input_mode, InputMode::Regular);
+    assert_eq!(line_editor.current_buffer_contents(), "ls");
+    assert_eq!(line_editor.edited_history_item, Some(id));
+}
+
+#[test]
+fn history_menu_entries_can_be_pinned() {
+    use crate::{ListMenu, MenuBuilder};
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_menu(ReedlineMenu::HistoryMenu(Box::new(
+        ListMenu::default().with_name("history_menu"),
+    )));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    for line in ["ls", "cd", "ls -l", "cd"] {
+        line_editor.run_edit_commands(&[EditCommand::InsertString(line.to_string())]);
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::Submit)
+            .unwrap();
+    }
+    let menu_values = |line_editor: &mut Reedline| {
+        line_editor.repaint(&prompt).unwrap();
+        line_editor.menus[0]
+            .get_values()
+            .iter()
+            .map(|suggestion| suggestion.value.clone())
+            .collect::<Vec<_>>()
+    };
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Menu("history_menu".into()))
+        .unwrap();
+    assert_eq!(menu_values(&mut line_editor), ["cd", "ls -l", "ls"]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::MenuNext)
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::TogglePinHistoryItem)
+        .unwrap();
+    assert_eq!(menu_values(&mut line_editor), ["ls -l", "cd", "ls"]);
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::TogglePinHistoryItem)
+        .unwrap();
+    assert_eq!(menu_values(&mut line_editor), ["cd", "ls -l", "ls"]);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn directory_history_only_navigates_the_entries_of_the_directory() {
+    let prompt = crate::DefaultPrompt::default();
+    let cwd = std::env::current_dir()
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+    let mut history = crate::SqliteBackedHistory::in_memory().unwrap();
+    for (command_line, dir) in [
+        ("cargo test", cwd.clone()),
+        ("ls src", format!("{cwd}/src")),
+        ("cd /", "/".to_string()),
+    ] {
+        history
+            .save(HistoryItem {
+                cwd: Some(dir),
+                ..HistoryItem::from_command_line(command_line)
+            })
+            .unwrap();
+    }
+    let mut line_editor = Reedline::create()
+        .with_history(Box::new(history))
+        .with_directory_history(true, DirectoryHistoryScope::Directory);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    let previous = |line_editor: &mut Reedline| {
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::PreviousHistory)
+            .unwrap();
+        line_editor.current_buffer_contents().to_string()
+    };
+
+    assert_eq!(previous(&mut line_editor), "cargo test");
+    assert_eq!(previous(&mut line_editor), "cargo test");
+
+    line_editor.run_edit_commands(&[EditCommand::Clear]);
+    line_editor.input_mode = InputMode::Regular;
+    line_editor.directory_history_scope = DirectoryHistoryScope::Subtree;
+    assert_eq!(previous(&mut line_editor), "ls src");
+    assert_eq!(previous(&mut line_editor), "cargo test");
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::ToggleDirectoryHistory)
+        .unwrap();
+    assert!(!line_editor.directory_history());
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::SearchHistory)
+        .unwrap();
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('c')]),
+        )
+        .unwrap();
+    assert_eq!(
+        line_editor.history_cursor.string_at_cursor().as_deref(),
+        Some("cd /")
+    );
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::ToggleDirectoryHistory)
+        .unwrap();
+    assert_eq!(
+        line_editor.history_cursor.string_at_cursor().as_deref(),
+        Some("ls src")
+    );
+}
+
+#[test]
+fn directory_history_needs_a_history_with_directories() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::ToggleDirectoryHistory)
+        .unwrap();
+    assert!(!line_editor.directory_history());
+    assert!(line_editor.command_line_message.is_some());
+}
+
+#[test]
+fn the_context_is_handed_to_the_completer() {
+    use crate::{ColumnarMenu, MenuBuilder, Span, Suggestion};
+
+    struct Variables(Vec<&'static str>);
+
+    struct VariableCompleter;
+
+    impl Completer for VariableCompleter {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            vec![]
+        }
+
+        fn complete_in_context(
+            &mut self,
+            line: &str,
+            pos: usize,
+            context: &dyn Any,
+        ) -> Vec<Suggestion> {
+            let variables = context.downcast_ref::<Variables>().unwrap();
+            variables
+                .0
+                .iter()
+                .filter(|variable| variable.starts_with(line))
+                .map(|variable| Suggestion {
+                    value: variable.to_string(),
+                    span: Span::new(0, pos),
+                    ..Suggestion::default()
+                })
+                .collect()
+        }
+    }
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create()
+        .with_completer(Box::new(VariableCompleter))
+        .with_completion_cache(true)
+        .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default().with_name("completion_menu"),
+        )))
+        .with_context(Variables(vec!["$HOME"]));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("$".into())]);
+    let complete = |line_editor: &mut Reedline| {
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::Esc)
+            .unwrap();
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::Menu("completion_menu".into()))
+            .unwrap();
+        line_editor.repaint(&prompt).unwrap();
+        line_editor.menus[0]
+            .get_values()
+            .iter()
+            .map(|suggestion| suggestion.value.clone())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(complete(&mut line_editor), ["$HOME"]);
+    line_editor
+        .context_mut::<Variables>()
+        .unwrap()
+        .0
+        .push("$PWD");
+    assert_eq!(complete(&mut line_editor), ["$HOME", "$PWD"]);
+    assert!(line_editor.context::<String>().is_none());
+}
+
+#[test]
+fn the_post_insert_hook_completes_again_after_a_directory() {
+    use crate::{ColumnarMenu, DefaultCompleter, MenuBuilder, Suggestion};
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut completer = DefaultCompleter::with_inclusions(&['/']);
+    completer.insert(vec!["src/".into(), "srcset".into()]);
+    let mut line_editor = Reedline::create()
+        .with_completer(Box::new(completer))
+        .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default()
+                .with_name("completion_menu")
+                .with_post_insert(|suggestion: &Suggestion| {
+                    suggestion
+                        .value
+                        .ends_with('/')
+                        .then(|| ReedlineEvent::Menu("completion_menu".into()))
+                }),
+        )));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("sr".into())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Menu("completion_menu".into()))
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Enter)
+        .unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "src/");
+    assert!(line_editor.menus[0].is_active());
+}
+
+#[test]
+fn a_line_submitted_by_a_menu_is_accepted_from_the_menu() {
+    use crate::{ColumnarMenu, DefaultCompleter, MenuBuilder, Suggestion};
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut completer = DefaultCompleter::default();
+    completer.insert(vec!["status".into(), "stash".into()]);
+    let mut line_editor = Reedline::create()
+        .with_completer(Box::new(completer))
+        .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default()
+                .with_name("completion_menu")
+                .with_post_insert(|_: &Suggestion| Some(ReedlineEvent::Submit)),
+        )));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("st".into())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Menu("completion_menu".into()))
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::Enter),
+        Ok(EventStatus::Exits(Signal::Success(_)))
+    ));
+    assert_eq!(
+        line_editor.last_signal_info().map(|info| &info.reason),
+        Some(&SignalReason::MenuAccept)
+    );
+
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ls".into())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Enter)
+        .unwrap();
+    assert_eq!(
+        line_editor.last_signal_info().map(|info| &info.reason),
+        Some(&SignalReason::Enter)
+    );
+}
+
+#[test]
+fn the_menu_opens_again_after_a_retrigger_char() {
+    use crate::{ColumnarMenu, DefaultCompleter, MenuBuilder};
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut completer = DefaultCompleter::with_inclusions(&['/']);
+    completer.insert(vec!["src/".into(), "srcset".into()]);
+    let mut line_editor = Reedline::create()
+        .with_completer(Box::new(completer))
+        .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default()
+                .with_name("completion_menu")
+                .with_retrigger_chars(&['/']),
+        )));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("sr".into())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Menu("completion_menu".into()))
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Enter)
+        .unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "src/");
+    assert!(line_editor.menus[0].is_active());
+}
+
+#[test]
+fn tab_inserts_and_keeps_the_menu_filtering() {
+    use crate::{ColumnarMenu, DefaultCompleter, MenuBuilder};
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut completer = DefaultCompleter::default();
+    completer.insert(vec!["srcdoc".into(), "srcset".into()]);
+    let mut line_editor = Reedline::create()
+        .with_completer(Box::new(completer))
+        .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default()
+                .with_name("completion_menu")
+                .with_insert_and_filter(true),
+        )));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("s".into())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Menu("completion_menu".into()))
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+
+    // The common prefix first, then the selected value
+    for expected in ["src", "srcdoc"] {
+        line_editor
+            .handle_event(&prompt, ReedlineEvent::MenuNext)
+            .unwrap();
+        line_editor.repaint(&prompt).unwrap();
+        assert_eq!(line_editor.current_buffer_contents(), expected);
+        assert!(line_editor.menus[0].is_active());
+    }
+}
+
+#[test]
+fn formatting_the_buffer_is_a_single_undo_step() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_formatter(Box::new(|buffer: &str| {
+        Some(buffer.split_whitespace().collect::<Vec<_>>().join(" "))
+    }));
+    line_editor.run_edit_commands(&[
+        EditCommand::InsertString("ls   -la    src".into()),
+        EditCommand::MoveLeft { select: false },
+    ]);
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::FormatBuffer)
+        .unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "ls -la src");
+    assert_eq!(line_editor.current_insertion_point(), 9);
+    line_editor.run_edit_commands(&[EditCommand::Undo]);
+    assert_eq!(line_editor.current_buffer_contents(), "ls   -la    src");
+}
+
+#[test]
+fn the_selection_is_sent_without_ending_the_read() {
+    use std::sync::{Arc, Mutex};
+
+    let prompt = crate::DefaultPrompt::default();
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let handler_sent = Arc::clone(&sent);
+    let mut line_editor = Reedline::create().with_selection_handler(move |action, text| {
+        handler_sent
+            .lock()
+            .unwrap()
+            .push(format!("{action}: {text}"));
+    });
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ls -la | wc".into())]);
+
+    let send = ReedlineEvent::SendSelection("run".into());
+    let status = line_editor.handle_event(&prompt, send.clone()).unwrap();
+    assert!(matches!(status, EventStatus::Handled));
+    line_editor.set_selection(Some((0, 6)));
+    line_editor.handle_event(&prompt, send).unwrap();
+    assert_eq!(*sent.lock().unwrap(), ["run: ls -la | wc", "run: ls -la"]);
+    assert_eq!(line_editor.current_selection(), Some(0..6));
+    assert_eq!(line_editor.current_buffer_contents(), "ls -la | wc");
+}
+
+#[test]
+fn the_buffer_is_dimmed_while_a_menu_is_active() {
+    use crate::{ColumnarMenu, DefaultCompleter, MenuBuilder};
+
+    let prompt = crate::DefaultPrompt::default();
+    let completer = DefaultCompleter::new_with_wordlen(vec!["lsblk".to_string()], 2);
+    let mut line_editor = Reedline::create()
+        .with_completer(Box::new(completer))
+        .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default().with_name("completion_menu"),
+        )))
+        .with_inactive_buffer_style(Some(Style::new().dimmed()));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ls".into())]);
+    // Dimmed, maybe together with the style of the highlighter
+    let dim = "\u{1b}[2";
+
+    line_editor.repaint(&prompt).unwrap();
+    assert!(!line_editor.rendered_buffer.0.contains(dim));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Menu("completion_menu".into()))
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    assert!(line_editor.rendered_buffer.0.contains(dim));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Esc)
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    assert!(!line_editor.rendered_buffer.0.contains(dim));
+}
+
+#[test]
+fn the_key_overlay_shows_the_pressed_keys() {
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_key_overlay(Some(Duration::from_secs(60)));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    for (modifiers, code) in [
+        (KeyModifiers::NONE, KeyCode::Char('l')),
+        (KeyModifiers::NONE, KeyCode::Char('s')),
+        (KeyModifiers::CONTROL, KeyCode::Char('a')),
+    ] {
+        let event = Event::Key(KeyEvent::new(code, modifiers));
+        let event = line_editor.parse_raw_event(ReedlineRawEvent::convert_from(event).unwrap());
+        line_editor.handle_event(&prompt, event).unwrap();
+    }
+    line_editor.repaint(&prompt).unwrap();
+
+    assert!(line_editor.rendered_buffer.1.contains("ls ⌃A"));
+    assert!(line_editor.idle_timeout().is_some());
+}
+
+#[test]
+fn the_key_overlay_keeps_masked_input_secret() {
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_key_overlay(Some(Duration::from_secs(60)));
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    let press = |line_editor: &mut Reedline, c| {
+        let event = Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        let event = line_editor.parse_raw_event(ReedlineRawEvent::convert_from(event).unwrap());
+        line_editor.handle_event(&prompt, event).unwrap();
+    };
+    press(&mut line_editor, 'p');
+    line_editor.input_mask = Some(Some('*'));
+    for c in "hunter2".chars() {
+        press(&mut line_editor, c);
+    }
+    line_editor.repaint(&prompt).unwrap();
+    assert!(!line_editor.rendered_buffer.1.contains('p'));
+    assert!(!line_editor.rendered_buffer.1.contains("hunter2"));
+
+    line_editor.input_mask = None;
+    line_editor.repaint(&prompt).unwrap();
+    assert!(strip_ansi(&line_editor.rendered_buffer.1).ends_with(" p"));
+}
+
+#[test]
+fn the_debug_state_describes_the_engine() {
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_edit_mode(Box::<crate::Vi>::default());
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit(vec![EditCommand::InsertString("ls -l".into())]),
+        )
+        .unwrap();
+    for code in [KeyCode::Esc, KeyCode::Char('2'), KeyCode::Char('d')] {
+        let event = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        let event = line_editor.parse_raw_event(ReedlineRawEvent::convert_from(event).unwrap());
+        line_editor.handle_event(&prompt, event).unwrap();
+    }
+
+    let state = line_editor.debug_state();
+    assert_eq!(state.buffer, "ls -l");
+    assert_eq!(state.cursor, 5);
+    assert_eq!(state.active_menu, None);
+    assert_eq!(state.undo_depth, 1);
+    assert_eq!(state.pending_keys, "2d");
+    assert!(matches!(
+        state.mode,
+        PromptEditMode::Vi(crate::PromptViMode::Pending(crate::PromptViPending {
+            count: Some(2),
+            ..
+        }))
+    ));
+    assert!(matches!(state.last_event, Some(ReedlineEvent::Multiple(_))));
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::ToggleDebugPanel)
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    assert!(line_editor
+        .rendered_buffer
+        .1
+        .contains("last event: ToggleDebugPanel"));
+}
+
+#[test]
+fn failing_to_save_the_history_does_not_end_the_line() {
+    use std::sync::{Arc, Mutex};
+
+    // Every write fails, e.g. because the disk is full
+    struct FullHistory;
+
+    impl History for FullHistory {
+        fn save(&mut self, _: HistoryItem) -> crate::Result<HistoryItem> {
+            Err(io::Error::new(io::ErrorKind::Other, "disk full").into())
+        }
+        fn load(&self, _: HistoryItemId) -> crate::Result<HistoryItem> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "empty").into())
+        }
+        fn count(&self, _: SearchQuery) -> crate::Result<i64> {
+            Ok(0)
+        }
+        fn search(&self, _: SearchQuery) -> crate::Result<Vec<HistoryItem>> {
+            Ok(vec![])
+        }
+        fn update(
+            &mut self,
+            _: HistoryItemId,
+            _: &dyn Fn(HistoryItem) -> HistoryItem,
+        ) -> crate::Result<()> {
+            self.save(HistoryItem::from_command_line("")).map(|_| ())
+        }
+        fn clear(&mut self) -> crate::Result<()> {
+            Ok(())
+        }
+        fn delete(&mut self, _: HistoryItemId) -> crate::Result<()> {
+            Ok(())
+        }
+        fn sync(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+        fn session(&self) -> Option<HistorySessionId> {
+            None
+        }
+    }
+
+    let prompt = crate::DefaultPrompt::default();
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let handler_reported = Arc::clone(&reported);
+    let mut line_editor = Reedline::create()
+        .with_history(Box::new(FullHistory))
+        .with_history_error_handler(move |err| {
+            handler_reported.lock().unwrap().push(err.category())
+        });
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ls".into())]);
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::Enter),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "ls"
+    ));
+    assert_eq!(
+        *reported.lock().unwrap(),
+        [crate::ReedlineErrorCategory::HistoryBackend]
+    );
+    assert!(!line_editor.has_last_command_context());
+}
+
+// A history whose searches fail, e.g. because its database went away
+#[cfg(test)]
+struct UnsearchableHistory(Vec<String>);
+
+#[cfg(test)]
+impl History for UnsearchableHistory {
+    fn save(&mut self, mut h: HistoryItem) -> crate::Result<HistoryItem> {
+        h.id = Some(HistoryItemId::new(self.0.len() as i64));
+        self.0.push(h.command_line.clone());
+        Ok(h)
+    }
+    fn load(&self, id: HistoryItemId) -> crate::Result<HistoryItem> {
+        let mut item = HistoryItem::from_command_line(&self.0[id.0 as usize]);
+        item.id = Some(id);
+        Ok(item)
+    }
+    fn count(&self, _: SearchQuery) -> crate::Result<i64> {
+        Ok(self.0.len() as i64)
+    }
+    fn search(&self, _: SearchQuery) -> crate::Result<Vec<HistoryItem>> {
+        Err(io::Error::new(io::ErrorKind::Other, "database is gone").into())
+    }
+    fn update(
+        &mut self,
+        _: HistoryItemId,
+        _: &dyn Fn(HistoryItem) -> HistoryItem,
+    ) -> crate::Result<()> {
+        Ok(())
+    }
+    fn clear(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+    fn delete(&mut self, _: HistoryItemId) -> crate::Result<()> {
+        Ok(())
+    }
+    fn sync(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+    fn session(&self) -> Option<HistorySessionId> {
+        None
+    }
+}
+
+// A line editor with an `UnsearchableHistory` holding `lines`, and the reported errors
+#[cfg(test)]
+fn unsearchable_line_editor(lines: &[&str]) -> (Reedline, std::sync::Arc<std::sync::Mutex<usize>>) {
+    let reported = std::sync::Arc::new(std::sync::Mutex::new(0));
+    let handler_reported = std::sync::Arc::clone(&reported);
+    let history = UnsearchableHistory(lines.iter().map(|line| line.to_string()).collect());
+    let mut line_editor = Reedline::create()
+        .with_history(Box::new(history))
+        .with_history_error_handler(move |_| *handler_reported.lock().unwrap() += 1);
+    line_editor
+        .handle_event(
+            &crate::DefaultPrompt::default(),
+            ReedlineEvent::Resize(80, 24),
+        )
+        .unwrap();
+    (line_editor, reported)
+}
+
+#[test]
+fn failing_to_find_the_next_history_entry_still_submits_the_line() {
+    let prompt = crate::DefaultPrompt::default();
+    let (mut line_editor, reported) = unsearchable_line_editor(&["ls", "cd .."]);
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ls".into())]);
+    line_editor.history_recalled_id = Some(HistoryItemId::new(0));
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::OperateAndGetNext),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "ls"
+    ));
+    assert_eq!(line_editor.current_buffer_contents(), "");
+    assert_eq!(*reported.lock().unwrap(), 1);
+}
+
+#[test]
+fn failing_to_search_after_deleting_an_entry_keeps_the_history_search() {
+    let prompt = crate::DefaultPrompt::default();
+    let (mut line_editor, reported) = unsearchable_line_editor(&["ls", "cd .."]);
+    let entry = line_editor.history.load(HistoryItemId::new(1)).unwrap();
+    line_editor.input_mode = InputMode::HistorySearch;
+    line_editor.history_cursor = HistoryCursor::new_at(
+        HistoryNavigationQuery::SubstringSearch("cd".to_string()),
+        None,
+        entry,
+    );
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::DeleteHistoryItem),
+        Ok(EventStatus::Handled)
+    ));
+    assert!(line_editor.input_mode == InputMode::HistorySearch);
+    assert_eq!(*reported.lock().unwrap(), 1);
+}
+
+#[test]
+fn failing_to_search_after_toggling_the_directory_filter_keeps_the_history_search() {
+    let prompt = crate::DefaultPrompt::default();
+    let (mut line_editor, reported) = unsearchable_line_editor(&["ls"]);
+    line_editor.input_mode = InputMode::HistorySearch;
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::ToggleDirectoryHistory),
+        Ok(EventStatus::Handled)
+    ));
+    assert!(line_editor.input_mode == InputMode::HistorySearch);
+    assert_eq!(*reported.lock().unwrap(), 1);
+}
+
+#[test]
+fn failing_to_search_the_history_keeps_the_buffer_and_the_search() {
+    let prompt = crate::DefaultPrompt::default();
+    let (mut line_editor, reported) = unsearchable_line_editor(&["ls"]);
+    line_editor.run_edit_commands(&[EditCommand::InsertString("cd".into())]);
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::Up),
+        Ok(EventStatus::Handled)
+    ));
+    assert_eq!(line_editor.current_buffer_contents(), "cd");
+    assert_eq!(*reported.lock().unwrap(), 1);
+
+    line_editor.input_mode = InputMode::HistorySearch;
+    for event in [
+        ReedlineEvent::Edit(vec![EditCommand::InsertChar('l')]),
+        ReedlineEvent::Up,
+        ReedlineEvent::Down,
+    ] {
+        assert!(matches!(
+            line_editor.handle_event(&prompt, event),
+            Ok(EventStatus::Handled)
+        ));
+    }
+    assert!(line_editor.input_mode == InputMode::HistorySearch);
+    assert_eq!(*reported.lock().unwrap(), 4);
+}
+
+#[test]
+fn deleting_in_the_history_search_reports_an_unsupported_history() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut history = Box::<crate::FileBackedHistory>::default();
+    for command_line in ["git status", "ls"] {
+        history
+            .save(HistoryItem::from_command_line(command_line))
+            .unwrap();
+    }
+    let mut line_editor = Reedline::create().with_history(history);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::SearchHistory)
+        .unwrap();
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('l')]),
+        )
+        .unwrap();
+    assert_eq!(line_editor.history_cursor.string_at_cursor().unwrap(), "ls");
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::DeleteHistoryItem),
+        Ok(EventStatus::Handled)
+    ));
+    assert!(line_editor
+        .command_line_message
+        .as_deref()
+        .unwrap()
+        .starts_with("Can't delete the history entry"));
+    assert!(line_editor.input_mode == InputMode::HistorySearch);
+    assert_eq!(line_editor.history_cursor.string_at_cursor().unwrap(), "ls");
+}
+
+#[test]
+fn pinning_in_the_history_search_reports_an_unsupported_history() {
+    let prompt = crate::DefaultPrompt::default();
+    let (mut line_editor, _) = unsearchable_line_editor(&["ls"]);
+    let entry = line_editor.history.load(HistoryItemId::new(0)).unwrap();
+    line_editor.input_mode = InputMode::HistorySearch;
+    line_editor.history_cursor = HistoryCursor::new_at(
+        HistoryNavigationQuery::SubstringSearch("ls".to_string()),
+        None,
+        entry,
+    );
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::TogglePinHistoryItem),
+        Ok(EventStatus::Handled)
+    ));
+    assert!(line_editor
+        .command_line_message
+        .as_deref()
+        .unwrap()
+        .starts_with("Can't pin the history entry"));
+}
+
+#[test]
+fn history_search_cycles_through_the_matches_and_restores_the_buffer() {
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut history = Box::<crate::FileBackedHistory>::default();
+    for command_line in ["git status", "git log", "ls", "git diff"] {
+        history
+            .save(HistoryItem::from_command_line(command_line))
+            .unwrap();
+    }
+    let mut line_editor = Reedline::create().with_history(history);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[
+        EditCommand::InsertString("echo hi".into()),
+        EditCommand::MoveLeft { select: false },
+        EditCommand::MoveLeft { select: false },
+    ]);
+
+    let press = |line_editor: &mut Reedline, code| {
+        let event = line_editor.parse_raw_event(
+            ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(code, KeyModifiers::CONTROL)))
+                .unwrap(),
+        );
+        line_editor.handle_event(&prompt, event).unwrap();
+    };
+    press(&mut line_editor, KeyCode::Char('r'));
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit("git".chars().map(EditCommand::InsertChar).collect()),
+        )
+        .unwrap();
+    assert_eq!(
+        line_editor.history_search_match_position("git"),
+        Some((1, 3))
+    );
+    press(&mut line_editor, KeyCode::Char('r'));
+    press(&mut line_editor, KeyCode::Char('r'));
+    assert_eq!(
+        line_editor.history_cursor.string_at_cursor().as_deref(),
+        Some("git status")
+    );
+    assert_eq!(
+        line_editor.history_search_match_position("git"),
+        Some((3, 3))
+    );
+    press(&mut line_editor, KeyCode::Char('s'));
+    assert_eq!(
+        line_editor.history_search_match_position("git"),
+        Some((2, 3))
+    );
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Esc)
+        .unwrap();
+    assert_eq!(line_editor.input_mode, InputMode::Regular);
+    assert_eq!(line_editor.current_buffer_contents(), "echo hi");
+    assert_eq!(line_editor.editor.insertion_point(), 5);
+}
+
+#[test]
+fn the_buffer_can_be_searched_incrementally() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[
+        EditCommand::InsertString("let a = 1;\nlet b = a;\nlet c = b;".into()),
+        EditCommand::MoveToStart { select: false },
+    ]);
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::SearchBuffer)
+        .unwrap();
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit("let".chars().map(EditCommand::InsertChar).collect()),
+        )
+        .unwrap();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::SearchBuffer)
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    assert!(line_editor.rendered_buffer.0.ends_with("Search (2/3): let"));
+    assert_eq!(line_editor.editor.insertion_point(), 11);
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Enter)
+        .unwrap();
+    assert_eq!(line_editor.input_mode, InputMode::Regular);
+    assert_eq!(line_editor.editor.insertion_point(), 11);
+    assert_eq!(
+        line_editor.current_buffer_contents(),
+        "let a = 1;\nlet b = a;\nlet c = b;"
+    );
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::SearchBuffer)
+        .unwrap();
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('c')]),
+        )
+        .unwrap();
+    assert_eq!(line_editor.editor.insertion_point(), 26);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Esc)
+        .unwrap();
+    assert_eq!(line_editor.editor.insertion_point(), 11);
+}
+
+#[test]
+fn words_are_completed_from_the_buffer_and_the_history() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut history = Box::<crate::FileBackedHistory>::default();
+    for command_line in ["cargo test --release", "cargo run --example demo"] {
+        history
+            .save(HistoryItem::from_command_line(command_line))
+            .unwrap();
+    }
+    let mut line_editor = Reedline::create().with_history(history);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("cargo b --ex".into())]);
+
+    let mut complete = |event| {
+        let status = line_editor.handle_event(&prompt, event).unwrap();
+        (
+            matches!(status, EventStatus::Handled),
+            line_editor.editor.get_buffer().to_string(),
+        )
+    };
+    assert_eq!(
+        complete(ReedlineEvent::CompleteWordNext),
+        (true, "cargo b --example".to_string())
+    );
+    assert_eq!(
+        complete(ReedlineEvent::CompleteWordNext),
+        (true, "cargo b --ex".to_string())
+    );
+    assert_eq!(
+        complete(ReedlineEvent::Edit(vec![EditCommand::InsertString(
+            "ample ca".into()
+        )])),
+        (true, "cargo b --example ca".to_string())
+    );
+    assert_eq!(
+        complete(ReedlineEvent::CompleteWordPrevious),
+        (true, "cargo b --example cargo".to_string())
+    );
+    assert_eq!(
+        complete(ReedlineEvent::Edit(vec![EditCommand::InsertString(
+            " zz".into()
+        )])),
+        (true, "cargo b --example cargo zz".to_string())
+    );
+    assert_eq!(
+        complete(ReedlineEvent::CompleteWordNext),
+        (false, "cargo b --example cargo zz".to_string())
+    );
+}
+
+#[test]
+fn the_buffer_is_validated_for_the_prompt_indicator_after_the_debounce() {
+    let prompt = crate::DefaultPrompt::default();
+    let validation = |line_editor: &Reedline| {
+        line_editor
+            .live_validation
+            .as_ref()
+            .map(|(_, validation)| *validation)
+    };
+
+    let mut line_editor = Reedline::create()
+        .with_validator(Box::new(crate::DefaultValidator))
+        .with_live_validation(Duration::ZERO);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("echo (a".into())]);
+    line_editor.repaint(&prompt).unwrap();
+    assert_eq!(validation(&line_editor), Some(ValidationResult::Incomplete));
+
+    line_editor.live_validation_debounce = Some(Duration::from_secs(3600));
+    line_editor.run_edit_commands(&[EditCommand::InsertChar(')')]);
+    line_editor.repaint(&prompt).unwrap();
+    assert_eq!(validation(&line_editor), Some(ValidationResult::Incomplete));
+    assert!(line_editor.idle_timeout().unwrap() > Duration::from_secs(3000));
+
+    line_editor.live_validation_debounce = Some(Duration::ZERO);
+    line_editor.live_validation_pending = None;
+    assert!(line_editor.update_live_validation());
+    assert_eq!(validation(&line_editor), Some(ValidationResult::Complete));
+    assert!(!line_editor.update_live_validation());
+}
+
+#[test]
+fn paragraph_and_sentence_motions_take_operators_and_counts() {
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_edit_mode(Box::<crate::Vi>::default());
+    let type_keys = |line_editor: &mut Reedline, keys: &str| {
+        for c in keys.chars() {
+            let code = if c == '\u{1b}' {
+                KeyCode::Esc
+            } else {
+                KeyCode::Char(c)
+            };
+            let event = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+            let event = line_editor.parse_raw_event(ReedlineRawEvent::convert_from(event).unwrap());
+            line_editor.handle_event(&prompt, event).unwrap();
+        }
+    };
+    line_editor.run_edit_commands(&[
+        EditCommand::InsertString("one\ntwo\n\nthree\n\nfour. Five".into()),
+        EditCommand::MoveToStart { select: false },
+    ]);
+
+    type_keys(&mut line_editor, "\u{1b}d}");
+    assert_eq!(
+        line_editor.current_buffer_contents(),
+        "\nthree\n\nfour. Five"
+    );
+    type_keys(&mut line_editor, "}");
+    assert_eq!(line_editor.current_insertion_point(), 7);
+    type_keys(&mut line_editor, "2)");
+    assert_eq!(line_editor.current_insertion_point(), 14);
+    type_keys(&mut line_editor, "d(");
+    assert_eq!(line_editor.current_buffer_contents(), "\nthree\n\nFive");
+}
+
+#[test]
+fn viewport_motions_move_within_the_lines_on_the_screen() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(40, 10))
+        .unwrap();
+    let buffer = (0..30).map(|line| format!("line{line}")).join("\n");
+    line_editor.run_edit_commands(&[EditCommand::InsertString(buffer)]);
+    line_editor.repaint(&prompt).unwrap();
+    let cursor_line = |line_editor: &Reedline| {
+        line_editor.editor.get_buffer()[..line_editor.editor.insertion_point()]
+            .matches('\n')
+            .count()
+    };
+
+    let visible = line_editor.painter.visible_lines().unwrap();
+    assert_eq!(visible.end, 30);
+    assert_eq!(cursor_line(&line_editor), 29);
+
+    // The lines stay where they are while the cursor moves between them
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::MoveToViewport(ViewportLine::Top))
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    assert_eq!(cursor_line(&line_editor), visible.start);
+    assert_eq!(line_editor.painter.visible_lines(), Some(visible.clone()));
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::MoveToViewport(ViewportLine::Middle))
+        .unwrap();
+    let middle = cursor_line(&line_editor);
+    assert_eq!(middle, visible.start + (visible.len() - 1) / 2);
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::ScrollViewport(ViewportLine::Top))
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    assert_eq!(line_editor.painter.visible_lines().unwrap().start, middle);
+    assert_eq!(cursor_line(&line_editor), middle);
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::ScrollViewport(ViewportLine::Bottom))
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    assert_eq!(line_editor.painter.visible_lines().unwrap().end, middle + 1);
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::ScrollViewport(ViewportLine::Middle))
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    let visible = line_editor.painter.visible_lines().unwrap();
+    assert_eq!(visible.start + (visible.len() - 1) / 2, middle);
+
+    line_editor.run_edit_commands(&[EditCommand::Clear]);
+    line_editor.repaint(&prompt).unwrap();
+    assert_eq!(line_editor.painter.visible_lines(), None);
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::ScrollViewport(ViewportLine::Top)),
+        Ok(EventStatus::Inapplicable)
+    ));
+}
+
+#[test]
+fn vi_viewport_keys_move_and_scroll_a_buffer_taller_than_the_screen() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_edit_mode(Box::<crate::Vi>::default());
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(40, 10))
+        .unwrap();
+    let type_keys = |line_editor: &mut Reedline, keys: &str| {
+        for c in keys.chars() {
+            let code = if c == '\u{1b}' {
+                KeyCode::Esc
+            } else {
+                KeyCode::Char(c)
+            };
+            let event = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+            let event = line_editor.parse_raw_event(ReedlineRawEvent::convert_from(event).unwrap());
+            line_editor.handle_event(&prompt, event).unwrap();
+            line_editor.repaint(&prompt).unwrap();
+        }
+    };
+    let cursor_line = |line_editor: &Reedline| {
+        line_editor.editor.get_buffer()[..line_editor.editor.insertion_point()]
+            .matches('\n')
+            .count()
+    };
+    let buffer = (0..30).map(|line| format!("line{line}")).join("\n");
+    line_editor.run_edit_commands(&[EditCommand::InsertString(buffer)]);
+    line_editor.repaint(&prompt).unwrap();
+    type_keys(&mut line_editor, "\u{1b}");
+
+    // Only the last lines of the buffer fit on the screen
+    let visible = line_editor.painter.visible_lines().unwrap();
+    assert!(visible.start > 0 && visible.len() <= 10);
+    assert_eq!(visible.end, 30);
+
+    type_keys(&mut line_editor, "H");
+    assert_eq!(cursor_line(&line_editor), visible.start);
+    assert_eq!(
+        line_editor
+            .current_buffer_contents()
+            .find(&format!("line{}", visible.start)),
+        Some(line_editor.current_insertion_point())
+    );
+    type_keys(&mut line_editor, "L");
+    assert_eq!(cursor_line(&line_editor), 29);
+    type_keys(&mut line_editor, "M");
+    assert_eq!(
+        cursor_line(&line_editor),
+        visible.start + (visible.len() - 1) / 2
+    );
+    assert_eq!(line_editor.painter.visible_lines(), Some(visible.clone()));
+
+    // Moving up past the screen scrolls it, L then jumps to the new last line
+    type_keys(&mut line_editor, "29k");
+    assert_eq!(cursor_line(&line_editor), 0);
+    let top = line_editor.painter.visible_lines().unwrap();
+    assert_eq!(top.start, 0);
+    assert_eq!(top.len(), visible.len());
+    type_keys(&mut line_editor, "L");
+    assert_eq!(cursor_line(&line_editor), top.end - 1);
+
+    // zt, zz and zb scroll around the cursor line, which stays where it is
+    type_keys(&mut line_editor, "5jzt");
+    let line = top.end + 4;
+    assert_eq!(cursor_line(&line_editor), line);
+    assert_eq!(line_editor.painter.visible_lines().unwrap().start, line);
+    type_keys(&mut line_editor, "zb");
+    assert_eq!(line_editor.painter.visible_lines().unwrap().end, line + 1);
+    type_keys(&mut line_editor, "zz");
+    let centered = line_editor.painter.visible_lines().unwrap();
+    assert_eq!(centered.start + (centered.len() - 1) / 2, line);
+    assert_eq!(cursor_line(&line_editor), line);
+
+    // H and L stay within the scrolled view
+    type_keys(&mut line_editor, "H");
+    assert_eq!(cursor_line(&line_editor), centered.start);
+    type_keys(&mut line_editor, "L");
+    assert_eq!(cursor_line(&line_editor), centered.end - 1);
+    assert_eq!(line_editor.painter.visible_lines(), Some(centered));
+}
+
+#[test]
+fn vi_viewport_keys_stay_within_a_buffer_that_fits_on_the_screen() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_edit_mode(Box::<crate::Vi>::default());
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(40, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[
+        EditCommand::InsertString("one\ntwo\nthree".into()),
+        EditCommand::MoveToStart { select: false },
+    ]);
+    line_editor.repaint(&prompt).unwrap();
+    assert_eq!(line_editor.painter.visible_lines(), None);
+
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::MoveToViewport(ViewportLine::Bottom))
+        .unwrap();
+    assert_eq!(line_editor.current_insertion_point(), 8);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::MoveToViewport(ViewportLine::Middle))
+        .unwrap();
+    assert_eq!(line_editor.current_insertion_point(), 4);
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::ScrollViewport(ViewportLine::Middle)),
+        Ok(EventStatus::Inapplicable)
+    ));
+}
+
+#[test]
+fn pasted_blocks_are_painted_folded_and_submitted_whole() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_paste_folding(3);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    let pasted = "1\n2\n3\n4";
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit(vec![EditCommand::InsertChar('[')]),
+        )
+        .unwrap();
+    line_editor
+        .handle_event(
+            &prompt,
+            ReedlineEvent::Edit(vec![EditCommand::InsertString(pasted.into())]),
+        )
+        .unwrap();
+    line_editor.repaint(&prompt).unwrap();
+    assert_eq!(strip_ansi(&line_editor.rendered_buffer.0), "[⋯ 4 lines");
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::ToggleFolds),
+        Ok(EventStatus::Handled)
+    ));
+    line_editor.repaint(&prompt).unwrap();
+    assert!(!line_editor.rendered_buffer.0.contains('⋯'));
+
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::Enter),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "[1\n2\n3\n4"
+    ));
+}
+
+#[test]
+fn read_only_prefixes_survive_the_edits() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.prefill_buffer("sudo ", None);
+    line_editor.set_read_only(Some(0..5));
+
+    line_editor.run_edit_commands(&[
+        EditCommand::Backspace,
+        EditCommand::Undo,
+        EditCommand::MoveToStart { select: false },
+    ]);
+    assert_eq!(line_editor.current_buffer_contents(), "sudo ");
+    assert_eq!(line_editor.current_insertion_point(), 5);
+    line_editor.run_edit_commands(&[
+        EditCommand::InsertString("ls".into()),
+        EditCommand::MoveToStart { select: false },
+        EditCommand::CutToEnd,
+    ]);
+    assert_eq!(line_editor.current_buffer_contents(), "sudo ");
+
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ls".into())]);
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::Enter),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "sudo ls"
+    ));
+    assert!(line_editor.editor.is_empty());
+}
+
+#[test]
+fn tab_moves_between_the_fields_of_a_template() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    let template = Template::new()
+        .with_text("ssh ")
+        .with_field("user", "root")
+        .with_text("@")
+        .with_field("host", "");
+    line_editor.start_template(&template);
+    let mut press = |code, modifiers| {
+        let event = line_editor.parse_raw_event(
+            ReedlineRawEvent::convert_from(Event::Key(KeyEvent::new(code, modifiers))).unwrap(),
+        );
+        line_editor.handle_event(&prompt, event).unwrap()
+    };
+    for code in [
+        KeyCode::Char('m'),
+        KeyCode::Char('e'),
+        KeyCode::Tab,
+        KeyCode::Backspace,
+        KeyCode::Char('h'),
+    ] {
+        press(code, KeyModifiers::NONE);
+    }
+    press(KeyCode::BackTab, KeyModifiers::SHIFT);
+    press(KeyCode::Char('x'), KeyModifiers::NONE);
+    assert!(matches!(
+        press(KeyCode::Enter, KeyModifiers::NONE),
+        EventStatus::Exits(Signal::Success(line)) if line == "ssh x@h"
+    ));
+
+    let fields = line_editor.template_fields.take().unwrap();
+    let values = template.values("ssh x@h", &fields);
+    assert_eq!(values["user"], "x");
+    assert_eq!(values["host"], "h");
+}
+
+#[test]
+fn submissions_report_how_the_line_was_edited() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_hinter(Box::<crate::DefaultHinter>::default());
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[
+        EditCommand::InsertString("hello world".into()),
+        EditCommand::MoveLeft { select: false },
+    ]);
+    let signal = match line_editor.handle_event(&prompt, ReedlineEvent::Enter) {
+        Ok(EventStatus::Exits(signal)) => signal,
+        _ => panic!("the line wasn't submitted"),
+    };
+    let submission = line_editor.submission(signal);
+    assert!(matches!(&submission.signal, Signal::Success(line) if line == "hello world"));
+    assert_eq!(submission.styled_text.raw_string(), "hello world");
+    assert_eq!(submission.cursor_position, 10);
+    assert!(!submission.used_hint && !submission.used_completion);
+
+    // As the next `read_line` would
+    line_editor.line_usage = LineUsage::new();
+    line_editor.hide_hints = false;
+    line_editor.run_edit_commands(&[EditCommand::InsertString("hel".into())]);
+    line_editor.repaint(&prompt).unwrap();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::HistoryHintComplete)
+        .unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "hello world");
+    let submission = line_editor.submission(Signal::CtrlC);
+    assert!(submission.used_hint);
+    assert!(submission.styled_text.raw_string().is_empty());
+}
+
+#[test]
+fn the_submit_echo_paints_the_whole_line_without_the_selection() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_submit_echo(SubmitEcho::new());
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[
+        EditCommand::InsertString("ls -la".into()),
+        EditCommand::MoveToStart { select: false },
+        EditCommand::MoveWordRight { select: true },
+    ]);
+    assert!(matches!(
+        line_editor.handle_event(&prompt, ReedlineEvent::Enter),
+        Ok(EventStatus::Exits(Signal::Success(line))) if line == "ls -la"
+    ));
+    assert!(strip_ansi(&line_editor.rendered_buffer.0).ends_with("ls -la"));
+    assert!(line_editor.rendered_buffer.1.is_empty());
+    assert_eq!(line_editor.last_signal_info().unwrap().cursor_position, 2);
+}
+
+#[test]
+fn the_post_submit_text_of_the_prompt_follows_the_submitted_line() {
+    struct TimedPrompt(crate::DefaultPrompt);
+
+    impl Prompt for TimedPrompt {
+        fn render_prompt_left(&self) -> Cow<'_, str> {
+            self.0.render_prompt_left()
+        }
+
+        fn render_prompt_right(&self) -> Cow<'_, str> {
+            self.0.render_prompt_right()
+        }
+
+        fn render_prompt_indicator(&self, prompt_mode: PromptEditMode) -> Cow<'_, str> {
+            self.0.render_prompt_indicator(prompt_mode)
+        }
+
+        fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
+            self.0.render_prompt_multiline_indicator()
+        }
+
+        fn render_prompt_history_search_indicator(
+            &self,
+            history_search: PromptHistorySearch,
+        ) -> Cow<'_, str> {
+            self.0
+                .render_prompt_history_search_indicator(history_search)
+        }
+
+        fn render_post_submit(&self) -> Cow<'_, str> {
+            "\x1b[2Jtook 3.2s".into()
+        }
+    }
+
+    let prompt = TimedPrompt(crate::DefaultPrompt::default());
+    let mut line_editor = Reedline::create().with_sanitizer(crate::Sanitizer::default());
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::CtrlC)
+        .unwrap();
+    assert_eq!(line_editor.post_submit, None);
+    line_editor.run_edit_commands(&[EditCommand::InsertString("sleep 3".into())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Enter)
+        .unwrap();
+    assert_eq!(line_editor.post_submit.as_deref(), Some("took 3.2s"));
+}
+
+#[test]
+fn conditional_events_depend_on_the_state_of_the_editor() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create().with_hinter(Box::<crate::DefaultHinter>::default());
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("echo hello".into())]);
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Enter)
+        .unwrap();
+    line_editor.hide_hints = false;
+
+    let tab = ReedlineEvent::Conditional {
+        condition: EventCondition::HintVisible,
+        then: Box::new(ReedlineEvent::HistoryHintComplete),
+        otherwise: Box::new(ReedlineEvent::Edit(vec![EditCommand::InsertChar('?')])),
+    };
+    line_editor.run_edit_commands(&[EditCommand::InsertString("ec".into())]);
+    line_editor.repaint(&prompt).unwrap();
+    line_editor.handle_event(&prompt, tab.clone()).unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "echo hello");
+    line_editor.repaint(&prompt).unwrap();
+    line_editor.handle_event(&prompt, tab).unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "echo hello?");
+
+    let right = ReedlineEvent::Conditional {
+        condition: EventCondition::All(vec![
+            EventCondition::Not(Box::new(EventCondition::CursorAtLineEnd)),
+            EventCondition::Not(Box::new(EventCondition::SelectionActive)),
+        ]),
+        then: Box::new(ReedlineEvent::Right),
+        otherwise: Box::new(ReedlineEvent::None),
+    };
+    line_editor.run_edit_commands(&[EditCommand::MoveLeft { select: false }]);
+    line_editor.handle_event(&prompt, right.clone()).unwrap();
+    assert_eq!(line_editor.current_insertion_point(), 11);
+    assert!(matches!(
+        line_editor.handle_event(&prompt, right),
+        Ok(EventStatus::Inapplicable)
+    ));
+    assert_eq!(line_editor.current_insertion_point(), 11);
+}
+
+#[test]
+fn until_found_lists_explain_their_branches() {
+    let line_editor = Reedline::create();
+    let events = vec![
+        ReedlineEvent::HistoryHintComplete,
+        ReedlineEvent::Menu("completion_menu".into()),
+        ReedlineEvent::Conditional {
+            condition: EventCondition::BufferEmpty,
+            then: Box::new(ReedlineEvent::Left),
+            otherwise: Box::new(ReedlineEvent::None),
+        },
+        ReedlineEvent::Right,
+    ];
+    let event = ReedlineEvent::UntilFound(events.clone());
+    assert_eq!(
+        line_editor.explain_until_found(&event),
+        vec![
+            (
+                &ReedlineEvent::HistoryHintComplete,
+                BranchOutcome::Inapplicable("no hint is shown at the cursor")
+            ),
+            (
+                &ReedlineEvent::Menu("completion_menu".into()),
+                BranchOutcome::Inapplicable("there is no menu of that name")
+            ),
+            (&events[2], BranchOutcome::Handled),
+            (&ReedlineEvent::Right, BranchOutcome::NotTried),
+        ]
+    );
+}
+
+#[test]
+fn scrolling_a_buffer_that_fits_on_the_screen_tries_the_next_event() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+    line_editor.run_edit_commands(&[EditCommand::InsertString("one\ntwo".into())]);
+    line_editor.repaint(&prompt).unwrap();
+
+    let event = ReedlineEvent::UntilFound(vec![
+        ReedlineEvent::ScrollViewport(ViewportLine::Top),
+        ReedlineEvent::Up,
+    ]);
+    assert_eq!(
+        line_editor.explain_until_found(&event),
+        vec![
+            (
+                &ReedlineEvent::ScrollViewport(ViewportLine::Top),
+                BranchOutcome::Inapplicable("the buffer fits on the screen")
+            ),
+            (&ReedlineEvent::Up, BranchOutcome::Handled),
+        ]
+    );
+    line_editor.handle_event(&prompt, event).unwrap();
+    assert_eq!(line_editor.editor.line_buffer().line(), 0);
+}
+
+#[test]
+fn inapplicable_reasons_agree_with_handling_the_events() {
+    let prompt = crate::DefaultPrompt::default();
+    let events = vec![
+        ReedlineEvent::None,
+        ReedlineEvent::Menu("completion_menu".into()),
+        ReedlineEvent::Menu("help_menu".into()),
+        ReedlineEvent::MenuNext,
+        ReedlineEvent::HistoryHintComplete,
+        ReedlineEvent::HistoryHintWordComplete,
+        ReedlineEvent::DeleteHistoryItem,
+        ReedlineEvent::CompleteWordNext,
+        ReedlineEvent::FormatBuffer,
+        ReedlineEvent::SendSelection("copy".into()),
+        ReedlineEvent::Custom("shout".into()),
+        ReedlineEvent::ToggleFolds,
+        ReedlineEvent::ExCommand,
+        ReedlineEvent::PreviousHistory,
+        ReedlineEvent::Edit(vec![EditCommand::InsertChar('!')]),
+        ReedlineEvent::Resize(80, 24),
+        ReedlineEvent::ScrollViewport(ViewportLine::Top),
+        ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::MenuNext,
+            ReedlineEvent::HistoryHintComplete,
+        ]),
+    ];
+    for masked in [false, true] {
+        for event in &events {
+            let mut line_editor = Reedline::create()
+                .with_hinter(Box::<crate::DefaultHinter>::default())
+                .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                    crate::ColumnarMenu::default(),
+                )));
+            line_editor
+                .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+                .unwrap();
+            line_editor.run_edit_commands(&[EditCommand::InsertString("echo hello".into())]);
+            line_editor
+                .handle_event(&prompt, ReedlineEvent::Enter)
+                .unwrap();
+            line_editor.hide_hints = false;
+            line_editor.run_edit_commands(&[EditCommand::InsertString("ec".into())]);
+            line_editor.repaint(&prompt).unwrap();
+            if masked {
+                line_editor.input_mask = Some(Some('*'));
+            }
+
+            let reason = line_editor.inapplicable_reason(event);
+            let status = line_editor.handle_event(&prompt, event.clone()).unwrap();
+            assert_eq!(
+                reason.is_some(),
+                matches!(status, EventStatus::Inapplicable),
+                "{event} (masked: {masked}): {reason:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn conditional_events_are_decided_before_the_events_they_are_chained_with() {
+    let prompt = crate::DefaultPrompt::default();
+    let mut line_editor = Reedline::create();
+    line_editor
+        .handle_event(&prompt, ReedlineEvent::Resize(80, 24))
+        .unwrap();
+
+    let event = ReedlineEvent::Multiple(vec![
+        ReedlineEvent::Edit(vec![EditCommand::InsertChar('a')]),
+        ReedlineEvent::UntilFound(vec![ReedlineEvent::Conditional {
+            condition: EventCondition::BufferEmpty,
+            then: Box::new(ReedlineEvent::Edit(vec![EditCommand::InsertChar('x')])),
+            otherwise: Box::new(ReedlineEvent::Edit(vec![EditCommand::InsertChar('y')])),
+        }]),
+    ]);
+    line_editor.handle_event(&prompt, event.clone()).unwrap();
+    assert_eq!(line_editor.current_buffer_contents(), "ax");
+
+    line_editor.input_mode = InputMode::ExCommand;
+    line_editor.handle_event(&prompt, event).unwrap();
+    assert_eq!(line_editor.ex_command_line.get_buffer(), "ay");
+    assert_eq!(line_editor.current_buffer_contents(), "ax");
+}
+
+#[test]
+fn vi_returns_to_normal_mode_after_the_ex_command_line() {
+    let prompt = crate::DefaultPrompt::default();
+    let type_keys = |line_editor: &mut Reedline, keys: &str| {
+        for c in keys.chars() {
+            let code = match c {
+                '\u{1b}' => KeyCode::Esc,
+                '\n' => KeyCode::Enter,
+                '\u{8}' => KeyCode::Backspace,
+                c => KeyCode::Char(c),
+            };
+            let event = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+            let event = line_editor.parse_raw_event(ReedlineRawEvent::convert_from(event).unwrap());
+            line_editor.handle_event(&prompt, event).unwrap();
+        }
+    };
+    let in_normal_mode = |line_editor: &Reedline| {
+        matches!(
+            line_editor.prompt_edit_mode(),
+            PromptEditMode::Vi(crate::PromptViMode::Normal)
+        )
+    };
+    let mut line_editor = Reedline::create()
+        .with_edit_mode(Box::<crate::Vi>::default())
+        .use_ex_commands(true);
+    line_editor.run_edit_commands(&[EditCommand::InsertString("one\ntwo".into())]);
+
+    type_keys(&mut line_editor, "\u{1b}:%s/o/0/g\n");
+    assert_eq!(line_editor.current_buffer_contents(), "0ne\ntw0");
+    assert_eq!(line_editor.input_mode, InputMode::Regular);
+    assert!(in_normal_mode(&line_editor));
+
+    type_keys(&mut line_editor, ":1\nx");
+    assert_eq!(line_editor.current_buffer_contents(), "ne\ntw0");
+
+    for leave in ["\u{1b}", "\u{8}\u{8}"] {
+        type_keys(&mut line_editor, ":2");
+        type_keys(&mut line_editor, leave);
+        assert_eq!(line_editor.input_mode, InputMode::Regular);
+        assert!(in_normal_mode(&line_editor));
+    }
+    assert_eq!(line_editor.current_buffer_contents(), "ne\ntw0");
+
+    // Without the ex command line `:` does nothing
+    let mut line_editor = Reedline::create().with_edit_mode(Box::<crate::Vi>::default());
+    type_keys(&mut line_editor, "\u{1b}:x");
+    assert_eq!(line_editor.input_mode, InputMode::Regular);
+    assert!(in_normal_mode(&line_editor));
+    assert_eq!(line_editor.current_buffer_contents(), "");
+}
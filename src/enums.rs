@@ -1,8 +1,13 @@
 use crossterm::event::{Event, KeyEvent, KeyEventKind};
 use serde::{Deserialize, Serialize};
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
 use strum_macros::EnumIter;
 
+use crate::{KeyCombination, StyledText};
+
 /// Valid ways how `Reedline::read_line()` can return
 #[derive(Debug)]
 pub enum Signal {
@@ -14,6 +19,65 @@ pub enum Signal {
     CtrlD, // End terminal session
 }
 
+/// The action that ended [`crate::Reedline::read_line()`], see
+/// `Reedline::last_signal_info`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignalReason {
+    /// [`ReedlineEvent::Enter`] with a complete buffer
+    Enter,
+    /// [`ReedlineEvent::Submit`]
+    Submit,
+    /// [`ReedlineEvent::SubmitOrNewline`] with the cursor at the end of a complete buffer
+    SubmitOrNewline,
+    /// [`ReedlineEvent::AcceptAndHold`]
+    AcceptAndHold,
+    /// [`ReedlineEvent::AcceptAndDownHistory`]
+    AcceptAndDownHistory,
+    /// [`ReedlineEvent::OperateAndGetNext`]
+    OperateAndGetNext,
+    /// [`ReedlineEvent::SubmitCustom`] with the name of the binding
+    Custom(String),
+    /// A menu inserted its selected value and submitted the line with the event after the
+    /// insertion, see [`crate::MenuBuilder::with_post_insert`]
+    MenuAccept,
+    /// [`ReedlineEvent::ExecuteHostCommand`], the returned string is the command
+    ExecuteHostCommand,
+    /// [`ReedlineEvent::CtrlC`]
+    CtrlC,
+    /// [`ReedlineEvent::CtrlD`] on an empty buffer
+    CtrlD,
+}
+
+/// Details on how [`crate::Reedline::read_line()`] returned its [`Signal`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignalInfo {
+    /// The action that ended the line
+    pub reason: SignalReason,
+    /// Position of the cursor in the buffer at that moment
+    pub cursor_position: usize,
+}
+
+/// The [`Signal`] of [`crate::Reedline::read_line_detailed()`] with details on how the line
+/// was edited, e.g. for analytics or to echo the line as it was highlighted
+#[derive(Debug)]
+pub struct Submission {
+    /// What [`crate::Reedline::read_line()`] would have returned
+    pub signal: Signal,
+    /// The submitted line as the highlighter styled it last, empty unless the line
+    /// succeeded and wasn't masked
+    pub styled_text: StyledText,
+    /// Position of the cursor in the buffer when the line ended
+    pub cursor_position: usize,
+    /// The time from the start of the line until it ended, including the time spent in
+    /// host commands in between
+    pub editing_time: Duration,
+    /// Whether a hint was accepted into the buffer, in full or word by word
+    pub used_hint: bool,
+    /// Whether a value of a completion menu was inserted, in full or as the common prefix
+    /// of the values
+    pub used_completion: bool,
+}
+
 /// Editing actions which can be mapped to key bindings.
 ///
 /// Executed by `Reedline::run_edit_commands()`
@@ -98,6 +162,30 @@ pub enum EditCommand {
         select: bool,
     },
 
+    /// Move to the blank line before the paragraph, or the start of the buffer
+    MoveParagraphLeft {
+        /// Select the text between the current cursor position and destination
+        select: bool,
+    },
+
+    /// Move to the blank line after the paragraph, or the end of the buffer
+    MoveParagraphRight {
+        /// Select the text between the current cursor position and destination
+        select: bool,
+    },
+
+    /// Move to the start of the sentence, or of the previous one when already there
+    MoveSentenceLeft {
+        /// Select the text between the current cursor position and destination
+        select: bool,
+    },
+
+    /// Move to the start of the next sentence
+    MoveSentenceRight {
+        /// Select the text between the current cursor position and destination
+        select: bool,
+    },
+
     /// Move to position
     MoveToPosition {
         /// Position to move to
@@ -151,6 +239,10 @@ pub enum EditCommand {
     /// Cut the current line
     CutCurrentLine,
 
+    /// Cut the current paragraph, the lines up to the blank lines before and after the line of
+    /// the insertion point, together with the blank lines following it
+    CutCurrentParagraph,
+
     /// Cut from the start of the buffer to the insertion point
     CutFromStart,
 
@@ -205,6 +297,39 @@ pub enum EditCommand {
     /// Swap the current grapheme/character with the one to the right
     SwapGraphemes,
 
+    /// Swap the word under or before the cursor with the previous word, keeping the
+    /// punctuation between them in place and moving the cursor after them, like `Alt-t`
+    TransposeWords,
+
+    /// Swap the grapheme before the cursor with the one after it the given number of times,
+    /// dragging it forward like `Ctrl-t` with a count in Emacs
+    TransposeGraphemes(usize),
+
+    /// Move the current line above the previous line
+    MoveLineUp,
+
+    /// Move the current line below the next line
+    MoveLineDown,
+
+    /// Insert a copy of the current line below it
+    DuplicateLine,
+
+    /// Join the next line onto the end of the current line
+    JoinLines,
+
+    /// Indent the current line or all lines touched by the selection
+    IndentLine,
+
+    /// Remove one level of indentation from the current line or all lines touched by the selection
+    DedentLine,
+
+    /// Comment or uncomment the current line or all lines touched by the selection
+    ToggleComment {
+        /// Prefix marking a line as comment. When empty the default prefix of the editor is
+        /// used (see `Reedline::with_comment_prefix`)
+        prefix: String,
+    },
+
     /// Undo the previous edit command
     Undo,
 
@@ -255,9 +380,73 @@ pub enum EditCommand {
         select: bool,
     },
 
+    /// Switch the case of up to the given number of chars from the cursor on the current line,
+    /// moving the cursor after them but not past the last char of the line, like `~` in vim
+    SwitchcaseChars(usize),
+
+    /// Switch the case of the selected text, leaving the cursor at its start, like `g~` in vim
+    SwitchcaseSelection,
+
+    /// Add one to the number at or after the cursor on the current line, like `Ctrl-a` in vim
+    IncrementNumber,
+
+    /// Subtract one from the number at or after the cursor on the current line, like `Ctrl-x`
+    /// in vim
+    DecrementNumber,
+
+    /// Move to or before a char on any line of the buffer, like [`EditCommand::MoveRightUntil`],
+    /// [`EditCommand::MoveRightBefore`], [`EditCommand::MoveLeftUntil`] and
+    /// [`EditCommand::MoveLeftBefore`] do on the current line
+    MoveToCharAcrossLines {
+        /// Char to move towards
+        c: char,
+        /// Search to the right of the cursor, instead of to the left
+        right: bool,
+        /// Stop before the char, instead of on it
+        before: bool,
+        /// Select the text between the current cursor position and destination
+        select: bool,
+    },
+
+    /// Cut to or before a char on any line of the buffer, like [`EditCommand::CutRightUntil`],
+    /// [`EditCommand::CutRightBefore`], [`EditCommand::CutLeftUntil`] and
+    /// [`EditCommand::CutLeftBefore`] do on the current line
+    CutToCharAcrossLines {
+        /// Char to cut towards
+        c: char,
+        /// Search to the right of the cursor, instead of to the left
+        right: bool,
+        /// Stop before the char, instead of cutting it too
+        before: bool,
+    },
+
+    /// Move to the start of the next match of a regular expression
+    MoveToRegexMatch {
+        /// Regular expression to search for
+        pattern: String,
+        /// Select the text between the current cursor position and destination
+        select: bool,
+    },
+
+    /// Cut from the insertion point up to the start of the next match of a regular expression
+    CutToRegexMatch {
+        /// Regular expression to search for
+        pattern: String,
+    },
+
     /// Select whole input buffer
     SelectAll,
 
+    /// Select the last selection again, with the cursor on the same end, like `gv` in vim
+    ReselectLast,
+
+    /// Select the next field, the text between read-only ranges, or the first one after the
+    /// last, see [`crate::Reedline::set_read_only`]
+    SelectNextField,
+
+    /// Select the previous field, or the last one before the first
+    SelectPreviousField,
+
     /// Cut selection to local buffer
     CutSelection,
 
@@ -306,6 +495,18 @@ impl Display for EditCommand {
             EditCommand::MoveBigWordRightEnd { .. } => {
                 write!(f, "MoveBigWordRightEnd Optional[select: <bool>]")
             }
+            EditCommand::MoveParagraphLeft { .. } => {
+                write!(f, "MoveParagraphLeft Optional[select: <bool>]")
+            }
+            EditCommand::MoveParagraphRight { .. } => {
+                write!(f, "MoveParagraphRight Optional[select: <bool>]")
+            }
+            EditCommand::MoveSentenceLeft { .. } => {
+                write!(f, "MoveSentenceLeft Optional[select: <bool>]")
+            }
+            EditCommand::MoveSentenceRight { .. } => {
+                write!(f, "MoveSentenceRight Optional[select: <bool>]")
+            }
             EditCommand::MoveWordRightStart { .. } => {
                 write!(f, "MoveWordRightStart Optional[select: <bool>]")
             }
@@ -335,6 +536,7 @@ impl Display for EditCommand {
             EditCommand::ClearToLineEnd => write!(f, "ClearToLineEnd"),
             EditCommand::Complete => write!(f, "Complete"),
             EditCommand::CutCurrentLine => write!(f, "CutCurrentLine"),
+            EditCommand::CutCurrentParagraph => write!(f, "CutCurrentParagraph"),
             EditCommand::CutFromStart => write!(f, "CutFromStart"),
             EditCommand::CutFromLineStart => write!(f, "CutFromLineStart"),
             EditCommand::CutToEnd => write!(f, "CutToEnd"),
@@ -353,6 +555,15 @@ impl Display for EditCommand {
             EditCommand::CapitalizeChar => write!(f, "CapitalizeChar"),
             EditCommand::SwapWords => write!(f, "SwapWords"),
             EditCommand::SwapGraphemes => write!(f, "SwapGraphemes"),
+            EditCommand::TransposeWords => write!(f, "TransposeWords"),
+            EditCommand::TransposeGraphemes(_) => write!(f, "TransposeGraphemes Value: <int>"),
+            EditCommand::MoveLineUp => write!(f, "MoveLineUp"),
+            EditCommand::MoveLineDown => write!(f, "MoveLineDown"),
+            EditCommand::DuplicateLine => write!(f, "DuplicateLine"),
+            EditCommand::JoinLines => write!(f, "JoinLines"),
+            EditCommand::IndentLine => write!(f, "IndentLine"),
+            EditCommand::DedentLine => write!(f, "DedentLine"),
+            EditCommand::ToggleComment { .. } => write!(f, "ToggleComment Value: <string>"),
             EditCommand::Undo => write!(f, "Undo"),
             EditCommand::Redo => write!(f, "Redo"),
             EditCommand::CutRightUntil(_) => write!(f, "CutRightUntil Value: <char>"),
@@ -361,7 +572,24 @@ impl Display for EditCommand {
             EditCommand::MoveRightBefore { .. } => write!(f, "MoveRightBefore Value: <char>"),
             EditCommand::CutLeftUntil(_) => write!(f, "CutLeftUntil Value: <char>"),
             EditCommand::CutLeftBefore(_) => write!(f, "CutLeftBefore Value: <char>"),
+            EditCommand::SwitchcaseChars(_) => write!(f, "SwitchcaseChars Value: <int>"),
+            EditCommand::SwitchcaseSelection => write!(f, "SwitchcaseSelection"),
+            EditCommand::IncrementNumber => write!(f, "IncrementNumber"),
+            EditCommand::DecrementNumber => write!(f, "DecrementNumber"),
+            EditCommand::MoveToCharAcrossLines { .. } => write!(
+                f,
+                "MoveToCharAcrossLines Value: <char>, Optional[right: <bool>, before: <bool>, select: <bool>]"
+            ),
+            EditCommand::CutToCharAcrossLines { .. } => write!(
+                f,
+                "CutToCharAcrossLines Value: <char>, Optional[right: <bool>, before: <bool>]"
+            ),
+            EditCommand::MoveToRegexMatch { .. } => write!(f, "MoveToRegexMatch Value: <string>"),
+            EditCommand::CutToRegexMatch { .. } => write!(f, "CutToRegexMatch Value: <string>"),
             EditCommand::SelectAll => write!(f, "SelectAll"),
+            EditCommand::ReselectLast => write!(f, "ReselectLast"),
+            EditCommand::SelectNextField => write!(f, "SelectNextField"),
+            EditCommand::SelectPreviousField => write!(f, "SelectPreviousField"),
             EditCommand::CutSelection => write!(f, "CutSelection"),
             EditCommand::CopySelection => write!(f, "CopySelection"),
             EditCommand::Paste => write!(f, "Paste"),
@@ -395,14 +623,23 @@ impl EditCommand {
             | EditCommand::MoveBigWordRightStart { select, .. }
             | EditCommand::MoveWordRightEnd { select, .. }
             | EditCommand::MoveBigWordRightEnd { select, .. }
+            | EditCommand::MoveParagraphLeft { select, .. }
+            | EditCommand::MoveParagraphRight { select, .. }
+            | EditCommand::MoveSentenceLeft { select, .. }
+            | EditCommand::MoveSentenceRight { select, .. }
             | EditCommand::MoveRightUntil { select, .. }
             | EditCommand::MoveRightBefore { select, .. }
             | EditCommand::MoveLeftUntil { select, .. }
-            | EditCommand::MoveLeftBefore { select, .. } => {
+            | EditCommand::MoveLeftBefore { select, .. }
+            | EditCommand::MoveToCharAcrossLines { select, .. }
+            | EditCommand::MoveToRegexMatch { select, .. } => {
                 EditType::MoveCursor { select: *select }
             }
 
-            EditCommand::SelectAll => EditType::MoveCursor { select: true },
+            EditCommand::SelectAll
+            | EditCommand::ReselectLast
+            | EditCommand::SelectNextField
+            | EditCommand::SelectPreviousField => EditType::MoveCursor { select: true },
             // Text edits
             EditCommand::InsertChar(_)
             | EditCommand::Backspace
@@ -418,6 +655,7 @@ impl EditCommand {
             | EditCommand::ClearToLineEnd
             | EditCommand::Complete
             | EditCommand::CutCurrentLine
+            | EditCommand::CutCurrentParagraph
             | EditCommand::CutFromStart
             | EditCommand::CutFromLineStart
             | EditCommand::CutToLineEnd
@@ -436,10 +674,25 @@ impl EditCommand {
             | EditCommand::CapitalizeChar
             | EditCommand::SwapWords
             | EditCommand::SwapGraphemes
+            | EditCommand::TransposeWords
+            | EditCommand::TransposeGraphemes(_)
+            | EditCommand::MoveLineUp
+            | EditCommand::MoveLineDown
+            | EditCommand::DuplicateLine
+            | EditCommand::JoinLines
+            | EditCommand::IndentLine
+            | EditCommand::DedentLine
+            | EditCommand::ToggleComment { .. }
             | EditCommand::CutRightUntil(_)
             | EditCommand::CutRightBefore(_)
             | EditCommand::CutLeftUntil(_)
             | EditCommand::CutLeftBefore(_)
+            | EditCommand::CutToCharAcrossLines { .. }
+            | EditCommand::IncrementNumber
+            | EditCommand::DecrementNumber
+            | EditCommand::SwitchcaseChars(_)
+            | EditCommand::SwitchcaseSelection
+            | EditCommand::CutToRegexMatch { .. }
             | EditCommand::CutSelection
             | EditCommand::Paste => EditType::EditText,
 
@@ -522,10 +775,102 @@ impl UndoBehavior {
     }
 }
 
+/// A shell command run by [`ReedlineEvent::RunHostCommand`] without leaving
+/// [`crate::Reedline::read_line()`]
+///
+/// The terminal is restored to cooked mode while the command runs and switched back to raw mode
+/// afterwards, even if the command could not be started.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default)]
+pub struct HostCommand {
+    /// Command line passed to the system shell (`sh -c` or `cmd /C`)
+    pub command: String,
+    /// Save the current buffer to the history before running the command
+    pub save_buffer_to_history: bool,
+    /// Capture the standard output of the command and insert it at the cursor instead of
+    /// printing it to the terminal. Newlines are normalized, trailing newlines trimmed and
+    /// the size is limited, see `Reedline::with_max_insert_size`
+    pub insert_output: bool,
+}
+
+impl HostCommand {
+    /// Run `command` with its output printed to the terminal and without touching the history
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            save_buffer_to_history: false,
+            insert_output: false,
+        }
+    }
+
+    /// Set whether the current buffer is saved to the history before running the command
+    #[must_use]
+    pub fn with_save_buffer_to_history(mut self, save_buffer_to_history: bool) -> Self {
+        self.save_buffer_to_history = save_buffer_to_history;
+        self
+    }
+
+    /// Set whether the standard output of the command is inserted at the cursor
+    #[must_use]
+    pub fn with_insert_output(mut self, insert_output: bool) -> Self {
+        self.insert_output = insert_output;
+        self
+    }
+}
+
+/// A state of the editor that decides a [`ReedlineEvent::Conditional`]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default)]
+pub enum EventCondition {
+    /// A hint is shown that [`ReedlineEvent::HistoryHintComplete`] would accept
+    #[default]
+    HintVisible,
+    /// The cursor is at the end of its line
+    CursorAtLineEnd,
+    /// The cursor is at the end of the buffer
+    CursorAtBufferEnd,
+    /// The buffer is empty
+    BufferEmpty,
+    /// A menu is active
+    MenuActive,
+    /// Part of the buffer is selected
+    SelectionActive,
+    /// The condition doesn't hold
+    Not(Box<EventCondition>),
+    /// All of the conditions hold, also if there are none
+    All(Vec<EventCondition>),
+    /// Any of the conditions holds
+    Any(Vec<EventCondition>),
+}
+
+/// A line of the screen while a buffer is taller than it, see
+/// [`ReedlineEvent::MoveToViewport`] and [`ReedlineEvent::ScrollViewport`]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ViewportLine {
+    /// The first line on the screen
+    #[default]
+    Top,
+    /// The line in the middle of the screen
+    Middle,
+    /// The last line on the screen
+    Bottom,
+}
+
+/// How an event of a [`ReedlineEvent::UntilFound`] fares in the current state of the
+/// editor, see [`crate::Reedline::explain_until_found`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BranchOutcome {
+    /// The event would be handled, the events after it aren't tried
+    Handled,
+    /// The event doesn't apply for the given reason, the next one is tried
+    Inapplicable(&'static str),
+    /// An event before it would be handled
+    NotTried,
+}
+
 /// Reedline supported actions.
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, EnumIter)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default, EnumIter)]
 pub enum ReedlineEvent {
     /// No op event
+    #[default]
     None,
 
     /// Complete history hint (default in full)
@@ -555,11 +900,15 @@ pub enum ReedlineEvent {
     /// Clears the screen and sets prompt to first line
     ClearScreen,
 
-    /// Clears the screen and the scrollback buffer
+    /// Clears the screen and the scrollback buffer (`CSI 3J`)
     ///
     /// Sets the prompt back to the first line
     ClearScrollback,
 
+    /// Clears the lines above the prompt, leaving the prompt where it is and the scrollback
+    /// buffer as it is
+    ClearAbovePrompt,
+
     /// Handle enter event
     Enter,
 
@@ -569,6 +918,27 @@ pub enum ReedlineEvent {
     /// Submit at the end of the *complete* text, otherwise newline
     SubmitOrNewline,
 
+    /// Submit the buffer without validation, reporting the given name as
+    /// [`SignalReason::Custom`] so the application can tell custom accept bindings apart
+    SubmitCustom(String),
+
+    /// Submit the buffer and keep its content for the next prompt (zsh `accept-and-hold`)
+    AcceptAndHold,
+
+    /// Submit the buffer and load the history entry following it for the next prompt
+    /// (zsh `accept-line-and-down-history`)
+    ///
+    /// Only loads an entry if the buffer was recalled from the history
+    AcceptAndDownHistory,
+
+    /// Submit the buffer and load the history entry following the one it was recalled from
+    /// (readline `operate-and-get-next`, usually bound to `Ctrl-o`)
+    ///
+    /// Unlike [`ReedlineEvent::AcceptAndDownHistory`] the history position is kept when the
+    /// recalled entry was edited, and it accepts the current match of the history search.
+    /// It isn't bound by default, `Ctrl-o` opens the external editor.
+    OperateAndGetNext,
+
     /// Esc event
     Esc,
 
@@ -603,8 +973,76 @@ pub enum ReedlineEvent {
     NextHistory,
 
     /// Search the history for a string
+    ///
+    /// While searching, moves to the next older match.
     SearchHistory,
 
+    /// Search the history for a string like [`ReedlineEvent::SearchHistory`]
+    ///
+    /// While searching, moves to the next newer match.
+    SearchHistoryForward,
+
+    /// Open the ex command line below the buffer, see [`crate::ExCommand`]
+    ExCommand,
+
+    /// Delete the entry selected in a history menu or found by the history search from the
+    /// history, with all of its duplicates in a menu
+    DeleteHistoryItem,
+
+    /// Load the entry selected in a history menu or found by the history search into the
+    /// buffer, to update it in the history when submitted instead of adding a new entry
+    EditHistoryItem,
+
+    /// Pin the entry selected in a history menu or found by the history search, or unpin it
+    /// if it is pinned already, see [`crate::History::set_pinned`]
+    TogglePinHistoryItem,
+
+    /// Turn the restriction of the history navigation and search to the entries of the
+    /// working directory on or off, see [`crate::Reedline::with_directory_history`]
+    ToggleDirectoryHistory,
+
+    /// Show or hide a panel below the buffer with the state of the engine, the same as
+    /// [`crate::Reedline::debug_state`]
+    ToggleDebugPanel,
+
+    /// Expand the pasted blocks painted collapsed, or collapse them again, see
+    /// [`crate::Reedline::with_paste_folding`]
+    ToggleFolds,
+
+    /// Replace text in the whole buffer: prompts for a pattern and a replacement, then asks
+    /// for every match whether to replace it (`y`), skip it (`n`), replace all remaining
+    /// matches (`a`) or stop (`q`)
+    SearchReplace,
+
+    /// Search incrementally within the buffer: every match of the typed text is highlighted,
+    /// moving to the next (`SearchBuffer`) or previous (`SearchHistory`) match, and the cursor
+    /// is placed at the current match on `Enter` or put back on `Esc`
+    ///
+    /// While searching the history, moves to the next newer match like
+    /// [`ReedlineEvent::SearchHistoryForward`].
+    SearchBuffer,
+
+    /// Move the cursor to the start of the first, middle or last line of the buffer on the
+    /// screen, without scrolling it (vi `H`, `M` and `L`)
+    MoveToViewport(ViewportLine),
+
+    /// Scroll a buffer taller than the screen so the line of the cursor is the first, middle
+    /// or last line on the screen (vi `zt`, `zz` and `zb`)
+    ///
+    /// The buffer then stays in place until the cursor leaves the screen.
+    ScrollViewport(ViewportLine),
+
+    /// Complete the word before the cursor with the next word of the buffer or of the recent
+    /// history that starts with it, the closest before the cursor first
+    ///
+    /// Repeating the event cycles through the words and back to the typed text, independent of
+    /// the [`crate::Completer`].
+    CompleteWordNext,
+
+    /// Complete the word before the cursor like [`ReedlineEvent::CompleteWordNext`], cycling
+    /// through the words in the opposite direction
+    CompleteWordPrevious,
+
     /// In vi mode multiple reedline events can be chained while parsing the
     /// command or movement characters
     Multiple(Vec<ReedlineEvent>),
@@ -612,6 +1050,30 @@ pub enum ReedlineEvent {
     /// Test
     UntilFound(Vec<ReedlineEvent>),
 
+    /// Handle one of two events depending on the state of the editor when the event comes,
+    /// e.g. accept the hint with Tab while one is shown and open the completion menu otherwise
+    ///
+    /// Inside [`ReedlineEvent::Multiple`] and [`ReedlineEvent::UntilFound`] the condition is
+    /// also decided when the whole event comes, before any of the events are handled.
+    ///
+    /// ```rust
+    /// use reedline::{EventCondition, ReedlineEvent};
+    ///
+    /// let tab = ReedlineEvent::Conditional {
+    ///     condition: EventCondition::HintVisible,
+    ///     then: Box::new(ReedlineEvent::HistoryHintComplete),
+    ///     otherwise: Box::new(ReedlineEvent::Menu("completion_menu".to_string())),
+    /// };
+    /// ```
+    Conditional {
+        /// What decides the event
+        condition: EventCondition,
+        /// The event handled if the condition holds
+        then: Box<ReedlineEvent>,
+        /// The event handled otherwise, [`ReedlineEvent::None`] to do nothing
+        otherwise: Box<ReedlineEvent>,
+    },
+
     /// Trigger a menu event. It activates a menu with the event name
     Menu(String),
 
@@ -642,8 +1104,29 @@ pub enum ReedlineEvent {
     /// Way to bind the execution of a whole command (directly returning from [`crate::Reedline::read_line()`]) to a keybinding
     ExecuteHostCommand(String),
 
+    /// Run a shell command while keeping the current buffer, see [`HostCommand`]
+    RunHostCommand(HostCommand),
+
+    /// Application defined event passed by name to the [`crate::CustomEventHandler`] of the
+    /// engine, without ending the line
+    Custom(String),
+
+    /// Insert the contents of the file at the given path at the cursor
+    ///
+    /// Newlines are normalized and the size is limited like for the captured output of
+    /// [`ReedlineEvent::RunHostCommand`], see `Reedline::with_max_insert_size`
+    ReadFileIntoBuffer(String),
+
     /// Open text editor
     OpenEditor,
+
+    /// Replace the buffer with the result of the formatter, see `Reedline::with_formatter`
+    FormatBuffer,
+
+    /// Hand the selection, or the whole buffer without one, to the handler of
+    /// `Reedline::with_selection_handler` together with the given action name, e.g.
+    /// `"explain"`, and keep editing
+    SendSelection(String),
 }
 
 impl Display for ReedlineEvent {
@@ -656,9 +1139,14 @@ impl Display for ReedlineEvent {
             ReedlineEvent::CtrlC => write!(f, "CtrlC"),
             ReedlineEvent::ClearScreen => write!(f, "ClearScreen"),
             ReedlineEvent::ClearScrollback => write!(f, "ClearScrollback"),
+            ReedlineEvent::ClearAbovePrompt => write!(f, "ClearAbovePrompt"),
             ReedlineEvent::Enter => write!(f, "Enter"),
             ReedlineEvent::Submit => write!(f, "Submit"),
             ReedlineEvent::SubmitOrNewline => write!(f, "SubmitOrNewline"),
+            ReedlineEvent::SubmitCustom(_) => write!(f, "SubmitCustom Value: <string>"),
+            ReedlineEvent::AcceptAndHold => write!(f, "AcceptAndHold"),
+            ReedlineEvent::AcceptAndDownHistory => write!(f, "AcceptAndDownHistory"),
+            ReedlineEvent::OperateAndGetNext => write!(f, "OperateAndGetNext"),
             ReedlineEvent::Esc => write!(f, "Esc"),
             ReedlineEvent::Mouse => write!(f, "Mouse"),
             ReedlineEvent::Resize(_, _) => write!(f, "Resize <int> <int>"),
@@ -674,8 +1162,26 @@ impl Display for ReedlineEvent {
             ReedlineEvent::Left => write!(f, "Left"),
             ReedlineEvent::NextHistory => write!(f, "NextHistory"),
             ReedlineEvent::SearchHistory => write!(f, "SearchHistory"),
+            ReedlineEvent::SearchHistoryForward => write!(f, "SearchHistoryForward"),
+            ReedlineEvent::ExCommand => write!(f, "ExCommand"),
+            ReedlineEvent::DeleteHistoryItem => write!(f, "DeleteHistoryItem"),
+            ReedlineEvent::EditHistoryItem => write!(f, "EditHistoryItem"),
+            ReedlineEvent::TogglePinHistoryItem => write!(f, "TogglePinHistoryItem"),
+            ReedlineEvent::ToggleDirectoryHistory => write!(f, "ToggleDirectoryHistory"),
+            ReedlineEvent::ToggleDebugPanel => write!(f, "ToggleDebugPanel"),
+            ReedlineEvent::ToggleFolds => write!(f, "ToggleFolds"),
+            ReedlineEvent::MoveToViewport(_) => write!(f, "MoveToViewport Top|Middle|Bottom"),
+            ReedlineEvent::ScrollViewport(_) => write!(f, "ScrollViewport Top|Middle|Bottom"),
+            ReedlineEvent::SearchReplace => write!(f, "SearchReplace"),
+            ReedlineEvent::SearchBuffer => write!(f, "SearchBuffer"),
+            ReedlineEvent::CompleteWordNext => write!(f, "CompleteWordNext"),
+            ReedlineEvent::CompleteWordPrevious => write!(f, "CompleteWordPrevious"),
             ReedlineEvent::Multiple(_) => write!(f, "Multiple[ {{ ReedLineEvents, }} ]"),
             ReedlineEvent::UntilFound(_) => write!(f, "UntilFound [ {{ ReedLineEvents, }} ]"),
+            ReedlineEvent::Conditional { .. } => write!(
+                f,
+                "Conditional {{ condition: <EventCondition>, then: <ReedLineEvent>, otherwise: <ReedLineEvent> }}"
+            ),
             ReedlineEvent::Menu(_) => write!(f, "Menu Name: <string>"),
             ReedlineEvent::MenuNext => write!(f, "MenuNext"),
             ReedlineEvent::MenuPrevious => write!(f, "MenuPrevious"),
@@ -686,7 +1192,12 @@ impl Display for ReedlineEvent {
             ReedlineEvent::MenuPageNext => write!(f, "MenuPageNext"),
             ReedlineEvent::MenuPagePrevious => write!(f, "MenuPagePrevious"),
             ReedlineEvent::ExecuteHostCommand(_) => write!(f, "ExecuteHostCommand"),
+            ReedlineEvent::RunHostCommand(_) => write!(f, "RunHostCommand"),
+            ReedlineEvent::ReadFileIntoBuffer(_) => write!(f, "ReadFileIntoBuffer Value: <string>"),
+            ReedlineEvent::Custom(_) => write!(f, "Custom Value: <string>"),
             ReedlineEvent::OpenEditor => write!(f, "OpenEditor"),
+            ReedlineEvent::FormatBuffer => write!(f, "FormatBuffer"),
+            ReedlineEvent::SendSelection(_) => write!(f, "SendSelection Value: <string>"),
         }
     }
 }
@@ -734,4 +1245,17 @@ impl ReedlineRawEvent {
     pub fn into(self) -> Event {
         self.inner
     }
+
+    /// The pressed key, if the event is a key press
+    pub(crate) fn key(&self) -> Option<KeyCombination> {
+        match &self.inner {
+            Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) => Some(KeyCombination {
+                modifier: *modifiers,
+                key_code: *code,
+            }),
+            _ => None,
+        }
+    }
 }
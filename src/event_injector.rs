@@ -0,0 +1,76 @@
+use crate::{EditCommand, ReedlineEvent};
+use std::sync::{Arc, Mutex};
+
+/// Lets other threads control a running [`crate::Reedline::read_line()`], e.g. a plugin
+/// system or an IPC server
+///
+/// The events are handled as if they came from the keyboard, within a few tens of
+/// milliseconds. Clones share the same queue; pass one of them to
+/// [`crate::Reedline::with_event_injector`].
+///
+/// ```rust
+/// use reedline::{EditCommand, EventInjector, Reedline, ReedlineEvent};
+///
+/// let injector = EventInjector::new();
+/// let line_editor = Reedline::create().with_event_injector(injector.clone());
+/// std::thread::spawn(move || {
+///     injector.send_edit(vec![EditCommand::InsertString("ls -l".to_string())]);
+///     injector.send(ReedlineEvent::Submit);
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EventInjector {
+    events: Arc<Mutex<Vec<ReedlineEvent>>>,
+}
+
+impl EventInjector {
+    /// An injector not yet connected to an editor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `event` to be handled after the ones sent before
+    pub fn send(&self, event: ReedlineEvent) {
+        self.lock().push(event);
+    }
+
+    /// Queue the edit `commands` to be run after the events sent before
+    pub fn send_edit(&self, commands: Vec<EditCommand>) {
+        self.send(ReedlineEvent::Edit(commands));
+    }
+
+    /// The events sent since the last call, in order
+    pub(crate) fn take_events(&self) -> Vec<ReedlineEvent> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<ReedlineEvent>> {
+        // A panic while holding the lock cannot leave the queue half updated
+        self.events
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn events_are_taken_in_order() {
+        let injector = EventInjector::new();
+        let sender = injector.clone();
+        sender.send_edit(vec![EditCommand::InsertChar('a')]);
+        sender.send(ReedlineEvent::Submit);
+
+        assert_eq!(
+            injector.take_events(),
+            vec![
+                ReedlineEvent::Edit(vec![EditCommand::InsertChar('a')]),
+                ReedlineEvent::Submit
+            ]
+        );
+        assert_eq!(injector.take_events(), vec![]);
+    }
+}
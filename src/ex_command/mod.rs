@@ -0,0 +1,317 @@
+use crate::{core_editor::Editor, LineBuffer, UndoBehavior};
+use regex::Regex;
+use std::path::Path;
+
+/// An application defined command for the Vi ex command line opened with `:`
+///
+/// Registered commands are looked up by the first word of the command line before the
+/// built-in commands (`:w <file>`, `:[%]s/pattern/replacement/[g]` and `:<line>`)
+///
+/// The pattern of `:s` is a regular expression in the syntax of the `regex` crate, matched
+/// line by line. In the replacement `&` is the whole match and `\1` to `\9` are its groups,
+/// `\&` and `\\` are a literal `&` and `\`.
+pub trait ExCommand: Send {
+    /// The name the command is invoked with, e.g. `"wq"` for `:wq`
+    fn name(&self) -> &str;
+
+    /// Run the command with the remainder of the command line as `args`
+    ///
+    /// The returned error message is shown to the user below the buffer
+    fn run(&mut self, args: &str, editor: &mut Editor) -> Result<(), String>;
+}
+
+/// Execute an ex `command_line` (without the leading `:`) against the `editor`
+pub(crate) fn run_ex_command(
+    command_line: &str,
+    editor: &mut Editor,
+    commands: &mut [Box<dyn ExCommand>],
+) -> Result<(), String> {
+    let command_line = command_line.trim();
+    if command_line.is_empty() {
+        return Ok(());
+    }
+
+    let (name, args) = command_line
+        .split_once(char::is_whitespace)
+        .map_or((command_line, ""), |(name, args)| (name, args.trim_start()));
+
+    if let Some(command) = commands.iter_mut().find(|command| command.name() == name) {
+        return command.run(args, editor);
+    }
+
+    if let Ok(line) = command_line.parse::<usize>() {
+        goto_line(editor, line);
+        Ok(())
+    } else if let Some(substitution) = command_line.strip_prefix('%') {
+        substitute(editor, substitution, true)
+    } else if command_line.starts_with('s') {
+        substitute(editor, command_line, false)
+    } else if name == "w" {
+        write_buffer(editor, args)
+    } else {
+        Err(format!("Not an editor command: {command_line}"))
+    }
+}
+
+/// Move the cursor to the start of the one-based `line`, clamped to the last line
+fn goto_line(editor: &mut Editor, line: usize) {
+    let buffer = editor.get_buffer();
+    let offset = if line <= 1 {
+        0
+    } else {
+        buffer
+            .match_indices('\n')
+            .take(line - 1)
+            .last()
+            .map_or(0, |(idx, _)| idx + 1)
+    };
+
+    let mut line_buffer = editor.line_buffer().clone();
+    line_buffer.set_insertion_point(offset);
+    editor.set_line_buffer(line_buffer, UndoBehavior::MoveCursor);
+}
+
+/// `s/pattern/replacement/[g]` with a regular expression on the current line or on the whole buffer
+fn substitute(editor: &mut Editor, substitution: &str, whole_buffer: bool) -> Result<(), String> {
+    let (pattern, replacement, global) = parse_substitution(substitution)?;
+    let regex = Regex::new(&pattern).map_err(|_| format!("Invalid pattern: {pattern}"))?;
+    let replacement = regex_replacement(&replacement);
+
+    let line_buffer = editor.line_buffer();
+    let buffer = line_buffer.get_buffer();
+    let range = if whole_buffer {
+        0..buffer.len()
+    } else {
+        line_buffer.current_line_range()
+    };
+
+    let mut replaced = false;
+    let lines = buffer[range.clone()]
+        .split_inclusive('\n')
+        .map(|line| {
+            let (text, newline) = line
+                .strip_suffix('\n')
+                .map_or((line, ""), |text| (text, "\n"));
+            if !regex.is_match(text) {
+                return line.to_string();
+            }
+            replaced = true;
+            let limit = if global { 0 } else { 1 };
+            regex
+                .replacen(text, limit, replacement.as_str())
+                .into_owned()
+                + newline
+        })
+        .collect::<String>();
+
+    if !replaced {
+        return Err(format!("Pattern not found: {pattern}"));
+    }
+
+    let mut new_buffer = String::with_capacity(buffer.len());
+    new_buffer.push_str(&buffer[..range.start]);
+    new_buffer.push_str(&lines);
+    new_buffer.push_str(&buffer[range.end..]);
+
+    let mut insertion_point = line_buffer.insertion_point().min(new_buffer.len());
+    while !new_buffer.is_char_boundary(insertion_point) {
+        insertion_point -= 1;
+    }
+
+    let mut line_buffer = LineBuffer::from(new_buffer.as_str());
+    line_buffer.set_insertion_point(insertion_point);
+    editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+
+    Ok(())
+}
+
+/// Split `s/pattern/replacement/flags` into its parts.
+/// Any non-alphanumeric character following the `s` works as separator and can be escaped
+/// with a backslash.
+fn parse_substitution(substitution: &str) -> Result<(String, String, bool), String> {
+    let mut chars = substitution.chars();
+    if chars.next() != Some('s') {
+        return Err(format!("Not an editor command: {substitution}"));
+    }
+    let separator = match chars.next() {
+        Some(c) if !c.is_alphanumeric() && !c.is_whitespace() && c != '\\' => c,
+        _ => return Err(format!("Not an editor command: {substitution}")),
+    };
+
+    let mut parts = vec![String::new()];
+    let mut escaped = false;
+    for c in chars {
+        if escaped {
+            if c != separator {
+                parts.last_mut().expect("parts is never empty").push('\\');
+            }
+            parts.last_mut().expect("parts is never empty").push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == separator && parts.len() < 3 {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().expect("parts is never empty").push(c);
+        }
+    }
+    if escaped {
+        parts.last_mut().expect("parts is never empty").push('\\');
+    }
+
+    let mut parts = parts.into_iter();
+    let pattern = parts.next().unwrap_or_default();
+    let replacement = parts.next().unwrap_or_default();
+    let flags = parts.next().unwrap_or_default();
+
+    if pattern.is_empty() {
+        return Err("Empty search pattern".to_string());
+    }
+    if let Some(flag) = flags.chars().find(|c| *c != 'g') {
+        return Err(format!("Invalid substitute flag: {flag}"));
+    }
+
+    Ok((pattern, replacement, !flags.is_empty()))
+}
+
+/// The vi `replacement` of a substitution in the syntax of [`Regex::replace`]
+fn regex_replacement(replacement: &str) -> String {
+    let mut converted = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => converted.push_str("${0}"),
+            '$' => converted.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(group @ '1'..='9') => {
+                    converted.push_str("${");
+                    converted.push(group);
+                    converted.push('}');
+                }
+                Some('$') => converted.push_str("$$"),
+                Some(escaped @ ('&' | '\\')) => converted.push(escaped),
+                Some(other) => {
+                    converted.push('\\');
+                    converted.push(other);
+                }
+                None => converted.push('\\'),
+            },
+            c => converted.push(c),
+        }
+    }
+    converted
+}
+
+/// Write the buffer contents to the file at `path`
+fn write_buffer(editor: &Editor, path: &str) -> Result<(), String> {
+    if path.is_empty() {
+        return Err("No file name".to_string());
+    }
+
+    std::fs::write(Path::new(path), editor.get_buffer())
+        .map_err(|err| format!("Can't write \"{path}\": {err}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn editor_with(buffer: &str, insertion_point: usize) -> Editor {
+        let mut line_buffer = LineBuffer::from(buffer);
+        line_buffer.set_insertion_point(insertion_point);
+        let mut editor = Editor::default();
+        editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+        editor
+    }
+
+    struct Upcase;
+
+    impl ExCommand for Upcase {
+        fn name(&self) -> &str {
+            "upcase"
+        }
+
+        fn run(&mut self, args: &str, editor: &mut Editor) -> Result<(), String> {
+            let buffer = editor.get_buffer().to_uppercase() + args;
+            editor.edit_buffer(|lb| lb.set_buffer(buffer), UndoBehavior::CreateUndoPoint);
+            Ok(())
+        }
+    }
+
+    #[rstest]
+    #[case("foo foo\nfoo", 0, "%s/foo/bar/g", "bar bar\nbar", 0)]
+    #[case("foo foo\nfoo", 0, "%s/foo/bar/", "bar foo\nbar", 0)]
+    #[case("foo foo\nfoo", 0, "s/foo/bar/g", "bar bar\nfoo", 0)]
+    #[case("foo\nfoo foo", 5, "s/foo/ba/", "foo\nba foo", 5)]
+    #[case("a/b", 3, "%s#/#\\##", "a#b", 3)]
+    #[case("a/b", 0, "%s/\\//-/", "a-b", 0)]
+    #[case("foo", 3, "%s/foo//", "", 0)]
+    #[case("foo bar", 0, "s/o+/0/", "f0 bar", 0)]
+    #[case("foo\nboo", 0, "%s/o$/0/", "fo0\nbo0", 0)]
+    #[case("ab cd", 0, "%s/(\\w)(\\w)/\\2\\1/g", "ba dc", 0)]
+    #[case("cost", 0, "s/cost/& $5 \\&/", "cost $5 &", 0)]
+    #[case("one\ntwo\nthree", 12, "2", "one\ntwo\nthree", 4)]
+    #[case("one\ntwo\nthree", 12, "1", "one\ntwo\nthree", 0)]
+    #[case("one\ntwo\nthree", 0, "0", "one\ntwo\nthree", 0)]
+    #[case("one\ntwo\nthree", 0, "9", "one\ntwo\nthree", 8)]
+    #[case("one", 1, "  ", "one", 1)]
+    fn test_run_ex_command(
+        #[case] input: &str,
+        #[case] in_location: usize,
+        #[case] command_line: &str,
+        #[case] output: &str,
+        #[case] out_location: usize,
+    ) {
+        let mut editor = editor_with(input, in_location);
+        run_ex_command(command_line, &mut editor, &mut []).unwrap();
+
+        assert_eq!(editor.get_buffer(), output);
+        assert_eq!(editor.insertion_point(), out_location);
+    }
+
+    #[rstest]
+    #[case("%s/baz/bar/", "Pattern not found: baz")]
+    #[case("%s//bar/", "Empty search pattern")]
+    #[case("%s/foo/bar/x", "Invalid substitute flag: x")]
+    #[case("%s/(foo/bar/", "Invalid pattern: (foo")]
+    #[case("w", "No file name")]
+    #[case("frobnicate", "Not an editor command: frobnicate")]
+    fn test_run_ex_command_errors(#[case] command_line: &str, #[case] message: &str) {
+        let mut editor = editor_with("foo", 0);
+
+        assert_eq!(
+            run_ex_command(command_line, &mut editor, &mut []),
+            Err(message.to_string())
+        );
+        assert_eq!(editor.get_buffer(), "foo");
+    }
+
+    #[test]
+    fn test_custom_ex_command() {
+        let mut editor = editor_with("foo", 0);
+        let mut commands: Vec<Box<dyn ExCommand>> = vec![Box::new(Upcase)];
+
+        run_ex_command("upcase  bar", &mut editor, &mut commands).unwrap();
+
+        assert_eq!(editor.get_buffer(), "FOObar");
+    }
+
+    #[test]
+    fn test_write_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("buffer.txt");
+        let mut editor = editor_with("line 1\nline 2", 0);
+
+        run_ex_command(
+            &format!("w {}", path.to_string_lossy()),
+            &mut editor,
+            &mut [],
+        )
+        .unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, "line 1\nline 2");
+    }
+}
@@ -0,0 +1,122 @@
+use crate::StyledText;
+use nu_ansi_term::{Color, Style};
+use std::ops::Range;
+
+/// Long pasted blocks of the buffer, painted collapsed to a single marker like `⋯ 120 lines`,
+/// see [`crate::Reedline::with_paste_folding`]
+///
+/// Only the painting is folded: the buffer, and so the submitted line, keeps the whole
+/// block. A block is painted in full while the cursor is in it and forgotten once it is
+/// edited or the buffer no longer contains it.
+pub(crate) struct PasteFolds {
+    min_lines: usize,
+    blocks: Vec<String>,
+    expanded: bool,
+}
+
+impl PasteFolds {
+    pub(crate) fn new(min_lines: usize) -> Self {
+        Self {
+            min_lines,
+            blocks: Vec::new(),
+            expanded: false,
+        }
+    }
+
+    /// Remember the inserted `text` as a block to fold if it has more than the minimum of lines
+    pub(crate) fn add(&mut self, text: &str) {
+        if text.lines().count() > self.min_lines {
+            self.blocks.push(text.to_string());
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Expand all the blocks, or collapse them again, `false` if there are none
+    pub(crate) fn toggle(&mut self) -> bool {
+        if self.blocks.is_empty() {
+            return false;
+        }
+        self.expanded = !self.expanded;
+        true
+    }
+
+    // The ranges of `buffer` painted collapsed. Blocks no longer in the buffer are forgotten.
+    fn collapsed_ranges(&mut self, buffer: &str, cursor: usize) -> Vec<Range<usize>> {
+        self.blocks.retain(|block| buffer.contains(block.as_str()));
+        if self.blocks.is_empty() {
+            self.expanded = false;
+        }
+        if self.expanded {
+            return Vec::new();
+        }
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for block in &self.blocks {
+            let found = buffer.match_indices(block.as_str()).find(|(start, _)| {
+                ranges
+                    .iter()
+                    .all(|range| start + block.len() <= range.start || *start >= range.end)
+            });
+            if let Some((start, _)) = found {
+                ranges.push(start..start + block.len());
+            }
+        }
+        ranges.retain(|range| cursor <= range.start || cursor >= range.end);
+        ranges.sort_by_key(|range| range.start);
+        ranges
+    }
+
+    /// Collapse the blocks of `buffer` in its `styled_text`, returning where the cursor is
+    /// painted
+    pub(crate) fn fold(
+        &mut self,
+        styled_text: &mut StyledText,
+        buffer: &str,
+        cursor: usize,
+    ) -> usize {
+        let mut painted_cursor = cursor;
+        for range in self.collapsed_ranges(buffer, cursor).into_iter().rev() {
+            let lines = buffer[range.clone()].lines().count();
+            let marker = format!("⋯ {lines} lines");
+            if cursor >= range.end {
+                painted_cursor = painted_cursor - range.len() + marker.len();
+            }
+            styled_text.replace_range(range, (Style::new().fg(Color::DarkGray), marker));
+        }
+        painted_cursor
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn long_blocks_are_collapsed_unless_the_cursor_is_in_them() {
+        let mut folds = PasteFolds::new(2);
+        folds.add("a\nb");
+        folds.add("1\n2\n3");
+        let buffer = "echo 1\n2\n3 done";
+
+        let mut styled_text = StyledText::new();
+        styled_text.push((Style::new(), buffer.to_string()));
+        let cursor = folds.fold(&mut styled_text, buffer, buffer.len());
+        assert_eq!(styled_text.raw_string(), "echo ⋯ 3 lines done");
+        assert_eq!(cursor, "echo ⋯ 3 lines done".len());
+
+        let mut styled_text = StyledText::new();
+        styled_text.push((Style::new(), buffer.to_string()));
+        assert_eq!(folds.fold(&mut styled_text, buffer, 7), 7);
+        assert_eq!(styled_text.raw_string(), buffer);
+
+        assert!(folds.toggle());
+        assert!(folds.collapsed_ranges(buffer, 0).is_empty());
+        assert!(folds.toggle());
+        assert_eq!(folds.collapsed_ranges(buffer, 0), vec![5..10]);
+        assert!(folds.collapsed_ranges("echo 1\n2", 0).is_empty());
+        assert!(!folds.toggle());
+    }
+}
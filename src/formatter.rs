@@ -0,0 +1,143 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Pretty-prints the whole buffer on [`crate::ReedlineEvent::FormatBuffer`], for shells that
+/// can format their own syntax
+///
+/// The buffer is replaced by the result in a single undo step. The cursor keeps its place
+/// among the characters that aren't whitespace, as formatting mostly moves these around.
+/// Closures taking the buffer are formatters too:
+///
+/// ```rust
+/// use reedline::{Reedline, ReedlineEvent};
+///
+/// let mut line_editor = Reedline::create().with_formatter(Box::new(|buffer: &str| {
+///     Some(buffer.split_whitespace().collect::<Vec<_>>().join(" "))
+/// }));
+/// ```
+pub trait BufferFormatter: Send {
+    /// The formatted `buffer`, or `None` to leave it as is, e.g. when it doesn't parse
+    fn format(&mut self, buffer: &str) -> Option<String>;
+}
+
+impl<F> BufferFormatter for F
+where
+    F: FnMut(&str) -> Option<String> + Send,
+{
+    fn format(&mut self, buffer: &str) -> Option<String> {
+        self(buffer)
+    }
+}
+
+/// A [`BufferFormatter`] piping the buffer through an external command, e.g. `shfmt`
+///
+/// The command gets the buffer on its standard input and the buffer is replaced by its
+/// standard output, without the trailing newline. If the command fails the buffer stays as
+/// it is.
+///
+/// ```rust
+/// use reedline::{CommandFormatter, Reedline};
+/// use std::process::Command;
+///
+/// let mut line_editor =
+///     Reedline::create().with_formatter(Box::new(CommandFormatter::new(Command::new("shfmt"))));
+/// ```
+pub struct CommandFormatter {
+    command: Command,
+}
+
+impl CommandFormatter {
+    /// Format with `command`, run once for every formatting
+    pub fn new(command: Command) -> Self {
+        Self { command }
+    }
+}
+
+impl BufferFormatter for CommandFormatter {
+    fn format(&mut self, buffer: &str) -> Option<String> {
+        let mut child = self
+            .command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        // Written on another thread, a command may start printing before it read everything
+        let mut stdin = child.stdin.take()?;
+        let input = buffer.to_string();
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output().ok()?;
+        writer.join().ok()?.ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let formatted = String::from_utf8(output.stdout).ok()?;
+        Some(formatted.trim_end_matches(['\n', '\r']).to_string())
+    }
+}
+
+/// The position in `formatted` with as many non-whitespace characters before it as there
+/// are before `cursor` in `buffer`
+///
+/// A cursor right before a word stays before it, one after a word stays after it.
+pub(crate) fn formatted_cursor(buffer: &str, cursor: usize, formatted: &str) -> usize {
+    let before = buffer[..cursor]
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .count();
+    let at_word = buffer[cursor..]
+        .chars()
+        .next()
+        .map_or(false, |c| !c.is_whitespace());
+    if before == 0 && !at_word {
+        return 0;
+    }
+    let mut seen = 0;
+    for (index, c) in formatted.char_indices() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if at_word && seen == before {
+            return index;
+        }
+        seen += 1;
+        if !at_word && seen == before {
+            return index + c.len_utf8();
+        }
+    }
+    formatted.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("if  true{echo   1}", 13, "if true {\n    echo 1\n}", 18)]
+    #[case("if  true{echo   1}", 16, "if true {\n    echo 1\n}", 19)]
+    #[case("ls   -la", 5, "ls -la", 3)]
+    #[case("ls   -la", 2, "ls -la", 2)]
+    #[case("  ls", 0, "ls", 0)]
+    #[case("ls -la", 6, "ls -l", 5)]
+    fn the_cursor_keeps_its_place_among_the_words(
+        #[case] buffer: &str,
+        #[case] cursor: usize,
+        #[case] formatted: &str,
+        #[case] expected: usize,
+    ) {
+        assert_eq!(formatted_cursor(buffer, cursor, formatted), expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_format_from_stdin_to_stdout() {
+        let mut formatter = CommandFormatter::new(Command::new("rev"));
+        assert_eq!(formatter.format("ls -la").as_deref(), Some("al- sl"));
+
+        let mut formatter = CommandFormatter::new(Command::new("false"));
+        assert_eq!(formatter.format("ls -la"), None);
+    }
+}
@@ -0,0 +1,47 @@
+//! Timing breakdown of the work done for a batch of input events, and cache statistics
+//!
+//! Enabled by the `tracing` feature, which also emits [`tracing`](https://docs.rs/tracing)
+//! spans for the individual stages.
+#[cfg(feature = "tracing")]
+use std::time::Duration;
+
+/// Time spent in the stages of handling one batch of input events ("frame"), from
+/// reading the keystrokes up to the repaint of the line
+///
+/// The durations of a stage are summed up, if it runs several times during a frame.
+///
+/// ## Required feature:
+/// `tracing`
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameTimings {
+    /// Translating the terminal events with the [`crate::EditMode`]
+    pub event_parsing: Duration,
+    /// Running the resulting [`crate::ReedlineEvent`]s against the editor
+    ///
+    /// Contains the stages below if an event repaints by itself, e.g. on submit
+    pub event_handling: Duration,
+    /// [`crate::Highlighter::highlight`]
+    pub highlighting: Duration,
+    /// [`crate::Hinter::handle`]
+    pub hinting: Duration,
+    /// Updating the active menu, including the calls to the [`crate::Completer`]
+    pub completion: Duration,
+    /// Writing the prompt, buffer and menu to the terminal
+    pub painting: Duration,
+    /// The whole frame, including work not covered by the stages above
+    pub total: Duration,
+}
+
+/// How often a cache of the engine could reuse an earlier result
+///
+/// ## Required feature:
+/// `tracing`
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+    /// Lookups that had to compute the result
+    pub misses: u64,
+}
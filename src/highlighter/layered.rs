@@ -0,0 +1,232 @@
+use crate::highlighter::Highlighter;
+use crate::{painting::overlay, StyledText};
+use nu_ansi_term::Style;
+use std::{any::Any, ops::Range};
+
+/// Combines several highlighters, e.g. for syntax, matching brackets, search matches and
+/// diagnostics, into one
+///
+/// Every layer styles the whole line and the layers are painted on top of each other, in
+/// the order of their priorities. A layer only overrides what it sets: text it leaves in
+/// the default style shows the layers below it, and a layer setting only a background keeps
+/// the foreground of the layers below. Layers of the same priority are painted in the
+/// order they were added.
+///
+/// Any [`Highlighter`] can be a layer as is, as long as it returns the line unchanged. To
+/// move from a single highlighter passed to [`crate::Reedline::with_highlighter`], add it
+/// as the lowest layer and pass the [`LayeredHighlighter`] instead:
+///
+/// ```rust
+/// use reedline::{ExampleHighlighter, LayeredHighlighter, Reedline, SimpleMatchHighlighter};
+///
+/// let highlighter = LayeredHighlighter::new()
+///     .with_layer(0, Box::new(ExampleHighlighter::new(vec!["cargo".into()])))
+///     .with_layer(10, Box::new(SimpleMatchHighlighter::new("test".into())));
+/// let mut line_editor = Reedline::create().with_highlighter(Box::new(highlighter));
+/// ```
+#[derive(Default)]
+pub struct LayeredHighlighter {
+    // Sorted by priority, lowest first
+    layers: Vec<(i32, Box<dyn Highlighter>)>,
+}
+
+impl LayeredHighlighter {
+    /// A highlighter without layers, leaving the line unstyled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `highlighter` as a layer, painted over the layers of lower `priority`
+    #[must_use]
+    pub fn with_layer(mut self, priority: i32, highlighter: Box<dyn Highlighter>) -> Self {
+        self.add_layer(priority, highlighter);
+        self
+    }
+
+    /// Add `highlighter` as a layer, painted over the layers of lower `priority`
+    pub fn add_layer(&mut self, priority: i32, highlighter: Box<dyn Highlighter>) {
+        let idx = self
+            .layers
+            .partition_point(|(layer_priority, _)| *layer_priority <= priority);
+        self.layers.insert(idx, (priority, highlighter));
+    }
+
+    /// The number of layers
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Whether there are no layers
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+}
+
+impl Highlighter for LayeredHighlighter {
+    fn highlight(&self, line: &str, cursor: usize) -> StyledText {
+        let layers = self
+            .layers
+            .iter()
+            .map(|(_, layer)| layer.highlight(line, cursor))
+            .collect();
+        merge(line, layers)
+    }
+
+    fn highlight_in_context(&self, line: &str, cursor: usize, context: &dyn Any) -> StyledText {
+        let layers = self
+            .layers
+            .iter()
+            .map(|(_, layer)| layer.highlight_in_context(line, cursor, context))
+            .collect();
+        merge(line, layers)
+    }
+}
+
+/// The layers painted on top of each other, lowest first
+fn merge(line: &str, layers: Vec<StyledText>) -> StyledText {
+    let underlines = layers
+        .iter()
+        .flat_map(|layer| layer.underlines.iter().cloned())
+        .collect();
+    let links = layers
+        .iter()
+        .flat_map(|layer| layer.links.iter().cloned())
+        .collect();
+    let layers: Vec<_> = layers
+        .iter()
+        .map(|layer| spans(layer, line.len()))
+        .collect();
+    let mut boundaries: Vec<_> = layers
+        .iter()
+        .flatten()
+        .flat_map(|(range, _)| [range.start, range.end])
+        .filter(|idx| line.is_char_boundary(*idx))
+        .chain([0, line.len()])
+        .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut styled_text = StyledText::new();
+    for segment in boundaries.windows(2) {
+        let (start, end) = (segment[0], segment[1]);
+        let style = layers.iter().fold(Style::default(), |style, layer| {
+            match layer.iter().find(|(range, _)| range.contains(&start)) {
+                Some((_, layer_style)) => overlay(style, *layer_style),
+                None => style,
+            }
+        });
+        match styled_text.buffer.last_mut() {
+            Some((last_style, text)) if *last_style == style => text.push_str(&line[start..end]),
+            _ => styled_text.push((style, line[start..end].to_string())),
+        }
+    }
+    styled_text.underlines = underlines;
+    styled_text.links = links;
+    styled_text
+}
+
+/// The byte ranges of the parts of `styled_text`, up to `len`
+fn spans(styled_text: &StyledText, len: usize) -> Vec<(Range<usize>, Style)> {
+    let mut start = 0;
+    styled_text
+        .buffer
+        .iter()
+        .filter_map(|(style, text)| {
+            let range = start.min(len)..(start + text.len()).min(len);
+            start += text.len();
+            (!range.is_empty()).then_some((range, *style))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SimpleMatchHighlighter, Underline};
+    use nu_ansi_term::Color;
+    use pretty_assertions::assert_eq;
+
+    fn styled(parts: &[(Style, &str)]) -> Vec<(Style, String)> {
+        parts
+            .iter()
+            .map(|(style, text)| (*style, text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn higher_layers_are_painted_over_lower_ones() {
+        let green = Style::new().fg(Color::Green);
+        let red = Style::new().fg(Color::Red);
+        let highlighter = LayeredHighlighter::new()
+            .with_layer(
+                5,
+                Box::new(SimpleMatchHighlighter::new("cd".into()).with_match_style(red)),
+            )
+            .with_layer(
+                0,
+                Box::new(SimpleMatchHighlighter::new("bcd".into()).with_match_style(green)),
+            );
+
+        assert_eq!(
+            highlighter.highlight("abcde", 0).buffer,
+            styled(&[
+                (Style::default(), "a"),
+                (green, "b"),
+                (red, "cd"),
+                (Style::default(), "e")
+            ])
+        );
+    }
+
+    #[test]
+    fn layers_only_override_what_they_set() {
+        let green = Style::new().fg(Color::Green);
+        let on_blue = Style::new().on(Color::Blue).bold();
+        let highlighter = LayeredHighlighter::new()
+            .with_layer(
+                0,
+                Box::new(SimpleMatchHighlighter::new("ls -l".into()).with_match_style(green)),
+            )
+            .with_layer(
+                1,
+                Box::new(SimpleMatchHighlighter::new("-".into()).with_match_style(on_blue)),
+            );
+
+        assert_eq!(
+            highlighter.highlight("ls -l x", 0).buffer,
+            styled(&[
+                (green, "ls "),
+                (Color::Green.on(Color::Blue).bold(), "-"),
+                (green, "l"),
+                (Style::default(), " x")
+            ])
+        );
+        assert_eq!(
+            LayeredHighlighter::new().highlight("ls", 0).buffer,
+            styled(&[(Style::default(), "ls")])
+        );
+    }
+
+    struct Diagnostics;
+
+    impl Highlighter for Diagnostics {
+        fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
+            let mut styled_text = StyledText::new();
+            styled_text.push((Style::default(), line.to_string()));
+            styled_text.underline_range(0, 2, Underline::new().with_color(Color::Red));
+            styled_text
+        }
+    }
+
+    #[test]
+    fn the_underlines_of_all_layers_are_kept() {
+        let highlighter = LayeredHighlighter::new()
+            .with_layer(0, Box::new(SimpleMatchHighlighter::new("ls".into())))
+            .with_layer(1, Box::new(Diagnostics));
+
+        assert_eq!(
+            highlighter.highlight("ls -l", 0).underlines,
+            [(0..2, Underline::new().with_color(Color::Red))]
+        );
+    }
+}
@@ -1,9 +1,12 @@
 mod example;
+mod layered;
 mod simple_match;
 
 use crate::StyledText;
+use std::any::Any;
 
 pub use example::ExampleHighlighter;
+pub use layered::LayeredHighlighter;
 pub use simple_match::SimpleMatchHighlighter;
 /// The syntax highlighting trait. Implementers of this trait will take in the current string and then
 /// return a `StyledText` object, which represents the contents of the original line as styled strings
@@ -12,4 +15,13 @@ pub trait Highlighter: Send {
     ///
     /// Cursor position as byte offsets in the string
     fn highlight(&self, line: &str, cursor: usize) -> StyledText;
+
+    /// [`Highlighter::highlight`] with the state of the application given to
+    /// [`crate::Reedline::with_context`]
+    ///
+    /// Called instead of [`Highlighter::highlight`] once there is a context.
+    fn highlight_in_context(&self, line: &str, cursor: usize, context: &dyn Any) -> StyledText {
+        let _ = context;
+        self.highlight(line, cursor)
+    }
 }
@@ -1,11 +1,19 @@
-use crate::{hinter::get_first_token, history::SearchQuery, Hinter, History};
+use crate::{
+    hinter::get_first_token, history::SearchQuery, CompletionUsage, Hinter, History, StyledText,
+};
 use nu_ansi_term::{Color, Style};
 
 /// A hinter that uses the completions or the history to show a hint to the user
+///
+/// The first token of the hint, the part that completes the current word and that
+/// [`crate::ReedlineEvent::HistoryHintWordComplete`] accepts, can stand out from the rest
+/// with [`DefaultHinter::with_token_style`].
 pub struct DefaultHinter {
     style: Style,
+    token_style: Option<Style>,
     current_hint: String,
     min_chars: usize,
+    completion_usage: Option<CompletionUsage>,
 }
 
 impl Hinter for DefaultHinter {
@@ -16,7 +24,10 @@ impl Hinter for DefaultHinter {
         history: &dyn History,
         use_ansi_coloring: bool,
     ) -> String {
-        self.current_hint = if line.chars().count() >= self.min_chars {
+        let learned = self.learned_hint(line);
+        self.current_hint = if let Some(learned) = learned {
+            learned
+        } else if line.chars().count() >= self.min_chars {
             history
                 .search(SearchQuery::last_with_prefix(
                     line.to_string(),
@@ -36,7 +47,7 @@ impl Hinter for DefaultHinter {
         };
 
         if use_ansi_coloring && !self.current_hint.is_empty() {
-            self.style.paint(&self.current_hint).to_string()
+            self.styled_hint().render_simple()
         } else {
             self.current_hint.clone()
         }
@@ -49,14 +60,20 @@ impl Hinter for DefaultHinter {
     fn next_hint_token(&self) -> String {
         get_first_token(&self.current_hint)
     }
+
+    fn hint_depends_on_cursor(&self) -> bool {
+        false
+    }
 }
 
 impl Default for DefaultHinter {
     fn default() -> Self {
         DefaultHinter {
             style: Style::new().fg(Color::LightGray),
+            token_style: None,
             current_hint: String::new(),
             min_chars: 1,
+            completion_usage: None,
         }
     }
 }
@@ -69,10 +86,92 @@ impl DefaultHinter {
         self
     }
 
+    /// A builder that sets the style of the first token of the hint, by default the style of
+    /// the rest of the hint
+    #[must_use]
+    pub fn with_token_style(mut self, style: Style) -> Self {
+        self.token_style = Some(style);
+        self
+    }
+
     /// A builder that sets the number of characters that have to be present to enable history hints
     #[must_use]
     pub fn with_min_chars(mut self, min_chars: usize) -> Self {
         self.min_chars = min_chars;
         self
     }
+
+    /// A builder that prefers completing the last word with the suggestion picked most
+    /// for it from the menus over the history
+    #[must_use]
+    pub fn with_completion_usage(mut self, usage: CompletionUsage) -> Self {
+        self.completion_usage = Some(usage);
+        self
+    }
+
+    fn styled_hint(&self) -> StyledText {
+        let token = get_first_token(&self.current_hint);
+        let mut styled_hint = StyledText::new();
+        styled_hint.push((self.token_style.unwrap_or(self.style), token.clone()));
+        let rest = &self.current_hint[token.len()..];
+        if !rest.is_empty() {
+            styled_hint.push((self.style, rest.to_string()));
+        }
+        styled_hint
+    }
+
+    fn learned_hint(&self, line: &str) -> Option<String> {
+        let usage = self.completion_usage.as_ref()?;
+        let token = line.rsplit(char::is_whitespace).next()?;
+        if token.is_empty() {
+            return None;
+        }
+        usage
+            .best_completion(token)
+            .map(|value| value[token.len()..].to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{FileBackedHistory, HistoryItem};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn the_hint_is_styled_uniformly_by_default() {
+        let mut history = FileBackedHistory::default();
+        history
+            .save(HistoryItem::from_command_line("cargo build"))
+            .unwrap();
+        let mut hinter = DefaultHinter::default();
+
+        let style = Style::new().fg(Color::LightGray);
+        assert_eq!(
+            hinter.handle("car", 3, &history, true),
+            format!("{}{}", style.paint("go"), style.paint(" build"))
+        );
+    }
+
+    #[test]
+    fn the_first_token_of_the_hint_stands_out() {
+        let mut history = FileBackedHistory::default();
+        history
+            .save(HistoryItem::from_command_line("cargo build --release"))
+            .unwrap();
+        let mut hinter = DefaultHinter::default().with_token_style(Style::new().bold());
+
+        assert_eq!(
+            hinter.handle("car", 3, &history, false),
+            "go build --release"
+        );
+        assert_eq!(
+            hinter.handle("car", 3, &history, true),
+            format!(
+                "{}{}",
+                Style::new().bold().paint("go"),
+                Style::new().fg(Color::LightGray).paint(" build --release")
+            )
+        );
+    }
 }
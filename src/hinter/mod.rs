@@ -27,13 +27,18 @@ pub fn get_first_token(string: &str) -> String {
     result
 }
 
+#[cfg(feature = "tracing")]
+use crate::CacheStats;
 use crate::History;
+use std::any::Any;
 /// A trait that's responsible for returning the hint for the current line and position
 /// Hints are often shown in-line as part of the buffer, showing the user text they can accept or ignore
 pub trait Hinter: Send {
     /// Handle the hinting duty by using the line, position, and current history
     ///
-    /// Returns the formatted output to show the user
+    /// Returns the formatted output to show the user. Parts of the hint can have styles of
+    /// their own, e.g. a [`crate::StyledText`] of segments rendered with
+    /// [`crate::StyledText::render_simple`].
     fn handle(
         &mut self,
         line: &str,
@@ -42,10 +47,198 @@ pub trait Hinter: Send {
         use_ansi_coloring: bool,
     ) -> String;
 
+    /// [`Hinter::handle`] with the state of the application given to
+    /// [`crate::Reedline::with_context`]
+    ///
+    /// Called instead of [`Hinter::handle`] once there is a context.
+    fn handle_in_context(
+        &mut self,
+        line: &str,
+        pos: usize,
+        history: &dyn History,
+        use_ansi_coloring: bool,
+        context: &dyn Any,
+    ) -> String {
+        let _ = context;
+        self.handle(line, pos, history, use_ansi_coloring)
+    }
+
     /// Return the current hint unformatted to perform the completion of the full hint
     fn complete_hint(&self) -> String;
 
     /// Return the first semantic token of the hint
     /// for incremental completion
     fn next_hint_token(&self) -> String;
+
+    /// Whether the hint depends on the position of the cursor in the line
+    ///
+    /// The engine reuses the last hint as long as the line and the history stay the
+    /// same. Returning `false` lets it also keep the hint while only the cursor moves.
+    fn hint_depends_on_cursor(&self) -> bool {
+        true
+    }
+
+    /// Whether the hint of the last call is still being computed, e.g. on another thread
+    ///
+    /// While it is, the engine asks the hinter again every few milliseconds and shows a
+    /// progress indicator once that takes longer than
+    /// [`crate::Reedline::with_progress_indicator_delay`].
+    fn is_pending(&self) -> bool {
+        false
+    }
+}
+
+/// The last hint, reused while neither its line nor the history changed
+///
+/// The engine clears it whenever it changes the history or runs host commands,
+/// that might change what the hinter depends on
+#[derive(Default)]
+pub(crate) struct HintCache {
+    entry: Option<HintCacheEntry>,
+    #[cfg(feature = "tracing")]
+    stats: CacheStats,
+}
+
+struct HintCacheEntry {
+    line: String,
+    // Only set if the hint depends on the cursor
+    pos: Option<usize>,
+    use_ansi_coloring: bool,
+    hint: String,
+    // The hinter was still working on it, so it is asked again
+    pending: bool,
+}
+
+impl HintCache {
+    /// The hint of `hinter` for the line, computed again only if the key changed
+    pub(crate) fn handle(
+        &mut self,
+        hinter: &mut dyn Hinter,
+        line: &str,
+        pos: usize,
+        history: &dyn History,
+        use_ansi_coloring: bool,
+    ) -> &str {
+        let key_pos = hinter.hint_depends_on_cursor().then_some(pos);
+        let is_cached = self.entry.as_ref().map_or(false, |entry| {
+            !entry.pending
+                && entry.line == line
+                && entry.pos == key_pos
+                && entry.use_ansi_coloring == use_ansi_coloring
+        });
+
+        #[cfg(feature = "tracing")]
+        if is_cached {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+        if !is_cached {
+            let hint = hinter.handle(line, pos, history, use_ansi_coloring);
+            self.entry = Some(HintCacheEntry {
+                line: line.to_string(),
+                pos: key_pos,
+                use_ansi_coloring,
+                hint,
+                pending: hinter.is_pending(),
+            });
+        }
+        self.entry.as_ref().map_or("", |entry| entry.hint.as_str())
+    }
+
+    /// Forget the last hint, the next one is computed by the hinter
+    pub(crate) fn clear(&mut self) {
+        self.entry = None;
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FileBackedHistory;
+    use pretty_assertions::assert_eq;
+
+    // Hints the number of times it was asked, like a hinter with an expensive search
+    struct CountingHinter {
+        calls: usize,
+        depends_on_cursor: bool,
+        pending: bool,
+    }
+
+    impl Hinter for CountingHinter {
+        fn handle(&mut self, _: &str, _: usize, _: &dyn History, _: bool) -> String {
+            self.calls += 1;
+            self.calls.to_string()
+        }
+
+        fn complete_hint(&self) -> String {
+            String::new()
+        }
+
+        fn next_hint_token(&self) -> String {
+            String::new()
+        }
+
+        fn hint_depends_on_cursor(&self) -> bool {
+            self.depends_on_cursor
+        }
+
+        fn is_pending(&self) -> bool {
+            self.pending
+        }
+    }
+
+    #[test]
+    fn hint_cache_reuses_hint_until_line_changes_or_cleared() {
+        let history = FileBackedHistory::default();
+        let mut hinter = CountingHinter {
+            calls: 0,
+            depends_on_cursor: false,
+            pending: false,
+        };
+        let mut cache = HintCache::default();
+
+        assert_eq!(cache.handle(&mut hinter, "ls", 2, &history, true), "1");
+        assert_eq!(cache.handle(&mut hinter, "ls", 1, &history, true), "1");
+        assert_eq!(cache.handle(&mut hinter, "ls -", 4, &history, true), "2");
+        assert_eq!(cache.handle(&mut hinter, "ls -", 4, &history, false), "3");
+        cache.clear();
+        assert_eq!(cache.handle(&mut hinter, "ls -", 4, &history, false), "4");
+    }
+
+    #[test]
+    fn hint_cache_respects_cursor_dependent_hinters() {
+        let history = FileBackedHistory::default();
+        let mut hinter = CountingHinter {
+            calls: 0,
+            depends_on_cursor: true,
+            pending: false,
+        };
+        let mut cache = HintCache::default();
+
+        assert_eq!(cache.handle(&mut hinter, "ls", 2, &history, true), "1");
+        assert_eq!(cache.handle(&mut hinter, "ls", 2, &history, true), "1");
+        assert_eq!(cache.handle(&mut hinter, "ls", 1, &history, true), "2");
+    }
+
+    #[test]
+    fn hint_cache_asks_pending_hinters_again() {
+        let history = FileBackedHistory::default();
+        let mut hinter = CountingHinter {
+            calls: 0,
+            depends_on_cursor: false,
+            pending: true,
+        };
+        let mut cache = HintCache::default();
+
+        assert_eq!(cache.handle(&mut hinter, "ls", 2, &history, true), "1");
+        hinter.pending = false;
+        assert_eq!(cache.handle(&mut hinter, "ls", 2, &history, true), "2");
+        assert_eq!(cache.handle(&mut hinter, "ls", 2, &history, true), "2");
+    }
 }
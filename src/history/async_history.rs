@@ -0,0 +1,499 @@
+use super::{
+    base::{up_to_page, SearchQuery},
+    History, HistoryItem, HistoryItemId, HistorySessionId,
+};
+use crate::{ReedlineError, ReedlineErrorVariants, Result};
+use std::{
+    future::Future,
+    ops::ControlFlow,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Wake, Waker},
+    thread::{self, Thread},
+};
+
+/// The result of an operation of an [`AsyncHistory`]
+pub type HistoryFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + 'a>>;
+
+/// A [`History`] whose operations can wait without blocking, e.g. for a sync server
+///
+/// The editor calls the history synchronously, while the line is edited. Wrap an
+/// [`AsyncHistory`] in a [`BlockingHistory`] to use it with [`crate::Reedline::with_history`],
+/// which waits for the operations either itself or with the executor of the application.
+///
+/// The methods work like the ones of the same name of [`History`].
+///
+/// ## Required feature:
+/// `async_history`
+pub trait AsyncHistory: Send {
+    /// see [`History::save`]
+    fn save(&mut self, item: HistoryItem) -> HistoryFuture<'_, HistoryItem>;
+    /// see [`History::save_batch`]
+    ///
+    /// The default implementation saves the items one by one.
+    fn save_batch(&mut self, items: Vec<HistoryItem>) -> HistoryFuture<'_, Vec<HistoryItem>> {
+        Box::pin(async move {
+            let mut saved = Vec::with_capacity(items.len());
+            for item in items {
+                saved.push(self.save(item).await?);
+            }
+            Ok(saved)
+        })
+    }
+    /// see [`History::load`]
+    fn load(&self, id: HistoryItemId) -> HistoryFuture<'_, HistoryItem>;
+    /// see [`History::count`]
+    fn count(&self, query: SearchQuery) -> HistoryFuture<'_, i64>;
+    /// see [`History::search`]
+    fn search(&self, query: SearchQuery) -> HistoryFuture<'_, Vec<HistoryItem>>;
+    /// see [`History::search_paged`]
+    ///
+    /// The default implementation searches up to the end of the page and drops the pages
+    /// before it.
+    fn search_paged(
+        &self,
+        query: SearchQuery,
+        page: usize,
+        page_size: usize,
+    ) -> HistoryFuture<'_, Vec<HistoryItem>> {
+        let (query, skip) = up_to_page(query, page, page_size);
+        Box::pin(async move { Ok(self.search(query).await?.into_iter().skip(skip).collect()) })
+    }
+    /// see [`History::search_streaming`]
+    ///
+    /// The default implementation hands on the items of [`AsyncHistory::search`].
+    fn search_streaming<'a>(
+        &'a self,
+        query: SearchQuery,
+        on_item: &'a mut dyn FnMut(HistoryItem) -> ControlFlow<()>,
+    ) -> HistoryFuture<'a, ()> {
+        Box::pin(async move {
+            for item in self.search(query).await? {
+                if on_item(item).is_break() {
+                    break;
+                }
+            }
+            Ok(())
+        })
+    }
+    /// see [`History::search_batch`]
+    ///
+    /// The default implementation runs the queries one by one.
+    fn search_batch(&self, queries: Vec<SearchQuery>) -> HistoryFuture<'_, Vec<Vec<HistoryItem>>> {
+        Box::pin(async move {
+            let mut results = Vec::with_capacity(queries.len());
+            for query in queries {
+                results.push(self.search(query).await?);
+            }
+            Ok(results)
+        })
+    }
+    /// see [`History::update`]
+    fn update<'a>(
+        &'a mut self,
+        id: HistoryItemId,
+        updater: &'a dyn Fn(HistoryItem) -> HistoryItem,
+    ) -> HistoryFuture<'a, ()>;
+    /// see [`History::clear`]
+    fn clear(&mut self) -> HistoryFuture<'_, ()>;
+    /// see [`History::delete`]
+    fn delete(&mut self, id: HistoryItemId) -> HistoryFuture<'_, ()>;
+    /// see [`History::set_pinned`]
+    ///
+    /// The default implementation doesn't support pinning.
+    fn set_pinned(&mut self, id: HistoryItemId, pinned: bool) -> HistoryFuture<'_, ()> {
+        let _ = (id, pinned);
+        unsupported_pinning()
+    }
+    /// see [`History::is_pinned`]
+    ///
+    /// The default implementation doesn't support pinning.
+    fn is_pinned(&self, id: HistoryItemId) -> HistoryFuture<'_, bool> {
+        let _ = id;
+        unsupported_pinning()
+    }
+    /// see [`History::pinned`]
+    ///
+    /// The default implementation doesn't support pinning.
+    fn pinned(&self) -> HistoryFuture<'_, Vec<HistoryItem>> {
+        unsupported_pinning()
+    }
+    /// see [`History::supports_directories`]
+    fn supports_directories(&self) -> bool {
+        false
+    }
+    /// see [`History::sync`]
+    fn sync(&mut self) -> HistoryFuture<'_, ()>;
+    /// see [`History::session`]
+    fn session(&self) -> Option<HistorySessionId>;
+}
+
+fn unsupported_pinning<T>() -> HistoryFuture<'static, T> {
+    Box::pin(async {
+        Err(ReedlineError(
+            ReedlineErrorVariants::HistoryFeatureUnsupported {
+                history: "this history",
+                feature: "pinning entries",
+            },
+        ))
+    })
+}
+
+// Runs a future to completion, see `BlockingHistory::with_executor`
+type Executor = Box<dyn Fn(Pin<Box<dyn Future<Output = ()> + '_>>) + Send>;
+
+/// A [`History`] running the operations of an [`AsyncHistory`] to completion
+///
+/// By default the operations are polled on the thread of the editor, which is parked
+/// while they wait. Futures that need a runtime, e.g. the I/O of tokio, are run with
+/// [`BlockingHistory::with_executor`] instead.
+///
+/// ## Required feature:
+/// `async_history`
+pub struct BlockingHistory<H> {
+    history: H,
+    executor: Executor,
+}
+
+impl<H: AsyncHistory> BlockingHistory<H> {
+    /// Wait for the operations of `history` on the thread of the editor
+    pub fn new(history: H) -> Self {
+        Self {
+            history,
+            executor: Box::new(block_on),
+        }
+    }
+
+    /// A builder that runs the operations with `executor`, which returns once the given
+    /// future is complete, e.g. `move |future| runtime.block_on(future)` for a tokio runtime
+    #[must_use]
+    pub fn with_executor(
+        mut self,
+        executor: impl Fn(Pin<Box<dyn Future<Output = ()> + '_>>) + Send + 'static,
+    ) -> Self {
+        self.executor = Box::new(executor);
+        self
+    }
+
+    /// The wrapped history
+    pub fn inner(&self) -> &H {
+        &self.history
+    }
+
+    /// Mutable access to the wrapped history
+    pub fn inner_mut(&mut self) -> &mut H {
+        &mut self.history
+    }
+}
+
+// Waits for `future` with the executor, which can't name the result type itself
+fn run<T>(executor: &Executor, future: HistoryFuture<'_, T>) -> Result<T> {
+    let mut output = None;
+    executor(Box::pin(async {
+        output = Some(future.await);
+    }));
+    output.expect("the executor returned before the future was complete")
+}
+
+impl<H: AsyncHistory> History for BlockingHistory<H> {
+    fn save(&mut self, h: HistoryItem) -> Result<HistoryItem> {
+        run(&self.executor, self.history.save(h))
+    }
+
+    fn save_batch(&mut self, items: Vec<HistoryItem>) -> Result<Vec<HistoryItem>> {
+        run(&self.executor, self.history.save_batch(items))
+    }
+
+    fn load(&self, id: HistoryItemId) -> Result<HistoryItem> {
+        run(&self.executor, self.history.load(id))
+    }
+
+    fn count(&self, query: SearchQuery) -> Result<i64> {
+        run(&self.executor, self.history.count(query))
+    }
+
+    fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>> {
+        run(&self.executor, self.history.search(query))
+    }
+
+    fn search_streaming(
+        &self,
+        query: SearchQuery,
+        on_item: &mut dyn FnMut(HistoryItem) -> ControlFlow<()>,
+    ) -> Result<()> {
+        run(
+            &self.executor,
+            self.history.search_streaming(query, on_item),
+        )
+    }
+
+    fn search_batch(&self, queries: Vec<SearchQuery>) -> Result<Vec<Vec<HistoryItem>>> {
+        run(&self.executor, self.history.search_batch(queries))
+    }
+
+    fn search_paged(
+        &self,
+        query: SearchQuery,
+        page: usize,
+        page_size: usize,
+    ) -> Result<Vec<HistoryItem>> {
+        run(
+            &self.executor,
+            self.history.search_paged(query, page, page_size),
+        )
+    }
+
+    fn update(
+        &mut self,
+        id: HistoryItemId,
+        updater: &dyn Fn(HistoryItem) -> HistoryItem,
+    ) -> Result<()> {
+        run(&self.executor, self.history.update(id, updater))
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        run(&self.executor, self.history.clear())
+    }
+
+    fn delete(&mut self, h: HistoryItemId) -> Result<()> {
+        run(&self.executor, self.history.delete(h))
+    }
+
+    fn set_pinned(&mut self, id: HistoryItemId, pinned: bool) -> Result<()> {
+        run(&self.executor, self.history.set_pinned(id, pinned))
+    }
+
+    fn is_pinned(&self, id: HistoryItemId) -> Result<bool> {
+        run(&self.executor, self.history.is_pinned(id))
+    }
+
+    fn pinned(&self) -> Result<Vec<HistoryItem>> {
+        run(&self.executor, self.history.pinned())
+    }
+
+    fn supports_directories(&self) -> bool {
+        self.history.supports_directories()
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        run(&self.executor, self.history.sync()).map_err(Into::into)
+    }
+
+    fn session(&self) -> Option<HistorySessionId> {
+        self.history.session()
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Polls `future` on the current thread, parking it until the future is woken
+fn block_on(mut future: Pin<Box<dyn Future<Output = ()> + '_>>) {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    while future.as_mut().poll(&mut context).is_pending() {
+        thread::park();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{history::SearchDirection, FileBackedHistory};
+    use pretty_assertions::assert_eq;
+    use std::{
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        task::Poll,
+        time::Duration,
+    };
+
+    // A history behind a slow connection
+    struct Remote {
+        history: FileBackedHistory,
+    }
+
+    // Pending until another thread wakes it a bit later, like a network request
+    struct Delayed<T> {
+        value: Option<T>,
+        done: Option<Arc<AtomicBool>>,
+    }
+
+    fn delayed<T>(value: T) -> Delayed<T> {
+        Delayed {
+            value: Some(value),
+            done: None,
+        }
+    }
+
+    impl<T: Unpin> Future for Delayed<T> {
+        type Output = T;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+            let this = self.get_mut();
+            match &this.done {
+                Some(done) if done.load(Ordering::SeqCst) => {
+                    Poll::Ready(this.value.take().expect("polled after completion"))
+                }
+                Some(_) => Poll::Pending,
+                None => {
+                    let done = Arc::new(AtomicBool::new(false));
+                    this.done = Some(Arc::clone(&done));
+                    let waker = cx.waker().clone();
+                    thread::spawn(move || {
+                        thread::sleep(Duration::from_millis(5));
+                        done.store(true, Ordering::SeqCst);
+                        waker.wake();
+                    });
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    impl AsyncHistory for Remote {
+        fn save(&mut self, item: HistoryItem) -> HistoryFuture<'_, HistoryItem> {
+            let saved = self.history.save(item);
+            Box::pin(delayed(saved))
+        }
+        fn load(&self, id: HistoryItemId) -> HistoryFuture<'_, HistoryItem> {
+            Box::pin(delayed(self.history.load(id)))
+        }
+        fn count(&self, query: SearchQuery) -> HistoryFuture<'_, i64> {
+            Box::pin(delayed(self.history.count(query)))
+        }
+        fn search(&self, query: SearchQuery) -> HistoryFuture<'_, Vec<HistoryItem>> {
+            Box::pin(delayed(self.history.search(query)))
+        }
+        fn update<'a>(
+            &'a mut self,
+            id: HistoryItemId,
+            updater: &'a dyn Fn(HistoryItem) -> HistoryItem,
+        ) -> HistoryFuture<'a, ()> {
+            Box::pin(delayed(self.history.update(id, updater)))
+        }
+        fn clear(&mut self) -> HistoryFuture<'_, ()> {
+            Box::pin(delayed(self.history.clear()))
+        }
+        fn delete(&mut self, id: HistoryItemId) -> HistoryFuture<'_, ()> {
+            Box::pin(delayed(self.history.delete(id)))
+        }
+        fn sync(&mut self) -> HistoryFuture<'_, ()> {
+            Box::pin(delayed(
+                History::sync(&mut self.history).map_err(Into::into),
+            ))
+        }
+        fn session(&self) -> Option<HistorySessionId> {
+            None
+        }
+    }
+
+    #[test]
+    fn async_histories_are_waited_for() {
+        let mut history = BlockingHistory::new(Remote {
+            history: FileBackedHistory::default(),
+        });
+        history
+            .save_batch(vec![
+                HistoryItem::from_command_line("cargo test"),
+                HistoryItem::from_command_line("git status"),
+            ])
+            .unwrap();
+        history.save(HistoryItem::from_command_line("ls")).unwrap();
+
+        let command_lines: Vec<_> = history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect();
+        assert_eq!(command_lines, ["cargo test", "git status", "ls"]);
+        assert_eq!(history.inner().history.count_all().unwrap(), 3);
+    }
+
+    #[test]
+    fn pages_are_searched_up_to_their_end() {
+        let mut history = BlockingHistory::new(Remote {
+            history: FileBackedHistory::default(),
+        });
+        for command_line in ["ls", "cd", "git", "cargo", "rm"] {
+            history
+                .save(HistoryItem::from_command_line(command_line))
+                .unwrap();
+        }
+
+        let page: Vec<_> = history
+            .search_paged(
+                SearchQuery::everything(SearchDirection::Forward, None),
+                1,
+                2,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect();
+        assert_eq!(page, ["git", "cargo"]);
+        let (query, skip) = up_to_page(
+            SearchQuery::everything(SearchDirection::Forward, None),
+            1,
+            2,
+        );
+        assert_eq!((query.limit, skip), (Some(4), 2));
+    }
+
+    #[test]
+    fn streaming_and_the_default_pinning_are_forwarded() {
+        let mut history = BlockingHistory::new(Remote {
+            history: FileBackedHistory::default(),
+        });
+        let saved = history
+            .save_batch(
+                ["ls", "cd", "git"]
+                    .map(HistoryItem::from_command_line)
+                    .to_vec(),
+            )
+            .unwrap();
+
+        let mut streamed = Vec::new();
+        history
+            .search_streaming(
+                SearchQuery::everything(SearchDirection::Backward, None),
+                &mut |item| {
+                    streamed.push(item.command_line);
+                    if streamed.len() == 2 {
+                        ControlFlow::Break(())
+                    } else {
+                        ControlFlow::Continue(())
+                    }
+                },
+            )
+            .unwrap();
+        assert_eq!(streamed, ["git", "cd"]);
+
+        // `Remote` leaves pinning to the default implementation
+        let id = saved[0].id.unwrap();
+        assert!(history.set_pinned(id, true).is_err());
+        assert!(history.is_pinned(id).is_err());
+        assert!(history.pinned().is_err());
+    }
+
+    #[test]
+    fn an_executor_can_run_the_operations() {
+        let polled = Arc::new(AtomicUsize::new(0));
+        let executor_polled = Arc::clone(&polled);
+        let mut history = BlockingHistory::new(Remote {
+            history: FileBackedHistory::default(),
+        })
+        .with_executor(move |future| {
+            executor_polled.fetch_add(1, Ordering::SeqCst);
+            block_on(future)
+        });
+        history.save(HistoryItem::from_command_line("ls")).unwrap();
+        assert_eq!(history.count_all().unwrap(), 1);
+        assert_eq!(polled.load(Ordering::SeqCst), 2);
+    }
+}
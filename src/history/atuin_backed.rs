@@ -0,0 +1,641 @@
+use super::{
+    base::{CommandLineSearch, SearchDirection, SearchQuery},
+    History, HistoryItem, HistoryItemId, HistorySessionId,
+};
+use crate::{
+    result::{ReedlineError, ReedlineErrorVariants},
+    Result,
+};
+use chrono::{TimeZone, Utc};
+use rusqlite::{named_params, params, Connection, OptionalExtension, ToSql};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    ops::ControlFlow,
+    path::PathBuf,
+    time::Duration,
+};
+
+// Atuin stores unknown durations and exit statuses as -1
+const UNKNOWN: i64 = -1;
+
+/// A history kept in the local store of [Atuin](https://atuin.sh), shared with the
+/// shells that Atuin is set up for and synced by it
+///
+/// Entries are read and written in the database of Atuin directly, so the commands of
+/// the application show up in `atuin search` and the next `atuin sync` uploads them.
+/// Talking to a sync server is left to Atuin itself. Deleted entries are only marked as
+/// deleted, like Atuin does, so a sync removes them from the other machines too.
+///
+/// The session id of the editor is stored as Atuin session, entries of the sessions of
+/// other applications have no session id. Their hostnames are `host:user`, like Atuin
+/// writes them.
+///
+/// ## Required feature:
+/// `atuin`
+pub struct AtuinHistory {
+    db: Connection,
+    session: Option<HistorySessionId>,
+    session_timestamp: Option<chrono::DateTime<Utc>>,
+    hostname: String,
+}
+
+fn map_sqlite_err(err: rusqlite::Error) -> ReedlineError {
+    ReedlineError(ReedlineErrorVariants::HistoryDatabaseError(format!(
+        "{err:?}"
+    )))
+}
+
+type BoxedNamedParams<'a> = Vec<(&'static str, Box<dyn ToSql + 'a>)>;
+
+fn deserialize_history_item(row: &rusqlite::Row) -> rusqlite::Result<HistoryItem> {
+    let known = |value: i64| (value != UNKNOWN).then_some(value);
+    Ok(HistoryItem {
+        id: Some(HistoryItemId::new(row.get("rowid")?)),
+        start_timestamp: Some(Utc.timestamp_nanos(row.get("timestamp")?)),
+        command_line: row.get("command")?,
+        session_id: session_id(&row.get::<_, String>("session")?),
+        hostname: Some(row.get("hostname")?),
+        cwd: Some(row.get("cwd")?),
+        duration: known(row.get("duration")?).map(|nanos| Duration::from_nanos(nanos as u64)),
+        exit_status: known(row.get("exit")?),
+        more_info: None,
+    })
+}
+
+// The Atuin session of the editor session `id`
+fn session_name(id: HistorySessionId) -> String {
+    format!("{:032x}", id.0)
+}
+
+// The editor session of an Atuin session, for the ones named by `session_name`
+fn session_id(session: &str) -> Option<HistorySessionId> {
+    let id = u128::from_str_radix(session, 16).ok()?;
+    i64::try_from(id).ok().map(HistorySessionId::new)
+}
+
+// A new id in the format of Atuin: a UUIDv7 in hex without dashes
+fn new_id() -> String {
+    let millis = Utc::now().timestamp_millis() as u128 & ((1 << 48) - 1);
+    let random = |seed: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(seed);
+        hasher.finish() as u128
+    };
+    let random = (random(1) << 64 | random(2)) & ((1 << 74) - 1);
+    // 48 bits time, the version 7, 12 random bits, the variant 0b10, 62 random bits
+    let uuid =
+        millis << 80 | 0x7 << 76 | (random >> 62) << 64 | 0b10 << 62 | (random & ((1 << 62) - 1));
+    format!("{uuid:032x}")
+}
+
+impl AtuinHistory {
+    /// The store of an existing Atuin installation in `file`, usually
+    /// [`AtuinHistory::default_file`]
+    ///
+    /// Fails if the file is not set up by Atuin yet.
+    pub fn with_file(
+        file: PathBuf,
+        session: Option<HistorySessionId>,
+        session_timestamp: Option<chrono::DateTime<Utc>>,
+    ) -> Result<Self> {
+        let db = Connection::open(&file).map_err(map_sqlite_err)?;
+        let store_exists: i64 = db
+            .query_row(
+                "select count(*) from sqlite_master where type = 'table' and name = 'history'",
+                params![],
+                |r| r.get(0),
+            )
+            .map_err(map_sqlite_err)?;
+        if store_exists == 0 {
+            return Err(ReedlineError(ReedlineErrorVariants::ConfigError(format!(
+                "{} is not an Atuin history, set up Atuin first",
+                file.display()
+            ))));
+        }
+        Ok(Self::from_connection(db, session, session_timestamp))
+    }
+
+    /// A store in memory with the tables of Atuin, e.g. for tests
+    pub fn in_memory() -> Result<Self> {
+        let db = Connection::open_in_memory().map_err(map_sqlite_err)?;
+        // The schema after the migrations of Atuin, which creates the tables itself
+        db.execute_batch(
+            "
+        create table history (
+            id text primary key,
+            timestamp integer not null,
+            duration integer not null,
+            exit integer not null,
+            command text not null,
+            cwd text not null,
+            session text not null,
+            hostname text not null,
+            deleted_at integer,
+            unique(timestamp, cwd, command)
+        );
+        ",
+        )
+        .map_err(map_sqlite_err)?;
+        Ok(Self::from_connection(db, None, None))
+    }
+
+    fn from_connection(
+        db: Connection,
+        session: Option<HistorySessionId>,
+        session_timestamp: Option<chrono::DateTime<Utc>>,
+    ) -> Self {
+        // Atuin takes these from the environment too
+        let var = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| std::env::var(name).ok())
+                .unwrap_or_default()
+        };
+        let hostname = format!(
+            "{}:{}",
+            var(&["ATUIN_HOST_NAME", "HOSTNAME"]),
+            var(&["ATUIN_HOST_USER", "USER", "USERNAME"])
+        );
+        Self {
+            db,
+            session,
+            session_timestamp,
+            hostname,
+        }
+    }
+
+    /// Where Atuin keeps its store by default: `$XDG_DATA_HOME/atuin/history.db`, or
+    /// `~/.local/share/atuin/history.db`
+    pub fn default_file() -> Option<PathBuf> {
+        let data_dir = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })?;
+        Some(data_dir.join("atuin").join("history.db"))
+    }
+
+    /// The `host:user` written as hostname of entries saved without one
+    #[must_use]
+    pub fn with_hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = hostname.into();
+        self
+    }
+
+    fn construct_query<'a>(
+        &self,
+        query: &'a SearchQuery,
+        select_expression: &str,
+    ) -> (String, BoxedNamedParams<'a>) {
+        let (is_asc, asc) = match query.direction {
+            SearchDirection::Forward => (true, "asc"),
+            SearchDirection::Backward => (false, "desc"),
+        };
+        let mut wheres = vec!["deleted_at is null"];
+        let mut params: BoxedNamedParams = Vec::new();
+        if let Some(start) = query.start_time {
+            wheres.push(if is_asc {
+                "timestamp > :start_time"
+            } else {
+                "timestamp < :start_time"
+            });
+            params.push((":start_time", Box::new(start.timestamp_nanos_opt())));
+        }
+        if let Some(end) = query.end_time {
+            wheres.push(if is_asc {
+                ":end_time >= timestamp"
+            } else {
+                ":end_time <= timestamp"
+            });
+            params.push((":end_time", Box::new(end.timestamp_nanos_opt())));
+        }
+        if let Some(start) = query.start_id {
+            wheres.push(if is_asc {
+                "rowid > :start_id"
+            } else {
+                "rowid < :start_id"
+            });
+            params.push((":start_id", Box::new(start.0)));
+        }
+        if let Some(end) = query.end_id {
+            wheres.push(if is_asc {
+                ":end_id >= rowid"
+            } else {
+                ":end_id <= rowid"
+            });
+            params.push((":end_id", Box::new(end.0)));
+        }
+        let limit = match query.limit {
+            Some(l) => {
+                params.push((":limit", Box::new(l)));
+                "limit :limit"
+            }
+            None => "",
+        };
+        if let Some(command_line) = &query.filter.command_line {
+            match command_line {
+                CommandLineSearch::Exact(e) => {
+                    wheres.push("command == :command_line");
+                    params.push((":command_line", Box::new(e)));
+                }
+                CommandLineSearch::Prefix(prefix) => {
+                    wheres.push("instr(command, :command_line) == 1");
+                    params.push((":command_line", Box::new(prefix)));
+                }
+                CommandLineSearch::Substring(cont) => {
+                    wheres.push("instr(command, :command_line) >= 1");
+                    params.push((":command_line", Box::new(cont)));
+                }
+            };
+        }
+        if let Some(str) = &query.filter.not_command_line {
+            wheres.push("command != :not_cmd");
+            params.push((":not_cmd", Box::new(str)));
+        }
+        if let Some(hostname) = &query.filter.hostname {
+            wheres.push("hostname = :hostname");
+            params.push((":hostname", Box::new(hostname)));
+        }
+        if let Some(cwd_exact) = &query.filter.cwd_exact {
+            wheres.push("cwd = :cwd");
+            params.push((":cwd", Box::new(cwd_exact)));
+        }
+        if let Some(cwd_prefix) = &query.filter.cwd_prefix {
+            wheres.push("cwd like :cwd_like");
+            let cwd_like = format!("{cwd_prefix}%");
+            params.push((":cwd_like", Box::new(cwd_like)));
+        }
+        if let Some(exit_successful) = query.filter.exit_successful {
+            if exit_successful {
+                wheres.push("exit = 0");
+            } else {
+                wheres.push("exit != 0");
+            }
+        }
+        if let (Some(session_id), Some(session_timestamp)) =
+            (query.filter.session, self.session_timestamp)
+        {
+            // The entries of this session and the ones from before it started
+            wheres.push("(session = :session or timestamp < :session_timestamp)");
+            params.push((":session", Box::new(session_name(session_id))));
+            params.push((
+                ":session_timestamp",
+                Box::new(session_timestamp.timestamp_nanos_opt()),
+            ));
+        }
+        let wheres = wheres.join(" and ");
+        let query = format!(
+            "SELECT {select_expression} \
+             FROM history \
+             WHERE ({wheres}) \
+             ORDER BY rowid {asc} \
+             {limit}"
+        );
+        (query, params)
+    }
+}
+
+impl History for AtuinHistory {
+    fn save(&mut self, mut entry: HistoryItem) -> Result<HistoryItem> {
+        let timestamp = *entry.start_timestamp.get_or_insert_with(Utc::now);
+        let session = entry
+            .session_id
+            .or(self.session)
+            .map(session_name)
+            .unwrap_or_else(new_id);
+        let values = named_params! {
+            ":rowid": entry.id.map(|id| id.0),
+            ":id": new_id(),
+            ":timestamp": timestamp.timestamp_nanos_opt(),
+            ":duration": entry.duration.map_or(UNKNOWN, |duration| duration.as_nanos() as i64),
+            ":exit": entry.exit_status.unwrap_or(UNKNOWN),
+            ":command": entry.command_line,
+            ":cwd": entry.cwd.clone().unwrap_or_default(),
+            ":session": session,
+            ":hostname": entry.hostname.clone().unwrap_or_else(|| self.hostname.clone()),
+        };
+        // The id of Atuin stays the same for updates, so syncing replaces the entry
+        let rowid: i64 = self
+            .db
+            .prepare(
+                "insert into history
+                               (rowid,  id,  timestamp,  duration,  exit,  command,  cwd,  session,  hostname)
+                        values (:rowid, :id, :timestamp, :duration, :exit, :command, :cwd, :session, :hostname)
+                    on conflict (rowid) do update set
+                        timestamp = excluded.timestamp,
+                        duration = excluded.duration,
+                        exit = excluded.exit,
+                        command = excluded.command,
+                        cwd = excluded.cwd,
+                        session = excluded.session,
+                        hostname = excluded.hostname
+                    returning rowid",
+            )
+            .map_err(map_sqlite_err)?
+            .query_row(values, |row| row.get(0))
+            .map_err(map_sqlite_err)?;
+        entry.id = Some(HistoryItemId::new(rowid));
+        Ok(entry)
+    }
+
+    fn save_batch(&mut self, items: Vec<HistoryItem>) -> Result<Vec<HistoryItem>> {
+        self.db.execute_batch("begin").map_err(map_sqlite_err)?;
+        let saved: Result<Vec<_>> = items.into_iter().map(|item| self.save(item)).collect();
+        let end = if saved.is_ok() { "commit" } else { "rollback" };
+        self.db.execute_batch(end).map_err(map_sqlite_err)?;
+        saved
+    }
+
+    fn load(&self, id: HistoryItemId) -> Result<HistoryItem> {
+        self.db
+            .prepare("select rowid, * from history where rowid = :rowid and deleted_at is null")
+            .map_err(map_sqlite_err)?
+            .query_row(named_params! { ":rowid": id.0 }, deserialize_history_item)
+            .map_err(map_sqlite_err)
+    }
+
+    fn count(&self, query: SearchQuery) -> Result<i64> {
+        let (query, params) = self.construct_query(&query, "coalesce(count(*), 0)");
+        let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
+        self.db
+            .prepare(&query)
+            .map_err(map_sqlite_err)?
+            .query_row(&params_borrow[..], |r| r.get(0))
+            .map_err(map_sqlite_err)
+    }
+
+    fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>> {
+        let (query, params) = self.construct_query(&query, "rowid, *");
+        let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
+        let results = self
+            .db
+            .prepare(&query)
+            .map_err(map_sqlite_err)?
+            .query_map(&params_borrow[..], deserialize_history_item)
+            .map_err(map_sqlite_err)?
+            .collect::<rusqlite::Result<Vec<HistoryItem>>>()
+            .map_err(map_sqlite_err)?;
+        Ok(results)
+    }
+
+    fn search_paged(
+        &self,
+        query: SearchQuery,
+        page: usize,
+        page_size: usize,
+    ) -> Result<Vec<HistoryItem>> {
+        let offset = i64::try_from(page.saturating_mul(page_size)).unwrap_or(i64::MAX);
+        let page_size = i64::try_from(page_size).unwrap_or(i64::MAX);
+        // The limit of the query ends the results, which may be within the page
+        let take = match query.limit {
+            Some(limit) => page_size.min(limit.saturating_sub(offset).max(0)),
+            None => page_size,
+        };
+        let query = SearchQuery {
+            limit: Some(take),
+            ..query
+        };
+        let (query, mut params) = self.construct_query(&query, "rowid, *");
+        let query = format!("{query} offset :offset");
+        params.push((":offset", Box::new(offset)));
+        let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
+        let results = self
+            .db
+            .prepare(&query)
+            .map_err(map_sqlite_err)?
+            .query_map(&params_borrow[..], deserialize_history_item)
+            .map_err(map_sqlite_err)?
+            .collect::<rusqlite::Result<Vec<HistoryItem>>>()
+            .map_err(map_sqlite_err)?;
+        Ok(results)
+    }
+
+    fn search_streaming(
+        &self,
+        query: SearchQuery,
+        on_item: &mut dyn FnMut(HistoryItem) -> ControlFlow<()>,
+    ) -> Result<()> {
+        let (query, params) = self.construct_query(&query, "rowid, *");
+        let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
+        let mut statement = self.db.prepare(&query).map_err(map_sqlite_err)?;
+        let rows = statement
+            .query_map(&params_borrow[..], deserialize_history_item)
+            .map_err(map_sqlite_err)?;
+        for item in rows {
+            if on_item(item.map_err(map_sqlite_err)?).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn update(
+        &mut self,
+        id: HistoryItemId,
+        updater: &dyn Fn(HistoryItem) -> HistoryItem,
+    ) -> Result<()> {
+        let item = self.load(id)?;
+        self.save(updater(item))?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.db
+            .execute(
+                "update history set deleted_at = ? where deleted_at is null",
+                params![Utc::now().timestamp_nanos_opt()],
+            )
+            .map_err(map_sqlite_err)?;
+        Ok(())
+    }
+
+    fn delete(&mut self, h: HistoryItemId) -> Result<()> {
+        let deleted = self
+            .db
+            .query_row(
+                "update history set deleted_at = ? where rowid = ? and deleted_at is null \
+                 returning rowid",
+                params![Utc::now().timestamp_nanos_opt(), h.0],
+                |r| r.get::<_, i64>(0),
+            )
+            .optional()
+            .map_err(map_sqlite_err)?;
+        match deleted {
+            Some(_) => Ok(()),
+            None => Err(ReedlineError(ReedlineErrorVariants::HistoryDatabaseError(
+                "Could not find item".to_string(),
+            ))),
+        }
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        // Every change is written right away, syncing with the server is up to Atuin
+        Ok(())
+    }
+
+    fn supports_directories(&self) -> bool {
+        true
+    }
+
+    fn session(&self) -> Option<HistorySessionId> {
+        self.session
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn command_lines(history: &AtuinHistory, query: SearchQuery) -> Vec<String> {
+        history
+            .search(query)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect()
+    }
+
+    #[test]
+    fn entries_are_stored_like_atuin_does() {
+        let mut history = AtuinHistory::in_memory().unwrap().with_hostname("box:me");
+        let saved = history
+            .save(HistoryItem {
+                cwd: Some("/tmp".to_string()),
+                session_id: Some(HistorySessionId::new(42)),
+                ..HistoryItem::from_command_line("cargo test")
+            })
+            .unwrap();
+        history
+            .update(saved.id.unwrap(), &|mut item| {
+                item.exit_status = Some(0);
+                item.duration = Some(Duration::from_millis(1500));
+                item
+            })
+            .unwrap();
+
+        let (id, duration, exit, session, hostname): (String, i64, i64, String, String) = history
+            .db
+            .query_row(
+                "select id, duration, exit, session, hostname from history",
+                params![],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!(id.len(), 32);
+        assert_eq!(&id[12..13], "7");
+        assert_eq!(duration, 1_500_000_000);
+        assert_eq!(exit, 0);
+        assert_eq!(session_id(&session), Some(HistorySessionId::new(42)));
+        assert_eq!(hostname, "box:me");
+
+        let loaded = history.load(saved.id.unwrap()).unwrap();
+        assert_eq!(loaded.exit_status, Some(0));
+        assert_eq!(loaded.cwd.as_deref(), Some("/tmp"));
+        assert_eq!(loaded.start_timestamp, saved.start_timestamp);
+    }
+
+    #[test]
+    fn entries_of_atuin_are_found_until_deleted() {
+        let mut history = AtuinHistory::in_memory().unwrap();
+        // Written by a shell, with a random session and an unknown exit status
+        history
+            .db
+            .execute(
+                "insert into history values \
+                 ('0190e4d6c1f47a3b9c4e2f1a5b6c7d8e', 1000, -1, -1, 'git status', '/home', \
+                  '0190e4d6c1f47a3b9c4e2f1a5b6c7d8f', 'box:me', null)",
+                params![],
+            )
+            .unwrap();
+        history
+            .save(HistoryItem::from_command_line("git log"))
+            .unwrap();
+
+        let items = history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap();
+        assert_eq!(items[0].command_line, "git status");
+        assert_eq!(items[0].exit_status, None);
+        assert_eq!(items[0].session_id, None);
+        assert_eq!(
+            command_lines(
+                &history,
+                SearchQuery::all_that_contain_rev("git".to_string())
+            ),
+            ["git log", "git status"]
+        );
+
+        history.delete(items[0].id.unwrap()).unwrap();
+        assert!(history.delete(items[0].id.unwrap()).is_err());
+        assert_eq!(history.count_all().unwrap(), 1);
+        history.clear().unwrap();
+        assert_eq!(history.count_all().unwrap(), 0);
+        // Kept for the next sync to remove them elsewhere
+        let rows: i64 = history
+            .db
+            .query_row("select count(*) from history", params![], |r| r.get(0))
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[test]
+    fn pages_and_streams_are_read_without_loading_everything() {
+        let mut history = AtuinHistory::in_memory().unwrap();
+        for command_line in ["ls", "cd", "git", "cargo", "rm"] {
+            history
+                .save(HistoryItem::from_command_line(command_line))
+                .unwrap();
+        }
+        let query = |limit| SearchQuery {
+            limit,
+            ..SearchQuery::everything(SearchDirection::Backward, None)
+        };
+        let page = |limit, page| -> Vec<String> {
+            history
+                .search_paged(query(limit), page, 2)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.command_line)
+                .collect()
+        };
+        assert_eq!(page(None, 0), ["rm", "cargo"]);
+        assert_eq!(page(None, 2), ["ls"]);
+        assert_eq!(page(Some(3), 1), ["git"]);
+        assert!(page(Some(3), 2).is_empty());
+
+        let mut streamed = Vec::new();
+        history
+            .search_streaming(query(None), &mut |item| {
+                streamed.push(item.command_line);
+                if streamed.len() == 2 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap();
+        assert_eq!(streamed, ["rm", "cargo"]);
+    }
+
+    #[test]
+    fn only_stores_set_up_by_atuin_are_opened() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("history.db");
+        assert!(AtuinHistory::with_file(file.clone(), None, None).is_err());
+
+        Connection::open(&file)
+            .unwrap()
+            .execute_batch(
+                "create table history (id text primary key, timestamp integer not null, \
+                 duration integer not null, exit integer not null, command text not null, \
+                 cwd text not null, session text not null, hostname text not null, \
+                 deleted_at integer, unique(timestamp, cwd, command))",
+            )
+            .unwrap();
+        let mut history = AtuinHistory::with_file(file, None, None).unwrap();
+        history.save(HistoryItem::from_command_line("ls")).unwrap();
+        assert_eq!(history.count_all().unwrap(), 1);
+    }
+}
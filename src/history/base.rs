@@ -1,6 +1,7 @@
 use super::HistoryItemId;
 use crate::{core_editor::LineBuffer, HistoryItem, HistorySessionId, Result};
 use chrono::Utc;
+use std::ops::ControlFlow;
 
 /// Browsing modes for a [`History`]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -15,6 +16,17 @@ pub enum HistoryNavigationQuery {
     // Fuzzy Search
 }
 
+/// The entries considered by the history navigation and search while the directory
+/// history is on, see [`crate::Reedline::with_directory_history`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DirectoryHistoryScope {
+    /// Entries recorded in the current working directory
+    #[default]
+    Directory,
+    /// Entries recorded in the current working directory or below it
+    Subtree,
+}
+
 /// Ways to search for a particular command line in the [`History`]
 // todo: merge with [HistoryNavigationQuery]
 pub enum CommandLineSearch {
@@ -183,6 +195,25 @@ impl SearchQuery {
     }
 }
 
+/// The `query` limited to the results up to the end of the `page`, and how many of them
+/// come before it
+pub(crate) fn up_to_page(
+    query: SearchQuery,
+    page: usize,
+    page_size: usize,
+) -> (SearchQuery, usize) {
+    let skip = page.saturating_mul(page_size);
+    let end = i64::try_from(skip.saturating_add(page_size)).unwrap_or(i64::MAX);
+    let limit = query.limit.map_or(end, |limit| limit.min(end));
+    (
+        SearchQuery {
+            limit: Some(limit),
+            ..query
+        },
+        skip,
+    )
+}
+
 /// Represents a history file or database
 /// Data could be stored e.g. in a plain text file, in a `JSONL` file, in a `SQLite` database
 pub trait History: Send {
@@ -190,11 +221,18 @@ pub trait History: Send {
     /// if given id is None, a new id is created and set in the return value
     /// if given id is Some, the existing entry is updated
     fn save(&mut self, h: HistoryItem) -> Result<HistoryItem>;
+    /// save several history items, e.g. when importing a history, returning them with their
+    /// ids in the same order
+    ///
+    /// The default implementation saves the items one by one and stops at the first error.
+    /// Backends that can write several items at once, e.g. in one transaction or one
+    /// request to a server, should override it.
+    fn save_batch(&mut self, items: Vec<HistoryItem>) -> Result<Vec<HistoryItem>> {
+        items.into_iter().map(|item| self.save(item)).collect()
+    }
     /// load a history item by its id
     fn load(&self, id: HistoryItemId) -> Result<HistoryItem>;
 
-    /// retrieves the next unused session id
-
     /// count the results of a query
     fn count(&self, query: SearchQuery) -> Result<i64>;
     /// return the total number of history items
@@ -202,7 +240,55 @@ pub trait History: Send {
         self.count(SearchQuery::everything(SearchDirection::Forward, None))
     }
     /// return the results of a query
+    ///
+    /// Loads all results at once, for huge histories prefer [`History::search_paged`]
+    /// or [`History::search_streaming`]
     fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>>;
+    /// return the results of several queries, in the order of the queries
+    ///
+    /// Lets backends answer e.g. the queries for a hint and for a menu with one request.
+    /// The default implementation runs the queries one by one.
+    fn search_batch(&self, queries: Vec<SearchQuery>) -> Result<Vec<Vec<HistoryItem>>> {
+        queries
+            .into_iter()
+            .map(|query| self.search(query))
+            .collect()
+    }
+    /// return one page of the results of a query
+    ///
+    /// The results are split into pages of `page_size` entries, in the order of the
+    /// query, and `page` is counted from 0. A page shorter than `page_size` is the last
+    /// one. The `limit` of the query restricts the results before they are paged.
+    ///
+    /// The default implementation runs [`History::search`] limited to the end of the page
+    /// and drops the pages before it, backends that can skip to the page directly should
+    /// override it.
+    fn search_paged(
+        &self,
+        query: SearchQuery,
+        page: usize,
+        page_size: usize,
+    ) -> Result<Vec<HistoryItem>> {
+        let (query, skip) = up_to_page(query, page, page_size);
+        Ok(self.search(query)?.into_iter().skip(skip).collect())
+    }
+    /// pass the results of a query to `on_item` one by one, as they are found
+    ///
+    /// The search stops early once `on_item` returns [`ControlFlow::Break`].
+    /// The default implementation collects all results with [`History::search`] first,
+    /// backends that find the results incrementally should override it.
+    fn search_streaming(
+        &self,
+        query: SearchQuery,
+        on_item: &mut dyn FnMut(HistoryItem) -> ControlFlow<()>,
+    ) -> Result<()> {
+        for item in self.search(query)? {
+            if on_item(item).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
 
     /// update an item atomically
     fn update(
@@ -214,6 +300,37 @@ pub trait History: Send {
     fn clear(&mut self) -> Result<()>;
     /// remove an item from this history
     fn delete(&mut self, h: HistoryItemId) -> Result<()>;
+    /// pin or unpin an item
+    ///
+    /// Pinned items are listed first by the history menus and are never removed to stay
+    /// within the capacity of the history. Backends that can't store the flag return
+    /// [`crate::ReedlineErrorVariants::HistoryFeatureUnsupported`].
+    fn set_pinned(&mut self, id: HistoryItemId, pinned: bool) -> Result<()> {
+        let _ = (id, pinned);
+        Err(crate::ReedlineError(
+            crate::ReedlineErrorVariants::HistoryFeatureUnsupported {
+                history: "this history",
+                feature: "pinning entries",
+            },
+        ))
+    }
+    /// whether an item is pinned
+    fn is_pinned(&self, id: HistoryItemId) -> Result<bool> {
+        let _ = id;
+        Ok(false)
+    }
+    /// all pinned items, the most recent first
+    fn pinned(&self) -> Result<Vec<HistoryItem>> {
+        Ok(Vec::new())
+    }
+    /// whether the history stores the working directories of its items and can filter by
+    /// [`SearchFilter::cwd_exact`] and [`SearchFilter::cwd_prefix`]
+    ///
+    /// The editor restricts the history to the working directory only if this returns
+    /// `true`, see [`crate::Reedline::with_directory_history`].
+    fn supports_directories(&self) -> bool {
+        false
+    }
     /// ensure that this history is written to disk
     fn sync(&mut self) -> std::io::Result<()>;
     /// get the history session id
@@ -397,6 +514,37 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn search_paged() -> Result<()> {
+        let history = create_filled_example_history()?;
+        let query = |limit| SearchQuery {
+            filter: SearchFilter::from_text_search(
+                CommandLineSearch::Substring("c".to_string()),
+                None,
+            ),
+            limit,
+            ..SearchQuery::everything(SearchDirection::Forward, None)
+        };
+        search_returned(
+            &*history,
+            history.search_paged(query(None), 0, 4)?,
+            vec![1, 4, 7, 8],
+        )?;
+        search_returned(
+            &*history,
+            history.search_paged(query(None), 1, 4)?,
+            vec![10, 12],
+        )?;
+        search_returned(&*history, history.search_paged(query(None), 2, 4)?, vec![])?;
+        search_returned(
+            &*history,
+            history.search_paged(query(Some(5)), 1, 4)?,
+            vec![10],
+        )?;
+
+        Ok(())
+    }
+
     #[test]
     fn clear_history() -> Result<()> {
         let mut history = create_filled_example_history()?;
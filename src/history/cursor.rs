@@ -1,11 +1,15 @@
 use crate::{History, HistoryNavigationQuery, HistorySessionId};
 
 use super::base::CommandLineSearch;
+use super::base::DirectoryHistoryScope;
 use super::base::SearchDirection;
 use super::base::SearchFilter;
 use super::HistoryItem;
+use super::HistoryItemId;
 use super::SearchQuery;
+use crate::utils::typo::contains_with_typos;
 use crate::Result;
+use std::ops::ControlFlow;
 
 /// Interface of a stateful navigation via [`HistoryNavigationQuery`].
 #[derive(Debug)]
@@ -14,6 +18,10 @@ pub struct HistoryCursor {
     current: Option<HistoryItem>,
     skip_dupes: bool,
     session: Option<HistorySessionId>,
+    directory: Option<(DirectoryHistoryScope, String)>,
+    typo_tolerance: usize,
+    // Set once a substring search found nothing, the entries are then matched with typos
+    with_typos: bool,
 }
 
 impl HistoryCursor {
@@ -23,6 +31,34 @@ impl HistoryCursor {
             current: None,
             skip_dupes: true,
             session,
+            directory: None,
+            typo_tolerance: 0,
+            with_typos: false,
+        }
+    }
+
+    /// Only consider the entries recorded in the working directory `cwd`, or below it
+    pub(crate) fn in_directory(mut self, scope: DirectoryHistoryScope, cwd: String) -> Self {
+        self.directory = Some((scope, cwd));
+        self
+    }
+
+    /// Match a substring search with up to `max_typos` typos if it matches no entry exactly,
+    /// e.g. `gti sttaus` finds `git status`
+    pub(crate) fn with_typo_tolerance(mut self, max_typos: usize) -> Self {
+        self.typo_tolerance = max_typos;
+        self
+    }
+
+    /// Create a cursor that already points at `item`
+    pub(crate) fn new_at(
+        query: HistoryNavigationQuery,
+        session: Option<HistorySessionId>,
+        item: HistoryItem,
+    ) -> HistoryCursor {
+        HistoryCursor {
+            current: Some(item),
+            ..HistoryCursor::new(query, session)
         }
     }
 
@@ -39,7 +75,7 @@ impl HistoryCursor {
     }
 
     fn get_search_filter(&self) -> SearchFilter {
-        let filter = match self.query.clone() {
+        let mut filter = match self.query.clone() {
             HistoryNavigationQuery::Normal(_) => SearchFilter::anything(self.session),
             HistoryNavigationQuery::PrefixSearch(prefix) => {
                 SearchFilter::from_text_search(CommandLineSearch::Prefix(prefix), self.session)
@@ -49,6 +85,11 @@ impl HistoryCursor {
                 self.session,
             ),
         };
+        match &self.directory {
+            Some((DirectoryHistoryScope::Directory, cwd)) => filter.cwd_exact = Some(cwd.clone()),
+            Some((DirectoryHistoryScope::Subtree, cwd)) => filter.cwd_prefix = Some(cwd.clone()),
+            None => {}
+        }
         if let (true, Some(current)) = (self.skip_dupes, &self.current) {
             SearchFilter {
                 not_command_line: Some(current.command_line.clone()),
@@ -67,21 +108,89 @@ impl HistoryCursor {
             // if searching forward but we don't have a starting point, assume we are at the end
             return Ok(());
         }
+        if self.with_typos {
+            return self.navigate_with_typos(history, direction);
+        }
         let start_id = self.current.as_ref().and_then(|e| e.id);
-        let mut next = history.search(SearchQuery {
-            start_id,
-            end_id: None,
-            start_time: None,
-            end_time: None,
-            direction,
-            limit: Some(1),
-            filter: self.get_search_filter(),
-        })?;
+        let mut next = history.search_paged(
+            SearchQuery {
+                start_id,
+                end_id: None,
+                start_time: None,
+                end_time: None,
+                direction,
+                limit: Some(1),
+                filter: self.get_search_filter(),
+            },
+            0,
+            1,
+        )?;
         if next.len() == 1 {
             self.current = Some(next.swap_remove(0));
         } else if direction == SearchDirection::Forward {
             // no result and searching forward: we are at the end
             self.current = None;
+        } else if self.current.is_none() && self.typo_pattern_candidate().is_some() {
+            self.with_typos = true;
+            return self.navigate_with_typos(history, direction);
+        }
+        Ok(())
+    }
+
+    // The substring to match with typos if nothing matches it exactly
+    fn typo_pattern_candidate(&self) -> Option<&str> {
+        match &self.query {
+            HistoryNavigationQuery::SubstringSearch(substring)
+                if self.typo_tolerance > 0 && !substring.is_empty() =>
+            {
+                Some(substring)
+            }
+            _ => None,
+        }
+    }
+
+    // The substring and the typos it is matched with, once it didn't match exactly
+    fn typo_pattern(&self) -> Option<(&str, usize)> {
+        if self.with_typos {
+            self.typo_pattern_candidate()
+                .map(|pattern| (pattern, self.typo_tolerance))
+        } else {
+            None
+        }
+    }
+
+    // Navigates to the next entry containing the substring with typos, the history can't
+    // filter by them itself
+    fn navigate_with_typos(
+        &mut self,
+        history: &dyn History,
+        direction: SearchDirection,
+    ) -> Result<()> {
+        let (pattern, max_typos) = match self.typo_pattern() {
+            Some(pattern) => pattern,
+            None => return Ok(()),
+        };
+        let mut next = None;
+        history.search_streaming(
+            SearchQuery {
+                start_id: self.current.as_ref().and_then(|e| e.id),
+                filter: SearchFilter {
+                    command_line: None,
+                    ..self.get_search_filter()
+                },
+                ..SearchQuery::everything(direction, None)
+            },
+            &mut |item| {
+                if contains_with_typos(&item.command_line, pattern, max_typos) {
+                    next = Some(item);
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            },
+        )?;
+        if next.is_some() || direction == SearchDirection::Forward {
+            self.current = next;
         }
         Ok(())
     }
@@ -91,6 +200,56 @@ impl HistoryCursor {
         self.current.as_ref().map(|e| e.command_line.to_string())
     }
 
+    /// Returns the id of the entry (if present) at the cursor
+    pub(crate) fn id_at_cursor(&self) -> Option<HistoryItemId> {
+        self.current.as_ref().and_then(|e| e.id)
+    }
+
+    /// Which of the matches of the query the cursor is at, counted from the most recent one,
+    /// and how many matches there are
+    ///
+    /// Like the navigation, a match repeating the command line of the match before it is
+    /// not counted separately.
+    pub(crate) fn match_position(&self, history: &dyn History) -> Result<Option<(usize, usize)>> {
+        let current = match self.id_at_cursor() {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let mut filter = SearchFilter {
+            not_command_line: None,
+            ..self.get_search_filter()
+        };
+        let typo_pattern = self.typo_pattern();
+        if typo_pattern.is_some() {
+            filter.command_line = None;
+        }
+        let mut total = 0;
+        let mut position = None;
+        let mut previous: Option<String> = None;
+        history.search_streaming(
+            SearchQuery {
+                filter,
+                ..SearchQuery::everything(SearchDirection::Backward, None)
+            },
+            &mut |item| {
+                if let Some((pattern, max_typos)) = typo_pattern {
+                    if !contains_with_typos(&item.command_line, pattern, max_typos) {
+                        return ControlFlow::Continue(());
+                    }
+                }
+                if previous.as_ref() != Some(&item.command_line) {
+                    total += 1;
+                    previous = Some(item.command_line);
+                }
+                if item.id == Some(current) {
+                    position = Some(total);
+                }
+                ControlFlow::Continue(())
+            },
+        )?;
+        Ok(position.map(|position| (position, total)))
+    }
+
     /// Poll the current [`HistoryNavigationQuery`] mode
     pub fn get_navigation(&self) -> HistoryNavigationQuery {
         self.query.clone()
@@ -140,6 +299,68 @@ mod tests {
         });
     }
 
+    #[test]
+    fn matches_are_counted_like_they_are_navigated() -> Result<()> {
+        let (mut hist, _) = create_history();
+        add_text_entries(
+            hist.as_mut(),
+            &["git status", "git log", "ls", "git log", "git diff"],
+        );
+        let mut cursor = HistoryCursor::new(
+            HistoryNavigationQuery::SubstringSearch("git".to_string()),
+            None,
+        );
+        assert_eq!(cursor.match_position(hist.as_ref())?, None);
+
+        cursor.back(hist.as_ref())?;
+        assert_eq!(cursor.match_position(hist.as_ref())?, Some((1, 3)));
+        cursor.back(hist.as_ref())?;
+        assert_eq!(cursor.string_at_cursor().as_deref(), Some("git log"));
+        assert_eq!(cursor.match_position(hist.as_ref())?, Some((2, 3)));
+        cursor.back(hist.as_ref())?;
+        assert_eq!(cursor.string_at_cursor().as_deref(), Some("git status"));
+        assert_eq!(cursor.match_position(hist.as_ref())?, Some((3, 3)));
+        Ok(())
+    }
+
+    #[test]
+    fn substrings_are_matched_with_typos_if_nothing_matches_exactly() -> Result<()> {
+        let (mut hist, _) = create_history();
+        add_text_entries(
+            hist.as_mut(),
+            &["git status", "ls", "git stash", "git status --short"],
+        );
+        let mut cursor = HistoryCursor::new(
+            HistoryNavigationQuery::SubstringSearch("gti sttaus".to_string()),
+            None,
+        )
+        .with_typo_tolerance(2);
+
+        cursor.back(hist.as_ref())?;
+        assert_eq!(
+            cursor.string_at_cursor().as_deref(),
+            Some("git status --short")
+        );
+        cursor.back(hist.as_ref())?;
+        assert_eq!(cursor.string_at_cursor().as_deref(), Some("git status"));
+        assert_eq!(cursor.match_position(hist.as_ref())?, Some((2, 2)));
+        cursor.forward(hist.as_ref())?;
+        assert_eq!(
+            cursor.string_at_cursor().as_deref(),
+            Some("git status --short")
+        );
+
+        let mut exact = HistoryCursor::new(
+            HistoryNavigationQuery::SubstringSearch("git sta".to_string()),
+            None,
+        )
+        .with_typo_tolerance(2);
+        exact.back(hist.as_ref())?;
+        exact.back(hist.as_ref())?;
+        assert_eq!(exact.string_at_cursor().as_deref(), Some("git stash"));
+        Ok(())
+    }
+
     #[test]
     fn accessing_empty_history_returns_nothing() -> Result<()> {
         let (_hist, cursor) = create_history();
@@ -509,6 +730,43 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn truncation_keeps_pinned_entries() -> Result<()> {
+        use tempfile::tempdir;
+
+        let tmp = tempdir().unwrap();
+        let histfile = tmp.path().join(".history");
+        let expected_entries = vec!["test 1", "test 4", "test 5"];
+
+        {
+            let (mut writing_hist, _) = create_history_at(10, &histfile);
+            add_text_entries(writing_hist.as_mut(), &["test 1", "test 2", "test 3"]);
+            writing_hist.set_pinned(HistoryItemId::new(0), true)?;
+        }
+
+        {
+            let (mut truncating_hist, _) = create_history_at(3, &histfile);
+            add_text_entries(truncating_hist.as_mut(), &["test 4", "test 5"]);
+            let actual: Vec<_> = get_all_entry_texts(truncating_hist.as_ref());
+            assert_eq!(expected_entries, actual);
+        }
+
+        let (reading_hist, _) = create_history_at(3, &histfile);
+
+        let actual: Vec<_> = get_all_entry_texts(reading_hist.as_ref());
+        assert_eq!(expected_entries, actual);
+        assert!(reading_hist.is_pinned(HistoryItemId::new(0))?);
+        let pinned: Vec<_> = reading_hist
+            .pinned()?
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect();
+        assert_eq!(pinned, ["test 1"]);
+
+        tmp.close().unwrap();
+        Ok(())
+    }
+
     #[test]
     fn concurrent_histories_do_not_erase_each_other() -> Result<()> {
         use tempfile::tempdir;
@@ -7,11 +7,11 @@ use crate::{
 };
 
 use std::{
-    collections::VecDeque,
+    collections::{HashSet, VecDeque},
     fs::OpenOptions,
     io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
-    ops::{Deref, DerefMut},
-    path::PathBuf,
+    ops::{ControlFlow, Deref, DerefMut},
+    path::{Path, PathBuf},
 };
 
 /// Default size of the [`FileBackedHistory`] used when calling [`FileBackedHistory::default()`]
@@ -24,13 +24,26 @@ pub const NEWLINE_ESCAPE: &str = "<\\n>";
 /// Similar to bash's behavior without HISTTIMEFORMAT.
 /// (See <https://www.gnu.org/software/bash/manual/html_node/Bash-History-Facilities.html>)
 /// If the history is associated to a file all new changes within a given history capacity will be written to disk when History is dropped.
+///
+/// Pinned command lines are kept next to the history file, in the same file name
+/// with `.pinned` appended, and are never truncated.
+///
+/// Each saved entry gets a new id, which stays the same while older entries are dropped to
+/// make room for new ones and while the history is synced with its file. Only when the file
+/// holds other entries, e.g. because another history wrote to it in between,
+/// [`History::sync`] gives all entries new ids, so the old ids aren't found anymore instead
+/// of pointing at different entries.
 #[derive(Debug)]
 pub struct FileBackedHistory {
     capacity: usize,
-    entries: VecDeque<String>,
+    // Ordered by their ids
+    entries: VecDeque<(HistoryItemId, String)>,
+    next_id: i64,
     file: Option<PathBuf>,
     len_on_disk: usize, // Keep track what was previously written to disk
     session: Option<HistorySessionId>,
+    pinned: HashSet<String>,
+    pins_changed: bool,
 }
 
 impl Default for FileBackedHistory {
@@ -57,26 +70,38 @@ fn decode_entry(s: &str) -> String {
     s.replace(NEWLINE_ESCAPE, "\n")
 }
 
+fn pinned_file(file: &Path) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(".pinned");
+    PathBuf::from(name)
+}
+
 impl History for FileBackedHistory {
     /// only saves a value if it's different than the last value
     fn save(&mut self, h: HistoryItem) -> Result<HistoryItem> {
         let entry = h.command_line;
         // Don't append if the preceding value is identical or the string empty
-        let entry_id = if self
-            .entries
-            .back()
-            .map_or(true, |previous| previous != &entry)
+        let entry_id = if self.entries.back().map(|(_, last)| last) != Some(&entry)
             && !entry.is_empty()
             && self.capacity > 0
         {
             if self.entries.len() == self.capacity {
-                // History is "full", so we delete the oldest entry first,
-                // before adding a new one.
-                self.entries.pop_front();
-                self.len_on_disk = self.len_on_disk.saturating_sub(1);
+                // History is "full", so we delete the oldest entry that isn't
+                // pinned first, before adding a new one.
+                let oldest = self
+                    .entries
+                    .iter()
+                    .position(|(_, entry)| !self.pinned.contains(entry))
+                    .unwrap_or(0);
+                self.entries.remove(oldest);
+                if oldest < self.len_on_disk {
+                    self.len_on_disk -= 1;
+                }
             }
-            self.entries.push_back(entry.to_string());
-            Some(HistoryItemId::new((self.entries.len() - 1) as i64))
+            let id = HistoryItemId::new(self.next_id);
+            self.next_id += 1;
+            self.entries.push_back((id, entry.to_string()));
+            Some(id)
         } else {
             None
         };
@@ -84,14 +109,17 @@ impl History for FileBackedHistory {
     }
 
     fn load(&self, id: HistoryItemId) -> Result<super::HistoryItem> {
+        let index = self
+            .entries
+            .binary_search_by_key(&id, |(id, _)| *id)
+            .map_err(|_| {
+                ReedlineError(ReedlineErrorVariants::OtherHistoryError(
+                    "Item does not exist",
+                ))
+            })?;
         Ok(FileBackedHistory::construct_entry(
             Some(id),
-            self.entries
-                .get(id.0 as usize)
-                .ok_or(ReedlineError(ReedlineErrorVariants::OtherHistoryError(
-                    "Item does not exist",
-                )))?
-                .clone(),
+            self.entries[index].1.clone(),
         ))
     }
 
@@ -101,82 +129,33 @@ impl History for FileBackedHistory {
     }
 
     fn search(&self, query: SearchQuery) -> Result<Vec<HistoryItem>> {
-        if query.start_time.is_some() || query.end_time.is_some() {
-            return Err(ReedlineError(
-                ReedlineErrorVariants::HistoryFeatureUnsupported {
-                    history: "FileBackedHistory",
-                    feature: "filtering by time",
-                },
-            ));
-        }
+        Ok(self.search_iter(query)?.collect())
+    }
 
-        if query.filter.hostname.is_some()
-            || query.filter.cwd_exact.is_some()
-            || query.filter.cwd_prefix.is_some()
-            || query.filter.exit_successful.is_some()
-        {
-            return Err(ReedlineError(
-                ReedlineErrorVariants::HistoryFeatureUnsupported {
-                    history: "FileBackedHistory",
-                    feature: "filtering by extra info",
-                },
-            ));
-        }
-        let (min_id, max_id) = {
-            let start = query.start_id.map(|e| e.0);
-            let end = query.end_id.map(|e| e.0);
-            if let SearchDirection::Backward = query.direction {
-                (end, start)
-            } else {
-                (start, end)
+    fn search_streaming(
+        &self,
+        query: SearchQuery,
+        on_item: &mut dyn FnMut(HistoryItem) -> ControlFlow<()>,
+    ) -> Result<()> {
+        for item in self.search_iter(query)? {
+            if on_item(item).is_break() {
+                break;
             }
-        };
-        // add one to make it inclusive
-        let min_id = min_id.map(|e| e + 1).unwrap_or(0);
-        // subtract one to make it inclusive
-        let max_id = max_id
-            .map(|e| e - 1)
-            .unwrap_or(self.entries.len() as i64 - 1);
-        if max_id < 0 || min_id > self.entries.len() as i64 - 1 {
-            return Ok(vec![]);
         }
-        let intrinsic_limit = max_id - min_id + 1;
-        let limit = if let Some(given_limit) = query.limit {
-            std::cmp::min(intrinsic_limit, given_limit) as usize
-        } else {
-            intrinsic_limit as usize
-        };
-        let filter = |(idx, cmd): (usize, &String)| {
-            if !match &query.filter.command_line {
-                Some(CommandLineSearch::Prefix(p)) => cmd.starts_with(p),
-                Some(CommandLineSearch::Substring(p)) => cmd.contains(p),
-                Some(CommandLineSearch::Exact(p)) => cmd == p,
-                None => true,
-            } {
-                return None;
-            }
-            if let Some(str) = &query.filter.not_command_line {
-                if cmd == str {
-                    return None;
-                }
-            }
-            Some(FileBackedHistory::construct_entry(
-                Some(HistoryItemId::new(idx as i64)),
-                cmd.to_string(), // todo: this copy might be a perf bottleneck
-            ))
-        };
+        Ok(())
+    }
 
-        let iter = self
-            .entries
-            .iter()
-            .enumerate()
-            .skip(min_id as usize)
-            .take(intrinsic_limit as usize);
-        if let SearchDirection::Backward = query.direction {
-            Ok(iter.rev().filter_map(filter).take(limit).collect())
-        } else {
-            Ok(iter.filter_map(filter).take(limit).collect())
-        }
+    fn search_paged(
+        &self,
+        query: SearchQuery,
+        page: usize,
+        page_size: usize,
+    ) -> Result<Vec<HistoryItem>> {
+        Ok(self
+            .search_iter(query)?
+            .skip(page.saturating_mul(page_size))
+            .take(page_size)
+            .collect())
     }
 
     fn update(
@@ -195,11 +174,19 @@ impl History for FileBackedHistory {
     fn clear(&mut self) -> Result<()> {
         self.entries.clear();
         self.len_on_disk = 0;
+        self.pinned.clear();
+        self.pins_changed = false;
 
         if let Some(file) = &self.file {
             if let Err(err) = std::fs::remove_file(file) {
                 return Err(ReedlineError(ReedlineErrorVariants::IOError(err)));
             }
+            match std::fs::remove_file(pinned_file(file)) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(ReedlineError(ReedlineErrorVariants::IOError(err)));
+                }
+                _ => {}
+            }
         }
 
         Ok(())
@@ -214,13 +201,42 @@ impl History for FileBackedHistory {
         ))
     }
 
+    /// Pins all entries with the same command line as the entry `id`
+    fn set_pinned(&mut self, id: HistoryItemId, pinned: bool) -> Result<()> {
+        let command_line = self.load(id)?.command_line;
+        self.pins_changed |= if pinned {
+            self.pinned.insert(command_line)
+        } else {
+            self.pinned.remove(&command_line)
+        };
+        Ok(())
+    }
+
+    fn is_pinned(&self, id: HistoryItemId) -> Result<bool> {
+        Ok(self.pinned.contains(&self.load(id)?.command_line))
+    }
+
+    fn pinned(&self) -> Result<Vec<HistoryItem>> {
+        let mut seen = HashSet::new();
+        Ok(self
+            .entries
+            .iter()
+            .rev()
+            .filter(|(_, entry)| self.pinned.contains(entry) && seen.insert(entry))
+            .map(|(id, entry)| FileBackedHistory::construct_entry(Some(*id), entry.clone()))
+            .collect())
+    }
+
     /// Writes unwritten history contents to disk.
     ///
-    /// If file would exceed `capacity` truncates the oldest entries.
+    /// If file would exceed `capacity` truncates the oldest entries that aren't pinned.
     fn sync(&mut self) -> std::io::Result<()> {
         if let Some(fname) = &self.file {
             // The unwritten entries
-            let own_entries = self.entries.range(self.len_on_disk..);
+            let own_entries = self
+                .entries
+                .range(self.len_on_disk..)
+                .map(|(_, entry)| entry);
 
             if let Some(base_dir) = fname.parent() {
                 std::fs::create_dir_all(base_dir)?;
@@ -242,12 +258,17 @@ impl History for FileBackedHistory {
                     .map(|o| o.map(|i| decode_entry(&i)))
                     .collect::<std::io::Result<VecDeque<_>>>()?;
                 if from_file.len() + own_entries.len() > self.capacity {
-                    (
-                        from_file.split_off(
-                            from_file.len() - (self.capacity.saturating_sub(own_entries.len())),
-                        ),
-                        true,
-                    )
+                    let mut excess =
+                        from_file.len() - (self.capacity.saturating_sub(own_entries.len()));
+                    from_file.retain(|entry| {
+                        if excess > 0 && !self.pinned.contains(entry) {
+                            excess -= 1;
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                    (from_file, true)
                 } else {
                     (from_file, false)
                 }
@@ -277,11 +298,38 @@ impl History for FileBackedHistory {
                 file.set_len(file_len)?;
             }
 
-            let own_entries = self.entries.drain(self.len_on_disk..);
-            foreign_entries.extend(own_entries);
-            self.entries = foreign_entries;
+            // Unless another history wrote to the file, it holds the same entries as this one,
+            // which keep their ids
+            if foreign_entries.iter().ne(self
+                .entries
+                .range(..self.len_on_disk)
+                .map(|(_, entry)| entry))
+            {
+                // The entries of the file are put before the own ones, so all entries get new
+                // ids that weren't given out before
+                let own_entries = self.entries.drain(self.len_on_disk..);
+                foreign_entries.extend(own_entries.map(|(_, entry)| entry));
+                self.entries = foreign_entries
+                    .into_iter()
+                    .map(|entry| {
+                        let id = HistoryItemId::new(self.next_id);
+                        self.next_id += 1;
+                        (id, entry)
+                    })
+                    .collect();
+            }
 
             self.len_on_disk = self.entries.len();
+
+            if self.pins_changed {
+                let mut writer = BufWriter::new(std::fs::File::create(pinned_file(fname))?);
+                for line in &self.pinned {
+                    writer.write_all(encode_entry(line).as_bytes())?;
+                    writer.write_all("\n".as_bytes())?;
+                }
+                writer.flush()?;
+                self.pins_changed = false;
+            }
         }
         Ok(())
     }
@@ -296,17 +344,20 @@ impl FileBackedHistory {
     ///
     pub fn new(capacity: usize) -> Result<Self> {
         if capacity == usize::MAX {
-            return Err(ReedlineError(ReedlineErrorVariants::OtherHistoryError(
-                "History capacity too large to be addressed safely",
+            return Err(ReedlineError(ReedlineErrorVariants::ConfigError(
+                "History capacity too large to be addressed safely".to_string(),
             )));
         }
 
         Ok(FileBackedHistory {
             capacity,
             entries: VecDeque::new(),
+            next_id: 0,
             file: None,
             len_on_disk: 0,
             session: None,
+            pinned: HashSet::new(),
+            pins_changed: false,
         })
     }
 
@@ -323,6 +374,11 @@ impl FileBackedHistory {
         if let Some(base_dir) = file.parent() {
             std::fs::create_dir_all(base_dir)?;
         }
+        match std::fs::read_to_string(pinned_file(&file)) {
+            Ok(pinned) => hist.pinned = pinned.lines().map(decode_entry).collect(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(ReedlineError(ReedlineErrorVariants::IOError(err))),
+        }
         hist.file = Some(file);
         hist.sync()?;
         Ok(hist)
@@ -342,6 +398,85 @@ impl FileBackedHistory {
             more_info: None,
         }
     }
+
+    // The results of a query, created one by one while iterating
+    fn search_iter(
+        &self,
+        query: SearchQuery,
+    ) -> Result<Box<dyn Iterator<Item = HistoryItem> + '_>> {
+        if query.start_time.is_some() || query.end_time.is_some() {
+            return Err(ReedlineError(
+                ReedlineErrorVariants::HistoryFeatureUnsupported {
+                    history: "FileBackedHistory",
+                    feature: "filtering by time",
+                },
+            ));
+        }
+
+        if query.filter.hostname.is_some()
+            || query.filter.cwd_exact.is_some()
+            || query.filter.cwd_prefix.is_some()
+            || query.filter.exit_successful.is_some()
+        {
+            return Err(ReedlineError(
+                ReedlineErrorVariants::HistoryFeatureUnsupported {
+                    history: "FileBackedHistory",
+                    feature: "filtering by extra info",
+                },
+            ));
+        }
+        let (min_id, max_id) = {
+            let start = query.start_id.map(|e| e.0);
+            let end = query.end_id.map(|e| e.0);
+            if let SearchDirection::Backward = query.direction {
+                (end, start)
+            } else {
+                (start, end)
+            }
+        };
+        // The entries between the ids, which aren't included themselves
+        let first = min_id.map_or(0, |min_id| {
+            self.entries.partition_point(|(id, _)| id.0 <= min_id)
+        });
+        let end = max_id.map_or(self.entries.len(), |max_id| {
+            self.entries.partition_point(|(id, _)| id.0 < max_id)
+        });
+        if first >= end {
+            return Ok(Box::new(std::iter::empty()));
+        }
+        let intrinsic_limit = (end - first) as i64;
+        let limit = if let Some(given_limit) = query.limit {
+            std::cmp::min(intrinsic_limit, given_limit) as usize
+        } else {
+            intrinsic_limit as usize
+        };
+        let filter = move |(id, cmd): &(HistoryItemId, String)| {
+            if !match &query.filter.command_line {
+                Some(CommandLineSearch::Prefix(p)) => cmd.starts_with(p),
+                Some(CommandLineSearch::Substring(p)) => cmd.contains(p),
+                Some(CommandLineSearch::Exact(p)) => cmd == p,
+                None => true,
+            } {
+                return None;
+            }
+            if let Some(str) = &query.filter.not_command_line {
+                if cmd == str {
+                    return None;
+                }
+            }
+            Some(FileBackedHistory::construct_entry(
+                Some(*id),
+                cmd.to_string(), // todo: this copy might be a perf bottleneck
+            ))
+        };
+
+        let iter = self.entries.range(first..end);
+        if let SearchDirection::Backward = query.direction {
+            Ok(Box::new(iter.rev().filter_map(filter).take(limit)))
+        } else {
+            Ok(Box::new(iter.filter_map(filter).take(limit)))
+        }
+    }
 }
 
 impl Drop for FileBackedHistory {
@@ -350,3 +485,109 @@ impl Drop for FileBackedHistory {
         let _res = self.sync();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn save_all(history: &mut FileBackedHistory, lines: &[&str]) -> Vec<HistoryItemId> {
+        lines
+            .iter()
+            .map(|line| {
+                history
+                    .save(HistoryItem::from_command_line(*line))
+                    .unwrap()
+                    .id
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn command_lines(history: &FileBackedHistory) -> Vec<String> {
+        history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect()
+    }
+
+    #[test]
+    fn ids_stay_when_older_entries_are_dropped() {
+        let mut history = FileBackedHistory::new(3).unwrap();
+        let ids = save_all(&mut history, &["a", "b", "c", "d"]);
+
+        assert_eq!(command_lines(&history), ["b", "c", "d"]);
+        assert!(history.load(ids[0]).is_err());
+        for (id, line) in ids[1..].iter().zip(["b", "c", "d"]) {
+            assert_eq!(history.load(*id).unwrap().command_line, line);
+        }
+        let after_b = history
+            .search(SearchQuery {
+                start_id: Some(ids[1]),
+                limit: Some(1),
+                ..SearchQuery::everything(SearchDirection::Forward, None)
+            })
+            .unwrap();
+        assert_eq!(after_b[0].id, Some(ids[2]));
+    }
+
+    #[test]
+    fn pinned_entries_keep_their_ids_when_others_are_dropped() {
+        let mut history = FileBackedHistory::new(2).unwrap();
+        let ids = save_all(&mut history, &["a", "b"]);
+        history.set_pinned(ids[0], true).unwrap();
+        let c = save_all(&mut history, &["c"])[0];
+
+        assert_eq!(command_lines(&history), ["a", "c"]);
+        assert_eq!(history.load(ids[0]).unwrap().command_line, "a");
+        assert!(history.load(ids[1]).is_err());
+        assert_eq!(history.load(c).unwrap().command_line, "c");
+    }
+
+    #[test]
+    fn ids_stay_when_syncing_and_truncating_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("history.txt");
+        let mut history = FileBackedHistory::with_file(3, file.clone()).unwrap();
+        let mut ids = save_all(&mut history, &["a", "b"]);
+        history.sync().unwrap();
+        ids.extend(save_all(&mut history, &["c", "d"]));
+        history.sync().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "b\nc\nd\n");
+        assert!(history.load(ids[0]).is_err());
+        for (id, line) in ids[1..].iter().zip(["b", "c", "d"]) {
+            assert_eq!(history.load(*id).unwrap().command_line, line);
+        }
+        let e = save_all(&mut history, &["e"])[0];
+        assert!(e > ids[3]);
+    }
+
+    #[test]
+    fn entries_written_by_another_history_give_all_entries_new_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("history.txt");
+        let mut history = FileBackedHistory::with_file(10, file.clone()).unwrap();
+        let mut other = FileBackedHistory::with_file(10, file).unwrap();
+        let a = save_all(&mut history, &["a"])[0];
+        history.sync().unwrap();
+        save_all(&mut other, &["b"]);
+        other.sync().unwrap();
+        let c = save_all(&mut history, &["c"])[0];
+        history.sync().unwrap();
+
+        assert_eq!(command_lines(&history), ["a", "b", "c"]);
+        // The old ids aren't found instead of pointing at other entries
+        assert!(history.load(a).is_err());
+        assert!(history.load(c).is_err());
+        let ids: Vec<_> = history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap()
+            .into_iter()
+            .map(|item| item.id.unwrap())
+            .collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(ids[0] > c);
+    }
+}
@@ -1,3 +1,7 @@
+#[cfg(feature = "async_history")]
+mod async_history;
+#[cfg(feature = "atuin")]
+mod atuin_backed;
 mod base;
 mod cursor;
 mod file_backed;
@@ -7,8 +11,14 @@ mod sqlite_backed;
 #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
 pub use sqlite_backed::SqliteBackedHistory;
 
+#[cfg(feature = "async_history")]
+pub use async_history::{AsyncHistory, BlockingHistory, HistoryFuture};
+#[cfg(feature = "atuin")]
+pub use atuin_backed::AtuinHistory;
+
 pub use base::{
-    CommandLineSearch, History, HistoryNavigationQuery, SearchDirection, SearchFilter, SearchQuery,
+    CommandLineSearch, DirectoryHistoryScope, History, HistoryNavigationQuery, SearchDirection,
+    SearchFilter, SearchQuery,
 };
 pub use cursor::HistoryCursor;
 pub use item::{HistoryItem, HistoryItemId, HistorySessionId};
@@ -8,7 +8,7 @@ use crate::{
 };
 use chrono::{TimeZone, Utc};
 use rusqlite::{named_params, params, Connection, ToSql};
-use std::{path::PathBuf, time::Duration};
+use std::{ops::ControlFlow, path::PathBuf, time::Duration};
 const SQLITE_APPLICATION_ID: i32 = 1151497937;
 
 /// A history that stores the values to an SQLite database.
@@ -21,6 +21,7 @@ pub struct SqliteBackedHistory {
     db: rusqlite::Connection,
     session: Option<HistorySessionId>,
     session_timestamp: Option<chrono::DateTime<Utc>>,
+    substring_index: bool,
 }
 
 fn deserialize_history_item(row: &rusqlite::Row) -> rusqlite::Result<HistoryItem> {
@@ -97,6 +98,15 @@ impl History for SqliteBackedHistory {
         Ok(entry)
     }
 
+    fn save_batch(&mut self, items: Vec<HistoryItem>) -> Result<Vec<HistoryItem>> {
+        // One transaction, so either all items are saved or none
+        self.db.execute_batch("begin").map_err(map_sqlite_err)?;
+        let saved: Result<Vec<_>> = items.into_iter().map(|item| self.save(item)).collect();
+        let end = if saved.is_ok() { "commit" } else { "rollback" };
+        self.db.execute_batch(end).map_err(map_sqlite_err)?;
+        saved
+    }
+
     fn load(&self, id: HistoryItemId) -> Result<HistoryItem> {
         let entry = self
             .db
@@ -133,6 +143,57 @@ impl History for SqliteBackedHistory {
         Ok(results)
     }
 
+    fn search_paged(
+        &self,
+        query: SearchQuery,
+        page: usize,
+        page_size: usize,
+    ) -> Result<Vec<HistoryItem>> {
+        let offset = i64::try_from(page.saturating_mul(page_size)).unwrap_or(i64::MAX);
+        let page_size = i64::try_from(page_size).unwrap_or(i64::MAX);
+        // The limit of the query ends the results, which may be within the page
+        let take = match query.limit {
+            Some(limit) => page_size.min(limit.saturating_sub(offset).max(0)),
+            None => page_size,
+        };
+        let query = SearchQuery {
+            limit: Some(take),
+            ..query
+        };
+        let (query, mut params) = self.construct_query(&query, "*");
+        let query = format!("{query} offset :offset");
+        params.push((":offset", Box::new(offset)));
+        let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
+        let results: Vec<HistoryItem> = self
+            .db
+            .prepare(&query)
+            .map_err(map_sqlite_err)?
+            .query_map(&params_borrow[..], deserialize_history_item)
+            .map_err(map_sqlite_err)?
+            .collect::<rusqlite::Result<Vec<HistoryItem>>>()
+            .map_err(map_sqlite_err)?;
+        Ok(results)
+    }
+
+    fn search_streaming(
+        &self,
+        query: SearchQuery,
+        on_item: &mut dyn FnMut(HistoryItem) -> ControlFlow<()>,
+    ) -> Result<()> {
+        let (query, params) = self.construct_query(&query, "*");
+        let params_borrow: Vec<(&str, &dyn ToSql)> = params.iter().map(|e| (e.0, &*e.1)).collect();
+        let mut statement = self.db.prepare(&query).map_err(map_sqlite_err)?;
+        let rows = statement
+            .query_map(&params_borrow[..], deserialize_history_item)
+            .map_err(map_sqlite_err)?;
+        for item in rows {
+            if on_item(item.map_err(map_sqlite_err)?).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
     fn update(
         &mut self,
         id: HistoryItemId,
@@ -171,11 +232,62 @@ impl History for SqliteBackedHistory {
         Ok(())
     }
 
+    fn set_pinned(&mut self, id: HistoryItemId, pinned: bool) -> Result<()> {
+        if pinned {
+            self.db
+                .execute(
+                    "insert or ignore into history_pinned (history_id) values (?)",
+                    params![id.0],
+                )
+                .map_err(map_sqlite_err)?;
+        } else {
+            self.db
+                .execute(
+                    "delete from history_pinned where history_id = ?",
+                    params![id.0],
+                )
+                .map_err(map_sqlite_err)?;
+        }
+        Ok(())
+    }
+
+    fn is_pinned(&self, id: HistoryItemId) -> Result<bool> {
+        let count: i64 = self
+            .db
+            .query_row(
+                "select count(*) from history_pinned where history_id = ?",
+                params![id.0],
+                |r| r.get(0),
+            )
+            .map_err(map_sqlite_err)?;
+        Ok(count > 0)
+    }
+
+    fn pinned(&self) -> Result<Vec<HistoryItem>> {
+        let results = self
+            .db
+            .prepare(
+                "select history.* from history \
+                 join history_pinned on history_pinned.history_id = history.id \
+                 order by history.id desc",
+            )
+            .map_err(map_sqlite_err)?
+            .query_map(params![], deserialize_history_item)
+            .map_err(map_sqlite_err)?
+            .collect::<rusqlite::Result<Vec<HistoryItem>>>()
+            .map_err(map_sqlite_err)?;
+        Ok(results)
+    }
+
     fn sync(&mut self) -> std::io::Result<()> {
         // no-op (todo?)
         Ok(())
     }
 
+    fn supports_directories(&self) -> bool {
+        true
+    }
+
     fn session(&self) -> Option<HistorySessionId> {
         self.session
     }
@@ -263,17 +375,73 @@ impl SqliteBackedHistory {
         create index if not exists idx_history_exit_status on history(exit_status);
         create index if not exists idx_history_cmd on history(command_line);
         create index if not exists idx_history_cmd on history(session_id);
+        create table if not exists history_pinned (
+            history_id integer primary key references history(id) on delete cascade
+        ) strict;
         -- todo: better indexes
         ",
         )
         .map_err(map_sqlite_err)?;
+        let substring_index = db
+            .query_row(
+                "select count(*) from sqlite_master where type = 'table' and name = 'history_fts'",
+                params![],
+                |r| r.get::<_, i64>(0),
+            )
+            .map_err(map_sqlite_err)?
+            > 0;
         Ok(SqliteBackedHistory {
             db,
             session,
             session_timestamp,
+            substring_index,
         })
     }
 
+    /// Index the command lines for substring searches
+    ///
+    /// Speeds up searches for entries containing a string
+    /// ([`CommandLineSearch::Substring`]) of at least three characters, which
+    /// otherwise scan the whole history. The index is stored in the database, so it
+    /// only has to be enabled once and then keeps being used and updated.
+    ///
+    /// Needs SQLite 3.34 or later for its trigram tokenizer, which the bundled
+    /// version of the `sqlite` feature satisfies.
+    pub fn enable_substring_index(&mut self) -> Result<()> {
+        if self.substring_index {
+            return Ok(());
+        }
+        self.db
+            .execute_batch(
+                "
+        begin;
+        create virtual table history_fts using fts5(
+            command_line,
+            content = 'history',
+            content_rowid = 'id',
+            tokenize = 'trigram case_sensitive 1'
+        );
+        create trigger history_fts_insert after insert on history begin
+            insert into history_fts(rowid, command_line) values (new.id, new.command_line);
+        end;
+        create trigger history_fts_delete after delete on history begin
+            insert into history_fts(history_fts, rowid, command_line)
+                values ('delete', old.id, old.command_line);
+        end;
+        create trigger history_fts_update after update of command_line on history begin
+            insert into history_fts(history_fts, rowid, command_line)
+                values ('delete', old.id, old.command_line);
+            insert into history_fts(rowid, command_line) values (new.id, new.command_line);
+        end;
+        insert into history_fts(history_fts) values ('rebuild');
+        commit;
+        ",
+            )
+            .map_err(map_sqlite_err)?;
+        self.substring_index = true;
+        Ok(())
+    }
+
     fn construct_query<'a>(
         &self,
         query: &'a SearchQuery,
@@ -336,6 +504,14 @@ impl SqliteBackedHistory {
                     params.push((":command_line", Box::new(prefix)));
                 }
                 CommandLineSearch::Substring(cont) => {
+                    // The trigram index only finds strings of at least three characters
+                    if self.substring_index && cont.chars().count() >= 3 {
+                        wheres.push(
+                            "id in (select rowid from history_fts where history_fts match :fts_query)",
+                        );
+                        let fts_query = format!("\"{}\"", cont.replace('"', "\"\""));
+                        params.push((":fts_query", Box::new(fts_query)));
+                    }
                     wheres.push("instr(command_line, :command_line) >= 1");
                     params.push((":command_line", Box::new(cont)));
                 }
@@ -393,3 +569,180 @@ impl SqliteBackedHistory {
         (query, params)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn history_with(command_lines: &[&str], substring_index: bool) -> SqliteBackedHistory {
+        let mut history = SqliteBackedHistory::in_memory().unwrap();
+        if substring_index {
+            history.enable_substring_index().unwrap();
+        }
+        for command_line in command_lines {
+            history
+                .save(HistoryItem::from_command_line(*command_line))
+                .unwrap();
+        }
+        history
+    }
+
+    fn command_lines_containing(history: &SqliteBackedHistory, contains: &str) -> Vec<String> {
+        history
+            .search(SearchQuery::all_that_contain_rev(contains.to_string()))
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect()
+    }
+
+    #[rstest]
+    #[case("c")]
+    #[case("ca")]
+    #[case("cargo")]
+    #[case("Cargo")]
+    #[case("test --")]
+    #[case("\"quoted\"")]
+    #[case("not there")]
+    fn substring_index_finds_the_same_entries(#[case] contains: &str) {
+        let command_lines = [
+            "cargo test --workspace",
+            "echo \"quoted\"",
+            "Cargo build",
+            "ls",
+            "cat Cargo.toml",
+        ];
+        let scanning = history_with(&command_lines, false);
+        let indexed = history_with(&command_lines, true);
+
+        assert_eq!(
+            command_lines_containing(&indexed, contains),
+            command_lines_containing(&scanning, contains)
+        );
+    }
+
+    #[test]
+    fn substring_index_indexes_existing_and_changed_entries() {
+        let mut history = history_with(&["cargo test", "git status"], false);
+        history.enable_substring_index().unwrap();
+        assert_eq!(command_lines_containing(&history, "status"), ["git status"]);
+
+        let saved = history
+            .save(HistoryItem::from_command_line("git stash"))
+            .unwrap();
+        assert_eq!(command_lines_containing(&history, "stash"), ["git stash"]);
+
+        history
+            .update(saved.id.unwrap(), &|mut item| {
+                item.command_line = "git log".to_string();
+                item
+            })
+            .unwrap();
+        assert_eq!(
+            command_lines_containing(&history, "stash"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            command_lines_containing(&history, "git"),
+            ["git log", "git status"]
+        );
+
+        history.delete(saved.id.unwrap()).unwrap();
+        assert_eq!(command_lines_containing(&history, "git"), ["git status"]);
+    }
+
+    #[test]
+    fn substring_index_is_kept_in_the_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("history.sqlite3");
+        let mut history = SqliteBackedHistory::with_file(file.clone(), None, None).unwrap();
+        history.enable_substring_index().unwrap();
+        drop(history);
+
+        let mut history = SqliteBackedHistory::with_file(file, None, None).unwrap();
+        assert!(history.substring_index);
+        history.enable_substring_index().unwrap();
+    }
+
+    #[test]
+    fn pinned_entries_survive_updates_but_not_deletion() {
+        let mut history = history_with(&["cargo test", "git status", "ls"], false);
+        let items = history
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap();
+        let (first, second) = (items[0].id.unwrap(), items[1].id.unwrap());
+        history.set_pinned(first, true).unwrap();
+        history.set_pinned(second, true).unwrap();
+        history.set_pinned(second, true).unwrap();
+        assert!(history.is_pinned(first).unwrap());
+        assert!(!history.is_pinned(items[2].id.unwrap()).unwrap());
+
+        history
+            .update(first, &|mut item| {
+                item.exit_status = Some(0);
+                item
+            })
+            .unwrap();
+        let pinned: Vec<_> = history
+            .pinned()
+            .unwrap()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect();
+        assert_eq!(pinned, ["git status", "cargo test"]);
+
+        history.set_pinned(second, false).unwrap();
+        history.delete(first).unwrap();
+        assert!(history.pinned().unwrap().is_empty());
+    }
+
+    #[test]
+    fn batches_are_saved_and_searched_together() {
+        let mut history = history_with(&["ls"], false);
+        let saved = history
+            .save_batch(vec![
+                HistoryItem::from_command_line("cargo test"),
+                HistoryItem::from_command_line("git status"),
+            ])
+            .unwrap();
+        assert_eq!(saved.len(), 2);
+        assert!(saved[0].id.unwrap() < saved[1].id.unwrap());
+
+        let results = history
+            .search_batch(vec![
+                SearchQuery::all_that_contain_rev("cargo".to_string()),
+                SearchQuery::everything(SearchDirection::Forward, None),
+            ])
+            .unwrap();
+        let command_lines: Vec<Vec<_>> = results
+            .into_iter()
+            .map(|items| items.into_iter().map(|item| item.command_line).collect())
+            .collect();
+        assert_eq!(
+            command_lines,
+            [vec!["cargo test"], vec!["ls", "cargo test", "git status"]]
+        );
+    }
+
+    #[test]
+    fn search_streaming_stops_when_asked() {
+        let history = history_with(&["one", "two", "three"], false);
+        let mut seen = Vec::new();
+        history
+            .search_streaming(
+                SearchQuery::everything(SearchDirection::Backward, None),
+                &mut |item| {
+                    seen.push(item.command_line);
+                    if seen.len() == 2 {
+                        ControlFlow::Break(())
+                    } else {
+                        ControlFlow::Continue(())
+                    }
+                },
+            )
+            .unwrap();
+        assert_eq!(seen, ["three", "two"]);
+    }
+}
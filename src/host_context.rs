@@ -0,0 +1,143 @@
+use crate::{Completer, CompletionError, Hinter, History, Suggestion};
+use std::any::Any;
+
+/// The state of the application given to [`crate::Reedline::with_context`]
+pub(crate) type HostContext = Box<dyn Any + Send>;
+
+/// `completer` asked through [`Completer::complete_in_context`] if there is a context
+pub(crate) struct ContextCompleter<'a> {
+    completer: &'a mut dyn Completer,
+    context: Option<&'a (dyn Any + Send)>,
+}
+
+// Safe to implement Send since the ContextCompleter only lives while the menus are
+// updated and that must happen in the same thread
+unsafe impl<'a> Send for ContextCompleter<'a> {}
+
+impl<'a> ContextCompleter<'a> {
+    pub(crate) fn new(
+        completer: &'a mut dyn Completer,
+        context: Option<&'a (dyn Any + Send)>,
+    ) -> Self {
+        Self { completer, context }
+    }
+}
+
+impl<'a> Completer for ContextCompleter<'a> {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        match self.context {
+            Some(context) => self.completer.complete_in_context(line, pos, context),
+            None => self.completer.complete(line, pos),
+        }
+    }
+
+    fn complete_with_base_ranges(
+        &mut self,
+        line: &str,
+        pos: usize,
+    ) -> (Vec<Suggestion>, Vec<std::ops::Range<usize>>) {
+        if self.context.is_some() {
+            let suggestions = self.complete(line, pos);
+            let mut ranges: Vec<_> = suggestions
+                .iter()
+                .map(|suggestion| suggestion.span.start..suggestion.span.end)
+                .collect();
+            ranges.dedup();
+            (suggestions, ranges)
+        } else {
+            self.completer.complete_with_base_ranges(line, pos)
+        }
+    }
+
+    fn try_complete_with_base_ranges(
+        &mut self,
+        line: &str,
+        pos: usize,
+    ) -> Result<(Vec<Suggestion>, Vec<std::ops::Range<usize>>), CompletionError> {
+        if self.context.is_some() {
+            Ok(self.complete_with_base_ranges(line, pos))
+        } else {
+            self.completer.try_complete_with_base_ranges(line, pos)
+        }
+    }
+
+    fn partial_complete(
+        &mut self,
+        line: &str,
+        pos: usize,
+        start: usize,
+        offset: usize,
+    ) -> Vec<Suggestion> {
+        if self.context.is_some() {
+            self.complete(line, pos)
+                .into_iter()
+                .skip(start)
+                .take(offset)
+                .collect()
+        } else {
+            self.completer.partial_complete(line, pos, start, offset)
+        }
+    }
+
+    fn total_completions(&mut self, line: &str, pos: usize) -> usize {
+        if self.context.is_some() {
+            self.complete(line, pos).len()
+        } else {
+            self.completer.total_completions(line, pos)
+        }
+    }
+
+    fn is_pending(&self) -> bool {
+        self.completer.is_pending()
+    }
+}
+
+/// `hinter` asked through [`Hinter::handle_in_context`] if there is a context
+pub(crate) struct ContextHinter<'a> {
+    hinter: &'a mut dyn Hinter,
+    context: Option<&'a (dyn Any + Send)>,
+}
+
+// Safe to implement Send since the ContextHinter only lives while the hint is painted
+// and that must happen in the same thread
+unsafe impl<'a> Send for ContextHinter<'a> {}
+
+impl<'a> ContextHinter<'a> {
+    pub(crate) fn new(hinter: &'a mut dyn Hinter, context: Option<&'a (dyn Any + Send)>) -> Self {
+        Self { hinter, context }
+    }
+}
+
+impl<'a> Hinter for ContextHinter<'a> {
+    fn handle(
+        &mut self,
+        line: &str,
+        pos: usize,
+        history: &dyn History,
+        use_ansi_coloring: bool,
+    ) -> String {
+        match self.context {
+            Some(context) => {
+                self.hinter
+                    .handle_in_context(line, pos, history, use_ansi_coloring, context)
+            }
+            None => self.hinter.handle(line, pos, history, use_ansi_coloring),
+        }
+    }
+
+    fn complete_hint(&self) -> String {
+        self.hinter.complete_hint()
+    }
+
+    fn next_hint_token(&self) -> String {
+        self.hinter.next_hint_token()
+    }
+
+    fn hint_depends_on_cursor(&self) -> bool {
+        self.hinter.hint_depends_on_cursor()
+    }
+
+    fn is_pending(&self) -> bool {
+        self.hinter.is_pending()
+    }
+}
@@ -0,0 +1,33 @@
+use crate::Indenter;
+
+/// A default indenter which keeps the indentation of the line the newline is inserted on
+pub struct DefaultIndenter;
+
+impl Indenter for DefaultIndenter {
+    fn indent(&self, line: &str, pos: usize) -> String {
+        let before = &line[..pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        before[line_start..]
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("", 0, "")]
+    #[case("foo {", 5, "")]
+    #[case("foo {\n    bar", 13, "    ")]
+    #[case("foo {\n\tbar", 10, "\t")]
+    #[case("foo {\n    bar", 7, " ")]
+    #[case("  foo\nbar", 9, "")]
+    fn indent_copies_previous_line(#[case] line: &str, #[case] pos: usize, #[case] expected: &str) {
+        assert_eq!(DefaultIndenter.indent(line, pos), expected);
+    }
+}
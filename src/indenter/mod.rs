@@ -0,0 +1,12 @@
+mod default;
+pub use default::DefaultIndenter;
+
+/// The auto-indentation trait. Implementers of this trait decide which indentation a new line
+/// receives when Enter inserts a newline into an incomplete input (see [`crate::Validator`])
+///
+/// The indentation is adjusted afterwards with [`crate::EditCommand::IndentLine`] and
+/// [`crate::EditCommand::DedentLine`], bound to `Tab` and `Shift-Tab` by default
+pub trait Indenter: Send {
+    /// Return the indentation for a new line inserted at byte offset `pos` of `line`
+    fn indent(&self, line: &str, pos: usize) -> String;
+}
@@ -0,0 +1,165 @@
+use crate::edit_mode::KeyCombination;
+use crossterm::event::{KeyCode, KeyModifiers};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Keys shown at most at once
+const MAX_KEYS: usize = 8;
+
+// Modifiers in the order they are usually printed on keycaps
+const MODIFIER_GLYPHS: [(KeyModifiers, &str); 3] = [
+    (KeyModifiers::CONTROL, "⌃"),
+    (KeyModifiers::ALT, "⌥"),
+    (KeyModifiers::SHIFT, "⇧"),
+];
+
+/// The recently pressed keys, shown by [`crate::Reedline::with_key_overlay`]
+pub(crate) struct KeyOverlay {
+    duration: Duration,
+    keys: VecDeque<PressedKey>,
+}
+
+struct PressedKey {
+    text: String,
+    // Typed characters, which the next typed characters are added to
+    typed: bool,
+    at: Instant,
+}
+
+impl KeyOverlay {
+    pub(crate) fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            keys: VecDeque::new(),
+        }
+    }
+
+    /// Add `key`, pressed at `now`
+    pub(crate) fn record(&mut self, key: &KeyCombination, now: Instant) {
+        self.prune(now);
+        let (text, typed) = key_glyphs(key);
+        match self.keys.back_mut() {
+            Some(last) if typed && last.typed => {
+                last.text.push_str(&text);
+                last.at = now;
+            }
+            _ => self.keys.push_back(PressedKey {
+                text,
+                typed,
+                at: now,
+            }),
+        }
+        while self.keys.len() > MAX_KEYS {
+            self.keys.pop_front();
+        }
+    }
+
+    /// Forget the keys shown long enough, returns whether there were any
+    pub(crate) fn prune(&mut self, now: Instant) -> bool {
+        let before = self.keys.len();
+        let duration = self.duration;
+        self.keys.retain(|key| now < key.at + duration);
+        self.keys.len() != before
+    }
+
+    /// When the next key is forgotten
+    pub(crate) fn next_expiry(&self) -> Option<Instant> {
+        self.keys.iter().map(|key| key.at + self.duration).min()
+    }
+
+    /// The keys to show, oldest first
+    pub(crate) fn text(&self) -> Option<String> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        Some(
+            self.keys
+                .iter()
+                .map(|key| key.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+}
+
+/// The key as printed on keycaps, e.g. `⌃R`, and whether it types a character
+fn key_glyphs(key: &KeyCombination) -> (String, bool) {
+    let mut modifiers = key.modifier;
+    if let KeyCode::Char(_) = key.key_code {
+        // Shift is already part of the character
+        modifiers.remove(KeyModifiers::SHIFT);
+    }
+    let mut text: String = MODIFIER_GLYPHS
+        .iter()
+        .filter(|(modifier, _)| modifiers.contains(*modifier))
+        .map(|(_, glyph)| *glyph)
+        .collect();
+    let typed = modifiers.is_empty() && matches!(key.key_code, KeyCode::Char(c) if c != ' ');
+    match key.key_code {
+        KeyCode::Char(' ') => text.push('␣'),
+        KeyCode::Char(c) if modifiers.is_empty() => text.push(c),
+        KeyCode::Char(c) => text.extend(c.to_uppercase()),
+        KeyCode::Enter => text.push('⏎'),
+        KeyCode::Tab => text.push('⇥'),
+        KeyCode::BackTab => text.push_str("⇧⇥"),
+        KeyCode::Backspace => text.push('⌫'),
+        KeyCode::Delete => text.push('⌦'),
+        KeyCode::Esc => text.push('⎋'),
+        KeyCode::Up => text.push('↑'),
+        KeyCode::Down => text.push('↓'),
+        KeyCode::Left => text.push('←'),
+        KeyCode::Right => text.push('→'),
+        KeyCode::Home => text.push_str("Home"),
+        KeyCode::End => text.push_str("End"),
+        KeyCode::PageUp => text.push_str("PgUp"),
+        KeyCode::PageDown => text.push_str("PgDn"),
+        KeyCode::F(n) => text.push_str(&format!("F{n}")),
+        other => text.push_str(&format!("{other:?}")),
+    }
+    (text, typed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn key(modifier: KeyModifiers, key_code: KeyCode) -> KeyCombination {
+        KeyCombination { modifier, key_code }
+    }
+
+    #[test]
+    fn keys_are_shown_like_on_keycaps() {
+        let glyphs = |modifier, key_code| key_glyphs(&key(modifier, key_code)).0;
+        assert_eq!(glyphs(KeyModifiers::CONTROL, KeyCode::Char('r')), "⌃R");
+        assert_eq!(
+            glyphs(KeyModifiers::CONTROL | KeyModifiers::ALT, KeyCode::Left),
+            "⌃⌥←"
+        );
+        assert_eq!(glyphs(KeyModifiers::SHIFT, KeyCode::Char('W')), "W");
+        assert_eq!(glyphs(KeyModifiers::SHIFT, KeyCode::Enter), "⇧⏎");
+        assert_eq!(glyphs(KeyModifiers::NONE, KeyCode::Char(' ')), "␣");
+        assert_eq!(glyphs(KeyModifiers::NONE, KeyCode::F(5)), "F5");
+    }
+
+    #[test]
+    fn typed_characters_are_grouped_until_they_expire() {
+        let start = Instant::now();
+        let mut overlay = KeyOverlay::new(Duration::from_secs(2));
+        overlay.record(&key(KeyModifiers::NONE, KeyCode::Esc), start);
+        overlay.record(&key(KeyModifiers::NONE, KeyCode::Char('d')), start);
+        overlay.record(&key(KeyModifiers::NONE, KeyCode::Char('w')), start);
+        assert_eq!(overlay.text().as_deref(), Some("⎋ dw"));
+
+        let later = start + Duration::from_secs(1);
+        overlay.record(&key(KeyModifiers::CONTROL, KeyCode::Char('r')), later);
+        assert_eq!(overlay.next_expiry(), Some(start + Duration::from_secs(2)));
+        assert!(overlay.prune(start + Duration::from_secs(2)));
+        assert_eq!(overlay.text().as_deref(), Some("⌃R"));
+        assert!(!overlay.prune(start + Duration::from_secs(2)));
+        assert!(overlay.prune(later + Duration::from_secs(2)));
+        assert_eq!(overlay.text(), None);
+    }
+}
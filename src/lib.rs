@@ -182,6 +182,9 @@
 //! - `sqlite`: Provides the `SqliteBackedHistory` to store richer information in the history. Statically links the required sqlite version.
 //! - `sqlite-dynlib`: Alternative to the feature `sqlite`. Will not statically link. Requires `sqlite >= 3.38` to link dynamically!
 //! - `external_printer`: **Experimental:** Thread-safe `ExternalPrinter` handle to print lines from concurrently running threads.
+//! - `atuin`: Provides the `AtuinHistory` to share the history with the shells set up for [Atuin](https://atuin.sh) through its local store. Links sqlite like `sqlite-dynlib`, unless `sqlite` is enabled too.
+//! - `async_history`: Provides the `AsyncHistory` trait for histories that wait for e.g. a sync server, used through the `BlockingHistory` adapter.
+//! - `image_preview`: Lets the `IdeMenu` show an image next to the selected value, e.g. of a completed path, painted with the kitty graphics protocol or sixels where the terminal supports them.
 //!
 //! ## Are we prompt yet? (Development status)
 //!
@@ -226,47 +229,62 @@
 #![warn(missing_docs)]
 // #![deny(warnings)]
 mod core_editor;
-pub use core_editor::Editor;
 pub use core_editor::LineBuffer;
+pub use core_editor::{AutoPairFilter, AutoPairs, Editor};
 
 mod enums;
-pub use enums::{EditCommand, ReedlineEvent, ReedlineRawEvent, Signal, UndoBehavior};
+pub use enums::{
+    BranchOutcome, EditCommand, EventCondition, HostCommand, ReedlineEvent, ReedlineRawEvent,
+    Signal, SignalInfo, SignalReason, Submission, UndoBehavior, ViewportLine,
+};
 
 mod painting;
-pub use painting::{Painter, StyledText};
+pub use painting::{Painter, Sanitizer, StyledText, SubmitEcho, Underline};
 
 mod engine;
-pub use engine::Reedline;
+pub use engine::{Reedline, SuspendGuard};
 
 mod result;
-pub use result::{ReedlineError, ReedlineErrorVariants, Result};
+pub use result::{
+    ReedlineError, ReedlineErrorCategory, ReedlineErrorVariants, RenderErrorAction, Result,
+};
 
 mod history;
+#[cfg(feature = "atuin")]
+pub use history::AtuinHistory;
 #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
 pub use history::SqliteBackedHistory;
+#[cfg(feature = "async_history")]
+pub use history::{AsyncHistory, BlockingHistory, HistoryFuture};
 pub use history::{
-    CommandLineSearch, FileBackedHistory, History, HistoryItem, HistoryItemId,
-    HistoryNavigationQuery, HistorySessionId, SearchDirection, SearchFilter, SearchQuery,
-    HISTORY_SIZE,
+    CommandLineSearch, DirectoryHistoryScope, FileBackedHistory, History, HistoryItem,
+    HistoryItemId, HistoryNavigationQuery, HistorySessionId, SearchDirection, SearchFilter,
+    SearchQuery, HISTORY_SIZE,
 };
 
 mod prompt;
 pub use prompt::{
     DefaultPrompt, DefaultPromptSegment, Prompt, PromptEditMode, PromptHistorySearch,
-    PromptHistorySearchStatus, PromptViMode,
+    PromptHistorySearchStatus, PromptViMode, PromptViPending,
 };
 
 mod edit_mode;
 pub use edit_mode::{
     default_emacs_keybindings, default_vi_insert_keybindings, default_vi_normal_keybindings,
-    CursorConfig, EditMode, Emacs, Keybindings, Vi,
+    CursorConfig, EditMode, Emacs, KeyCombination, KeybindingConflict, KeybindingDescription,
+    KeybindingReport, Keybindings, Vi, ViCharSearchReset,
 };
 
 mod highlighter;
-pub use highlighter::{ExampleHighlighter, Highlighter, SimpleMatchHighlighter};
+pub use highlighter::{
+    ExampleHighlighter, Highlighter, LayeredHighlighter, SimpleMatchHighlighter,
+};
 
 mod completion;
-pub use completion::{Completer, DefaultCompleter, Span, Suggestion};
+pub use completion::{
+    Completer, CompletionCacheInvalidator, CompletionError, CompletionUsage, CompletionUsageEntry,
+    DefaultCompleter, Span, Suggestion, SuggestionComparator, SuggestionSort,
+};
 
 mod hinter;
 pub use hinter::CwdAwareHinter;
@@ -275,11 +293,66 @@ pub use hinter::{DefaultHinter, Hinter};
 mod validator;
 pub use validator::{DefaultValidator, ValidationResult, Validator};
 
+mod indenter;
+pub use indenter::{DefaultIndenter, Indenter};
+
+mod corrector;
+pub use corrector::Corrector;
+
+mod abbreviations;
+pub use abbreviations::Abbreviations;
+
+mod formatter;
+pub use formatter::{BufferFormatter, CommandFormatter};
+
+mod ex_command;
+mod folding;
+pub use ex_command::ExCommand;
+
+mod search_replace;
+
+mod choice;
+
+mod word_expansion;
+
+mod buffer_search;
+
+mod custom_event;
+pub use custom_event::CustomEventHandler;
+
+mod unhandled_input;
+pub use unhandled_input::UnhandledInput;
+
+mod template;
+pub use template::{FilledTemplate, Template};
+
+mod read_line_options;
+pub use read_line_options::ReadLineOptions;
+
+mod repaint_handle;
+pub use repaint_handle::RepaintHandle;
+
+mod event_injector;
+pub use event_injector::EventInjector;
+
+mod bookmarks;
+pub use bookmarks::{Bookmark, Bookmarks};
+
+mod host_context;
+
+mod key_overlay;
+
+mod debug_state;
+pub use debug_state::EngineStateSnapshot;
+
 mod menu;
 pub use menu::{
-    menu_functions, ColumnarMenu, DescriptionMenu, DescriptionMode, IdeMenu, ListMenu, Menu,
-    MenuBuilder, MenuEvent, MenuTextStyle, ReedlineMenu,
+    menu_functions, ColumnarMenu, DescriptionMenu, DescriptionMode, FillDirection, IdeMenu,
+    ListMenu, Menu, MenuBuilder, MenuEvent, MenuPlacement, MenuTextStyle, PostInsertHook,
+    PreInsertHook, ReedlineMenu,
 };
+#[cfg(feature = "image_preview")]
+pub use menu::{GraphicsProtocol, Preview, PreviewImage, Previewer};
 
 mod terminal_extensions;
 pub use terminal_extensions::kitty_protocol_available;
@@ -287,6 +360,7 @@ pub use terminal_extensions::kitty_protocol_available;
 mod utils;
 
 mod external_printer;
+mod frame_timings;
 pub use utils::{
     get_reedline_default_keybindings, get_reedline_edit_commands,
     get_reedline_keybinding_modifiers, get_reedline_keycodes, get_reedline_prompt_edit_modes,
@@ -300,3 +374,5 @@ pub use crossterm::{
 };
 #[cfg(feature = "external_printer")]
 pub use external_printer::ExternalPrinter;
+#[cfg(feature = "tracing")]
+pub use frame_timings::{CacheStats, FrameTimings};
@@ -1,12 +1,26 @@
 use super::{Menu, MenuBuilder, MenuEvent, MenuSettings};
 use crate::{
     core_editor::Editor,
-    menu_functions::{can_partially_complete, completer_input, replace_in_buffer},
+    menu_functions::{
+        can_partially_complete, completer_input, completion_error_msg, hyperlink,
+        replace_in_buffer, try_complete,
+    },
     painting::Painter,
-    Completer, Suggestion,
+    Completer, CompletionError, Suggestion,
 };
 use nu_ansi_term::ansi::RESET;
-use unicode_width::UnicodeWidthStr;
+use std::borrow::Cow;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The order in which the columnar menu lays out its values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillDirection {
+    /// Values fill a row before continuing on the next one
+    #[default]
+    RowMajor,
+    /// Values fill a column before continuing on the next one, like `ls` does
+    ColumnMajor,
+}
 
 /// Default values used as reference for the menu. These values are set during
 /// the initial declaration of the menu and are always kept as reference for the
@@ -18,6 +32,12 @@ struct DefaultColumnDetails {
     pub col_width: Option<usize>,
     /// Column padding
     pub col_padding: usize,
+    /// Whether the number of columns is kept even if the values don't fit in them
+    pub fixed_columns: bool,
+    /// Narrowest column width
+    pub min_col_width: Option<usize>,
+    /// Widest column width, longer values are truncated
+    pub max_col_width: Option<usize>,
 }
 
 impl Default for DefaultColumnDetails {
@@ -26,6 +46,9 @@ impl Default for DefaultColumnDetails {
             columns: 4,
             col_width: None,
             col_padding: 2,
+            fixed_columns: false,
+            min_col_width: None,
+            max_col_width: None,
         }
     }
 }
@@ -59,6 +82,8 @@ pub struct ColumnarMenu {
     working_details: ColumnDetails,
     /// Menu cached values
     values: Vec<Suggestion>,
+    /// Why the completer failed to provide the values
+    completion_error: Option<CompletionError>,
     /// column position of the cursor. Starts from 0
     col_pos: u16,
     /// row position in the menu. Starts from 0
@@ -67,8 +92,16 @@ pub struct ColumnarMenu {
     event: Option<MenuEvent>,
     /// Longest suggestion found in the values
     longest_suggestion: usize,
+    /// Whether any of the values has a description
+    values_have_description: bool,
     /// String collected after the menu is activated
     input: Option<String>,
+    /// Order in which the values are laid out
+    fill_direction: FillDirection,
+    /// Whether the column width only grows while the menu is active
+    stable_layout: bool,
+    /// Widest column width since the menu was activated, when the layout is stable
+    stable_col_width: usize,
 }
 
 impl Default for ColumnarMenu {
@@ -80,11 +113,16 @@ impl Default for ColumnarMenu {
             min_rows: 3,
             working_details: ColumnDetails::default(),
             values: Vec::new(),
+            completion_error: None,
             col_pos: 0,
             row_pos: 0,
             event: None,
             longest_suggestion: 0,
+            values_have_description: false,
             input: None,
+            fill_direction: FillDirection::default(),
+            stable_layout: false,
+            stable_col_width: 0,
         }
     }
 }
@@ -118,56 +156,62 @@ impl ColumnarMenu {
         self.default_details.col_padding = col_padding;
         self
     }
+
+    /// Menu builder which keeps the number of columns set with [`Self::with_columns`],
+    /// truncating the values that don't fit instead of using fewer columns
+    #[must_use]
+    pub fn with_fixed_columns(mut self, fixed_columns: bool) -> Self {
+        self.default_details.fixed_columns = fixed_columns;
+        self
+    }
+
+    /// Menu builder with the narrowest column width
+    #[must_use]
+    pub fn with_min_column_width(mut self, min_col_width: Option<usize>) -> Self {
+        self.default_details.min_col_width = min_col_width;
+        self
+    }
+
+    /// Menu builder with the widest column width, longer values are truncated
+    #[must_use]
+    pub fn with_max_column_width(mut self, max_col_width: Option<usize>) -> Self {
+        self.default_details.max_col_width = max_col_width;
+        self
+    }
+
+    /// Menu builder with the order in which the values are laid out
+    #[must_use]
+    pub fn with_fill_direction(mut self, fill_direction: FillDirection) -> Self {
+        self.fill_direction = fill_direction;
+        self
+    }
+
+    /// Menu builder which keeps the columns from narrowing while the menu is active, so that
+    /// the values don't move around as the typed filter narrows them down
+    #[must_use]
+    pub fn with_stable_layout(mut self, stable_layout: bool) -> Self {
+        self.stable_layout = stable_layout;
+        self
+    }
 }
 
 // Menu functionality
 impl ColumnarMenu {
     /// Move menu cursor to the next element
     fn move_next(&mut self) {
-        let mut new_col = self.col_pos + 1;
-        let mut new_row = self.row_pos;
-
-        if new_col >= self.get_cols() {
-            new_row += 1;
-            new_col = 0;
-        }
-
-        if new_row >= self.get_rows() {
-            new_row = 0;
-            new_col = 0;
-        }
-
-        let position = new_row * self.get_cols() + new_col;
-        if position >= self.get_values().len() as u16 {
+        let next = self.index() + 1;
+        if next >= self.get_values().len() {
             self.reset_position();
         } else {
-            self.col_pos = new_col;
-            self.row_pos = new_row;
+            self.set_index(next);
         }
     }
 
     /// Move menu cursor to the previous element
     fn move_previous(&mut self) {
-        let new_col = self.col_pos.checked_sub(1);
-
-        let (new_col, new_row) = match new_col {
-            Some(col) => (col, self.row_pos),
-            None => match self.row_pos.checked_sub(1) {
-                Some(row) => (self.get_cols().saturating_sub(1), row),
-                None => (
-                    self.get_cols().saturating_sub(1),
-                    self.get_rows().saturating_sub(1),
-                ),
-            },
-        };
-
-        let position = new_row * self.get_cols() + new_col;
-        if position >= self.get_values().len() as u16 {
-            self.col_pos = (self.get_values().len() as u16 % self.get_cols()).saturating_sub(1);
-            self.row_pos = self.get_rows().saturating_sub(1);
-        } else {
-            self.col_pos = new_col;
-            self.row_pos = new_row;
+        match self.index().checked_sub(1) {
+            Some(previous) => self.set_index(previous),
+            None => self.set_index(self.get_values().len().saturating_sub(1)),
         }
     }
 
@@ -177,11 +221,10 @@ impl ColumnarMenu {
             new_row
         } else {
             let new_row = self.get_rows().saturating_sub(1);
-            let index = new_row * self.get_cols() + self.col_pos;
-            if index >= self.values.len() as u16 {
-                new_row.saturating_sub(1)
-            } else {
+            if self.has_value_at(new_row, self.col_pos) {
                 new_row
+            } else {
+                new_row.saturating_sub(1)
             }
         }
     }
@@ -189,43 +232,71 @@ impl ColumnarMenu {
     /// Move menu cursor left
     fn move_down(&mut self) {
         let new_row = self.row_pos + 1;
-        self.row_pos = if new_row >= self.get_rows() {
-            0
+        self.row_pos = if self.has_value_at(new_row, self.col_pos) {
+            new_row
         } else {
-            let index = new_row * self.get_cols() + self.col_pos;
-            if index >= self.values.len() as u16 {
-                0
-            } else {
-                new_row
-            }
+            0
         }
     }
 
     /// Move menu cursor left
     fn move_left(&mut self) {
-        self.col_pos = if let Some(row) = self.col_pos.checked_sub(1) {
-            row
-        } else if self.index() + 1 == self.values.len() {
-            0
+        self.col_pos = if let Some(col) = self.col_pos.checked_sub(1) {
+            col
         } else {
-            self.get_cols().saturating_sub(1)
+            // Wraps around to the last column with a value in this row
+            (0..self.get_cols())
+                .rev()
+                .find(|col| self.has_value_at(self.row_pos, *col))
+                .unwrap_or(0)
         }
     }
 
     /// Move menu cursor element
     fn move_right(&mut self) {
         let new_col = self.col_pos + 1;
-        self.col_pos = if new_col >= self.get_cols() || self.index() + 2 > self.values.len() {
-            0
-        } else {
+        self.col_pos = if new_col < self.get_cols() && self.has_value_at(self.row_pos, new_col) {
             new_col
+        } else {
+            0
         }
     }
 
     /// Menu index based on column and row position
     fn index(&self) -> usize {
-        let index = self.row_pos * self.get_cols() + self.col_pos;
-        index as usize
+        self.index_at(self.row_pos, self.col_pos)
+    }
+
+    /// Index of the value shown in a row and column, depending on the fill direction
+    fn index_at(&self, row: u16, col: u16) -> usize {
+        let (row, col) = (row as usize, col as usize);
+        match self.fill_direction {
+            FillDirection::RowMajor => row * self.get_cols() as usize + col,
+            FillDirection::ColumnMajor => col * self.get_rows() as usize + row,
+        }
+    }
+
+    /// Whether a value is shown in a row and column
+    fn has_value_at(&self, row: u16, col: u16) -> bool {
+        row < self.get_rows()
+            && col < self.get_cols()
+            && self.index_at(row, col) < self.get_values().len()
+    }
+
+    /// Moves the menu cursor to the value at `index`
+    fn set_index(&mut self, index: usize) {
+        let (row, col) = match self.fill_direction {
+            FillDirection::RowMajor => {
+                let cols = self.get_cols() as usize;
+                (index / cols, index % cols)
+            }
+            FillDirection::ColumnMajor => {
+                let rows = self.get_rows() as usize;
+                (index % rows, index / rows)
+            }
+        };
+        self.row_pos = row as u16;
+        self.col_pos = col as u16;
     }
 
     /// Get selected value from the menu
@@ -262,6 +333,13 @@ impl ColumnarMenu {
     }
 
     fn no_records_msg(&self, use_ansi_coloring: bool) -> String {
+        if let Some(error) = &self.completion_error {
+            return completion_error_msg(
+                error,
+                &self.settings.color.error_style,
+                use_ansi_coloring,
+            );
+        }
         let msg = "NO RECORDS FOUND";
         if use_ansi_coloring {
             format!(
@@ -289,6 +367,32 @@ impl ColumnarMenu {
         }
     }
 
+    /// The suggestion with its value truncated to fit in the column
+    fn fit_in_column<'a>(&self, suggestion: &'a Suggestion) -> Cow<'a, Suggestion> {
+        let available = self
+            .get_width()
+            .saturating_sub(self.default_details.col_padding);
+        if self.values_have_description || suggestion.value.width() <= available {
+            return Cow::Borrowed(suggestion);
+        }
+
+        let mut value = String::new();
+        let mut width = 0;
+        for c in suggestion.value.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if width + char_width >= available {
+                break;
+            }
+            width += char_width;
+            value.push(c);
+        }
+        value.push('…');
+        Cow::Owned(Suggestion {
+            value,
+            ..suggestion.clone()
+        })
+    }
+
     /// Creates default string that represents one suggestion from the menu
     fn create_string(
         &self,
@@ -299,7 +403,15 @@ impl ColumnarMenu {
         use_ansi_coloring: bool,
     ) -> String {
         if use_ansi_coloring {
-            let match_len = self.working_details.shortest_base_string.len();
+            let mut match_len = self
+                .working_details
+                .shortest_base_string
+                .len()
+                .min(suggestion.value.len());
+            // A truncated value may end in the middle of the match
+            while !suggestion.value.is_char_boundary(match_len) {
+                match_len -= 1;
+            }
 
             // Split string so the match text can be styled
             let (match_str, remaining_str) = suggestion.value.split_at(match_len);
@@ -329,11 +441,15 @@ impl ColumnarMenu {
                         RESET,
                         self.settings.color.description_style.prefix(),
                         self.settings.color.selected_text_style.prefix(),
-                        description
-                            .chars()
-                            .take(right_text_size)
-                            .collect::<String>()
-                            .replace('\n', " "),
+                        hyperlink(
+                            &description
+                                .chars()
+                                .take(right_text_size)
+                                .collect::<String>()
+                                .replace('\n', " "),
+                            suggestion.link.as_deref(),
+                            true,
+                        ),
                         RESET,
                         self.end_of_line(column),
                     )
@@ -364,11 +480,15 @@ impl ColumnarMenu {
                     remaining_str,
                     RESET,
                     self.settings.color.description_style.prefix(),
-                    description
-                        .chars()
-                        .take(right_text_size)
-                        .collect::<String>()
-                        .replace('\n', " "),
+                    hyperlink(
+                        &description
+                            .chars()
+                            .take(right_text_size)
+                            .collect::<String>()
+                            .replace('\n', " "),
+                        suggestion.link.as_deref(),
+                        true,
+                    ),
                     RESET,
                     self.end_of_line(column),
                 )
@@ -474,7 +594,10 @@ impl Menu for ColumnarMenu {
     /// Selects what type of event happened with the menu
     fn menu_event(&mut self, event: MenuEvent) {
         match &event {
-            MenuEvent::Activate(_) => self.active = true,
+            MenuEvent::Activate(_) => {
+                self.active = true;
+                self.stable_col_width = 0;
+            }
             MenuEvent::Deactivate => {
                 self.active = false;
                 self.input = None;
@@ -494,9 +617,29 @@ impl Menu for ColumnarMenu {
             self.settings.only_buffer_difference,
         );
 
-        let (values, base_ranges) = completer.complete_with_base_ranges(&input, pos);
+        let (values, base_ranges) = match try_complete(completer, &input, pos) {
+            Ok(completed) => {
+                self.completion_error = None;
+                completed
+            }
+            Err(error) => {
+                self.completion_error = Some(error);
+                Default::default()
+            }
+        };
 
         self.values = values;
+        // Measured once here, so that moving through thousands of values stays cheap
+        self.longest_suggestion = self
+            .values
+            .iter()
+            .map(|suggestion| suggestion.value.len())
+            .max()
+            .unwrap_or_default();
+        self.values_have_description = self
+            .values
+            .iter()
+            .any(|suggestion| suggestion.description.is_some());
         self.working_details.shortest_base_string = base_ranges
             .iter()
             .map(|range| editor.get_buffer()[range.clone()].to_string())
@@ -520,57 +663,57 @@ impl Menu for ColumnarMenu {
             //
             // If there is at least one suggestion that contains a description, then the layout
             // is changed to one column to fit the description
-            let exist_description = self
-                .get_values()
-                .iter()
-                .any(|suggestion| suggestion.description.is_some());
-
-            if exist_description {
+            if self.values_have_description {
                 self.working_details.columns = 1;
                 self.working_details.col_width = painter.screen_width() as usize;
-
-                self.longest_suggestion = self.get_values().iter().fold(0, |prev, suggestion| {
-                    if prev >= suggestion.value.len() {
-                        prev
-                    } else {
-                        suggestion.value.len()
-                    }
-                });
             } else {
-                let max_width = self.get_values().iter().fold(0, |acc, suggestion| {
-                    let str_len = suggestion.value.len() + self.default_details.col_padding;
-                    if str_len > acc {
-                        str_len
-                    } else {
-                        acc
-                    }
-                });
+                let max_width = if self.get_values().is_empty() {
+                    0
+                } else {
+                    self.longest_suggestion + self.default_details.col_padding
+                };
 
                 // If no default width is found, then the total screen width is used to estimate
                 // the column width based on the default number of columns
                 let default_width = if let Some(col_width) = self.default_details.col_width {
                     col_width
                 } else {
-                    let col_width = painter.screen_width() / self.default_details.columns;
+                    let col_width = painter.screen_width() / self.default_details.columns.max(1);
                     col_width as usize
                 };
 
                 // Adjusting the working width of the column based the max line width found
-                // in the menu values
-                if max_width > default_width {
-                    self.working_details.col_width = max_width;
+                // in the menu values, unless the number of columns is fixed
+                let mut col_width = if self.default_details.fixed_columns {
+                    default_width
                 } else {
-                    self.working_details.col_width = default_width;
+                    max_width.max(default_width)
                 };
+                if let Some(min_col_width) = self.default_details.min_col_width {
+                    col_width = col_width.max(min_col_width);
+                }
+                if let Some(max_col_width) = self.default_details.max_col_width {
+                    col_width = col_width.min(max_col_width);
+                }
+                if self.stable_layout {
+                    col_width = col_width.max(self.stable_col_width);
+                    self.stable_col_width = col_width;
+                }
 
                 // The working columns is adjusted based on possible number of columns
                 // that could be fitted in the screen with the calculated column width
-                let possible_cols = painter.screen_width() / self.working_details.col_width as u16;
-                if possible_cols > self.default_details.columns {
+                let possible_cols = painter.screen_width() as usize / col_width.max(1);
+                if self.default_details.fixed_columns {
+                    self.working_details.columns = self.default_details.columns.max(1);
+                    let fitting_width =
+                        painter.screen_width() as usize / self.working_details.columns as usize;
+                    col_width = col_width.min(fitting_width);
+                } else if possible_cols > self.default_details.columns as usize {
                     self.working_details.columns = self.default_details.columns.max(1);
                 } else {
-                    self.working_details.columns = possible_cols;
+                    self.working_details.columns = possible_cols as u16;
                 }
+                self.working_details.col_width = col_width;
             }
 
             match event {
@@ -610,8 +753,12 @@ impl Menu for ColumnarMenu {
     }
 
     /// The buffer gets replaced in the Span location
+    fn selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
     fn replace_in_buffer(&self, editor: &mut Editor) {
-        replace_in_buffer(self.get_value(), editor);
+        replace_in_buffer(self.settings.prepare_insert(self.get_value()), editor);
     }
 
     /// Minimum rows that should be displayed by the menu
@@ -624,6 +771,10 @@ impl Menu for ColumnarMenu {
         &self.values
     }
 
+    fn completion_error(&self) -> Option<&CompletionError> {
+        self.completion_error.as_ref()
+    }
+
     fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
         self.get_rows()
     }
@@ -632,33 +783,48 @@ impl Menu for ColumnarMenu {
         if self.get_values().is_empty() {
             self.no_records_msg(use_ansi_coloring)
         } else {
-            // The skip values represent the number of lines that should be skipped
+            // The skip rows represent the number of lines that should be skipped
             // while printing the menu
-            let skip_values = if self.row_pos >= available_lines {
-                let skip_lines = self.row_pos.saturating_sub(available_lines) + 1;
-                (skip_lines * self.get_cols()) as usize
+            let skip_rows = if self.row_pos >= available_lines {
+                self.row_pos.saturating_sub(available_lines) + 1
             } else {
                 0
             };
+            let last_row = self
+                .get_rows()
+                .min(skip_rows.saturating_add(available_lines));
 
             // It seems that crossterm prefers to have a complete string ready to be printed
             // rather than looping through the values and printing multiple things
             // This reduces the flickering when printing the menu
-            let available_values = (available_lines * self.get_cols()) as usize;
-            self.get_values()
-                .iter()
-                .skip(skip_values)
-                .take(available_values)
-                .enumerate()
-                .map(|(index, suggestion)| {
-                    // Correcting the enumerate index based on the number of skipped values
-                    let index = index + skip_values;
-                    let column = index as u16 % self.get_cols();
-                    let empty_space = self.get_width().saturating_sub(suggestion.value.len());
-
-                    self.create_string(suggestion, index, column, empty_space, use_ansi_coloring)
-                })
-                .collect()
+            let mut menu = String::new();
+            for row in skip_rows..last_row {
+                for column in 0..self.get_cols() {
+                    let index = self.index_at(row, column);
+                    match self.get_values().get(index) {
+                        Some(suggestion) => {
+                            let suggestion = self.fit_in_column(suggestion);
+                            let empty_space =
+                                self.get_width().saturating_sub(suggestion.value.width());
+                            menu.push_str(&self.create_string(
+                                &suggestion,
+                                index,
+                                column,
+                                empty_space,
+                                use_ansi_coloring,
+                            ));
+                        }
+                        None => {
+                            // The rest of the row is empty
+                            if row + 1 < last_row {
+                                menu.push_str("\r\n");
+                            }
+                            break;
+                        }
+                    }
+                }
+            }
+            menu
         }
     }
 }
@@ -750,6 +916,7 @@ mod tests {
             extra: None,
             span: Span { start: 0, end: pos },
             append_whitespace: false,
+            link: None,
         }
     }
 
@@ -773,4 +940,208 @@ mod tests {
             "cursor should be at the end after completion"
         );
     }
+
+    #[test]
+    fn test_values_are_measured_when_updated() {
+        let mut completer = FakeCompleter::new(&["ls", "cargo", "cd"]);
+        let mut menu = ColumnarMenu::default().with_name("testmenu");
+        let mut editor = Editor::default();
+
+        menu.update_values(&mut editor, &mut completer);
+        assert_eq!(menu.longest_suggestion, 5);
+        assert!(!menu.values_have_description);
+
+        let mut completer = FakeCompleter::new(&[]);
+        menu.update_values(&mut editor, &mut completer);
+        assert_eq!(menu.longest_suggestion, 0);
+    }
+
+    fn painter_with_width(width: u16) -> Painter {
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(width, 24);
+        painter
+    }
+
+    #[test]
+    fn values_fill_the_columns_in_column_major_order() {
+        let mut completer = FakeCompleter::new(&["a", "b", "c", "d", "e"]);
+        let mut menu = ColumnarMenu::default()
+            .with_columns(3)
+            .with_column_width(Some(4))
+            .with_fill_direction(FillDirection::ColumnMajor);
+        let mut editor = Editor::default();
+        let painter = painter_with_width(40);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.menu_string(10, false), ">A  c   e   \r\nb   d   ");
+
+        menu.menu_event(MenuEvent::NextElement);
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.get_value().unwrap().value, "b");
+        menu.menu_event(MenuEvent::MoveRight);
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.get_value().unwrap().value, "d");
+        menu.menu_event(MenuEvent::MoveRight);
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.get_value().unwrap().value, "b");
+    }
+
+    #[test]
+    fn fixed_columns_truncate_the_values() {
+        let mut completer = FakeCompleter::new(&["short", "a-rather-long-value"]);
+        let mut menu = ColumnarMenu::default()
+            .with_columns(2)
+            .with_fixed_columns(true);
+        let mut editor = Editor::default();
+        let painter = painter_with_width(20);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.get_cols(), 2);
+        assert_eq!(menu.get_width(), 10);
+        assert_eq!(menu.menu_string(10, false), ">SHORT    a-rathe…  \r\n");
+    }
+
+    #[test]
+    fn the_columns_dont_narrow_in_a_stable_layout() {
+        let mut menu = ColumnarMenu::default()
+            .with_column_width(Some(4))
+            .with_max_column_width(Some(12))
+            .with_stable_layout(true);
+        let mut editor = Editor::default();
+        let painter = painter_with_width(80);
+
+        let mut completer = FakeCompleter::new(&["cargo", "cargo-clippy"]);
+        menu.update_values(&mut editor, &mut completer);
+        menu.menu_event(MenuEvent::Activate(true));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.get_width(), 12);
+
+        let mut completer = FakeCompleter::new(&["cargo"]);
+        menu.update_values(&mut editor, &mut completer);
+        menu.menu_event(MenuEvent::Edit(true));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.get_width(), 12);
+
+        menu.menu_event(MenuEvent::Activate(true));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.get_width(), 7);
+    }
+
+    #[test]
+    fn the_selected_value_is_transformed_before_it_is_inserted() {
+        let mut completer = FakeCompleter::new(&["my file.txt"]);
+        let mut menu = ColumnarMenu::default().with_pre_insert(|suggestion: Suggestion| {
+            let value = format!("'{}'", suggestion.value);
+            Suggestion {
+                value,
+                ..suggestion
+            }
+        });
+        let mut editor = Editor::default();
+        editor.set_buffer("my".to_string(), UndoBehavior::CreateUndoPoint);
+
+        menu.update_values(&mut editor, &mut completer);
+        menu.replace_in_buffer(&mut editor);
+        assert_eq!(editor.get_buffer(), "'my file.txt'");
+        assert_eq!(menu.selected_value().unwrap().value, "my file.txt");
+    }
+
+    struct FailingCompleter;
+
+    impl Completer for FailingCompleter {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            self.try_complete_with_base_ranges(line, pos)
+                .unwrap_or_default()
+                .0
+        }
+
+        fn try_complete_with_base_ranges(
+            &mut self,
+            _line: &str,
+            _pos: usize,
+        ) -> Result<(Vec<Suggestion>, Vec<std::ops::Range<usize>>), CompletionError> {
+            Err(CompletionError::Io(
+                "permission denied\nos error 13".to_string(),
+            ))
+        }
+    }
+
+    #[test]
+    fn the_menu_shows_why_the_completer_failed() {
+        let mut menu = ColumnarMenu::default();
+        let mut editor = Editor::default();
+
+        menu.update_values(&mut editor, &mut FailingCompleter);
+        assert!(menu.get_values().is_empty());
+        assert_eq!(
+            menu.completion_error(),
+            Some(&CompletionError::Io(
+                "permission denied\nos error 13".to_string()
+            ))
+        );
+        assert_eq!(
+            menu.menu_string(10, false),
+            "completion failed: IO error: permission denied"
+        );
+
+        menu.update_values(&mut editor, &mut FakeCompleter::new(&["ls"]));
+        assert_eq!(menu.completion_error(), None);
+    }
+
+    struct PanickingCompleter;
+
+    impl Completer for PanickingCompleter {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            panic!("no completions for {}", "ls")
+        }
+    }
+
+    #[test]
+    fn a_panicking_completer_fails_the_completion() {
+        let mut menu = ColumnarMenu::default();
+        let mut editor = Editor::default();
+
+        menu.update_values(&mut editor, &mut PanickingCompleter);
+        assert!(menu.get_values().is_empty());
+        assert_eq!(
+            menu.completion_error(),
+            Some(&CompletionError::Panicked(
+                "no completions for ls".to_string()
+            ))
+        );
+        assert_eq!(
+            menu.menu_string(10, false),
+            "completion failed: panicked: no completions for ls"
+        );
+    }
+
+    struct LinkCompleter;
+
+    impl Completer for LinkCompleter {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            vec![Suggestion {
+                value: "ls".to_string(),
+                description: Some("list".to_string()),
+                span: Span { start: 0, end: pos },
+                link: Some("https://man.example/ls".to_string()),
+                ..Suggestion::default()
+            }]
+        }
+    }
+
+    #[test]
+    fn the_description_links_to_the_target_of_the_value() {
+        let mut menu = ColumnarMenu::default();
+        let mut editor = Editor::default();
+        let painter = painter_with_width(40);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut LinkCompleter, &painter);
+        assert!(menu
+            .menu_string(10, true)
+            .contains("\x1b]8;;https://man.example/ls\x1b\\list\x1b]8;;\x1b\\"));
+        assert!(!menu.menu_string(10, false).contains('\x1b'));
+    }
 }
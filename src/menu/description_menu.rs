@@ -1,8 +1,10 @@
 use {
     super::MenuSettings,
     crate::{
-        menu_functions::{completer_input, replace_in_buffer},
-        Completer, Editor, Menu, MenuBuilder, MenuEvent, Painter, Suggestion,
+        menu_functions::{
+            completer_input, completion_error_msg, hyperlink, replace_in_buffer, try_complete,
+        },
+        Completer, CompletionError, Editor, Menu, MenuBuilder, MenuEvent, Painter, Suggestion,
     },
     nu_ansi_term::ansi::RESET,
 };
@@ -63,6 +65,8 @@ pub struct DescriptionMenu {
     working_details: WorkingDetails,
     /// Menu cached values
     values: Vec<Suggestion>,
+    /// Why the completer failed to provide the values
+    completion_error: Option<CompletionError>,
     /// column position of the cursor. Starts from 0
     col_pos: u16,
     /// row position in the menu. Starts from 0
@@ -93,6 +97,7 @@ impl Default for DescriptionMenu {
             min_rows: 3,
             working_details: WorkingDetails::default(),
             values: Vec::new(),
+            completion_error: None,
             col_pos: 0,
             row_pos: 0,
             event: None,
@@ -237,6 +242,13 @@ impl DescriptionMenu {
     }
 
     fn no_records_msg(&self, use_ansi_coloring: bool) -> String {
+        if let Some(error) = &self.completion_error {
+            return completion_error_msg(
+                error,
+                &self.settings.color.error_style,
+                use_ansi_coloring,
+            );
+        }
         let msg = "TYPE TO START SEARCH";
         if use_ansi_coloring {
             format!(
@@ -345,10 +357,11 @@ impl DescriptionMenu {
             .join("\r\n");
 
         if use_ansi_coloring && !description.is_empty() {
+            let link = self.get_value().and_then(|suggestion| suggestion.link);
             format!(
                 "{}{}{}",
                 self.settings.color.description_style.prefix(),
-                description,
+                hyperlink(&description, link.as_deref(), true),
                 RESET,
             )
         } else {
@@ -448,7 +461,16 @@ impl Menu for DescriptionMenu {
             self.input.as_deref(),
             self.settings.only_buffer_difference,
         );
-        self.values = completer.complete(&input, pos);
+        match try_complete(completer, &input, pos) {
+            Ok((values, _)) => {
+                self.values = values;
+                self.completion_error = None;
+            }
+            Err(error) => {
+                self.values = Vec::new();
+                self.completion_error = Some(error);
+            }
+        }
 
         self.reset_position();
     }
@@ -582,6 +604,10 @@ impl Menu for DescriptionMenu {
     }
 
     /// The buffer gets replaced in the Span location
+    fn selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
     fn replace_in_buffer(&self, editor: &mut Editor) {
         if let Some(mut suggestion) = self.get_value() {
             if let Some(example_index) = self.example_index {
@@ -591,7 +617,7 @@ impl Menu for DescriptionMenu {
                     .expect("the example index is always checked");
                 suggestion.value = example.clone();
             }
-            replace_in_buffer(Some(suggestion), editor);
+            replace_in_buffer(self.settings.prepare_insert(Some(suggestion)), editor);
         }
     }
 
@@ -605,6 +631,10 @@ impl Menu for DescriptionMenu {
         &self.values
     }
 
+    fn completion_error(&self) -> Option<&CompletionError> {
+        self.completion_error.as_ref()
+    }
+
     fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
         let example_lines = self
             .examples
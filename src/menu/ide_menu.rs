@@ -1,9 +1,14 @@
 use super::{Menu, MenuBuilder, MenuEvent, MenuSettings};
+#[cfg(feature = "image_preview")]
+use crate::menu::{GraphicsProtocol, Previewer, RenderedPreview};
 use crate::{
     core_editor::Editor,
-    menu_functions::{can_partially_complete, completer_input, replace_in_buffer},
+    menu_functions::{
+        can_partially_complete, completer_input, completion_error_msg, hyperlink,
+        replace_in_buffer, try_complete,
+    },
     painting::Painter,
-    Completer, Suggestion,
+    Completer, CompletionError, Suggestion,
 };
 use itertools::{
     EitherOrBoth::{Both, Left, Right},
@@ -85,6 +90,10 @@ struct DefaultIdeMenuDetails {
     ///      str split
     /// ```
     pub correct_cursor_pos: bool,
+    /// Whether a scrollbar shows the position of the visible values in the list
+    pub scrollbar: bool,
+    /// Whether the menu is painted above the cursor when there is no room below it
+    pub flip_above: bool,
 }
 
 impl Default for DefaultIdeMenuDetails {
@@ -102,6 +111,8 @@ impl Default for DefaultIdeMenuDetails {
             max_description_height: 10,
             description_offset: 1,
             correct_cursor_pos: false,
+            scrollbar: false,
+            flip_above: false,
         }
     }
 }
@@ -127,6 +138,8 @@ struct IdeMenuDetails {
     pub description_offset: u16,
     /// The shortest of the strings, which the suggestions are based on
     pub shortest_base_string: String,
+    /// Width of the token before the cursor, which the suggestions replace
+    pub token_width: u16,
 }
 
 /// Menu to present suggestions like similar to Ide completion menus
@@ -142,6 +155,8 @@ pub struct IdeMenu {
     working_details: IdeMenuDetails,
     /// Menu cached values
     values: Vec<Suggestion>,
+    /// Why the completer failed to provide the values
+    completion_error: Option<CompletionError>,
     /// Selected value. Starts at 0
     selected: u16,
     /// Event sent to the menu
@@ -150,6 +165,15 @@ pub struct IdeMenu {
     longest_suggestion: usize,
     /// String collected after the menu is activated
     input: Option<String>,
+    /// Provides the images shown in place of the descriptions
+    #[cfg(feature = "image_preview")]
+    previewer: Option<Box<dyn Previewer>>,
+    /// The protocol the images are painted with
+    #[cfg(feature = "image_preview")]
+    graphics_protocol: Option<GraphicsProtocol>,
+    /// The preview of the selected value, with the index of the value it was made for
+    #[cfg(feature = "image_preview")]
+    preview: Option<(usize, Option<RenderedPreview>)>,
 }
 
 impl Default for IdeMenu {
@@ -160,10 +184,17 @@ impl Default for IdeMenu {
             default_details: DefaultIdeMenuDetails::default(),
             working_details: IdeMenuDetails::default(),
             values: Vec::new(),
+            completion_error: None,
             selected: 0,
             event: None,
             longest_suggestion: 0,
             input: None,
+            #[cfg(feature = "image_preview")]
+            previewer: None,
+            #[cfg(feature = "image_preview")]
+            graphics_protocol: None,
+            #[cfg(feature = "image_preview")]
+            preview: None,
         }
     }
 }
@@ -282,6 +313,43 @@ impl IdeMenu {
         self.default_details.correct_cursor_pos = correct_cursor_pos;
         self
     }
+
+    /// Menu builder with a scrollbar, shown in the right border if there is one
+    #[must_use]
+    pub fn with_scrollbar(mut self, scrollbar: bool) -> Self {
+        self.default_details.scrollbar = scrollbar;
+        self
+    }
+
+    /// Menu builder which paints the menu above the cursor, over the lines of the buffer,
+    /// when there is no room for it below the cursor
+    #[must_use]
+    pub fn with_flip_above(mut self, flip_above: bool) -> Self {
+        self.default_details.flip_above = flip_above;
+        self
+    }
+
+    /// Menu builder with a previewer, whose images are shown in place of the description of
+    /// the selected value
+    ///
+    /// The images are painted with the protocol found by [`GraphicsProtocol::detect`]. A
+    /// [`crate::Sanitizer`] removes them, leaving their room empty.
+    #[cfg(feature = "image_preview")]
+    #[must_use]
+    pub fn with_previewer(mut self, previewer: Box<dyn Previewer>) -> Self {
+        self.previewer = Some(previewer);
+        self.graphics_protocol = GraphicsProtocol::detect();
+        self
+    }
+
+    /// Menu builder with the protocol the previews are painted with, `None` to show their
+    /// fallback texts, replacing the detected one
+    #[cfg(feature = "image_preview")]
+    #[must_use]
+    pub fn with_graphics_protocol(mut self, graphics_protocol: Option<GraphicsProtocol>) -> Self {
+        self.graphics_protocol = graphics_protocol;
+        self
+    }
 }
 
 // Menu functionality
@@ -325,8 +393,7 @@ impl IdeMenu {
         }
 
         let description_height = self
-            .get_value()
-            .and_then(|value| value.description)
+            .selected_description()
             .map(|description| {
                 self.description_dims(
                     description,
@@ -347,11 +414,50 @@ impl IdeMenu {
         self.working_details.menu_width
     }
 
+    /// Width of the scrollbar column, which takes the place of the right border if there is one
+    fn scrollbar_width(&self) -> usize {
+        usize::from(self.default_details.scrollbar && self.default_details.border.is_none())
+    }
+
+    /// Puts the scrollbar at the end of the value lines, `skip` values being above them
+    fn add_scrollbar(&self, strings: &mut [String], skip: usize) {
+        let total = self.get_values().len();
+        let visible = strings.len();
+        if !self.default_details.scrollbar || visible == 0 || total <= visible {
+            return;
+        }
+
+        let thumb_len = (visible * visible / total).max(1);
+        let thumb_start = skip * (visible - thumb_len) / (total - visible);
+        let track = self
+            .default_details
+            .border
+            .as_ref()
+            .map_or('│', |border| border.vertical);
+        for (row, string) in strings.iter_mut().enumerate() {
+            if self.default_details.border.is_some() {
+                string.pop();
+            }
+            if (thumb_start..thumb_start + thumb_len).contains(&row) {
+                string.push('┃');
+            } else {
+                string.push(track);
+            }
+        }
+    }
+
     fn reset_position(&mut self) {
         self.selected = 0;
     }
 
     fn no_records_msg(&self, use_ansi_coloring: bool) -> String {
+        if let Some(error) = &self.completion_error {
+            return completion_error_msg(
+                error,
+                &self.settings.color.error_style,
+                use_ansi_coloring,
+            );
+        }
         let msg = "NO RECORDS FOUND";
         if use_ansi_coloring {
             format!(
@@ -365,15 +471,100 @@ impl IdeMenu {
         }
     }
 
+    // The description of the selected value, or the fallback text or the room of its preview
+    fn selected_description(&self) -> Option<String> {
+        #[cfg(feature = "image_preview")]
+        match self.selected_preview() {
+            Some(RenderedPreview::Image { .. }) => return Some(String::new()),
+            Some(RenderedPreview::Text(text)) if !text.is_empty() => return Some(text.clone()),
+            _ => {}
+        }
+        self.get_value()
+            .and_then(|value| value.description)
+            .filter(|description| !description.is_empty())
+    }
+
+    #[cfg(feature = "image_preview")]
+    fn selected_preview(&self) -> Option<&RenderedPreview> {
+        self.preview
+            .as_ref()
+            .filter(|(index, _)| *index == self.index())
+            .and_then(|(_, preview)| preview.as_ref())
+    }
+
+    // Paints the image of the preview into the room left for it in the description box, from
+    // the end of the last line, so that the lines painted after it don't cover it
+    #[cfg(feature = "image_preview")]
+    fn paint_preview(&self, strings: &mut [String], description_height: u16) {
+        let (sequence, columns, rows) = match self.selected_preview() {
+            Some(RenderedPreview::Image {
+                sequence,
+                columns,
+                rows,
+            }) => (sequence, *columns, *rows),
+            _ => return,
+        };
+        let border = u16::from(self.default_details.border.is_some());
+        let fits = self.working_details.description_width >= columns + 2 * border
+            && description_height >= rows + 2 * border;
+        let last = strings.len().saturating_sub(1);
+        if !fits || last < border as usize {
+            return;
+        }
+        let mut column = self.working_details.space_left + border;
+        if self.working_details.description_is_right {
+            column +=
+                self.working_details.completion_width + self.working_details.description_offset;
+        }
+        let up = last - border as usize;
+        let line = &mut strings[last];
+        line.push('\r');
+        if up > 0 {
+            line.push_str(&format!("\x1b[{up}A"));
+        }
+        if column > 0 {
+            line.push_str(&format!("\x1b[{column}C"));
+        }
+        line.push_str(sequence);
+    }
+
+    // Asks the previewer for the preview of the selected value, unless it is already known
+    #[cfg(feature = "image_preview")]
+    fn update_preview(&mut self) {
+        let index = self.index();
+        if matches!(self.preview, Some((preview_index, _)) if preview_index == index) {
+            return;
+        }
+        let preview = match (&mut self.previewer, self.values.get(index)) {
+            (Some(previewer), Some(value)) => previewer
+                .preview(value)
+                .map(|preview| preview.render(self.graphics_protocol)),
+            _ => None,
+        };
+        self.preview = Some((index, preview));
+    }
+
+    // The lines of the description, or blank lines leaving room for the image of the preview
+    #[cfg_attr(not(feature = "image_preview"), allow(unused_variables))]
+    fn description_lines(&self, description: &str, width: u16, height: u16) -> Vec<String> {
+        #[cfg(feature = "image_preview")]
+        if let Some(RenderedPreview::Image { columns, rows, .. }) = self.selected_preview() {
+            let line = " ".repeat((*columns).min(width) as usize);
+            return vec![line; (*rows).min(height) as usize];
+        }
+        split_string(description, width as usize)
+    }
+
     fn create_description(
         &self,
         description: String,
+        link: Option<&str>,
         use_ansi_coloring: bool,
         available_width: u16,
         available_height: u16,
         min_width: u16,
     ) -> Vec<String> {
-        if description.is_empty() || available_width == 0 || available_height == 0 {
+        if available_width == 0 || available_height == 0 {
             return Vec::new();
         }
 
@@ -386,7 +577,11 @@ impl IdeMenu {
         let content_width = available_width.saturating_sub(border_width);
         let content_height = available_height.saturating_sub(border_width);
 
-        let mut description_lines = split_string(&description, content_width as usize);
+        let mut description_lines =
+            self.description_lines(&description, content_width, content_height);
+        if description_lines.is_empty() {
+            return Vec::new();
+        }
 
         if description_lines.len() > content_height as usize {
             description_lines.truncate(content_height as usize);
@@ -413,7 +608,7 @@ impl IdeMenu {
                         "{}{}{}{}{}{}",
                         border.vertical,
                         self.settings.color.description_style.prefix(),
-                        line,
+                        hyperlink(line, link, true),
                         padding,
                         RESET,
                         border.vertical
@@ -442,7 +637,7 @@ impl IdeMenu {
                     *line = format!(
                         "{}{}{}{}",
                         self.settings.color.description_style.prefix(),
-                        line,
+                        hyperlink(line, link, true),
                         padding,
                         RESET
                     );
@@ -466,7 +661,8 @@ impl IdeMenu {
         // we will calculate the uncapped height, the real height
         // will be capped by the available lines
 
-        let lines = self.create_description(description, false, max_width, max_height, min_width);
+        let lines =
+            self.create_description(description, None, false, max_width, max_height, min_width);
         let height = lines.len() as u16;
         let string = lines.first().cloned().unwrap_or_default();
         let width = string.width() as u16;
@@ -493,6 +689,8 @@ impl IdeMenu {
             .map(|border| border.vertical)
             .unwrap_or_default();
 
+        let border_width = border_width + self.scrollbar_width();
+
         let padding_right = (self.working_details.completion_width as usize)
             .saturating_sub(suggestion.value.chars().count() + border_width + padding);
 
@@ -631,14 +829,44 @@ impl Menu for IdeMenu {
             self.input.as_deref(),
             self.settings.only_buffer_difference,
         );
-        let (values, base_ranges) = completer.complete_with_base_ranges(&input, pos);
+        let (values, base_ranges) = match try_complete(completer, &input, pos) {
+            Ok(completed) => {
+                self.completion_error = None;
+                completed
+            }
+            Err(error) => {
+                self.completion_error = Some(error);
+                Default::default()
+            }
+        };
 
         self.values = values;
+        #[cfg(feature = "image_preview")]
+        {
+            self.preview = None;
+        }
+        // Measured once here, so that moving through thousands of values stays cheap
+        self.longest_suggestion = self
+            .values
+            .iter()
+            .map(|suggestion| suggestion.value.len())
+            .max()
+            .unwrap_or_default();
         self.working_details.shortest_base_string = base_ranges
             .iter()
             .map(|range| editor.get_buffer()[range.clone()].to_string())
             .min_by_key(|s| s.len())
             .unwrap_or_default();
+        let cursor = editor.insertion_point();
+        self.working_details.token_width = self
+            .values
+            .first()
+            .and_then(|value| {
+                editor
+                    .get_buffer()
+                    .get(value.span.start.min(cursor)..cursor)
+            })
+            .map_or(0, |token| token.width().min(u16::MAX as usize) as u16);
 
         self.reset_position();
     }
@@ -684,42 +912,23 @@ impl Menu for IdeMenu {
                 | MenuEvent::NextPage => {}
             }
 
-            self.longest_suggestion = self.get_values().iter().fold(0, |prev, suggestion| {
-                if prev >= suggestion.value.len() {
-                    prev
-                } else {
-                    suggestion.value.len()
-                }
-            });
-
             let terminal_width = painter.screen_width();
             let mut cursor_pos = self.working_details.cursor_col;
 
+            // The menu is anchored at the start of the token that is completed
             if self.default_details.correct_cursor_pos {
-                let base_string = &self.working_details.shortest_base_string;
-
-                cursor_pos = cursor_pos.saturating_sub(base_string.width() as u16);
+                cursor_pos = cursor_pos.saturating_sub(self.working_details.token_width);
             }
 
             let border_width = if self.default_details.border.is_some() {
                 2
             } else {
                 0
-            };
+            } + self.scrollbar_width() as u16;
 
-            let description = self
-                .get_value()
-                .map(|v| {
-                    if let Some(v) = v.description {
-                        if v.is_empty() {
-                            return None;
-                        } else {
-                            return Some(v);
-                        }
-                    }
-                    None
-                })
-                .unwrap_or_default();
+            #[cfg(feature = "image_preview")]
+            self.update_preview();
+            let description = self.selected_description();
 
             let mut min_description_width = if description.is_some() {
                 self.default_details.min_description_width
@@ -804,8 +1013,12 @@ impl Menu for IdeMenu {
     }
 
     /// The buffer gets replaced in the Span location
+    fn selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
     fn replace_in_buffer(&self, editor: &mut Editor) {
-        replace_in_buffer(self.get_value(), editor);
+        replace_in_buffer(self.settings.prepare_insert(self.get_value()), editor);
     }
 
     /// Minimum rows that should be displayed by the menu
@@ -817,6 +1030,10 @@ impl Menu for IdeMenu {
         &self.values
     }
 
+    fn completion_error(&self) -> Option<&CompletionError> {
+        self.completion_error.as_ref()
+    }
+
     fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
         self.get_rows()
     }
@@ -847,7 +1064,9 @@ impl Menu for IdeMenu {
             let available_values = available_lines.saturating_sub(border_width) as usize;
 
             let max_padding = self.working_details.completion_width.saturating_sub(
-                self.longest_suggestion.min(u16::MAX as usize) as u16 + border_width,
+                self.longest_suggestion.min(u16::MAX as usize) as u16
+                    + border_width
+                    + self.scrollbar_width() as u16,
             ) / 2;
 
             let corrected_padding = self.default_details.padding.min(max_padding) as usize;
@@ -870,6 +1089,7 @@ impl Menu for IdeMenu {
                     )
                 })
                 .collect::<Vec<String>>();
+            self.add_scrollbar(&mut strings, skip_values);
 
             // Add top and bottom border
             if let Some(border) = &self.default_details.border {
@@ -895,12 +1115,14 @@ impl Menu for IdeMenu {
 
             let description_height =
                 available_lines.min(self.default_details.max_description_height);
+            let value = self.get_value();
+            let link = value.as_ref().and_then(|value| value.link.as_deref());
             let description_lines = self
-                .get_value()
-                .and_then(|value| value.clone().description)
+                .selected_description()
                 .map(|description| {
                     self.create_description(
                         description,
+                        link,
                         use_ansi_coloring,
                         self.working_details.description_width,
                         description_height,
@@ -978,6 +1200,11 @@ impl Menu for IdeMenu {
                 }
             }
 
+            #[cfg(feature = "image_preview")]
+            if use_ansi_coloring {
+                self.paint_preview(&mut strings, description_height);
+            }
+
             strings.join("\r\n")
         }
     }
@@ -985,6 +1212,10 @@ impl Menu for IdeMenu {
     fn set_cursor_pos(&mut self, pos: (u16, u16)) {
         self.working_details.cursor_col = pos.0;
     }
+
+    fn flips_above(&self) -> bool {
+        self.default_details.flip_above
+    }
 }
 
 /// Split the input into strings that are at most `max_length` (in columns, not in chars) long
@@ -1054,10 +1285,7 @@ fn truncate_string_list(list: &mut [String], truncation_chars: &str) {
         let mut new_line = String::new();
         for grapheme in chars.into_iter().rev() {
             if to_replace > 0 {
-                new_line.insert_str(
-                    0,
-                    &truncation_chars[truncation_len - to_replace].to_string(),
-                );
+                new_line.insert(0, truncation_chars[truncation_len - to_replace]);
                 to_replace -= 1;
             } else {
                 new_line.insert_str(0, grapheme);
@@ -1381,6 +1609,7 @@ mod tests {
             extra: None,
             span: Span { start: 0, end: pos },
             append_whitespace: false,
+            link: None,
         }
     }
 
@@ -1404,4 +1633,103 @@ mod tests {
             "cursor should be at the end after completion"
         );
     }
+
+    fn painter_with_width(width: u16) -> Painter {
+        let mut painter = Painter::new(std::io::BufWriter::new(std::io::stderr()));
+        painter.handle_resize(width, 24);
+        painter
+    }
+
+    #[test]
+    fn the_scrollbar_shows_the_visible_part_of_the_list() {
+        let mut completer = FakeCompleter::new(&["a", "b", "c", "d", "e", "f"]);
+        let mut menu = IdeMenu::default()
+            .with_default_border()
+            .with_scrollbar(true);
+        let mut editor = Editor::default();
+        let painter = painter_with_width(40);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        let lines = menu.menu_string(5, false);
+        let scrollbar: Vec<char> = lines
+            .split("\r\n")
+            .map(|line| line.chars().last().unwrap())
+            .collect();
+        assert_eq!(scrollbar, ['╮', '┃', '│', '│', '╯']);
+
+        for _ in 0..5 {
+            menu.menu_event(MenuEvent::NextElement);
+            menu.update_working_details(&mut editor, &mut completer, &painter);
+        }
+        let lines = menu.menu_string(5, false);
+        let scrollbar: Vec<char> = lines
+            .split("\r\n")
+            .map(|line| line.chars().last().unwrap())
+            .collect();
+        assert_eq!(scrollbar, ['╮', '│', '│', '┃', '╯']);
+    }
+
+    #[test]
+    fn the_menu_is_anchored_at_the_start_of_the_token() {
+        let mut completer = FakeCompleter::new(&["build.rs", "build-all.sh"]);
+        let mut menu = IdeMenu::default().with_correct_cursor_pos(true);
+        let mut editor = Editor::default();
+        editor.set_buffer("cargo bu".to_string(), UndoBehavior::CreateUndoPoint);
+        let painter = painter_with_width(40);
+
+        // The fake completer replaces the whole buffer
+        menu.set_cursor_pos((8, 0));
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.working_details.space_left, 0);
+
+        let mut completer = TokenCompleter;
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert_eq!(menu.working_details.space_left, 6);
+    }
+
+    struct TokenCompleter;
+
+    impl Completer for TokenCompleter {
+        fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+            vec![Suggestion {
+                span: Span { start: 6, end: pos },
+                ..fake_suggestion("build", pos)
+            }]
+        }
+    }
+
+    #[cfg(feature = "image_preview")]
+    #[test]
+    fn the_preview_replaces_the_description() {
+        use crate::menu::{Preview, PreviewImage};
+
+        let previewer = |suggestion: &Suggestion| {
+            let image = PreviewImage::Sixel(format!("\x1bPq{}\x1b\\", suggestion.value));
+            Some(Preview::new(image, 4, 2).with_fallback("no image"))
+        };
+        let mut completer = FakeCompleter::new(&["a.png", "b.png"]);
+        let mut menu = IdeMenu::default()
+            .with_default_border()
+            .with_previewer(Box::new(previewer))
+            .with_graphics_protocol(Some(GraphicsProtocol::Sixel));
+        let mut editor = Editor::default();
+        let painter = painter_with_width(40);
+
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert!(menu.menu_string(10, true).contains("\x1bPqa.png\x1b\\"));
+
+        menu.menu_event(MenuEvent::NextElement);
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        assert!(menu.menu_string(10, true).contains("\x1bPqb.png\x1b\\"));
+
+        let mut menu = menu.with_graphics_protocol(None);
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut completer, &painter);
+        let lines = menu.menu_string(10, false);
+        assert!(lines.contains("no image") && !lines.contains("\x1bP"));
+    }
 }
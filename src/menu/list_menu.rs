@@ -2,9 +2,9 @@ use {
     super::{menu_functions::parse_selection_char, Menu, MenuBuilder, MenuEvent, MenuSettings},
     crate::{
         core_editor::Editor,
-        menu_functions::{completer_input, replace_in_buffer},
+        menu_functions::{completer_input, completion_error_msg, replace_in_buffer, try_complete},
         painting::{estimate_single_line_wraps, Painter},
-        Completer, Suggestion,
+        Completer, CompletionError, Suggestion,
     },
     nu_ansi_term::ansi::RESET,
     std::{fmt::Write, iter::Sum},
@@ -67,6 +67,8 @@ pub struct ListMenu {
     event: Option<MenuEvent>,
     /// String collected after the menu is activated
     input: Option<String>,
+    /// Why the completer failed the last time the values were updated
+    completion_error: Option<CompletionError>,
 }
 
 impl Default for ListMenu {
@@ -87,6 +89,7 @@ impl Default for ListMenu {
             pages: Vec::new(),
             event: None,
             input: None,
+            completion_error: None,
         }
     }
 }
@@ -362,6 +365,7 @@ impl Menu for ListMenu {
             self.reset_position();
         }
 
+        self.completion_error = None;
         self.values = if parsed.remainder.is_empty() {
             self.query_size = Some(completer.total_completions(parsed.remainder, pos));
 
@@ -375,7 +379,13 @@ impl Menu for ListMenu {
             completer.partial_complete(&input, pos, skip, take)
         } else {
             self.query_size = None;
-            completer.complete(&input, pos)
+            match try_complete(completer, &input, pos) {
+                Ok((values, _)) => values,
+                Err(error) => {
+                    self.completion_error = Some(error);
+                    Vec::new()
+                }
+            }
         }
     }
 
@@ -407,8 +417,12 @@ impl Menu for ListMenu {
     }
 
     /// The buffer gets cleared with the actual value
+    fn selected_value(&self) -> Option<Suggestion> {
+        self.get_value()
+    }
+
     fn replace_in_buffer(&self, editor: &mut Editor) {
-        replace_in_buffer(self.get_value(), editor);
+        replace_in_buffer(self.settings.prepare_insert(self.get_value()), editor);
     }
 
     fn update_working_details(
@@ -534,6 +548,13 @@ impl Menu for ListMenu {
 
     /// Creates the menu representation as a string which will be painted by the painter
     fn menu_string(&self, _available_lines: u16, use_ansi_coloring: bool) -> String {
+        if let Some(error) = &self.completion_error {
+            return completion_error_msg(
+                error,
+                &self.settings.color.error_style,
+                use_ansi_coloring,
+            );
+        }
         let values_before_page = self.pages.iter().take(self.page).sum::<Page>().size;
         match self.pages.get(self.page) {
             Some(page) => {
@@ -589,6 +610,10 @@ impl Menu for ListMenu {
     fn min_rows(&self) -> u16 {
         self.max_lines + 1
     }
+
+    fn completion_error(&self) -> Option<&CompletionError> {
+        self.completion_error.as_ref()
+    }
 }
 
 fn number_of_lines(entry: &str, max_lines: usize, terminal_columns: u16) -> u16 {
@@ -630,6 +655,33 @@ fn count_digits(mut n: usize) -> u16 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::UndoBehavior;
+
+    struct PanickingCompleter;
+
+    impl Completer for PanickingCompleter {
+        fn complete(&mut self, _line: &str, _pos: usize) -> Vec<Suggestion> {
+            panic!("no history")
+        }
+    }
+
+    #[test]
+    fn the_menu_shows_why_the_completer_failed() {
+        let mut menu = ListMenu::default().with_only_buffer_difference(false);
+        let mut editor = Editor::default();
+        editor.set_buffer("ls".to_string(), UndoBehavior::CreateUndoPoint);
+
+        menu.update_values(&mut editor, &mut PanickingCompleter);
+        assert!(menu.get_values().is_empty());
+        assert_eq!(
+            menu.completion_error(),
+            Some(&CompletionError::Panicked("no history".to_string()))
+        );
+        assert_eq!(
+            menu.menu_string(10, false),
+            "completion failed: panicked: no history"
+        );
+    }
 
     #[test]
     fn number_of_lines_test() {
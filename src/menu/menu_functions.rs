@@ -1,5 +1,10 @@
 //! Collection of common functions that can be used to create menus
-use crate::{Editor, Suggestion, UndoBehavior};
+use crate::{Completer, CompletionError, Editor, Suggestion, UndoBehavior};
+use nu_ansi_term::{ansi::RESET, Style};
+use std::{
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+};
 
 /// Index result obtained from parsing a string with an index marker
 /// For example, the next string:
@@ -51,7 +56,7 @@ pub enum ParseAction {
 /// )
 ///
 /// ```
-pub fn parse_selection_char(buffer: &str, marker: char) -> ParseResult {
+pub fn parse_selection_char(buffer: &str, marker: char) -> ParseResult<'_> {
     if buffer.is_empty() {
         return ParseResult {
             remainder: buffer,
@@ -245,6 +250,52 @@ pub fn string_difference<'a>(new_string: &'a str, old_string: &str) -> (usize, &
     }
 }
 
+/// The row shown by a menu in place of the values when the completer failed
+pub fn completion_error_msg(
+    error: &CompletionError,
+    style: &Style,
+    use_ansi_coloring: bool,
+) -> String {
+    let msg = format!("completion failed: {error}");
+    // Only the first line, the menu reserves a single row for it
+    let msg = msg.lines().next().unwrap_or_default();
+    if use_ansi_coloring {
+        format!("{}{}{}", style.prefix(), msg, RESET)
+    } else {
+        msg.to_string()
+    }
+}
+
+/// [`Completer::try_complete_with_base_ranges`], failing with [`CompletionError::Panicked`]
+/// instead of unwinding through the line editor when the completer panics
+pub(crate) fn try_complete(
+    completer: &mut dyn Completer,
+    line: &str,
+    pos: usize,
+) -> Result<(Vec<Suggestion>, Vec<Range<usize>>), CompletionError> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        completer.try_complete_with_base_ranges(line, pos)
+    }))
+    .unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err(CompletionError::Panicked(message))
+    })
+}
+
+/// `text` as a hyperlink (OSC 8) to `link`, or `text` as is without a link or colors
+pub fn hyperlink(text: &str, link: Option<&str>, use_ansi_coloring: bool) -> String {
+    match link {
+        Some(link) if use_ansi_coloring && !text.is_empty() => {
+            format!("\x1b]8;;{link}\x1b\\{text}\x1b]8;;\x1b\\")
+        }
+        _ => text.to_string(),
+    }
+}
+
 /// Get the part of the line that should be given as input to the completer, as well
 /// as the index of the end of that piece of text
 ///
@@ -298,6 +349,22 @@ pub fn replace_in_buffer(value: Option<Suggestion>, editor: &mut Editor) {
     }
 }
 
+/// Whether the common string of `values` is longer than the typed text it replaces, so that
+/// [`can_partially_complete`] inserts more than was typed
+pub fn common_string_extends_input(values: &[Suggestion], editor: &Editor) -> bool {
+    match find_common_string(values) {
+        (Some(Suggestion { value, span, .. }), Some(index)) => {
+            let matching = &value[..index.min(value.len())];
+            let typed = editor
+                .get_buffer()
+                .get(span.start..span.end)
+                .unwrap_or_default();
+            matching.len() > typed.len() && matching.starts_with(typed)
+        }
+        _ => false,
+    }
+}
+
 /// Helper for `Menu::can_partially_complete`
 pub fn can_partially_complete(values: &[Suggestion], editor: &mut Editor) -> bool {
     if let (Some(Suggestion { value, span, .. }), Some(index)) = find_common_string(values) {
@@ -589,6 +656,7 @@ mod tests {
                 extra: None,
                 span: Span::new(0, s.len()),
                 append_whitespace: false,
+                link: None,
             })
             .collect();
         let res = find_common_string(&input);
@@ -609,6 +677,7 @@ mod tests {
                 extra: None,
                 span: Span::new(0, s.len()),
                 append_whitespace: false,
+                link: None,
             })
             .collect();
         let res = find_common_string(&input);
@@ -664,6 +733,7 @@ mod tests {
                 extra: None,
                 span: Span::new(start, end),
                 append_whitespace: false,
+                link: None,
             }),
             &mut editor,
         );
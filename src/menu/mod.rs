@@ -3,16 +3,28 @@ mod description_menu;
 mod ide_menu;
 mod list_menu;
 pub mod menu_functions;
+#[cfg(feature = "image_preview")]
+mod preview;
 
 use crate::core_editor::Editor;
 use crate::History;
-use crate::{completion::history::HistoryCompleter, painting::Painter, Completer, Suggestion};
-pub use columnar_menu::ColumnarMenu;
+use crate::{
+    completion::{history::HistoryCompleter, CompletionUsage},
+    host_context::ContextCompleter,
+    painting::{Painter, SanitizedCompleter, Sanitizer},
+    Completer, CompletionError, ReedlineEvent, Suggestion, SuggestionSort,
+};
+pub use columnar_menu::{ColumnarMenu, FillDirection};
 pub use description_menu::DescriptionMenu;
 pub use ide_menu::DescriptionMode;
 pub use ide_menu::IdeMenu;
 pub use list_menu::ListMenu;
 use nu_ansi_term::{Color, Style};
+#[cfg(feature = "image_preview")]
+pub use preview::{GraphicsProtocol, Preview, PreviewImage, Previewer};
+#[cfg(feature = "image_preview")]
+pub(crate) use preview::{RenderedPreview, KITTY_DELETE};
+use std::{any::Any, sync::Arc};
 
 /// Struct to store the menu style
 pub struct MenuTextStyle {
@@ -28,6 +40,8 @@ pub struct MenuTextStyle {
     /// Text style of the parts of the suggestions that match the
     /// typed text
     pub match_style: Style,
+    /// Text style of the row shown when the completer failed
+    pub error_style: Style,
 }
 
 impl Default for MenuTextStyle {
@@ -38,10 +52,22 @@ impl Default for MenuTextStyle {
             description_style: Color::Yellow.normal(),
             selected_match_style: Color::Green.bold().reverse().underline(),
             match_style: Style::default().underline(),
+            error_style: Color::Red.normal(),
         }
     }
 }
 
+/// Where a menu is painted relative to the buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MenuPlacement {
+    /// Below the line with the cursor
+    #[default]
+    Below,
+    /// Above the prompt, which moves down to make room for the menu. Suits prompts at the
+    /// bottom of the screen and applications that draw below the prompt
+    Above,
+}
+
 /// Defines all possible events that could happen with a menu.
 #[derive(Clone)]
 pub enum MenuEvent {
@@ -91,6 +117,11 @@ pub trait Menu: Send {
         &self.settings().marker
     }
 
+    /// Where the menu is painted
+    fn placement(&self) -> MenuPlacement {
+        self.settings().placement
+    }
+
     /// Checks if the menu is active
     fn is_active(&self) -> bool;
 
@@ -144,12 +175,36 @@ pub trait Menu: Send {
 
     /// Gets cached values from menu that will be displayed
     fn get_values(&self) -> &[Suggestion];
+    /// The value that is put into the buffer when the menu is accepted, if any
+    fn selected_value(&self) -> Option<Suggestion> {
+        None
+    }
+
     /// Sets the position of the cursor (currently only required by the IDE menu)
     fn set_cursor_pos(&mut self, _pos: (u16, u16)) {
         // empty implementation to make it optional
     }
+
+    /// Whether the menu is painted above the cursor, over the lines of the buffer, when there
+    /// is no room for it below the cursor. The lines of the menu string are then painted from
+    /// their first non-space character on, so that the buffer stays visible around the menu
+    fn flips_above(&self) -> bool {
+        false
+    }
+
+    /// Why the completer failed the last time the values were updated, shown by the menu
+    /// in place of the values
+    fn completion_error(&self) -> Option<&CompletionError> {
+        None
+    }
 }
 
+/// Turns the selected suggestion into the one inserted, see [`MenuBuilder::with_pre_insert`]
+pub type PreInsertHook = Arc<dyn Fn(Suggestion) -> Suggestion + Send + Sync>;
+
+/// Tells what to do after a suggestion was inserted, see [`MenuBuilder::with_post_insert`]
+pub type PostInsertHook = Arc<dyn Fn(&Suggestion) -> Option<ReedlineEvent> + Send + Sync>;
+
 pub struct MenuSettings {
     /// Menu name
     name: String,
@@ -160,6 +215,20 @@ pub struct MenuSettings {
     /// Calls the completer using only the line buffer difference difference
     /// after the menu was activated
     only_buffer_difference: bool,
+    /// Order of the suggestions
+    sort: SuggestionSort,
+    /// Typos tolerated when matching the history entries of a history menu
+    typo_tolerance: usize,
+    /// Where the menu is painted
+    placement: MenuPlacement,
+    /// Applied to the selected suggestion before it is inserted
+    pre_insert: Option<PreInsertHook>,
+    /// Called after the selected suggestion was inserted
+    post_insert: Option<PostInsertHook>,
+    /// Inserted suggestions ending with one of these open the menu again
+    retrigger_chars: Vec<char>,
+    /// Going to the next value inserts, keeping the menu open to filter again
+    insert_and_filter: bool,
 }
 
 impl Default for MenuSettings {
@@ -169,6 +238,13 @@ impl Default for MenuSettings {
             color: MenuTextStyle::default(),
             marker: "| ".to_string(),
             only_buffer_difference: false,
+            sort: SuggestionSort::default(),
+            typo_tolerance: 0,
+            placement: MenuPlacement::default(),
+            pre_insert: None,
+            post_insert: None,
+            retrigger_chars: Vec::new(),
+            insert_and_filter: false,
         }
     }
 }
@@ -201,6 +277,80 @@ impl MenuSettings {
         self.only_buffer_difference = only_buffer_difference;
         self
     }
+
+    /// MenuSettings builder with sort
+    #[must_use]
+    pub fn with_sort(mut self, sort: SuggestionSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// MenuSettings builder with typo_tolerance
+    #[must_use]
+    pub fn with_typo_tolerance(mut self, max_typos: usize) -> Self {
+        self.typo_tolerance = max_typos;
+        self
+    }
+
+    /// MenuSettings builder with placement
+    #[must_use]
+    pub fn with_placement(mut self, placement: MenuPlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// MenuSettings builder with pre_insert
+    #[must_use]
+    pub fn with_pre_insert(mut self, pre_insert: Option<PreInsertHook>) -> Self {
+        self.pre_insert = pre_insert;
+        self
+    }
+
+    /// MenuSettings builder with post_insert
+    #[must_use]
+    pub fn with_post_insert(mut self, post_insert: Option<PostInsertHook>) -> Self {
+        self.post_insert = post_insert;
+        self
+    }
+
+    /// MenuSettings builder with retrigger_chars
+    #[must_use]
+    pub fn with_retrigger_chars(mut self, retrigger_chars: &[char]) -> Self {
+        self.retrigger_chars = retrigger_chars.to_vec();
+        self
+    }
+
+    /// MenuSettings builder with insert_and_filter
+    #[must_use]
+    pub fn with_insert_and_filter(mut self, insert_and_filter: bool) -> Self {
+        self.insert_and_filter = insert_and_filter;
+        self
+    }
+
+    /// Whether going to the next value inserts instead, see
+    /// [`MenuBuilder::with_insert_and_filter`]
+    pub(crate) fn inserts_and_filters(&self) -> bool {
+        self.insert_and_filter
+    }
+
+    /// The suggestion to insert for the selected `suggestion`
+    pub(crate) fn prepare_insert(&self, suggestion: Option<Suggestion>) -> Option<Suggestion> {
+        match &self.pre_insert {
+            Some(pre_insert) => suggestion.map(|suggestion| pre_insert(suggestion)),
+            None => suggestion,
+        }
+    }
+
+    /// The event to run after `suggestion` was inserted
+    pub(crate) fn after_insert(&self, suggestion: &Suggestion) -> Option<ReedlineEvent> {
+        let retriggers = suggestion
+            .value
+            .ends_with(|c| self.retrigger_chars.contains(&c));
+        self.post_insert
+            .as_ref()
+            .and_then(|post_insert| post_insert(suggestion))
+            .or_else(|| retriggers.then(|| ReedlineEvent::Menu(self.name.clone())))
+    }
 }
 
 /// Common builder for all menus
@@ -255,6 +405,13 @@ pub trait MenuBuilder: Menu + Sized {
         self
     }
 
+    /// Menu builder with new value for the style of the row shown when the completer failed
+    #[must_use]
+    fn with_error_text_style(mut self, color: Style) -> Self {
+        self.settings_mut().color.error_style = color;
+        self
+    }
+
     /// Menu builder with new value for marker
     #[must_use]
     fn with_marker(mut self, marker: &str) -> Self {
@@ -268,6 +425,117 @@ pub trait MenuBuilder: Menu + Sized {
         self.settings_mut().only_buffer_difference = only_buffer_difference;
         self
     }
+
+    /// Menu builder with the order of the suggestions, independent of the completer
+    #[must_use]
+    fn with_sort(mut self, sort: SuggestionSort) -> Self {
+        self.settings_mut().sort = sort;
+        self
+    }
+
+    /// Menu builder with the typos tolerated when none of the entries of a
+    /// [`ReedlineMenu::HistoryMenu`] contains the line exactly, e.g. `gti sttaus`
+    /// then lists `git status`
+    ///
+    /// At most one typo per three characters of the line is tolerated. The completers of the
+    /// other menus match the values themselves, see [`crate::DefaultCompleter::with_typo_tolerance`].
+    #[must_use]
+    fn with_typo_tolerance(mut self, max_typos: usize) -> Self {
+        self.settings_mut().typo_tolerance = max_typos;
+        self
+    }
+
+    /// Menu builder with where the menu is painted, below the cursor by default
+    ///
+    /// A menu above the prompt is painted below it anyway while the buffer fills the screen.
+    #[must_use]
+    fn with_placement(mut self, placement: MenuPlacement) -> Self {
+        self.settings_mut().placement = placement;
+        self
+    }
+
+    /// Menu builder with a transformation of the selected suggestion before it is
+    /// inserted, e.g. to quote it or to append a trailing `=`
+    ///
+    /// ```rust
+    /// use reedline::{ColumnarMenu, MenuBuilder, Suggestion};
+    ///
+    /// let menu = ColumnarMenu::default().with_pre_insert(|suggestion: Suggestion| {
+    ///     if suggestion.value.contains(' ') {
+    ///         let value = format!("'{}'", suggestion.value);
+    ///         Suggestion { value, ..suggestion }
+    ///     } else {
+    ///         suggestion
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    fn with_pre_insert(
+        mut self,
+        pre_insert: impl Fn(Suggestion) -> Suggestion + Send + Sync + 'static,
+    ) -> Self {
+        self.settings_mut().pre_insert = Some(Arc::new(pre_insert));
+        self
+    }
+
+    /// Menu builder with a callback after the selected suggestion was inserted, returning
+    /// the event to run next if any, e.g. to complete again after a directory
+    ///
+    /// The callback gets the suggestion as selected, before [`MenuBuilder::with_pre_insert`].
+    /// Completing again after a directory is also built in, see
+    /// [`MenuBuilder::with_retrigger_chars`].
+    ///
+    /// ```rust
+    /// use reedline::{ColumnarMenu, MenuBuilder, ReedlineEvent, Suggestion};
+    ///
+    /// let menu = ColumnarMenu::default()
+    ///     .with_name("completion_menu")
+    ///     .with_post_insert(|suggestion: &Suggestion| {
+    ///         suggestion
+    ///             .value
+    ///             .ends_with('/')
+    ///             .then(|| ReedlineEvent::Menu("completion_menu".to_string()))
+    ///     });
+    /// ```
+    #[must_use]
+    fn with_post_insert(
+        mut self,
+        post_insert: impl Fn(&Suggestion) -> Option<ReedlineEvent> + Send + Sync + 'static,
+    ) -> Self {
+        self.settings_mut().post_insert = Some(Arc::new(post_insert));
+        self
+    }
+
+    /// Menu builder with the characters that open the menu again when an inserted
+    /// suggestion ends with them, e.g. `&['/']` to drill into a path segment by segment
+    ///
+    /// None by default. The event returned by [`MenuBuilder::with_post_insert`] comes first.
+    #[must_use]
+    fn with_retrigger_chars(mut self, retrigger_chars: &[char]) -> Self {
+        self.settings_mut().retrigger_chars = retrigger_chars.to_vec();
+        self
+    }
+
+    /// Menu builder where going to the next value, with [`ReedlineEvent::MenuNext`] bound to
+    /// Tab, inserts instead and keeps the menu open, so that typing on filters the values
+    /// again for the longer token, e.g. for a fuzzy completer
+    ///
+    /// The common prefix of the values is inserted when it is longer than the typed token,
+    /// otherwise the selected value. Off by default.
+    #[must_use]
+    fn with_insert_and_filter(mut self, insert_and_filter: bool) -> Self {
+        self.settings_mut().insert_and_filter = insert_and_filter;
+        self
+    }
+}
+
+/// Where the menus take their values from besides the completer of the engine
+pub(crate) struct CompletionSources<'a> {
+    pub(crate) history: &'a dyn History,
+    pub(crate) usage: &'a CompletionUsage,
+    pub(crate) context: Option<&'a (dyn Any + Send)>,
+    // Removes the control sequences from the suggestions, see `Reedline::with_sanitizer`
+    pub(crate) sanitizer: Option<Sanitizer>,
 }
 
 /// Allowed menus in Reedline
@@ -302,71 +570,75 @@ impl ReedlineMenu {
         }
     }
 
-    pub(crate) fn can_partially_complete(
+    // Calls `f` with the menu and the completer of its values, in the order of the menu and
+    // sanitized
+    fn with_completer<R>(
         &mut self,
-        values_updated: bool,
-        editor: &mut Editor,
         completer: &mut dyn Completer,
-        history: &dyn History,
-    ) -> bool {
-        match self {
-            Self::EngineCompleter(menu) => {
-                menu.can_partially_complete(values_updated, editor, completer)
-            }
+        sources: &CompletionSources,
+        f: impl FnOnce(&mut dyn Menu, &mut dyn Completer) -> R,
+    ) -> R {
+        let sort = self.settings().sort.clone();
+        let typo_tolerance = self.settings().typo_tolerance;
+        let mut history_completer;
+        let mut own_completer;
+        let (menu, completer): (&mut dyn Menu, &mut dyn Completer) = match self {
+            Self::EngineCompleter(menu) => (menu.as_mut(), completer),
             Self::HistoryMenu(menu) => {
-                let mut history_completer = HistoryCompleter::new(history);
-                menu.can_partially_complete(values_updated, editor, &mut history_completer)
+                history_completer =
+                    HistoryCompleter::new(sources.history).with_typo_tolerance(typo_tolerance);
+                (menu.as_mut(), &mut history_completer)
             }
-            Self::WithCompleter {
+            Self::WithCompleter { menu, completer } => {
+                own_completer = ContextCompleter::new(completer.as_mut(), sources.context);
+                (menu.as_mut(), &mut own_completer)
+            }
+        };
+        match sources.sanitizer {
+            Some(sanitizer) => f(
                 menu,
-                completer: own_completer,
-            } => menu.can_partially_complete(values_updated, editor, own_completer.as_mut()),
+                &mut sort.wrap(
+                    &mut SanitizedCompleter::new(completer, sanitizer),
+                    sources.usage,
+                ),
+            ),
+            None => f(menu, &mut sort.wrap(completer, sources.usage)),
         }
     }
 
+    pub(crate) fn can_partially_complete(
+        &mut self,
+        values_updated: bool,
+        editor: &mut Editor,
+        completer: &mut dyn Completer,
+        sources: &CompletionSources,
+    ) -> bool {
+        self.with_completer(completer, sources, |menu, completer| {
+            menu.can_partially_complete(values_updated, editor, completer)
+        })
+    }
+
     pub(crate) fn update_values(
         &mut self,
         editor: &mut Editor,
         completer: &mut dyn Completer,
-        history: &dyn History,
+        sources: &CompletionSources,
     ) {
-        match self {
-            Self::EngineCompleter(menu) => menu.update_values(editor, completer),
-            Self::HistoryMenu(menu) => {
-                let mut history_completer = HistoryCompleter::new(history);
-                menu.update_values(editor, &mut history_completer);
-            }
-            Self::WithCompleter {
-                menu,
-                completer: own_completer,
-            } => {
-                menu.update_values(editor, own_completer.as_mut());
-            }
-        }
+        self.with_completer(completer, sources, |menu, completer| {
+            menu.update_values(editor, completer);
+        });
     }
 
     pub(crate) fn update_working_details(
         &mut self,
         editor: &mut Editor,
         completer: &mut dyn Completer,
-        history: &dyn History,
+        sources: &CompletionSources,
         painter: &Painter,
     ) {
-        match self {
-            Self::EngineCompleter(menu) => {
-                menu.update_working_details(editor, completer, painter);
-            }
-            Self::HistoryMenu(menu) => {
-                let mut history_completer = HistoryCompleter::new(history);
-                menu.update_working_details(editor, &mut history_completer, painter);
-            }
-            Self::WithCompleter {
-                menu,
-                completer: own_completer,
-            } => {
-                menu.update_working_details(editor, own_completer.as_mut(), painter);
-            }
-        }
+        self.with_completer(completer, sources, |menu, completer| {
+            menu.update_working_details(editor, completer, painter);
+        });
     }
 }
 
@@ -466,7 +738,18 @@ impl Menu for ReedlineMenu {
         self.as_ref().get_values()
     }
 
+    fn selected_value(&self) -> Option<Suggestion> {
+        self.as_ref().selected_value()
+    }
+
     fn set_cursor_pos(&mut self, pos: (u16, u16)) {
         self.as_mut().set_cursor_pos(pos);
     }
+    fn flips_above(&self) -> bool {
+        self.as_ref().flips_above()
+    }
+
+    fn completion_error(&self) -> Option<&CompletionError> {
+        self.as_ref().completion_error()
+    }
 }
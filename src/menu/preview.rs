@@ -0,0 +1,213 @@
+use crate::Suggestion;
+
+// The id of the image of the kitty graphics protocol, so that a new preview replaces the last
+const KITTY_IMAGE_ID: u32 = 0x7265_6564;
+
+/// Removes the last preview painted with the kitty graphics protocol
+pub(crate) const KITTY_DELETE: &str = "\x1b_Ga=d,d=I,i=1919247716,q=2\x1b\\";
+
+/// The graphics protocols the previews can be painted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    /// The [kitty graphics protocol](https://sw.kovidgoyal.net/kitty/graphics-protocol/),
+    /// e.g. in kitty, WezTerm and Ghostty
+    Kitty,
+    /// Sixel graphics, e.g. in foot, mlterm, iTerm2 and xterm started with sixel support
+    Sixel,
+}
+
+impl GraphicsProtocol {
+    /// The protocol the terminal is expected to understand, judging by the environment, or
+    /// `None` if the terminal shows no images
+    pub fn detect() -> Option<Self> {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        let term = var("TERM");
+        let term_program = var("TERM_PROGRAM");
+        if std::env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+            || ["WezTerm", "ghostty"].contains(&term_program.as_str())
+        {
+            Some(Self::Kitty)
+        } else if ["foot", "mlterm", "sixel"]
+            .iter()
+            .any(|name| term.contains(name))
+            || term_program == "iTerm.app"
+        {
+            Some(Self::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+/// The image shown by a [`Preview`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewImage {
+    /// A PNG image, painted with the kitty graphics protocol
+    Png(Vec<u8>),
+    /// A complete sixel sequence (`ESC P ... q ... ESC \`), e.g. the output of `img2sixel`
+    Sixel(String),
+}
+
+impl PreviewImage {
+    fn protocol(&self) -> GraphicsProtocol {
+        match self {
+            PreviewImage::Png(_) => GraphicsProtocol::Kitty,
+            PreviewImage::Sixel(_) => GraphicsProtocol::Sixel,
+        }
+    }
+}
+
+/// An image shown next to the selected value of an [`crate::IdeMenu`], in place of its
+/// description, e.g. the picture behind a completed path
+///
+/// The image is scaled to `columns` by `rows` cells. Terminals that can't paint it, or not
+/// in its protocol, show the fallback text as the description instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preview {
+    image: PreviewImage,
+    columns: u16,
+    rows: u16,
+    fallback: String,
+}
+
+impl Preview {
+    /// A preview of `image` taking `columns` by `rows` cells
+    pub fn new(image: PreviewImage, columns: u16, rows: u16) -> Self {
+        Self {
+            image,
+            columns: columns.max(1),
+            rows: rows.max(1),
+            fallback: String::new(),
+        }
+    }
+
+    /// A builder which sets the text shown where the image can't be, nothing by default
+    #[must_use]
+    pub fn with_fallback(mut self, fallback: impl Into<String>) -> Self {
+        self.fallback = fallback.into();
+        self
+    }
+
+    pub(crate) fn render(self, protocol: Option<GraphicsProtocol>) -> RenderedPreview {
+        if protocol != Some(self.image.protocol()) {
+            return RenderedPreview::Text(self.fallback);
+        }
+        let sequence = match self.image {
+            PreviewImage::Png(png) => kitty_sequence(&png, self.columns, self.rows),
+            PreviewImage::Sixel(sixel) => sixel,
+        };
+        RenderedPreview::Image {
+            sequence,
+            columns: self.columns,
+            rows: self.rows,
+        }
+    }
+}
+
+/// Provides the [`Preview`] of the selected value of a menu, see
+/// [`crate::IdeMenu::with_previewer`]
+///
+/// It is asked again whenever another value is selected, so reading and converting images
+/// should be quick or cached. Closures taking the suggestion are previewers too.
+pub trait Previewer: Send {
+    /// The preview of `suggestion`, or `None` to show its description
+    fn preview(&mut self, suggestion: &Suggestion) -> Option<Preview>;
+}
+
+impl<F> Previewer for F
+where
+    F: FnMut(&Suggestion) -> Option<Preview> + Send,
+{
+    fn preview(&mut self, suggestion: &Suggestion) -> Option<Preview> {
+        self(suggestion)
+    }
+}
+
+/// A [`Preview`] the way the terminal gets it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RenderedPreview {
+    Image {
+        sequence: String,
+        columns: u16,
+        rows: u16,
+    },
+    Text(String),
+}
+
+// Transmits and places `png` at the cursor, in chunks as the protocol requires
+fn kitty_sequence(png: &[u8], columns: u16, rows: u16) -> String {
+    let data = base64(png);
+    let chunks: Vec<_> = data.as_bytes().chunks(4096).collect();
+    let mut sequence = String::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(index + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if index == 0 {
+            sequence.push_str(&format!(
+                "\x1b_Ga=T,f=100,i={KITTY_IMAGE_ID},c={columns},r={rows},C=1,q=2,m={more};{chunk}\x1b\\"
+            ));
+        } else {
+            sequence.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\"));
+        }
+    }
+    sequence
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | u32::from(*byte) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(char::from(
+                    ALPHABET[(group >> (18 - 6 * index)) as usize & 63],
+                ));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(b"", "")]
+    #[case(b"f", "Zg==")]
+    #[case(b"fo", "Zm8=")]
+    #[case(b"foo", "Zm9v")]
+    #[case(b"foobar", "Zm9vYmFy")]
+    fn bytes_are_base64_encoded(#[case] bytes: &[u8], #[case] expected: &str) {
+        assert_eq!(base64(bytes), expected);
+    }
+
+    #[test]
+    fn the_image_falls_back_to_text_without_its_protocol() {
+        let preview = Preview::new(PreviewImage::Png(b"png".to_vec()), 8, 4).with_fallback("a.png");
+        assert_eq!(
+            preview.clone().render(Some(GraphicsProtocol::Sixel)),
+            RenderedPreview::Text("a.png".into())
+        );
+        assert_eq!(
+            preview.render(Some(GraphicsProtocol::Kitty)),
+            RenderedPreview::Image {
+                sequence: format!(
+                    "\x1b_Ga=T,f=100,i={KITTY_IMAGE_ID},c=8,r=4,C=1,q=2,m=0;cG5n\x1b\\"
+                ),
+                columns: 8,
+                rows: 4,
+            }
+        );
+        assert!(KITTY_DELETE.contains(&format!("i={KITTY_IMAGE_ID},")));
+    }
+}
@@ -0,0 +1,398 @@
+use {
+    crossterm::{
+        cursor::MoveTo,
+        style::Print,
+        terminal::{Clear, ClearType},
+        QueueableCommand,
+    },
+    std::io::{Result, Write},
+    unicode_width::UnicodeWidthChar,
+};
+
+const RESET: &str = "\x1b[0m";
+const TAB_WIDTH: u16 = 8;
+
+/// A single character cell of the screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cell {
+    // The character shown in the cell, empty for the second half of a wide character
+    symbol: String,
+    // The SGR escape sequences active when the symbol was printed
+    style: String,
+}
+
+impl Cell {
+    fn blank() -> Self {
+        Cell {
+            symbol: " ".to_string(),
+            style: String::new(),
+        }
+    }
+
+    fn is_blank(&self) -> bool {
+        self.symbol == " " && self.style.is_empty()
+    }
+
+    fn is_continuation(&self) -> bool {
+        self.symbol.is_empty()
+    }
+}
+
+/// The screen rows from the start of the prompt down, as left behind by a repaint
+///
+/// Built by interpreting the output of the painter, so the next repaint only has to write the
+/// cells that changed in between. Output that can't be interpreted reliably, like scrolling or
+/// unknown escape sequences, makes the frame unusable and forces a full repaint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Frame {
+    size: (u16, u16),
+    top: u16,
+    rows: Vec<Vec<Cell>>,
+    // (column, row) on the screen
+    cursor: (u16, u16),
+    saved_cursor: Option<(u16, u16)>,
+    style: String,
+    // The last column was written, the next character goes to the next row
+    pending_wrap: bool,
+}
+
+impl Frame {
+    /// An empty frame for a screen of `size` (width, height) with the prompt at row `top`
+    fn new(size: (u16, u16), top: u16) -> Self {
+        let (width, height) = size;
+        Frame {
+            size,
+            top,
+            rows: vec![vec![Cell::blank(); width as usize]; height.saturating_sub(top) as usize],
+            cursor: (0, top),
+            saved_cursor: None,
+            style: String::new(),
+            pending_wrap: false,
+        }
+    }
+
+    /// The text of the row `row` from the top of the frame, without the trailing blanks
+    #[cfg(test)]
+    pub(crate) fn row_text(&self, row: usize) -> String {
+        self.rows[row]
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    /// Interpret the terminal `output` of a repaint starting at row `top`
+    ///
+    /// Returns `None` if the output contains anything the frame can't keep track of.
+    pub(crate) fn render(output: &[u8], size: (u16, u16), top: u16) -> Option<Self> {
+        let mut frame = Frame::new(size, top);
+        frame.apply(std::str::from_utf8(output).ok()?)?;
+        Some(frame)
+    }
+
+    /// Can the frame be painted as a difference to the `previous` one
+    pub(crate) fn is_comparable(&self, previous: &Frame) -> bool {
+        self.size == previous.size && self.top == previous.top
+    }
+
+    /// Queue the output turning the screen showing `previous` into this frame
+    ///
+    /// Leaves the cursor where the repaint placed it.
+    pub(crate) fn queue_diff(&self, previous: &Frame, out: &mut impl Write) -> Result<()> {
+        for (index, (row, previous_row)) in self.rows.iter().zip(&previous.rows).enumerate() {
+            let first = match row.iter().zip(previous_row).position(|(a, b)| a != b) {
+                Some(first) if first > 0 && row[first].is_continuation() => first - 1,
+                Some(first) => first,
+                None => continue,
+            };
+            let end = content_end(row);
+            let previous_end = content_end(previous_row);
+
+            out.queue(MoveTo(first as u16, self.top + index as u16))?;
+            if first < end {
+                out.queue(Print(RESET))?;
+                let mut style = "";
+                for cell in row[first..end]
+                    .iter()
+                    .filter(|cell| !cell.is_continuation())
+                {
+                    if cell.style != style {
+                        out.queue(Print(RESET))?.queue(Print(&cell.style))?;
+                        style = &cell.style;
+                    }
+                    out.queue(Print(&cell.symbol))?;
+                }
+                out.queue(Print(RESET))?;
+            }
+            // `end` is before the last column, otherwise there would be nothing left to clear
+            if previous_end > end.max(first) {
+                out.queue(Clear(ClearType::UntilNewLine))?;
+            }
+        }
+
+        let (column, row) = self.saved_cursor.unwrap_or(self.cursor);
+        out.queue(MoveTo(column, row))?;
+        Ok(())
+    }
+
+    fn apply(&mut self, output: &str) -> Option<()> {
+        let mut chars = output.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => match chars.next()? {
+                    '[' => {
+                        let mut params = String::new();
+                        let command = loop {
+                            match chars.next()? {
+                                c @ '@'..='~' => break c,
+                                c => params.push(c),
+                            }
+                        };
+                        self.control_sequence(&params, command)?;
+                    }
+                    '7' => self.saved_cursor = Some(self.cursor),
+                    '8' => {
+                        if let Some(saved_cursor) = self.saved_cursor {
+                            self.cursor = saved_cursor;
+                            self.pending_wrap = false;
+                        }
+                    }
+                    _ => return None,
+                },
+                '\r' => {
+                    self.cursor.0 = 0;
+                    self.pending_wrap = false;
+                }
+                '\n' => self.line_feed()?,
+                '\t' => {
+                    let column = (self.cursor.0 / TAB_WIDTH + 1) * TAB_WIDTH;
+                    self.cursor.0 = column.min(self.size.0.saturating_sub(1));
+                    self.pending_wrap = false;
+                }
+                '\x07' => {}
+                c if c.is_control() => return None,
+                c => self.print(c)?,
+            }
+        }
+        Some(())
+    }
+
+    fn control_sequence(&mut self, params: &str, command: char) -> Option<()> {
+        let mut args = params.split(';');
+        let mut next_arg = |default| match args.next() {
+            None | Some("") => Some(default),
+            Some(arg) => arg.parse::<u16>().ok(),
+        };
+
+        match command {
+            'm' if !params.starts_with('?') => {
+                if params.is_empty() || params == "0" {
+                    self.style.clear();
+                } else if params.starts_with("0;") {
+                    self.style = format!("\x1b[{params}m");
+                } else {
+                    self.style.push_str(&format!("\x1b[{params}m"));
+                }
+            }
+            'H' | 'f' => {
+                let row = next_arg(1)?.saturating_sub(1);
+                let column = next_arg(1)?.saturating_sub(1);
+                self.move_to(column, row)?;
+            }
+            'A' => self.move_to(self.cursor.0, self.cursor.1.saturating_sub(next_arg(1)?))?,
+            'B' => self.move_to(self.cursor.0, self.cursor.1.saturating_add(next_arg(1)?))?,
+            'C' => self.move_to(self.cursor.0.saturating_add(next_arg(1)?), self.cursor.1)?,
+            'D' => self.move_to(self.cursor.0.saturating_sub(next_arg(1)?), self.cursor.1)?,
+            'G' => self.move_to(next_arg(1)?.saturating_sub(1), self.cursor.1)?,
+            'J' if params.is_empty() || params == "0" => {
+                self.erase_line_from_cursor();
+                let row = (self.cursor.1 - self.top) as usize;
+                for cells in self.rows.iter_mut().skip(row + 1) {
+                    cells.fill(Cell::blank());
+                }
+            }
+            'K' if params.is_empty() || params == "0" => self.erase_line_from_cursor(),
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn move_to(&mut self, column: u16, row: u16) -> Option<()> {
+        if row < self.top || row >= self.size.1 {
+            return None;
+        }
+        self.cursor = (column.min(self.size.0.saturating_sub(1)), row);
+        self.pending_wrap = false;
+        Some(())
+    }
+
+    fn line_feed(&mut self) -> Option<()> {
+        // Would scroll the screen
+        if self.cursor.1 + 1 >= self.size.1 {
+            return None;
+        }
+        self.cursor.1 += 1;
+        self.pending_wrap = false;
+        Some(())
+    }
+
+    fn erase_line_from_cursor(&mut self) {
+        let column = self.cursor.0 as usize;
+        let cells = &mut self.rows[(self.cursor.1 - self.top) as usize];
+        if column > 0 && cells[column].is_continuation() {
+            cells[column - 1] = Cell::blank();
+        }
+        cells[column..].fill(Cell::blank());
+        self.pending_wrap = false;
+    }
+
+    fn print(&mut self, c: char) -> Option<()> {
+        let width = c.width().unwrap_or(0);
+        if width == 0 {
+            // Combining characters join the previously printed one
+            let column = if self.pending_wrap {
+                self.cursor.0 as usize
+            } else {
+                (self.cursor.0 as usize).checked_sub(1)?
+            };
+            let cells = &mut self.rows[(self.cursor.1 - self.top) as usize];
+            let column = if cells[column].is_continuation() {
+                column.checked_sub(1)?
+            } else {
+                column
+            };
+            cells[column].symbol.push(c);
+            return Some(());
+        }
+
+        let screen_width = self.size.0 as usize;
+        if width > screen_width {
+            return None;
+        }
+        if self.pending_wrap || self.cursor.0 as usize + width > screen_width {
+            self.cursor.0 = 0;
+            self.line_feed()?;
+        }
+
+        let column = self.cursor.0 as usize;
+        let cells = &mut self.rows[(self.cursor.1 - self.top) as usize];
+        // Overwriting half of a wide character leaves the other half blank
+        if column > 0 && cells[column].is_continuation() {
+            cells[column - 1] = Cell::blank();
+        }
+        if column + width < screen_width && cells[column + width].is_continuation() {
+            cells[column + width] = Cell::blank();
+        }
+        cells[column] = Cell {
+            symbol: c.to_string(),
+            style: self.style.clone(),
+        };
+        if width == 2 {
+            cells[column + 1] = Cell {
+                symbol: String::new(),
+                style: self.style.clone(),
+            };
+        }
+
+        if column + width >= screen_width {
+            self.cursor.0 = self.size.0 - 1;
+            self.pending_wrap = true;
+        } else {
+            self.cursor.0 = (column + width) as u16;
+        }
+        Some(())
+    }
+}
+
+// Number of cells up to the last non-blank one
+fn content_end(cells: &[Cell]) -> usize {
+    cells
+        .iter()
+        .rposition(|cell| !cell.is_blank())
+        .map_or(0, |last| last + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    const SIZE: (u16, u16) = (10, 5);
+    const TOP: u16 = 1;
+
+    fn render(output: &str) -> Frame {
+        Frame::render(output.as_bytes(), SIZE, TOP).unwrap()
+    }
+
+    fn diff(previous: &Frame, next: &Frame) -> String {
+        let mut out = Vec::new();
+        next.queue_diff(previous, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[rstest]
+    #[case("\x1b[2;1H\x1b[J0123456789ab", &["0123456789", "ab", "", ""])]
+    #[case("\x1b[2;1H\x1b[Jab\r\ncd", &["ab", "cd", "", ""])]
+    #[case("\x1b[2;1H\x1b[Jabcdefghi漢", &["abcdefghi", "漢", "", ""])]
+    #[case("\x1b[2;1H\x1b[Jabc\x1b[2;2Hx", &["axc", "", "", ""])]
+    #[case("\x1b[2;1H\x1b[Jabc\x1b[2;2H\x1b[K", &["a", "", "", ""])]
+    #[case("\x1b[2;1H\x1b[Je\u{301}x", &["e\u{301}x", "", "", ""])]
+    #[case("\x1b[2;1H\x1b[Ja\tb", &["a       b", "", "", ""])]
+    fn test_render(#[case] output: &str, #[case] rows: &[&str]) {
+        let frame = render(output);
+
+        let rendered = (0..frame.rows.len())
+            .map(|row| frame.row_text(row))
+            .collect::<Vec<_>>();
+        assert_eq!(rendered, rows);
+    }
+
+    #[rstest]
+    #[case("\x1b]0;title\x07")]
+    #[case("\x1b[2;1H\n\n\n\n")]
+    #[case("\x1b[1;1Habc")]
+    #[case("\x1b[2J")]
+    #[case("\x1b[2;1H\x1b[?25l")]
+    fn test_render_unsupported(#[case] output: &str) {
+        assert_eq!(Frame::render(output.as_bytes(), SIZE, TOP), None);
+    }
+
+    #[test]
+    fn test_styles_are_tracked_per_cell() {
+        let frame = render("\x1b[2;1H\x1b[J\x1b[31ma\x1b[1mb\x1b[0mc");
+
+        assert_eq!(frame.rows[0][0].style, "\x1b[31m");
+        assert_eq!(frame.rows[0][1].style, "\x1b[31m\x1b[1m");
+        assert_eq!(frame.rows[0][2].style, "");
+    }
+
+    #[test]
+    fn test_diff_writes_only_changed_cells() {
+        let previous = render("\x1b[2;1H\x1b[Jabc\x1b7");
+        let next = render("\x1b[2;1H\x1b[Jabd\x1b7");
+
+        assert_eq!(diff(&previous, &next), "\x1b[2;3H\x1b[0md\x1b[0m\x1b[2;4H");
+        assert_eq!(diff(&next, &next), "\x1b[2;4H");
+    }
+
+    #[rstest]
+    #[case("> abc\r\n  def", "> abd\r\n  de")]
+    #[case("> abcdefghijkl", "> ab")]
+    #[case("> a\x1b[32mbc\x1b[0m", "> a\x1b[31mbc\x1b[0md")]
+    #[case("> 漢字", "> a字")]
+    #[case("> a漢", "> ab漢")]
+    #[case("> a\r\nmenu\r\nentries", "> ab")]
+    #[case("", "> a\x1b7\r\nhint")]
+    fn test_diff_reproduces_frame(#[case] previous: &str, #[case] next: &str) {
+        let previous = render(&format!("\x1b[2;1H\x1b[J{previous}"));
+        let next = render(&format!("\x1b[2;1H\x1b[J{next}"));
+
+        let mut painted = previous.clone();
+        painted.apply(&diff(&previous, &next)).unwrap();
+
+        assert_eq!(painted.rows, next.rows);
+        assert_eq!(painted.cursor, next.saved_cursor.unwrap_or(next.cursor));
+    }
+}
@@ -1,9 +1,17 @@
+mod frame;
 mod painter;
 mod prompt_lines;
+mod sanitizer;
 mod styled_text;
+mod submit_echo;
 mod utils;
 
 pub use painter::Painter;
 pub(crate) use prompt_lines::PromptLines;
-pub use styled_text::StyledText;
-pub(crate) use utils::estimate_single_line_wraps;
+pub use sanitizer::Sanitizer;
+pub(crate) use sanitizer::{SanitizedCompleter, SanitizedPrompt};
+pub(crate) use styled_text::overlay;
+pub use styled_text::{StyledText, Underline};
+pub(crate) use submit_echo::EchoPrompt;
+pub use submit_echo::SubmitEcho;
+pub(crate) use utils::{estimate_single_line_wraps, strip_ansi};
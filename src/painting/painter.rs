@@ -1,19 +1,24 @@
-use crate::{CursorConfig, PromptEditMode, PromptViMode};
-
+use crate::PromptEditMode;
 use {
-    super::utils::{coerce_crlf, line_width},
+    super::{
+        frame::Frame,
+        utils::{coerce_crlf, line_width},
+    },
     crate::{
-        menu::{Menu, ReedlineMenu},
+        menu::{Menu, MenuPlacement, ReedlineMenu},
         painting::PromptLines,
-        Prompt,
+        Prompt, ViewportLine,
     },
     crossterm::{
-        cursor::{self, MoveTo, RestorePosition, SavePosition},
+        cursor::{self, MoveTo, RestorePosition, SavePosition, SetCursorStyle},
         style::{Attribute, Print, ResetColor, SetAttribute, SetForegroundColor},
         terminal::{self, Clear, ClearType},
         QueueableCommand,
     },
-    std::io::{Result, Write},
+    std::{
+        io::{Result, Write},
+        ops::Range,
+    },
 };
 #[cfg(feature = "external_printer")]
 use {crate::LineBuffer, crossterm::cursor::MoveUp};
@@ -49,30 +54,159 @@ fn skip_buffer_lines(string: &str, skip: usize, offset: Option<usize>) -> &str {
 /// the type used by crossterm operations
 pub type W = std::io::BufWriter<std::io::Stderr>;
 
+// The terminal output, that can be diverted into a buffer to capture a repaint
+struct Output {
+    terminal: W,
+    capture: Option<Vec<u8>>,
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match &mut self.capture {
+            Some(capture) => capture.write(buf),
+            None => self.terminal.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.terminal.flush()
+    }
+}
+
 /// Implementation of the output to the terminal
 pub struct Painter {
     // Stdout
-    stdout: W,
+    stdout: Output,
     prompt_start_row: u16,
     terminal_size: (u16, u16),
     last_required_lines: u16,
     large_buffer: bool,
+    diff_repaint: bool,
+    // What the last repaint left on the screen, if it is still there
+    painted_frame: Option<Frame>,
+    // Memory of the last captured repaint, reused by the next one
+    frame_buffer: Vec<u8>,
+    // Rows at the (top, bottom) of the terminal that are left to the application
+    reserved_rows: (u16, u16),
+    // Whether the scrolling region of the terminal excludes the reserved rows
+    scroll_region_set: bool,
+    // Whether the last menu showed a preview with the kitty graphics protocol, which stays
+    // on the screen until it is deleted
+    #[cfg(feature = "image_preview")]
+    kitty_preview: bool,
+    // The first row of a large buffer on the screen, `None` keeps the cursor on the last row
+    view_top: Option<usize>,
+    // Where the cursor line of a large buffer is to be scrolled by the next repaint
+    view_request: Option<ViewportLine>,
+    // The first row and the lines of the buffer on the screen in the last repaint of a large
+    // buffer
+    painted_view: Option<(usize, Range<usize>)>,
 }
 
 impl Painter {
     pub(crate) fn new(stdout: W) -> Self {
         Painter {
-            stdout,
+            stdout: Output {
+                terminal: stdout,
+                capture: None,
+            },
             prompt_start_row: 0,
             terminal_size: (0, 0),
             last_required_lines: 0,
             large_buffer: false,
+            diff_repaint: false,
+            painted_frame: None,
+            frame_buffer: Vec::new(),
+            reserved_rows: (0, 0),
+            scroll_region_set: false,
+            #[cfg(feature = "image_preview")]
+            kitty_preview: false,
+            view_top: None,
+            view_request: None,
+            painted_view: None,
         }
     }
 
-    /// Height of the current terminal window
+    /// Only write the parts of the screen that changed since the last repaint
+    ///
+    /// Not supported on Windows, where the painter always repaints everything
+    pub(crate) fn set_diff_repaint(&mut self, enable: bool) {
+        self.diff_repaint = enable && !cfg!(windows);
+        self.painted_frame = None;
+    }
+
+    /// Forget the content of the screen, the next repaint writes everything
+    ///
+    /// Needed whenever something else than the painter writes to the terminal
+    pub(crate) fn invalidate_painted_frame(&mut self) {
+        self.painted_frame = None;
+    }
+
+    /// Height of the current terminal window, without the rows reserved by the application
     pub fn screen_height(&self) -> u16 {
-        self.terminal_size.1
+        self.screen_bottom().saturating_sub(self.reserved_rows.0)
+    }
+
+    // The row below the last one the painter paints in
+    fn screen_bottom(&self) -> u16 {
+        self.terminal_size.1.saturating_sub(self.reserved_rows.1)
+    }
+
+    /// Leave `top` rows at the top and `bottom` rows at the bottom of the terminal to the
+    /// application, see [`crate::Reedline::with_reserved_rows`]
+    ///
+    /// Takes effect on the terminal with [`Painter::apply_reserved_rows`].
+    pub(crate) fn set_reserved_rows(&mut self, top: u16, bottom: u16) {
+        self.reserved_rows = (top, bottom);
+        self.painted_frame = None;
+    }
+
+    /// Limit the scrolling of the terminal to the rows that aren't reserved, or lift the
+    /// limit once no rows are reserved anymore
+    pub(crate) fn apply_reserved_rows(&mut self) -> Result<()> {
+        if self.terminal_size != (0, 0) {
+            self.queue_scroll_region()?;
+            self.prompt_start_row = self
+                .prompt_start_row
+                .clamp(self.reserved_rows.0, self.screen_bottom().saturating_sub(1));
+        }
+        self.stdout.flush()
+    }
+
+    /// Reset the scrolling region of the terminal while the reserved rows stay reserved, to be
+    /// set again with [`Painter::apply_reserved_rows`]
+    pub(crate) fn release_scroll_region(&mut self) -> Result<()> {
+        self.queue_scroll_region_reset()?;
+        self.stdout.flush()
+    }
+
+    // Sets the scrolling region of the terminal to the rows that aren't reserved, so that
+    // neither the painter nor the output of the application moves the reserved rows
+    fn queue_scroll_region(&mut self) -> Result<()> {
+        let (top, _) = self.reserved_rows;
+        if self.reserved_rows != (0, 0) {
+            let bottom = self.screen_bottom().max(top + 1);
+            // Setting the region moves the cursor, which is kept where it was
+            self.stdout
+                .queue(SavePosition)?
+                .queue(Print(format!("\x1b[{};{}r", top + 1, bottom)))?
+                .queue(RestorePosition)?;
+            self.scroll_region_set = true;
+        } else {
+            self.queue_scroll_region_reset()?;
+        }
+        Ok(())
+    }
+
+    fn queue_scroll_region_reset(&mut self) -> Result<()> {
+        if self.scroll_region_set {
+            self.stdout
+                .queue(SavePosition)?
+                .queue(Print("\x1b[r"))?
+                .queue(RestorePosition)?;
+            self.scroll_region_set = false;
+        }
+        Ok(())
     }
 
     /// Width of the current terminal window
@@ -82,7 +216,26 @@ impl Painter {
 
     /// Returns the available lines from the prompt down
     pub fn remaining_lines(&self) -> u16 {
-        self.screen_height().saturating_sub(self.prompt_start_row)
+        self.screen_bottom().saturating_sub(self.prompt_start_row)
+    }
+
+    /// Row of the top of the prompt
+    pub(crate) fn prompt_start_row(&self) -> u16 {
+        self.prompt_start_row
+    }
+
+    /// Moves the prompt origin up to `row`, where an earlier prompt was painted
+    ///
+    /// Only done if the screen can't have scrolled since, which would have left the cursor
+    /// on the last row. Everything below `row` is cleared by the next repaint.
+    pub(crate) fn move_prompt_to_row(&mut self, row: u16) {
+        if row < self.prompt_start_row
+            && row >= self.reserved_rows.0
+            && self.prompt_start_row + 1 < self.screen_bottom()
+        {
+            self.prompt_start_row = row;
+            self.painted_frame = None;
+        }
     }
 
     /// Sets the prompt origin position and screen size for a new line editor
@@ -102,6 +255,10 @@ impl Painter {
                 size
             }
         };
+        self.queue_scroll_region()?;
+        self.stdout.flush()?;
+        self.view_top = None;
+        self.view_request = None;
         // Cursor positions are 0 based here.
         let (column, row) = cursor::position()?;
         // Assumption: if the cursor is not on the zeroth column,
@@ -111,13 +268,16 @@ impl Painter {
         //  the condition above, we need to make room for the prompt.
         //  Otherwise printing the prompt would scroll of the stored prompt
         //  origin, causing issues after repaints.
-        let new_row = if new_row == self.screen_height() {
+        let bottom = self.screen_bottom();
+        let new_row = if new_row >= bottom {
+            self.stdout.queue(MoveTo(0, bottom.saturating_sub(1)))?;
             self.print_crlf()?;
-            new_row.saturating_sub(1)
+            bottom.saturating_sub(1)
         } else {
-            new_row
+            new_row.max(self.reserved_rows.0)
         };
         self.prompt_start_row = new_row;
+        self.painted_frame = None;
         Ok(())
     }
 
@@ -134,22 +294,35 @@ impl Painter {
         &mut self,
         prompt: &dyn Prompt,
         lines: &PromptLines,
-        prompt_mode: PromptEditMode,
         menu: Option<&ReedlineMenu>,
         use_ansi_coloring: bool,
-        cursor_config: &Option<CursorConfig>,
+        cursor_shape: Option<SetCursorStyle>,
     ) -> Result<()> {
         self.stdout.queue(cursor::Hide)?;
+        #[cfg(feature = "image_preview")]
+        let kitty_preview = std::mem::take(&mut self.kitty_preview);
 
         let screen_width = self.screen_width();
         let screen_height = self.screen_height();
 
         // Lines and distance parameters
         let remaining_lines = self.remaining_lines();
-        let required_lines = lines.required_lines(screen_width, menu);
+        let menu_above = menu.map_or(false, |menu| self.menu_flips_above(menu, lines));
+        let required_lines = match menu {
+            // A menu above the cursor is painted over the lines of the buffer
+            Some(menu) if menu_above => lines
+                .required_lines(screen_width, Some(menu))
+                .saturating_sub(menu.menu_required_lines(screen_width)),
+            _ => lines.required_lines(screen_width, menu),
+        };
 
         // Marking the painter state as larger buffer to avoid animations
         self.large_buffer = required_lines >= screen_height;
+        if !self.large_buffer {
+            self.view_top = None;
+            self.view_request = None;
+            self.painted_view = None;
+        }
 
         // Moving the start position of the cursor based on the size of the required lines
         if required_lines >= remaining_lines {
@@ -157,6 +330,15 @@ impl Painter {
                 std::cmp::min(required_lines, screen_height).saturating_sub(remaining_lines);
             self.queue_universal_scroll(extra)?;
             self.prompt_start_row = self.prompt_start_row.saturating_sub(extra);
+            if extra > 0 {
+                self.painted_frame = None;
+            }
+        }
+
+        if self.diff_repaint {
+            let mut buffer = std::mem::take(&mut self.frame_buffer);
+            buffer.clear();
+            self.stdout.capture = Some(buffer);
         }
 
         // Moving the cursor to the start of the prompt
@@ -165,34 +347,101 @@ impl Painter {
             .queue(cursor::MoveTo(0, self.prompt_start_row))?
             .queue(Clear(ClearType::FromCursorDown))?;
 
-        if self.large_buffer {
-            self.print_large_buffer(prompt, lines, menu, use_ansi_coloring)?;
-        } else {
-            self.print_small_buffer(prompt, lines, menu, use_ansi_coloring)?;
+        match menu {
+            _ if self.large_buffer => {
+                self.print_large_buffer(prompt, lines, menu, use_ansi_coloring)?;
+            }
+            Some(menu) if menu.placement() == MenuPlacement::Above => {
+                // The menu takes the first rows and the buffer follows it
+                let menu_lines = self.print_menu_before_prompt(menu, use_ansi_coloring)?;
+                let top = self.prompt_start_row;
+                self.prompt_start_row += menu_lines;
+                let printed =
+                    self.print_small_buffer(prompt, lines, Some(menu), false, use_ansi_coloring);
+                self.prompt_start_row = top;
+                printed?;
+            }
+            _ => self.print_small_buffer(prompt, lines, menu, menu_above, use_ansi_coloring)?,
         }
 
         // The last_required_lines is used to move the cursor at the end where stdout
         // can print without overwriting the things written during the painting
         self.last_required_lines = required_lines;
 
-        self.stdout.queue(RestorePosition)?;
+        if let Some(output) = self.stdout.capture.take() {
+            self.paint_frame(&output)?;
+            self.frame_buffer = output;
+        } else {
+            self.stdout.queue(RestorePosition)?;
+        }
 
-        if let Some(shapes) = cursor_config {
-            let shape = match &prompt_mode {
-                PromptEditMode::Emacs => shapes.emacs,
-                PromptEditMode::Vi(PromptViMode::Insert) => shapes.vi_insert,
-                PromptEditMode::Vi(PromptViMode::Normal) => shapes.vi_normal,
-                _ => None,
-            };
-            if let Some(shape) = shape {
-                self.stdout.queue(shape)?;
-            }
+        #[cfg(feature = "image_preview")]
+        if kitty_preview && !self.kitty_preview {
+            self.stdout.queue(Print(crate::menu::KITTY_DELETE))?;
+        }
+        if let Some(shape) = cursor_shape {
+            self.stdout.queue(shape)?;
         }
         self.stdout.queue(cursor::Show)?;
 
         self.stdout.flush()
     }
 
+    /// Paints the prompt and the buffer around the cursor into a byte buffer, the
+    /// way a repaint on a terminal of `size` would write it, starting at its top row
+    ///
+    /// Meant for benchmarking the painting without a terminal
+    #[doc(hidden)]
+    pub fn render_to_bytes(
+        prompt: &dyn Prompt,
+        before_cursor: &str,
+        after_cursor: &str,
+        size: (u16, u16),
+    ) -> Result<Vec<u8>> {
+        let mut painter = Painter::new(W::new(std::io::stderr()));
+        painter.terminal_size = size;
+        painter.stdout.capture = Some(Vec::new());
+
+        let lines = PromptLines::new(
+            prompt,
+            PromptEditMode::Default,
+            None,
+            before_cursor,
+            after_cursor,
+            "",
+            "",
+        );
+        painter.large_buffer = lines.required_lines(size.0, None) >= size.1;
+
+        painter
+            .stdout
+            .queue(cursor::MoveTo(0, 0))?
+            .queue(Clear(ClearType::FromCursorDown))?;
+        if painter.large_buffer {
+            painter.print_large_buffer(prompt, &lines, None, true)?;
+        } else {
+            painter.print_small_buffer(prompt, &lines, None, false, true)?;
+        }
+
+        Ok(painter.stdout.capture.take().unwrap_or_default())
+    }
+
+    // Writes the captured `output` of a repaint, or only its difference to the last frame
+    fn paint_frame(&mut self, output: &[u8]) -> Result<()> {
+        let frame = Frame::render(output, self.terminal_size, self.prompt_start_row);
+        match (&frame, &self.painted_frame) {
+            (Some(frame), Some(previous)) if frame.is_comparable(previous) => {
+                frame.queue_diff(previous, &mut self.stdout)?;
+            }
+            _ => {
+                self.stdout.write_all(output)?;
+                self.stdout.queue(RestorePosition)?;
+            }
+        }
+        self.painted_frame = frame;
+        Ok(())
+    }
+
     fn print_right_prompt(&mut self, lines: &PromptLines) -> Result<()> {
         let prompt_length_right = line_width(&lines.prompt_str_right);
         let start_position = self
@@ -226,17 +475,32 @@ impl Painter {
         let screen_width = self.screen_width();
         let screen_height = self.screen_height();
         let cursor_distance = lines.distance_from_prompt(screen_width);
+        let status_lines = lines.status_lines();
 
         // If there is not enough space to print the menu, then the starting
         // drawing point for the menu will overwrite the last rows in the buffer
-        let starting_row = if cursor_distance >= screen_height.saturating_sub(1) {
-            screen_height.saturating_sub(menu.min_rows())
+        let status_row = if cursor_distance >= screen_height.saturating_sub(1 + status_lines) {
+            self.screen_bottom()
+                .saturating_sub(menu.min_rows() + status_lines)
         } else {
             self.prompt_start_row + cursor_distance + 1
         };
 
-        let remaining_lines = screen_height.saturating_sub(starting_row);
+        // The status bar sits between the buffer and the menu
+        if status_lines > 0 {
+            self.stdout
+                .queue(cursor::MoveTo(0, status_row))?
+                .queue(Clear(ClearType::FromCursorDown))?
+                .queue(Print(&lines.status))?;
+        }
+
+        let starting_row = status_row + status_lines;
+        let remaining_lines = self.screen_bottom().saturating_sub(starting_row);
         let menu_string = menu.menu_string(remaining_lines, use_ansi_coloring);
+        #[cfg(feature = "image_preview")]
+        {
+            self.kitty_preview |= menu_string.contains("\x1b_G");
+        }
         self.stdout
             .queue(cursor::MoveTo(0, starting_row))?
             .queue(Clear(ClearType::FromCursorDown))?
@@ -245,11 +509,80 @@ impl Painter {
         Ok(())
     }
 
+    // Paints the menu at the start of the prompt and moves to the row after it, returning the
+    // number of rows it takes
+    fn print_menu_before_prompt(
+        &mut self,
+        menu: &dyn Menu,
+        use_ansi_coloring: bool,
+    ) -> Result<u16> {
+        let menu_lines = menu.menu_required_lines(self.screen_width());
+        let menu_string = menu.menu_string(menu_lines, use_ansi_coloring);
+        #[cfg(feature = "image_preview")]
+        {
+            self.kitty_preview |= menu_string.contains("\x1b_G");
+        }
+        self.stdout
+            .queue(Print(menu_string.trim_end_matches('\n')))?
+            .queue(cursor::MoveTo(0, self.prompt_start_row + menu_lines))?;
+
+        Ok(menu_lines)
+    }
+
+    // Whether the menu is painted above the cursor, as it flips there when there is no room
+    // below the cursor and the lines of the buffer above the cursor can hold it
+    fn menu_flips_above(&self, menu: &dyn Menu, lines: &PromptLines) -> bool {
+        let screen_width = self.screen_width();
+        let cursor_distance = lines.distance_from_prompt(screen_width);
+        let menu_lines = menu.menu_required_lines(screen_width);
+        let lines_below = self
+            .remaining_lines()
+            .saturating_sub(cursor_distance + 1 + lines.status_lines());
+
+        menu.flips_above()
+            && menu.placement() == MenuPlacement::Below
+            && lines.required_lines(screen_width, None) < self.screen_height()
+            && menu_lines > lines_below
+            && menu_lines <= cursor_distance
+    }
+
+    // Paints the menu over the lines above the cursor, from the first non-space character of
+    // each of its lines, so that the buffer stays visible around it
+    fn print_menu_above(
+        &mut self,
+        menu: &dyn Menu,
+        lines: &PromptLines,
+        use_ansi_coloring: bool,
+    ) -> Result<()> {
+        let cursor_distance = lines.distance_from_prompt(self.screen_width());
+        let cursor_row = self.prompt_start_row + cursor_distance;
+        let menu_lines = menu
+            .menu_required_lines(self.screen_width())
+            .min(cursor_distance);
+
+        let menu_string = menu.menu_string(menu_lines, use_ansi_coloring);
+        #[cfg(feature = "image_preview")]
+        {
+            self.kitty_preview |= menu_string.contains("\x1b_G");
+        }
+        let first_row = cursor_row.saturating_sub(menu_lines);
+        for (row, line) in (first_row..cursor_row).zip(menu_string.split("\r\n")) {
+            let content = line.trim_start_matches(' ');
+            let indent = (line.len() - content.len()) as u16;
+            self.stdout
+                .queue(cursor::MoveTo(indent, row))?
+                .queue(Print(content))?;
+        }
+
+        Ok(())
+    }
+
     fn print_small_buffer(
         &mut self,
         prompt: &dyn Prompt,
         lines: &PromptLines,
         menu: Option<&ReedlineMenu>,
+        menu_above: bool,
         use_ansi_coloring: bool,
     ) -> Result<()> {
         // print our prompt with color
@@ -263,7 +596,7 @@ impl Painter {
 
         if use_ansi_coloring {
             self.stdout
-                .queue(SetForegroundColor(prompt.get_indicator_color()))?;
+                .queue(SetForegroundColor(lines.indicator_color))?;
         }
 
         self.stdout
@@ -287,15 +620,33 @@ impl Painter {
             .queue(SavePosition)?
             .queue(Print(&lines.after_cursor))?;
 
-        if let Some(menu) = menu {
-            self.print_menu(menu, lines, use_ansi_coloring)?;
-        } else {
-            self.stdout.queue(Print(&lines.hint))?;
+        match menu {
+            Some(menu) if menu_above => {
+                self.print_status(lines)?;
+                self.print_menu_above(menu, lines, use_ansi_coloring)?;
+            }
+            // Painted before the prompt
+            Some(menu) if menu.placement() == MenuPlacement::Above => self.print_status(lines)?,
+            Some(menu) => self.print_menu(menu, lines, use_ansi_coloring)?,
+            None => {
+                self.stdout.queue(Print(&lines.hint))?;
+                self.print_status(lines)?;
+            }
         }
 
         Ok(())
     }
 
+    // Prints the status bar on the line below the buffer and hint
+    fn print_status(&mut self, lines: &PromptLines) -> Result<()> {
+        if !lines.status.is_empty() {
+            self.stdout
+                .queue(Print("\r\n"))?
+                .queue(Print(&lines.status))?;
+        }
+        Ok(())
+    }
+
     fn print_large_buffer(
         &mut self,
         prompt: &dyn Prompt,
@@ -320,6 +671,32 @@ impl Painter {
         // Extra rows represent how many rows are "above" the visible area in the terminal
         let extra_rows = (total_lines_before).saturating_sub(screen_height as usize);
 
+        // The view is only moved off the cursor without a menu, which is painted below the
+        // cursor line
+        let cursor_row = total_lines_before.saturating_sub(1);
+        let rows = screen_height.saturating_sub(lines.status_lines()).max(1) as usize;
+        let view_top = if menu.is_some() {
+            None
+        } else {
+            let top = match self.view_request.take() {
+                Some(ViewportLine::Top) => Some(cursor_row),
+                Some(ViewportLine::Middle) => Some(cursor_row.saturating_sub((rows - 1) / 2)),
+                Some(ViewportLine::Bottom) => Some(cursor_row.saturating_sub(rows - 1)),
+                None => self.view_top,
+            };
+            self.view_top =
+                top.map(|top| top.clamp(cursor_row.saturating_sub(rows - 1), cursor_row));
+            self.view_top
+        };
+        let extra_rows = view_top.unwrap_or(extra_rows);
+
+        let first_buffer_row = cursor_row.saturating_sub(before_cursor_lines.saturating_sub(1));
+        self.painted_view = Some((
+            extra_rows,
+            extra_rows.saturating_sub(first_buffer_row)
+                ..(extra_rows + rows).saturating_sub(first_buffer_row),
+        ));
+
         // print our prompt with color
         if use_ansi_coloring {
             self.stdout
@@ -345,7 +722,7 @@ impl Painter {
 
         if use_ansi_coloring {
             self.stdout
-                .queue(SetForegroundColor(prompt.get_indicator_color()))?;
+                .queue(SetForegroundColor(lines.indicator_color))?;
         }
         let indicator_skipped = skip_buffer_lines(&lines.prompt_indicator, extra_rows, None);
         self.stdout.queue(Print(&coerce_crlf(indicator_skipped)))?;
@@ -358,13 +735,13 @@ impl Painter {
         // space to print the menu. This will only happen if the cursor is at the last line and
         // it is a large buffer
         let offset = menu.and_then(|menu| {
-            if cursor_distance >= screen_height.saturating_sub(1) {
+            if cursor_distance >= screen_height.saturating_sub(1 + lines.status_lines()) {
                 let rows = lines
                     .before_cursor
                     .lines()
                     .count()
                     .saturating_sub(extra_rows)
-                    .saturating_sub(menu.min_rows() as usize);
+                    .saturating_sub((menu.min_rows() + lines.status_lines()) as usize);
                 Some(rows)
             } else {
                 None
@@ -390,22 +767,55 @@ impl Painter {
             // Selecting lines for the hint
             // The -1 subtraction is done because the remaining lines consider the line where the
             // cursor is located as a remaining line. That has to be removed to get the correct offset
-            // for the after-cursor and hint lines
-            let offset = remaining_lines.saturating_sub(1) as usize;
+            // for the after-cursor and hint lines, as is the line of the status bar
+            let offset = match view_top {
+                Some(top) => (rows - 1).saturating_sub(cursor_row - top),
+                None => remaining_lines
+                    .saturating_sub(1)
+                    .saturating_sub(lines.status_lines()) as usize,
+            };
             // Selecting lines after the cursor
             let after_cursor_skipped = skip_buffer_lines(&lines.after_cursor, 0, Some(offset));
             self.stdout.queue(Print(after_cursor_skipped))?;
             // Hint lines
             let hint_skipped = skip_buffer_lines(&lines.hint, 0, Some(offset));
             self.stdout.queue(Print(hint_skipped))?;
+            self.print_status(lines)?;
         }
 
         Ok(())
     }
 
+    /// The lines of the buffer on the screen, `None` if the whole buffer fits on it
+    pub(crate) fn visible_lines(&self) -> Option<Range<usize>> {
+        self.painted_view.as_ref().map(|(_, lines)| lines.clone())
+    }
+
+    /// Keep the lines of a large buffer where they are on the screen while the cursor moves
+    /// between them, instead of following the cursor on the last row
+    pub(crate) fn hold_view(&mut self) {
+        if let Some((top, _)) = self.painted_view {
+            self.view_top = Some(top);
+        }
+    }
+
+    /// Scroll a large buffer with the next repaint, so the line of the cursor is on the top,
+    /// in the middle or at the bottom of the screen
+    pub(crate) fn scroll_view(&mut self, line: ViewportLine) {
+        if self.large_buffer {
+            self.view_request = Some(line);
+        }
+    }
+
     /// Updates prompt origin and offset to handle a screen resize event
     pub(crate) fn handle_resize(&mut self, width: u16, height: u16) {
         self.terminal_size = (width, height);
+        self.painted_frame = None;
+        if self.reserved_rows != (0, 0) {
+            // The region is set again for the new height, failing to do so only leaves the
+            // reserved rows unprotected
+            let _ = self.queue_scroll_region();
+        }
 
         // `cursor::position() is blocking and can timeout.
         // The question is whether we can afford it. If not, perhaps we should use it in some scenarios but not others
@@ -419,17 +829,29 @@ impl Painter {
         // I assume this is a bug with the position() call but haven't figured that
         // out yet.
         if let Ok(position) = cursor::position() {
-            self.prompt_start_row = position.1;
+            self.prompt_start_row = position
+                .1
+                .clamp(self.reserved_rows.0, self.screen_bottom().saturating_sub(1));
         }
     }
 
     /// Writes `line` to the terminal with a following carriage return and newline
     pub(crate) fn paint_line(&mut self, line: &str) -> Result<()> {
+        self.painted_frame = None;
         self.stdout.queue(Print(line))?.queue(Print("\r\n"))?;
 
         self.stdout.flush()
     }
 
+    /// Prints the text of [`Prompt::render_post_submit`] where
+    /// [`Painter::move_cursor_to_end`] left the cursor, below the submitted line
+    pub(crate) fn paint_post_submit(&mut self, text: &str) -> Result<()> {
+        for line in text.lines() {
+            self.stdout.queue(Print(line))?.queue(Print("\r\n"))?;
+        }
+        self.stdout.flush()
+    }
+
     /// Goes to the beginning of the next line
     ///
     /// Also works in raw mode
@@ -439,6 +861,13 @@ impl Painter {
         self.stdout.flush()
     }
 
+    /// Ring the terminal bell
+    pub(crate) fn ring_bell(&mut self) -> Result<()> {
+        self.stdout.queue(Print('\x07'))?;
+
+        self.stdout.flush()
+    }
+
     /// Clear the screen by printing enough whitespace to start the prompt or
     /// other output back at the first line of the terminal.
     pub(crate) fn clear_screen(&mut self) -> Result<()> {
@@ -447,18 +876,37 @@ impl Painter {
         for _ in 0..2 * num_lines {
             self.stdout.queue(Print("\n"))?;
         }
-        self.stdout.queue(MoveTo(0, 0))?;
+        self.stdout.queue(MoveTo(0, self.reserved_rows.0))?;
         self.stdout.queue(cursor::Show)?;
 
         self.stdout.flush()?;
         self.initialize_prompt_position()
     }
 
+    /// Clear the lines above the prompt, which stays where it is
+    pub(crate) fn clear_above_prompt(&mut self) -> Result<()> {
+        for row in self.reserved_rows.0..self.prompt_start_row {
+            self.stdout
+                .queue(cursor::MoveTo(0, row))?
+                .queue(Clear(ClearType::CurrentLine))?;
+        }
+        self.stdout.flush()
+    }
+
     pub(crate) fn clear_scrollback(&mut self) -> Result<()> {
+        if self.reserved_rows == (0, 0) {
+            self.stdout
+                .queue(crossterm::terminal::Clear(ClearType::All))?;
+        } else {
+            for row in self.reserved_rows.0..self.screen_bottom() {
+                self.stdout
+                    .queue(cursor::MoveTo(0, row))?
+                    .queue(Clear(ClearType::CurrentLine))?;
+            }
+        }
         self.stdout
-            .queue(crossterm::terminal::Clear(ClearType::All))?
             .queue(crossterm::terminal::Clear(ClearType::Purge))?
-            .queue(cursor::MoveTo(0, 0))?
+            .queue(cursor::MoveTo(0, self.reserved_rows.0))?
             .flush()?;
         self.initialize_prompt_position()
     }
@@ -467,12 +915,12 @@ impl Painter {
     // If the prompt is in the middle of a multiline buffer, then the output to stdout
     // could overwrite the buffer writing
     pub(crate) fn move_cursor_to_end(&mut self) -> Result<()> {
-        let final_row = std::cmp::min(
-            self.last_required_lines + self.prompt_start_row,
-            self.screen_height(),
-        );
-        self.stdout.queue(MoveTo(0, final_row))?;
-        if final_row == self.screen_height() {
+        self.painted_frame = None;
+        let bottom = self.screen_bottom();
+        let final_row = std::cmp::min(self.last_required_lines + self.prompt_start_row, bottom);
+        self.stdout
+            .queue(MoveTo(0, final_row.min(bottom.saturating_sub(1))))?;
+        if final_row == bottom {
             self.stdout.queue(Print("\r\n"))?;
         }
         self.stdout.flush()
@@ -489,6 +937,7 @@ impl Painter {
         line_buffer: &LineBuffer,
         prompt: &dyn Prompt,
     ) -> Result<()> {
+        self.painted_frame = None;
         // adding 3 seems to be right for first line-wrap
         let prompt_len = prompt.render_prompt_right().len() + 3;
         let mut buffer_num_lines = 0_u16;
@@ -521,7 +970,7 @@ impl Painter {
             // print causes visible flicker.
             self.stdout.queue(Print(line))?.queue(Print("\r\n"))?;
             let new_start = self.prompt_start_row.saturating_add(1);
-            let height = self.screen_height();
+            let height = self.screen_bottom();
             if new_start >= height {
                 self.prompt_start_row = height - 1;
             } else {
@@ -546,7 +995,8 @@ impl Painter {
     fn queue_universal_scroll(&mut self, num: u16) -> Result<()> {
         // If cursor is not near end of screen printing new will not scroll terminal.
         // Move it to the last line to ensure that every newline results in scroll
-        self.stdout.queue(MoveTo(0, self.screen_height() - 1))?;
+        self.stdout
+            .queue(MoveTo(0, self.screen_bottom().saturating_sub(1)))?;
         for _ in 0..num {
             self.stdout.queue(Print(&coerce_crlf("\n")))?;
         }
@@ -609,4 +1059,130 @@ mod tests {
         assert_eq!(skip_buffer_lines(string, 0, Some(0)), "sentence1",);
         assert_eq!(skip_buffer_lines(string, 1, Some(0)), "sentence2",);
     }
+
+    #[test]
+    fn menus_above_the_prompt_push_the_buffer_down() {
+        use crate::{
+            core_editor::Editor, ColumnarMenu, DefaultCompleter, DefaultPrompt,
+            DefaultPromptSegment, MenuBuilder, MenuEvent, UndoBehavior,
+        };
+
+        let mut painter = Painter::new(W::new(std::io::stderr()));
+        painter.terminal_size = (40, 10);
+        painter.set_diff_repaint(true);
+        let mut editor = Editor::default();
+        editor.set_buffer("ca".to_string(), UndoBehavior::CreateUndoPoint);
+        let mut completer = DefaultCompleter::new(vec!["cargo".into(), "cat".into()]);
+        let mut menu = ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default().with_placement(MenuPlacement::Above),
+        ));
+        menu.menu_event(MenuEvent::Activate(false));
+        Menu::update_working_details(&mut menu, &mut editor, &mut completer, &painter);
+
+        let prompt = DefaultPrompt::new(
+            DefaultPromptSegment::Basic("p".to_string()),
+            DefaultPromptSegment::Empty,
+        );
+        let lines = PromptLines::new(&prompt, PromptEditMode::Default, None, "ca", "", "", "");
+        painter
+            .repaint_buffer(&prompt, &lines, Some(&menu), false, None)
+            .unwrap();
+
+        let frame = painter.painted_frame.as_ref().unwrap();
+        assert_eq!(frame.row_text(0), ">CARGO    cat");
+        assert_eq!(frame.row_text(1), "p〉ca");
+        assert_eq!(painter.prompt_start_row, 0);
+        assert_eq!(painter.last_required_lines, 2);
+    }
+
+    #[test]
+    fn sanitized_menus_keep_their_rows() {
+        use crate::{
+            completion::CompletionUsage, core_editor::Editor, menu::CompletionSources,
+            ColumnarMenu, Completer, DefaultPrompt, DefaultPromptSegment, FileBackedHistory,
+            MenuEvent, Sanitizer, Span, Suggestion,
+        };
+
+        struct UntrustedCompleter;
+
+        impl Completer for UntrustedCompleter {
+            fn complete(&mut self, _line: &str, pos: usize) -> Vec<Suggestion> {
+                ["one\x1b[2J", "two\r", "\x1b[31mthree\x1b[0m"]
+                    .into_iter()
+                    .map(|value| Suggestion {
+                        value: value.to_string(),
+                        span: Span::new(0, pos),
+                        ..Suggestion::default()
+                    })
+                    .collect()
+            }
+        }
+
+        let mut painter = Painter::new(W::new(std::io::stderr()));
+        painter.terminal_size = (40, 10);
+        painter.set_diff_repaint(true);
+        let mut editor = Editor::default();
+        let mut menu =
+            ReedlineMenu::EngineCompleter(Box::new(ColumnarMenu::default().with_columns(1)));
+        let history = FileBackedHistory::default();
+        let sources = CompletionSources {
+            history: &history,
+            usage: &CompletionUsage::default(),
+            context: None,
+            sanitizer: Some(Sanitizer::default().with_styles(false)),
+        };
+        menu.menu_event(MenuEvent::Activate(false));
+        menu.update_working_details(&mut editor, &mut UntrustedCompleter, &sources, &painter);
+
+        let prompt = DefaultPrompt::new(
+            DefaultPromptSegment::Basic("p".to_string()),
+            DefaultPromptSegment::Empty,
+        );
+        let lines = PromptLines::new(&prompt, PromptEditMode::Default, None, "", "", "", "");
+        painter
+            .repaint_buffer(&prompt, &lines, Some(&menu), true, None)
+            .unwrap();
+
+        // Each suggestion starts its own row, and the menu still highlights the selected one
+        let frame = painter.painted_frame.as_ref().unwrap();
+        assert_eq!(frame.row_text(1), "one");
+        assert_eq!(frame.row_text(2), "two");
+        assert_eq!(frame.row_text(3), "three");
+        assert!(menu.menu_string(3, true).contains("\x1b["));
+        assert_eq!(menu.get_values()[2].value, "three");
+    }
+
+    #[test]
+    fn reserved_rows_are_left_out_of_the_screen() {
+        let mut painter = Painter::new(W::new(std::io::stderr()));
+        painter.terminal_size = (40, 10);
+        painter.prompt_start_row = 9;
+        painter.stdout.capture = Some(Vec::new());
+
+        painter.set_reserved_rows(1, 2);
+        painter.apply_reserved_rows().unwrap();
+        assert_eq!(painter.screen_height(), 7);
+        assert_eq!(painter.prompt_start_row, 7);
+        assert_eq!(painter.remaining_lines(), 1);
+
+        painter.set_reserved_rows(0, 0);
+        painter.apply_reserved_rows().unwrap();
+        assert_eq!(painter.screen_height(), 10);
+        let output = String::from_utf8(painter.stdout.capture.take().unwrap()).unwrap();
+        assert!(output.contains("\x1b[2;8r"));
+        assert!(output.contains("\x1b[r"));
+    }
+
+    #[test]
+    fn only_the_lines_above_the_prompt_are_cleared() {
+        let mut painter = Painter::new(W::new(std::io::stderr()));
+        painter.terminal_size = (40, 10);
+        painter.prompt_start_row = 3;
+        painter.stdout.capture = Some(Vec::new());
+
+        painter.clear_above_prompt().unwrap();
+        let output = String::from_utf8(painter.stdout.capture.take().unwrap()).unwrap();
+        assert_eq!(output.matches("\x1b[2K").count(), 3);
+        assert_eq!(painter.prompt_start_row, 3);
+    }
 }
@@ -2,8 +2,9 @@ use super::utils::{coerce_crlf, estimate_required_lines, line_width};
 use crate::{
     menu::{Menu, ReedlineMenu},
     prompt::PromptEditMode,
-    Prompt, PromptHistorySearch,
+    Prompt, PromptHistorySearch, ValidationResult,
 };
+use crossterm::style::Color;
 use std::borrow::Cow;
 
 /// Aggregate of prompt and input string used by `Painter`
@@ -12,9 +13,12 @@ pub(crate) struct PromptLines<'prompt> {
     pub(crate) prompt_str_left: Cow<'prompt, str>,
     pub(crate) prompt_str_right: Cow<'prompt, str>,
     pub(crate) prompt_indicator: Cow<'prompt, str>,
+    pub(crate) indicator_color: Color,
     pub(crate) before_cursor: Cow<'prompt, str>,
     pub(crate) after_cursor: Cow<'prompt, str>,
     pub(crate) hint: Cow<'prompt, str>,
+    /// Single line painted below the buffer, empty if there is no status bar
+    pub(crate) status: Cow<'prompt, str>,
     pub(crate) right_prompt_on_last_line: bool,
 }
 
@@ -29,6 +33,7 @@ impl<'prompt> PromptLines<'prompt> {
         before_cursor: &'prompt str,
         after_cursor: &'prompt str,
         hint: &'prompt str,
+        status: &'prompt str,
     ) -> Self {
         let prompt_str_left = prompt.render_prompt_left();
         let prompt_str_right = prompt.render_prompt_right();
@@ -47,13 +52,28 @@ impl<'prompt> PromptLines<'prompt> {
             prompt_str_left,
             prompt_str_right,
             prompt_indicator,
+            indicator_color: prompt.get_indicator_color(),
             before_cursor,
             after_cursor,
             hint,
+            status: Cow::Borrowed(status),
             right_prompt_on_last_line,
         }
     }
 
+    /// Use the indicator and its color for the live validation of the buffer
+    pub(crate) fn with_validation(
+        mut self,
+        prompt: &'prompt dyn Prompt,
+        prompt_mode: PromptEditMode,
+        validation: ValidationResult,
+    ) -> Self {
+        self.prompt_indicator =
+            prompt.render_prompt_indicator_for_validation(prompt_mode, validation);
+        self.indicator_color = prompt.get_indicator_color_for_validation(validation);
+        self
+    }
+
     /// The required lines to paint the buffer are calculated by counting the
     /// number of newlines in all the strings that form the prompt and buffer.
     /// The plus 1 is to indicate that there should be at least one line.
@@ -71,15 +91,20 @@ impl<'prompt> PromptLines<'prompt> {
                 + &self.after_cursor
         };
 
-        let lines = estimate_required_lines(&input, terminal_columns);
+        let lines = estimate_required_lines(&input, terminal_columns) as u16 + self.status_lines();
 
         if let Some(menu) = menu {
-            lines as u16 + menu.menu_required_lines(terminal_columns)
+            lines + menu.menu_required_lines(terminal_columns)
         } else {
-            lines as u16
+            lines
         }
     }
 
+    /// Lines taken by the status bar below the buffer
+    pub(crate) fn status_lines(&self) -> u16 {
+        u16::from(!self.status.is_empty())
+    }
+
     /// Estimated distance of the cursor to the prompt.
     /// This considers line wrapping
     pub(crate) fn distance_from_prompt(&self, terminal_columns: u16) -> u16 {
@@ -242,9 +267,11 @@ mod tests {
             prompt_str_left: Cow::Borrowed(prompt_str_left),
             prompt_str_right: Cow::Borrowed(""),
             prompt_indicator: Cow::Borrowed(prompt_indicator),
+            indicator_color: Color::Reset,
             before_cursor: Cow::Borrowed(before_cursor),
             after_cursor: Cow::Borrowed(""),
             hint: Cow::Borrowed(""),
+            status: Cow::Borrowed(""),
             right_prompt_on_last_line: false,
         };
 
@@ -252,4 +279,24 @@ mod tests {
 
         assert_eq!(pos, expected);
     }
+
+    #[test]
+    fn status_bar_takes_a_line_below_the_buffer() {
+        let mut prompt_lines = PromptLines {
+            prompt_str_left: Cow::Borrowed("~/path/"),
+            prompt_str_right: Cow::Borrowed(""),
+            prompt_indicator: Cow::Borrowed("❯ "),
+            indicator_color: Color::Reset,
+            before_cursor: Cow::Borrowed("ls"),
+            after_cursor: Cow::Borrowed(" -la"),
+            hint: Cow::Borrowed(""),
+            status: Cow::Borrowed(""),
+            right_prompt_on_last_line: false,
+        };
+        assert_eq!(prompt_lines.required_lines(100, None), 1);
+
+        prompt_lines.status = Cow::Borrowed("NORMAL");
+        assert_eq!(prompt_lines.required_lines(100, None), 2);
+        assert_eq!(prompt_lines.cursor_pos(100), (11, 0));
+    }
 }
@@ -0,0 +1,374 @@
+use crate::{
+    Completer, CompletionError, Prompt, PromptEditMode, PromptHistorySearch, Suggestion,
+    ValidationResult,
+};
+use crossterm::style::Color;
+use std::{any::Any, borrow::Cow, ops::Range};
+
+const ESC: char = '\u{1b}';
+const BEL: char = '\u{7}';
+
+/// Removes the control sequences from text the editor paints but doesn't produce itself
+///
+/// Applies to the prompt, the hints, the menus and the messages of the external printer, see
+/// [`crate::Reedline::with_sanitizer`]. Text that moves the cursor, clears the screen or
+/// changes the modes of the terminal would leave the screen out of sync with what the painter
+/// assumes. Colors and other text styles are kept by default, just like line breaks and tabs.
+///
+/// ```rust
+/// use reedline::Sanitizer;
+///
+/// let sanitizer = Sanitizer::default();
+/// assert_eq!(sanitizer.sanitize("\x1b[2J\x1b[31mred\x1b[0m"), "\x1b[31mred\x1b[0m");
+///
+/// let sanitizer = Sanitizer::default().with_styles(false).with_escaping(true);
+/// assert_eq!(sanitizer.sanitize("\x1b[31mred"), "^[[31mred");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sanitizer {
+    styles: bool,
+    hyperlinks: bool,
+    escaping: bool,
+}
+
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Self {
+            styles: true,
+            hyperlinks: false,
+            escaping: false,
+        }
+    }
+}
+
+impl Sanitizer {
+    /// A builder which keeps the sequences setting colors and other text styles (SGR,
+    /// `ESC [ ... m`), on by default
+    #[must_use]
+    pub fn with_styles(mut self, styles: bool) -> Self {
+        self.styles = styles;
+        self
+    }
+
+    /// A builder which keeps the hyperlinks (OSC 8, `ESC ] 8 ; ... ESC \`), off by default
+    #[must_use]
+    pub fn with_hyperlinks(mut self, hyperlinks: bool) -> Self {
+        self.hyperlinks = hyperlinks;
+        self
+    }
+
+    /// A builder which shows the removed control characters in caret notation (`^[` for
+    /// `ESC`) instead of dropping them with their sequences, off by default
+    #[must_use]
+    pub fn with_escaping(mut self, escaping: bool) -> Self {
+        self.escaping = escaping;
+        self
+    }
+
+    /// `text` without the control sequences that are not allowed
+    pub fn sanitize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !text.chars().any(is_unsafe_control) {
+            return Cow::Borrowed(text);
+        }
+        let mut sanitized = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            if c == ESC {
+                let length = sequence_length(rest);
+                let sequence = &rest[..length];
+                if self.allows(sequence) {
+                    sanitized.push_str(sequence);
+                } else if self.escaping {
+                    sequence
+                        .chars()
+                        .for_each(|c| push_escaped(&mut sanitized, c));
+                }
+                rest = &rest[length..];
+            } else if rest.starts_with("\r\n") {
+                // A line break in raw mode, as the menus separate their rows
+                sanitized.push_str("\r\n");
+                rest = &rest[2..];
+            } else {
+                if !is_unsafe_control(c) {
+                    sanitized.push(c);
+                } else if self.escaping {
+                    push_escaped(&mut sanitized, c);
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        Cow::Owned(sanitized)
+    }
+
+    pub(crate) fn sanitize_cow<'a>(&self, text: Cow<'a, str>) -> Cow<'a, str> {
+        match text {
+            Cow::Borrowed(text) => self.sanitize(text),
+            Cow::Owned(text) if !text.chars().any(is_unsafe_control) => Cow::Owned(text),
+            Cow::Owned(text) => Cow::Owned(self.sanitize(&text).into_owned()),
+        }
+    }
+
+    fn allows(&self, sequence: &str) -> bool {
+        let styles = self.styles && sequence.starts_with("\u{1b}[") && sequence.ends_with('m');
+        let hyperlinks = self.hyperlinks && sequence.starts_with("\u{1b}]8;");
+        styles || hyperlinks
+    }
+}
+
+fn is_unsafe_control(c: char) -> bool {
+    c.is_control() && c != '\n' && c != '\t'
+}
+
+// Shows `c` in caret notation if it's a control character, C1 controls as their `ESC` form
+fn push_escaped(sanitized: &mut String, c: char) {
+    match c as u32 {
+        code @ 0..=0x1f => {
+            sanitized.push('^');
+            sanitized.push(char::from(code as u8 + 0x40));
+        }
+        0x7f => sanitized.push_str("^?"),
+        code @ 0x80..=0x9f => {
+            sanitized.push_str("^[");
+            sanitized.push(char::from(code as u8 - 0x40));
+        }
+        _ => sanitized.push(c),
+    }
+}
+
+// The length of the escape sequence at the start of `text`, which starts with `ESC`
+fn sequence_length(text: &str) -> usize {
+    let mut chars = text.char_indices().skip(1);
+    match chars.next() {
+        // Control Sequence: parameters and intermediates up to a final byte
+        Some((_, '[')) => chars
+            .find(|(_, c)| ('\u{40}'..='\u{7e}').contains(c))
+            .map_or(text.len(), |(index, c)| index + c.len_utf8()),
+        // Operating System Command and other strings, up to `BEL` or `ESC \`
+        Some((_, ']' | 'P' | '_' | '^' | 'X')) => {
+            let mut previous = ' ';
+            chars
+                .find(|(_, c)| {
+                    let end = *c == BEL || (previous == ESC && *c == '\\');
+                    previous = *c;
+                    end
+                })
+                .map_or(text.len(), |(index, c)| index + c.len_utf8())
+        }
+        Some((index, c)) => index + c.len_utf8(),
+        None => text.len(),
+    }
+}
+
+/// A [`Prompt`] painting the text of another with the control sequences removed
+pub(crate) struct SanitizedPrompt<'prompt> {
+    prompt: &'prompt dyn Prompt,
+    sanitizer: Sanitizer,
+}
+
+// Safe to implement Send since the prompt is only wrapped for painting, which happens on the
+// thread the wrapped prompt is borrowed on
+unsafe impl<'prompt> Send for SanitizedPrompt<'prompt> {}
+
+impl<'prompt> SanitizedPrompt<'prompt> {
+    pub(crate) fn new(prompt: &'prompt dyn Prompt, sanitizer: Sanitizer) -> Self {
+        Self { prompt, sanitizer }
+    }
+}
+
+impl<'prompt> Prompt for SanitizedPrompt<'prompt> {
+    fn render_prompt_left(&self) -> Cow<'_, str> {
+        self.sanitizer
+            .sanitize_cow(self.prompt.render_prompt_left())
+    }
+
+    fn render_prompt_right(&self) -> Cow<'_, str> {
+        self.sanitizer
+            .sanitize_cow(self.prompt.render_prompt_right())
+    }
+
+    fn render_prompt_indicator(&self, prompt_mode: PromptEditMode) -> Cow<'_, str> {
+        self.sanitizer
+            .sanitize_cow(self.prompt.render_prompt_indicator(prompt_mode))
+    }
+
+    fn render_prompt_indicator_for_validation(
+        &self,
+        prompt_mode: PromptEditMode,
+        validation: ValidationResult,
+    ) -> Cow<'_, str> {
+        self.sanitizer.sanitize_cow(
+            self.prompt
+                .render_prompt_indicator_for_validation(prompt_mode, validation),
+        )
+    }
+
+    fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
+        self.sanitizer
+            .sanitize_cow(self.prompt.render_prompt_multiline_indicator())
+    }
+
+    fn render_prompt_history_search_indicator(
+        &self,
+        history_search: PromptHistorySearch,
+    ) -> Cow<'_, str> {
+        self.sanitizer.sanitize_cow(
+            self.prompt
+                .render_prompt_history_search_indicator(history_search),
+        )
+    }
+
+    fn get_prompt_color(&self) -> Color {
+        self.prompt.get_prompt_color()
+    }
+
+    fn get_prompt_multiline_color(&self) -> nu_ansi_term::Color {
+        self.prompt.get_prompt_multiline_color()
+    }
+
+    fn get_indicator_color(&self) -> Color {
+        self.prompt.get_indicator_color()
+    }
+
+    fn get_indicator_color_for_validation(&self, validation: ValidationResult) -> Color {
+        self.prompt.get_indicator_color_for_validation(validation)
+    }
+
+    fn get_prompt_right_color(&self) -> Color {
+        self.prompt.get_prompt_right_color()
+    }
+
+    fn right_prompt_on_last_line(&self) -> bool {
+        self.prompt.right_prompt_on_last_line()
+    }
+
+    fn render_post_submit(&self) -> Cow<'_, str> {
+        self.sanitizer
+            .sanitize_cow(self.prompt.render_post_submit())
+    }
+}
+
+/// A [`Completer`] returning the suggestions of another one with the control sequences
+/// removed from their text, before a menu lays them out
+pub(crate) struct SanitizedCompleter<'a> {
+    completer: &'a mut dyn Completer,
+    sanitizer: Sanitizer,
+}
+
+impl<'a> SanitizedCompleter<'a> {
+    pub(crate) fn new(completer: &'a mut dyn Completer, sanitizer: Sanitizer) -> Self {
+        Self {
+            completer,
+            sanitizer,
+        }
+    }
+
+    fn sanitize_all(&self, mut suggestions: Vec<Suggestion>) -> Vec<Suggestion> {
+        for suggestion in &mut suggestions {
+            self.sanitize_suggestion(suggestion);
+        }
+        suggestions
+    }
+
+    fn sanitize_suggestion(&self, suggestion: &mut Suggestion) {
+        let sanitize = |text: &mut String| {
+            if let Cow::Owned(sanitized) = self.sanitizer.sanitize(text) {
+                *text = sanitized;
+            }
+        };
+        sanitize(&mut suggestion.value);
+        suggestion.description.iter_mut().for_each(sanitize);
+        suggestion.extra.iter_mut().flatten().for_each(sanitize);
+        suggestion.link.iter_mut().for_each(sanitize);
+    }
+}
+
+impl<'a> Completer for SanitizedCompleter<'a> {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let suggestions = self.completer.complete(line, pos);
+        self.sanitize_all(suggestions)
+    }
+
+    fn complete_in_context(
+        &mut self,
+        line: &str,
+        pos: usize,
+        context: &dyn Any,
+    ) -> Vec<Suggestion> {
+        let suggestions = self.completer.complete_in_context(line, pos, context);
+        self.sanitize_all(suggestions)
+    }
+
+    fn complete_with_base_ranges(
+        &mut self,
+        line: &str,
+        pos: usize,
+    ) -> (Vec<Suggestion>, Vec<Range<usize>>) {
+        let (suggestions, ranges) = self.completer.complete_with_base_ranges(line, pos);
+        (self.sanitize_all(suggestions), ranges)
+    }
+
+    fn try_complete_with_base_ranges(
+        &mut self,
+        line: &str,
+        pos: usize,
+    ) -> Result<(Vec<Suggestion>, Vec<Range<usize>>), CompletionError> {
+        let (suggestions, ranges) = self.completer.try_complete_with_base_ranges(line, pos)?;
+        Ok((self.sanitize_all(suggestions), ranges))
+    }
+
+    fn partial_complete(
+        &mut self,
+        line: &str,
+        pos: usize,
+        start: usize,
+        offset: usize,
+    ) -> Vec<Suggestion> {
+        let suggestions = self.completer.partial_complete(line, pos, start, offset);
+        self.sanitize_all(suggestions)
+    }
+
+    fn total_completions(&mut self, line: &str, pos: usize) -> usize {
+        self.completer.total_completions(line, pos)
+    }
+
+    fn is_pending(&self) -> bool {
+        self.completer.is_pending()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("plain text\n\twith breaks", "plain text\n\twith breaks")]
+    #[case("\x1b[31mred\x1b[0m", "\x1b[31mred\x1b[0m")]
+    #[case("\x1b[2J\x1b[Hcleared", "cleared")]
+    #[case("\x1b]0;title\x07\x1b]8;;http://x\x1b\\link", "link")]
+    #[case("bell\x07 and\r return", "bell and return")]
+    #[case("rows\r\nin raw mode\n\r", "rows\r\nin raw mode\n")]
+    #[case("\x1bcreset", "reset")]
+    #[case("\u{9b}2J\u{8d}c1", "2Jc1")]
+    #[case("cut off \x1b[3", "cut off ")]
+    fn unsafe_sequences_are_removed(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(Sanitizer::default().sanitize(text), expected);
+    }
+
+    #[test]
+    fn the_allowlist_is_configurable() {
+        let text = "\x1b[1mbold\x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\\x1b[?25l";
+        let sanitizer = Sanitizer::default().with_hyperlinks(true);
+        assert_eq!(
+            sanitizer.sanitize(text),
+            "\x1b[1mbold\x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\"
+        );
+
+        let sanitizer = Sanitizer::default().with_styles(false).with_escaping(true);
+        assert_eq!(
+            sanitizer.sanitize("\x1b[1mbold\x1b[?25l\r\u{9b}"),
+            "^[[1mbold^[[?25l^M^[["
+        );
+    }
+}
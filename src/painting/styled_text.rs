@@ -1,14 +1,102 @@
-use nu_ansi_term::Style;
+use std::{fmt::Write, ops::Range};
 
-use crate::Prompt;
+use nu_ansi_term::{Color, Style};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{terminal_extensions::extended_underlines_supported, Prompt};
 
 use super::utils::strip_ansi;
 
 /// A representation of a buffer with styling, used for doing syntax highlighting
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct StyledText {
     /// The component, styled parts of the text
     pub buffer: Vec<(Style, String)>,
+    /// Byte ranges of the text that are underlined beyond what [`Style`] can express, see
+    /// [`StyledText::underline_range`]
+    pub underlines: Vec<(Range<usize>, Underline)>,
+    /// Byte ranges of the text that are hyperlinks to the given targets, see
+    /// [`StyledText::link_range`]
+    pub links: Vec<(Range<usize>, String)>,
+}
+
+/// An underline with a color of its own or drawn twice, e.g. to mark diagnostics without
+/// changing the color of the text
+///
+/// Only some terminals (kitty, WezTerm, foot, Alacritty, iTerm2, VTE based ones, ...) draw
+/// these. The others get a plain underline in the color of the text. Blinking text needs no
+/// support of its own, it is [`Style::blink`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Underline {
+    /// The color of the underline, the color of the text if `None`
+    pub color: Option<Color>,
+    /// Draw two lines instead of one
+    pub double: bool,
+}
+
+impl Underline {
+    /// A single underline in the color of the text
+    pub const fn new() -> Self {
+        Self {
+            color: None,
+            double: false,
+        }
+    }
+
+    /// A builder which sets the color of the underline
+    #[must_use]
+    pub const fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// A builder which draws the underline twice
+    #[must_use]
+    pub const fn with_double(mut self, double: bool) -> Self {
+        self.double = double;
+        self
+    }
+
+    // The parameters of the SGR sequence drawing the underline
+    fn write_sgr(&self, out: &mut String, extended: bool) {
+        if !extended {
+            out.push_str("\x1b[4m");
+            return;
+        }
+        out.push_str(if self.double { "\x1b[4:2" } else { "\x1b[4" });
+        let _ = match self.color {
+            None => Ok(()),
+            Some(Color::Fixed(index)) => write!(out, ";58:5:{index}"),
+            Some(Color::Rgb(r, g, b)) => write!(out, ";58:2::{r}:{g}:{b}"),
+            Some(Color::Default) => write!(out, ";59"),
+            Some(color) => write!(out, ";58:5:{}", palette_index(color)),
+        };
+        out.push('m');
+    }
+}
+
+// The index of a named color in the 256 color palette
+fn palette_index(color: Color) -> u8 {
+    match color {
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Purple | Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::White => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightPurple | Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::LightGray => 15,
+        Color::Fixed(index) => index,
+        Color::Rgb(..) | Color::Default => 7,
+    }
 }
 
 impl Default for StyledText {
@@ -20,7 +108,11 @@ impl Default for StyledText {
 impl StyledText {
     /// Construct a new `StyledText`
     pub const fn new() -> Self {
-        Self { buffer: vec![] }
+        Self {
+            buffer: vec![],
+            underlines: vec![],
+            links: vec![],
+        }
     }
 
     /// Add a new styled string to the buffer
@@ -28,6 +120,121 @@ impl StyledText {
         self.buffer.push(styled_string);
     }
 
+    /// Paint `style` over all parts, keeping what it does not set
+    pub(crate) fn overlay_style(&mut self, style: Style) {
+        for (part_style, _) in &mut self.buffer {
+            *part_style = overlay(*part_style, style);
+        }
+    }
+
+    /// Underline the bytes from `from` to `to` with `underline`, on top of their style
+    ///
+    /// Where underlines overlap the one added last is drawn.
+    pub fn underline_range(&mut self, from: usize, to: usize, underline: Underline) {
+        let (from, to) = if from > to { (to, from) } else { (from, to) };
+        if from < to {
+            self.underlines.push((from..to, underline));
+        }
+    }
+
+    /// Make the bytes from `from` to `to` a hyperlink to `target`, e.g. a `file://` or
+    /// `https://` URL
+    ///
+    /// The link is painted as an OSC 8 sequence, which terminals that don't support it
+    /// ignore. Where links overlap the one added last is painted. A [`crate::Sanitizer`]
+    /// only keeps them [`crate::Sanitizer::with_hyperlinks`].
+    pub fn link_range(&mut self, from: usize, to: usize, target: impl Into<String>) {
+        let (from, to) = if from > to { (to, from) } else { (from, to) };
+        if from < to {
+            self.links.push((from..to, target.into()));
+        }
+    }
+
+    // The parts of the text, split further where underlines or links start or end
+    fn segments(&self) -> Vec<(Paint<'_>, &str)> {
+        let extended = !self.underlines.is_empty() && extended_underlines_supported();
+        let ranges = || {
+            let underlines = self.underlines.iter().map(|(range, _)| range);
+            underlines.chain(self.links.iter().map(|(range, _)| range))
+        };
+        let mut segments = Vec::with_capacity(self.buffer.len());
+        let mut start = 0;
+        for (style, text) in &self.buffer {
+            let end = start + text.len();
+            let mut boundaries: Vec<_> = ranges()
+                .flat_map(|range| [range.start, range.end])
+                .filter(|idx| *idx > start && *idx < end && text.is_char_boundary(idx - start))
+                .chain([start, end])
+                .collect();
+            boundaries.sort_unstable();
+            boundaries.dedup();
+            for segment in boundaries.windows(2) {
+                let covering = |range: &Range<usize>| range.contains(&segment[0]);
+                let paint = Paint {
+                    style,
+                    underline: self
+                        .underlines
+                        .iter()
+                        .rev()
+                        .find(|(range, _)| covering(range))
+                        .map(|(_, underline)| underline),
+                    link: self
+                        .links
+                        .iter()
+                        .rev()
+                        .find(|(range, _)| covering(range))
+                        .map(|(_, target)| target.as_str()),
+                    extended,
+                };
+                segments.push((paint, &text[segment[0] - start..segment[1] - start]));
+            }
+            if text.is_empty() {
+                segments.push((Paint::new(style), text));
+            }
+            start = end;
+        }
+        segments
+    }
+
+    /// Replace the bytes in `range` by `replacement`, dropping the underlines and links
+    /// within them
+    pub(crate) fn replace_range(&mut self, range: Range<usize>, replacement: (Style, String)) {
+        let shift = |index: usize| index - range.len() + replacement.1.len();
+        let keep =
+            |covered: &Range<usize>| covered.end <= range.start || covered.start >= range.end;
+        self.underlines.retain(|(covered, _)| keep(covered));
+        self.links.retain(|(covered, _)| keep(covered));
+        for covered in self
+            .underlines
+            .iter_mut()
+            .map(|(covered, _)| covered)
+            .chain(self.links.iter_mut().map(|(covered, _)| covered))
+            .filter(|covered| covered.start >= range.end)
+        {
+            *covered = shift(covered.start)..shift(covered.end);
+        }
+
+        let mut parts = Vec::with_capacity(self.buffer.len() + 2);
+        let mut replacement = Some(replacement);
+        let mut start = 0;
+        for (style, text) in self.buffer.drain(..) {
+            let end = start + text.len();
+            if start < range.start {
+                parts.push((style, text[..range.start.min(end) - start].to_string()));
+            }
+            if range.start < end {
+                parts.extend(replacement.take());
+            }
+            if range.end < end {
+                parts.push((style, text[range.end.max(start) - start..].to_string()));
+            }
+            start = end;
+        }
+        parts.extend(replacement);
+        parts.retain(|(_, text)| !text.is_empty());
+        self.buffer = parts;
+    }
+
     /// Style range with the provided style
     pub fn style_range(&mut self, from: usize, to: usize, new_style: Style) {
         let (from, to) = if from > to { (to, from) } else { (from, to) };
@@ -103,80 +310,205 @@ impl StyledText {
         // multiline_prompt: &str,
         use_ansi_coloring: bool,
     ) -> (String, String) {
-        let mut current_idx = 0;
         let mut left_string = String::new();
         let mut right_string = String::new();
+        self.render_around_insertion_point_into(
+            insertion_point,
+            prompt,
+            use_ansi_coloring,
+            &mut left_string,
+            &mut right_string,
+        );
+        (left_string, right_string)
+    }
+
+    /// Like [`StyledText::render_around_insertion_point`], rendering into the given
+    /// strings, so that their memory can be reused from one repaint to the next
+    pub(crate) fn render_around_insertion_point_into(
+        &self,
+        insertion_point: usize,
+        prompt: &dyn Prompt,
+        use_ansi_coloring: bool,
+        left_string: &mut String,
+        right_string: &mut String,
+    ) {
+        left_string.clear();
+        right_string.clear();
 
         let multiline_prompt = prompt.render_prompt_multiline_indicator();
-        let prompt_style = Style::new().fg(prompt.get_prompt_multiline_color());
+        let prompt_style = if use_ansi_coloring {
+            Style::new().fg(prompt.get_prompt_multiline_color())
+        } else {
+            Style::new()
+        };
+        let plain = Paint::new(&prompt_style);
+        let render = |out: &mut String, paint: &Paint, text: &str| {
+            let paint = if use_ansi_coloring { paint } else { &plain };
+            render_into(out, paint, text, &prompt_style, &multiline_prompt);
+        };
 
-        for pair in &self.buffer {
+        let mut current_idx = 0;
+        for (paint, text) in self.segments() {
             if current_idx >= insertion_point {
-                right_string.push_str(&render_as_string(pair, &prompt_style, &multiline_prompt));
-            } else if pair.1.len() + current_idx <= insertion_point {
-                left_string.push_str(&render_as_string(pair, &prompt_style, &multiline_prompt));
-            } else if pair.1.len() + current_idx > insertion_point {
-                let offset = insertion_point - current_idx;
-
-                let left_side = pair.1[..offset].to_string();
-                let right_side = pair.1[offset..].to_string();
-
-                left_string.push_str(&render_as_string(
-                    &(pair.0, left_side),
-                    &prompt_style,
-                    &multiline_prompt,
-                ));
-                right_string.push_str(&render_as_string(
-                    &(pair.0, right_side),
-                    &prompt_style,
-                    &multiline_prompt,
-                ));
+                render(right_string, &paint, text);
+            } else if text.len() + current_idx <= insertion_point {
+                render(left_string, &paint, text);
+            } else {
+                let (left_side, right_side) = text.split_at(insertion_point - current_idx);
+                render(left_string, &paint, left_side);
+                render(right_string, &paint, right_side);
             }
-            current_idx += pair.1.len();
+            current_idx += text.len();
         }
 
-        if use_ansi_coloring {
-            (left_string, right_string)
-        } else {
-            (strip_ansi(&left_string), strip_ansi(&right_string))
+        // Escape sequences typed or pasted into the buffer are not printed either
+        if !use_ansi_coloring {
+            for string in [left_string, right_string] {
+                if string.contains('\x1b') {
+                    *string = strip_ansi(string);
+                }
+            }
         }
     }
 
     /// Apply the ANSI style formatting to the full string.
     pub fn render_simple(&self) -> String {
-        self.buffer
-            .iter()
-            .map(|(style, text)| style.paint(text).to_string())
-            .collect()
+        let mut rendered = String::new();
+        for (paint, text) in self.segments() {
+            paint.write(&mut rendered, text);
+        }
+        rendered
     }
 
     /// Get the unformatted text as a single continuous string.
     pub fn raw_string(&self) -> String {
         self.buffer.iter().map(|(_, str)| str.as_str()).collect()
     }
+
+    /// Renders the text as a single line at most `max_width` columns wide, cutting off
+    /// what doesn't fit and replacing line breaks with spaces
+    pub(crate) fn render_single_line(&self, max_width: usize, use_ansi_coloring: bool) -> String {
+        let mut rendered = String::new();
+        let mut width = 0;
+        for (paint, text) in self.segments() {
+            let mut fitting = String::new();
+            let mut truncated = false;
+            for c in strip_ansi(text).chars() {
+                let c = if c == '\n' || c == '\r' || c == '\t' {
+                    ' '
+                } else {
+                    c
+                };
+                let char_width = c.width().unwrap_or(0);
+                if width + char_width > max_width {
+                    truncated = true;
+                    break;
+                }
+                width += char_width;
+                fitting.push(c);
+            }
+            if use_ansi_coloring {
+                paint.write(&mut rendered, &fitting);
+            } else {
+                rendered.push_str(&fitting);
+            }
+            if truncated {
+                break;
+            }
+        }
+        rendered
+    }
+}
+
+// How a segment of the text is painted
+struct Paint<'a> {
+    style: &'a Style,
+    underline: Option<&'a Underline>,
+    link: Option<&'a str>,
+    // Whether the terminal draws colored and double underlines
+    extended: bool,
+}
+
+impl<'a> Paint<'a> {
+    fn new(style: &'a Style) -> Self {
+        Self {
+            style,
+            underline: None,
+            link: None,
+            extended: false,
+        }
+    }
+
+    fn write(&self, out: &mut String, text: &str) {
+        match self.link {
+            Some(target) if !text.is_empty() => {
+                let _ = write!(out, "\x1b]8;;{target}\x1b\\");
+                self.write_styled(out, text);
+                out.push_str("\x1b]8;;\x1b\\");
+            }
+            _ => self.write_styled(out, text),
+        }
+    }
+
+    fn write_styled(&self, out: &mut String, text: &str) {
+        match self.underline {
+            Some(underline) if !text.is_empty() => {
+                let _ = write!(out, "{}", self.style.prefix());
+                underline.write_sgr(out, self.extended);
+                let _ = write!(out, "{text}\x1b[0m");
+            }
+            _ => {
+                let _ = write!(out, "{}", self.style.paint(text));
+            }
+        }
+    }
 }
 
-fn render_as_string(
-    renderable: &(Style, String),
+// Writes `text` painted with `paint` to `out`, continuing every line after the first
+// with the multiline prompt
+fn render_into(
+    out: &mut String,
+    paint: &Paint,
+    text: &str,
     prompt_style: &Style,
     multiline_prompt: &str,
-) -> String {
-    let mut rendered = String::new();
-    let formatted_multiline_prompt = format!("\n{multiline_prompt}");
-    for (line_number, line) in renderable.1.split('\n').enumerate() {
+) {
+    for (line_number, line) in text.split('\n').enumerate() {
         if line_number != 0 {
-            rendered.push_str(&prompt_style.paint(&formatted_multiline_prompt).to_string());
+            let _ = write!(
+                out,
+                "{}\n{multiline_prompt}{}",
+                prompt_style.prefix(),
+                prompt_style.suffix()
+            );
         }
-        rendered.push_str(&renderable.0.paint(line).to_string());
+        paint.write(out, line);
+    }
+}
+
+/// `top` painted over `base`, keeping what `top` does not set
+pub(crate) fn overlay(base: Style, top: Style) -> Style {
+    Style {
+        foreground: top.foreground.or(base.foreground),
+        background: top.background.or(base.background),
+        is_bold: base.is_bold || top.is_bold,
+        is_dimmed: base.is_dimmed || top.is_dimmed,
+        is_italic: base.is_italic || top.is_italic,
+        is_underline: base.is_underline || top.is_underline,
+        is_blink: base.is_blink || top.is_blink,
+        is_reverse: base.is_reverse || top.is_reverse,
+        is_hidden: base.is_hidden || top.is_hidden,
+        is_strikethrough: base.is_strikethrough || top.is_strikethrough,
+        prefix_with_reset: base.prefix_with_reset || top.prefix_with_reset,
     }
-    rendered
 }
 
 #[cfg(test)]
 mod test {
     use nu_ansi_term::{Color, Style};
 
-    use crate::StyledText;
+    use super::{Paint, Underline};
+    use crate::{Prompt, StyledText};
 
     fn get_styled_text_template() -> (super::StyledText, Style, Style) {
         let before_style = Style::new().on(Color::Black);
@@ -188,6 +520,7 @@ mod test {
                     (before_style, "bbb".into()),
                     (before_style, "ccc".into()),
                 ],
+                ..StyledText::new()
             },
             before_style,
             after_style,
@@ -239,6 +572,7 @@ mod test {
         let (_, before_style, after_style) = get_styled_text_template();
         let mut styled_text = StyledText {
             buffer: vec![(before_style, "asdf".into())],
+            ..StyledText::new()
         };
         styled_text.style_range(3, 4, after_style);
         assert_eq!(styled_text.buffer[0], (before_style, "asd".into()));
@@ -249,6 +583,7 @@ mod test {
         let (_, before_style, after_style) = get_styled_text_template();
         let mut styled_text = StyledText {
             buffer: vec![(before_style, "asdf".into())],
+            ..StyledText::new()
         };
         styled_text.style_range(2, 3, after_style);
         assert_eq!(styled_text.buffer[0], (before_style, "as".into()));
@@ -264,6 +599,7 @@ mod test {
                 (before_style, " ".into()),
                 (before_style, "run".into()),
             ],
+            ..StyledText::new()
         };
         styled_text.style_range(8, 7, after_style);
         assert_eq!(styled_text.buffer[0], (before_style, "cargo".into()));
@@ -272,4 +608,144 @@ mod test {
         assert_eq!(styled_text.buffer[3], (after_style, "u".into()));
         assert_eq!(styled_text.buffer[4], (before_style, "n".into()));
     }
+
+    #[test]
+    fn render_around_insertion_point_continues_lines_with_the_multiline_prompt() {
+        let prompt = crate::DefaultPrompt::default();
+        let style = Style::new().fg(Color::Red);
+        let multiline_style = Style::new().fg(prompt.get_prompt_multiline_color());
+        let styled_text = StyledText {
+            buffer: vec![(style, "ab\ncd".into()), (Style::new(), "ef".into())],
+            ..StyledText::new()
+        };
+
+        let (left, right) = styled_text.render_around_insertion_point(4, &prompt, true);
+        assert_eq!(
+            left,
+            format!(
+                "{}{}{}",
+                style.paint("ab"),
+                multiline_style.paint("\n::: "),
+                style.paint("c")
+            )
+        );
+        assert_eq!(right, format!("{}ef", style.paint("d")));
+
+        let (left, right) = styled_text.render_around_insertion_point(4, &prompt, false);
+        assert_eq!(left, "ab\n::: c");
+        assert_eq!(right, "def");
+    }
+
+    #[test]
+    fn underlines_split_the_parts_they_cover() {
+        let style = Style::new().fg(Color::Red);
+        let mut styled_text = StyledText {
+            buffer: vec![(style, "abc".into()), (Style::new(), "de".into())],
+            ..StyledText::new()
+        };
+        let underline = Underline::new().with_color(Color::Yellow);
+        styled_text.underline_range(4, 1, underline);
+
+        let segments: Vec<_> = styled_text
+            .segments()
+            .into_iter()
+            .map(|(paint, text)| (paint.underline.is_some(), text))
+            .collect();
+        assert_eq!(
+            segments,
+            [(false, "a"), (true, "bc"), (true, "d"), (false, "e")]
+        );
+    }
+
+    #[test]
+    fn underlines_fall_back_to_plain_ones() {
+        let style = Style::new().fg(Color::Red);
+        let underline = Underline::new().with_color(Color::Yellow).with_double(true);
+        let paint = |extended| {
+            let mut out = String::new();
+            Paint {
+                underline: Some(&underline),
+                extended,
+                ..Paint::new(&style)
+            }
+            .write(&mut out, "x");
+            out
+        };
+        assert_eq!(paint(true), "\x1b[31m\x1b[4:2;58:5:3mx\x1b[0m");
+        assert_eq!(paint(false), "\x1b[31m\x1b[4mx\x1b[0m");
+
+        let mut out = String::new();
+        Underline::new()
+            .with_color(Color::Rgb(1, 2, 3))
+            .write_sgr(&mut out, true);
+        assert_eq!(out, "\x1b[4;58:2::1:2:3m");
+    }
+
+    #[test]
+    fn links_are_painted_around_their_text() {
+        let style = Style::new().fg(Color::Blue);
+        let mut styled_text = StyledText {
+            buffer: vec![(Style::new(), "cat ".into()), (style, "a.txt\nb".into())],
+            ..StyledText::new()
+        };
+        styled_text.link_range(4, 9, "file:///a.txt");
+
+        assert_eq!(
+            styled_text.render_simple(),
+            format!(
+                "cat \x1b]8;;file:///a.txt\x1b\\{}\x1b]8;;\x1b\\{}",
+                style.paint("a.txt"),
+                style.paint("\nb")
+            )
+        );
+        let prompt = crate::DefaultPrompt::default();
+        let (left, right) = styled_text.render_around_insertion_point(11, &prompt, false);
+        assert_eq!(left, "cat a.txt\n::: b");
+        assert_eq!(right, "");
+    }
+
+    #[test]
+    fn replacing_a_range_keeps_the_styles_around_it() {
+        let style = Style::new().fg(Color::Blue);
+        let marker = Style::new().fg(Color::DarkGray);
+        let mut styled_text = StyledText {
+            buffer: vec![(Style::new(), "cat a".into()), (style, "b\nc d".into())],
+            ..StyledText::new()
+        };
+        styled_text.link_range(2, 5, "file:///b");
+        styled_text.link_range(9, 10, "file:///d");
+        styled_text.replace_range(4..8, (marker, "..".into()));
+
+        assert_eq!(
+            styled_text.buffer,
+            vec![
+                (Style::new(), "cat ".into()),
+                (marker, "..".into()),
+                (style, " d".into())
+            ]
+        );
+        assert_eq!(styled_text.links, vec![(7..8, "file:///d".into())]);
+    }
+
+    #[test]
+    fn render_single_line_truncates_to_the_width() {
+        let style = Style::new().fg(Color::Green);
+        let styled_text = StyledText {
+            buffer: vec![
+                (style, "NORMAL".into()),
+                (Style::new(), " 3\nmatches".into()),
+            ],
+            ..StyledText::new()
+        };
+
+        assert_eq!(
+            styled_text.render_single_line(10, true),
+            format!("{}{}", style.paint("NORMAL"), Style::new().paint(" 3 m"))
+        );
+        assert_eq!(styled_text.render_single_line(4, false), "NORM");
+        assert_eq!(
+            styled_text.render_single_line(40, false),
+            "NORMAL 3 matches"
+        );
+    }
 }
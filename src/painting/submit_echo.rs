@@ -0,0 +1,146 @@
+use crate::{Prompt, PromptEditMode, PromptHistorySearch, ValidationResult};
+use chrono::Local;
+use crossterm::style::Color;
+use std::borrow::Cow;
+
+/// How a submitted line is painted once more before the output of the application, see
+/// [`crate::Reedline::with_submit_echo`]
+///
+/// The line is highlighted as a whole, with the cursor at its end and without the selection,
+/// the hints or the menus. A timestamp can take the place of the right prompt.
+///
+/// ```rust
+/// use reedline::SubmitEcho;
+///
+/// let echo = SubmitEcho::new().with_timestamp("%H:%M:%S");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubmitEcho {
+    timestamp: Option<String>,
+}
+
+impl SubmitEcho {
+    /// An echo keeping the right prompt
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder which paints the time of the submission, formatted with the `chrono`
+    /// `format`, right-aligned on the last line instead of the right prompt
+    #[must_use]
+    pub fn with_timestamp(mut self, format: impl Into<String>) -> Self {
+        self.timestamp = Some(format.into());
+        self
+    }
+
+    pub(crate) fn render_timestamp(&self) -> Option<String> {
+        let format = self.timestamp.as_ref()?;
+        Some(Local::now().format(format).to_string())
+    }
+}
+
+/// A [`Prompt`] painting another one with the timestamp of a [`SubmitEcho`] as its right
+/// prompt, if there is one
+pub(crate) struct EchoPrompt<'prompt> {
+    prompt: &'prompt dyn Prompt,
+    timestamp: Option<String>,
+}
+
+// Safe to implement Send since the prompt is only wrapped for painting, which happens on the
+// thread the wrapped prompt is borrowed on
+unsafe impl<'prompt> Send for EchoPrompt<'prompt> {}
+
+impl<'prompt> EchoPrompt<'prompt> {
+    pub(crate) fn new(prompt: &'prompt dyn Prompt, echo: &SubmitEcho) -> Self {
+        Self {
+            prompt,
+            timestamp: echo.render_timestamp(),
+        }
+    }
+}
+
+impl<'prompt> Prompt for EchoPrompt<'prompt> {
+    fn render_prompt_left(&self) -> Cow<'_, str> {
+        self.prompt.render_prompt_left()
+    }
+
+    fn render_prompt_right(&self) -> Cow<'_, str> {
+        match &self.timestamp {
+            Some(timestamp) => timestamp.into(),
+            None => self.prompt.render_prompt_right(),
+        }
+    }
+
+    fn render_prompt_indicator(&self, prompt_mode: PromptEditMode) -> Cow<'_, str> {
+        self.prompt.render_prompt_indicator(prompt_mode)
+    }
+
+    fn render_prompt_indicator_for_validation(
+        &self,
+        prompt_mode: PromptEditMode,
+        validation: ValidationResult,
+    ) -> Cow<'_, str> {
+        self.prompt
+            .render_prompt_indicator_for_validation(prompt_mode, validation)
+    }
+
+    fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
+        self.prompt.render_prompt_multiline_indicator()
+    }
+
+    fn render_prompt_history_search_indicator(
+        &self,
+        history_search: PromptHistorySearch,
+    ) -> Cow<'_, str> {
+        self.prompt
+            .render_prompt_history_search_indicator(history_search)
+    }
+
+    fn get_prompt_color(&self) -> Color {
+        self.prompt.get_prompt_color()
+    }
+
+    fn get_prompt_multiline_color(&self) -> nu_ansi_term::Color {
+        self.prompt.get_prompt_multiline_color()
+    }
+
+    fn get_indicator_color(&self) -> Color {
+        self.prompt.get_indicator_color()
+    }
+
+    fn get_indicator_color_for_validation(&self, validation: ValidationResult) -> Color {
+        self.prompt.get_indicator_color_for_validation(validation)
+    }
+
+    fn get_prompt_right_color(&self) -> Color {
+        self.prompt.get_prompt_right_color()
+    }
+
+    fn right_prompt_on_last_line(&self) -> bool {
+        self.timestamp.is_some() || self.prompt.right_prompt_on_last_line()
+    }
+
+    fn render_post_submit(&self) -> Cow<'_, str> {
+        self.prompt.render_post_submit()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::DefaultPrompt;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn the_timestamp_replaces_the_right_prompt() {
+        let prompt = DefaultPrompt::default();
+        let echo = EchoPrompt::new(&prompt, &SubmitEcho::new().with_timestamp("[%Y]"));
+        assert_eq!(echo.render_prompt_right().len(), 6);
+        assert!(echo.right_prompt_on_last_line());
+        assert_eq!(echo.render_prompt_left(), prompt.render_prompt_left());
+
+        let echo = EchoPrompt::new(&prompt, &SubmitEcho::new());
+        assert!(!echo.render_prompt_right().starts_with('['));
+        assert!(!echo.right_prompt_on_last_line());
+    }
+}
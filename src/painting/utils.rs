@@ -5,7 +5,7 @@ use unicode_width::UnicodeWidthStr;
 ///
 /// Needed for correct output in raw mode.
 /// Only replaces solitary LF with CRLF.
-pub(crate) fn coerce_crlf(input: &str) -> Cow<str> {
+pub(crate) fn coerce_crlf(input: &str) -> Cow<'_, str> {
     let mut result = Cow::Borrowed(input);
     let mut cursor: usize = 0;
     for (idx, _) in input.match_indices('\n') {
@@ -67,7 +67,43 @@ pub(crate) fn estimate_single_line_wraps(line: &str, terminal_columns: u16) -> u
 
 /// Compute the line width for ANSI escaped text
 pub(crate) fn line_width(line: &str) -> usize {
-    strip_ansi(line).width()
+    // Fast path for plain ASCII, where every character takes one column
+    if line.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+        return line.len();
+    }
+    ansi_line_width(line).unwrap_or_else(|| strip_ansi(line).width())
+}
+
+// Width of `line` skipping CSI and OSC sequences, without allocating a stripped copy
+//
+// Returns `None` for other escape sequences, which are left to `strip_ansi`
+fn ansi_line_width(line: &str) -> Option<usize> {
+    let mut width = 0;
+    let mut rest = line;
+    while let Some(start) = rest.find('\x1b') {
+        width += rest[..start].width();
+        let sequence = &rest[start + 1..];
+        rest = match sequence.as_bytes().first() {
+            // CSI: parameters and intermediate bytes up to a final byte in `@`..=`~`
+            Some(b'[') => {
+                let end = sequence[1..].find(|c| ('@'..='~').contains(&c))?;
+                &sequence[end + 2..]
+            }
+            // OSC: terminated by BEL or ST (`ESC \\`)
+            Some(b']') => {
+                let end = sequence.find(['\x07', '\x1b'])?;
+                if sequence[end..].starts_with('\x07') {
+                    &sequence[end + 1..]
+                } else if sequence[end..].starts_with("\x1b\\") {
+                    &sequence[end + 2..]
+                } else {
+                    return None;
+                }
+            }
+            _ => return None,
+        };
+    }
+    Some(width + rest.width())
 }
 
 #[cfg(test)]
@@ -94,4 +130,18 @@ mod test {
             "Unnecessary allocation"
         )
     }
+
+    #[rstest]
+    #[case("plain ascii")]
+    #[case("")]
+    #[case("\x1b[1;32mls\x1b[0m -l")]
+    #[case("\x1b[38;5;208mcargo\x1b[m 漢字 😇")]
+    #[case("tab\tand\rreturn")]
+    #[case("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\")]
+    #[case("\x1b]0;title\x07prompt> ")]
+    #[case("unterminated \x1b[1")]
+    #[case("other \x1b(B escape")]
+    fn test_line_width_matches_stripped_width(#[case] input: &str) {
+        assert_eq!(line_width(input), strip_ansi(input).width());
+    }
 }
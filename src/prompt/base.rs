@@ -1,4 +1,5 @@
 use {
+    crate::ValidationResult,
     crossterm::style::Color,
     serde::{Deserialize, Serialize},
     std::{
@@ -30,6 +31,9 @@ pub struct PromptHistorySearch {
 
     /// The search term used during the search
     pub term: String,
+
+    /// Which match is shown, counted from the most recent one, and the number of matches
+    pub match_position: Option<(usize, usize)>,
 }
 
 impl PromptHistorySearch {
@@ -38,8 +42,16 @@ impl PromptHistorySearch {
         PromptHistorySearch {
             status,
             term: search_term,
+            match_position: None,
         }
     }
+
+    /// A builder setting the shown match and the number of matches, e.g. `3` of `41`
+    #[must_use]
+    pub const fn with_match_position(mut self, position: usize, total: usize) -> Self {
+        self.match_position = Some((position, total));
+        self
+    }
 }
 
 /// Modes that the prompt can be in
@@ -67,6 +79,26 @@ pub enum PromptViMode {
 
     /// Insertion mode
     Insert,
+
+    /// Normal mode in the middle of a key sequence, e.g. after `2"ad`
+    Pending(PromptViPending),
+}
+
+/// The part of a vi normal mode key sequence typed so far, for prompts showing a mode line
+/// like vim's
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PromptViPending {
+    /// All keys of the sequence so far, e.g. `2"ad`
+    pub keys: String,
+
+    /// The count the command or motion is repeated by, e.g. `6` after `2d3`
+    pub count: Option<usize>,
+
+    /// The register named with `"`, e.g. `a` after `"a`
+    pub register: Option<char>,
+
+    /// The keys of the operator waiting for its motion, e.g. `d` or `g~`
+    pub operator: Option<String>,
 }
 
 impl Display for PromptEditMode {
@@ -85,18 +117,29 @@ impl Display for PromptEditMode {
 /// displayed before the `LineBuffer` is drawn.
 pub trait Prompt: Send {
     /// Provide content of the left full prompt
-    fn render_prompt_left(&self) -> Cow<str>;
+    fn render_prompt_left(&self) -> Cow<'_, str>;
     /// Provide content of the right full prompt
-    fn render_prompt_right(&self) -> Cow<str>;
+    fn render_prompt_right(&self) -> Cow<'_, str>;
     /// Render the prompt indicator (Last part of the prompt that changes based on the editor mode)
-    fn render_prompt_indicator(&self, prompt_mode: PromptEditMode) -> Cow<str>;
+    fn render_prompt_indicator(&self, prompt_mode: PromptEditMode) -> Cow<'_, str>;
+    /// Render the prompt indicator for the latest validation of the edited buffer, see
+    /// [`crate::Reedline::with_live_validation`]
+    ///
+    /// Defaults to [`Prompt::render_prompt_indicator`].
+    fn render_prompt_indicator_for_validation(
+        &self,
+        prompt_mode: PromptEditMode,
+        _validation: ValidationResult,
+    ) -> Cow<'_, str> {
+        self.render_prompt_indicator(prompt_mode)
+    }
     /// Indicator to show before explicit new lines
-    fn render_prompt_multiline_indicator(&self) -> Cow<str>;
+    fn render_prompt_multiline_indicator(&self) -> Cow<'_, str>;
     /// Render the prompt indicator for `Ctrl-R` history search
     fn render_prompt_history_search_indicator(
         &self,
         history_search: PromptHistorySearch,
-    ) -> Cow<str>;
+    ) -> Cow<'_, str>;
     /// Get the default prompt color
     fn get_prompt_color(&self) -> Color {
         DEFAULT_PROMPT_COLOR
@@ -109,6 +152,13 @@ pub trait Prompt: Send {
     fn get_indicator_color(&self) -> Color {
         DEFAULT_INDICATOR_COLOR
     }
+    /// Get the indicator color for the latest validation of the edited buffer, e.g. red while
+    /// the input is incomplete, see [`crate::Reedline::with_live_validation`]
+    ///
+    /// Defaults to [`Prompt::get_indicator_color`].
+    fn get_indicator_color_for_validation(&self, _validation: ValidationResult) -> Color {
+        self.get_indicator_color()
+    }
     /// Get the default right prompt color
     fn get_prompt_right_color(&self) -> Color {
         DEFAULT_PROMPT_RIGHT_COLOR
@@ -118,4 +168,12 @@ pub trait Prompt: Send {
     fn right_prompt_on_last_line(&self) -> bool {
         false
     }
+    /// Text printed below a submitted line, before the output of the application, e.g. the
+    /// time of the submission
+    ///
+    /// Asked once per submitted line, of the transient prompt if there is one. Nothing is
+    /// printed for an empty text, the default.
+    fn render_post_submit(&self) -> Cow<'_, str> {
+        Cow::Borrowed("")
+    }
 }
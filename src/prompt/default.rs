@@ -38,7 +38,7 @@ pub enum DefaultPromptSegment {
 /// Given a prompt segment, render it to a Cow<str> that we can use to
 /// easily implement [`Prompt`]'s `render_prompt_left` and `render_prompt_right`
 /// functions.
-fn render_prompt_segment(prompt: &DefaultPromptSegment) -> Cow<str> {
+fn render_prompt_segment(prompt: &DefaultPromptSegment) -> Cow<'_, str> {
     match &prompt {
         DefaultPromptSegment::Basic(s) => Cow::Borrowed(s),
         DefaultPromptSegment::WorkingDirectory => {
@@ -51,42 +51,48 @@ fn render_prompt_segment(prompt: &DefaultPromptSegment) -> Cow<str> {
 }
 
 impl Prompt for DefaultPrompt {
-    fn render_prompt_left(&self) -> Cow<str> {
+    fn render_prompt_left(&self) -> Cow<'_, str> {
         render_prompt_segment(&self.left_prompt)
     }
 
-    fn render_prompt_right(&self) -> Cow<str> {
+    fn render_prompt_right(&self) -> Cow<'_, str> {
         render_prompt_segment(&self.right_prompt)
     }
 
-    fn render_prompt_indicator(&self, edit_mode: PromptEditMode) -> Cow<str> {
+    fn render_prompt_indicator(&self, edit_mode: PromptEditMode) -> Cow<'_, str> {
         match edit_mode {
             PromptEditMode::Default | PromptEditMode::Emacs => DEFAULT_PROMPT_INDICATOR.into(),
             PromptEditMode::Vi(vi_mode) => match vi_mode {
-                PromptViMode::Normal => DEFAULT_VI_NORMAL_PROMPT_INDICATOR.into(),
+                PromptViMode::Normal | PromptViMode::Pending(_) => {
+                    DEFAULT_VI_NORMAL_PROMPT_INDICATOR.into()
+                }
                 PromptViMode::Insert => DEFAULT_VI_INSERT_PROMPT_INDICATOR.into(),
             },
             PromptEditMode::Custom(str) => format!("({str})").into(),
         }
     }
 
-    fn render_prompt_multiline_indicator(&self) -> Cow<str> {
+    fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
         Cow::Borrowed(DEFAULT_MULTILINE_INDICATOR)
     }
 
     fn render_prompt_history_search_indicator(
         &self,
         history_search: PromptHistorySearch,
-    ) -> Cow<str> {
+    ) -> Cow<'_, str> {
         let prefix = match history_search.status {
             PromptHistorySearchStatus::Passing => "",
             PromptHistorySearchStatus::Failing => "failing ",
         };
         // NOTE: magic strings, given there is logic on how these compose I am not sure if it
         // is worth extracting in to static constant
+        let position = match history_search.match_position {
+            Some((position, total)) => format!(", match {position}/{total}"),
+            None => String::new(),
+        };
         Cow::Owned(format!(
-            "({}reverse-search: {}) ",
-            prefix, history_search.term
+            "({}reverse-search: {}{}) ",
+            prefix, history_search.term, position
         ))
     }
 }
@@ -3,6 +3,7 @@ mod default;
 
 pub use base::{
     Prompt, PromptEditMode, PromptHistorySearch, PromptHistorySearchStatus, PromptViMode,
+    PromptViPending,
 };
 
 pub use default::{DefaultPrompt, DefaultPromptSegment};
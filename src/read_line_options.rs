@@ -0,0 +1,92 @@
+use crate::Keybindings;
+use std::ops::Range;
+
+/// Settings for a single [`crate::Reedline::read_line_with_options`] call
+///
+/// Lets applications reuse one [`crate::Reedline`] for different kinds of prompts: the
+/// options only apply until the line is returned, the editor is configured as before for
+/// the next call.
+///
+/// ```rust
+/// use reedline::{default_emacs_keybindings, KeyCode, KeyModifiers, ReadLineOptions, ReedlineEvent};
+///
+/// let mut keybindings = default_emacs_keybindings();
+/// keybindings.add_binding(KeyModifiers::NONE, KeyCode::Esc, ReedlineEvent::CtrlC);
+///
+/// let options = ReadLineOptions::new()
+///     .with_buffer("commit -m \"\"", Some(11))
+///     .with_history(false)
+///     .with_keybindings(keybindings);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReadLineOptions {
+    pub(crate) buffer: Option<(String, Option<usize>)>,
+    pub(crate) history: bool,
+    pub(crate) hints: bool,
+    pub(crate) keybindings: Option<Keybindings>,
+    pub(crate) menu: Option<String>,
+    pub(crate) read_only: Option<Vec<Range<usize>>>,
+}
+
+impl Default for ReadLineOptions {
+    fn default() -> Self {
+        Self {
+            buffer: None,
+            history: true,
+            hints: true,
+            keybindings: None,
+            menu: None,
+            read_only: None,
+        }
+    }
+}
+
+impl ReadLineOptions {
+    /// Options changing nothing about the editor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start with `buffer` instead of the current buffer, with the cursor at the byte offset
+    /// `cursor` or at the end, see [`crate::Reedline::prefill_buffer`]
+    #[must_use]
+    pub fn with_buffer(mut self, buffer: impl Into<String>, cursor: Option<usize>) -> Self {
+        self.buffer = Some((buffer.into(), cursor));
+        self
+    }
+
+    /// Whether the line is saved to the history and the history can be browsed (default: true)
+    #[must_use]
+    pub fn with_history(mut self, history: bool) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Whether the hinter shows hints (default: true)
+    #[must_use]
+    pub fn with_hints(mut self, hints: bool) -> Self {
+        self.hints = hints;
+        self
+    }
+
+    /// Bindings taking precedence over the ones of the edit mode
+    #[must_use]
+    pub fn with_keybindings(mut self, keybindings: Keybindings) -> Self {
+        self.keybindings = Some(keybindings);
+        self
+    }
+
+    /// Open the menu called `name` right away
+    #[must_use]
+    pub fn with_menu(mut self, name: impl Into<String>) -> Self {
+        self.menu = Some(name.into());
+        self
+    }
+
+    /// Protect the byte `ranges` of the buffer from edits, see [`crate::Reedline::set_read_only`]
+    #[must_use]
+    pub fn with_read_only(mut self, ranges: impl IntoIterator<Item = Range<usize>>) -> Self {
+        self.read_only = Some(ranges.into_iter().collect());
+        self
+    }
+}
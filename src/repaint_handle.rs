@@ -0,0 +1,60 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Lets other threads ask a running [`crate::Reedline::read_line()`] to repaint the prompt
+///
+/// For prompts showing values that change in the background, e.g. the result of an
+/// asynchronous `git status` or a clock. Clones share the same editor; pass one of them to
+/// [`crate::Reedline::with_repaint_handle`]. Requests are picked up within a few tens of
+/// milliseconds, several of them in between cause one repaint.
+///
+/// ```rust
+/// use reedline::{Reedline, RepaintHandle};
+///
+/// let handle = RepaintHandle::new();
+/// let line_editor = Reedline::create().with_repaint_handle(handle.clone());
+/// std::thread::spawn(move || {
+///     // ... update what the prompt shows
+///     handle.request_repaint();
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RepaintHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl RepaintHandle {
+    /// A handle not yet connected to an editor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Repaint the prompt and the buffer as soon as possible
+    pub fn request_repaint(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether a repaint was requested since the last call
+    pub(crate) fn take_request(&self) -> bool {
+        self.requested.swap(false, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn requests_are_taken_once() {
+        let handle = RepaintHandle::new();
+        let requester = handle.clone();
+        assert!(!handle.take_request());
+
+        requester.request_repaint();
+        requester.request_repaint();
+        assert!(handle.take_request());
+        assert!(!handle.take_request());
+    }
+}
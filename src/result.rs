@@ -1,11 +1,12 @@
-use std::fmt::Display;
+use std::{fmt::Display, io};
 use thiserror::Error;
 
 /// non-public (for now)
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum ReedlineErrorVariants {
     // todo: we should probably be more specific here
-    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib"))]
+    #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib", feature = "atuin"))]
     /// Error within history database
     #[error("error within history database: {0}")]
     HistoryDatabaseError(String),
@@ -27,18 +28,101 @@ pub enum ReedlineErrorVariants {
     /// I/O error
     #[error("I/O error: {0}")]
     IOError(std::io::Error),
+
+    /// Reading input from or setting up the terminal failed
+    #[error("terminal error: {0}")]
+    TerminalError(std::io::Error),
+
+    /// Painting the prompt, buffer or menus failed
+    #[error("error painting the line: {0}")]
+    RenderError(std::io::Error),
+
+    /// The configuration of the editor or of one of its parts is invalid
+    #[error("invalid configuration: {0}")]
+    ConfigError(String),
+}
+
+/// The part of the editor a [`ReedlineError`] comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReedlineErrorCategory {
+    /// The terminal, e.g. it can't be read from or put into raw mode
+    Terminal,
+    /// The [`crate::History`], e.g. its file or database can't be written
+    HistoryBackend,
+    /// Painting the line, see [`crate::Reedline::with_render_error_handler`]
+    Render,
+    /// The configuration, e.g. invalid settings of a history
+    Config,
+}
+
+/// How [`crate::Reedline::read_line`] goes on after an error painting the line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderErrorAction {
+    /// End [`crate::Reedline::read_line`] with the error
+    #[default]
+    Abort,
+    /// Keep editing, painting the whole line again with the next repaint
+    Continue,
 }
 
 /// separate struct to not expose anything to the public (for now)
 #[derive(Debug)]
 pub struct ReedlineError(pub ReedlineErrorVariants);
 
+impl ReedlineError {
+    /// The part of the editor the error comes from
+    ///
+    /// Plain I/O errors are only created by the histories, e.g. for their files.
+    pub fn category(&self) -> ReedlineErrorCategory {
+        match &self.0 {
+            ReedlineErrorVariants::TerminalError(_) => ReedlineErrorCategory::Terminal,
+            ReedlineErrorVariants::RenderError(_) => ReedlineErrorCategory::Render,
+            ReedlineErrorVariants::ConfigError(_) => ReedlineErrorCategory::Config,
+            #[cfg(any(feature = "sqlite", feature = "sqlite-dynlib", feature = "atuin"))]
+            ReedlineErrorVariants::HistoryDatabaseError(_) => ReedlineErrorCategory::HistoryBackend,
+            ReedlineErrorVariants::OtherHistoryError(_)
+            | ReedlineErrorVariants::HistoryFeatureUnsupported { .. }
+            | ReedlineErrorVariants::IOError(_) => ReedlineErrorCategory::HistoryBackend,
+        }
+    }
+
+    // An error of the terminal, unless `err` carries an error of another category
+    pub(crate) fn terminal(err: io::Error) -> Self {
+        match Self::from(err) {
+            ReedlineError(ReedlineErrorVariants::IOError(err)) => {
+                Self(ReedlineErrorVariants::TerminalError(err))
+            }
+            other => other,
+        }
+    }
+}
+
 impl From<std::io::Error> for ReedlineError {
     fn from(err: std::io::Error) -> Self {
+        // Unwraps the errors converted to `io::Error` below
+        if err
+            .get_ref()
+            .map_or(false, |inner| inner.is::<ReedlineError>())
+        {
+            let inner = err.into_inner().expect("checked to have an inner error");
+            return *inner.downcast().expect("checked to be a ReedlineError");
+        }
         Self(ReedlineErrorVariants::IOError(err))
     }
 }
 
+/// Keeps the [`ReedlineError`] inside, it is returned again when converting back
+impl From<ReedlineError> for io::Error {
+    fn from(err: ReedlineError) -> Self {
+        match err.0 {
+            ReedlineErrorVariants::IOError(err) => err,
+            ReedlineErrorVariants::TerminalError(ref inner)
+            | ReedlineErrorVariants::RenderError(ref inner) => io::Error::new(inner.kind(), err),
+            _ => io::Error::new(io::ErrorKind::Other, err),
+        }
+    }
+}
+
 impl Display for ReedlineError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
@@ -48,3 +132,36 @@ impl std::error::Error for ReedlineError {}
 
 /// Standard [`std::result::Result`], with [`ReedlineError`] as the error variant
 pub type Result<T> = std::result::Result<T, ReedlineError>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn errors_keep_their_category_through_io_errors() {
+        let render = ReedlineError(ReedlineErrorVariants::RenderError(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "closed",
+        )));
+        let io_error = io::Error::from(render);
+        assert_eq!(io_error.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(
+            ReedlineError::terminal(io_error).category(),
+            ReedlineErrorCategory::Render
+        );
+
+        let plain = io::Error::new(io::ErrorKind::UnexpectedEof, "eof");
+        assert_eq!(
+            ReedlineError::from(plain).category(),
+            ReedlineErrorCategory::HistoryBackend
+        );
+        let plain = io::Error::new(io::ErrorKind::UnexpectedEof, "eof");
+        let terminal = ReedlineError::terminal(plain);
+        assert_eq!(terminal.category(), ReedlineErrorCategory::Terminal);
+        assert_eq!(
+            io::Error::from(terminal).kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
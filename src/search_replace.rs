@@ -0,0 +1,276 @@
+use crate::{core_editor::Editor, EditCommand, UndoBehavior};
+use std::ops::Range;
+
+/// Step of the interactive search & replace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SearchReplaceStage {
+    /// Typing the text to search for
+    Pattern,
+    /// Typing the replacement text
+    Replacement,
+    /// Stepping through the matches asking for confirmation
+    Confirm,
+}
+
+/// Answer to the confirmation of a single match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SearchReplaceAnswer {
+    /// Replace this match and go to the next one
+    Yes,
+    /// Keep this match and go to the next one
+    No,
+    /// Replace this and all remaining matches
+    All,
+    /// Stop replacing
+    Quit,
+}
+
+impl SearchReplaceAnswer {
+    pub(crate) fn from_char(c: char) -> Option<Self> {
+        match c {
+            'y' | 'Y' => Some(Self::Yes),
+            'n' | 'N' => Some(Self::No),
+            'a' | 'A' => Some(Self::All),
+            'q' | 'Q' => Some(Self::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// State of an interactive, buffer-wide search & replace
+///
+/// The pattern and the replacement are entered in a command line below the buffer,
+/// afterwards every match of the pattern is highlighted in turn and replaced on confirmation.
+pub(crate) struct SearchReplace {
+    stage: SearchReplaceStage,
+    input: Editor,
+    pattern: String,
+    replacement: String,
+    current_match: Option<Range<usize>>,
+    matches: usize,
+    replaced: usize,
+}
+
+impl SearchReplace {
+    pub(crate) fn new() -> Self {
+        Self {
+            stage: SearchReplaceStage::Pattern,
+            input: Editor::default(),
+            pattern: String::new(),
+            replacement: String::new(),
+            current_match: None,
+            matches: 0,
+            replaced: 0,
+        }
+    }
+
+    pub(crate) const fn stage(&self) -> SearchReplaceStage {
+        self.stage
+    }
+
+    /// The command line being edited while entering the pattern or replacement
+    pub(crate) const fn input(&self) -> &Editor {
+        &self.input
+    }
+
+    pub(crate) fn run_input_command(&mut self, command: &EditCommand) {
+        self.input.run_edit_command(command);
+    }
+
+    /// The match currently waiting for confirmation
+    pub(crate) fn current_match(&self) -> Option<Range<usize>> {
+        self.current_match.clone()
+    }
+
+    /// Prompt shown in front of the command line
+    pub(crate) fn prompt(&self) -> String {
+        match self.stage {
+            SearchReplaceStage::Pattern => "Replace: ".to_string(),
+            SearchReplaceStage::Replacement => format!("Replace {} with: ", self.pattern),
+            SearchReplaceStage::Confirm => format!(
+                "Replace {} with {}? (y/n/a/q) ",
+                self.pattern, self.replacement
+            ),
+        }
+    }
+
+    /// Summary shown once the search & replace is finished
+    pub(crate) fn summary(&self) -> String {
+        match self.replaced {
+            0 if self.matches == 0 => format!("Pattern not found: {}", self.pattern),
+            1 => "Replaced 1 occurrence".to_string(),
+            n => format!("Replaced {n} occurrences"),
+        }
+    }
+
+    /// Accept the text of the command line.
+    ///
+    /// Returns `false` if the search & replace is finished, because the pattern is empty or not
+    /// contained in the buffer.
+    pub(crate) fn submit_input(&mut self, editor: &mut Editor) -> bool {
+        let input = self.input.get_buffer().to_string();
+        self.input = Editor::default();
+
+        match self.stage {
+            SearchReplaceStage::Pattern => {
+                if input.is_empty() {
+                    return false;
+                }
+                self.pattern = input;
+                self.stage = SearchReplaceStage::Replacement;
+                true
+            }
+            SearchReplaceStage::Replacement => {
+                self.replacement = input;
+                self.stage = SearchReplaceStage::Confirm;
+                self.find_match(editor, 0)
+            }
+            SearchReplaceStage::Confirm => true,
+        }
+    }
+
+    /// Handle the answer for the current match.
+    ///
+    /// Returns `false` if the search & replace is finished.
+    pub(crate) fn confirm(&mut self, answer: SearchReplaceAnswer, editor: &mut Editor) -> bool {
+        let current = match self.current_match.clone() {
+            Some(current) => current,
+            None => return false,
+        };
+
+        match answer {
+            SearchReplaceAnswer::Yes => {
+                let next = self.replace(editor, current);
+                self.find_match(editor, next)
+            }
+            SearchReplaceAnswer::No => self.find_match(editor, current.end),
+            SearchReplaceAnswer::All => {
+                let mut next = self.replace(editor, current);
+                while self.find_match(editor, next) {
+                    next = self.replace(editor, self.current_match.clone().unwrap_or_default());
+                }
+                false
+            }
+            SearchReplaceAnswer::Quit => false,
+        }
+    }
+
+    /// Replace `range` and return the offset after the inserted replacement
+    fn replace(&mut self, editor: &mut Editor, range: Range<usize>) -> usize {
+        let mut line_buffer = editor.line_buffer().clone();
+        line_buffer.replace_range(range.clone(), &self.replacement);
+        let next = range.start + self.replacement.len();
+        line_buffer.set_insertion_point(next);
+        editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+        self.replaced += 1;
+        next
+    }
+
+    /// Find the next match at or after `from` and move the cursor to it
+    fn find_match(&mut self, editor: &mut Editor, from: usize) -> bool {
+        let buffer = editor.get_buffer();
+        self.current_match = buffer
+            .get(from..)
+            .and_then(|rest| rest.find(&self.pattern))
+            .map(|start| from + start..from + start + self.pattern.len());
+
+        if let Some(current) = &self.current_match {
+            self.matches += 1;
+            let mut line_buffer = editor.line_buffer().clone();
+            line_buffer.set_insertion_point(current.start);
+            editor.set_line_buffer(line_buffer, UndoBehavior::MoveCursor);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::LineBuffer;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn editor_with(buffer: &str) -> Editor {
+        let mut editor = Editor::default();
+        editor.set_line_buffer(LineBuffer::from(buffer), UndoBehavior::CreateUndoPoint);
+        editor
+    }
+
+    fn start(editor: &mut Editor, pattern: &str, replacement: &str) -> (SearchReplace, bool) {
+        let mut search_replace = SearchReplace::new();
+        search_replace.run_input_command(&EditCommand::InsertString(pattern.to_string()));
+        assert!(search_replace.submit_input(editor));
+        search_replace.run_input_command(&EditCommand::InsertString(replacement.to_string()));
+        let found = search_replace.submit_input(editor);
+        (search_replace, found)
+    }
+
+    #[rstest]
+    #[case("foo bar foo\nfoo", "yyy", "baz bar baz\nbaz", 3)]
+    #[case("foo bar foo\nfoo", "nyn", "foo bar baz\nfoo", 1)]
+    #[case("foo bar foo\nfoo", "na", "foo bar baz\nbaz", 2)]
+    #[case("foo bar foo\nfoo", "yq", "baz bar foo\nfoo", 1)]
+    #[case("foo bar foo\nfoo", "nnn", "foo bar foo\nfoo", 0)]
+    fn test_confirm(
+        #[case] input: &str,
+        #[case] answers: &str,
+        #[case] output: &str,
+        #[case] replaced: usize,
+    ) {
+        let mut editor = editor_with(input);
+        let (mut search_replace, found) = start(&mut editor, "foo", "baz");
+        assert!(found);
+
+        for answer in answers.chars() {
+            let answer = SearchReplaceAnswer::from_char(answer).unwrap();
+            if !search_replace.confirm(answer, &mut editor) {
+                break;
+            }
+        }
+
+        assert_eq!(editor.get_buffer(), output);
+        assert_eq!(search_replace.replaced, replaced);
+    }
+
+    #[test]
+    fn test_replacement_containing_pattern_is_not_matched_again() {
+        let mut editor = editor_with("a a");
+        let (mut search_replace, _) = start(&mut editor, "a", "aa");
+
+        assert!(!search_replace.confirm(SearchReplaceAnswer::All, &mut editor));
+        assert_eq!(editor.get_buffer(), "aa aa");
+        assert_eq!(search_replace.summary(), "Replaced 2 occurrences");
+    }
+
+    #[test]
+    fn test_cursor_follows_current_match() {
+        let mut editor = editor_with("foo bar foo");
+        let (mut search_replace, _) = start(&mut editor, "foo", "x");
+        assert_eq!(search_replace.current_match(), Some(0..3));
+        assert_eq!(editor.insertion_point(), 0);
+
+        assert!(search_replace.confirm(SearchReplaceAnswer::Yes, &mut editor));
+        assert_eq!(search_replace.current_match(), Some(6..9));
+        assert_eq!(editor.insertion_point(), 6);
+    }
+
+    #[test]
+    fn test_pattern_not_found() {
+        let mut editor = editor_with("foo");
+        let (search_replace, found) = start(&mut editor, "bar", "baz");
+
+        assert!(!found);
+        assert_eq!(search_replace.summary(), "Pattern not found: bar");
+    }
+
+    #[test]
+    fn test_empty_pattern_finishes() {
+        let mut editor = editor_with("foo");
+        let mut search_replace = SearchReplace::new();
+
+        assert!(!search_replace.submit_input(&mut editor));
+    }
+}
@@ -0,0 +1,143 @@
+use std::{collections::HashMap, ops::Range};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field { name: String, default: String },
+}
+
+/// A line of fixed text with named fields to fill in, read with
+/// [`crate::Reedline::read_template`]
+///
+/// The text can't be edited, Tab and Shift-Tab select the next and the previous field. A
+/// field follows the text before it, so fields without text between them are read as one:
+/// the first of them gets what was typed there, the others are left empty.
+///
+/// ```rust
+/// use reedline::Template;
+///
+/// let template = Template::new()
+///     .with_text("git commit -m \"")
+///     .with_field("message", "")
+///     .with_text("\" --author ")
+///     .with_field("author", "me");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// A template without text or fields
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder which adds the fixed `text`
+    #[must_use]
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        let text = text.into();
+        match self.parts.last_mut() {
+            _ if text.is_empty() => {}
+            Some(Part::Text(last)) => last.push_str(&text),
+            _ => self.parts.push(Part::Text(text)),
+        }
+        self
+    }
+
+    /// A builder which adds the field called `name`, starting with `default`
+    #[must_use]
+    pub fn with_field(mut self, name: impl Into<String>, default: impl Into<String>) -> Self {
+        self.parts.push(Part::Field {
+            name: name.into(),
+            default: default.into(),
+        });
+        self
+    }
+
+    /// The whole line with the defaults of the fields
+    pub(crate) fn text(&self) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.as_str(),
+                Part::Field { default, .. } => default.as_str(),
+            })
+            .collect()
+    }
+
+    /// The ranges of the fixed text in [`Template::text`]
+    pub(crate) fn read_only(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => {
+                    ranges.push(start..start + text.len());
+                    start += text.len();
+                }
+                Part::Field { default, .. } => start += default.len(),
+            }
+        }
+        ranges
+    }
+
+    /// The values of the fields in `buffer`, whose fields, the text between the read-only
+    /// ranges, are `fields`
+    pub(crate) fn values(&self, buffer: &str, fields: &[Range<usize>]) -> HashMap<String, String> {
+        let starts_with_field = matches!(self.parts.first(), Some(Part::Field { .. }));
+        let mut texts = 0;
+        let mut last_field = None;
+        let mut values = HashMap::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(_) => texts += 1,
+                Part::Field { name, .. } => {
+                    let field = (texts + usize::from(starts_with_field))
+                        .checked_sub(1)
+                        .and_then(|index| fields.get(index))
+                        .filter(|_| last_field != Some(texts))
+                        .and_then(|range| buffer.get(range.clone()));
+                    values.insert(name.clone(), field.unwrap_or_default().to_string());
+                    last_field = Some(texts);
+                }
+            }
+        }
+        values
+    }
+}
+
+/// The line read with [`crate::Reedline::read_template`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilledTemplate {
+    /// The whole line, the text and the fields
+    pub line: String,
+    /// The value of every field, by its name
+    pub fields: HashMap<String, String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn the_values_are_read_between_the_texts() {
+        let template = Template::new()
+            .with_field("command", "ls")
+            .with_text(" -")
+            .with_text("")
+            .with_text("-color=")
+            .with_field("when", "auto")
+            .with_field("unused", "")
+            .with_text(" ");
+        assert_eq!(template.text(), "ls --color=auto ");
+        assert_eq!(template.read_only(), vec![2..11, 15..16]);
+
+        let buffer = "cargo --color=never ";
+        let values = template.values(buffer, &[0..5, 14..19, 20..20]);
+        assert_eq!(values["command"], "cargo");
+        assert_eq!(values["when"], "never");
+        assert_eq!(values["unused"], "");
+    }
+}
@@ -0,0 +1,40 @@
+use crossterm::{
+    cursor::{SetCursorStyle, Show},
+    execute,
+};
+
+/// Helper restoring the cursor shape of the user once reedline changed it
+///
+/// The shape is restored when `read_line` returns, when the terminal is handed to the
+/// application and when the guard is dropped, which includes unwinding from a panic.
+#[derive(Default)]
+pub(crate) struct CursorShapeGuard {
+    enabled: bool,
+    changed: bool,
+}
+
+impl CursorShapeGuard {
+    pub fn set(&mut self, enable: bool) {
+        self.enabled = enable && super::cursor_shapes_supported();
+    }
+
+    /// The shape to paint, if any, remembering that the shape of the user has to be restored
+    pub fn change(&mut self, shape: Option<SetCursorStyle>) -> Option<SetCursorStyle> {
+        let shape = shape.filter(|_| self.enabled);
+        self.changed |= shape.is_some();
+        shape
+    }
+
+    pub fn exit(&mut self) {
+        if self.changed {
+            let _ = execute!(std::io::stdout(), SetCursorStyle::DefaultUserShape, Show);
+            self.changed = false;
+        }
+    }
+}
+
+impl Drop for CursorShapeGuard {
+    fn drop(&mut self) {
+        self.exit();
+    }
+}
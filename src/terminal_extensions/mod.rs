@@ -1,4 +1,5 @@
 pub(crate) mod bracketed_paste;
+pub(crate) mod cursor_shape;
 pub(crate) mod kitty;
 
 /// Return if the terminal supports the kitty keyboard enhancement protocol
@@ -9,3 +10,23 @@ pub(crate) mod kitty;
 pub fn kitty_protocol_available() -> bool {
     crossterm::terminal::supports_keyboard_enhancement().unwrap_or_default()
 }
+
+/// Return if the terminal is expected to draw colored and double underlines (`SGR 4:2` and
+/// `SGR 58`), which others may misread
+pub(crate) fn extended_underlines_supported() -> bool {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    let term = var("TERM");
+    let term_program = var("TERM_PROGRAM");
+    ["kitty", "wezterm", "foot", "alacritty", "ghostty"]
+        .iter()
+        .any(|name| term.contains(name))
+        || ["WezTerm", "iTerm.app", "vscode", "ghostty"].contains(&term_program.as_str())
+        || std::env::var_os("VTE_VERSION").is_some()
+        || std::env::var_os("KITTY_WINDOW_ID").is_some()
+}
+
+/// Return if the terminal is expected to understand the sequences setting the cursor shape
+/// (DECSCUSR), which the Linux console and dumb terminals would print or misread
+pub(crate) fn cursor_shapes_supported() -> bool {
+    !matches!(std::env::var("TERM").as_deref(), Ok("dumb" | "linux"))
+}
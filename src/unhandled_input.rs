@@ -0,0 +1,19 @@
+use crate::KeyCombination;
+
+/// What [`crate::Reedline`] does when a key is bound to nothing, set with
+/// [`crate::Reedline::with_unhandled_input`]
+///
+/// Keys that start a sequence of the edit mode, e.g. `d` in vi normal mode, are only
+/// unhandled once the sequence turns out to be invalid.
+#[derive(Default)]
+pub enum UnhandledInput {
+    /// Silently drop the key
+    #[default]
+    Ignore,
+    /// Ring the terminal bell
+    Bell,
+    /// Briefly show the prompt indicator in reverse video, when coloring is enabled
+    Flash,
+    /// Pass every unhandled key to the application
+    Callback(Box<dyn FnMut(&KeyCombination) + Send>),
+}
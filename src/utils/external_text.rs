@@ -0,0 +1,135 @@
+// Text inserted into the buffer from files and from the output of shell commands
+
+use std::{
+    fs::File,
+    io::{Read, Result},
+    process::Command,
+};
+
+/// Read at most `limit` bytes from the file at `path`, returning whether the file is longer
+pub(crate) fn read_file_prefix(path: &str, limit: usize) -> Result<(String, bool)> {
+    let mut bytes = Vec::new();
+    File::open(path)?
+        .take(limit as u64 + 1)
+        .read_to_end(&mut bytes)?;
+    Ok(decode_prefix(bytes, limit))
+}
+
+/// Decode at most `limit` of the `bytes`, returning whether there are more of them
+///
+/// A character cut by the limit is left out, invalid bytes are replaced.
+pub(crate) fn decode_prefix(mut bytes: Vec<u8>, limit: usize) -> (String, bool) {
+    let truncated = bytes.len() > limit;
+    if truncated {
+        bytes.truncate(limit);
+        let tail = bytes.len().saturating_sub(3);
+        let last_start = (tail..bytes.len()).rev().find(|&i| bytes[i] & 0xC0 != 0x80);
+        if let Some(start) = last_start {
+            if matches!(std::str::from_utf8(&bytes[start..]), Err(err) if err.error_len().is_none())
+            {
+                bytes.truncate(start);
+            }
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), truncated)
+}
+
+/// Normalize newlines to `\n` and trim trailing newlines
+pub(crate) fn normalize_external_text(text: &str) -> String {
+    let mut text = text.replace("\r\n", "\n").replace('\r', "\n");
+    text.truncate(text.trim_end_matches('\n').len());
+    text
+}
+
+/// Run `command` through the system shell, returning its standard output if `capture_output`
+pub(crate) fn run_in_shell(command: &str, capture_output: bool) -> Result<Option<Vec<u8>>> {
+    #[cfg(windows)]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+    #[cfg(not(windows))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+
+    if capture_output {
+        let output = shell.stderr(std::process::Stdio::inherit()).output()?;
+        Ok(Some(output.stdout))
+    } else {
+        shell.status()?;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn run_in_shell_captures_output() {
+        assert_eq!(
+            run_in_shell("echo hello", true).unwrap(),
+            Some(b"hello\n".to_vec())
+        );
+        assert_eq!(run_in_shell("true", false).unwrap(), None);
+    }
+
+    #[test]
+    fn normalize_external_text_unifies_newlines() {
+        assert_eq!(normalize_external_text("a\r\nb\rc\n\n"), "a\nb\nc");
+    }
+
+    #[test]
+    fn decode_prefix_limits_size() {
+        assert_eq!(
+            decode_prefix(b"abcdef".to_vec(), 4),
+            ("abcd".to_string(), true)
+        );
+        assert_eq!(
+            decode_prefix("aé".as_bytes().to_vec(), 2),
+            ("a".to_string(), true)
+        );
+        // Replacing invalid bytes doesn't count as truncation
+        assert_eq!(
+            decode_prefix(b"a\xffb".to_vec(), 3),
+            ("a\u{fffd}b".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn read_file_prefix_reads_up_to_limit() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "0123456789").unwrap();
+        let path = file.path().to_string_lossy().into_owned();
+
+        assert_eq!(
+            read_file_prefix(&path, 4).unwrap(),
+            ("0123".to_string(), true)
+        );
+        assert_eq!(
+            read_file_prefix(&path, 10).unwrap(),
+            ("0123456789".to_string(), false)
+        );
+        assert!(read_file_prefix("/nonexistent/reedline", 4).is_err());
+
+        // The character cut by the limit is left out
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all("aé€".as_bytes()).unwrap();
+        let path = file.path().to_string_lossy().into_owned();
+        assert_eq!(
+            read_file_prefix(&path, 5).unwrap(),
+            ("aé".to_string(), true)
+        );
+        assert_eq!(
+            read_file_prefix(&path, 6).unwrap(),
+            ("aé€".to_string(), false)
+        );
+    }
+}
@@ -1,5 +1,27 @@
+pub(crate) mod external_text;
 mod query;
 pub(crate) mod text_manipulation;
+pub(crate) mod typo;
+
+/// Overwrites the memory of `string` with zeros, including its spare capacity, and empties it
+///
+/// For secrets that should not linger in freed memory.
+pub(crate) fn zeroize(string: &mut String) {
+    zeroize_bytes(&mut std::mem::take(string).into_bytes());
+}
+
+/// Overwrites the memory of `bytes` with zeros, like [`zeroize`], and empties it
+pub(crate) fn zeroize_bytes(bytes: &mut Vec<u8>) {
+    let mut bytes = std::mem::take(bytes);
+    let capacity = bytes.capacity();
+    bytes.resize(capacity, 0);
+    for byte in bytes.iter_mut() {
+        // SAFETY: the pointer comes from a mutable reference, volatile keeps the write from
+        // being optimized away as the bytes are dropped right after
+        unsafe { std::ptr::write_volatile(byte, 0) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
 
 pub use query::{
     get_reedline_default_keybindings, get_reedline_edit_commands,
@@ -0,0 +1,74 @@
+// Matching that tolerates typos, used when nothing matches exactly
+//
+// A typo is a missing, an extra or a wrong character, or two swapped neighbouring characters.
+// Short patterns allow fewer typos, one per three characters, as almost any text is within a
+// few typos of them.
+
+/// Whether `text` contains `pattern` with at most `max_typos` typos
+pub(crate) fn contains_with_typos(text: &str, pattern: &str, max_typos: usize) -> bool {
+    typos(text, pattern, false) <= tolerated_typos(pattern, max_typos)
+}
+
+/// Whether `text` starts with `pattern` with at most `max_typos` typos
+pub(crate) fn starts_with_typos(text: &str, pattern: &str, max_typos: usize) -> bool {
+    typos(text, pattern, true) <= tolerated_typos(pattern, max_typos)
+}
+
+fn tolerated_typos(pattern: &str, max_typos: usize) -> usize {
+    max_typos.min(pattern.chars().count() / 3)
+}
+
+// The fewest typos that turn `pattern` into a part of `text`, which starts at the start of
+// `text` if `anchored`
+fn typos(text: &str, pattern: &str, anchored: bool) -> usize {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    // The rows of the pattern characters before the current one
+    let mut before_previous: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=text.len())
+        .map(|j| if anchored { j } else { 0 })
+        .collect();
+    for (i, p) in pattern.iter().enumerate() {
+        let mut current = vec![i + 1; text.len() + 1];
+        for (j, t) in text.iter().enumerate() {
+            let substitution = previous[j] + usize::from(p != t);
+            let mut typos = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            if i > 0 && j > 0 && *p == text[j - 1] && pattern[i - 1] == *t {
+                typos = typos.min(before_previous[j - 1] + 1);
+            }
+            current[j + 1] = typos;
+        }
+        before_previous = std::mem::replace(&mut previous, current);
+    }
+    previous.into_iter().min().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("git status", "gti sttaus", true)]
+    #[case("git status", "git stat", true)]
+    #[case("cargo build --release", "buidl", true)]
+    #[case("cargo build --release", "reelase", true)]
+    #[case("cargo build --release", "rlss", false)]
+    #[case("git status", "gti", true)]
+    #[case("ls -la", "cd", false)]
+    #[case("git status", "docker compose", false)]
+    fn typos_are_tolerated_in_longer_patterns(
+        #[case] text: &str,
+        #[case] pattern: &str,
+        #[case] matches: bool,
+    ) {
+        assert_eq!(contains_with_typos(text, pattern, 2), matches);
+    }
+
+    #[test]
+    fn only_the_start_is_matched_when_anchored() {
+        assert!(starts_with_typos("status", "sttau", 2));
+        assert!(!starts_with_typos("git status", "sttau", 2));
+        assert!(!contains_with_typos("git status", "gti sttaus", 0));
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{core_editor::Editor, UndoBehavior};
+
+/// Completion of the word before the cursor from the words already typed
+///
+/// The candidates are the words of the buffer that start with the same prefix, the closest
+/// before the cursor first and then the ones after it, followed by the words of the recent
+/// history. Cycling past the last candidate puts the typed prefix back.
+pub(crate) struct WordExpansion {
+    start: usize,
+    prefix: String,
+    candidates: Vec<String>,
+    current: Option<usize>,
+    // The buffer and the cursor after the last expansion, to tell if the next one continues it
+    expanded: (String, usize),
+}
+
+impl WordExpansion {
+    /// Start expanding the word ending at `cursor`, `None` if there is no word before it
+    pub(crate) fn new(buffer: &str, cursor: usize) -> Option<Self> {
+        let start = buffer[..cursor]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_word_char(*c))
+            .last()
+            .map(|(index, _)| index)?;
+        let mut expansion = Self {
+            start,
+            prefix: buffer[start..cursor].to_string(),
+            candidates: Vec::new(),
+            current: None,
+            expanded: (buffer.to_string(), cursor),
+        };
+
+        let before = buffer[..start].split(|c| !is_word_char(c)).rev();
+        // The rest of the word under the cursor is not a word of its own
+        let after = buffer[cursor..].split(|c| !is_word_char(c)).skip(1);
+        expansion.add_words(before.chain(after));
+        Some(expansion)
+    }
+
+    /// The start of the word that is expanded
+    pub(crate) fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Add the words of `lines` as candidates after the words of the buffer
+    pub(crate) fn add_lines<'a>(&mut self, lines: impl IntoIterator<Item = &'a str>) {
+        for line in lines {
+            self.add_words(line.split(|c| !is_word_char(c)));
+        }
+    }
+
+    /// The words that can replace the prefix, in the order they are cycled through
+    pub(crate) fn candidates(&self) -> &[String] {
+        &self.candidates
+    }
+
+    /// Whether `editor` is still as the last expansion left it
+    pub(crate) fn is_continued_in(&self, editor: &Editor) -> bool {
+        self.expanded.0 == editor.get_buffer() && self.expanded.1 == editor.insertion_point()
+    }
+
+    /// Replace the word with the next candidate
+    pub(crate) fn next(&mut self, editor: &mut Editor) {
+        self.current = match self.current {
+            None => Some(0),
+            Some(current) if current + 1 < self.candidates.len() => Some(current + 1),
+            Some(_) => None,
+        };
+        self.expand(editor);
+    }
+
+    /// Replace the word with the previous candidate
+    pub(crate) fn previous(&mut self, editor: &mut Editor) {
+        self.current = match self.current {
+            None => self.candidates.len().checked_sub(1),
+            Some(current) => current.checked_sub(1),
+        };
+        self.expand(editor);
+    }
+
+    fn add_words<'a>(&mut self, words: impl Iterator<Item = &'a str>) {
+        for word in words {
+            if word.len() > self.prefix.len()
+                && word.starts_with(&self.prefix)
+                && !self.candidates.iter().any(|candidate| candidate == word)
+            {
+                self.candidates.push(word.to_string());
+            }
+        }
+    }
+
+    fn expand(&mut self, editor: &mut Editor) {
+        let replaced = self.start..self.expanded.1;
+        let word = self
+            .current
+            .map_or(self.prefix.as_str(), |current| &self.candidates[current]);
+        let cursor = self.start + word.len();
+        editor.edit_buffer(
+            |line_buffer| {
+                line_buffer.replace_range(replaced, word);
+                line_buffer.set_insertion_point(cursor);
+            },
+            UndoBehavior::CreateUndoPoint,
+        );
+        self.expanded = (editor.get_buffer().to_string(), cursor);
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::LineBuffer;
+    use pretty_assertions::assert_eq;
+
+    fn editor_with(buffer: &str, insertion_point: usize) -> Editor {
+        let mut line_buffer = LineBuffer::from(buffer);
+        line_buffer.set_insertion_point(insertion_point);
+        let mut editor = Editor::default();
+        editor.set_line_buffer(line_buffer, UndoBehavior::CreateUndoPoint);
+        editor
+    }
+
+    #[test]
+    fn the_closest_words_come_first() {
+        let buffer = "let value = valid; let val = 1; let variable = value_2;";
+        let expansion = WordExpansion::new(buffer, 26).unwrap();
+
+        assert_eq!(expansion.prefix(), "val");
+        assert_eq!(expansion.candidates(), ["valid", "value", "value_2"]);
+    }
+
+    #[test]
+    fn history_words_follow_the_buffer_words() {
+        let mut expansion = WordExpansion::new("cargo build --rel", 17).unwrap();
+        expansion.add_lines(["cargo test --release", "git rebase --reapply-cherry-picks"]);
+
+        assert_eq!(expansion.candidates(), ["release"]);
+        assert!(WordExpansion::new("cargo build --", 14).is_none());
+    }
+
+    #[test]
+    fn cycling_wraps_around_to_the_prefix() {
+        let mut editor = editor_with("format for fo", 13);
+        let mut expansion = WordExpansion::new(editor.get_buffer(), 13).unwrap();
+
+        expansion.next(&mut editor);
+        assert_eq!(editor.get_buffer(), "format for for");
+        expansion.next(&mut editor);
+        assert_eq!(editor.get_buffer(), "format for format");
+        assert!(expansion.is_continued_in(&editor));
+        expansion.next(&mut editor);
+        assert_eq!(editor.get_buffer(), "format for fo");
+        expansion.previous(&mut editor);
+        assert_eq!(editor.get_buffer(), "format for format");
+        assert_eq!(editor.insertion_point(), 17);
+
+        editor.run_edit_command(&crate::EditCommand::InsertChar(' '));
+        assert!(!expansion.is_continued_in(&editor));
+    }
+}